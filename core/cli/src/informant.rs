@@ -19,7 +19,7 @@
 use ansi_term::Colour;
 use std::time::{Duration, Instant};
 use futures::{Future, Stream};
-use service::{Service, Components};
+use service::{Service, Components, TransactionPool};
 use tokio::runtime::TaskExecutor;
 use tokio::timer::Interval;
 use sysinfo::{get_current_pid, ProcessExt, System, SystemExt};
@@ -39,6 +39,7 @@ pub fn start<C>(service: &Service<C>, exit: ::exit_future::Exit, handle: TaskExe
 	let client = service.client();
 	let txpool = service.transaction_pool();
 	let mut last_number = None;
+	let mut last_validations = None;
 
 	let mut sys = System::new();
 	let self_pid = get_current_pid();
@@ -57,7 +58,11 @@ pub fn start<C>(service: &Service<C>, exit: ::exit_future::Exit, handle: TaskExe
 				(SyncState::Downloading, Some(n)) => (format!("Syncing{}", speed()), format!(", target=#{}", n)),
 			};
 			last_number = Some(best_number);
-			let txpool_status = txpool.status();
+			let txpool_status = txpool.pool_status();
+			let validations_per_sec = last_validations
+				.map(|last| txpool_status.validations.saturating_sub(last) as u64 * 1000 / TIMER_INTERVAL_MS)
+				.unwrap_or(0);
+			last_validations = Some(txpool_status.validations);
 			info!(
 				target: "substrate",
 				"{}{} ({} peers), best: #{} ({})",
@@ -81,6 +86,12 @@ pub fn start<C>(service: &Service<C>, exit: ::exit_future::Exit, handle: TaskExe
 				"height" => best_number,
 				"best" => ?hash,
 				"txcount" => txpool_status.ready,
+				"txpool_ready_bytes" => txpool_status.ready_bytes,
+				"txpool_future" => txpool_status.future,
+				"txpool_future_bytes" => txpool_status.future_bytes,
+				"txpool_banned" => txpool_status.banned,
+				"txpool_validations_per_sec" => validations_per_sec,
+				"import_queue" => sync_status.sync.queued_blocks,
 				"cpu" => cpu_usage,
 				"memory" => memory
 			);
@@ -99,7 +110,7 @@ pub fn start<C>(service: &Service<C>, exit: ::exit_future::Exit, handle: TaskExe
 
 	let txpool = service.transaction_pool();
 	let display_txpool_import = txpool.import_notification_stream().for_each(move |_| {
-		let status = txpool.status();
+		let status = txpool.pool_status();
 		telemetry!("txpool.import"; "ready" => status.ready, "future" => status.future);
 		Ok(())
 	});
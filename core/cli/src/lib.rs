@@ -68,14 +68,16 @@ use network::{
 	multiaddr,
 };
 use primitives::H256;
+use primitives::hexdisplay::HexDisplay;
 
 use std::io::{Write, Read, stdin, stdout};
 use std::iter;
 use std::fs;
 use std::fs::File;
-use std::net::{Ipv4Addr, SocketAddr};
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::time::Duration;
 use names::{Generator, Name};
 use regex::Regex;
 use structopt::StructOpt;
@@ -223,6 +225,25 @@ where
 			.map_err(|_| error::ErrorKind::Input("Invalid pruning mode specified".to_owned()))?),
 	};
 
+	config.max_reorg_depth = match matches.value_of("max_reorg_depth") {
+		Some(s) => Some(s.parse()
+			.map_err(|_| error::ErrorKind::Input("Invalid maximum reorg depth specified".to_owned()))?),
+		None => None,
+	};
+
+	config.index_events = matches.is_present("index_events");
+
+	let watchdog_secs = |name: &str, what: &str| match matches.value_of(name) {
+		Some(s) => s.parse().map(|secs| Some(Duration::from_secs(secs)))
+			.map_err(|_| error::ErrorKind::Input(format!("Invalid {} watchdog duration specified", what))),
+		None => Ok(None),
+	};
+	config.watchdog = service::WatchdogConfig {
+		best_block_stall: watchdog_secs("watchdog_best_block", "best block")?,
+		finality_stall: watchdog_secs("watchdog_finality", "finality")?,
+		restart_sync: matches.is_present("watchdog_restart_sync"),
+	};
+
 	let role =
 		if matches.is_present("light") {
 			config.block_execution_strategy = service::ExecutionStrategy::NativeWhenPossible;
@@ -271,11 +292,18 @@ where
 			config.network.listen_addresses = vec![
 				iter::once(Protocol::Ip4(Ipv4Addr::new(0, 0, 0, 0)))
 					.chain(iter::once(Protocol::Tcp(port)))
-					.collect()
+					.collect(),
+				iter::once(Protocol::Ip6(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0)))
+					.chain(iter::once(Protocol::Tcp(port)))
+					.collect(),
 			];
 		}
 
 		config.network.public_addresses = Vec::new();
+		for addr in matches.values_of("public_addr").unwrap_or_default() {
+			let addr = addr.parse().map_err(|_| "Invalid public multiaddress")?;
+			config.network.public_addresses.push(addr);
+		}
 
 		config.network.client_version = config.client_id();
 		config.network.use_secret = match matches.value_of("node_key").map(H256::from_str) {
@@ -283,6 +311,7 @@ where
 			Some(Err(err)) => return Err(format!("Error parsing node key: {}", err).into()),
 			None => None,
 		};
+		config.node_key_from_keystore = matches.is_present("node_key_from_keystore");
 
 		let in_peers = match matches.value_of("in_peers") {
 			Some(in_peers) => in_peers.parse().map_err(|_| "Invalid in-peers value specified.")?,
@@ -302,8 +331,9 @@ where
 		config.keys.push("Alice".into());
 	}
 
-	let rpc_interface: &str = if matches.is_present("rpc_external") { "0.0.0.0" } else { "127.0.0.1" };
-	let ws_interface: &str = if matches.is_present("ws_external") { "0.0.0.0" } else { "127.0.0.1" };
+	let chain_type = config.chain_spec.chain_type().clone();
+	let rpc_interface = rpc_listen_interface(matches, "rpc_external", "unsafe_rpc_external", &chain_type)?;
+	let ws_interface = rpc_listen_interface(matches, "ws_external", "unsafe_ws_external", &chain_type)?;
 
 	config.rpc_http = Some(parse_address(&format!("{}:{}", rpc_interface, 9933), "rpc_port", &matches)?);
 	config.rpc_ws = Some(parse_address(&format!("{}:{}", ws_interface, 9944), "ws_port", &matches)?);
@@ -313,11 +343,38 @@ where
 		config.telemetry_url = None;
 	} else if let Some(url) = matches.value_of("telemetry_url") {
 		config.telemetry_url = Some(url.to_owned());
+	} else if chain_type.is_local() && !matches.is_present("telemetry") {
+		config.telemetry_url = None;
 	}
 
 	Ok((spec, config))
 }
 
+/// Returns the interface an RPC server listens on. Listening to all interfaces is refused on
+/// live chains, unless explicitly asked for with the `unsafe_external` flag.
+fn rpc_listen_interface(
+	matches: &clap::ArgMatches,
+	external: &str,
+	unsafe_external: &str,
+	chain_type: &service::ChainType,
+) -> error::Result<&'static str> {
+	if matches.is_present(unsafe_external) {
+		return Ok("0.0.0.0");
+	}
+	if !matches.is_present(external) {
+		return Ok("127.0.0.1");
+	}
+	if !chain_type.is_local() {
+		return Err(error::ErrorKind::Input(format!(
+			"--{} is refused on {} chains, use --{} to listen to all interfaces anyway",
+			external.replace('_', "-"),
+			chain_type,
+			unsafe_external.replace('_', "-"),
+		)).into());
+	}
+	Ok("0.0.0.0")
+}
+
 //
 // IANA unassigned port ranges that we could use:
 // 6717-6766		Unassigned
@@ -356,6 +413,21 @@ where
 	} else if let Some(matches) = matches.subcommand_matches("import-blocks") {
 		import_blocks::<F, _>(db_path, matches, spec, exit.into_exit())?;
 		return Ok(Action::ExecutedInternally);
+	} else if let Some(matches) = matches.subcommand_matches("export-state") {
+		export_state::<F>(db_path, matches, spec)?;
+		return Ok(Action::ExecutedInternally);
+	} else if let Some(matches) = matches.subcommand_matches("import-state") {
+		import_state::<F>(db_path, matches, spec)?;
+		return Ok(Action::ExecutedInternally);
+	} else if let Some(matches) = matches.subcommand_matches("export-sync-state") {
+		export_sync_state::<F>(db_path, matches, spec)?;
+		return Ok(Action::ExecutedInternally);
+	} else if let Some(matches) = matches.subcommand_matches("verify-wasm") {
+		verify_wasm::<F>(db_path, matches, spec)?;
+		return Ok(Action::ExecutedInternally);
+	} else if let Some(matches) = matches.subcommand_matches("try-runtime") {
+		try_runtime::<F>(matches)?;
+		return Ok(Action::ExecutedInternally);
 	} else if let Some(matches) = matches.subcommand_matches("revert") {
 		revert_chain::<F>(db_path, matches, spec)?;
 		return Ok(Action::ExecutedInternally);
@@ -473,6 +545,128 @@ fn import_blocks<F, E>(
 	Ok(service::chain_ops::import_blocks::<F, _, _>(config, exit, file)?)
 }
 
+fn export_state<F>(
+	db_path: PathBuf,
+	matches: &clap::ArgMatches,
+	spec: ChainSpec<FactoryGenesis<F>>
+) -> error::Result<()>
+	where F: ServiceFactory,
+{
+	let mut config = service::Configuration::default_with_spec(spec);
+	config.database_path = db_path.to_string_lossy().into();
+	info!("DB path: {}", config.database_path);
+
+	let block: Option<u64> = match matches.value_of("block") {
+		Some(v) => Some(v.parse().map_err(|_| "Invalid --block argument")?),
+		None => None,
+	};
+
+	let file: Box<Write> = match matches.value_of("output") {
+		Some(filename) => Box::new(File::create(filename)?),
+		None => Box::new(stdout()),
+	};
+
+	Ok(service::chain_ops::export_state::<F, _>(config, file, block.map(As::sa))?)
+}
+
+fn import_state<F>(
+	db_path: PathBuf,
+	matches: &clap::ArgMatches,
+	spec: ChainSpec<FactoryGenesis<F>>
+) -> error::Result<()>
+	where F: ServiceFactory,
+{
+	let mut config = service::Configuration::default_with_spec(spec);
+	config.database_path = db_path.to_string_lossy().into();
+	info!("DB path: {}", config.database_path);
+
+	let file: Box<Read> = match matches.value_of("input") {
+		Some(filename) => Box::new(File::open(filename)?),
+		None => Box::new(stdin()),
+	};
+
+	Ok(service::chain_ops::import_state::<F, _>(config, file)?)
+}
+
+fn export_sync_state<F>(
+	db_path: PathBuf,
+	matches: &clap::ArgMatches,
+	spec: ChainSpec<FactoryGenesis<F>>
+) -> error::Result<()>
+	where F: ServiceFactory,
+{
+	let mut config = service::Configuration::default_with_spec(spec);
+	config.database_path = db_path.to_string_lossy().into();
+	info!("DB path: {}", config.database_path);
+
+	let file: Box<Write> = match matches.value_of("output") {
+		Some(filename) => Box::new(File::create(filename)?),
+		None => Box::new(stdout()),
+	};
+
+	Ok(service::chain_ops::export_sync_state::<F, _>(config, file)?)
+}
+
+fn verify_wasm<F>(
+	db_path: PathBuf,
+	matches: &clap::ArgMatches,
+	spec: ChainSpec<FactoryGenesis<F>>
+) -> error::Result<()>
+	where F: ServiceFactory,
+{
+	let mut config = service::Configuration::default_with_spec(spec);
+	config.database_path = db_path.to_string_lossy().into();
+	info!("DB path: {}", config.database_path);
+
+	let block: Option<u64> = match matches.value_of("block") {
+		Some(v) => Some(v.parse().map_err(|_| "Invalid --block argument")?),
+		None => None,
+	};
+
+	let mut wasm = Vec::new();
+	let input = matches.value_of("input").expect("input is a required argument; qed");
+	File::open(input)?.read_to_end(&mut wasm)?;
+
+	match service::chain_ops::verify_wasm::<F>(config, &wasm, block.map(As::sa))? {
+		service::chain_ops::WasmVerdict::Identical => println!("Verdict: the runtime code is identical"),
+		service::chain_ops::WasmVerdict::SameCode => println!("Verdict: the runtime code matches, apart from names and debug info"),
+		service::chain_ops::WasmVerdict::Different => return Err("Verdict: the runtime code differs".into()),
+	}
+	Ok(())
+}
+
+fn try_runtime<F>(matches: &clap::ArgMatches) -> error::Result<()>
+	where F: ServiceFactory,
+{
+	let url = matches.value_of("url").expect("url has a default value; qed");
+	let at = match matches.value_of("at") {
+		Some(v) => Some(H256::from_str(v).map_err(|_| "Invalid --at argument")?),
+		None => None,
+	};
+
+	let mut wasm = Vec::new();
+	let input = matches.value_of("input").expect("input is a required argument; qed");
+	File::open(input)?.read_to_end(&mut wasm)?;
+
+	let report = service::chain_ops::try_runtime::<F>(url, at, &wasm)?;
+	for diff in &report.diffs {
+		println!("0x{}", HexDisplay::from(&diff.key));
+		match diff.before {
+			Some(ref value) => println!("  before: 0x{}", HexDisplay::from(value)),
+			None => println!("  before: none"),
+		}
+		match diff.after {
+			Some(ref value) => println!("  after:  0x{}", HexDisplay::from(value)),
+			None => println!("  after:  none"),
+		}
+	}
+	println!(
+		"{} entries changed, {} reads and {} writes, weight {}",
+		report.diffs.len(), report.reads, report.writes, report.weight(),
+	);
+	Ok(())
+}
+
 fn revert_chain<F>(
 	db_path: PathBuf,
 	matches: &clap::ArgMatches,
@@ -41,6 +41,10 @@ pub struct CoreParams {
     #[structopt(long = "node-key", value_name = "KEY")]
     node_key: Option<String>,
 
+    /// Derive the node key from the keystore, so it is backed up and rotated along with the other keys. Ignored if --node-key is given
+    #[structopt(long = "node-key-from-keystore")]
+    node_key_from_keystore: bool,
+
     /// Enable validator mode
     #[structopt(long = "validator")]
     validator: bool,
@@ -53,11 +57,15 @@ pub struct CoreParams {
     #[structopt(long = "dev")]
     dev: bool,
 
-    /// Listen on this multiaddress
+    /// Listen on this multiaddress. Can be passed multiple times; `/dns4` and `/dns6` addresses are resolved on startup
     #[structopt(long = "listen-addr", value_name = "LISTEN_ADDR")]
     listen_addr: Vec<String>,
 
-    /// Specify p2p protocol TCP port. Only used if --listen-addr is not specified.
+    /// Advertise this multiaddress to other nodes, in addition to the detected ones. Can be passed multiple times
+    #[structopt(long = "public-addr", value_name = "PUBLIC_ADDR")]
+    public_addr: Vec<String>,
+
+    /// Specify p2p protocol TCP port, used for both IPv4 and IPv6. Only used if --listen-addr is not specified.
     #[structopt(long = "port", value_name = "PORT")]
     port: Option<u32>,
 
@@ -69,6 +77,14 @@ pub struct CoreParams {
     #[structopt(long = "ws-external")]
     ws_external: bool,
 
+    /// Listen to all RPC interfaces, even on live chains where --rpc-external is refused
+    #[structopt(long = "unsafe-rpc-external")]
+    unsafe_rpc_external: bool,
+
+    /// Listen to all Websocket interfaces, even on live chains where --ws-external is refused
+    #[structopt(long = "unsafe-ws-external")]
+    unsafe_ws_external: bool,
+
     /// Specify HTTP RPC server TCP port
     #[structopt(long = "rpc-port", value_name = "PORT")]
     rpc_port: Option<u32>,
@@ -101,6 +117,26 @@ pub struct CoreParams {
     #[structopt(long = "pruning", value_name = "PRUNING_MODE")]
     pruning: Option<u32>,
 
+    /// Refuse to import blocks which would revert more than the given number of blocks of the best chain
+    #[structopt(long = "max-reorg-depth", value_name = "BLOCKS")]
+    max_reorg_depth: Option<u64>,
+
+    /// Index the runtime events of the imported blocks, to look them up with the `events_*` RPCs
+    #[structopt(long = "index-events")]
+    index_events: bool,
+
+    /// Warn when no new best block has been imported for the given number of seconds
+    #[structopt(long = "watchdog-best-block", value_name = "SECS")]
+    watchdog_best_block: Option<u64>,
+
+    /// Warn when no block has been finalized for the given number of seconds
+    #[structopt(long = "watchdog-finality", value_name = "SECS")]
+    watchdog_finality: Option<u64>,
+
+    /// Restart the sync when the best block stalls for as long as given by `--watchdog-best-block`
+    #[structopt(long = "watchdog-restart-sync")]
+    watchdog_restart_sync: bool,
+
     /// The human-readable name for this node, as reported to the telemetry server, if enabled
     #[structopt(long = "name", value_name = "NAME")]
     name: Option<String>,
@@ -206,6 +242,62 @@ pub enum CoreCommands {
         max_heap_pages: Option<u32>,
     },
 
+    /// Export the state of a finalized block to a snapshot file
+    #[structopt(name = "export-state")]
+    ExportState {
+        /// Output file name or stdout if unspecified.
+        #[structopt(parse(from_os_str))]
+        output: Option<PathBuf>,
+
+        /// Specify the block to export the state of. Last finalized block by default.
+        #[structopt(long = "block", value_name = "BLOCK")]
+        block: Option<u128>,
+    },
+
+    /// Import a state snapshot into an empty database. The chain then starts at the snapshot block.
+    #[structopt(name = "import-state")]
+    ImportState {
+        /// Input file or stdin if unspecified.
+        #[structopt(parse(from_os_str))]
+        input: Option<PathBuf>,
+    },
+
+    /// Export the finality state of the last finalized block, to be embedded into the chain spec for light clients
+    #[structopt(name = "export-sync-state")]
+    ExportSyncState {
+        /// Output file name or stdout if unspecified.
+        #[structopt(parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
+
+    /// Compare the runtime code of a block with a locally built wasm blob, ignoring names and debug info
+    #[structopt(name = "verify-wasm")]
+    VerifyWasm {
+        /// The locally built wasm blob.
+        #[structopt(parse(from_os_str))]
+        input: PathBuf,
+
+        /// Specify the block to compare the runtime code of. Best block by default.
+        #[structopt(long = "block", value_name = "BLOCK")]
+        block: Option<u128>,
+    },
+
+    /// Rehearse an upgrade to a locally built wasm runtime against the state of a live node, reporting the storage changed by its migrations and their weight
+    #[structopt(name = "try-runtime")]
+    TryRuntime {
+        /// The locally built wasm runtime.
+        #[structopt(parse(from_os_str))]
+        input: PathBuf,
+
+        /// Specify the HTTP RPC endpoint of the node to fetch the state from.
+        #[structopt(long = "url", value_name = "URL", default_value = "http://localhost:9933")]
+        url: String,
+
+        /// Specify the hash of the block to rehearse the upgrade on top of. Best block by default.
+        #[structopt(long = "at", value_name = "HASH")]
+        at: Option<String>,
+    },
+
     ///Revert chain to the previous state
     #[structopt(name = "revert")]
     Revert {
@@ -57,16 +57,18 @@ mod utils;
 use std::sync::Arc;
 use std::path::PathBuf;
 use std::io;
+use std::time::{Duration, Instant};
 
 use client::backend::NewBlockState;
 use codec::{Decode, Encode};
 use hash_db::Hasher;
-use kvdb::{KeyValueDB, DBTransaction};
+use kvdb::{KeyValueDB, DBTransaction, DBOp};
 use trie::MemoryDB;
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 use primitives::{H256, AuthorityId, Blake2Hasher, ChangesTrieConfiguration, convert_hash};
+use primitives::crypto::Keystore;
 use primitives::storage::well_known_keys;
-use runtime_primitives::{generic::BlockId, Justification, StorageMap, ChildrenStorageMap};
+use runtime_primitives::{generic::BlockId, ConsensusEngineId, Justification, StorageMap, ChildrenStorageMap};
 use runtime_primitives::traits::{Block as BlockT, Header as HeaderT, As, NumberFor, Zero, Digest, DigestItem};
 use runtime_primitives::BuildStorage;
 use state_machine::backend::Backend as StateBackend;
@@ -77,8 +79,19 @@ use client::LeafSet;
 use state_db::StateDb;
 pub use state_db::PruningMode;
 
-const CANONICALIZATION_DELAY: u64 = 256;
+/// Number of blocks after which a non-finalized block is canonicalized.
+pub const CANONICALIZATION_DELAY: u64 = 256;
 const MIN_BLOCKS_TO_KEEP_CHANGES_TRIES_FOR: u64 = 32768;
+/// Maximum size, in bytes, of the buffered block writes before they are flushed to disk.
+const MAX_BUFFERED_WRITE_BYTES: usize = 64 * 1024 * 1024;
+/// Maximum time block writes stay buffered before they are flushed to disk.
+const MAX_BUFFERED_WRITE_LATENCY: Duration = Duration::from_secs(2);
+/// Version of the layout of the full database. Version 1 tags justifications with the id of
+/// their consensus engine.
+const DB_VERSION: u32 = 1;
+/// The engine of the justifications stored before they were tagged: GRANDPA was the only one
+/// producing them.
+const UNTAGGED_JUSTIFICATION_ENGINE_ID: ConsensusEngineId = *b"FRNK";
 
 /// DB-backed patricia trie state, transaction type is an overlay of changes to commit.
 pub type DbState = state_machine::TrieBackend<Arc<state_machine::Storage<Blake2Hasher>>, Blake2Hasher>;
@@ -93,13 +106,15 @@ pub struct DatabaseSettings {
 	pub pruning: PruningMode,
 }
 
-/// Create an instance of db-backed client.
+/// Create an instance of db-backed client. Offchain workers of the client get access to
+/// `keystore`, if any.
 pub fn new_client<E, S, Block, RA>(
 	settings: DatabaseSettings,
 	executor: E,
 	genesis_storage: S,
 	block_execution_strategy: ExecutionStrategy,
 	api_execution_strategy: ExecutionStrategy,
+	keystore: Option<Arc<Keystore>>,
 ) -> Result<client::Client<Backend<Block>, client::LocalCallExecutor<Backend<Block>, E>, Block, RA>, client::error::Error>
 	where
 		Block: BlockT<Hash=H256>,
@@ -108,6 +123,10 @@ pub fn new_client<E, S, Block, RA>(
 {
 	let backend = Arc::new(Backend::new(settings, CANONICALIZATION_DELAY)?);
 	let executor = client::LocalCallExecutor::new(backend.clone(), executor);
+	let executor = match keystore {
+		Some(keystore) => executor.with_keystore(keystore),
+		None => executor,
+	};
 	Ok(client::Client::new(backend, executor, genesis_storage, block_execution_strategy, api_execution_strategy)?)
 }
 
@@ -275,6 +294,7 @@ pub struct BlockImportOperation<Block: BlockT, H: Hasher> {
 	changes_trie_updates: MemoryDB<H>,
 	pending_block: Option<PendingBlock<Block>>,
 	aux_ops: Vec<(Vec<u8>, Option<Vec<u8>>)>,
+	commit_buffered: bool,
 }
 
 impl<Block: BlockT, H: Hasher> BlockImportOperation<Block, H> {
@@ -364,6 +384,10 @@ where Block: BlockT<Hash=H256>,
 		self.aux_ops = ops.into_iter().collect();
 		Ok(())
 	}
+
+	fn set_commit_buffered(&mut self, buffered: bool) {
+		self.commit_buffered = buffered;
+	}
 }
 
 struct StorageDb<Block: BlockT> {
@@ -496,6 +520,23 @@ impl<Block: BlockT> state_machine::ChangesTrieStorage<Blake2Hasher> for DbChange
 	}
 }
 
+/// Block writes which have been handed to the database but not flushed yet.
+#[derive(Default)]
+struct BufferedWrites {
+	/// Approximate size of the buffered writes.
+	bytes: usize,
+	/// Time of the oldest buffered write, `None` if nothing is buffered.
+	since: Option<Instant>,
+}
+
+/// Approximate number of bytes written by the transaction.
+fn transaction_size(transaction: &DBTransaction) -> usize {
+	transaction.ops.iter().map(|op| match *op {
+		DBOp::Insert { ref key, ref value, .. } => key.len() + value.len(),
+		DBOp::Delete { ref key, .. } => key.len(),
+	}).sum()
+}
+
 /// Disk backend. Keeps data in a key-value store. In archive mode, trie nodes are kept from all blocks.
 /// Otherwise, trie nodes are kept only from some recent blocks.
 pub struct Backend<Block: BlockT> {
@@ -503,6 +544,41 @@ pub struct Backend<Block: BlockT> {
 	changes_tries_storage: DbChangesTrieStorage<Block>,
 	blockchain: BlockchainDb<Block>,
 	canonicalization_delay: u64,
+	buffered_writes: Mutex<BufferedWrites>,
+	aux_lock: Mutex<()>,
+}
+
+/// Upgrade a full database written with an earlier version of its layout to the current one.
+fn upgrade_db(db: &KeyValueDB) -> Result<(), client::error::Error> {
+	let version = match db.get(columns::META, meta_keys::DB_VERSION).map_err(db_err)? {
+		Some(version) => u32::decode(&mut &version[..])
+			.ok_or_else(|| client::error::ErrorKind::Backend("Error decoding database version".into()))?,
+		None => 0,
+	};
+
+	match version {
+		DB_VERSION => return Ok(()),
+		0 => {},
+		_ => return Err(client::error::ErrorKind::Backend(
+			format!("Unsupported database version {}", version)
+		).into()),
+	}
+
+	// justifications used to be the encoded justification of GRANDPA alone.
+	let mut transaction = DBTransaction::new();
+	for (key, justification) in db.iter(columns::JUSTIFICATION) {
+		let untagged = Vec::<u8>::decode(&mut &justification[..])
+			.ok_or_else(|| client::error::ErrorKind::Backend("Error decoding justification".into()))?;
+		let justification: Justification = if untagged.is_empty() {
+			Vec::new()
+		} else {
+			vec![(UNTAGGED_JUSTIFICATION_ENGINE_ID, untagged)]
+		};
+		transaction.put(columns::JUSTIFICATION, &key, &justification.encode());
+	}
+	transaction.put(columns::META, meta_keys::DB_VERSION, &DB_VERSION.encode());
+
+	db.write(transaction).map_err(db_err)
 }
 
 impl<Block: BlockT> Backend<Block> {
@@ -529,6 +605,8 @@ impl<Block: BlockT> Backend<Block> {
 	}
 
 	fn from_kvdb(db: Arc<KeyValueDB>, pruning: PruningMode, canonicalization_delay: u64) -> Result<Self, client::error::Error> {
+		upgrade_db(&*db)?;
+
 		let is_archive_pruning = pruning.is_archive();
 		let blockchain = BlockchainDb::new(db.clone())?;
 		let meta = blockchain.meta.clone();
@@ -550,9 +628,116 @@ impl<Block: BlockT> Backend<Block> {
 			changes_tries_storage,
 			blockchain,
 			canonicalization_delay,
+			buffered_writes: Mutex::new(BufferedWrites::default()),
+			aux_lock: Mutex::new(()),
 		})
 	}
 
+	// writes the transaction to the database. Buffered transactions are batched with the
+	// following ones until the size or latency bound is reached; any other transaction flushes
+	// the pending batch along with itself, so the writes always reach the disk in order.
+	fn write_transaction(&self, transaction: DBTransaction, buffered: bool) -> Result<(), client::error::Error> {
+		let mut pending = self.buffered_writes.lock();
+		if !buffered && pending.since.is_none() {
+			return self.storage.db.write(transaction).map_err(db_err);
+		}
+
+		pending.bytes += transaction_size(&transaction);
+		let since = *pending.since.get_or_insert_with(Instant::now);
+		self.storage.db.write_buffered(transaction);
+
+		if !buffered || pending.bytes >= MAX_BUFFERED_WRITE_BYTES || since.elapsed() >= MAX_BUFFERED_WRITE_LATENCY {
+			trace!(target: "db", "Flushing {} bytes of buffered block writes", pending.bytes);
+			*pending = BufferedWrites::default();
+			self.storage.db.flush().map_err(db_err)?;
+		}
+		Ok(())
+	}
+
+	// flushes the buffered writes, if any.
+	fn flush_buffered_writes(&self) -> Result<(), client::error::Error> {
+		let mut pending = self.buffered_writes.lock();
+		if pending.since.is_some() {
+			*pending = BufferedWrites::default();
+			self.storage.db.flush().map_err(db_err)?;
+		}
+		Ok(())
+	}
+
+	// writes auxiliary data. Must be called with the aux lock held.
+	fn write_aux<'a, 'b: 'a, 'c: 'a, I: IntoIterator<Item=&'a (&'c [u8], &'c [u8])>, D: IntoIterator<Item=&'a &'b [u8]>>
+		(&self, insert: I, delete: D) -> Result<(), client::error::Error>
+	{
+		let mut transaction = DBTransaction::new();
+		for (k, v) in insert {
+			transaction.put(columns::AUX, k, v);
+		}
+		for k in delete {
+			transaction.delete(columns::AUX, k);
+		}
+		self.write_transaction(transaction, false)
+	}
+
+	/// Initialise an empty database with the state of a block, typically restored from a state
+	/// snapshot. The block becomes the best and finalized block: the chain starts from there and
+	/// the ancestors of the block are never available. `genesis_hash` is the hash of the genesis
+	/// block of the chain the block belongs to.
+	pub fn import_state(
+		&self,
+		genesis_hash: Block::Hash,
+		header: Block::Header,
+		top: StorageMap,
+		children: ChildrenStorageMap,
+	) -> Result<(), client::error::Error> where
+		Block: BlockT<Hash=H256>,
+	{
+		use client::backend::{Backend as ClientBackend, BlockImportOperation as ClientOperation};
+
+		if self.blockchain.meta.read().genesis_hash != Default::default() {
+			return Err(client::error::ErrorKind::Backend("Cannot import state into a non-empty database".into()).into());
+		}
+
+		let hash = header.hash();
+		let number = *header.number();
+		let mut operation = self.begin_operation(BlockId::Hash(Default::default()))?;
+		let root = operation.reset_storage(top, children)?;
+		if root != *header.state_root() {
+			return Err(client::error::ErrorKind::InvalidStateSnapshot(
+				format!("state root mismatch: expected {:?}, got {:?}", header.state_root(), root)
+			).into());
+		}
+
+		let mut transaction = DBTransaction::new();
+		let lookup_key = ::utils::number_and_hash_to_lookup_key(number, hash);
+		::utils::insert_number_to_key_mapping(&mut transaction, columns::KEY_LOOKUP, number, hash);
+		::utils::insert_hash_to_key_mapping(&mut transaction, columns::KEY_LOOKUP, number, hash);
+		transaction.put(columns::HEADER, &lookup_key, &header.encode());
+		transaction.put(columns::META, meta_keys::BEST_BLOCK, &lookup_key);
+		transaction.put(columns::META, meta_keys::FINALIZED_BLOCK, &lookup_key);
+		transaction.put(columns::META, meta_keys::GENESIS_HASH, genesis_hash.as_ref());
+
+		let mut changeset: state_db::ChangeSet<H256> = state_db::ChangeSet::default();
+		for (key, (val, rc)) in operation.updates.drain() {
+			if rc > 0 {
+				changeset.inserted.push((key, val.to_vec()));
+			}
+		}
+		let commit = self.storage.state_db.import_canonical(&hash, number.as_(), changeset)
+			.map_err(|e: state_db::Error<io::Error>| client::error::Error::from(format!("State database error: {:?}", e)))?;
+		apply_state_commit(&mut transaction, commit);
+
+		{
+			let mut leaves = self.blockchain.leaves.write();
+			leaves.import(hash, number, *header.parent_hash());
+			leaves.prepare_transaction(&mut transaction, columns::META, meta_keys::LEAF_PREFIX);
+		}
+
+		self.write_transaction(transaction, false)?;
+		self.blockchain.update_meta(hash, number, true, true);
+		self.blockchain.meta.write().genesis_hash = genesis_hash;
+		Ok(())
+	}
+
 	// performs forced canonicaliziation with a delay after importning a non-finalized block.
 	fn force_delayed_canonicalize(
 		&self,
@@ -656,6 +841,7 @@ impl<Block> client::backend::Backend<Block, Blake2Hasher> for Backend<Block> whe
 			updates: MemoryDB::default(),
 			changes_trie_updates: MemoryDB::default(),
 			aux_ops: Vec::new(),
+			commit_buffered: false,
 		})
 	}
 
@@ -776,7 +962,7 @@ impl<Block> client::backend::Backend<Block, Blake2Hasher> for Backend<Block> whe
 				let displaced_leaf = leaves.import(hash, number, parent_hash);
 				leaves.prepare_transaction(&mut transaction, columns::META, meta_keys::LEAF_PREFIX);
 
-				let write_result = self.storage.db.write(transaction).map_err(db_err);
+				let write_result = self.write_transaction(transaction, operation.commit_buffered);
 				if let Err(e) = write_result {
 					// revert leaves set update, if there was one.
 					if let Some(displaced_leaf) = displaced_leaf {
@@ -797,7 +983,9 @@ impl<Block> client::backend::Backend<Block, Blake2Hasher> for Backend<Block> whe
 		Ok(())
 	}
 
-	fn finalize_block(&self, block: BlockId<Block>) -> Result<(), client::error::Error> {
+	fn finalize_block(&self, block: BlockId<Block>, justification: Option<Justification>)
+		-> Result<(), client::error::Error>
+	{
 		use runtime_primitives::traits::Header;
 
 		if let Some(header) = ::client::blockchain::HeaderBackend::header(&self.blockchain, block)? {
@@ -805,7 +993,15 @@ impl<Block> client::backend::Backend<Block, Blake2Hasher> for Backend<Block> whe
 			// TODO: ensure best chain contains this block.
 			let hash = header.hash();
 			self.note_finalized(&mut transaction, &header, hash.clone())?;
-			self.storage.db.write(transaction).map_err(db_err)?;
+			if let Some(justification) = justification {
+				let number = header.number().clone();
+				transaction.put(
+					columns::JUSTIFICATION,
+					&::utils::number_and_hash_to_lookup_key(number, hash.clone()),
+					&justification.encode(),
+				);
+			}
+			self.write_transaction(transaction, false)?;
 			self.blockchain.update_meta(hash, header.number().clone(), false, true);
 			Ok(())
 		} else {
@@ -837,7 +1033,7 @@ impl<Block> client::backend::Backend<Block, Blake2Hasher> for Backend<Block> whe
 					let lookup_key = ::utils::number_and_hash_to_lookup_key(header.number().clone(), header.hash().clone());
 					transaction.put(columns::META, meta_keys::BEST_BLOCK, &lookup_key);
 					transaction.delete(columns::KEY_LOOKUP, header.hash().as_ref());
-					self.storage.db.write(transaction).map_err(db_err)?;
+					self.write_transaction(transaction, false)?;
 					self.blockchain.update_meta(header.hash().clone(), best.clone(), true, false);
 					self.blockchain.leaves.write().revert(header.hash().clone(), header.number().clone(), header.parent_hash().clone());
 				}
@@ -877,20 +1073,43 @@ impl<Block> client::backend::Backend<Block, Blake2Hasher> for Backend<Block> whe
 	fn insert_aux<'a, 'b: 'a, 'c: 'a, I: IntoIterator<Item=&'a (&'c [u8], &'c [u8])>, D: IntoIterator<Item=&'a &'b [u8]>>
 		(&self, insert: I, delete: D) -> Result<(), client::error::Error>
 	{
-		let mut transaction = DBTransaction::new();
-		for (k, v) in insert {
-			transaction.put(columns::AUX, k, v);
-		}
-		for k in delete {
-			transaction.delete(columns::AUX, k);
-		}
-		self.storage.db.write(transaction).map_err(db_err)?;
-		Ok(())
+		let _lock = self.aux_lock.lock();
+		self.write_aux(insert, delete)
 	}
 
 	fn get_aux(&self, key: &[u8]) -> Result<Option<Vec<u8>>, client::error::Error> {
 		Ok(self.storage.db.get(columns::AUX, key).map(|r| r.map(|v| v.to_vec())).map_err(db_err)?)
 	}
+
+	fn aux_with_prefix(&self, prefix: &[u8]) -> Result<Vec<(Vec<u8>, Vec<u8>)>, client::error::Error> {
+		// iterators only see the writes which reached the database.
+		self.flush_buffered_writes()?;
+		Ok(self.storage.db.iter_from_prefix(columns::AUX, prefix)
+			.take_while(|&(ref k, _)| k.starts_with(prefix))
+			.map(|(k, v)| (k.into_vec(), v.into_vec()))
+			.collect())
+	}
+
+	fn compare_and_swap_aux<'a, 'b: 'a, 'c: 'a, I: IntoIterator<Item=&'a (&'c [u8], &'c [u8])>, D: IntoIterator<Item=&'a &'b [u8]>>
+		(&self, expected: &[(&[u8], Option<&[u8]>)], insert: I, delete: D) -> Result<bool, client::error::Error>
+	{
+		let _lock = self.aux_lock.lock();
+		for &(key, value) in expected {
+			if self.get_aux(key)?.as_ref().map(|v| &v[..]) != value {
+				return Ok(false);
+			}
+		}
+		self.write_aux(insert, delete)?;
+		Ok(true)
+	}
+}
+
+impl<Block: BlockT> Drop for Backend<Block> {
+	fn drop(&mut self) {
+		if let Err(e) = self.flush_buffered_writes() {
+			warn!(target: "db", "Failed to flush buffered block writes: {:?}", e);
+		}
+	}
 }
 
 impl<Block> client::backend::LocalBackend<Block, Blake2Hasher> for Backend<Block>
@@ -1196,8 +1415,8 @@ mod tests {
 			assert!(backend.storage.db.get(::columns::STATE, key.as_bytes()).unwrap().is_none());
 		}
 
-		backend.finalize_block(BlockId::Number(1)).unwrap();
-		backend.finalize_block(BlockId::Number(2)).unwrap();
+		backend.finalize_block(BlockId::Number(1), None).unwrap();
+		backend.finalize_block(BlockId::Number(2), None).unwrap();
 		assert!(backend.storage.db.get(::columns::STATE, key.as_bytes()).unwrap().is_none());
 	}
 
@@ -1490,6 +1709,57 @@ mod tests {
 		test_client::trait_tests::test_blockchain_query_by_number_gets_canonical(backend);
 	}
 
+	#[test]
+	fn import_state_starts_chain_at_block() {
+		let db = Backend::<Block>::new_test(2, 0);
+		let storage = vec![
+			(vec![1, 3, 5], vec![2, 4, 6]),
+			(vec![1, 2, 3], vec![9, 9, 9]),
+		];
+		let mut header = Header {
+			number: 10,
+			parent_hash: H256::from_low_u64_be(9),
+			state_root: Default::default(),
+			digest: Default::default(),
+			extrinsics_root: Default::default(),
+		};
+		header.state_root = db.begin_operation(BlockId::Hash(Default::default())).unwrap()
+			.old_state.storage_root(storage.iter().cloned().map(|(k, v)| (k, Some(v)))).0.into();
+		let hash = header.hash();
+
+		let mut bad_header = header.clone();
+		bad_header.state_root = Default::default();
+		assert!(db.import_state(H256::from_low_u64_be(1), bad_header, storage.iter().cloned().collect(), Default::default()).is_err());
+
+		db.import_state(H256::from_low_u64_be(1), header.clone(), storage.iter().cloned().collect(), Default::default()).unwrap();
+		assert!(db.import_state(H256::from_low_u64_be(1), header, storage.iter().cloned().collect(), Default::default()).is_err());
+
+		let info = db.blockchain().info().unwrap();
+		assert_eq!(info.genesis_hash, H256::from_low_u64_be(1));
+		assert_eq!((info.best_hash, info.finalized_hash), (hash, hash));
+		let state = db.state_at(BlockId::Number(10)).unwrap();
+		assert_eq!(state.storage(&[1, 2, 3]).unwrap(), Some(vec![9, 9, 9]));
+
+		let mut op = db.begin_operation(BlockId::Number(10)).unwrap();
+		let changes = vec![(vec![5, 5, 5], Some(vec![4, 5, 6]))];
+		let (root, overlay) = op.old_state.storage_root(changes.iter().cloned());
+		op.update_storage(overlay).unwrap();
+		let header = Header {
+			number: 11,
+			parent_hash: hash,
+			state_root: root.into(),
+			digest: Default::default(),
+			extrinsics_root: Default::default(),
+		};
+		op.set_block_data(header, Some(vec![]), None, NewBlockState::Final).unwrap();
+		db.commit_operation(op).unwrap();
+
+		let state = db.state_at(BlockId::Number(11)).unwrap();
+		assert_eq!(state.storage(&[1, 3, 5]).unwrap(), Some(vec![2, 4, 6]));
+		assert_eq!(state.storage(&[5, 5, 5]).unwrap(), Some(vec![4, 5, 6]));
+		assert_eq!(db.blockchain().info().unwrap().finalized_number, 11);
+	}
+
 	#[test]
 	fn test_aux() {
 		let backend: Backend<test_client::runtime::Block> = Backend::new_test(0, 0);
@@ -1499,4 +1769,102 @@ mod tests {
 		backend.insert_aux(&[], &[&b"test"[..]]).unwrap();
 		assert!(backend.get_aux(b"test").unwrap().is_none());
 	}
+
+	#[test]
+	fn test_aux_prefix_and_compare_and_swap() {
+		let backend: Backend<test_client::runtime::Block> = Backend::new_test(0, 0);
+		backend.insert_aux(&[
+			(&b"set:2"[..], &b"two"[..]),
+			(&b"set:1"[..], &b"one"[..]),
+			(&b"sets"[..], &b"other"[..]),
+		], &[]).unwrap();
+		assert_eq!(backend.aux_with_prefix(b"set:").unwrap(), vec![
+			(b"set:1".to_vec(), b"one".to_vec()),
+			(b"set:2".to_vec(), b"two".to_vec()),
+		]);
+
+		assert!(!backend.compare_and_swap_aux(&[(&b"set:1"[..], Some(&b"two"[..]))], &[(&b"set:3"[..], &b"three"[..])], &[]).unwrap());
+		assert!(!backend.compare_and_swap_aux(&[(&b"set:1"[..], None)], &[(&b"set:3"[..], &b"three"[..])], &[]).unwrap());
+		assert!(backend.get_aux(b"set:3").unwrap().is_none());
+
+		assert!(backend.compare_and_swap_aux(
+			&[(&b"set:1"[..], Some(&b"one"[..])), (&b"set:3"[..], None)],
+			&[(&b"set:3"[..], &b"three"[..])],
+			&[&b"set:1"[..]],
+		).unwrap());
+		assert_eq!(backend.aux_with_prefix(b"set:").unwrap(), vec![
+			(b"set:2".to_vec(), b"two".to_vec()),
+			(b"set:3".to_vec(), b"three".to_vec()),
+		]);
+	}
+
+	#[test]
+	fn buffered_commits_are_flushed_by_the_next_unbuffered_write() {
+		let backend = Backend::<Block>::new_test(10, 10);
+		let genesis = insert_header(&backend, 0, Default::default(), Vec::new(), Default::default());
+
+		let header = Header {
+			number: 1,
+			parent_hash: genesis,
+			state_root: Default::default(),
+			digest: Default::default(),
+			extrinsics_root: Default::default(),
+		};
+		let hash = header.hash();
+		let mut op = backend.begin_operation(BlockId::Number(0)).unwrap();
+		op.set_block_data(header, None, None, NewBlockState::Best).unwrap();
+		op.set_commit_buffered(true);
+		backend.commit_operation(op).unwrap();
+
+		assert!(backend.buffered_writes.lock().since.is_some());
+		assert_eq!(backend.blockchain().hash(1).unwrap(), Some(hash));
+
+		backend.insert_aux(&[(&b"test"[..], &b"hello"[..])], &[]).unwrap();
+		assert!(backend.buffered_writes.lock().since.is_none());
+		assert_eq!(backend.blockchain().hash(1).unwrap(), Some(hash));
+	}
+
+	#[test]
+	fn finalize_block_stores_given_justification() {
+		use client::blockchain::Backend as BlockchainBackend;
+
+		let backend = Backend::<Block>::new_test(10, 10);
+		let genesis = insert_header(&backend, 0, Default::default(), Vec::new(), Default::default());
+		let block1 = insert_header(&backend, 1, genesis, Vec::new(), Default::default());
+
+		let justification = vec![(*b"test", vec![1, 2, 3])];
+		backend.finalize_block(BlockId::Hash(block1), Some(justification.clone())).unwrap();
+
+		assert_eq!(backend.blockchain().justification(BlockId::Hash(block1)).unwrap(), Some(justification));
+	}
+
+	#[test]
+	fn untagged_justifications_are_upgraded() {
+		use client::blockchain::Backend as BlockchainBackend;
+
+		let backend = Backend::<Block>::new_test(10, 10);
+		let genesis = insert_header(&backend, 0, Default::default(), Vec::new(), Default::default());
+		let block1 = insert_header(&backend, 1, genesis, Vec::new(), Default::default());
+		backend.finalize_block(BlockId::Hash(block1), Some(vec![(*b"test", vec![1, 2, 3])])).unwrap();
+
+		// store the justification as an earlier version of the database did.
+		let db = backend.storage.db.clone();
+		let lookup_key = ::utils::block_id_to_lookup_key(&*db, columns::KEY_LOOKUP, BlockId::<Block>::Hash(block1))
+			.unwrap()
+			.unwrap();
+		let mut transaction = DBTransaction::new();
+		transaction.put(columns::JUSTIFICATION, &lookup_key, &vec![1u8, 2, 3].encode());
+		transaction.delete(columns::META, meta_keys::DB_VERSION);
+		db.write(transaction).unwrap();
+
+		let backend = Backend::<Block>::from_kvdb(db, PruningMode::keep_blocks(10), 10).unwrap();
+		assert_eq!(
+			backend.blockchain().justification(BlockId::Hash(block1)).unwrap(),
+			Some(vec![(*b"FRNK", vec![1, 2, 3])]),
+		);
+		assert_eq!(
+			backend.storage.db.get(columns::META, meta_keys::DB_VERSION).unwrap().map(|v| v.into_vec()),
+			Some(DB_VERSION.encode()),
+		);
+	}
 }
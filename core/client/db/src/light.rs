@@ -241,21 +241,21 @@ impl<Block: BlockT> LightStorage<Block> {
 				);
 			}
 
-			// prune headers that are replaced with CHT
+			// prune headers that are replaced with CHT. Hash to number mappings are kept, so that
+			// pruned headers can still be requested by hash.
 			let mut prune_block = new_cht_start;
 			let new_cht_end = cht::end_number(cht::SIZE, new_cht_number);
 			trace!(target: "db", "Replacing blocks [{}..{}] with CHT#{}",
 				new_cht_start, new_cht_end, new_cht_number);
 
 			while prune_block <= new_cht_end {
-				if let Some(hash) = self.hash(prune_block)? {
+				if self.hash(prune_block)?.is_some() {
 					let lookup_key = block_id_to_lookup_key::<Block>(&*self.db, columns::KEY_LOOKUP, BlockId::Number(prune_block))?
 						.expect("retrieved hash for `prune_block` right above. therefore retrieving lookup key must succeed. q.e.d.");
-					::utils::remove_key_mappings(
+					::utils::remove_number_to_key_mapping(
 						transaction,
 						columns::KEY_LOOKUP,
 						prune_block,
-						hash
 					);
 					transaction.delete(columns::HEADER, &lookup_key);
 				}
@@ -625,9 +625,10 @@ pub(crate) mod tests {
 		// when headers are created without changes tries roots
 		let db = insert_headers(default_header);
 		assert_eq!(db.db.iter(columns::HEADER).count(), (1 + cht::SIZE + 1) as usize);
-		assert_eq!(db.db.iter(columns::KEY_LOOKUP).count(), (2 * (1 + cht::SIZE + 1)) as usize);
+		assert_eq!(db.db.iter(columns::KEY_LOOKUP).count(), (2 * (1 + cht::SIZE + 1) + cht::SIZE) as usize);
 		assert_eq!(db.db.iter(columns::CHT).count(), 1);
 		assert!((0..cht::SIZE).all(|i| db.header(BlockId::Number(1 + i)).unwrap().is_none()));
+		assert!((0..cht::SIZE).all(|i| db.hash(1 + i).unwrap().is_none()));
 		assert!(db.header_cht_root(cht::SIZE, cht::SIZE / 2).is_ok());
 		assert!(db.header_cht_root(cht::SIZE, cht::SIZE + cht::SIZE / 2).is_err());
 		assert!(db.changes_trie_cht_root(cht::SIZE, cht::SIZE / 2).is_err());
@@ -50,6 +50,8 @@ pub mod meta_keys {
 	pub const GENESIS_HASH: &[u8; 3] = b"gen";
 	/// Leaves prefix list key.
 	pub const LEAF_PREFIX: &[u8; 4] = b"leaf";
+	/// Version of the layout of the database.
+	pub const DB_VERSION: &[u8; 7] = b"version";
 }
 
 /// Database metadata.
@@ -119,17 +121,6 @@ pub fn remove_number_to_key_mapping<N: As<u64>>(
 	transaction.delete(key_lookup_col, number_index_key(number).as_ref())
 }
 
-/// Remove key mappings.
-pub fn remove_key_mappings<N: As<u64>, H: AsRef<[u8]>>(
-	transaction: &mut DBTransaction,
-	key_lookup_col: Option<u32>,
-	number: N,
-	hash: H,
-) {
-	remove_number_to_key_mapping(transaction, key_lookup_col, number);
-	transaction.delete(key_lookup_col, hash.as_ref());
-}
-
 /// Place a number mapping into the database. This maps number to current perceived
 /// block hash at that position.
 pub fn insert_number_to_key_mapping<N: As<u64> + Clone, H: AsRef<[u8]>>(
@@ -77,6 +77,10 @@ pub trait BlockImportOperation<Block, H> where
 	/// Update auxiliary keys. Values are `None` if should be deleted.
 	fn set_aux<I>(&mut self, ops: I) -> error::Result<()>
 		where I: IntoIterator<Item=(Vec<u8>, Option<Vec<u8>>)>;
+	/// Allow the backend to delay writing the operation and to batch it with the following
+	/// ones. Used when importing blocks far behind the head of the chain. Operations which
+	/// aren't buffered flush the pending batch.
+	fn set_commit_buffered(&mut self, _buffered: bool) {}
 }
 
 /// Client backend. Manages the data layer.
@@ -106,8 +110,9 @@ pub trait Backend<Block, H>: Send + Sync where
 	/// Commit block insertion.
 	fn commit_operation(&self, transaction: Self::BlockImportOperation) -> error::Result<()>;
 	/// Finalize block with given Id. This should only be called if the parent of the given
-	/// block has been finalized.
-	fn finalize_block(&self, block: BlockId<Block>) -> error::Result<()>;
+	/// block has been finalized. When a justification is given, it replaces the one that is
+	/// stored for the block.
+	fn finalize_block(&self, block: BlockId<Block>, justification: Option<Justification>) -> error::Result<()>;
 	/// Returns reference to blockchain backend.
 	fn blockchain(&self) -> &Self::Blockchain;
 	/// Returns reference to changes trie storage.
@@ -121,6 +126,12 @@ pub trait Backend<Block, H>: Send + Sync where
 	fn insert_aux<'a, 'b: 'a, 'c: 'a, I: IntoIterator<Item=&'a(&'c [u8], &'c [u8])>, D: IntoIterator<Item=&'a &'b [u8]>>(&self, insert: I, delete: D) -> error::Result<()>;
 	/// Query auxiliary data from key-value store.
 	fn get_aux(&self, key: &[u8]) -> error::Result<Option<Vec<u8>>>;
+	/// Query all auxiliary data whose key starts with `prefix`, sorted by key.
+	fn aux_with_prefix(&self, prefix: &[u8]) -> error::Result<Vec<(Vec<u8>, Vec<u8>)>>;
+	/// Insert auxiliary data into key-value store, provided that the `expected` keys currently
+	/// have the given values, `None` meaning that the key is absent. The check and the update are
+	/// atomic with respect to other auxiliary data updates. Returns whether the update was made.
+	fn compare_and_swap_aux<'a, 'b: 'a, 'c: 'a, I: IntoIterator<Item=&'a(&'c [u8], &'c [u8])>, D: IntoIterator<Item=&'a &'b [u8]>>(&self, expected: &[(&[u8], Option<&[u8]>)], insert: I, delete: D) -> error::Result<bool>;
 }
 
 /// Mark for all Backend implementations, that are making use of state data, stored locally.
@@ -24,7 +24,7 @@ use runtime_primitives::traits::{
 };
 use primitives::H256;
 use runtime_primitives::generic::BlockId;
-use runtime_api::Core;
+use runtime_api::{ApiExt, Core, ExecutionContext, OverlayedChanges};
 use error;
 use runtime_primitives::ApplyOutcome;
 
@@ -67,7 +67,7 @@ where
 		);
 
 		let api = api.runtime_api();
-		api.initialise_block(block_id, &header)?;
+		api.initialise_block_with_context(block_id, ExecutionContext::BlockConstruction, &header)?;
 
 		Ok(BlockBuilder {
 			header,
@@ -80,40 +80,51 @@ where
 
 	/// Push onto the block's list of extrinsics. This will ensure the extrinsic
 	/// can be validly executed (by executing it); if it is invalid, it'll be returned along with
-	/// the error. Otherwise, it will return a mutable reference to self (in order to chain).
+	/// the error and all of its changes are reverted, while the extrinsics pushed before are kept.
 	pub fn push(&mut self, xt: <Block as BlockT>::Extrinsic) -> error::Result<()> {
-		fn impl_push<'a, T, Block: BlockT, InherentData>(
-			api: &mut ApiRef<'a, T>,
-			block_id: &BlockId<Block>,
-			xt: Block::Extrinsic,
-			extrinsics: &mut Vec<Block::Extrinsic>
-		) -> error::Result<()> where T: BlockBuilderApi<Block, InherentData> {
-			api.map_api_result(|api| {
-				match api.apply_extrinsic(block_id, &xt)? {
-					Ok(ApplyOutcome::Success) | Ok(ApplyOutcome::Fail) => {
-						extrinsics.push(xt);
-						Ok(())
-					}
-					Err(e) => {
-						Err(error::ErrorKind::ApplyExtrinsicFailed(e).into())
-					}
-				}
-			})
-		}
+		let snapshot = self.api.snapshot();
+		let res = self.api.apply_extrinsic_with_context(
+			&self.block_id,
+			ExecutionContext::BlockConstruction,
+			&xt
+		);
 
-		//FIXME: Please NLL, help me!
-		impl_push(&mut self.api, &self.block_id, xt, &mut self.extrinsics)
+		match res {
+			Ok(Ok(ApplyOutcome::Success)) | Ok(Ok(ApplyOutcome::Fail)) => {
+				self.api.commit()?;
+				self.extrinsics.push(xt);
+				Ok(())
+			}
+			Ok(Err(e)) => {
+				self.api.revert_to(snapshot)?;
+				Err(error::ErrorKind::ApplyExtrinsicFailed(e).into())
+			}
+			Err(e) => {
+				self.api.revert_to(snapshot)?;
+				Err(e)
+			}
+		}
 	}
 
 	/// Consume the builder to return a valid `Block` containing all pushed extrinsics.
-	pub fn bake(mut self) -> error::Result<Block> {
-		self.header = self.api.finalise_block(&self.block_id)?;
+	pub fn bake(self) -> error::Result<Block> {
+		self.bake_with_changes().map(|(block, _)| block)
+	}
+
+	/// Consume the builder to return a valid `Block` containing all pushed extrinsics, along
+	/// with the storage changes made by building it.
+	pub fn bake_with_changes(mut self) -> error::Result<(Block, OverlayedChanges)> {
+		self.header = self.api.finalise_block_with_context(
+			&self.block_id,
+			ExecutionContext::BlockConstruction
+		)?;
 
 		debug_assert_eq!(
 			self.header.extrinsics_root().clone(),
 			HashFor::<Block>::ordered_trie_root(self.extrinsics.iter().map(Encode::encode)),
 		);
 
-		Ok(<Block as BlockT>::new(self.header, self.extrinsics))
+		let changes = self.api.take_storage_changes();
+		Ok((<Block as BlockT>::new(self.header, self.extrinsics), changes))
 	}
 }
@@ -16,19 +16,23 @@
 
 use std::sync::Arc;
 use std::cmp::Ord;
+use std::cell::RefCell;
+use std::rc::Rc;
 use runtime_primitives::generic::BlockId;
 use runtime_primitives::traits::Block as BlockT;
 use state_machine::{self, OverlayedChanges, Ext,
-	CodeExecutor, ExecutionManager, native_when_possible};
+	CodeExecutor, ExecutionManager, ExecutionContext, ExecutionLimits, Capabilities, native_when_possible};
 use executor::{RuntimeVersion, RuntimeInfo, NativeVersion};
 use hash_db::Hasher;
 use trie::MemoryDB;
-use codec::Decode;
+use codec::{Decode, Encode};
 use primitives::{H256, Blake2Hasher};
+use primitives::crypto::Keystore;
 use primitives::storage::well_known_keys;
 
 use backend;
 use error;
+use runtime_api::ProofRecorder;
 
 /// Information regarding the result of a call.
 #[derive(Debug, Clone)]
@@ -57,8 +61,46 @@ where
 		id: &BlockId<B>,
 		method: &str,
 		call_data: &[u8],
+	) -> Result<CallResult, error::Error> {
+		self.call_with_limits(id, method, call_data, ExecutionLimits::none())
+	}
+
+	/// Execute a call to a contract on top of state in a block of given hash, failing the call
+	/// once it exceeds any of `limits`.
+	///
+	/// No changes are made.
+	fn call_with_limits(&self,
+		id: &BlockId<B>,
+		method: &str,
+		call_data: &[u8],
+		limits: ExecutionLimits,
 	) -> Result<CallResult, error::Error>;
 
+	/// Execute a runtime API call on top of the state of a block, keeping in `changes` the
+	/// changes made by the previous calls of the same API instance.
+	///
+	/// Before the first call at `at`, the block is initialised with the header returned by
+	/// `prepare_environment_block`, unless the method is `initialise_block` itself. `context`
+	/// tells why the call is made. The call, the initialisation of the block included, fails
+	/// once it exceeds any of `limits`. If a `recorder` is given, all trie nodes accessed by the
+	/// call are recorded into it.
+	fn contextual_call<
+		PB: FnOnce() -> error::Result<B::Header>,
+		F: FnOnce(Result<Vec<u8>, Self::Error>, Result<Vec<u8>, Self::Error>) -> Result<Vec<u8>, Self::Error>,
+		M: Fn() -> ExecutionManager<F>,
+	>(&self,
+		at: &BlockId<B>,
+		method: &'static str,
+		call_data: &[u8],
+		changes: &mut OverlayedChanges,
+		initialised_block: &mut Option<BlockId<B>>,
+		prepare_environment_block: PB,
+		manager: M,
+		context: ExecutionContext,
+		limits: ExecutionLimits,
+		recorder: &Option<Rc<RefCell<ProofRecorder<B>>>>,
+	) -> Result<Vec<u8>, error::Error>;
+
 	/// Extract RuntimeVersion of given block
 	///
 	/// No changes are made.
@@ -97,12 +139,68 @@ where
 pub struct LocalCallExecutor<B, E> {
 	backend: Arc<B>,
 	executor: E,
+	keystore: Option<Arc<Keystore>>,
 }
 
 impl<B, E> LocalCallExecutor<B, E> {
 	/// Creates new instance of local call executor.
 	pub fn new(backend: Arc<B>, executor: E) -> Self {
-		LocalCallExecutor { backend, executor }
+		LocalCallExecutor { backend, executor, keystore: None }
+	}
+
+	/// Give the calls made with the `Capabilities::KEYSTORE` capability access to `keystore`.
+	pub fn with_keystore(mut self, keystore: Arc<Keystore>) -> Self {
+		self.keystore = Some(keystore);
+		self
+	}
+}
+
+impl<B, E> LocalCallExecutor<B, E> {
+	/// Execute the given method on top of `state`. If a `header` is given, the block is
+	/// initialised with it before.
+	fn execute_with_header<Block, S, F, M>(
+		&self,
+		state: &S,
+		changes: &mut OverlayedChanges,
+		header: Option<Block::Header>,
+		method: &str,
+		call_data: &[u8],
+		manager: &M,
+		context: ExecutionContext,
+		limits: ExecutionLimits,
+	) -> error::Result<Vec<u8>> where
+		B: backend::LocalBackend<Block, Blake2Hasher>,
+		E: CodeExecutor<Blake2Hasher>,
+		Block: BlockT<Hash=H256>,
+		S: state_machine::Backend<Blake2Hasher>,
+		F: FnOnce(Result<Vec<u8>, E::Error>, Result<Vec<u8>, E::Error>) -> Result<Vec<u8>, E::Error>,
+		M: Fn() -> ExecutionManager<F>,
+	{
+		let capabilities = context.capabilities();
+		let keystore = match self.keystore {
+			Some(ref keystore) if capabilities.contains(Capabilities::KEYSTORE) => Some(&**keystore),
+			_ => None,
+		};
+		let mut execute = |method: &str, call_data: &[u8]| -> error::Result<Vec<u8>> {
+			state_machine::execute_using_consensus_failure_handler(
+				state,
+				self.backend.changes_trie_storage(),
+				changes,
+				&self.executor,
+				method,
+				call_data,
+				capabilities,
+				keystore,
+				limits,
+				manager(),
+			).map(|res| res.0).map_err(Into::into)
+		};
+
+		if let Some(header) = header {
+			execute("initialise_block", &header.encode())?;
+		}
+
+		execute(method, call_data)
 	}
 }
 
@@ -111,6 +209,7 @@ impl<B, E> Clone for LocalCallExecutor<B, E> where E: Clone {
 		LocalCallExecutor {
 			backend: self.backend.clone(),
 			executor: self.executor.clone(),
+			keystore: self.keystore.clone(),
 		}
 	}
 }
@@ -123,22 +222,74 @@ where
 {
 	type Error = E::Error;
 
-	fn call(&self,
+	fn call_with_limits(&self,
 		id: &BlockId<Block>,
 		method: &str,
 		call_data: &[u8],
+		limits: ExecutionLimits,
 	) -> error::Result<CallResult> {
 		let mut changes = OverlayedChanges::default();
-		let (return_data, _, _) = self.call_at_state(
+		let (return_data, _, _) = state_machine::execute_using_consensus_failure_handler(
 			&self.backend.state_at(*id)?,
+			self.backend.changes_trie_storage(),
 			&mut changes,
+			&self.executor,
 			method,
 			call_data,
+			Capabilities::none(),
+			None,
+			limits,
 			native_when_possible(),
 		)?;
 		Ok(CallResult { return_data, changes })
 	}
 
+	fn contextual_call<
+		PB: FnOnce() -> error::Result<Block::Header>,
+		F: FnOnce(Result<Vec<u8>, Self::Error>, Result<Vec<u8>, Self::Error>) -> Result<Vec<u8>, Self::Error>,
+		M: Fn() -> ExecutionManager<F>,
+	>(&self,
+		at: &BlockId<Block>,
+		method: &'static str,
+		call_data: &[u8],
+		changes: &mut OverlayedChanges,
+		initialised_block: &mut Option<BlockId<Block>>,
+		prepare_environment_block: PB,
+		manager: M,
+		context: ExecutionContext,
+		limits: ExecutionLimits,
+		recorder: &Option<Rc<RefCell<ProofRecorder<Block>>>>,
+	) -> error::Result<Vec<u8>> {
+		let state = self.backend.state_at(*at)?;
+
+		//TODO: Find a better way to prevent double block initialization
+		let header = if method != "initialise_block" && initialised_block.map(|id| id != *at).unwrap_or(true) {
+			Some(prepare_environment_block()?)
+		} else {
+			None
+		};
+		let initialise = header.is_some();
+
+		let result = match recorder {
+			Some(recorder) => {
+				use state_machine::Backend;
+				let trie_state = state.try_into_trie_backend()
+					.ok_or_else(||
+						Box::new(state_machine::ExecutionError::UnableToGenerateProof) as Box<state_machine::Error>
+					)?;
+				let backend = state_machine::ProvingBackend::new_with_recorder(&trie_state, recorder.clone());
+				self.execute_with_header(&backend, changes, header, method, call_data, &manager, context, limits)
+			},
+			None => self.execute_with_header(&state, changes, header, method, call_data, &manager, context, limits),
+		}?;
+
+		if initialise {
+			*initialised_block = Some(*at);
+		}
+
+		Ok(result)
+	}
+
 	fn runtime_version(&self, id: &BlockId<Block>) -> error::Result<RuntimeVersion> {
 		let mut overlay = OverlayedChanges::default();
 		let state = self.backend.state_at(*id)?;
@@ -174,6 +325,9 @@ where
 			&self.executor,
 			method,
 			call_data,
+			Capabilities::none(),
+			None,
+			ExecutionLimits::none(),
 			manager,
 		).map_err(Into::into)
 	}
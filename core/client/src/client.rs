@@ -16,7 +16,7 @@
 
 //! Substrate Client
 
-use std::{marker::PhantomData, collections::{HashSet, BTreeMap}, sync::Arc};
+use std::{marker::PhantomData, collections::{HashSet, BTreeMap}, sync::Arc, cell::RefCell, rc::Rc};
 use error::Error;
 use futures::sync::mpsc;
 use parking_lot::{Mutex, RwLock};
@@ -32,16 +32,16 @@ use runtime_primitives::traits::{
 	ApiRef, ProvideRuntimeApi, Digest, DigestItem,
 };
 use runtime_primitives::BuildStorage;
-use runtime_api::{Core as CoreAPI, CallApiAt, TaggedTransactionQueue, ConstructRuntimeApi};
+use runtime_api::{Core as CoreAPI, CallApiAt, TaggedTransactionQueue, ConstructRuntimeApi, ProofRecorder};
 use primitives::{Blake2Hasher, H256, ChangesTrieConfiguration, convert_hash};
 use primitives::storage::{StorageKey, StorageData};
 use primitives::storage::well_known_keys;
 use codec::Decode;
 use state_machine::{
 	DBValue, Backend as StateBackend, CodeExecutor, ChangesTrieAnchorBlockId,
-	ExecutionStrategy, ExecutionManager, prove_read,
+	ExecutionStrategy, ExecutionManager, ExecutionContext, ExecutionLimits, prove_read,
 	ChangesTrieRootsStorage, ChangesTrieStorage,
-	key_changes, key_changes_proof, OverlayedChanges
+	key_changes, key_changes_proof, OverlayedChanges, MemoryDB, storage_changes_transaction,
 };
 use codec::Encode;
 
@@ -70,6 +70,8 @@ pub struct Client<B, E, Block, RA> where Block: BlockT {
 	importing_block: RwLock<Option<Block::Hash>>, // holds the block hash currently being imported. TODO: replace this with block queue
 	block_execution_strategy: ExecutionStrategy,
 	api_execution_strategy: ExecutionStrategy,
+	bad_blocks: HashSet<Block::Hash>,
+	max_reorg_depth: Option<u64>,
 	_phantom: PhantomData<RA>,
 }
 
@@ -145,12 +147,17 @@ pub struct BlockImportNotification<Block: BlockT> {
 }
 
 /// Summary of a finalized block.
+///
+/// When several blocks are finalized at once, a single notification is sent for the last
+/// of them.
 #[derive(Clone, Debug)]
 pub struct FinalityNotification<Block: BlockT> {
 	/// Imported block header hash.
 	pub hash: Block::Hash,
 	/// Imported block header.
 	pub header: Block::Header,
+	/// Hashes of the ancestors finalized along with the block, oldest first.
+	pub tree_route: Vec<Block::Hash>,
 }
 
 // used in importing a block, where additional changes are made after the runtime
@@ -231,7 +238,8 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 		block_execution_strategy: ExecutionStrategy,
 		api_execution_strategy: ExecutionStrategy,
 	) -> error::Result<Self> {
-		if backend.blockchain().header(BlockId::Number(Zero::zero()))?.is_none() {
+		// databases restored from a state snapshot start at the snapshot block rather than at genesis.
+		if backend.blockchain().info()?.genesis_hash == Default::default() {
 			let (genesis_storage, children_genesis_storage) = build_genesis_storage.build_storage()?;
 			let mut op = backend.begin_operation(BlockId::Hash(Default::default()))?;
 			let state_root = op.reset_storage(genesis_storage, children_genesis_storage)?;
@@ -257,10 +265,26 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 			importing_block: Default::default(),
 			block_execution_strategy,
 			api_execution_strategy,
+			bad_blocks: Default::default(),
+			max_reorg_depth: None,
 			_phantom: Default::default(),
 		})
 	}
 
+	/// Refuse to import the given blocks, and so any of their descendants.
+	pub fn with_bad_blocks(mut self, bad_blocks: HashSet<Block::Hash>) -> Self {
+		self.bad_blocks = bad_blocks;
+		self
+	}
+
+	/// Never make a block the best block if that reverts more than the given number of blocks of
+	/// the best chain. Such blocks are still imported, and finalized or justified blocks are
+	/// exempt.
+	pub fn with_max_reorg_depth(mut self, max_reorg_depth: Option<u64>) -> Self {
+		self.max_reorg_depth = max_reorg_depth;
+		self
+	}
+
 	/// Get a reference to the state at a given block.
 	pub fn state_at(&self, block: &BlockId<Block>) -> error::Result<B::State> {
 		self.backend.state_at(*block)
@@ -326,31 +350,6 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 		self.header_proof_with_cht_size(id, cht::SIZE)
 	}
 
-	pub(crate) fn call_at_state(
-		&self,
-		at: &BlockId<Block>,
-		function: &'static str,
-		args: Vec<u8>,
-		changes: &mut OverlayedChanges
-	) -> error::Result<Vec<u8>> {
-		let state = self.state_at(at)?;
-
-		let execution_manager = || match self.api_execution_strategy {
-			ExecutionStrategy::NativeWhenPossible => ExecutionManager::NativeWhenPossible,
-			ExecutionStrategy::AlwaysWasm => ExecutionManager::AlwaysWasm,
-			ExecutionStrategy::Both => ExecutionManager::Both(|wasm_result, native_result| {
-				warn!("Consensus error between wasm and native runtime execution at block {:?}", at);
-				warn!("   Function {:?}", function);
-				warn!("   Native result {:?}", native_result);
-				warn!("   Wasm result {:?}", wasm_result);
-				wasm_result
-			}),
-		};
-
-		self.executor.call_at_state(&state, changes, function, &args, execution_manager())
-			.map(|res| res.0)
-	}
-
 	/// Get block hash by number.
 	pub fn block_hash(&self, block_number: <<Block as BlockT>::Header as HeaderT>::Number) -> error::Result<Option<Block::Hash>> {
 		self.backend.blockchain().hash(block_number)
@@ -598,6 +597,7 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 		authorities: Option<Vec<AuthorityId>>,
 		finalized: bool,
 		aux: Vec<(Vec<u8>, Option<Vec<u8>>)>,
+		storage_changes: Option<OverlayedChanges>,
 	) -> error::Result<ImportResult> where
 		RA: TaggedTransactionQueue<Block>,
 		E: CallExecutor<Block, Blake2Hasher> + Send + Sync + Clone,
@@ -624,39 +624,46 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 		// ensure parent block is finalized to maintain invariant that
 		// finality is called sequentially.
 		if finalized {
-			self.apply_finality(parent_hash, last_best, make_notifications)?;
+			self.apply_finality(parent_hash, None, last_best, make_notifications)?;
 		}
 
 		let tags = self.transaction_tags(parent_hash, &body)?;
 		let mut transaction = self.backend.begin_operation(BlockId::Hash(parent_hash))?;
+		// blocks imported during major sync are far behind the head of the chain: let the
+		// backend batch their writes rather than syncing the database for every block.
+		if let BlockOrigin::NetworkInitialSync = origin {
+			transaction.set_commit_buffered(true);
+		}
 		let (storage_update, changes_update, storage_changes) = match transaction.state()? {
 			Some(transaction_state) => {
-				let mut overlay = Default::default();
-				let mut r = self.executor.call_at_state(
-					transaction_state,
-					&mut overlay,
-					"execute_block",
-					&<Block as BlockT>::new(import_headers.pre().clone(), body.clone().unwrap_or_default()).encode(),
-					match (origin, self.block_execution_strategy) {
-						(BlockOrigin::NetworkInitialSync, _) | (_, ExecutionStrategy::NativeWhenPossible) =>
-							ExecutionManager::NativeWhenPossible,
-						(_, ExecutionStrategy::AlwaysWasm) => ExecutionManager::AlwaysWasm,
-						_ => ExecutionManager::Both(|wasm_result, native_result| {
-							let header = import_headers.post();
-							warn!("Consensus error between wasm and native block execution at block {}", hash);
-							warn!("   Header {:?}", header);
-							warn!("   Native result {:?}", native_result);
-							warn!("   Wasm result {:?}", wasm_result);
-							telemetry!("block.execute.consensus_failure";
-								"hash" => ?hash,
-								"origin" => ?origin,
-								"header" => ?header
-							);
-							wasm_result
-						}),
-					},
-				);
-				let (_, storage_update, changes_update) = r?;
+				// changes made while building the block are only trusted if they lead to the
+				// state root and the changes trie root the block commits to.
+				let prepared = storage_changes.and_then(|mut overlay| {
+					let number: u64 = import_headers.pre().number().as_();
+					let parent_number = number.saturating_sub(1);
+					let (root, changes_trie_root, storage_update, changes_update) = storage_changes_transaction(
+						transaction_state,
+						self.backend.changes_trie_storage(),
+						&mut overlay,
+						parent_hash,
+						parent_number,
+					);
+
+					let expected_changes_trie_root = import_headers.pre().digest()
+						.log(DigestItem::as_changes_trie_root)
+						.cloned();
+					if root == *import_headers.pre().state_root() && changes_trie_root == expected_changes_trie_root {
+						Some((storage_update, changes_update, overlay))
+					} else {
+						warn!("Storage changes of block {} do not match its state or changes trie root. Executing it instead", hash);
+						None
+					}
+				});
+
+				let (storage_update, changes_update, mut overlay) = match prepared {
+					Some(prepared) => prepared,
+					None => self.execute_block_at_state(transaction_state, origin, hash, &import_headers, &body)?,
+				};
 				overlay.commit_prospective();
 				(Some(storage_update), Some(changes_update), Some(overlay.into_committed()))
 			},
@@ -664,7 +671,26 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 		};
 
 		// TODO: non longest-chain rule.
-		let is_new_best = finalized || import_headers.post().number() > &last_best_number;
+		let mut is_new_best = finalized || import_headers.post().number() > &last_best_number;
+		// blocks which are finalized or come with a justification become the best block
+		// regardless of the reorg they cause.
+		if is_new_best && !finalized && justification.is_empty() && parent_hash != last_best {
+			if let Some(max_reorg_depth) = self.max_reorg_depth {
+				let route = ::blockchain::tree_route(
+					self.backend.blockchain(),
+					BlockId::Hash(last_best),
+					BlockId::Hash(parent_hash),
+				)?;
+				if route.retracted().len() as u64 > max_reorg_depth {
+					warn!(
+						"Not making block {} the best block, which would revert {} blocks of the best chain",
+						hash,
+						route.retracted().len(),
+					);
+					is_new_best = false;
+				}
+			}
+		}
 		let leaf_state = if finalized {
 			::backend::NewBlockState::Final
 		} else if is_new_best {
@@ -706,6 +732,7 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 				let notification = FinalityNotification::<Block> {
 					hash,
 					header: import_headers.post().clone(),
+					tree_route: Vec::new(),
 				};
 
 				self.finality_notification_sinks.lock()
@@ -724,11 +751,57 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 				.retain(|sink| sink.unbounded_send(notification.clone()).is_ok());
 		}
 
-		Ok(ImportResult::Queued)
+		Ok(ImportResult::Queued(Default::default()))
 	}
 
-	/// Finalizes all blocks up to given.
-	fn apply_finality(&self, block: Block::Hash, best_block: Block::Hash, notify: bool) -> error::Result<()> {
+	/// Execute the block with the given headers and body on top of `state`, returning the
+	/// storage and changes trie updates along with the changes made.
+	fn execute_block_at_state<S: StateBackend<Blake2Hasher>>(
+		&self,
+		state: &S,
+		origin: BlockOrigin,
+		hash: Block::Hash,
+		import_headers: &PrePostHeader<Block::Header>,
+		body: &Option<Vec<Block::Extrinsic>>,
+	) -> error::Result<(S::Transaction, Option<MemoryDB<Blake2Hasher>>, OverlayedChanges)> {
+		let mut overlay = Default::default();
+		let r = self.executor.call_at_state(
+			state,
+			&mut overlay,
+			"execute_block",
+			&<Block as BlockT>::new(import_headers.pre().clone(), body.clone().unwrap_or_default()).encode(),
+			match (origin, self.block_execution_strategy) {
+				(BlockOrigin::NetworkInitialSync, _) | (_, ExecutionStrategy::NativeWhenPossible) =>
+					ExecutionManager::NativeWhenPossible,
+				(_, ExecutionStrategy::AlwaysWasm) => ExecutionManager::AlwaysWasm,
+				_ => ExecutionManager::Both(|wasm_result, native_result| {
+					let header = import_headers.post();
+					warn!("Consensus error between wasm and native block execution at block {}", hash);
+					warn!("   Header {:?}", header);
+					warn!("   Native result {:?}", native_result);
+					warn!("   Wasm result {:?}", wasm_result);
+					telemetry!("block.execute.consensus_failure";
+						"hash" => ?hash,
+						"origin" => ?origin,
+						"header" => ?header
+					);
+					wasm_result
+				}),
+			},
+		);
+		let (_, storage_update, changes_update) = r?;
+		Ok((storage_update, changes_update, overlay))
+	}
+
+	/// Finalizes all blocks up to given. If a justification is provided it is stored with the given
+	/// block.
+	fn apply_finality(
+		&self,
+		block: Block::Hash,
+		justification: Option<Justification>,
+		best_block: Block::Hash,
+		notify: bool,
+	) -> error::Result<()> {
 		// find tree route from last finalized to given block.
 		let last_finalized = self.backend.blockchain().last_finalized()?;
 
@@ -759,27 +832,27 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 			// `block`.
 		}
 
-		for finalize_new in route_from_finalized.enacted() {
-			self.backend.finalize_block(BlockId::Hash(finalize_new.hash))?;
+		let mut justification = justification;
+		let enacted = route_from_finalized.enacted();
+		for finalize_new in enacted {
+			// the justification only belongs to the last enacted block, which is `block`.
+			let justification = if finalize_new.hash == block { justification.take() } else { None };
+			self.backend.finalize_block(BlockId::Hash(finalize_new.hash), justification)?;
 		}
 
-		if notify {
-			// sometimes when syncing, tons of blocks can be finalized at once.
-			// we'll send notifications spuriously in that case.
-			const MAX_TO_NOTIFY: usize = 256;
-			let enacted = route_from_finalized.enacted();
-			let start = enacted.len() - ::std::cmp::min(enacted.len(), MAX_TO_NOTIFY);
-			let mut sinks = self.finality_notification_sinks.lock();
-			for finalized in &enacted[start..] {
-				let header = self.header(&BlockId::Hash(finalized.hash))?
-					.expect("header already known to exist in DB because it is indicated in the tree route; qed");
-				let notification = FinalityNotification {
-					header,
-					hash: finalized.hash,
-				};
+		// sometimes when syncing, tons of blocks can be finalized at once: send a single
+		// notification for the last one, listing the others.
+		if let (true, Some(last)) = (notify, enacted.last()) {
+			let header = self.header(&BlockId::Hash(last.hash))?
+				.expect("header already known to exist in DB because it is indicated in the tree route; qed");
+			let notification = FinalityNotification {
+				header,
+				hash: last.hash,
+				tree_route: enacted[..enacted.len() - 1].iter().map(|b| b.hash).collect(),
+			};
 
-				sinks.retain(|sink| sink.unbounded_send(notification.clone()).is_ok());
-			}
+			self.finality_notification_sinks.lock()
+				.retain(|sink| sink.unbounded_send(notification.clone()).is_ok());
 		}
 
 		Ok(())
@@ -788,10 +861,18 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 	/// Finalize a block. This will implicitly finalize all blocks up to it and
 	/// fire finality notifications.
 	///
+	/// The given justification, if any, is stored with the block, replacing the one it was
+	/// imported with.
+	///
 	/// Pass a flag to indicate whether finality notifications should be propagated.
 	/// This is usually tied to some synchronization state, where we don't send notifications
 	/// while performing major synchronization work.
-	pub fn finalize_block(&self, id: BlockId<Block>, notify: bool) -> error::Result<()> {
+	pub fn finalize_block(
+		&self,
+		id: BlockId<Block>,
+		justification: Option<Justification>,
+		notify: bool,
+	) -> error::Result<()> {
 		let last_best = self.backend.blockchain().info()?.best_hash;
 		let to_finalize_hash = match id {
 			BlockId::Hash(h) => h,
@@ -799,7 +880,7 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 				.ok_or_else(|| error::ErrorKind::UnknownBlock(format!("No block with number {:?}", n)))?,
 		};
 
-		self.apply_finality(to_finalize_hash, last_best, notify)
+		self.apply_finality(to_finalize_hash, justification, last_best, notify)
 	}
 
 	/// Attempts to revert the chain by `n` blocks. Returns the number of blocks that were
@@ -1033,11 +1114,30 @@ impl<B, E, Block, RA> CallApiAt<Block> for Client<B, E, Block, RA> where
 		args: Vec<u8>,
 		changes: &mut OverlayedChanges,
 		initialised_block: &mut Option<BlockId<Block>>,
+		context: ExecutionContext,
+		execution_strategy: Option<ExecutionStrategy>,
+		limits: ExecutionLimits,
+		recorder: &Option<Rc<RefCell<ProofRecorder<Block>>>>,
 	) -> error::Result<Vec<u8>> {
-		//TODO: Find a better way to prevent double block initialization
-		if function != "initialise_block" && initialised_block.map(|id| id != *at).unwrap_or(true) {
+		let strategy = execution_strategy.unwrap_or_else(|| match context {
+			ExecutionContext::Importing | ExecutionContext::Syncing | ExecutionContext::BlockConstruction =>
+				self.block_execution_strategy,
+			ExecutionContext::OffchainWorker | ExecutionContext::Other => self.api_execution_strategy,
+		});
+		let execution_manager = || match strategy {
+			ExecutionStrategy::NativeWhenPossible => ExecutionManager::NativeWhenPossible,
+			ExecutionStrategy::AlwaysWasm => ExecutionManager::AlwaysWasm,
+			ExecutionStrategy::Both => ExecutionManager::Both(|wasm_result, native_result| {
+				warn!("Consensus error between wasm and native runtime execution at block {:?}", at);
+				warn!("   Function {:?}", function);
+				warn!("   Native result {:?}", native_result);
+				warn!("   Wasm result {:?}", wasm_result);
+				wasm_result
+			}),
+		};
+		let prepare_environment_block = || {
 			let parent = at;
-			let header = <<Block as BlockT>::Header as HeaderT>::new(
+			Ok(<<Block as BlockT>::Header as HeaderT>::new(
 				self.block_number_from_id(parent)?
 					.ok_or_else(|| error::ErrorKind::UnknownBlock(format!("{:?}", parent)))?
 				+ As::sa(1),
@@ -1046,15 +1146,46 @@ impl<B, E, Block, RA> CallApiAt<Block> for Client<B, E, Block, RA> where
 				self.block_hash_from_id(&parent)?
 					.ok_or_else(|| error::ErrorKind::UnknownBlock(format!("{:?}", parent)))?,
 				Default::default()
-			);
-			self.call_at_state(at, "initialise_block", header.encode(), changes)?;
-			*initialised_block = Some(*at);
-		}
+			))
+		};
 
-		self.call_at_state(at, function, args, changes)
+		self.executor.contextual_call(
+			at,
+			function,
+			&args,
+			changes,
+			initialised_block,
+			prepare_environment_block,
+			execution_manager,
+			context,
+			limits,
+			recorder,
+		)
 	}
-}
 
+	fn runtime_version_at(&self, at: &BlockId<Block>) -> error::Result<RuntimeVersion> {
+		self.runtime_version_at(at)
+	}
+
+	fn storage_root_at(
+		&self,
+		at: &BlockId<Block>,
+		changes: &OverlayedChanges,
+	) -> error::Result<Block::Hash> {
+		let state = self.state_at(at)?;
+		let hash = self.block_hash_from_id(at)?
+			.ok_or_else(|| error::ErrorKind::UnknownBlock(format!("{:?}", at)))?;
+		let number: u64 = self.require_block_number_from_id(at)?.as_();
+		let (root, _, _, _) = storage_changes_transaction(
+			&state,
+			self.backend.changes_trie_storage(),
+			&mut changes.clone(),
+			hash,
+			number,
+		);
+		Ok(root)
+	}
+}
 
 impl<B, E, Block, RA> consensus::BlockImport<Block> for Client<B, E, Block, RA> where
 	B: backend::Backend<Block, Blake2Hasher>,
@@ -1080,9 +1211,19 @@ impl<B, E, Block, RA> consensus::BlockImport<Block> for Client<B, E, Block, RA>
 			body,
 			finalized,
 			auxiliary,
+			storage_changes,
 		} = import_block;
 		let parent_hash = header.parent_hash().clone();
 
+		// seals are never seen by the runtime, so they must be stripped off the pre-header
+		// by the verifier and only be given as post-digests.
+		if header.digest().logs().iter().any(|item| item.as_seal().is_some()) {
+			bail!(error::ErrorKind::BadSeal(format!("{:?}", header.hash())));
+		}
+		if post_digests.iter().any(|item| item.as_seal().is_none()) {
+			bail!(error::ErrorKind::BadSeal(format!("{:?}", header.hash())));
+		}
+
 		match self.backend.blockchain().status(BlockId::Hash(parent_hash))? {
 			blockchain::BlockStatus::InChain => {},
 			blockchain::BlockStatus::Unknown => return Ok(ImportResult::UnknownParent),
@@ -1099,6 +1240,11 @@ impl<B, E, Block, RA> consensus::BlockImport<Block> for Client<B, E, Block, RA>
 		};
 
 		let hash = import_headers.post().hash();
+		if self.bad_blocks.contains(&hash) || self.bad_blocks.contains(&parent_hash) {
+			warn!("Refusing to import block {}, which is or builds on a known bad block", hash);
+			return Ok(ImportResult::KnownBad);
+		}
+
 		let _import_lock = self.import_lock.lock();
 		let height: u64 = import_headers.post().number().as_();
 		*self.importing_block.write() = Some(hash);
@@ -1112,6 +1258,7 @@ impl<B, E, Block, RA> consensus::BlockImport<Block> for Client<B, E, Block, RA>
 			new_authorities,
 			finalized,
 			auxiliary,
+			storage_changes,
 		);
 
 		*self.importing_block.write() = None;
@@ -1218,7 +1365,7 @@ pub(crate) mod tests {
 	use runtime_primitives::traits::DigestItem as DigestItemT;
 	use runtime_primitives::generic::DigestItem;
 	use test_client::{self, TestClient};
-	use consensus::BlockOrigin;
+	use consensus::{BlockImport, BlockOrigin};
 	use test_client::client::backend::Backend as TestBackend;
 	use test_client::BlockBuilderExt;
 	use test_client::runtime::{self, Block, Transfer, RuntimeApi, test_api::TestAPI};
@@ -1316,6 +1463,150 @@ pub(crate) mod tests {
 		);
 	}
 
+	#[test]
+	fn runtime_api_records_proof_of_calls() {
+		use runtime_api::ApiExt;
+
+		let client = test_client::new();
+		let mut runtime_api = client.runtime_api();
+		assert!(runtime_api.extract_proof().is_none());
+
+		runtime_api.record_proof();
+		assert_eq!(
+			runtime_api.balance_of(
+				&BlockId::Number(0),
+				&Keyring::Alice.to_raw_public().into()
+			).unwrap(),
+			1000
+		);
+
+		assert!(!runtime_api.extract_proof().unwrap().is_empty());
+		assert!(runtime_api.extract_proof().is_none());
+	}
+
+	#[test]
+	fn runtime_api_changes_can_be_reverted_to_snapshot() {
+		use runtime_api::{ApiExt, Core};
+		use block_builder::api::BlockBuilder;
+		use test_client::runtime::{Extrinsic, Header};
+
+		let client = test_client::new();
+		let runtime_api = client.runtime_api();
+		let at = BlockId::Number(0);
+		let alice = Keyring::Alice.to_raw_public().into();
+		let transfer = |amount, nonce| {
+			let transfer = Transfer {
+				from: Keyring::Alice.to_raw_public().into(),
+				to: Keyring::Ferdie.to_raw_public().into(),
+				amount,
+				nonce,
+			};
+			let signature = Keyring::Alice.sign(&transfer.encode()).into();
+			Extrinsic { transfer, signature }
+		};
+
+		let header = Header::new(
+			1,
+			Default::default(),
+			Default::default(),
+			client.info().unwrap().chain.genesis_hash,
+			Default::default(),
+		);
+		runtime_api.initialise_block(&at, &header).unwrap();
+
+		let first = runtime_api.snapshot();
+		runtime_api.apply_extrinsic(&at, &transfer(42, 0)).unwrap().unwrap();
+		assert_eq!(runtime_api.balance_of(&at, &alice).unwrap(), 958);
+
+		let second = runtime_api.snapshot();
+		runtime_api.apply_extrinsic(&at, &transfer(8, 1)).unwrap().unwrap();
+		assert_eq!(runtime_api.balance_of(&at, &alice).unwrap(), 950);
+
+		runtime_api.revert_to(second).unwrap();
+		assert_eq!(runtime_api.balance_of(&at, &alice).unwrap(), 958);
+		assert!(runtime_api.revert_to(second).is_err());
+
+		runtime_api.commit().unwrap();
+		assert!(runtime_api.revert_to(first).is_err());
+		assert!(runtime_api.commit().is_err());
+		assert_eq!(runtime_api.balance_of(&at, &alice).unwrap(), 958);
+	}
+
+	#[test]
+	fn runtime_api_storage_changes_lead_to_the_built_state_root() {
+		use runtime_api::{ApiExt, Core};
+		use block_builder::api::BlockBuilder;
+		use test_client::runtime::{Extrinsic, Header};
+
+		let client = test_client::new();
+		let runtime_api = client.runtime_api();
+		let at = BlockId::Number(0);
+		let transfer = Transfer {
+			from: Keyring::Alice.to_raw_public().into(),
+			to: Keyring::Ferdie.to_raw_public().into(),
+			amount: 42,
+			nonce: 0,
+		};
+		let signature = Keyring::Alice.sign(&transfer.encode()).into();
+
+		let header = Header::new(
+			1,
+			Default::default(),
+			Default::default(),
+			client.info().unwrap().chain.genesis_hash,
+			Default::default(),
+		);
+		runtime_api.initialise_block(&at, &header).unwrap();
+		runtime_api.apply_extrinsic(&at, &Extrinsic { transfer, signature }).unwrap().unwrap();
+		let header = runtime_api.finalise_block(&at).unwrap();
+
+		let changes = runtime_api.take_storage_changes_with_root(&at).unwrap();
+		assert_eq!(changes.storage_root, *header.state_root());
+		assert_eq!(
+			runtime_api.take_storage_changes_with_root(&at).unwrap().storage_root,
+			*client.header(&at).unwrap().unwrap().state_root(),
+		);
+	}
+
+	#[test]
+	fn runtime_api_calls_can_be_forced_to_wasm() {
+		use runtime_api::ApiExt;
+
+		let client = test_client::new();
+		let runtime_api = client.runtime_api();
+		let alice: runtime::AccountId = Keyring::Alice.to_raw_public().into();
+
+		let balance = runtime_api.with_execution_strategy(ExecutionStrategy::AlwaysWasm, |api| {
+			api.balance_of(&BlockId::Number(0), &alice)
+		}).unwrap();
+		assert_eq!(balance, 1000);
+		assert_eq!(runtime_api.balance_of(&BlockId::Number(0), &alice).unwrap(), 1000);
+	}
+
+	#[test]
+	fn runtime_api_calls_fail_once_they_exceed_their_limits() {
+		use runtime_api::ApiExt;
+
+		let client = test_client::new();
+		let runtime_api = client.runtime_api();
+		let alice: runtime::AccountId = Keyring::Alice.to_raw_public().into();
+
+		let out_of_fuel = ExecutionLimits { max_fuel: Some(1), ..ExecutionLimits::none() };
+		assert!(runtime_api.with_execution_limits(out_of_fuel, |api| {
+			api.balance_of(&BlockId::Number(0), &alice)
+		}).is_err());
+
+		let enough = ExecutionLimits {
+			max_duration: Some(::std::time::Duration::from_secs(60)),
+			max_fuel: Some(1_000_000_000),
+			max_memory_growth: None,
+		};
+		let balance = runtime_api.with_execution_limits(enough, |api| {
+			api.balance_of(&BlockId::Number(0), &alice)
+		}).unwrap();
+		assert_eq!(balance, 1000);
+	}
+
 	#[test]
 	fn authorities_call_works() {
 		let client = test_client::new();
@@ -1372,6 +1663,143 @@ pub(crate) mod tests {
 		);
 	}
 
+	#[test]
+	fn blocks_import_with_the_storage_changes_of_building_them() {
+		let client = test_client::new();
+		let transfer = |nonce| Transfer {
+			from: Keyring::Alice.to_raw_public().into(),
+			to: Keyring::Ferdie.to_raw_public().into(),
+			amount: 42,
+			nonce,
+		};
+		let import = |block: Block, storage_changes| {
+			let (header, extrinsics) = block.deconstruct();
+			client.import_block(ImportBlock {
+				origin: BlockOrigin::Own,
+				header,
+				justification: vec![],
+				post_digests: vec![],
+				body: Some(extrinsics),
+				finalized: false,
+				auxiliary: Vec::new(),
+				storage_changes: Some(storage_changes),
+			}, None).unwrap();
+		};
+
+		let mut builder = client.new_block().unwrap();
+		builder.push_transfer(transfer(0)).unwrap();
+		let (block, storage_changes) = builder.bake_with_changes().unwrap();
+		import(block, storage_changes);
+
+		// changes that don't match the block are ignored and the block is executed instead.
+		let mut builder = client.new_block().unwrap();
+		builder.push_transfer(transfer(1)).unwrap();
+		let (block, _) = builder.bake_with_changes().unwrap();
+		let (_, unrelated_changes) = client.new_block().unwrap().bake_with_changes().unwrap();
+		import(block, unrelated_changes);
+
+		assert_eq!(client.info().unwrap().chain.best_number, 2);
+		let balance_of = |who: Keyring| client.runtime_api().balance_of(
+			&BlockId::Number(2),
+			&who.to_raw_public().into(),
+		).unwrap();
+		assert_eq!(balance_of(Keyring::Alice), 916);
+		assert_eq!(balance_of(Keyring::Ferdie), 84);
+	}
+
+	#[test]
+	fn storage_changes_are_checked_against_the_changes_trie_root() {
+		let client = test_client::new_with_changes_trie();
+		let mut builder = client.new_block().unwrap();
+		builder.push_transfer(Transfer {
+			from: Keyring::Alice.to_raw_public().into(),
+			to: Keyring::Ferdie.to_raw_public().into(),
+			amount: 42,
+			nonce: 0,
+		}).unwrap();
+		let (block, storage_changes) = builder.bake_with_changes().unwrap();
+		let (mut header, extrinsics) = block.deconstruct();
+		assert!(header.digest().log(DigestItem::as_changes_trie_root).is_some());
+
+		// the storage changes still match the state root, but not the changes trie root: the
+		// block is executed instead, which fails.
+		header.digest.logs = vec![DigestItem::ChangesTrieRoot([1; 32].into())];
+		assert!(client.import_block(ImportBlock {
+			origin: BlockOrigin::Own,
+			header,
+			justification: vec![],
+			post_digests: vec![],
+			body: Some(extrinsics),
+			finalized: false,
+			auxiliary: Vec::new(),
+			storage_changes: Some(storage_changes),
+		}, None).is_err());
+		assert_eq!(client.info().unwrap().chain.best_number, 0);
+	}
+
+	#[test]
+	fn import_refuses_known_bad_blocks_and_deep_reorgs_to_best() {
+		type FullClient = test_client::client::Client<test_client::Backend, test_client::Executor, Block, RuntimeApi>;
+
+		let client = test_client::new();
+		let import_justified = |client: &FullClient, block: Block, justification: Justification| {
+			let (header, extrinsics) = block.deconstruct();
+			client.import_block(ImportBlock {
+				origin: BlockOrigin::Own,
+				header,
+				justification,
+				post_digests: vec![],
+				body: Some(extrinsics),
+				finalized: false,
+				auxiliary: Vec::new(),
+				storage_changes: None,
+			}, None).unwrap()
+		};
+		let import = |client: &FullClient, block: Block| import_justified(client, block, vec![]);
+		let fork_block = |client: &FullClient, parent: BlockId<Block>, nonce| {
+			let mut builder = client.new_block_at(&parent).unwrap();
+			builder.push_transfer(Transfer {
+				from: Keyring::Alice.to_raw_public().into(),
+				to: Keyring::Ferdie.to_raw_public().into(),
+				amount: 42,
+				nonce,
+			}).unwrap();
+			builder.bake().unwrap()
+		};
+
+		let bad = fork_block(&client, BlockId::Number(0), 0);
+		let mut bad_blocks = HashSet::new();
+		bad_blocks.insert(bad.hash());
+		let client = client.with_bad_blocks(bad_blocks).with_max_reorg_depth(Some(1));
+		assert!(match import(&client, bad) { ImportResult::KnownBad => true, _ => false });
+
+		// #0 -> #1 -> #2
+		let a1 = client.new_block().unwrap().bake().unwrap();
+		assert!(match import(&client, a1) { ImportResult::Queued(_) => true, _ => false });
+		let a2 = client.new_block().unwrap().bake().unwrap();
+		assert!(match import(&client, a2) { ImportResult::Queued(_) => true, _ => false });
+
+		// #0 -> #1' -> #2' -> #3' would revert two blocks of the best chain: the fork is
+		// imported, but doesn't become the best chain.
+		let b1 = fork_block(&client, BlockId::Number(0), 1);
+		let b1_hash = b1.hash();
+		assert!(match import(&client, b1) { ImportResult::Queued(_) => true, _ => false });
+		let b2 = client.new_block_at(&BlockId::Hash(b1_hash)).unwrap().bake().unwrap();
+		let b2_hash = b2.hash();
+		assert!(match import(&client, b2) { ImportResult::Queued(_) => true, _ => false });
+		let b3 = client.new_block_at(&BlockId::Hash(b2_hash)).unwrap().bake().unwrap();
+		let b3_hash = b3.hash();
+		assert!(match import(&client, b3) { ImportResult::Queued(_) => true, _ => false });
+		assert_eq!(client.info().unwrap().chain.best_number, 2);
+
+		// a justified block is made the best block regardless.
+		let b4 = client.new_block_at(&BlockId::Hash(b3_hash)).unwrap().bake().unwrap();
+		let b4_hash = b4.hash();
+		let justification = vec![(*b"test", vec![1])];
+		assert!(match import_justified(&client, b4, justification) { ImportResult::Queued(_) => true, _ => false });
+		assert_eq!(client.info().unwrap().chain.best_hash, b4_hash);
+	}
+
 	#[test]
 	fn client_uses_authorities_from_blockchain_cache() {
 		let client = test_client::new();
@@ -1457,6 +1885,45 @@ pub(crate) mod tests {
 		assert_eq!(a2.hash(), client.best_containing(a2.hash(), None).unwrap().unwrap());
 	}
 
+	#[test]
+	fn finalizing_a_range_sends_a_single_notification() {
+		use futures::{Future, Stream};
+
+		let client = test_client::new();
+		let mut hashes = Vec::new();
+		for _ in 0..5 {
+			let block = client.new_block().unwrap().bake().unwrap();
+			hashes.push(block.hash());
+			client.justify_and_import(BlockOrigin::Own, block).unwrap();
+		}
+
+		let notifications = client.finality_notification_stream();
+		client.finalize_block(BlockId::Hash(hashes[4]), None, true).unwrap();
+		drop(client);
+
+		let notifications = notifications.collect().wait().unwrap();
+		assert_eq!(notifications.len(), 1);
+		assert_eq!(notifications[0].hash, hashes[4]);
+		assert_eq!(notifications[0].tree_route, hashes[..4].to_vec());
+	}
+
+	#[test]
+	fn finalizing_a_block_stores_its_justification() {
+		let client = test_client::new();
+		let mut hashes = Vec::new();
+		for _ in 0..2 {
+			let block = client.new_block().unwrap().bake().unwrap();
+			hashes.push(block.hash());
+			client.justify_and_import(BlockOrigin::Own, block).unwrap();
+		}
+
+		let justification = vec![(*b"test", vec![1, 2, 3])];
+		client.finalize_block(BlockId::Hash(hashes[1]), Some(justification.clone()), true).unwrap();
+
+		assert_eq!(client.justification(&BlockId::Hash(hashes[0])).unwrap(), Some(vec![]));
+		assert_eq!(client.justification(&BlockId::Hash(hashes[1])).unwrap(), Some(justification));
+	}
+
 	#[test]
 	fn best_containing_with_multiple_forks() {
 		// NOTE: we use the version of the trait from `test_client`
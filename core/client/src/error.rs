@@ -52,6 +52,12 @@ error_chain! {
 			display("Execution: {}", e),
 		}
 
+		/// The runtime could not decode an argument of the called method.
+		BadInput(method: String, index: u32) {
+			description("bad input data provided to runtime"),
+			display("Bad input data provided to {}: argument {} could not be decoded", method, index),
+		}
+
 		/// Blockchain error.
 		Blockchain(e: Box<std::error::Error + Send>) {
 			description("Blockchain error"),
@@ -82,6 +88,12 @@ error_chain! {
 			display("bad justification for header: {}", &*h),
 		}
 
+		/// Seals in the header of an imported block are misplaced.
+		BadSeal(h: String) {
+			description("misplaced seal in block header"),
+			display("Misplaced seal in header of block {}", &*h),
+		}
+
 		/// Not available on light client.
 		NotAvailableOnLightClient {
 			description("not available on light client"),
@@ -112,6 +124,12 @@ error_chain! {
 			display("Error decoding call result of {}", method)
 		}
 
+		/// The snapshot of runtime api changes was already committed or reverted.
+		InvalidSnapshot {
+			description("invalid snapshot"),
+			display("Snapshot of runtime api changes was already committed or reverted"),
+		}
+
 		/// Changes tries are not supported.
 		ChangesTriesNotSupported {
 			description("changes tries are not supported"),
@@ -136,18 +154,34 @@ error_chain! {
 			display("Potential long-range attack: block not in finalized chain."),
 		}
 
+		/// State snapshot is malformed or doesn't match the expected state.
+		InvalidStateSnapshot(s: String) {
+			description("invalid state snapshot"),
+			display("Invalid state snapshot: {}", s),
+		}
+
 		/// Hash that is required for building CHT is missing.
 		MissingHashRequiredForCHT(cht_num: u64, block_number: u64) {
 			description("missed hash required for building CHT"),
 			display("Failed to get hash of block#{} for building CHT#{}", block_number, cht_num),
 		}
+
+		/// The header of the block has been pruned, only the root of the CHT proving it is known.
+		HeaderPruned(block_number: String, cht_root: String) {
+			description("header has been pruned"),
+			display("Header of block#{} has been pruned; it is proven by the CHT with root {}", block_number, cht_root),
+		}
 	}
 }
 
 // TODO [ToDr] Temporary, state_machine::Error should be a regular error not Box.
 impl From<Box<state_machine::Error>> for Error {
 	fn from(e: Box<state_machine::Error>) -> Self {
-		ErrorKind::Execution(e).into()
+		let bad_input = e.bad_input().map(|(method, index)| (method.to_owned(), index));
+		match bad_input {
+			Some((method, index)) => ErrorKind::BadInput(method, index).into(),
+			None => ErrorKind::Execution(e).into(),
+		}
 	}
 }
 
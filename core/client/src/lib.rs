@@ -87,6 +87,8 @@ pub mod cht;
 pub mod in_mem;
 #[cfg(feature = "std")]
 pub mod genesis;
+#[cfg(feature = "std")]
+pub mod snapshot;
 pub mod block_builder;
 #[cfg(feature = "std")]
 pub mod light;
@@ -118,4 +120,6 @@ pub use state_machine::ExecutionStrategy;
 pub use leaves::LeafSet;
 
 #[doc(inline)]
-pub use sr_api_macros::{decl_runtime_apis, impl_runtime_apis};
+pub use sr_api_macros::{
+	decl_runtime_apis, impl_runtime_apis, mock_impl_runtime_apis, runtime_version
+};
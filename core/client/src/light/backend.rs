@@ -102,7 +102,7 @@ impl<S, F, Block, H> ClientBackend<Block, H> for Backend<S, F> where
 		)
 	}
 
-	fn finalize_block(&self, block: BlockId<Block>) -> ClientResult<()> {
+	fn finalize_block(&self, block: BlockId<Block>, _justification: Option<Justification>) -> ClientResult<()> {
 		self.blockchain.storage().finalize_header(block)
 	}
 
@@ -139,6 +139,14 @@ impl<S, F, Block, H> ClientBackend<Block, H> for Backend<S, F> where
 	fn get_aux(&self, _key: &[u8]) -> ClientResult<Option<Vec<u8>>> {
 		Err(ClientErrorKind::NotAvailableOnLightClient.into())
 	}
+
+	fn aux_with_prefix(&self, _prefix: &[u8]) -> ClientResult<Vec<(Vec<u8>, Vec<u8>)>> {
+		Err(ClientErrorKind::NotAvailableOnLightClient.into())
+	}
+
+	fn compare_and_swap_aux<'a, 'b: 'a, 'c: 'a, I: IntoIterator<Item=&'a (&'c [u8], &'c [u8])>, D: IntoIterator<Item=&'a &'b [u8]>>(&self, _expected: &[(&[u8], Option<&[u8]>)], _insert: I, _delete: D) -> ClientResult<bool> {
+		Err(ClientErrorKind::NotAvailableOnLightClient.into())
+	}
 }
 
 impl<S, F, Block, H> RemoteBackend<Block, H> for Backend<S, F>
@@ -244,6 +252,10 @@ where
 		// whole state is not available on light node
 	}
 
+	fn for_keys_with_prefix_while<A: FnMut(&[u8]) -> bool>(&self, _prefix: &[u8], _start: &[u8], _action: A) {
+		// whole state is not available on light node
+	}
+
 	fn for_keys_in_child_storage<A: FnMut(&[u8])>(&self, _storage_key: &[u8], _action: A) {
 		// whole state is not available on light node
 	}
@@ -18,7 +18,6 @@
 //! blocks. CHT roots are stored for headers of ancient blocks.
 
 use std::sync::Weak;
-use futures::{Future, IntoFuture};
 use parking_lot::Mutex;
 
 use primitives::AuthorityId;
@@ -30,7 +29,7 @@ use blockchain::{Backend as BlockchainBackend, BlockStatus, Cache as BlockchainC
 	HeaderBackend as BlockchainHeaderBackend, Info as BlockchainInfo};
 use cht;
 use error::{ErrorKind as ClientErrorKind, Result as ClientResult};
-use light::fetcher::{Fetcher, RemoteHeaderRequest};
+use light::fetcher::Fetcher;
 
 /// Light client blockchain storage.
 pub trait Storage<Block: BlockT>: BlockchainHeaderBackend<Block> {
@@ -111,14 +110,11 @@ impl<S, F, Block> BlockchainHeaderBackend<Block> for Blockchain<S, F> where Bloc
 					return Ok(None);
 				}
 
-				self.fetcher().upgrade().ok_or(ClientErrorKind::NotAvailableOnLightClient)?
-					.remote_header(RemoteHeaderRequest {
-						cht_root: self.storage.header_cht_root(cht::SIZE, number)?,
-						block: number,
-						retry_count: None,
-					})
-					.into_future().wait()
-					.map(Some)
+				// the header has been pruned => report the CHT root that proves it. it is not fetched
+				// from the network here, since callers (e.g. the network service) must not block on
+				// remote requests; `Fetcher::remote_header` does so asynchronously.
+				let cht_root = self.storage.header_cht_root(cht::SIZE, number)?;
+				Err(ClientErrorKind::HeaderPruned(number.to_string(), format!("{:?}", cht_root)).into())
 			}
 		}
 	}
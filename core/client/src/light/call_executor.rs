@@ -17,21 +17,24 @@
 //! Light client call exector. Executes methods on remote full nodes, fetching
 //! execution proof and checking it locally.
 
+use std::cell::RefCell;
 use std::marker::PhantomData;
+use std::rc::Rc;
 use std::sync::Arc;
 use futures::{IntoFuture, Future};
 
 use primitives::convert_hash;
 use runtime_primitives::generic::BlockId;
-use runtime_primitives::traits::{Block as BlockT, Header as HeaderT};
+use runtime_primitives::traits::{Block as BlockT, Header as HeaderT, ProvideRuntimeApi, ApiRef};
 use state_machine::{Backend as StateBackend, CodeExecutor, OverlayedChanges,
-	execution_proof_check, ExecutionManager};
+	execution_proof_check, ExecutionManager, ExecutionContext, ExecutionStrategy, ExecutionLimits};
 use hash_db::Hasher;
 
 use blockchain::Backend as ChainBackend;
 use call_executor::{CallExecutor, CallResult};
 use error::{Error as ClientError, ErrorKind as ClientErrorKind, Result as ClientResult};
 use light::fetcher::{Fetcher, RemoteCallRequest};
+use runtime_api::{CallApiAt, ConstructRuntimeApi, ProofRecorder};
 use executor::{RuntimeVersion, NativeVersion};
 use codec::Decode;
 use heapsize::HeapSizeOf;
@@ -72,7 +75,14 @@ where
 {
 	type Error = ClientError;
 
-	fn call(&self, id: &BlockId<Block>, method: &str, call_data: &[u8]) -> ClientResult<CallResult> {
+	fn call_with_limits(
+		&self,
+		id: &BlockId<Block>,
+		method: &str,
+		call_data: &[u8],
+		_limits: ExecutionLimits,
+	) -> ClientResult<CallResult> {
+		// the call is subject to the limits of the remote node.
 		let block_hash = match *id {
 			BlockId::Hash(hash) => hash,
 			BlockId::Number(number) => self.blockchain.hash(number)?
@@ -89,6 +99,32 @@ where
 		}).into_future().wait()
 	}
 
+	fn contextual_call<
+		PB: FnOnce() -> ClientResult<Block::Header>,
+		FF: FnOnce(Result<Vec<u8>, Self::Error>, Result<Vec<u8>, Self::Error>) -> Result<Vec<u8>, Self::Error>,
+		M: Fn() -> ExecutionManager<FF>,
+	>(&self,
+		at: &BlockId<Block>,
+		method: &'static str,
+		call_data: &[u8],
+		changes: &mut OverlayedChanges,
+		_initialised_block: &mut Option<BlockId<Block>>,
+		_prepare_environment_block: PB,
+		_manager: M,
+		_context: ExecutionContext,
+		_limits: ExecutionLimits,
+		recorder: &Option<Rc<RefCell<ProofRecorder<Block>>>>,
+	) -> ClientResult<Vec<u8>> {
+		// the remote node executes every call on top of the block state, without initialising
+		// the block: changes made by previous calls can't be taken into account.
+		// the light client has no state to generate a proof from either.
+		if !changes.is_empty() || recorder.is_some() {
+			return Err(ClientErrorKind::NotAvailableOnLightClient.into());
+		}
+
+		self.call(at, method, call_data).map(|result| result.return_data)
+	}
+
 	fn runtime_version(&self, id: &BlockId<Block>) -> ClientResult<RuntimeVersion> {
 		let call_result = self.call(id, "version", &[])?;
 		RuntimeVersion::decode(&mut call_result.return_data.as_slice())
@@ -146,15 +182,117 @@ pub fn check_execution_proof<Header, E, H>(
 		&mut changes,
 		executor,
 		&request.method,
-		&request.call_data)?;
+		&request.call_data,
+		ExecutionLimits::none(),
+	)?;
 
 	Ok(CallResult { return_data: local_result, changes })
 }
 
+/// Runtime API which executes calls on top of the state reconstructed from an execution proof.
+///
+/// The block passed to the API methods is ignored and isn't initialised: calls are executed
+/// on top of the state with the given root, and fail if they read storage missing from the
+/// proof.
+pub struct ProofCheckedApi<E, H: Hasher, Block, RA> {
+	executor: E,
+	root: H::Out,
+	proof: Vec<Vec<u8>>,
+	_marker: PhantomData<(Block, RA)>,
+}
+
+impl<E, H: Hasher, Block, RA> ProofCheckedApi<E, H, Block, RA> {
+	/// Creates new instance checking calls against `proof` of the state with the given `root`.
+	pub fn new(executor: E, root: H::Out, proof: Vec<Vec<u8>>) -> Self {
+		ProofCheckedApi { executor, root, proof, _marker: PhantomData }
+	}
+}
+
+impl<E, H, Block, RA> CallApiAt<Block> for ProofCheckedApi<E, H, Block, RA>
+	where
+		E: CodeExecutor<H>,
+		H: Hasher,
+		H::Out: Ord + HeapSizeOf,
+		Block: BlockT,
+{
+	fn call_api_at(
+		&self,
+		_at: &BlockId<Block>,
+		function: &'static str,
+		args: Vec<u8>,
+		changes: &mut OverlayedChanges,
+		_initialised_block: &mut Option<BlockId<Block>>,
+		_context: ExecutionContext,
+		_execution_strategy: Option<ExecutionStrategy>,
+		limits: ExecutionLimits,
+		recorder: &Option<Rc<RefCell<ProofRecorder<Block>>>>,
+	) -> ClientResult<Vec<u8>> {
+		// the calls are checked against a proof already, there is nothing to record.
+		if recorder.is_some() {
+			return Err(ClientErrorKind::NotAvailableOnLightClient.into());
+		}
+
+		execution_proof_check::<H, _>(
+			self.root,
+			self.proof.clone(),
+			changes,
+			&self.executor,
+			function,
+			&args,
+			limits,
+		).map_err(Into::into)
+	}
+
+	fn runtime_version_at(&self, at: &BlockId<Block>) -> ClientResult<RuntimeVersion> {
+		self.call_api_at_strong(
+			at,
+			"version",
+			&(),
+			&mut Default::default(),
+			&mut None,
+			ExecutionContext::Other,
+			None,
+			ExecutionLimits::none(),
+			&None,
+		)
+	}
+
+	fn storage_root_at(
+		&self,
+		_at: &BlockId<Block>,
+		_changes: &OverlayedChanges,
+	) -> ClientResult<Block::Hash> {
+		Err(ClientErrorKind::NotAvailableOnLightClient.into())
+	}
+}
+
+impl<E, H, Block, RA> ProvideRuntimeApi for ProofCheckedApi<E, H, Block, RA>
+	where
+		E: CodeExecutor<H>,
+		H: Hasher,
+		H::Out: Ord + HeapSizeOf,
+		Block: BlockT,
+		RA: ConstructRuntimeApi<Block>,
+{
+	type Api = RA;
+
+	fn runtime_api<'a>(&'a self) -> ApiRef<'a, Self::Api> {
+		RA::construct_runtime_api(self)
+	}
+}
+
 #[cfg(test)]
 mod tests {
+	use parking_lot::Mutex;
 	use test_client;
+	use test_client::runtime::{AccountId, Block, Header, RuntimeApi, test_api::TestAPI};
+	use keyring::Keyring;
+	use codec::Encode;
 	use executor::NativeExecutionDispatch;
+	use backend::NewBlockState;
+	use in_mem::Blockchain as InMemoryBlockchain;
+	use primitives::Blake2Hasher;
+	use state_machine::native_when_possible;
 	use super::*;
 
 	#[test]
@@ -184,4 +322,78 @@ mod tests {
 			retry_count: None,
 		}, remote_execution_proof).unwrap();
 	}
+
+	#[test]
+	fn contextual_call_is_executed_by_remote_node() {
+		let header = Header {
+			parent_hash: Default::default(),
+			number: 0,
+			state_root: Default::default(),
+			extrinsics_root: Default::default(),
+			digest: Default::default(),
+		};
+		let hash = header.hash();
+		let blockchain = InMemoryBlockchain::<Block>::new();
+		blockchain.insert(hash, header, None, None, NewBlockState::Final).unwrap();
+		let fetcher = Arc::new(Mutex::new(CallResult { return_data: vec![42], changes: Default::default() }));
+		let executor = RemoteCallExecutor::<_, _, Blake2Hasher>::new(Arc::new(blockchain), fetcher);
+
+		let mut initialised_block = None;
+		let result = executor.contextual_call(
+			&BlockId::Hash(hash),
+			"authorities",
+			&[],
+			&mut OverlayedChanges::default(),
+			&mut initialised_block,
+			|| -> ClientResult<Header> { panic!("light client doesn't initialise blocks") },
+			native_when_possible,
+			ExecutionContext::Other,
+			ExecutionLimits::none(),
+			&None,
+		).unwrap();
+		assert_eq!(result, vec![42]);
+		assert_eq!(initialised_block, None);
+	}
+
+	#[test]
+	fn runtime_api_is_called_on_proved_state() {
+		let remote_client = test_client::new();
+		let remote_block_id = BlockId::Number(0);
+		let remote_state_root = remote_client.state_at(&remote_block_id)
+			.unwrap().storage_root(::std::iter::empty()).0;
+		let alice: AccountId = Keyring::Alice.to_raw_public().into();
+		let proof = remote_client.execution_proof(&remote_block_id, "balance_of", &alice.encode()).unwrap().1;
+
+		let api = ProofCheckedApi::<_, Blake2Hasher, Block, RuntimeApi>::new(
+			test_client::LocalExecutor::new(),
+			remote_state_root,
+			proof,
+		);
+		assert_eq!(api.runtime_api().balance_of(&BlockId::Number(0), &alice).unwrap(), 1000);
+	}
+
+	#[test]
+	fn light_client_calls_are_answered_by_the_full_node() {
+		use consensus::BlockOrigin;
+		use test_client::{BlockBuilderExt, TestClient};
+		use test_client::runtime::Transfer;
+
+		let (full, light) = test_client::new_light();
+		let alice: AccountId = Keyring::Alice.to_raw_public().into();
+		assert_eq!(light.runtime_api().balance_of(&BlockId::Number(0), &alice).unwrap(), 1000);
+
+		let mut builder = full.new_block().unwrap();
+		builder.push_transfer(Transfer {
+			from: alice,
+			to: Keyring::Ferdie.to_raw_public().into(),
+			amount: 42,
+			nonce: 0,
+		}).unwrap();
+		let block = builder.bake().unwrap();
+		full.justify_and_import(BlockOrigin::Own, block.clone()).unwrap();
+		light.justify_and_import(BlockOrigin::NetworkBroadcast, block).unwrap();
+
+		assert_eq!(light.info().unwrap().chain.best_number, 1);
+		assert_eq!(light.runtime_api().balance_of(&BlockId::Number(1), &alice).unwrap(), 958);
+	}
 }
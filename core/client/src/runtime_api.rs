@@ -16,15 +16,18 @@
 
 //! All the functionality required for declaring and implementing runtime apis.
 
-#[doc(hidden)]
 #[cfg(feature = "std")]
 pub use state_machine::OverlayedChanges;
+#[cfg(feature = "std")]
+pub use state_machine::{ExecutionContext, ExecutionStrategy, ExecutionLimits};
+#[cfg(feature = "std")]
+use std::{cell::RefCell, rc::Rc};
 #[doc(hidden)]
 pub use runtime_primitives::{
 	traits::{Block as BlockT, GetNodeBlockType, GetRuntimeBlockType, ApiRef}, generic::BlockId,
 	transaction_validity::TransactionValidity
 };
-pub use runtime_version::{ApiId, RuntimeVersion};
+pub use runtime_version::{ApiId, RuntimeVersion, ApisVec};
 #[doc(hidden)]
 pub use rstd::slice;
 #[cfg(feature = "std")]
@@ -33,19 +36,56 @@ pub use codec::{Encode, Decode};
 #[cfg(feature = "std")]
 use error;
 use rstd::vec::Vec;
-use primitives::{AuthorityId, OpaqueMetadata};
+#[doc(hidden)]
+pub use primitives::AuthorityId;
+use primitives::OpaqueMetadata;
+
 
+/// A type that records all accessed trie nodes and generates a proof out of it.
+#[cfg(feature = "std")]
+pub type ProofRecorder<B> = ::state_machine::ProofRecorder<<B as BlockT>::Hash>;
 
 /// Something that can be constructed to a runtime api.
 #[cfg(feature = "std")]
-pub trait ConstructRuntimeApi<Block: BlockT>: Sized {
+pub trait ConstructRuntimeApi<Block: BlockT> {
 	/// Construct an instance of the runtime api.
-	fn construct_runtime_api<'a, T: CallApiAt<Block>>(call: &'a T) -> ApiRef<'a, Self>;
+	fn construct_runtime_api<'a, T: CallApiAt<Block>>(call: &'a T) -> ApiRef<'a, Self> where Self: Sized;
+}
+
+/// A handle to the storage changes of a `RuntimeApi` at the time `ApiExt::snapshot` was called.
+///
+/// Snapshots form a stack: reverting to or committing a snapshot releases all snapshots that
+/// were taken after it.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Snapshot(usize);
+
+#[cfg(feature = "std")]
+impl Snapshot {
+	#[doc(hidden)]
+	pub fn new(depth: usize) -> Self {
+		Snapshot(depth)
+	}
+
+	#[doc(hidden)]
+	pub fn depth(&self) -> usize {
+		self.0
+	}
+}
+
+/// The storage changes done by the api calls of a `RuntimeApi`, along with the storage root
+/// they lead to.
+#[cfg(feature = "std")]
+pub struct StorageChanges<Block: BlockT> {
+	/// The changes, to be applied on top of the state of the block the calls were done at.
+	pub changes: OverlayedChanges,
+	/// The storage root of the state after applying the changes.
+	pub storage_root: Block::Hash,
 }
 
 /// An extension for the `RuntimeApi`.
 #[cfg(feature = "std")]
-pub trait ApiExt {
+pub trait ApiExt<Block: BlockT> {
 	/// The given closure will be called with api instance. Inside the closure any api call is
 	/// allowed. After doing the api call, the closure is allowed to map the `Result` to a
 	/// different `Result` type. This can be important, as the internal data structure that keeps
@@ -54,14 +94,107 @@ pub trait ApiExt {
 	fn map_api_result<F: FnOnce(&Self) -> result::Result<R, E>, R, E>(
 		&self,
 		map_call: F
-	) -> result::Result<R, E>;
+	) -> result::Result<R, E> where Self: Sized;
+
+	/// Take a snapshot of the storage changes done by the api calls so far.
+	///
+	/// Changes done by later calls can be dropped again with `revert_to`, e.g. to discard an
+	/// extrinsic that failed to apply while keeping the ones applied before it.
+	fn snapshot(&self) -> Snapshot;
+
+	/// Restore the storage changes to the state of the given snapshot. The snapshot and all
+	/// snapshots taken after it are released.
+	///
+	/// Fails if the snapshot was already committed or reverted.
+	fn revert_to(&self, snapshot: Snapshot) -> error::Result<()>;
+
+	/// Keep the storage changes done since the most recent snapshot and release it.
+	///
+	/// Fails if there is no snapshot left to commit.
+	fn commit(&self) -> error::Result<()>;
+
+	/// Take the storage changes done by the api calls so far, leaving none behind.
+	///
+	/// Building a block this way yields the changes of executing it, so that it does not need
+	/// to be executed again on import.
+	fn take_storage_changes(&self) -> OverlayedChanges;
+
+	/// Take the storage changes done by the api calls so far, leaving none behind, together with
+	/// the storage root they lead to when applied on top of the state at `parent`.
+	///
+	/// The changes can be committed to the backend as they are, without executing the calls
+	/// again. Computing the root is costly: use `take_storage_changes` if it isn't needed.
+	fn take_storage_changes_with_root(
+		&self,
+		parent: &BlockId<Block>
+	) -> error::Result<StorageChanges<Block>> where Self: Sized;
+
+	/// Checks if the given api is implemented by the runtime at the given block, in the version
+	/// the client was compiled against.
+	///
+	/// The api is given as the trait object of its client side declaration, e.g.
+	/// `has_api::<GrandpaApi<Block>>(&at)`.
+	fn has_api<A: RuntimeApiInfo + ?Sized>(
+		&self,
+		at: &BlockId<Block>
+	) -> error::Result<bool> where Self: Sized;
+
+	/// Checks if the given api is implemented by the runtime at the given block, in a version
+	/// satisfying the given predicate.
+	///
+	/// Allows to support runtimes implementing an older version of the api, e.g.
+	/// `has_api_with::<GrandpaApi<Block>, _>(&at, |v| v >= 1)`.
+	fn has_api_with<A: RuntimeApiInfo + ?Sized, P: Fn(u32) -> bool>(
+		&self,
+		at: &BlockId<Block>,
+		predicate: P,
+	) -> error::Result<bool> where Self: Sized;
+
+	/// Execute all runtime api calls done inside the given closure with the given
+	/// `ExecutionStrategy`, instead of the strategy the client selects for the `ExecutionContext`
+	/// of the call. Can be nested inside `map_api_result`, e.g. to force wasm execution for the
+	/// guarded calls.
+	fn with_execution_strategy<F: FnOnce(&Self) -> R, R>(
+		&self,
+		strategy: ExecutionStrategy,
+		call: F
+	) -> R where Self: Sized;
+
+	/// Execute all runtime api calls done inside the given closure subject to the given
+	/// `ExecutionLimits`, failing the calls exceeding them, e.g. to keep a call made for an RPC
+	/// request from stalling the node. Calls with limits are always executed in wasm.
+	fn with_execution_limits<F: FnOnce(&Self) -> R, R>(
+		&self,
+		limits: ExecutionLimits,
+		call: F
+	) -> R where Self: Sized;
+
+	/// Start recording all accessed trie nodes for generating proofs.
+	fn record_proof(&mut self);
+
+	/// Extract the recorded proof.
+	///
+	/// This stops the proof recording. Returns `None` if the proof recording was not active.
+	fn extract_proof(&mut self) -> Option<Vec<Vec<u8>>>;
+}
+
+/// Information about a runtime api trait declared with `decl_runtime_apis!`.
+///
+/// The macro implements this trait for the client side declaration of each runtime api.
+pub trait RuntimeApiInfo {
+	/// The identifier of the runtime api.
+	const ID: ApiId;
+	/// The version of the runtime api.
+	const VERSION: u32;
 }
 
 /// Something that can call the runtime api at a given block.
 #[cfg(feature = "std")]
 pub trait CallApiAt<Block: BlockT> {
 	/// Calls the given api function with the given encoded arguments at the given block
-	/// and returns the encoded result.
+	/// and returns the encoded result. If a `recorder` is given, all accessed trie nodes are
+	/// recorded into it. If an `execution_strategy` is given, it overrides the strategy that is
+	/// selected for the `context`. The call fails once it exceeds any of `limits`.
 	fn call_api_at(
 		&self,
 		at: &BlockId<Block>,
@@ -69,6 +202,10 @@ pub trait CallApiAt<Block: BlockT> {
 		args: Vec<u8>,
 		changes: &mut OverlayedChanges,
 		initialised_block: &mut Option<BlockId<Block>>,
+		context: ExecutionContext,
+		execution_strategy: Option<ExecutionStrategy>,
+		limits: ExecutionLimits,
+		recorder: &Option<Rc<RefCell<ProofRecorder<Block>>>>,
 	) -> error::Result<Vec<u8>>;
 
 	/// Call the given api function with strong arguments at the given block
@@ -80,6 +217,10 @@ pub trait CallApiAt<Block: BlockT> {
 		args: &In,
 		changes: &mut OverlayedChanges,
 		initialised_block: &mut Option<BlockId<Block>>,
+		context: ExecutionContext,
+		execution_strategy: Option<ExecutionStrategy>,
+		limits: ExecutionLimits,
+		recorder: &Option<Rc<RefCell<ProofRecorder<Block>>>>,
 	) -> error::Result<Out> where Self: Sized {
 		let raw = self.call_api_at(
 			at,
@@ -87,6 +228,10 @@ pub trait CallApiAt<Block: BlockT> {
 			args.encode(),
 			changes,
 			initialised_block,
+			context,
+			execution_strategy,
+			limits,
+			recorder,
 		)?;
 
 		match Out::decode(&mut &raw[..]) {
@@ -94,20 +239,16 @@ pub trait CallApiAt<Block: BlockT> {
 			None => bail!(error::ErrorKind::CallResultDecode(function)),
 		}
 	}
-}
-
-/// The ApiIds for the various standard runtime APIs.
-pub mod id {
-	use super::ApiId;
-
-	/// ApiId for the BlockBuilder trait.
-	pub const BLOCK_BUILDER: ApiId = *b"blkbuild";
 
-	/// ApiId for the TaggedTransactionQueue trait.
-	pub const TAGGED_TRANSACTION_QUEUE: ApiId = *b"validatx";
+	/// Returns the runtime version at the given block.
+	fn runtime_version_at(&self, at: &BlockId<Block>) -> error::Result<RuntimeVersion>;
 
-	/// ApiId for the Metadata trait.
-	pub const METADATA: ApiId = *b"metadata";
+	/// Returns the storage root of the state at the given block after applying the given changes.
+	fn storage_root_at(
+		&self,
+		at: &BlockId<Block>,
+		changes: &OverlayedChanges,
+	) -> error::Result<Block::Hash>;
 }
 
 decl_runtime_apis! {
@@ -0,0 +1,275 @@
+// Copyright 2018 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! State snapshots.
+//!
+//! A snapshot holds the whole state of a block, so that a node can start from that block
+//! rather than from genesis. It is made of a `SnapshotHeader` followed by `StateChunk`s:
+//!
+//! - the header holds the format version, the genesis hash of the chain, the header of the
+//!   block the state belongs to and the commitment of every chunk;
+//! - a chunk holds key-value pairs sorted by key, either from the top-level storage or from
+//!   a single child storage. Its commitment is the trie root of its pairs, bound to the
+//!   child storage key.
+//!
+//! Every chunk is verified against the snapshot header as it is read. Once all chunks have
+//! been read, the root of the rebuilt state must match the state root of the block header:
+//! a snapshot can thus be trusted as long as its block is known to be finalized.
+
+use std::collections::{BTreeMap, HashMap};
+use codec::{Decode, Encode, Input, Output};
+use hash_db::Hasher;
+use primitives::storage::well_known_keys;
+use runtime_primitives::{StorageMap, ChildrenStorageMap};
+use state_machine::backend::Backend as StateBackend;
+use trie;
+
+use error::{ErrorKind, Result as ClientResult};
+
+/// Version of the snapshot format.
+pub const VERSION: u32 = 1;
+
+/// Default maximum size, in bytes, of the pairs of a chunk.
+pub const DEFAULT_CHUNK_SIZE: usize = 4 * 1024 * 1024;
+
+/// Header of a state snapshot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotHeader<Header, Hash> {
+	/// Version of the snapshot format.
+	pub version: u32,
+	/// Hash of the genesis block of the chain.
+	pub genesis_hash: Hash,
+	/// Header of the block the state belongs to.
+	pub header: Header,
+	/// Commitment of every chunk, in order.
+	pub chunks: Vec<Hash>,
+}
+
+impl<Header: Encode, Hash: Encode> Encode for SnapshotHeader<Header, Hash> {
+	fn encode_to<T: Output>(&self, dest: &mut T) {
+		dest.push(&self.version);
+		dest.push(&self.genesis_hash);
+		dest.push(&self.header);
+		dest.push(&self.chunks);
+	}
+}
+
+impl<Header: Decode, Hash: Decode> Decode for SnapshotHeader<Header, Hash> {
+	fn decode<I: Input>(input: &mut I) -> Option<Self> {
+		Some(SnapshotHeader {
+			version: Decode::decode(input)?,
+			genesis_hash: Decode::decode(input)?,
+			header: Decode::decode(input)?,
+			chunks: Decode::decode(input)?,
+		})
+	}
+}
+
+impl<Header, Hash: PartialEq + ::std::fmt::Debug> SnapshotHeader<Header, Hash> {
+	/// Create the header of a snapshot made of `chunks`.
+	pub fn new<H: Hasher<Out=Hash>>(genesis_hash: Hash, header: Header, chunks: &[StateChunk]) -> Self {
+		SnapshotHeader {
+			version: VERSION,
+			genesis_hash,
+			header,
+			chunks: chunks.iter().map(|chunk| chunk.commitment::<H>()).collect(),
+		}
+	}
+
+	/// Check that the snapshot uses a supported version of the format.
+	pub fn check_version(&self) -> ClientResult<()> {
+		if self.version != VERSION {
+			return Err(ErrorKind::InvalidStateSnapshot(
+				format!("unsupported version {}, expected {}", self.version, VERSION)
+			).into());
+		}
+		Ok(())
+	}
+
+	/// Check that `chunk` is the chunk at `index` of the snapshot.
+	pub fn verify_chunk<H: Hasher<Out=Hash>>(&self, index: usize, chunk: &StateChunk) -> ClientResult<()> {
+		let expected = self.chunks.get(index)
+			.ok_or_else(|| ErrorKind::InvalidStateSnapshot(format!("unexpected chunk #{}", index)))?;
+		let commitment = chunk.commitment::<H>();
+		if commitment != *expected {
+			return Err(ErrorKind::InvalidStateSnapshot(
+				format!("commitment mismatch for chunk #{}: expected {:?}, got {:?}", index, expected, commitment)
+			).into());
+		}
+		Ok(())
+	}
+}
+
+/// Chunk of the state of a snapshot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateChunk {
+	/// Key of the child storage the pairs belong to. `None` for the top-level storage.
+	pub child: Option<Vec<u8>>,
+	/// Key-value pairs, sorted by key.
+	pub pairs: Vec<(Vec<u8>, Vec<u8>)>,
+}
+
+impl Encode for StateChunk {
+	fn encode_to<T: Output>(&self, dest: &mut T) {
+		dest.push(&self.child);
+		dest.push(&self.pairs);
+	}
+}
+
+impl Decode for StateChunk {
+	fn decode<I: Input>(input: &mut I) -> Option<Self> {
+		Some(StateChunk {
+			child: Decode::decode(input)?,
+			pairs: Decode::decode(input)?,
+		})
+	}
+}
+
+impl StateChunk {
+	/// Commitment of the chunk: the trie root of its pairs, hashed along with the child key.
+	pub fn commitment<H: Hasher>(&self) -> H::Out {
+		let root = trie::trie_root::<H, _, _, _>(self.pairs.iter().map(|&(ref k, ref v)| (k, v)));
+		let mut data = self.child.encode();
+		data.extend_from_slice(root.as_ref());
+		H::hash(&data)
+	}
+}
+
+/// Split a storage into chunks of pairs whose size doesn't exceed `chunk_size`, unless a
+/// single pair does.
+fn split_storage(child: Option<Vec<u8>>, storage: BTreeMap<Vec<u8>, Vec<u8>>, chunk_size: usize, chunks: &mut Vec<StateChunk>) {
+	let mut pairs = Vec::new();
+	let mut size = 0;
+	for (key, value) in storage {
+		let pair_size = key.len() + value.len();
+		if !pairs.is_empty() && size + pair_size > chunk_size {
+			chunks.push(StateChunk { child: child.clone(), pairs: ::std::mem::replace(&mut pairs, Vec::new()) });
+			size = 0;
+		}
+		size += pair_size;
+		pairs.push((key, value));
+	}
+	if !pairs.is_empty() {
+		chunks.push(StateChunk { child, pairs });
+	}
+}
+
+/// Split a state into chunks. The top-level storage comes first, then every child storage
+/// sorted by key. Child storage roots aren't part of the chunks: they are recomputed on import.
+pub fn build_chunks<H: Hasher, S: StateBackend<H>>(state: &S, chunk_size: usize) -> ClientResult<Vec<StateChunk>> {
+	let mut top = BTreeMap::new();
+	let mut children = BTreeMap::new();
+	for (key, value) in state.pairs() {
+		if well_known_keys::is_child_storage_key(&key) {
+			let mut child_keys = Vec::new();
+			state.for_keys_in_child_storage(&key, |k| child_keys.push(k.to_vec()));
+			let mut child = BTreeMap::new();
+			for child_key in child_keys {
+				let child_value = state.child_storage(&key, &child_key)
+					.map_err(|e| ErrorKind::Backend(format!("{:?}", e)))?
+					.ok_or_else(|| ErrorKind::Backend("Child storage key without value".into()))?;
+				child.insert(child_key, child_value);
+			}
+			children.insert(key, child);
+		} else {
+			top.insert(key, value);
+		}
+	}
+
+	let mut chunks = Vec::new();
+	split_storage(None, top, chunk_size, &mut chunks);
+	for (key, child) in children {
+		split_storage(Some(key), child, chunk_size, &mut chunks);
+	}
+	Ok(chunks)
+}
+
+/// Add the pairs of a verified chunk to the state being rebuilt.
+pub fn apply_chunk(chunk: StateChunk, top: &mut StorageMap, children: &mut ChildrenStorageMap) -> ClientResult<()> {
+	match chunk.child {
+		Some(child) => {
+			if !well_known_keys::is_child_storage_key(&child) {
+				return Err(ErrorKind::InvalidStateSnapshot("invalid child storage key".into()).into());
+			}
+			children.entry(child).or_insert_with(HashMap::new).extend(chunk.pairs);
+		},
+		None => {
+			if chunk.pairs.iter().any(|&(ref k, _)| well_known_keys::is_child_storage_key(k)) {
+				return Err(ErrorKind::InvalidStateSnapshot("child storage root in top-level chunk".into()).into());
+			}
+			top.extend(chunk.pairs);
+		},
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use primitives::{Blake2Hasher, H256};
+	use state_machine::backend::InMemory;
+
+	fn test_state() -> InMemory<Blake2Hasher> {
+		let mut child = HashMap::new();
+		child.insert(b"ckey".to_vec(), b"cvalue".to_vec());
+		let mut top = HashMap::new();
+		for i in 0u8..10 {
+			top.insert(vec![i; 4], vec![i; 8]);
+		}
+
+		let mut storage = HashMap::new();
+		storage.insert(None, top);
+		storage.insert(Some(b":child_storage:default:test".to_vec()), child);
+		let state = InMemory::from(storage);
+		// child storage roots live in the top-level storage.
+		let (root, _, _) = state.child_storage_root(b":child_storage:default:test", ::std::iter::empty());
+		state.update(vec![(None, b":child_storage:default:test".to_vec(), Some(root))])
+	}
+
+	#[test]
+	fn chunks_are_bounded_and_cover_the_state() {
+		let state = test_state();
+		let chunks = build_chunks(&state, 36).unwrap();
+		// 10 top-level pairs of 12 bytes, 3 per chunk, then the child storage.
+		assert_eq!(chunks.len(), 5);
+		assert!(chunks[..4].iter().all(|chunk| chunk.child.is_none()));
+		assert_eq!(chunks[4].child, Some(b":child_storage:default:test".to_vec()));
+
+		let mut top = StorageMap::new();
+		let mut children = ChildrenStorageMap::new();
+		for chunk in chunks {
+			let decoded = StateChunk::decode(&mut &chunk.encode()[..]).unwrap();
+			apply_chunk(decoded, &mut top, &mut children).unwrap();
+		}
+		assert_eq!(top.len(), 10);
+		assert_eq!(children[&b":child_storage:default:test"[..]][&b"ckey"[..]], b"cvalue".to_vec());
+	}
+
+	#[test]
+	fn chunks_are_verified_against_header() {
+		let chunks = build_chunks(&test_state(), 36).unwrap();
+		let header = SnapshotHeader::new::<Blake2Hasher>(H256::default(), 42u64, &chunks);
+		let header: SnapshotHeader<u64, H256> = Decode::decode(&mut &header.encode()[..]).unwrap();
+		header.check_version().unwrap();
+		header.verify_chunk::<Blake2Hasher>(0, &chunks[0]).unwrap();
+		assert!(header.verify_chunk::<Blake2Hasher>(1, &chunks[0]).is_err());
+		assert!(header.verify_chunk::<Blake2Hasher>(chunks.len(), &chunks[0]).is_err());
+
+		let mut tampered = chunks[4].clone();
+		tampered.child = Some(b":child_storage:default:other".to_vec());
+		assert!(header.verify_chunk::<Blake2Hasher>(4, &tampered).is_err());
+	}
+}
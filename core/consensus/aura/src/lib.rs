@@ -56,17 +56,19 @@ extern crate futures;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
-use codec::Encode;
-use consensus_common::{Authorities, BlockImport, Environment, Proposer};
+use codec::{Encode, Decode};
+use consensus_common::{Authorities, BlockImport, Environment, Proposal, Proposer};
 use client::ChainHead;
+use client::block_builder::api::BlockBuilder as BlockBuilderApi;
+use client::runtime_api::ExecutionContext;
 use consensus_common::{ImportBlock, BlockOrigin};
-use runtime_primitives::{generic, generic::BlockId};
-use runtime_primitives::traits::{Block, Header, Digest, DigestItemFor};
-use network::import_queue::{Verifier, BasicQueue};
+use runtime_primitives::{generic, generic::BlockId, Justification, ConsensusEngineId};
+use runtime_primitives::traits::{Block, Header, Digest, DigestItemFor, ProvideRuntimeApi};
+use network::import_queue::{Verifier, BasicQueue, SharedJustificationImport};
 use primitives::{AuthorityId, ed25519};
 
 use futures::{Stream, Future, IntoFuture, future::{self, Either}};
-use tokio::timer::Interval;
+use tokio::timer::{Interval, Timeout};
 
 pub use consensus_common::SyncOracle;
 
@@ -88,7 +90,10 @@ pub struct Config {
 	/// The local authority keypair. Can be none if this is just an observer.
 	pub local_key: Option<Arc<ed25519::Pair>>,
 	/// The slot duration in seconds.
-	pub slot_duration: u64
+	pub slot_duration: u64,
+	/// Maximum number of consecutive slots in which authoring is skipped because the block
+	/// would be empty. Zero means that empty blocks are always authored.
+	pub max_skipped_empty_slots: u64,
 }
 
 /// Get slot author for given block along with authorities.
@@ -115,11 +120,34 @@ fn duration_now() -> Option<Duration> {
 	}).ok()
 }
 
+/// Whether to skip authoring in a slot, given whether the block would be empty. Updates the
+/// number of consecutive `skipped` slots.
+fn skip_slot(would_be_empty: bool, skipped: &mut u64, max_skipped: u64) -> bool {
+	if would_be_empty && *skipped < max_skipped {
+		*skipped += 1;
+		true
+	} else {
+		*skipped = 0;
+		false
+	}
+}
+
 /// Get the slot for now.
 fn slot_now(slot_duration: u64) -> Option<u64> {
 	duration_now().map(|s| s.as_secs() / slot_duration)
 }
 
+/// The instant at which the given slot ends.
+fn slot_end(slot_num: u64, slot_duration: u64) -> Option<Instant> {
+	duration_now().map(|now| {
+		let end = Duration::from_secs(slot_num.saturating_add(1).saturating_mul(slot_duration));
+		Instant::now() + end.checked_sub(now).unwrap_or_default()
+	})
+}
+
+/// The consensus engine id of Aura, used to tag its digest items.
+pub const AURA_ENGINE_ID: ConsensusEngineId = *b"aura";
+
 /// A digest item which is usable with aura consensus.
 pub trait CompatibleDigestItem: Sized {
 	/// Construct a digest item which is a slot number and a signature on the
@@ -127,19 +155,20 @@ pub trait CompatibleDigestItem: Sized {
 	fn aura_seal(slot_number: u64, signature: ed25519::Signature) -> Self;
 
 	/// If this item is an Aura seal, return the slot number and signature.
-	fn as_aura_seal(&self) -> Option<(u64, &ed25519::Signature)>;
+	fn as_aura_seal(&self) -> Option<(u64, ed25519::Signature)>;
 }
 
 impl<Hash, AuthorityId> CompatibleDigestItem for generic::DigestItem<Hash, AuthorityId> {
 	/// Construct a digest item which is a slot number and a signature on the
 	/// hash.
 	fn aura_seal(slot_number: u64, signature: ed25519::Signature) -> Self {
-		generic::DigestItem::Seal(slot_number, signature)
+		generic::DigestItem::Seal(AURA_ENGINE_ID, (slot_number, signature).encode())
 	}
 	/// If this item is an Aura seal, return the slot number and signature.
-	fn as_aura_seal(&self) -> Option<(u64, &ed25519::Signature)> {
+	fn as_aura_seal(&self) -> Option<(u64, ed25519::Signature)> {
 		match self {
-			generic::DigestItem::Seal(slot, ref sign) => Some((*slot, sign)),
+			generic::DigestItem::Seal(id, ref data) if *id == AURA_ENGINE_ID =>
+				Decode::decode(&mut &data[..]),
 			_ => None
 		}
 	}
@@ -173,7 +202,9 @@ pub fn start_aura<B, C, E, I, SO, Error>(
 
 		let local_keys = config.local_key.map(|pair| (pair.public(), pair));
 		let slot_duration = config.slot_duration;
+		let max_skipped_empty_slots = config.max_skipped_empty_slots;
 		let mut last_authored_slot = 0;
+		let mut skipped_empty_slots = 0;
 		let next_slot_start = duration_now().map(|now| {
 			let remaining_full_secs = slot_duration - (now.as_secs() % slot_duration) - 1;
 			let remaining_nanos = 1_000_000_000 - now.subsec_nanos();
@@ -223,7 +254,18 @@ pub fn start_aura<B, C, E, I, SO, Error>(
 							}
 						};
 
-						proposer.propose().into_future()
+						if skip_slot(proposer.would_be_empty(), &mut skipped_empty_slots, max_skipped_empty_slots) {
+							debug!(target: "aura", "Skipping empty block in slot {}", slot_num);
+							return Either::B(future::ok(()));
+						}
+
+						let deadline = match slot_end(slot_num, slot_duration) {
+							Some(deadline) => deadline,
+							None => return Either::B(future::err(())),
+						};
+
+						// the block is only useful if it can be imported before the slot ends.
+						Timeout::new_at(proposer.propose(deadline).into_future(), deadline)
 					} else {
 						return Either::B(future::ok(()));
 					}
@@ -231,8 +273,8 @@ pub fn start_aura<B, C, E, I, SO, Error>(
 
 				let block_import = block_import.clone();
 				Either::A(proposal_work
-					.map(move |b| {
-						let (header, body) = b.deconstruct();
+					.map(move |proposal: Proposal<B>| {
+						let (header, body) = proposal.block.deconstruct();
 						let pre_hash = header.hash();
 						let parent_hash = header.parent_hash().clone();
 
@@ -249,13 +291,20 @@ pub fn start_aura<B, C, E, I, SO, Error>(
 							body: Some(body),
 							finalized: false,
 							auxiliary: Vec::new(),
+							storage_changes: proposal.storage_changes,
 						};
 
 						if let Err(e) = block_import.import_block(import_block, None) {
 							warn!(target: "aura", "Error with block built on {:?}: {:?}", parent_hash, e);
 						}
 					})
-					.map_err(|e| warn!("Failed to construct block: {:?}", e))
+					.map_err(move |e| if e.is_elapsed() {
+						warn!(target: "aura", "Discarding proposal for slot {}; block production took too long", slot_num)
+					} else if let Some(e) = e.into_inner() {
+						warn!("Failed to construct block: {:?}", e)
+					} else {
+						warn!(target: "aura", "Faulty timer while constructing block for slot {}", slot_num)
+					})
 				)
 			})
 	};
@@ -302,7 +351,7 @@ fn check_header<B: Block>(slot_now: u64, mut header: B::Header, hash: B::Hash, a
 		Some(x) => x,
 		None => return Err(format!("Header {:?} is unsealed", hash)),
 	};
-	let (slot_num, &sig) = match digest_item.as_aura_seal() {
+	let (slot_num, sig) = match digest_item.as_aura_seal() {
 		Some(x) => x,
 		None => return Err(format!("Header {:?} is unsealed", hash)),
 	};
@@ -351,6 +400,71 @@ impl<B: Block> ExtraVerification<B> for NothingExtra {
 		Ok(())
 	}
 }
+
+/// Provider of the inherent data imported blocks are checked against.
+pub trait ProvideImportInherentData<B: Block, I>: Send + Sync {
+	/// The inherent data to check a block built on top of `parent` against.
+	fn inherent_data(&self, parent: &BlockId<B>) -> I;
+}
+
+impl<B: Block, I, F: Fn(&BlockId<B>) -> I + Send + Sync> ProvideImportInherentData<B, I> for F {
+	fn inherent_data(&self, parent: &BlockId<B>) -> I {
+		(self)(parent)
+	}
+}
+
+/// Extra verification that checks the inherents of imported blocks with the runtime.
+///
+/// The inherent data the block is checked against is created by the given `make_inherent_data`
+/// provider. Blocks without a body are not checked.
+pub struct CheckInherents<C, F> {
+	client: Arc<C>,
+	make_inherent_data: F,
+}
+
+impl<C, F> CheckInherents<C, F> {
+	/// Create a new instance.
+	pub fn new(client: Arc<C>, make_inherent_data: F) -> Self {
+		CheckInherents { client, make_inherent_data }
+	}
+}
+
+impl<B: Block, C, F, I> ExtraVerification<B> for CheckInherents<C, F> where
+	C: ProvideRuntimeApi + Send + Sync,
+	C::Api: BlockBuilderApi<B, I>,
+	F: ProvideImportInherentData<B, I>,
+{
+	type Verified = Result<(), String>;
+
+	fn verify(&self, header: &B::Header, body: Option<&[B::Extrinsic]>) -> Self::Verified {
+		let body = match body {
+			Some(body) => body,
+			None => return Ok(()),
+		};
+
+		let parent_hash = *header.parent_hash();
+		let block = B::new(header.clone(), body.to_vec());
+		let inherent_data = self.make_inherent_data.inherent_data(&BlockId::Hash(parent_hash));
+
+		let result = self.client.runtime_api()
+			.check_inherents_with_context(
+				&BlockId::Hash(parent_hash),
+				ExecutionContext::Importing,
+				&block,
+				&inherent_data,
+			)
+			.map_err(|e| format!("Could not check inherents on top of {:?}: {:?}", parent_hash, e))?;
+
+		if result.ok() {
+			Ok(())
+		} else if result.fatal_error() {
+			Err(format!("Block {:?} has invalid inherents: {:?}", header.hash(), result.into_errors()))
+		} else {
+			Err(format!("Inherents of block {:?} are not yet valid: {:?}", header.hash(), result.into_errors()))
+		}
+	}
+}
+
 /// A verifier for Aura blocks.
 pub struct AuraVerifier<C, E> {
 	config: Config,
@@ -367,7 +481,7 @@ impl<B: Block, C, E> Verifier<B> for AuraVerifier<C, E> where
 		&self,
 		origin: BlockOrigin,
 		header: B::Header,
-		_justification: Vec<u8>,
+		_justification: Justification,
 		body: Option<Vec<B::Extrinsic>>
 	) -> Result<(ImportBlock<B>, Option<Vec<AuthorityId>>), String> {
 		let slot_now = slot_now(self.config.slot_duration)
@@ -398,6 +512,7 @@ impl<B: Block, C, E> Verifier<B> for AuraVerifier<C, E> where
 					body,
 					finalized: false,
 					auxiliary: Vec::new(),
+					storage_changes: None,
 				};
 
 				// FIXME: extract authorities - https://github.com/paritytech/substrate/issues/1019
@@ -414,15 +529,21 @@ impl<B: Block, C, E> Verifier<B> for AuraVerifier<C, E> where
 /// The Aura import queue type.
 pub type AuraImportQueue<B, C, E> = BasicQueue<B, AuraVerifier<C, E>>;
 
-/// Start an import queue for the Aura consensus algorithm.
-pub fn import_queue<B, C, E>(config: Config, client: Arc<C>, extra: E) -> AuraImportQueue<B, C, E> where
+/// Start an import queue for the Aura consensus algorithm. Justifications of blocks that were
+/// imported without them are imported with `justification_import`, if any.
+pub fn import_queue<B, C, E>(
+	config: Config,
+	client: Arc<C>,
+	justification_import: Option<SharedJustificationImport<B>>,
+	extra: E,
+) -> AuraImportQueue<B, C, E> where
 	B: Block,
 	C: Authorities<B> + BlockImport<B,Error=client::error::Error> + Send + Sync,
 	DigestItemFor<B>: CompatibleDigestItem,
 	E: ExtraVerification<B>,
 {
 	let verifier = Arc::new(AuraVerifier { config, client: client.clone(), extra, });
-	BasicQueue::new(verifier, client)
+	BasicQueue::new(verifier, client, justification_import)
 }
 
 #[cfg(test)]
@@ -459,10 +580,11 @@ mod tests {
 
 	impl Proposer<TestBlock> for DummyProposer {
 		type Error = Error;
-		type Create = Result<TestBlock, Error>;
+		type Create = Result<Proposal<TestBlock>, Error>;
 
-		fn propose(&self) -> Result<TestBlock, Error> {
-			self.1.new_block().unwrap().bake().map_err(|e| e.into())
+		fn propose(&self, _deadline: Instant) -> Result<Proposal<TestBlock>, Error> {
+			let (block, storage_changes) = self.1.new_block().unwrap().bake_with_changes()?;
+			Ok(Proposal { block, storage_changes: Some(storage_changes) })
 		}
 	}
 
@@ -489,7 +611,7 @@ mod tests {
 		fn make_verifier(&self, client: Arc<PeersClient>, _cfg: &ProtocolConfig)
 			-> Arc<Self::Verifier>
 		{
-			let config = Config { local_key: None, slot_duration: SLOT_DURATION };
+			let config = Config { local_key: None, slot_duration: SLOT_DURATION, max_skipped_empty_slots: 0 };
 			Arc::new(AuraVerifier { client, config, extra: NothingExtra })
 		}
 
@@ -544,7 +666,8 @@ mod tests {
 			let aura = start_aura(
 				Config {
 					local_key: Some(Arc::new(key.clone().into())),
-					slot_duration: SLOT_DURATION
+					slot_duration: SLOT_DURATION,
+					max_skipped_empty_slots: 0,
 				},
 				client.clone(),
 				client,
@@ -571,4 +694,29 @@ mod tests {
 
 		runtime.block_on(wait_for.select(drive_to_completion).map_err(|_| ())).unwrap();
 	}
+
+	#[test]
+	fn empty_slots_are_skipped_up_to_limit() {
+		let mut skipped = 0;
+		assert!(skip_slot(true, &mut skipped, 2));
+		assert!(skip_slot(true, &mut skipped, 2));
+		assert!(!skip_slot(true, &mut skipped, 2));
+		assert!(skip_slot(true, &mut skipped, 2));
+		assert!(!skip_slot(false, &mut skipped, 2));
+		assert_eq!(skipped, 0);
+		assert!(!skip_slot(true, &mut skipped, 0));
+	}
+
+	#[test]
+	fn slot_ends_within_its_duration() {
+		let now = Instant::now();
+		let slot_num = slot_now(SLOT_DURATION).unwrap();
+
+		let end = slot_end(slot_num, SLOT_DURATION).unwrap();
+		assert!(end <= now + Duration::from_secs(SLOT_DURATION + 1));
+
+		// past slots have already ended.
+		let end = slot_end(slot_num - 1, SLOT_DURATION).unwrap();
+		assert!(end <= Instant::now());
+	}
 }
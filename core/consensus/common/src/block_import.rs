@@ -17,15 +17,16 @@
 //! Block import helpers.
 
 use primitives::AuthorityId;
-use runtime_primitives::traits::{Block as BlockT, Header as HeaderT, DigestItemFor};
+use runtime_primitives::traits::{Block as BlockT, Header as HeaderT, DigestItemFor, NumberFor};
 use runtime_primitives::Justification;
+use state_machine::OverlayedChanges;
 use std::borrow::Cow;
 
 /// Block import result.
 #[derive(Debug)]
 pub enum ImportResult {
 	/// Added to the import queue.
-	Queued,
+	Queued(ImportedAux),
 	/// Already in the import queue.
 	AlreadyQueued,
 	/// Already in the blockchain.
@@ -36,6 +37,14 @@ pub enum ImportResult {
 	UnknownParent,
 }
 
+/// Auxiliary data associated with an imported block.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct ImportedAux {
+	/// The block was imported without a justification, but it requires one to be finalized.
+	/// The justification should be fetched from the network.
+	pub needs_justification: bool,
+}
+
 /// Block data origin.
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum BlockOrigin {
@@ -83,6 +92,9 @@ pub struct ImportBlock<Block: BlockT> {
 	/// Contains a list of key-value pairs. If values are `None`, the keys
 	/// will be deleted.
 	pub auxiliary: Vec<(Vec<u8>, Option<Vec<u8>>)>,
+	/// The storage changes of executing the block, if they are known already, e.g. because the
+	/// block was built locally. The block is executed on import otherwise.
+	pub storage_changes: Option<OverlayedChanges>,
 }
 
 impl<Block: BlockT> ImportBlock<Block> {
@@ -138,3 +150,15 @@ pub trait BlockImport<B: BlockT> {
 		new_authorities: Option<Vec<AuthorityId>>
 	) -> Result<ImportResult, Self::Error>;
 }
+
+/// Justification import trait.
+pub trait JustificationImport<B: BlockT> {
+	type Error: ::std::error::Error + Send + 'static;
+	/// Import the justification of an already imported block and finalize the block.
+	fn import_justification(
+		&self,
+		hash: B::Hash,
+		number: NumberFor<B>,
+		justification: Justification,
+	) -> Result<(), Self::Error>;
+}
@@ -26,6 +26,7 @@ extern crate substrate_primitives as primitives;
 extern crate futures;
 extern crate sr_version as runtime_version;
 extern crate sr_primitives as runtime_primitives;
+extern crate substrate_state_machine as state_machine;
 extern crate tokio;
 
 extern crate parity_codec as codec;
@@ -36,6 +37,7 @@ extern crate parity_codec_derive;
 extern crate error_chain;
 
 use std::sync::Arc;
+use std::time::Instant;
 
 use primitives::{ed25519, AuthorityId};
 use runtime_primitives::generic::BlockId;
@@ -51,7 +53,10 @@ pub mod evaluation;
 const MAX_TRANSACTIONS_SIZE: usize = 4 * 1024 * 1024;
 
 pub use self::error::{Error, ErrorKind};
-pub use block_import::{BlockImport, ImportBlock, BlockOrigin, ImportResult};
+pub use block_import::{
+	BlockImport, ImportBlock, BlockOrigin, ImportResult, ImportedAux, JustificationImport,
+};
+pub use state_machine::OverlayedChanges;
 
 /// Trait for getting the authorities at a given block.
 pub trait Authorities<B: Block> {
@@ -73,6 +78,15 @@ pub trait Environment<B: Block> {
 		-> Result<Self::Proposer, Self::Error>;
 }
 
+/// A proposed block, along with the storage changes made by building it.
+pub struct Proposal<B: Block> {
+	/// The proposed block.
+	pub block: B,
+	/// The storage changes of executing the block, if the proposer kept them. Importing the
+	/// block along with them saves executing it once more.
+	pub storage_changes: Option<OverlayedChanges>,
+}
+
 /// Logic for a proposer.
 ///
 /// This will encapsulate creation and evaluation of proposals at a specific
@@ -81,9 +95,17 @@ pub trait Proposer<B: Block> {
 	/// Error type which can occur when proposing or evaluating.
 	type Error: From<Error> + ::std::fmt::Debug + 'static;
 	/// Future that resolves to a committed proposal.
-	type Create: IntoFuture<Item=B,Error=Self::Error>;
-	/// Create a proposal.
-	fn propose(&self) -> Self::Create;
+	type Create: IntoFuture<Item=Proposal<B>,Error=Self::Error>;
+	/// Create a proposal. Building it may happen in the background, so the caller can do other
+	/// work while it completes. The proposal is of no use after `deadline`, and proposers
+	/// should stop including extrinsics once it has passed.
+	fn propose(&self, deadline: Instant) -> Self::Create;
+
+	/// Whether a proposal created now would hold neither extrinsics nor inherent data that
+	/// must be included, such as offline reports. Proposers which can't tell return `false`.
+	fn would_be_empty(&self) -> bool {
+		false
+	}
 }
 
 /// An oracle for when major synchronization work is being undertaken.
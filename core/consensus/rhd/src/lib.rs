@@ -69,7 +69,7 @@ use std::time::{self, Instant, Duration};
 use codec::{Decode, Encode};
 use consensus::offline_tracker::OfflineTracker;
 use consensus::error::{ErrorKind as CommonErrorKind};
-use consensus::{Authorities, BlockImport, Environment, Proposer as BaseProposer};
+use consensus::{Authorities, BlockImport, Environment, Proposal, Proposer as BaseProposer};
 use client::{Client as SubstrateClient, CallExecutor};
 use client::runtime_api::{Core, BlockBuilder as BlockBuilderAPI, OldTxQueue, BlockBuilderError};
 use runtime_primitives::generic::{BlockId, Era, ImportResult, ImportBlock, BlockOrigin};
@@ -285,6 +285,11 @@ impl<B: Block, P: LocalProposer<B>> BftInstance<B, P>
 	}
 }
 
+// rhododendron agrees on blocks only, the storage changes of proposals are dropped.
+fn proposal_block<B: Block>(proposal: Proposal<B>) -> B {
+	proposal.block
+}
+
 impl<B: Block, P: LocalProposer<B>> rhododendron::Context for BftInstance<B, P>
 	where
 		B: Clone + Eq,
@@ -296,7 +301,7 @@ impl<B: Block, P: LocalProposer<B>> rhododendron::Context for BftInstance<B, P>
 	type Signature = LocalizedSignature;
 	type Candidate = B;
 	type RoundTimeout = Box<Future<Item=(),Error=Self::Error>>;
-	type CreateProposal = <P::Create as IntoFuture>::Future;
+	type CreateProposal = future::Map<<P::Create as IntoFuture>::Future, fn(Proposal<B>) -> B>;
 	type EvaluateProposal = <P::Evaluate as IntoFuture>::Future;
 
 	fn local_id(&self) -> AuthorityId {
@@ -304,7 +309,9 @@ impl<B: Block, P: LocalProposer<B>> rhododendron::Context for BftInstance<B, P>
 	}
 
 	fn proposal(&self) -> Self::CreateProposal {
-		self.proposer.propose().into_future()
+		// the proposal is useless once the first round has timed out.
+		let deadline = Instant::now() + self.round_timeout_duration(0);
+		self.proposer.propose(deadline).into_future().map(proposal_block as fn(Proposal<B>) -> B)
 	}
 
 	fn candidate_digest(&self, proposal: &B) -> B::Hash {
@@ -421,7 +428,8 @@ impl<B, P, I, InStream, OutSink> Future for BftFuture<B, P, I, InStream, OutSink
 				body: Some(body),
 				finalized: true,
 				post_digests: Default::default(),
-				auxiliary: Default::default()
+				auxiliary: Default::default(),
+				storage_changes: None,
 			};
 
 			let new_status = match self.import.import_block(import_block, None) {
@@ -1074,11 +1082,11 @@ impl<C, A> BaseProposer<<C as AuthoringApi>::Block> for Proposer<C, A> where
 		Into<<Runtime as SystemT>::Hash> + PartialEq<primitives::H256> + Into<primitives::H256>,
 	error::Error: From<<C as AuthoringApi>::Error>
 {
-	type Create = Result<<C as AuthoringApi>::Block, Error>;
+	type Create = Result<Proposal<<C as AuthoringApi>::Block>, Error>;
 	type Error = Error;
 	type Evaluate = Box<Future<Item=bool, Error=Error>>;
 
-	fn propose(&self) -> Self::Create {
+	fn propose(&self, _deadline: Instant) -> Self::Create {
 		use runtime_primitives::traits::BlakeTwo256;
 
 		const MAX_VOTE_OFFLINE_SECONDS: Duration = Duration::from_secs(60);
@@ -1150,7 +1158,7 @@ impl<C, A> BaseProposer<<C as AuthoringApi>::Block> for Proposer<C, A> where
 			self.parent_number,
 		).is_ok());
 
-		Ok(substrate_block)
+		Ok(Proposal { block: substrate_block, storage_changes: None })
 	}
 
 	fn evaluate(&self, unchecked_proposal: &<C as AuthoringApi>::Block) -> Self::Evaluate {
@@ -1214,7 +1222,8 @@ impl<C, A> BaseProposer<<C as AuthoringApi>::Block> for Proposer<C, A> where
 				.map_err(Error::from) {
 			Ok(()) => Ok(true),
 			Err(err) => match err.kind() {
-				error::ErrorKind::Client(client::error::ErrorKind::Execution(_)) => Ok(false),
+				error::ErrorKind::Client(client::error::ErrorKind::Execution(_)) |
+				error::ErrorKind::Client(client::error::ErrorKind::BadInput(..)) => Ok(false),
 				_ => Err(err)
 			}
 		};
@@ -1419,14 +1428,17 @@ mod tests {
 
 	impl BaseProposer<TestBlock> for DummyProposer {
 		type Error = Error;
-		type Create = Result<TestBlock, Error>;
+		type Create = Result<Proposal<TestBlock>, Error>;
 		type Evaluate = Result<bool, Error>;
 
-		fn propose(&self) -> Result<TestBlock, Error> {
+		fn propose(&self, _deadline: Instant) -> Result<Proposal<TestBlock>, Error> {
 
-			Ok(TestBlock {
-				header: from_block_number(self.0),
-				extrinsics: Default::default()
+			Ok(Proposal {
+				block: TestBlock {
+					header: from_block_number(self.0),
+					extrinsics: Default::default()
+				},
+				storage_changes: None,
 			})
 		}
 
@@ -0,0 +1,134 @@
+// Copyright 2018 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Allocator for the runtime heap.
+//!
+//! Allocation sizes are rounded up to the next power of two, starting at 8 bytes. Every size
+//! class has its own list of freed blocks: an allocation first reuses a freed block of its
+//! class and only moves the bump pointer when there is none. Blocks are never split nor
+//! merged, so both allocating and freeing are O(1).
+
+use std::collections::HashMap;
+use wasm_utils::UserError;
+
+/// Smallest size class is `1 << MIN_ORDER` bytes. This also keeps blocks 8-byte aligned.
+const MIN_ORDER: u32 = 3;
+/// Largest size class is `1 << MAX_ORDER` bytes.
+const MAX_ORDER: u32 = 25;
+/// Maximum size, in bytes, of a single allocation.
+pub const MAX_ALLOCATION: u32 = 1 << MAX_ORDER;
+
+/// Returns the index of the size class of an allocation of `size` bytes.
+fn order_of(size: u32) -> Result<usize, UserError> {
+	if size > MAX_ALLOCATION {
+		return Err(UserError("Requested allocation size is too large"));
+	}
+	let order = size.max(1 << MIN_ORDER).next_power_of_two().trailing_zeros();
+	Ok((order - MIN_ORDER) as usize)
+}
+
+/// Heap allocator which reuses freed blocks.
+pub struct FreeingBumpHeap {
+	/// Offset of the first byte that has never been allocated.
+	bumper: u32,
+	/// Offset right after the last byte of the heap.
+	end: u32,
+	/// Freed blocks, per size class.
+	free_lists: Vec<Vec<u32>>,
+	/// Size class of every live allocation.
+	allocated: HashMap<u32, usize>,
+}
+
+impl FreeingBumpHeap {
+	/// Create an allocator managing the memory in `[start, end)`.
+	pub fn new(start: u32, end: u32) -> Self {
+		FreeingBumpHeap {
+			bumper: start,
+			end,
+			free_lists: vec![Vec::new(); (MAX_ORDER - MIN_ORDER + 1) as usize],
+			allocated: HashMap::new(),
+		}
+	}
+
+	/// Allocate a block of at least `size` bytes and return its offset.
+	pub fn allocate(&mut self, size: u32) -> Result<u32, UserError> {
+		let order = order_of(size)?;
+		let ptr = match self.free_lists[order].pop() {
+			Some(ptr) => ptr,
+			None => {
+				let block_size = 1u32 << (order as u32 + MIN_ORDER);
+				let ptr = self.bumper;
+				let new_bumper = ptr.checked_add(block_size)
+					.filter(|&b| b <= self.end)
+					.ok_or_else(|| UserError("Allocator ran out of space"))?;
+				self.bumper = new_bumper;
+				ptr
+			}
+		};
+		self.allocated.insert(ptr, order);
+		Ok(ptr)
+	}
+
+	/// Free a block previously returned by `allocate`.
+	pub fn deallocate(&mut self, ptr: u32) -> Result<(), UserError> {
+		let order = self.allocated.remove(&ptr)
+			.ok_or_else(|| UserError("Invalid attempt to free a block that isn't allocated"))?;
+		self.free_lists[order].push(ptr);
+		Ok(())
+	}
+
+	/// Number of bytes currently handed out, rounded up to the size classes.
+	pub fn used(&self) -> u32 {
+		self.allocated.values().map(|order| 1u32 << (*order as u32 + MIN_ORDER)).sum()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn should_round_up_to_size_class() {
+		let mut heap = FreeingBumpHeap::new(0, 1024);
+		assert_eq!(heap.allocate(1).unwrap(), 0);
+		assert_eq!(heap.allocate(9).unwrap(), 8);
+		assert_eq!(heap.allocate(16).unwrap(), 24);
+		assert_eq!(heap.used(), 40);
+	}
+
+	#[test]
+	fn should_reuse_freed_blocks() {
+		let mut heap = FreeingBumpHeap::new(0, 64);
+		let first = heap.allocate(32).unwrap();
+		let second = heap.allocate(32).unwrap();
+		assert!(heap.allocate(32).is_err());
+
+		heap.deallocate(first).unwrap();
+		assert_eq!(heap.allocate(20).unwrap(), first);
+		heap.deallocate(second).unwrap();
+		assert_eq!(heap.used(), 32);
+	}
+
+	#[test]
+	fn should_reject_invalid_frees_and_huge_allocations() {
+		let mut heap = FreeingBumpHeap::new(0, u32::max_value());
+		let ptr = heap.allocate(8).unwrap();
+		assert!(heap.deallocate(ptr + 1).is_err());
+		heap.deallocate(ptr).unwrap();
+		assert!(heap.deallocate(ptr).is_err());
+		assert!(heap.allocate(MAX_ALLOCATION + 1).is_err());
+	}
+}
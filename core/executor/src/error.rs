@@ -64,6 +64,12 @@ error_chain! {
 			display("Invalid type returned (should be u64)"),
 		}
 
+		/// The runtime could not decode an argument of the called method.
+		BadInput(method: String, index: u32) {
+			description("bad input data provided to runtime"),
+			display("Bad input data provided to {}: argument {} could not be decoded", method, index),
+		}
+
 		/// Runtime failed.
 		Runtime {
 			description("runtime failure"),
@@ -78,4 +84,11 @@ error_chain! {
 	}
 }
 
-impl state_machine::Error for Error {}
+impl state_machine::Error for Error {
+	fn bad_input(&self) -> Option<(&str, u32)> {
+		match *self.kind() {
+			ErrorKind::BadInput(ref method, index) => Some((method, index)),
+			_ => None,
+		}
+	}
+}
@@ -0,0 +1,180 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Versioned sets of host functions.
+//!
+//! The functions the executor provides to runtimes are grouped into named sets, each with a
+//! version, and a runtime can only import the functions of the sets registered with the
+//! executor. Host functions are added in a new version of a set, and a node can register
+//! whichever version the runtimes it executes were built against.
+//!
+//! Runtimes importing functions the node doesn't provide fail to instantiate, unless missing
+//! host functions are allowed: they are then replaced by stubs which trap when called. This
+//! is meant for executing old runtimes, e.g. for benchmarking, and not for consensus.
+
+use std::cell::RefCell;
+
+use wasmi::{self, FuncInstance, FuncRef, ModuleImportResolver, Signature};
+
+/// Host function indices from this one on are given to the stubs of missing host functions.
+pub(crate) const MISSING_HOST_FUNCTION_INDEX: usize = 1 << 24;
+
+/// The host function charging the fuel used by a metered runtime. It is not part of any set:
+/// only the calls to it injected by the executor may import it.
+pub(crate) const GAS_FUNCTION: &str = "gas";
+
+/// A versioned set of host functions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HostFunctionSet {
+	/// The name of the set.
+	pub name: &'static str,
+	/// The version of the set.
+	pub version: u32,
+	/// The names of the functions in the set.
+	pub functions: &'static [&'static str],
+}
+
+impl HostFunctionSet {
+	/// Whether the set contains the function of the given name.
+	pub fn contains(&self, function: &str) -> bool {
+		self.functions.contains(&function)
+	}
+}
+
+/// Storage, hashing, trie and logging functions.
+pub const IO_V1: HostFunctionSet = HostFunctionSet {
+	name: "io",
+	version: 1,
+	functions: &[
+		"ext_print_utf8",
+		"ext_print_hex",
+		"ext_print_num",
+		"ext_malloc",
+		"ext_free",
+		"ext_set_storage",
+		"ext_set_child_storage",
+		"ext_clear_child_storage",
+		"ext_clear_storage",
+		"ext_exists_storage",
+		"ext_exists_child_storage",
+		"ext_storage_append",
+		"ext_clear_prefix",
+		"ext_clear_prefix_limited",
+		"ext_kill_child_storage",
+		"ext_get_allocated_storage",
+		"ext_get_allocated_child_storage",
+		"ext_get_storage_into",
+		"ext_get_child_storage_into",
+		"ext_storage_root",
+		"ext_child_storage_root",
+		"ext_storage_changes_root",
+		"ext_blake2_256_enumerated_trie_root",
+		"ext_keccak_256_enumerated_trie_root",
+		"ext_chain_id",
+		"ext_twox_128",
+		"ext_twox_256",
+		"ext_blake2_256",
+		"ext_keccak_256",
+		"ext_ed25519_verify",
+	],
+};
+
+/// Access to the keystore of the node.
+pub const KEYSTORE_V1: HostFunctionSet = HostFunctionSet {
+	name: "keystore",
+	version: 1,
+	functions: &[
+		"ext_keystore_public_keys",
+		"ext_keystore_sign",
+	],
+};
+
+/// Execution of sandboxed wasm modules.
+pub const SANDBOX_V1: HostFunctionSet = HostFunctionSet {
+	name: "sandbox",
+	version: 1,
+	functions: &[
+		"ext_sandbox_instantiate",
+		"ext_sandbox_instance_teardown",
+		"ext_sandbox_invoke",
+		"ext_sandbox_memory_new",
+		"ext_sandbox_memory_get",
+		"ext_sandbox_memory_set",
+		"ext_sandbox_memory_teardown",
+	],
+};
+
+/// The host function sets registered with an executor by default: the latest version of
+/// every set.
+pub fn default_host_functions() -> Vec<HostFunctionSet> {
+	vec![IO_V1, KEYSTORE_V1, SANDBOX_V1]
+}
+
+/// Resolves the imports of a module to the functions of the registered sets.
+pub(crate) struct HostFunctionResolver<'a> {
+	host_functions: &'a [HostFunctionSet],
+	allow_missing: bool,
+	metered: bool,
+	resolve: fn(&str, &Signature) -> Result<FuncRef, wasmi::Error>,
+	missing: RefCell<Vec<String>>,
+}
+
+impl<'a> HostFunctionResolver<'a> {
+	/// Create a resolver for the given sets, resolving the functions they contain with
+	/// `resolve`. `GAS_FUNCTION` is only resolved for `metered` modules.
+	pub(crate) fn new(
+		host_functions: &'a [HostFunctionSet],
+		allow_missing: bool,
+		metered: bool,
+		resolve: fn(&str, &Signature) -> Result<FuncRef, wasmi::Error>,
+	) -> Self {
+		HostFunctionResolver {
+			host_functions,
+			allow_missing,
+			metered,
+			resolve,
+			missing: RefCell::new(Vec::new()),
+		}
+	}
+
+	/// The names of the imported functions that were replaced by stubs, by index from
+	/// `MISSING_HOST_FUNCTION_INDEX` on.
+	pub(crate) fn into_missing(self) -> Vec<String> {
+		self.missing.into_inner()
+	}
+}
+
+impl<'a> ModuleImportResolver for HostFunctionResolver<'a> {
+	fn resolve_func(&self, name: &str, signature: &Signature) -> Result<FuncRef, wasmi::Error> {
+		let provided = self.host_functions.iter().any(|set| set.contains(name))
+			|| (self.metered && name == GAS_FUNCTION);
+		let resolved = if provided {
+			(self.resolve)(name, signature)
+		} else {
+			Err(wasmi::Error::Instantiation(format!("Export {} not found", name)))
+		};
+
+		match resolved {
+			Err(e) if self.allow_missing => {
+				debug!(target: "wasm-executor", "Replacing missing host function {} by a stub: {}", name, e);
+				let mut missing = self.missing.borrow_mut();
+				missing.push(name.to_owned());
+				Ok(FuncInstance::alloc_host(signature.clone(), MISSING_HOST_FUNCTION_INDEX + missing.len() - 1))
+			}
+			resolved => resolved,
+		}
+	}
+}
@@ -38,6 +38,8 @@ extern crate sr_version as runtime_version;
 extern crate substrate_trie as trie;
 
 extern crate wasmi;
+extern crate parity_wasm;
+extern crate pwasm_utils;
 extern crate byteorder;
 extern crate parking_lot;
 
@@ -62,15 +64,21 @@ extern crate hex_literal;
 
 #[macro_use]
 mod wasm_utils;
+mod host_functions;
 mod wasm_executor;
 #[macro_use]
 mod native_executor;
 mod sandbox;
+mod allocator;
 
 pub mod error;
 pub use wasm_executor::WasmExecutor;
-pub use native_executor::{with_native_environment, NativeExecutor, NativeExecutionDispatch};
-pub use state_machine::Externalities;
+pub use host_functions::{HostFunctionSet, IO_V1, KEYSTORE_V1, SANDBOX_V1, default_host_functions};
+pub use native_executor::{
+	with_native_environment, read_embedded_version, NativeExecutor, NativeExecutionDispatch,
+	NativeDispatch,
+};
+pub use state_machine::{Externalities, ExecutionLimits};
 pub use runtime_version::{RuntimeVersion, NativeVersion};
 pub use codec::Codec;
 use primitives::Blake2Hasher;
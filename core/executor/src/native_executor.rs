@@ -15,11 +15,13 @@
 // along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
 
 use error::{Error, ErrorKind, Result};
-use state_machine::{CodeExecutor, Externalities};
+use state_machine::{CodeExecutor, Externalities, ExecutionLimits};
 use wasm_executor::WasmExecutor;
 use wasmi::Module as WasmModule;
-use runtime_version::{NativeVersion, RuntimeVersion};
+use runtime_version::{NativeVersion, RuntimeVersion, RUNTIME_VERSION_SECTION, RUNTIME_APIS_SECTION};
+use parity_wasm::elements::{Module as RawModule, Section};
 use std::collections::HashMap;
+use std::fmt;
 use codec::Decode;
 use primitives::hashing::blake2_256;
 use parking_lot::{Mutex, MutexGuard};
@@ -48,9 +50,28 @@ fn gen_cache_key(code: &[u8]) -> [u8; 32] {
 	blake2_256(code)
 }
 
+/// Read the runtime version from the custom sections of the given wasm blob, without executing it.
+///
+/// Returns `None` if the code is not a valid wasm blob or does not contain the runtime version.
+pub fn read_embedded_version(code: &[u8]) -> Option<RuntimeVersion> {
+	let module: RawModule = ::parity_wasm::deserialize_buffer(code).ok()?;
+	let custom_section = |name: &str| module.sections().iter().filter_map(|section| match section {
+		Section::Custom(ref custom) if custom.name() == name => Some(custom.payload()),
+		_ => None,
+	}).next();
+
+	let version = custom_section(RUNTIME_VERSION_SECTION)?;
+	// A runtime without any api does not have the section.
+	let apis = custom_section(RUNTIME_APIS_SECTION).unwrap_or(&[]);
+
+	RuntimeVersion::decode_from_custom_sections(version, apis)
+}
+
 /// fetch a runtime version from the cache or if there is no cached version yet, create
 /// the runtime version entry for `code`, determines whether `Compatibility::IsCompatible`
-/// can be used by comparing returned RuntimeVersion to `ref_version`
+/// can be used by comparing returned RuntimeVersion to `ref_version`.
+/// The runtime version is read from the custom sections of `code`, only if it is not
+/// embedded, the `version` function of the runtime is called.
 fn fetch_cached_runtime_version<'a, E: Externalities<Blake2Hasher>>(
 	wasm_executor: &WasmExecutor,
 	cache: &'a mut MutexGuard<CacheType>,
@@ -61,9 +82,11 @@ fn fetch_cached_runtime_version<'a, E: Externalities<Blake2Hasher>>(
 	let maybe_runtime_preproc = cache.entry(gen_cache_key(code))
 		.or_insert_with(|| match WasmModule::from_buffer(code) {
 			Ok(module) => {
-				let version = wasm_executor.call_in_wasm_module(ext, heap_pages, &module, "version", &[])
-					.ok()
-					.and_then(|v| RuntimeVersion::decode(&mut v.as_slice()));
+				let version = read_embedded_version(code).or_else(||
+					wasm_executor.call_in_wasm_module(ext, heap_pages, &module, "version", &[])
+						.ok()
+						.and_then(|v| RuntimeVersion::decode(&mut v.as_slice()))
+				);
 				RuntimePreproc::ValidCode(module, version)
 			}
 			Err(e) => {
@@ -96,26 +119,34 @@ where F: ::std::panic::UnwindSafe + FnOnce() -> U
 	::runtime_io::with_externalities(ext, move || safe_call(f))
 }
 
+/// Dispatch of a call to a native runtime, as `NativeExecutionDispatch::dispatch`.
+pub type NativeDispatch = fn(&mut Externalities<Blake2Hasher>, &str, &[u8]) -> Result<Vec<u8>>;
+
 /// Delegate for dispatching a CodeExecutor call to native code.
 pub trait NativeExecutionDispatch: Send + Sync {
 	/// Get the wasm code that the native dispatch will be equivalent to.
 	fn native_equivalent() -> &'static [u8];
 
-	/// Dispatch a method and input data to be executed natively. Returns `Some` result or `None`
-	/// if the `method` is unknown. Panics if there's an unrecoverable error.
+	/// Dispatch a method and input data to be executed natively. Fails with `MethodNotFound` if the
+	/// `method` is unknown and with `BadInput` if the input data could not be decoded.
 	// fn dispatch<H: hash_db::Hasher>(ext: &mut Externalities<H>, method: &str, data: &[u8]) -> Result<Vec<u8>>;
 	fn dispatch(ext: &mut Externalities<Blake2Hasher>, method: &str, data: &[u8]) -> Result<Vec<u8>>;
 
 	/// Provide native runtime version.
 	fn native_version() -> NativeVersion;
 
+	/// Native runtimes of earlier versions compiled in along with this one, tried in order when
+	/// this one can't execute the on-chain runtime, e.g. to execute historical blocks natively.
+	fn earlier_versions() -> Vec<(NativeVersion, NativeDispatch)> {
+		Vec::new()
+	}
+
 	/// Construct corresponding `NativeExecutor`
 	fn new() -> NativeExecutor<Self> where Self: Sized;
 }
 
 /// A generic `CodeExecutor` implementation that uses a delegate to determine wasm code equivalence
 /// and dispatch to native code when possible, falling back on `WasmExecutor` when not.
-#[derive(Debug)]
 pub struct NativeExecutor<D: NativeExecutionDispatch> {
 	/// Dummy field to avoid the compiler complaining about us not using `D`.
 	_dummy: ::std::marker::PhantomData<D>,
@@ -123,17 +154,47 @@ pub struct NativeExecutor<D: NativeExecutionDispatch> {
 	fallback: WasmExecutor,
 	/// Native runtime version info.
 	native_version: NativeVersion,
+	/// Native runtimes of earlier versions.
+	earlier_versions: Vec<(NativeVersion, NativeDispatch)>,
 }
 
 impl<D: NativeExecutionDispatch> NativeExecutor<D> {
 	/// Create new instance.
 	pub fn new() -> Self {
+		Self::with_fallback(WasmExecutor::new())
+	}
+
+	/// Create new instance, executing wasm with the given executor, e.g. one providing other
+	/// host functions.
+	pub fn with_fallback(fallback: WasmExecutor) -> Self {
 		NativeExecutor {
 			_dummy: Default::default(),
-			fallback: WasmExecutor::new(),
+			fallback,
 			native_version: D::native_version(),
+			earlier_versions: D::earlier_versions(),
 		}
 	}
+
+	/// The dispatch of the native runtime able to execute the given on-chain runtime, along
+	/// with its version.
+	fn native_for(&self, onchain_version: &RuntimeVersion) -> Option<(&RuntimeVersion, NativeDispatch)> {
+		if onchain_version.can_call_with(&self.native_version.runtime_version) {
+			return Some((&self.native_version.runtime_version, D::dispatch as NativeDispatch));
+		}
+		self.earlier_versions.iter()
+			.find(|(version, _)| onchain_version.can_call_with(&version.runtime_version))
+			.map(|(version, dispatch)| (&version.runtime_version, *dispatch))
+	}
+}
+
+impl<D: NativeExecutionDispatch> fmt::Debug for NativeExecutor<D> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.debug_struct("NativeExecutor")
+			.field("fallback", &self.fallback)
+			.field("native_version", &self.native_version)
+			.field("earlier_versions", &self.earlier_versions.iter().map(|(version, _)| version).collect::<Vec<_>>())
+			.finish()
+	}
 }
 
 impl<D: NativeExecutionDispatch> Clone for NativeExecutor<D> {
@@ -142,6 +203,7 @@ impl<D: NativeExecutionDispatch> Clone for NativeExecutor<D> {
 			_dummy: Default::default(),
 			fallback: self.fallback.clone(),
 			native_version: D::native_version(),
+			earlier_versions: D::earlier_versions(),
 		}
 	}
 }
@@ -164,7 +226,7 @@ impl<D: NativeExecutionDispatch> RuntimeInfo for NativeExecutor<D> {
 impl<D: NativeExecutionDispatch> CodeExecutor<Blake2Hasher> for NativeExecutor<D> {
 	type Error = Error;
 
-	fn call<E: Externalities<Blake2Hasher>>(
+	fn call_with_limits<E: Externalities<Blake2Hasher>>(
 		&self,
 		ext: &mut E,
 		heap_pages: usize,
@@ -172,36 +234,51 @@ impl<D: NativeExecutionDispatch> CodeExecutor<Blake2Hasher> for NativeExecutor<D
 		method: &str,
 		data: &[u8],
 		use_native: bool,
+		limits: ExecutionLimits,
 	) -> (Result<Vec<u8>>, bool) {
+		if !limits.is_none() {
+			// native code can't be limited and the cached module isn't metered.
+			return (self.fallback.call_with_limits(ext, heap_pages, code, method, data, limits), false);
+		}
+
 		let mut c = RUNTIMES_CACHE.lock();
 		let (module, onchain_version) = match fetch_cached_runtime_version(&self.fallback, &mut c, ext, heap_pages, code) {
 			Ok((module, onchain_version)) => (module, onchain_version),
 			Err(_) => return (Err(ErrorKind::InvalidCode(code.into()).into()), false),
 		};
-		match (use_native, onchain_version.as_ref().map_or(false, |v| v.can_call_with(&self.native_version.runtime_version))) {
-			(_, false) => {
+		match (use_native, onchain_version.as_ref().and_then(|v| self.native_for(v))) {
+			(_, None) => {
 				trace!(target: "executor", "Request for native execution failed (native: {}, chain: {})", self.native_version.runtime_version, onchain_version.as_ref().map_or_else(||"<None>".into(), |v| format!("{}", v)));
 				(self.fallback.call_in_wasm_module(ext, heap_pages, module, method, data), false)
 			}
 			(false, _) => {
 				(self.fallback.call_in_wasm_module(ext, heap_pages, module, method, data), false)
 			}
-			_ => {
-				trace!(target: "executor", "Request for native execution succeeded (native: {}, chain: {})", self.native_version.runtime_version, onchain_version.as_ref().map_or_else(||"<None>".into(), |v| format!("{}", v)));
-				(D::dispatch(ext, method, data), true)
+			(true, Some((native_version, dispatch))) => {
+				trace!(target: "executor", "Request for native execution succeeded (native: {}, chain: {})", native_version, onchain_version.as_ref().map_or_else(||"<None>".into(), |v| format!("{}", v)));
+				(dispatch(ext, method, data), true)
 			}
 		}
 	}
 }
 
+/// Declare a unit struct implementing `NativeExecutionDispatch` for the given native runtime:
+/// its `dispatch` and `native_version` functions and its wasm code.
+///
+/// Executors of native runtimes of earlier versions may be listed last, e.g.
+/// `native_executor_instance!(pub Executor, dispatch, native_version, CODE, [v1::Executor])`,
+/// for blocks of those versions to still be executed natively.
 #[macro_export]
 macro_rules! native_executor_instance {
 	( $pub:vis $name:ident, $dispatcher:path, $version:path, $code:expr) => {
+		native_executor_instance!($pub $name, $dispatcher, $version, $code, []);
+	};
+	( $pub:vis $name:ident, $dispatcher:path, $version:path, $code:expr, [ $( $earlier:path ),* ]) => {
 		/// A unit struct which implements `NativeExecutionDispatch` feeding in the hard-coded runtime.
 		$pub struct $name;
-		native_executor_instance!(IMPL $name, $dispatcher, $version, $code);
+		native_executor_instance!(IMPL $name, $dispatcher, $version, $code, [ $( $earlier ),* ]);
 	};
-	(IMPL $name:ident, $dispatcher:path, $version:path, $code:expr) => {
+	(IMPL $name:ident, $dispatcher:path, $version:path, $code:expr, [ $( $earlier:path ),* ]) => {
 		// TODO: this is not so great – I think I should go back to have dispatch take a type param and modify this macro to accept a type param and then pass it in from the test-client instead
 		use primitives::Blake2Hasher as _Blake2Hasher;
 		impl $crate::NativeExecutionDispatch for $name {
@@ -211,17 +288,131 @@ macro_rules! native_executor_instance {
 				$code
 			}
 			fn dispatch(ext: &mut $crate::Externalities<_Blake2Hasher>, method: &str, data: &[u8]) -> $crate::error::Result<Vec<u8>> {
-				$crate::with_native_environment(ext, move || $dispatcher(method, data))?
-					.ok_or_else(|| $crate::error::ErrorKind::MethodNotFound(method.to_owned()).into())
+				match $crate::with_native_environment(ext, move || $dispatcher(method, data))? {
+					Some(Ok(output)) => Ok(output),
+					Some(Err(index)) => Err($crate::error::ErrorKind::BadInput(method.to_owned(), index).into()),
+					None => Err($crate::error::ErrorKind::MethodNotFound(method.to_owned()).into()),
+				}
 			}
 
 			fn native_version() -> $crate::NativeVersion {
 				$version()
 			}
 
+			fn earlier_versions() -> Vec<($crate::NativeVersion, $crate::NativeDispatch)> {
+				vec![ $( (
+					<$earlier as $crate::NativeExecutionDispatch>::native_version(),
+					<$earlier as $crate::NativeExecutionDispatch>::dispatch as $crate::NativeDispatch,
+				) ),* ]
+			}
+
 			fn new() -> $crate::NativeExecutor<$name> {
 				$crate::NativeExecutor::new()
 			}
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use codec::Encode;
+	use state_machine::TestExternalities;
+
+	fn native_version(spec_version: u32) -> NativeVersion {
+		NativeVersion {
+			runtime_version: RuntimeVersion {
+				spec_name: "test".into(),
+				impl_name: "parity-test".into(),
+				authoring_version: 1,
+				spec_version,
+				impl_version: 0,
+				apis: Default::default(),
+			},
+			can_author_with: Default::default(),
+		}
+	}
+
+	mod v1 {
+		pub fn dispatch(_: &str, _: &[u8]) -> Option<::std::result::Result<Vec<u8>, u32>> {
+			Some(Ok(b"v1".to_vec()))
+		}
+
+		pub fn native_version() -> ::runtime_version::NativeVersion {
+			super::native_version(1)
+		}
+
+		native_executor_instance!(pub Executor, dispatch, native_version, &[]);
+	}
+
+	mod v2 {
+		pub fn dispatch(_: &str, _: &[u8]) -> Option<::std::result::Result<Vec<u8>, u32>> {
+			Some(Ok(b"v2".to_vec()))
+		}
+
+		pub fn native_version() -> ::runtime_version::NativeVersion {
+			super::native_version(2)
+		}
+
+		native_executor_instance!(pub Executor, dispatch, native_version, &[], [super::v1::Executor]);
+	}
+
+	fn custom_section(name: &str, payload: &[u8]) -> Vec<u8> {
+		let mut section = vec![name.len() as u8];
+		section.extend(name.as_bytes());
+		section.extend(payload);
+
+		let mut res = vec![0, section.len() as u8];
+		res.extend(section);
+		res
+	}
+
+	#[test]
+	fn embedded_version_is_read_from_custom_sections() {
+		let mut code = vec![0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+		assert!(read_embedded_version(&code).is_none());
+
+		let version = (String::from("test"), String::from("parity-test"), 1u32, 2u32, 3u32).encode();
+		let apis = ([1u8; 8], 5u32).encode();
+		code.extend(custom_section(RUNTIME_VERSION_SECTION, &version));
+		code.extend(custom_section(RUNTIME_APIS_SECTION, &apis));
+
+		let version = read_embedded_version(&code).unwrap();
+		assert_eq!(version.spec_name, "test");
+		assert_eq!(version.impl_name, "parity-test");
+		assert_eq!(version.authoring_version, 1);
+		assert_eq!(version.spec_version, 2);
+		assert_eq!(version.impl_version, 3);
+		assert!(version.has_api([1u8; 8], 5));
+	}
+
+	/// Wasm code without any function, embedding a runtime version.
+	fn code_with_version(spec_version: u32) -> Vec<u8> {
+		let mut code = vec![0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+		let version = (String::from("test"), String::from("parity-test"), 1u32, spec_version, 0u32).encode();
+		code.extend(custom_section(RUNTIME_VERSION_SECTION, &version));
+		code
+	}
+
+	#[test]
+	fn native_runtime_of_the_onchain_version_is_selected() {
+		let executor = <v2::Executor as NativeExecutionDispatch>::new();
+		let mut ext = TestExternalities::<Blake2Hasher>::default();
+
+		for &(spec_version, expected) in &[(2, b"v2"), (1, b"v1")] {
+			let (result, native) = executor.call(&mut ext, 8, &code_with_version(spec_version), "test", &[], true);
+			assert_eq!(result.unwrap(), expected.to_vec());
+			assert!(native);
+		}
+
+		// no native runtime of that version, so the wasm code is called, which has no such method.
+		let (result, native) = executor.call(&mut ext, 8, &code_with_version(3), "test", &[], true);
+		assert!(result.is_err());
+		assert!(!native);
+
+		// native execution not requested.
+		let (result, native) = executor.call(&mut ext, 8, &code_with_version(1), "test", &[], false);
+		assert!(result.is_err());
+		assert!(!native);
+	}
+}
@@ -17,51 +17,42 @@
 //! Rust implementation of Substrate contracts.
 
 use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::time::Instant;
 
 use wasmi::{
 	Module, ModuleInstance, MemoryInstance, MemoryRef, TableRef, ImportsBuilder
 };
 use wasmi::RuntimeValue::{I32, I64};
 use wasmi::memory_units::{Pages, Bytes};
-use state_machine::Externalities;
+use state_machine::{Externalities, Capabilities, CodeExecutor, ExecutionLimits};
 use error::{Error, ErrorKind, Result};
 use wasm_utils::UserError;
-use primitives::{blake2_256, twox_128, twox_256, ed25519};
+use host_functions::{
+	HostFunctionSet, HostFunctionResolver, MISSING_HOST_FUNCTION_INDEX, default_host_functions,
+};
+use primitives::{blake2_256, keccak_256, twox_128, twox_256, ed25519};
 use primitives::hexdisplay::HexDisplay;
 use primitives::sandbox as sandbox_primitives;
-use primitives::{H256, Blake2Hasher};
+use primitives::{H256, Blake2Hasher, KeccakHasher, Hasher};
 use trie::ordered_trie_root;
 use sandbox;
+use allocator::FreeingBumpHeap;
 
 
-struct Heap {
-	end: u32,
-}
-
-impl Heap {
-	/// Construct new `Heap` struct with a given number of pages.
-	///
-	/// Returns `Err` if the heap couldn't allocate required
-	/// number of pages.
-	///
-	/// This could mean that wasm binary specifies memory
-	/// limit and we are trying to allocate beyond that limit.
-	fn new(memory: &MemoryRef, pages: usize) -> Result<Self> {
-		let prev_page_count = memory.initial();
-		memory.grow(Pages(pages)).map_err(|_| Error::from(ErrorKind::Runtime))?;
-		Ok(Heap {
-			end: Bytes::from(prev_page_count).0 as u32,
-		})
-	}
-
-	fn allocate(&mut self, size: u32) -> u32 {
-		let r = self.end;
-		self.end += size;
-		r
-	}
-
-	fn deallocate(&mut self, _offset: u32) {
-	}
+/// Construct the heap allocator with a given number of pages.
+///
+/// Returns `Err` if the heap couldn't allocate required
+/// number of pages.
+///
+/// This could mean that wasm binary specifies memory
+/// limit and we are trying to allocate beyond that limit.
+fn new_heap(memory: &MemoryRef, pages: usize) -> Result<FreeingBumpHeap> {
+	let prev_page_count = memory.initial();
+	memory.grow(Pages(pages)).map_err(|_| Error::from(ErrorKind::Runtime))?;
+	let start = Bytes::from(prev_page_count).0;
+	let end = Bytes::from(memory.current_size()).0;
+	Ok(FreeingBumpHeap::new(start as u32, end.min(u32::max_value() as usize) as u32))
 }
 
 #[cfg(feature="wasm-extern-trace")]
@@ -73,39 +64,110 @@ macro_rules! debug_trace {
 	( $( $x:tt )* ) => ()
 }
 
-struct FunctionExecutor<'e, E: Externalities<Blake2Hasher> + 'e> {
+/// Whether a call with the given limits must be metered: wasmi can't interrupt the execution of
+/// a runtime, so the time and fuel limits can only be checked by calls into the host.
+fn is_metered(limits: &ExecutionLimits) -> bool {
+	limits.max_duration.is_some() || limits.max_fuel.is_some()
+}
+
+/// Compile `code`, charging the fuel used by each block of instructions with a call to the
+/// `GAS_FUNCTION` host function at the start of the block.
+fn compile_metered(code: &[u8]) -> Result<Module> {
+	let module = ::parity_wasm::deserialize_buffer(code)
+		.map_err(|_| Error::from(ErrorKind::InvalidCode(code.to_vec())))?;
+	let module = ::pwasm_utils::inject_gas_counter(module, &::pwasm_utils::rules::Set::default())
+		.map_err(|_| Error::from(ErrorKind::InvalidCode(code.to_vec())))?;
+	Module::from_parity_wasm_module(module).map_err(Into::into)
+}
+
+struct FunctionExecutor<'e, E: Externalities<H> + 'e, H: Hasher<Out=H256>> {
 	sandbox_store: sandbox::Store,
-	heap: Heap,
+	heap: FreeingBumpHeap,
 	memory: MemoryRef,
 	table: Option<TableRef>,
 	ext: &'e mut E,
 	hash_lookup: HashMap<Vec<u8>, Vec<u8>>,
+	missing_host_functions: Vec<String>,
+	deadline: Option<Instant>,
+	fuel: Option<u64>,
+	max_memory: Option<Pages>,
+	_hasher: PhantomData<H>,
 }
 
-impl<'e, E: Externalities<Blake2Hasher>> FunctionExecutor<'e, E> {
-	fn new(m: MemoryRef, heap_pages: usize, t: Option<TableRef>, e: &'e mut E) -> Result<Self> {
+impl<'e, E: Externalities<H>, H: Hasher<Out=H256>> FunctionExecutor<'e, E, H> {
+	fn new(
+		m: MemoryRef,
+		heap_pages: usize,
+		t: Option<TableRef>,
+		e: &'e mut E,
+		missing_host_functions: Vec<String>,
+		limits: ExecutionLimits,
+	) -> Result<Self> {
+		let heap = new_heap(&m, heap_pages)?;
+		let max_memory = limits.max_memory_growth.map(|growth| m.current_size() + Pages(growth as usize));
 		Ok(FunctionExecutor {
 			sandbox_store: sandbox::Store::new(),
-			heap: Heap::new(&m, heap_pages)?,
+			heap,
 			memory: m,
 			table: t,
 			ext: e,
 			hash_lookup: HashMap::new(),
+			missing_host_functions,
+			deadline: limits.max_duration.map(|duration| Instant::now() + duration),
+			fuel: limits.max_fuel,
+			max_memory,
+			_hasher: PhantomData,
 		})
 	}
+
+	/// Trap if the call exceeded any of its limits.
+	fn check_limits(&self) -> ::std::result::Result<(), ::wasmi::Trap> {
+		if self.deadline.map_or(false, |deadline| Instant::now() >= deadline) {
+			warn!(target: "wasm-executor", "Runtime call exceeded its time limit");
+			return Err(UserError("Runtime call exceeded its time limit").into());
+		}
+		if self.max_memory.map_or(false, |max_memory| self.memory.current_size() > max_memory) {
+			warn!(target: "wasm-executor", "Runtime call exceeded its memory limit");
+			return Err(UserError("Runtime call exceeded its memory limit").into());
+		}
+		Ok(())
+	}
+
+	/// Use `amount` of the fuel left to the call, trapping if there isn't enough.
+	fn charge_fuel(&mut self, amount: u32) -> ::std::result::Result<(), ::wasmi::Trap> {
+		if let Some(ref mut fuel) = self.fuel {
+			match fuel.checked_sub(amount as u64) {
+				Some(left) => *fuel = left,
+				None => {
+					warn!(target: "wasm-executor", "Runtime call ran out of fuel");
+					return Err(UserError("Runtime call ran out of fuel").into());
+				}
+			}
+		}
+		Ok(())
+	}
+
+	/// The trap of calling the stub of a host function missing from the node.
+	fn missing_host_function(&self, index: usize) -> ::wasmi::Trap {
+		let name = index.checked_sub(MISSING_HOST_FUNCTION_INDEX)
+			.and_then(|index| self.missing_host_functions.get(index))
+			.map_or("<unknown>", |name| &name[..]);
+		warn!(target: "wasm-executor", "Runtime called host function {} missing from this node", name);
+		UserError("Runtime called a host function missing from this node").into()
+	}
 }
 
-impl<'e, E: Externalities<Blake2Hasher>> sandbox::SandboxCapabilities for FunctionExecutor<'e, E> {
+impl<'e, E: Externalities<H>, H: Hasher<Out=H256>> sandbox::SandboxCapabilities for FunctionExecutor<'e, E, H> {
 	fn store(&self) -> &sandbox::Store {
 		&self.sandbox_store
 	}
 	fn store_mut(&mut self) -> &mut sandbox::Store {
 		&mut self.sandbox_store
 	}
-	fn allocate(&mut self, len: u32) -> u32 {
+	fn allocate(&mut self, len: u32) -> ::std::result::Result<u32, UserError> {
 		self.heap.allocate(len)
 	}
-	fn deallocate(&mut self, ptr: u32) {
+	fn deallocate(&mut self, ptr: u32) -> ::std::result::Result<(), UserError> {
 		self.heap.deallocate(ptr)
 	}
 	fn write_memory(&mut self, ptr: u32, data: &[u8]) -> ::std::result::Result<(), UserError> {
@@ -141,7 +203,7 @@ impl ReadPrimitive<u32> for MemoryInstance {
 }
 
 // TODO: this macro does not support `where` clauses and that seems somewhat tricky to add
-impl_function_executor!(this: FunctionExecutor<'e, E>,
+impl_function_executor!(this: FunctionExecutor<'e, E, H>,
 	ext_print_utf8(utf8_data: *const u8, utf8_len: u32) => {
 		if let Ok(utf8) = this.memory.get(utf8_data, utf8_len as usize) {
 			if let Ok(message) = String::from_utf8(utf8) {
@@ -161,12 +223,12 @@ impl_function_executor!(this: FunctionExecutor<'e, E>,
 		Ok(())
 	},
 	ext_malloc(size: usize) -> *mut u8 => {
-		let r = this.heap.allocate(size);
+		let r = this.heap.allocate(size)?;
 		debug_trace!(target: "sr-io", "malloc {} bytes at {}", size, r);
 		Ok(r)
 	},
 	ext_free(addr: *mut u8) => {
-		this.heap.deallocate(addr);
+		this.heap.deallocate(addr)?;
 		debug_trace!(target: "sr-io", "free {}", addr);
 		Ok(())
 	},
@@ -244,11 +306,41 @@ impl_function_executor!(this: FunctionExecutor<'e, E>,
 		let key = this.memory.get(key_data, key_len as usize).map_err(|_| UserError("Invalid attempt to determine key in ext_exists_child_storage"))?;
 		Ok(if this.ext.exists_child_storage(&storage_key, &key) { 1 } else { 0 })
 	},
+	ext_storage_append(key_data: *const u8, key_len: u32, item_data: *const u8, item_len: u32) => {
+		let key = this.memory.get(key_data, key_len as usize).map_err(|_| UserError("Invalid attempt to determine key in ext_storage_append"))?;
+		let item = this.memory.get(item_data, item_len as usize).map_err(|_| UserError("Invalid attempt to determine item in ext_storage_append"))?;
+		this.ext.storage_append(key, item);
+		Ok(())
+	},
 	ext_clear_prefix(prefix_data: *const u8, prefix_len: u32) => {
 		let prefix = this.memory.get(prefix_data, prefix_len as usize).map_err(|_| UserError("Invalid attempt to determine prefix in ext_clear_prefix"))?;
 		this.ext.clear_prefix(&prefix);
 		Ok(())
 	},
+	// `cursor_len` is u32::max_value() if there is no cursor. Return 0 and place u32::max_value()
+	// into `next_len` if no entry with the prefix is left.
+	ext_clear_prefix_limited(prefix_data: *const u8, prefix_len: u32, cursor_data: *const u8, cursor_len: u32, limit: u32, removed: *mut u32, next_len: *mut u32) -> *mut u8 => {
+		let prefix = this.memory.get(prefix_data, prefix_len as usize).map_err(|_| UserError("Invalid attempt to determine prefix in ext_clear_prefix_limited"))?;
+		let cursor = if cursor_len == u32::max_value() {
+			None
+		} else {
+			Some(this.memory.get(cursor_data, cursor_len as usize).map_err(|_| UserError("Invalid attempt to determine cursor in ext_clear_prefix_limited"))?)
+		};
+		let (removed_count, next) = this.ext.clear_prefix_limited(&prefix, limit, cursor.as_ref().map(|cursor| &cursor[..]));
+		this.memory.write_primitive(removed, removed_count)
+			.map_err(|_| UserError("Invalid attempt to write removed in ext_clear_prefix_limited"))?;
+		if let Some(next) = next {
+			let offset = this.heap.allocate(next.len() as u32)?;
+			this.memory.set(offset, &next).map_err(|_| UserError("Invalid attempt to set memory in ext_clear_prefix_limited"))?;
+			this.memory.write_primitive(next_len, next.len() as u32)
+				.map_err(|_| UserError("Invalid attempt to write next_len in ext_clear_prefix_limited"))?;
+			Ok(offset)
+		} else {
+			this.memory.write_primitive(next_len, u32::max_value())
+				.map_err(|_| UserError("Invalid attempt to write failed next_len in ext_clear_prefix_limited"))?;
+			Ok(0)
+		}
+	},
 	ext_kill_child_storage(storage_key_data: *const u8, storage_key_len: u32) => {
 		let storage_key = this.memory.get(
 			storage_key_data,
@@ -280,7 +372,7 @@ impl_function_executor!(this: FunctionExecutor<'e, E>,
 		);
 
 		if let Some(value) = maybe_value {
-			let offset = this.heap.allocate(value.len() as u32) as u32;
+			let offset = this.heap.allocate(value.len() as u32)?;
 			this.memory.set(offset, &value).map_err(|_| UserError("Invalid attempt to set memory in ext_get_allocated_storage"))?;
 			this.memory.write_primitive(written_out, value.len() as u32)
 				.map_err(|_| UserError("Invalid attempt to write written_out in ext_get_allocated_storage"))?;
@@ -319,7 +411,7 @@ impl_function_executor!(this: FunctionExecutor<'e, E>,
 		);
 
 		if let Some(value) = maybe_value {
-			let offset = this.heap.allocate(value.len() as u32) as u32;
+			let offset = this.heap.allocate(value.len() as u32)?;
 			this.memory.set(offset, &value).map_err(|_| UserError("Invalid attempt to set memory in ext_get_allocated_child_storage"))?;
 			this.memory.write_primitive(written_out, value.len() as u32)
 				.map_err(|_| UserError("Invalid attempt to write written_out in ext_get_allocated_child_storage"))?;
@@ -401,7 +493,7 @@ impl_function_executor!(this: FunctionExecutor<'e, E>,
 		let storage_key = this.memory.get(storage_key_data, storage_key_len as usize).map_err(|_| UserError("Invalid attempt to determine storage_key in ext_child_storage_root"))?;
 		let r = this.ext.child_storage_root(&storage_key);
 		if let Some(value) = r {
-			let offset = this.heap.allocate(value.len() as u32) as u32;
+			let offset = this.heap.allocate(value.len() as u32)?;
 			this.memory.set(offset, &value).map_err(|_| UserError("Invalid attempt to set memory in ext_child_storage_root"))?;
 			this.memory.write_primitive(written_out, value.len() as u32)
 				.map_err(|_| UserError("Invalid attempt to write written_out in ext_child_storage_root"))?;
@@ -441,6 +533,21 @@ impl_function_executor!(this: FunctionExecutor<'e, E>,
 		this.memory.set(result, &r[..]).map_err(|_| UserError("Invalid attempt to set memory in ext_blake2_256_enumerated_trie_root"))?;
 		Ok(())
 	},
+	ext_keccak_256_enumerated_trie_root(values_data: *const u8, lens_data: *const u32, lens_len: u32, result: *mut u8) => {
+		let values = (0..lens_len)
+			.map(|i| this.memory.read_primitive(lens_data + i * 4))
+			.collect::<::std::result::Result<Vec<u32>, UserError>>()?
+			.into_iter()
+			.scan(0u32, |acc, v| { let o = *acc; *acc += v; Some((o, v)) })
+			.map(|(offset, len)|
+				this.memory.get(values_data + offset, len as usize)
+					.map_err(|_| UserError("Invalid attempt to get memory in ext_keccak_256_enumerated_trie_root"))
+			)
+			.collect::<::std::result::Result<Vec<_>, UserError>>()?;
+		let r = ordered_trie_root::<KeccakHasher, _, _>(values.into_iter());
+		this.memory.set(result, &r[..]).map_err(|_| UserError("Invalid attempt to set memory in ext_keccak_256_enumerated_trie_root"))?;
+		Ok(())
+	},
 	ext_chain_id() -> u64 => {
 		Ok(this.ext.chain_id())
 	},
@@ -486,6 +593,15 @@ impl_function_executor!(this: FunctionExecutor<'e, E>,
 		this.memory.set(out, &result).map_err(|_| UserError("Invalid attempt to set result in ext_blake2_256"))?;
 		Ok(())
 	},
+	ext_keccak_256(data: *const u8, len: u32, out: *mut u8) => {
+		let result = if len == 0 {
+			keccak_256(&[0u8; 0])
+		} else {
+			keccak_256(&this.memory.get(data, len as usize).map_err(|_| UserError("Invalid attempt to get data in ext_keccak_256"))?)
+		};
+		this.memory.set(out, &result).map_err(|_| UserError("Invalid attempt to set result in ext_keccak_256"))?;
+		Ok(())
+	},
 	ext_ed25519_verify(msg_data: *const u8, msg_len: u32, sig_data: *const u8, pubkey_data: *const u8) -> u32 => {
 		let mut sig = [0u8; 64];
 		this.memory.get_into(sig_data, &mut sig[..]).map_err(|_| UserError("Invalid attempt to get signature in ext_ed25519_verify"))?;
@@ -499,6 +615,47 @@ impl_function_executor!(this: FunctionExecutor<'e, E>,
 			5
 		})
 	},
+	ext_keystore_public_keys(key_type_data: *const u8, written_out: *mut u32) -> *mut u8 => {
+		let mut key_type = [0u8; 4];
+		this.memory.get_into(key_type_data, &mut key_type[..]).map_err(|_| UserError("Invalid attempt to get key type in ext_keystore_public_keys"))?;
+		if !this.ext.capabilities().contains(Capabilities::KEYSTORE) {
+			return Err(UserError("ext_keystore_public_keys is only available to offchain workers").into());
+		}
+
+		let keys: Vec<u8> = this.ext.keystore()
+			.map(|keystore| keystore.public_keys(key_type))
+			.unwrap_or_default()
+			.into_iter()
+			.flat_map(|public| public.0.to_vec())
+			.collect();
+
+		let offset = this.heap.allocate(keys.len() as u32)?;
+		this.memory.set(offset, &keys).map_err(|_| UserError("Invalid attempt to set memory in ext_keystore_public_keys"))?;
+		this.memory.write_primitive(written_out, keys.len() as u32)
+			.map_err(|_| UserError("Invalid attempt to write written_out in ext_keystore_public_keys"))?;
+		Ok(offset)
+	},
+	ext_keystore_sign(key_type_data: *const u8, pubkey_data: *const u8, msg_data: *const u8, msg_len: u32, sig_out: *mut u8) -> u32 => {
+		let mut key_type = [0u8; 4];
+		this.memory.get_into(key_type_data, &mut key_type[..]).map_err(|_| UserError("Invalid attempt to get key type in ext_keystore_sign"))?;
+		let mut pubkey = [0u8; 32];
+		this.memory.get_into(pubkey_data, &mut pubkey[..]).map_err(|_| UserError("Invalid attempt to get pubkey in ext_keystore_sign"))?;
+		let msg = this.memory.get(msg_data, msg_len as usize).map_err(|_| UserError("Invalid attempt to get message in ext_keystore_sign"))?;
+		if !this.ext.capabilities().contains(Capabilities::KEYSTORE) {
+			return Err(UserError("ext_keystore_sign is only available to offchain workers").into());
+		}
+
+		let signature = this.ext.keystore()
+			.and_then(|keystore| keystore.sign(key_type, &ed25519::Public(pubkey), &msg));
+
+		match signature {
+			Some(signature) => {
+				this.memory.set(sig_out, signature.as_ref()).map_err(|_| UserError("Invalid attempt to set signature in ext_keystore_sign"))?;
+				Ok(0)
+			}
+			None => Ok(1),
+		}
+	},
 	ext_sandbox_instantiate(
 		dispatch_thunk_idx: usize,
 		wasm_ptr: *const u8,
@@ -611,7 +768,12 @@ impl_function_executor!(this: FunctionExecutor<'e, E>,
 		this.sandbox_store.memory_teardown(memory_idx)?;
 		Ok(())
 	},
-	=> <'e, E: Externalities<Blake2Hasher> + 'e>
+	gas(amount: u32) => {
+		// the limits are checked before every host call, the deadline included.
+		this.charge_fuel(amount)?;
+		Ok(())
+	},
+	=> <'e, E: Externalities<H> + 'e, H: Hasher<Out=H256>>
 );
 
 /// Wasm rust executor for contracts.
@@ -619,18 +781,42 @@ impl_function_executor!(this: FunctionExecutor<'e, E>,
 /// Executes the provided code in a sandboxed wasm runtime.
 #[derive(Debug, Clone)]
 pub struct WasmExecutor {
+	host_functions: Vec<HostFunctionSet>,
+	allow_missing_host_functions: bool,
 }
 
 impl WasmExecutor {
 
-	/// Create a new instance.
+	/// Create a new instance, providing the default host function sets.
 	pub fn new() -> Self {
-		WasmExecutor{}
+		WasmExecutor {
+			host_functions: default_host_functions(),
+			allow_missing_host_functions: false,
+		}
+	}
+
+	/// Register a host function set, replacing any registered version of the same set.
+	pub fn with_host_functions(mut self, set: HostFunctionSet) -> Self {
+		self.host_functions.retain(|registered| registered.name != set.name);
+		self.host_functions.push(set);
+		self
+	}
+
+	/// Whether runtimes importing host functions that aren't registered can be executed. The
+	/// missing functions trap when called.
+	pub fn allow_missing_host_functions(mut self, allow: bool) -> Self {
+		self.allow_missing_host_functions = allow;
+		self
+	}
+
+	/// The registered host function sets.
+	pub fn host_functions(&self) -> &[HostFunctionSet] {
+		&self.host_functions
 	}
 
-	/// Call a given method in the given code.
+	/// Call a given method in the given code, with the state hashed by `H`.
 	/// This should be used for tests only.
-	pub fn call<E: Externalities<Blake2Hasher>>(
+	pub fn call<E: Externalities<H>, H: Hasher<Out=H256>>(
 		&self,
 		ext: &mut E,
 		heap_pages: usize,
@@ -642,21 +828,63 @@ impl WasmExecutor {
 		self.call_in_wasm_module(ext, heap_pages, &module, method, data)
 	}
 
+	/// Call a given method in the given code, failing the call once it exceeds any of `limits`.
+	///
+	/// The code is compiled again for every call with a time or fuel limit, to meter it.
+	pub fn call_with_limits<E: Externalities<H>, H: Hasher<Out=H256>>(
+		&self,
+		ext: &mut E,
+		heap_pages: usize,
+		code: &[u8],
+		method: &str,
+		data: &[u8],
+		limits: ExecutionLimits,
+	) -> Result<Vec<u8>> {
+		let module = if is_metered(&limits) {
+			compile_metered(code)?
+		} else {
+			Module::from_buffer(code)?
+		};
+		self.call_in_module(ext, heap_pages, &module, method, data, limits)
+	}
+
 	/// Call a given method in the given wasm-module runtime.
-	pub fn call_in_wasm_module<E: Externalities<Blake2Hasher>>(
+	pub fn call_in_wasm_module<E: Externalities<H>, H: Hasher<Out=H256>>(
+		&self,
+		ext: &mut E,
+		heap_pages: usize,
+		module: &Module,
+		method: &str,
+		data: &[u8],
+	) -> Result<Vec<u8>> {
+		self.call_in_module(ext, heap_pages, module, method, data, ExecutionLimits::none())
+	}
+
+	/// Call a given method in the given wasm-module runtime, subject to `limits`. The module must
+	/// be metered if the limits are.
+	fn call_in_module<E: Externalities<H>, H: Hasher<Out=H256>>(
 		&self,
 		ext: &mut E,
 		heap_pages: usize,
 		module: &Module,
 		method: &str,
 		data: &[u8],
+		limits: ExecutionLimits,
 	) -> Result<Vec<u8>> {
+		let resolver = HostFunctionResolver::new(
+			&self.host_functions,
+			self.allow_missing_host_functions,
+			is_metered(&limits),
+			FunctionExecutor::<E, H>::resolve_func,
+		);
+
 		// start module instantiation. Don't run 'start' function yet.
 		let intermediate_instance = ModuleInstance::new(
 			module,
 			&ImportsBuilder::new()
-				.with_resolver("env", FunctionExecutor::<E>::resolver())
+				.with_resolver("env", &resolver)
 		)?;
+		let missing_host_functions = resolver.into_missing();
 
 		// extract a reference to a linear memory, optional reference to a table
 		// and then initialize FunctionExecutor.
@@ -674,12 +902,12 @@ impl WasmExecutor {
 			.export_by_name("__indirect_function_table")
 			.and_then(|e| e.as_table().cloned());
 
-		let mut fec = FunctionExecutor::new(memory.clone(), heap_pages, table, ext)?;
+		let mut fec = FunctionExecutor::new(memory.clone(), heap_pages, table, ext, missing_host_functions, limits)?;
 
 		// finish instantiation by running 'start' function (if any).
 		let instance = intermediate_instance.run_start(&mut fec)?;
 		let size = data.len() as u32;
-		let offset = fec.heap.allocate(size);
+		let offset = fec.heap.allocate(size).map_err(|_| Error::from(ErrorKind::Runtime))?;
 		memory.set(offset, &data)?;
 
 		let result = instance.invoke_export(
@@ -697,10 +925,15 @@ impl WasmExecutor {
 				return Err(e.into())
 			},
 		};
+		fec.check_limits()?;
 
 		if let Some(I64(r)) = returned {
 			let offset = r as u32;
 			let length = (r >> 32) as u32 as usize;
+			// a null pointer signals bad input, the length being the index of the bad argument.
+			if offset == 0 {
+				return Err(ErrorKind::BadInput(method.to_owned(), length as u32).into());
+			}
 			memory.get(offset, length)
 				.map_err(|_| ErrorKind::Runtime.into())
 		} else {
@@ -709,16 +942,36 @@ impl WasmExecutor {
 	}
 }
 
+/// Executing runtimes in wasm only, with no native runtime, works with any state hasher. This is
+/// the executor of chains whose state isn't hashed with `Blake2Hasher`, e.g. with `KeccakHasher`.
+impl<H: Hasher<Out=H256>> CodeExecutor<H> for WasmExecutor {
+	type Error = Error;
+
+	fn call_with_limits<E: Externalities<H>>(
+		&self,
+		ext: &mut E,
+		heap_pages: usize,
+		code: &[u8],
+		method: &str,
+		data: &[u8],
+		_use_native: bool,
+		limits: ExecutionLimits,
+	) -> (Result<Vec<u8>>, bool) {
+		(WasmExecutor::call_with_limits(self, ext, heap_pages, code, method, data, limits), false)
+	}
+}
+
 
 #[cfg(test)]
 mod tests {
 	use super::*;
+	use std::time::Duration;
 	use codec::Encode;
 	use state_machine::TestExternalities;
 
 	#[test]
 	fn returning_should_work() {
-		let mut ext = TestExternalities::default();
+		let mut ext = TestExternalities::<Blake2Hasher>::default();
 		let test_code = include_bytes!("../wasm/target/wasm32-unknown-unknown/release/runtime_test.compact.wasm");
 
 		let output = WasmExecutor::new().call(&mut ext, 8, &test_code[..], "test_empty_return", &[]).unwrap();
@@ -727,7 +980,7 @@ mod tests {
 
 	#[test]
 	fn panicking_should_work() {
-		let mut ext = TestExternalities::default();
+		let mut ext = TestExternalities::<Blake2Hasher>::default();
 		let test_code = include_bytes!("../wasm/target/wasm32-unknown-unknown/release/runtime_test.compact.wasm");
 
 		let output = WasmExecutor::new().call(&mut ext, 8, &test_code[..], "test_panic", &[]);
@@ -739,7 +992,7 @@ mod tests {
 
 	#[test]
 	fn storage_should_work() {
-		let mut ext = TestExternalities::default();
+		let mut ext = TestExternalities::<Blake2Hasher>::default();
 		ext.set_storage(b"foo".to_vec(), b"bar".to_vec());
 		let test_code = include_bytes!("../wasm/target/wasm32-unknown-unknown/release/runtime_test.compact.wasm");
 
@@ -747,7 +1000,7 @@ mod tests {
 
 		assert_eq!(output, b"all ok!".to_vec());
 
-		let expected = TestExternalities::new(map![
+		let expected = TestExternalities::<Blake2Hasher>::new(map![
 			b"input".to_vec() => b"Hello world".to_vec(),
 			b"foo".to_vec() => b"bar".to_vec(),
 			b"baz".to_vec() => b"bar".to_vec()
@@ -755,9 +1008,74 @@ mod tests {
 		assert_eq!(ext, expected);
 	}
 
+	#[test]
+	fn calls_exceeding_their_time_limit_should_fail() {
+		let test_code = include_bytes!("../wasm/target/wasm32-unknown-unknown/release/runtime_test.compact.wasm");
+		let limits = ExecutionLimits { max_duration: Some(Duration::from_secs(0)), ..ExecutionLimits::none() };
+
+		let mut ext = TestExternalities::<Blake2Hasher>::default();
+		let output = WasmExecutor::new()
+			.call_with_limits(&mut ext, 8, &test_code[..], "test_data_in", b"Hello world", limits);
+		assert!(output.is_err());
+		assert_eq!(ext, TestExternalities::<Blake2Hasher>::default());
+
+		let limits = ExecutionLimits {
+			max_duration: Some(Duration::from_secs(60)),
+			max_fuel: Some(u64::max_value()),
+			max_memory_growth: Some(0),
+		};
+		let output = WasmExecutor::new()
+			.call_with_limits(&mut ext, 8, &test_code[..], "test_data_in", b"Hello world", limits).unwrap();
+		assert_eq!(output, b"all ok!".to_vec());
+	}
+
+	#[test]
+	fn looping_calls_are_interrupted() {
+		let code = ::wabt::wat2wasm(r#"
+		(module
+			(memory (export "memory") 1)
+			(func (export "loop_forever") (param i32 i32) (result i64)
+				(loop $l (br $l))
+				(i64.const 0)
+			)
+		)
+		"#).unwrap();
+		let mut ext = TestExternalities::<Blake2Hasher>::default();
+
+		let limits = ExecutionLimits { max_fuel: Some(1_000_000), ..ExecutionLimits::none() };
+		let output = WasmExecutor::new().call_with_limits(&mut ext, 8, &code, "loop_forever", &[], limits);
+		assert!(output.is_err());
+
+		let limits = ExecutionLimits { max_duration: Some(Duration::from_millis(100)), ..ExecutionLimits::none() };
+		let output = WasmExecutor::new().call_with_limits(&mut ext, 8, &code, "loop_forever", &[], limits);
+		assert!(output.is_err());
+	}
+
+	#[test]
+	fn gas_function_is_only_provided_to_metered_calls() {
+		let code = ::wabt::wat2wasm(r#"
+		(module
+			(import "env" "gas" (func $gas (param i32)))
+			(memory (export "memory") 1)
+			(func (export "test") (param i32 i32) (result i64)
+				;; an empty output at offset 1.
+				(i64.const 1)
+			)
+		)
+		"#).unwrap();
+		let mut ext = TestExternalities::<Blake2Hasher>::default();
+
+		let output = WasmExecutor::new().call_with_limits(&mut ext, 8, &code, "test", &[], ExecutionLimits::none());
+		assert!(output.is_err());
+
+		let limits = ExecutionLimits { max_fuel: Some(1_000), ..ExecutionLimits::none() };
+		let output = WasmExecutor::new().call_with_limits(&mut ext, 8, &code, "test", &[], limits);
+		assert_eq!(output.unwrap(), Vec::<u8>::new());
+	}
+
 	#[test]
 	fn clear_prefix_should_work() {
-		let mut ext = TestExternalities::default();
+		let mut ext = TestExternalities::<Blake2Hasher>::default();
 		ext.set_storage(b"aaa".to_vec(), b"1".to_vec());
 		ext.set_storage(b"aab".to_vec(), b"2".to_vec());
 		ext.set_storage(b"aba".to_vec(), b"3".to_vec());
@@ -780,7 +1098,7 @@ mod tests {
 
 	#[test]
 	fn blake2_256_should_work() {
-		let mut ext = TestExternalities::default();
+		let mut ext = TestExternalities::<Blake2Hasher>::default();
 		let test_code = include_bytes!("../wasm/target/wasm32-unknown-unknown/release/runtime_test.compact.wasm");
 		assert_eq!(
 			WasmExecutor::new().call(&mut ext, 8, &test_code[..], "test_blake2_256", &[]).unwrap(),
@@ -794,7 +1112,7 @@ mod tests {
 
 	#[test]
 	fn twox_256_should_work() {
-		let mut ext = TestExternalities::default();
+		let mut ext = TestExternalities::<Blake2Hasher>::default();
 		let test_code = include_bytes!("../wasm/target/wasm32-unknown-unknown/release/runtime_test.compact.wasm");
 		assert_eq!(
 			WasmExecutor::new().call(&mut ext, 8, &test_code[..], "test_twox_256", &[]).unwrap(),
@@ -808,7 +1126,7 @@ mod tests {
 
 	#[test]
 	fn twox_128_should_work() {
-		let mut ext = TestExternalities::default();
+		let mut ext = TestExternalities::<Blake2Hasher>::default();
 		let test_code = include_bytes!("../wasm/target/wasm32-unknown-unknown/release/runtime_test.compact.wasm");
 		assert_eq!(
 			WasmExecutor::new().call(&mut ext, 8, &test_code[..], "test_twox_128", &[]).unwrap(),
@@ -856,5 +1174,83 @@ mod tests {
 		);
 	}
 
+	#[test]
+	fn keccak_state_should_work_end_to_end() {
+		let mut ext = TestExternalities::<KeccakHasher>::default();
+		ext.set_storage(b"foo".to_vec(), b"bar".to_vec());
+		let test_code = include_bytes!("../wasm/target/wasm32-unknown-unknown/release/runtime_test.compact.wasm");
+		let executor = WasmExecutor::new();
+
+		let (output, native) = CodeExecutor::<KeccakHasher>::call(
+			&executor, &mut ext, 8, &test_code[..], "test_data_in", b"Hello world", true,
+		);
+		assert_eq!(output.unwrap(), b"all ok!".to_vec());
+		assert!(!native);
+
+		// the runtime gets the root of the state hashed with Keccak.
+		let pairs = vec![
+			(b"input".to_vec(), b"Hello world".to_vec()),
+			(b"foo".to_vec(), b"bar".to_vec()),
+			(b"baz".to_vec(), b"bar".to_vec()),
+		];
+		let (output, _) = CodeExecutor::<KeccakHasher>::call(
+			&executor, &mut ext, 8, &test_code[..], "test_storage_root", &[], false,
+		);
+		let root = output.unwrap();
+		assert_eq!(root, trie::trie_root::<KeccakHasher, _, _, _>(pairs.clone()).as_fixed_bytes().to_vec());
+		assert_ne!(root, trie::trie_root::<Blake2Hasher, _, _, _>(pairs).as_fixed_bytes().to_vec());
+
+		assert_eq!(
+			executor.call(&mut ext, 8, &test_code[..], "test_keccak_256", b"").unwrap(),
+			hex!("c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470").to_vec()
+		);
+		assert_eq!(
+			executor.call(&mut ext, 8, &test_code[..], "test_keccak_enumerated_trie_root", &[]).unwrap(),
+			ordered_trie_root::<KeccakHasher, _, _>(vec![b"zero".to_vec(), b"one".to_vec(), b"two".to_vec()].iter()).as_fixed_bytes().to_vec()
+		);
+	}
+
+	/// A module importing the given host function, which takes a single `i64`.
+	fn module_importing(function: &str) -> Vec<u8> {
+		::wabt::wat2wasm(format!(r#"
+		(module
+			(import "env" "{}" (func $imported (param i64)))
+			(memory (export "memory") 1)
+			(func (export "test_ok") (param i32 i32) (result i64)
+				i64.const 1
+			)
+			(func (export "test_call") (param i32 i32) (result i64)
+				i64.const 42
+				call $imported
+				i64.const 1
+			)
+		)
+		"#, function)).unwrap()
+	}
+
+	#[test]
+	fn only_registered_host_functions_are_provided() {
+		let mut ext = TestExternalities::<Blake2Hasher>::default();
+		let code = module_importing("ext_print_num");
+		assert_eq!(WasmExecutor::new().call(&mut ext, 8, &code, "test_call", &[]).unwrap(), Vec::<u8>::new());
+
+		const IO_V0: HostFunctionSet = HostFunctionSet { name: "io", version: 0, functions: &["ext_print_utf8"] };
+		let executor = WasmExecutor::new().with_host_functions(IO_V0);
+		assert!(executor.host_functions().contains(&IO_V0));
+		assert!(!executor.host_functions().contains(&::host_functions::IO_V1));
+		assert!(executor.call(&mut ext, 8, &code, "test_ok", &[]).is_err());
+	}
+
+	#[test]
+	fn missing_host_functions_trap_when_called() {
+		let mut ext = TestExternalities::<Blake2Hasher>::default();
+		let code = module_importing("ext_removed_long_ago");
+		assert!(WasmExecutor::new().call(&mut ext, 8, &code, "test_ok", &[]).is_err());
+
+		let executor = WasmExecutor::new().allow_missing_host_functions(true);
+		assert_eq!(executor.call(&mut ext, 8, &code, "test_ok", &[]).unwrap(), Vec::<u8>::new());
+		assert!(executor.call(&mut ext, 8, &code, "test_call", &[]).is_err());
+	}
+
 
 }
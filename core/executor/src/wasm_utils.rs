@@ -143,11 +143,11 @@ macro_rules! marshall {
 macro_rules! dispatch_fn {
 	( @iter $index:expr, $index_ident:ident, $objectname:ident, $args_iter:ident) => {
 		// `$index` comes from an argument of Externals::invoke_index;
-		// externals are always invoked with index given by resolve_fn! at resolve time;
+		// externals are always invoked with index given at resolve time;
 		// For each next function resolve_fn! gives new index, starting from 0;
 		// Both dispatch_fn! and resolve_fn! are called with the same list of functions;
-		// qed;
-		panic!("fn with index {} is undefined", $index);
+		// Any other index was given to the stub of a function missing from the node;
+		return Err($objectname.missing_host_function($index_ident));
 	};
 
 	( @iter $index:expr, $index_ident:ident, $objectname:ident, $args_iter:ident, $name:ident ( $( $names:ident : $params:ty ),* ) $( -> $returns:ty )* => $body:tt $($tail:tt)*) => (
@@ -169,18 +169,12 @@ macro_rules! impl_function_executor {
 	  => $($pre:tt)+ ) => (
 		impl $( $pre ) + $structname {
 			#[allow(unused)]
-			fn resolver() -> &'static $crate::wasmi::ModuleImportResolver {
-				struct Resolver;
-				impl $crate::wasmi::ModuleImportResolver for Resolver {
-					fn resolve_func(&self, name: &str, signature: &$crate::wasmi::Signature) -> ::std::result::Result<$crate::wasmi::FuncRef, $crate::wasmi::Error> {
-						resolve_fn!(signature, name, $( $name( $( $params ),* ) $( -> $returns )* => )*);
-
-						Err($crate::wasmi::Error::Instantiation(
-							format!("Export {} not found", name),
-						))
-					}
-				}
-				&Resolver
+			fn resolve_func(name: &str, signature: &$crate::wasmi::Signature) -> ::std::result::Result<$crate::wasmi::FuncRef, $crate::wasmi::Error> {
+				resolve_fn!(signature, name, $( $name( $( $params ),* ) $( -> $returns )* => )*);
+
+				Err($crate::wasmi::Error::Instantiation(
+					format!("Export {} not found", name),
+				))
 			}
 		}
 
@@ -191,6 +185,7 @@ macro_rules! impl_function_executor {
 				args: $crate::wasmi::RuntimeArgs,
 			) -> ::std::result::Result<Option<$crate::wasmi::RuntimeValue>, $crate::wasmi::Trap> {
 				let $objectname = self;
+				$objectname.check_limits()?;
 				let mut args = args.as_ref().iter();
 				dispatch_fn!(index, $objectname, args, $( $name( $( $names : $params ),* ) $( -> $returns )* => $body ),*);
 			}
@@ -11,8 +11,8 @@ extern crate sr_sandbox as sandbox;
 extern crate substrate_primitives;
 
 use runtime_io::{
-	set_storage, storage, clear_prefix, print, blake2_256,
-	twox_128, twox_256, ed25519_verify, enumerated_trie_root
+	set_storage, storage, clear_prefix, print, blake2_256, keccak_256,
+	twox_128, twox_256, ed25519_verify, enumerated_trie_root, storage_root
 };
 
 macro_rules! impl_stubs {
@@ -70,7 +70,9 @@ impl_stubs!(
 		}
 		input.to_vec()
 	},
+	test_storage_root => |_| storage_root().to_vec(),
 	test_blake2_256 => |input| blake2_256(input).to_vec(),
+	test_keccak_256 => |input| keccak_256(input).to_vec(),
 	test_twox_256 => |input| twox_256(input).to_vec(),
 	test_twox_128 => |input| twox_128(input).to_vec(),
 	test_ed25519_verify => |input: &[u8]| {
@@ -86,6 +88,9 @@ impl_stubs!(
 	test_enumerated_trie_root => |_| {
 		enumerated_trie_root::<substrate_primitives::Blake2Hasher>(&[&b"zero"[..], &b"one"[..], &b"two"[..]]).to_vec()
 	},
+	test_keccak_enumerated_trie_root => |_| {
+		enumerated_trie_root::<substrate_primitives::KeccakHasher>(&[&b"zero"[..], &b"one"[..], &b"two"[..]]).to_vec()
+	},
 	test_sandbox => |code: &[u8]| {
 		let ok = execute_sandboxed(code, &[]).is_ok();
 		[ok as u8].to_vec()
@@ -34,10 +34,14 @@ extern crate substrate_client as client;
 
 extern crate sr_std as rstd;
 
-use substrate_primitives::AuthorityId;
-use sr_primitives::traits::{DigestFor, NumberFor};
+use substrate_primitives::{AuthorityId, Signature, H256};
+use sr_primitives::{ConsensusEngineId, Ed25519Signature, traits::{DigestFor, NumberFor, Verify}};
+use parity_codec::Encode;
 use rstd::vec::Vec;
 
+/// The consensus engine id of GRANDPA, used to tag its justifications.
+pub const GRANDPA_ENGINE_ID: ConsensusEngineId = *b"FRNK";
+
 /// A scheduled change of authority set.
 #[cfg_attr(feature = "std", derive(Debug, PartialEq))]
 #[derive(Clone, Encode, Decode)]
@@ -48,25 +52,78 @@ pub struct ScheduledChange<N> {
 	pub delay: N,
 }
 
+/// A prevote or precommit of an equivocation: the block it targets and its signature.
+#[cfg_attr(feature = "std", derive(Debug, PartialEq))]
+#[derive(Clone, Encode, Decode)]
+pub struct SignedVote<H, N> {
+	/// The hash of the block the vote targets.
+	pub target_hash: H,
+	/// The number of the block the vote targets.
+	pub target_number: N,
+	/// The signature of the voter.
+	pub signature: Signature,
+}
+
+/// Proof that a GRANDPA authority signed two votes of the same kind for different blocks in the
+/// same round.
+#[cfg_attr(feature = "std", derive(Debug, PartialEq))]
+#[derive(Clone, Encode, Decode)]
+pub struct EquivocationProof<H, N> {
+	/// The id of the authority set the offender is part of.
+	pub set_id: u64,
+	/// The round in which the offender equivocated.
+	pub round: u64,
+	/// The equivocating authority.
+	pub offender: AuthorityId,
+	/// Whether both votes are precommits, rather than prevotes.
+	pub precommits: bool,
+	/// The first vote.
+	pub first: SignedVote<H, N>,
+	/// The second vote.
+	pub second: SignedVote<H, N>,
+}
+
+impl<H: Encode + PartialEq, N: Encode> EquivocationProof<H, N> {
+	/// Whether the proof is valid: the votes target different blocks and both are signed by the
+	/// offender as votes of the kind of the proof, in its round and set.
+	///
+	/// The caller must make sure that the offender is part of the given set.
+	pub fn is_valid(&self) -> bool {
+		let signed_by_offender = |vote: &SignedVote<H, N>| {
+			// the signed payload is the localized `grandpa::Message`: its variant, which is 0 for
+			// a prevote and 1 for a precommit, and the target, followed by the round and set id.
+			let payload = (
+				self.precommits as u8,
+				&vote.target_hash,
+				&vote.target_number,
+				self.round,
+				self.set_id,
+			).encode();
+
+			Ed25519Signature(vote.signature).verify(&payload[..], &H256::from(self.offender.0))
+		};
+
+		self.first.target_hash != self.second.target_hash
+			&& signed_by_offender(&self.first)
+			&& signed_by_offender(&self.second)
+	}
+}
+
 /// WASM function call to check for pending changes.
 pub const PENDING_CHANGE_CALL: &str = "grandpa_pending_change";
+/// WASM function call to check for forced changes.
+pub const FORCED_CHANGE_CALL: &str = "grandpa_forced_change";
 /// WASM function call to get current GRANDPA authorities.
 pub const AUTHORITIES_CALL: &str = "grandpa_authorities";
 
-/// The ApiIds for GRANDPA API.
-pub mod id {
-	use client::runtime_api::ApiId;
-
-	/// ApiId for the GrandpaApi trait.
-	pub const GRANDPA_API: ApiId = *b"fgrandpa";
-}
-
 /// Well-known storage keys for GRANDPA.
 pub mod well_known_keys {
 	/// The key for the authorities and weights vector in storage.
 	pub const AUTHORITY_PREFIX: &[u8] = b":grandpa:auth:";
 	/// The key for the authorities count.
 	pub const AUTHORITY_COUNT: &[u8] = b":grandpa:auth:len";
+	/// The key for the id of the current authority set.
+	pub const SET_ID: &[u8] = b":grandpa:set_id";
 }
 
 decl_runtime_apis! {
@@ -79,6 +136,9 @@ decl_runtime_apis! {
 	/// applied in the runtime after those N blocks have passed.
 	///
 	/// The consensus protocol will coordinate the handoff externally.
+	///
+	/// Version 2 adds `current_set_id`, `authority_set_keys` and `generate_key_ownership_proof`.
+	#[api_version(2)]
 	pub trait GrandpaApi {
 		/// Check a digest for pending changes.
 		/// Return `None` if there are no pending changes.
@@ -95,8 +155,38 @@ decl_runtime_apis! {
 		fn grandpa_pending_change(digest: DigestFor<Block>)
 			-> Option<ScheduledChange<NumberFor<Block>>>;
 
+		/// Check a digest for forced changes.
+		/// Return `None` if there are no forced changes. Otherwise, return the number of the
+		/// block the new set starts voting on top of, usually the median last finalized block
+		/// of the network, along with the change.
+		///
+		/// Forced changes are applied once the given delay of blocks has been imported on top
+		/// of the signalling block, without waiting for finality. They are a way to recover
+		/// when finality stalled, e.g. because too many voters of the current set went offline.
+		///
+		/// The same rules as for `grandpa_pending_change` apply.
+		fn grandpa_forced_change(digest: DigestFor<Block>)
+			-> Option<(NumberFor<Block>, ScheduledChange<NumberFor<Block>>)>;
+
 		/// Get the current GRANDPA authorities and weights. This should not change except
 		/// for when changes are scheduled and the corresponding delay has passed.
 		fn grandpa_authorities() -> Vec<(AuthorityId, u64)>;
+
+		/// Get the id of the current authority set. It starts at 0 and is incremented on every
+		/// change of the set, forced or not, in step with the set id of the finality gadget.
+		///
+		/// Returns `None` if the id was never initialised, e.g. on chains started before it was
+		/// tracked by the runtime. These have to initialise it with the set id of the gadget.
+		fn current_set_id() -> Option<u64>;
+
+		/// Get the storage keys holding the current set id, authorities and weights. A read
+		/// proof of these keys proves the current set against the state root of the block,
+		/// without replaying the changes scheduled since genesis.
+		fn authority_set_keys() -> Vec<Vec<u8>>;
+
+		/// Prove that the given authority key is owned by a validator of the current session.
+		/// The proof is opaque to the node: it is submitted along with a report of an
+		/// equivocation of the authority and checked by the runtime before punishing the owner.
+		fn generate_key_ownership_proof(authority: AuthorityId) -> Option<Vec<u8>>;
 	}
 }
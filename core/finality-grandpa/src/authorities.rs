@@ -20,6 +20,7 @@ use parking_lot::RwLock;
 use substrate_primitives::AuthorityId;
 
 use std::cmp::Ord;
+use std::collections::HashSet;
 use std::fmt::Debug;
 use std::ops::Add;
 use std::sync::Arc;
@@ -86,6 +87,7 @@ pub(crate) struct AuthoritySet<H, N> {
 	current_authorities: Vec<(AuthorityId, u64)>,
 	set_id: u64,
 	pending_changes: Vec<PendingChange<H, N>>,
+	pending_forced_changes: Vec<PendingChange<H, N>>,
 }
 
 impl<H, N> AuthoritySet<H, N> {
@@ -95,6 +97,7 @@ impl<H, N> AuthoritySet<H, N> {
 			current_authorities: initial,
 			set_id: 0,
 			pending_changes: Vec::new(),
+			pending_forced_changes: Vec::new(),
 		}
 	}
 
@@ -109,18 +112,24 @@ where
 	N: Add<Output=N> + Ord + Clone + Debug,
 	H: Debug
 {
-	/// Note an upcoming pending transition.
+	/// Note an upcoming pending transition. Forced changes are kept apart from the others, as
+	/// they are applied on import rather than on finality.
 	pub(crate) fn add_pending_change(&mut self, pending: PendingChange<H, N>) {
+		let changes = match pending.delay_kind {
+			DelayKind::Finalized => &mut self.pending_changes,
+			DelayKind::Best { .. } => &mut self.pending_forced_changes,
+		};
+
 		// ordered first by effective number and then by signal-block number.
 		let key = (pending.effective_number(), pending.canon_height.clone());
-		let idx = self.pending_changes
+		let idx = changes
 			.binary_search_by_key(&key, |change| (
 				change.effective_number(),
 				change.canon_height.clone(),
 			))
 			.unwrap_or_else(|i| i);
 
-		self.pending_changes.insert(idx, pending);
+		changes.insert(idx, pending);
 	}
 
 	/// Inspect pending changes.
@@ -129,11 +138,61 @@ where
 		&self.pending_changes
 	}
 
+	/// Inspect pending forced changes.
+	#[cfg(test)]
+	pub(crate) fn pending_forced_changes(&self) -> &[PendingChange<H, N>] {
+		&self.pending_forced_changes
+	}
+
 	/// Get the earliest limit-block number, if any.
 	pub(crate) fn current_limit(&self) -> Option<N> {
 		self.pending_changes.get(0).map(|change| change.effective_number().clone())
 	}
 
+	/// Whether a pending change takes effect at the given block number, i.e. the block
+	/// must be finalized with a justification of the current set.
+	pub(crate) fn enacts_change(&self, number: N) -> bool {
+		self.pending_changes.iter().any(|change| change.effective_number() == number)
+	}
+
+	/// Get the set that results from a forced change enacted by the given newly imported block,
+	/// if any. A forced change is enacted by the block at its effective number, provided that
+	/// the block descends from the one that signalled the change. Provide a closure that returns
+	/// the hash of the ancestor with the given number of the imported block.
+	///
+	/// The new set has the next set id and no pending changes: changes signalled to the current
+	/// set are void once it is replaced. Returns the number of the block the new set starts
+	/// voting on top of, along with the new set; the current set is left untouched.
+	pub(crate) fn apply_forced_changes<F, E>(&self, best_number: N, mut canonical: F)
+		-> Result<Option<(N, Self)>, E>
+		where F: FnMut(N) -> Result<Option<H>, E>
+	{
+		for change in &self.pending_forced_changes {
+			if change.effective_number() != best_number {
+				continue;
+			}
+
+			let median_last_finalized = match change.delay_kind {
+				DelayKind::Best { ref median_last_finalized } => median_last_finalized.clone(),
+				DelayKind::Finalized => continue,
+			};
+
+			if canonical(change.canon_height.clone())?.as_ref() == Some(&change.canon_hash) {
+				info!(target: "finality", "Applying forced authority set change scheduled at block #{:?}",
+					change.canon_height);
+
+				return Ok(Some((median_last_finalized, AuthoritySet {
+					current_authorities: change.next_authorities.clone(),
+					set_id: self.set_id + 1,
+					pending_changes: Vec::new(),
+					pending_forced_changes: Vec::new(),
+				})));
+			}
+		}
+
+		Ok(None)
+	}
+
 	/// Apply or prune any pending transitions. Provide a closure that can be used to check for the
 	/// finalized block with given number.
 	///
@@ -189,37 +248,236 @@ where
 			status.changed = true; // always changed because we strip at least the first change.
 		}
 
+		// forced changes are enacted on import; those not enacted by the time their effective
+		// number is finalized were signalled on another fork.
+		let forced_changes = self.pending_forced_changes.len();
+		self.pending_forced_changes.retain(|change| change.effective_number() > just_finalized);
+		if self.pending_forced_changes.len() != forced_changes {
+			status.changed = true;
+		}
+
 		Ok(status)
 	}
 }
 
+/// Kinds of delays for pending changes.
+#[derive(Debug, Clone, Encode, Decode, PartialEq)]
+pub(crate) enum DelayKind<N> {
+	/// The change is applied when the announcing block is at the given depth within the
+	/// finalized chain.
+	Finalized,
+	/// The change is forced: it is applied when the announcing block is at the given depth
+	/// within the best chain. The new set starts voting on top of the block with the given
+	/// number, usually the median last finalized block of the network.
+	Best { median_last_finalized: N },
+}
+
 /// A pending change to the authority set.
 ///
 /// This will be applied when the announcing block is at some depth within
-/// the finalized chain.
+/// the finalized chain, or within the best chain for forced changes.
 #[derive(Debug, Clone, Encode, Decode, PartialEq)]
 pub(crate) struct PendingChange<H, N> {
 	/// The new authorities and weights to apply.
 	pub(crate) next_authorities: Vec<(AuthorityId, u64)>,
-	/// How deep in the finalized chain the announcing block must be
+	/// How deep in the chain the announcing block must be
 	/// before the change is applied.
-	pub(crate) finalization_depth: N,
+	pub(crate) delay: N,
 	/// The announcing block's height.
 	pub(crate) canon_height: N,
 	/// The announcing block's hash.
 	pub(crate) canon_hash: H,
+	/// The kind of delay of the change.
+	pub(crate) delay_kind: DelayKind<N>,
 }
 
 impl<H, N: Add<Output=N> + Clone> PendingChange<H, N> {
 	/// Returns the effective number this change will be applied at.
 	fn effective_number(&self) -> N {
-		self.canon_height.clone() + self.finalization_depth.clone()
+		self.canon_height.clone() + self.delay.clone()
+	}
+}
+
+/// A pending change as stored by nodes before forced changes were supported.
+#[derive(Decode)]
+struct OldPendingChange<H, N> {
+	next_authorities: Vec<(AuthorityId, u64)>,
+	finalization_depth: N,
+	canon_height: N,
+	canon_hash: H,
+}
+
+/// An authority set as stored by nodes before forced changes were supported. Its pending changes
+/// are all applied on finality.
+#[derive(Decode)]
+pub(crate) struct OldAuthoritySet<H, N> {
+	current_authorities: Vec<(AuthorityId, u64)>,
+	set_id: u64,
+	pending_changes: Vec<OldPendingChange<H, N>>,
+}
+
+impl<H, N> From<OldAuthoritySet<H, N>> for AuthoritySet<H, N> {
+	fn from(old: OldAuthoritySet<H, N>) -> Self {
+		AuthoritySet {
+			current_authorities: old.current_authorities,
+			set_id: old.set_id,
+			pending_changes: old.pending_changes.into_iter().map(|change| PendingChange {
+				next_authorities: change.next_authorities,
+				delay: change.finalization_depth,
+				canon_height: change.canon_height,
+				canon_hash: change.canon_hash,
+				delay_kind: DelayKind::Finalized,
+			}).collect(),
+			pending_forced_changes: Vec::new(),
+		}
 	}
 }
 
+/// The weight needed to finalize a block with the given voters and weights, i.e. the weight of
+/// more than two thirds of the set.
+///
+/// Weights may be arbitrary, e.g. proportional to the stake of each voter. Returns `None` for
+/// sets GRANDPA can't vote with: empty sets and sets with duplicate voters, voters without
+/// weight or a total weight overflowing `u64`.
+pub(crate) fn voter_set_threshold<'a, I>(voters: I) -> Option<u64> where
+	I: IntoIterator<Item=(&'a AuthorityId, u64)>,
+{
+	let mut seen = HashSet::new();
+	let mut total_weight = 0u64;
+	for (id, weight) in voters {
+		if weight == 0 || !seen.insert(id) {
+			return None;
+		}
+		total_weight = total_weight.checked_add(weight)?;
+	}
+
+	if total_weight == 0 {
+		return None;
+	}
+
+	Some(total_weight - (total_weight - 1) / 3)
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
+	use rand::{Rng, SeedableRng, rngs::StdRng, seq::SliceRandom};
+	use std::cmp;
+
+	/// A random block tree, block `i` having hash `i` and the genesis block hash `0`.
+	struct BlockTree {
+		// parent hash and number of each block.
+		blocks: Vec<(u64, u64)>,
+	}
+
+	impl BlockTree {
+		fn random(rng: &mut StdRng, len: usize) -> Self {
+			let mut blocks = vec![(0, 0)];
+			for i in 1..len as u64 {
+				// mostly extend the latest block, sometimes fork off a recent one.
+				let parent = if rng.gen_bool(0.8) { i - 1 } else { rng.gen_range(i.saturating_sub(8), i) };
+				let number = blocks[parent as usize].1 + 1;
+				blocks.push((parent, number));
+			}
+
+			BlockTree { blocks }
+		}
+
+		fn number(&self, hash: u64) -> u64 {
+			self.blocks[hash as usize].1
+		}
+
+		/// The ancestor of the given block with the given number, if any.
+		fn ancestor(&self, mut hash: u64, number: u64) -> Option<u64> {
+			if number > self.number(hash) {
+				return None;
+			}
+			while self.number(hash) > number {
+				hash = self.blocks[hash as usize].0;
+			}
+			Some(hash)
+		}
+
+		fn leaves(&self) -> Vec<u64> {
+			(0..self.blocks.len() as u64)
+				.filter(|hash| !self.blocks.iter().skip(1).any(|&(parent, _)| parent == *hash))
+				.collect()
+		}
+	}
+
+	/// Changes signalled by distinct random blocks of the tree, with random delays and kinds.
+	/// Each change has a distinct set, so that enacted changes can be told apart.
+	fn random_changes(rng: &mut StdRng, tree: &BlockTree, count: usize) -> Vec<PendingChange<u64, u64>> {
+		let mut signals: Vec<_> = (1..tree.blocks.len() as u64).collect();
+		signals.shuffle(rng);
+
+		signals.into_iter().take(count).enumerate().map(|(i, hash)| {
+			let canon_height = tree.number(hash);
+			let delay_kind = if rng.gen_bool(0.3) {
+				DelayKind::Best { median_last_finalized: rng.gen_range(0, canon_height + 1) }
+			} else {
+				DelayKind::Finalized
+			};
+
+			PendingChange {
+				next_authorities: vec![([i as u8 + 1; 32].into(), 1)],
+				delay: rng.gen_range(0, 10),
+				canon_height,
+				canon_hash: hash,
+				delay_kind,
+			}
+		}).collect()
+	}
+
+	fn set_with_changes(changes: &[PendingChange<u64, u64>]) -> AuthoritySet<u64, u64> {
+		let mut set = AuthoritySet::genesis(vec![([0; 32].into(), 1)]);
+		for change in changes {
+			set.add_pending_change(change.clone());
+		}
+		set
+	}
+
+	type SetState = (u64, Vec<(AuthorityId, u64)>, Vec<PendingChange<u64, u64>>, Vec<PendingChange<u64, u64>>);
+
+	/// The state of the set, disregarding the order of changes with the same effective and
+	/// signal-block numbers.
+	fn state(set: &AuthoritySet<u64, u64>) -> SetState {
+		let sorted = |changes: &[PendingChange<u64, u64>]| {
+			let mut changes = changes.to_vec();
+			changes.sort_by_key(|change| (change.effective_number(), change.canon_height, change.canon_hash));
+			changes
+		};
+
+		(
+			set.set_id,
+			set.current_authorities.clone(),
+			sorted(&set.pending_changes),
+			sorted(&set.pending_forced_changes),
+		)
+	}
+
+	fn assert_sorted(changes: &[PendingChange<u64, u64>]) {
+		let keys: Vec<_> = changes.iter().map(|change| (change.effective_number(), change.canon_height)).collect();
+		let mut sorted = keys.clone();
+		sorted.sort();
+		assert_eq!(keys, sorted);
+	}
+
+	#[test]
+	fn voter_set_threshold_accounts_for_weights() {
+		let ids: Vec<_> = (0..4u8).map(|i| AuthorityId([i; 32])).collect();
+		let threshold = |weights: &[u64]| voter_set_threshold(ids.iter().zip(weights.iter().cloned()));
+
+		assert_eq!(threshold(&[1, 1, 1]), Some(3));
+		assert_eq!(threshold(&[1, 1, 1, 1]), Some(3));
+		assert_eq!(threshold(&[5, 1, 1]), Some(5));
+		assert_eq!(threshold(&[100, 200, 300, 400]), Some(667));
+
+		assert_eq!(threshold(&[]), None);
+		assert_eq!(threshold(&[1, 0, 1]), None);
+		assert_eq!(threshold(&[u64::max_value(), 1]), None);
+		assert_eq!(voter_set_threshold(vec![(&ids[0], 1), (&ids[0], 1)]), None);
+	}
 
 	#[test]
 	fn changes_sorted_in_correct_order() {
@@ -227,27 +485,31 @@ mod tests {
 			current_authorities: Vec::new(),
 			set_id: 0,
 			pending_changes: Vec::new(),
+			pending_forced_changes: Vec::new(),
 		};
 
 		let change_a = PendingChange {
 			next_authorities: Vec::new(),
-			finalization_depth: 10,
+			delay: 10,
 			canon_height: 5,
 			canon_hash: "hash_a",
+			delay_kind: DelayKind::Finalized,
 		};
 
 		let change_b = PendingChange {
 			next_authorities: Vec::new(),
-			finalization_depth: 0,
+			delay: 0,
 			canon_height: 16,
 			canon_hash: "hash_b",
+			delay_kind: DelayKind::Finalized,
 		};
 
 		let change_c = PendingChange {
 			next_authorities: Vec::new(),
-			finalization_depth: 5,
+			delay: 5,
 			canon_height: 10,
 			canon_hash: "hash_c",
+			delay_kind: DelayKind::Finalized,
 		};
 
 		authorities.add_pending_change(change_a.clone());
@@ -263,6 +525,7 @@ mod tests {
 			current_authorities: Vec::new(),
 			set_id: 0,
 			pending_changes: Vec::new(),
+			pending_forced_changes: Vec::new(),
 		};
 
 		let set_a = vec![([1; 32].into(), 5)];
@@ -270,16 +533,18 @@ mod tests {
 
 		let change_a = PendingChange {
 			next_authorities: set_a.clone(),
-			finalization_depth: 10,
+			delay: 10,
 			canon_height: 5,
 			canon_hash: "hash_a",
+			delay_kind: DelayKind::Finalized,
 		};
 
 		let change_b = PendingChange {
 			next_authorities: set_b.clone(),
-			finalization_depth: 10,
+			delay: 10,
 			canon_height: 5,
 			canon_hash: "hash_b",
+			delay_kind: DelayKind::Finalized,
 		};
 
 		authorities.add_pending_change(change_a.clone());
@@ -305,6 +570,7 @@ mod tests {
 			current_authorities: Vec::new(),
 			set_id: 0,
 			pending_changes: Vec::new(),
+			pending_forced_changes: Vec::new(),
 		};
 
 		let set_a = vec![([1; 32].into(), 5)];
@@ -313,24 +579,27 @@ mod tests {
 
 		let change_a = PendingChange {
 			next_authorities: set_a.clone(),
-			finalization_depth: 10,
+			delay: 10,
 			canon_height: 5,
 			canon_hash: "hash_a",
+			delay_kind: DelayKind::Finalized,
 		};
 
 		// will be ignored because it was signalled when change_a still pending.
 		let change_b = PendingChange {
 			next_authorities: set_b.clone(),
-			finalization_depth: 10,
+			delay: 10,
 			canon_height: 15,
 			canon_hash: "hash_b",
+			delay_kind: DelayKind::Finalized,
 		};
 
 		let change_c = PendingChange {
 			next_authorities: set_c.clone(),
-			finalization_depth: 10,
+			delay: 10,
 			canon_height: 16,
 			canon_hash: "hash_c",
+			delay_kind: DelayKind::Finalized,
 		};
 
 		authorities.add_pending_change(change_a.clone());
@@ -349,4 +618,170 @@ mod tests {
 		assert_eq!(authorities.set_id, 2); // has been bumped only twice
 		assert!(authorities.pending_changes.is_empty());
 	}
+
+	#[test]
+	fn forced_change_applies_on_import_of_descendant() {
+		let mut authorities = AuthoritySet {
+			current_authorities: Vec::new(),
+			set_id: 0,
+			pending_changes: Vec::new(),
+			pending_forced_changes: Vec::new(),
+		};
+
+		let set_a = vec![([1; 32].into(), 5)];
+		let set_b = vec![([2; 32].into(), 5)];
+
+		let change_a = PendingChange {
+			next_authorities: set_a.clone(),
+			delay: 10,
+			canon_height: 5,
+			canon_hash: "hash_a",
+			delay_kind: DelayKind::Finalized,
+		};
+
+		let change_b = PendingChange {
+			next_authorities: set_b.clone(),
+			delay: 10,
+			canon_height: 5,
+			canon_hash: "hash_b",
+			delay_kind: DelayKind::Best { median_last_finalized: 2 },
+		};
+
+		authorities.add_pending_change(change_a.clone());
+		authorities.add_pending_change(change_b.clone());
+		assert_eq!(authorities.pending_changes(), &[change_a]);
+		assert_eq!(authorities.pending_forced_changes(), &[change_b]);
+
+		// not enacted before the effective number, nor on another fork.
+		assert!(authorities.apply_forced_changes(14, |_| Ok::<_, ()>(Some("hash_b"))).unwrap().is_none());
+		assert!(authorities.apply_forced_changes(15, |_| Ok::<_, ()>(Some("hash_a"))).unwrap().is_none());
+
+		let (median, new_set) = authorities.apply_forced_changes(15, |n| match n {
+			5 => Ok(Some("hash_b")),
+			_ => Err(()),
+		}).unwrap().unwrap();
+
+		assert_eq!(median, 2);
+		assert_eq!(new_set.current(), (1, &set_b[..]));
+		assert!(new_set.pending_changes.is_empty());
+		assert!(new_set.pending_forced_changes.is_empty());
+
+		// the current set is untouched, and the forced change is pruned once finality passes it.
+		assert_eq!(authorities.set_id, 0);
+		let status = authorities.apply_changes(15, |n| match n {
+			5 => Ok("hash_b"),
+			_ => Err(()),
+		}).unwrap();
+
+		assert!(status.changed);
+		assert!(status.new_set_block.is_none());
+		assert!(authorities.pending_forced_changes.is_empty());
+	}
+
+	#[test]
+	fn fuzz_pending_changes_sorted_whatever_the_insertion_order() {
+		for seed in 0..100 {
+			let mut rng = StdRng::from_seed([seed; 32]);
+			let tree = BlockTree::random(&mut rng, 50);
+			let mut changes = random_changes(&mut rng, &tree, 20);
+
+			let authorities = set_with_changes(&changes);
+			assert_sorted(authorities.pending_changes());
+			assert_sorted(authorities.pending_forced_changes());
+			assert!(authorities.pending_changes().iter().all(|c| c.delay_kind == DelayKind::Finalized));
+			assert!(authorities.pending_forced_changes().iter().all(|c| c.delay_kind != DelayKind::Finalized));
+			assert_eq!(
+				authorities.pending_changes().len() + authorities.pending_forced_changes().len(),
+				changes.len()
+			);
+
+			changes.shuffle(&mut rng);
+			assert_eq!(state(&set_with_changes(&changes)), state(&authorities));
+		}
+	}
+
+	#[test]
+	fn fuzz_changes_only_enacted_when_signalled_on_the_finalized_chain() {
+		for seed in 0..100 {
+			let mut rng = StdRng::from_seed([seed; 32]);
+			let tree = BlockTree::random(&mut rng, 60);
+			let mut changes = random_changes(&mut rng, &tree, 20);
+			let finalized = *tree.leaves().choose(&mut rng).expect("the tree has at least one leaf; qed");
+			let finalized_number = tree.number(finalized);
+			let canonical = |number| tree.ancestor(finalized, number).ok_or(());
+			let is_canonical = |change: &PendingChange<u64, u64>| canonical(change.canon_height) == Ok(change.canon_hash);
+
+			// finalize the chain at once.
+			let mut authorities = set_with_changes(&changes);
+			let initial = state(&authorities);
+			let status = authorities.apply_changes(finalized_number, &canonical).unwrap();
+
+			assert_eq!(status.changed, state(&authorities) != initial);
+			assert_eq!(status.new_set_block.is_some(), authorities.set_id != 0);
+			assert!(authorities.pending_changes.iter().all(|c| c.effective_number() > finalized_number));
+			assert!(authorities.pending_forced_changes.iter().all(|c| c.effective_number() > finalized_number));
+
+			let enactable = changes.iter().any(|c|
+				c.delay_kind == DelayKind::Finalized && c.effective_number() <= finalized_number && is_canonical(c)
+			);
+			assert_eq!(authorities.set_id != 0, enactable);
+
+			// the last enacted change was signalled on the finalized chain and takes effect at
+			// the block the new set starts on.
+			if let Some((hash, number)) = status.new_set_block {
+				assert_eq!(canonical(number), Ok(hash));
+				assert!(changes.iter().any(|c|
+					c.delay_kind == DelayKind::Finalized &&
+						c.next_authorities == authorities.current_authorities &&
+						c.effective_number() == number &&
+						is_canonical(c)
+				));
+			}
+
+			// finalizing the same chain step by step, with changes noted in another order, ends
+			// up with the same set.
+			changes.shuffle(&mut rng);
+			let mut step_by_step = set_with_changes(&changes);
+			let mut number = 0;
+			while number < finalized_number {
+				number = cmp::min(finalized_number, number + rng.gen_range(1, 6));
+				step_by_step.apply_changes(number, &canonical).unwrap();
+			}
+
+			assert_eq!(state(&step_by_step), state(&authorities));
+		}
+	}
+
+	#[test]
+	fn fuzz_forced_changes_only_enacted_on_descendants_of_the_signal_block() {
+		for seed in 0..100 {
+			let mut rng = StdRng::from_seed([seed; 32]);
+			let tree = BlockTree::random(&mut rng, 60);
+			let authorities = set_with_changes(&random_changes(&mut rng, &tree, 20));
+
+			// import every block of the tree, whatever the fork.
+			for best in 0..tree.blocks.len() as u64 {
+				let best_number = tree.number(best);
+				let enacted = authorities
+					.apply_forced_changes(best_number, |number| Ok::<_, ()>(tree.ancestor(best, number)))
+					.unwrap();
+				let expected = authorities.pending_forced_changes().iter().find(|c|
+					c.effective_number() == best_number && tree.ancestor(best, c.canon_height) == Some(c.canon_hash)
+				);
+
+				match (enacted, expected) {
+					(None, None) => {},
+					(Some((median_last_finalized, new_set)), Some(change)) => {
+						assert_eq!(DelayKind::Best { median_last_finalized }, change.delay_kind);
+						assert_eq!(new_set.current(), (1, &change.next_authorities[..]));
+						assert!(new_set.pending_changes.is_empty());
+						assert!(new_set.pending_forced_changes.is_empty());
+					},
+					(enacted, expected) => panic!(
+						"Import of block {} enacted {:?} instead of {:?}", best, enacted, expected,
+					),
+				}
+			}
+		}
+	}
 }
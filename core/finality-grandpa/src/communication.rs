@@ -32,7 +32,7 @@ fn localized_payload<E: Encode>(round: u64, set_id: u64, message: &E) -> Vec<u8>
 }
 
 // check a message.
-fn check_message_sig<Block: BlockT>(
+pub(crate) fn check_message_sig<Block: BlockT>(
 	message: &Message<Block>,
 	id: &AuthorityId,
 	signature: &ed25519::Signature,
@@ -89,27 +89,56 @@ pub(crate) fn checked_message_stream<Block: BlockT, S>(
 		.map_err(|()| Error::Network(format!("Failed to receive message on unbounded stream")))
 }
 
-struct OutgoingMessages<Block: BlockT, N: Network> {
+struct OutgoingMessages<Block: BlockT, N: Network, P> {
 	round: u64,
 	set_id: u64,
 	locals: Option<(Arc<ed25519::Pair>, AuthorityId)>,
 	sender: mpsc::UnboundedSender<SignedMessage<Block>>,
 	network: N,
+	votes: Vec<SignedMessage<Block>>,
+	persist_votes: P,
 }
 
-impl<Block: BlockT, N: Network> Sink for OutgoingMessages<Block, N> {
+impl<Block: BlockT, N: Network, P> Sink for OutgoingMessages<Block, N, P> where
+	P: FnMut(&[SignedMessage<Block>]) -> Result<(), Error>,
+{
 	type SinkItem = Message<Block>;
 	type SinkError = Error;
 
 	fn start_send(&mut self, msg: Message<Block>) -> StartSend<Message<Block>, Error> {
 		// when locals exist, sign messages on import
 		if let Some((ref pair, local_id)) = self.locals {
-			let encoded = localized_payload(self.round, self.set_id, &msg);
-			let signature = pair.sign(&encoded[..]);
-			let signed = SignedMessage::<Block> {
-				message: msg,
-				signature,
-				id: local_id,
+			let kind = ::std::mem::discriminant(&msg);
+			let previous = self.votes.iter()
+				.find(|vote| ::std::mem::discriminant(&vote.message) == kind)
+				.cloned();
+
+			let signed = match previous {
+				// we already voted in this round before a restart. re-send that vote
+				// instead of casting a new one, which could be an equivocation.
+				Some(previous) => {
+					if previous.message != msg {
+						debug!(target: "afg", "Re-sending vote {:?} cast before restart instead of {:?}",
+							previous.message, msg);
+					}
+
+					previous
+				}
+				None => {
+					let encoded = localized_payload(self.round, self.set_id, &msg);
+					let signature = pair.sign(&encoded[..]);
+					let signed = SignedMessage::<Block> {
+						message: msg,
+						signature,
+						id: local_id,
+					};
+
+					// the vote must be on disk before it is sent out.
+					self.votes.push(signed.clone());
+					(self.persist_votes)(&self.votes)?;
+
+					signed
+				}
 			};
 
 			// forward to network and to inner sender.
@@ -128,27 +157,29 @@ impl<Block: BlockT, N: Network> Sink for OutgoingMessages<Block, N> {
 	}
 }
 
-impl<Block: BlockT, N: Network> Drop for OutgoingMessages<Block, N> {
-	fn drop(&mut self) {
-		self.network.drop_messages(self.round, self.set_id);
-	}
-}
-
 /// A sink for outgoing messages. This signs the messages with the key,
 /// if we are an authority. A stream for the signed messages is also returned.
 ///
 /// A future can push unsigned messages into the sink. They will be automatically
 /// broadcast to the network. The returned stream should be combined with other input.
-pub(crate) fn outgoing_messages<Block: BlockT, N: Network>(
+///
+/// All votes signed in this round are given to `persist_votes` before they are sent out.
+/// `restored_votes` are the votes that were cast in this round before a restart. When a vote
+/// of the same kind is pushed into the sink, the restored vote is sent out instead.
+pub(crate) fn outgoing_messages<Block: BlockT, N: Network, P>(
 	round: u64,
 	set_id: u64,
 	local_key: Option<Arc<ed25519::Pair>>,
 	voters: Arc<HashMap<AuthorityId, u64>>,
 	network: N,
+	restored_votes: Vec<SignedMessage<Block>>,
+	persist_votes: P,
 ) -> (
 	impl Stream<Item=SignedMessage<Block>,Error=Error>,
 	impl Sink<SinkItem=Message<Block>,SinkError=Error>,
-) {
+) where
+	P: FnMut(&[SignedMessage<Block>]) -> Result<(), Error>,
+{
 	let locals = local_key.and_then(|pair| {
 		let public = pair.public();
 		let id = AuthorityId(public.0);
@@ -160,12 +191,19 @@ pub(crate) fn outgoing_messages<Block: BlockT, N: Network>(
 	});
 
 	let (tx, rx) = mpsc::unbounded();
-	let outgoing = OutgoingMessages::<Block, N> {
+	// only votes signed with our current key can be restored.
+	let restored_votes = restored_votes.into_iter()
+		.filter(|vote| locals.as_ref().map_or(false, |&(_, ref id)| &vote.id == id))
+		.collect();
+
+	let outgoing = OutgoingMessages::<Block, N, P> {
 		round,
 		set_id,
 		network,
 		locals,
 		sender: tx,
+		votes: restored_votes,
+		persist_votes,
 	};
 
 	let rx = rx.map_err(move |()| Error::Network(
@@ -0,0 +1,275 @@
+// Copyright 2018 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Gossip validation of GRANDPA messages.
+//!
+//! Every node announces the set and round it is voting in to its peers through neighbor
+//! packets. Messages are only accepted, kept and sent on when they belong to a window of
+//! rounds around the view of the receiving node, so that messages of past rounds and sets
+//! are dropped instead of accumulating.
+//!
+//! Commits for blocks lower than the best one the local node finalized in a set are dropped as
+//! well, so that the many equivalent commits produced after a round are not relayed over and
+//! over.
+
+use std::collections::{HashMap, HashSet};
+
+use codec::{Encode, Decode};
+use network::NodeIndex;
+use network::config::Roles;
+use network::consensus_gossip::{Validator, ValidationResult, ConsensusMessage};
+use parking_lot::RwLock;
+use runtime_primitives::traits::{Block as BlockT, Header as HeaderT, Hash as HashT, NumberFor, As};
+
+use {message_topic, commit_topic};
+
+/// The set and round a node is currently voting in, announced to its peers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+pub(crate) struct NeighborPacket {
+	/// The round the node is voting in.
+	pub(crate) round: u64,
+	/// The authority set the node is voting in.
+	pub(crate) set_id: u64,
+}
+
+/// The topic neighbor packets are sent with.
+pub(crate) fn neighbor_topic<B: BlockT>() -> B::Hash {
+	<<B::Header as HeaderT>::Hashing as HashT>::hash(b"NEIGHBOR")
+}
+
+/// The view of a node and the topics of the messages it is interested in.
+struct View<H> {
+	packet: NeighborPacket,
+	topics: HashSet<H>,
+}
+
+impl<H: ::std::hash::Hash + Eq> View<H> {
+	/// Votes of the previous, current and next round of the set are accepted, together with its
+	/// commits. Peers that moved on to the next set already start sending its first votes and
+	/// commits, which are accepted too.
+	fn new<B: BlockT<Hash=H>>(packet: NeighborPacket) -> Self {
+		let NeighborPacket { round, set_id } = packet;
+		let mut topics: HashSet<H> = (round.saturating_sub(1)..=round + 1)
+			.map(|round| message_topic::<B>(round, set_id))
+			.collect();
+		topics.insert(message_topic::<B>(1, set_id + 1));
+		topics.insert(commit_topic::<B>(set_id));
+		topics.insert(commit_topic::<B>(set_id + 1));
+
+		View { packet, topics }
+	}
+}
+
+/// Validator of GRANDPA gossip messages, tracking the views of the local node and of its peers.
+pub(crate) struct GossipValidator<B: BlockT> {
+	local_view: RwLock<Option<View<B::Hash>>>,
+	peers: RwLock<HashMap<NodeIndex, View<B::Hash>>>,
+	/// The best block number finalized locally through a commit, by commit topic.
+	best_commits: RwLock<HashMap<B::Hash, u64>>,
+}
+
+impl<B: BlockT> GossipValidator<B> {
+	/// Create a new validator. All messages are accepted until the local view is known.
+	pub(crate) fn new() -> Self {
+		GossipValidator {
+			local_view: RwLock::new(None),
+			peers: RwLock::new(HashMap::new()),
+			best_commits: RwLock::new(HashMap::new()),
+		}
+	}
+
+	/// Note that the local node is voting in the given round. Views only move forward; returns
+	/// the neighbor packet to announce to peers if the view changed.
+	pub(crate) fn note_round(&self, round: u64, set_id: u64) -> Option<NeighborPacket> {
+		let packet = NeighborPacket { round, set_id };
+		let mut local_view = self.local_view.write();
+		let is_newer = local_view.as_ref()
+			.map_or(true, |view| (set_id, round) > (view.packet.set_id, view.packet.round));
+
+		if is_newer {
+			let view = View::new::<B>(packet);
+			self.best_commits.write().retain(|topic, _| view.topics.contains(topic));
+			*local_view = Some(view);
+			Some(packet)
+		} else {
+			None
+		}
+	}
+
+	/// Note that the local node follows the given set, without voting in any round yet.
+	pub(crate) fn note_set(&self, set_id: u64) -> Option<NeighborPacket> {
+		self.note_round(0, set_id)
+	}
+
+	/// Note that the block with given number was finalized through a commit of the given set.
+	/// Commits of the set for lower blocks are dropped from then on.
+	pub(crate) fn note_commit_finalized(&self, set_id: u64, number: u64) {
+		let mut best_commits = self.best_commits.write();
+		let best = best_commits.entry(commit_topic::<B>(set_id)).or_insert(number);
+		*best = ::std::cmp::max(*best, number);
+	}
+
+	fn local_packet(&self) -> Option<NeighborPacket> {
+		self.local_view.read().as_ref().map(|view| view.packet)
+	}
+
+	/// Whether the message is a commit for a block lower than the best finalized one of its set.
+	/// Undecodable commits are outdated as well.
+	fn is_outdated_commit(&self, topic: &B::Hash, message: &[u8]) -> bool {
+		let best = match self.best_commits.read().get(topic) {
+			Some(best) => *best,
+			None => return false,
+		};
+
+		// only the round and the target are decoded, not the precommits.
+		match <(u64, B::Hash, NumberFor<B>)>::decode(&mut &message[..]) {
+			Some((_, _, number)) => number.as_() < best,
+			None => true,
+		}
+	}
+}
+
+impl<B: BlockT> Validator<B> for GossipValidator<B> {
+	fn new_peer(&self, _who: NodeIndex, _roles: Roles) -> Vec<(B::Hash, ConsensusMessage)> {
+		self.local_packet()
+			.map(|packet| vec![(neighbor_topic::<B>(), packet.encode())])
+			.unwrap_or_default()
+	}
+
+	fn validate(&self, who: NodeIndex, topic: &B::Hash, message: &[u8]) -> ValidationResult<B::Hash> {
+		if topic == &neighbor_topic::<B>() {
+			return match NeighborPacket::decode(&mut &message[..]) {
+				Some(packet) => {
+					trace!(target: "afg", "Peer {} is now at {:?}", who, packet);
+					self.peers.write().insert(who, View::new::<B>(packet));
+					ValidationResult::ProcessAndDiscard(*topic)
+				}
+				None => ValidationResult::Discard,
+			};
+		}
+
+		match *self.local_view.read() {
+			Some(ref view) if !view.topics.contains(topic) => {
+				trace!(target: "afg", "Discarding message from {} outside of {:?}", who, view.packet);
+				return ValidationResult::Discard;
+			}
+			_ => {},
+		}
+
+		if self.is_outdated_commit(topic, message) {
+			trace!(target: "afg", "Discarding outdated commit from {}", who);
+			return ValidationResult::Discard;
+		}
+
+		ValidationResult::ProcessAndKeep(*topic)
+	}
+
+	fn message_expired(&self, topic: &B::Hash, message: &[u8]) -> bool {
+		self.local_view.read().as_ref().map_or(false, |view| !view.topics.contains(topic))
+			|| self.is_outdated_commit(topic, message)
+	}
+
+	fn message_allowed(&self, who: NodeIndex, topic: &B::Hash, message: &[u8]) -> bool {
+		self.peers.read().get(&who).map_or(true, |view| view.topics.contains(topic))
+			&& !self.is_outdated_commit(topic, message)
+	}
+
+	fn peer_disconnected(&self, who: NodeIndex) {
+		self.peers.write().remove(&who);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use substrate_primitives::H256;
+	use test_client::runtime::Block;
+
+	#[test]
+	fn messages_outside_local_view_are_discarded_and_expire() {
+		let validator = GossipValidator::<Block>::new();
+		let topic = message_topic::<Block>(10, 1);
+
+		// everything is accepted until the local view is known.
+		assert_eq!(validator.validate(1, &topic, &[]), ValidationResult::ProcessAndKeep(topic));
+		assert!(!validator.message_expired(&topic, &[]));
+
+		assert_eq!(validator.note_round(5, 1), Some(NeighborPacket { round: 5, set_id: 1 }));
+		assert_eq!(validator.validate(1, &topic, &[]), ValidationResult::Discard);
+		assert!(validator.message_expired(&topic, &[]));
+
+		for &(round, set_id) in &[(4, 1), (5, 1), (6, 1), (1, 2)] {
+			let topic = message_topic::<Block>(round, set_id);
+			assert_eq!(validator.validate(1, &topic, &[]), ValidationResult::ProcessAndKeep(topic));
+		}
+		assert!(!validator.message_expired(&commit_topic::<Block>(2), &[]));
+		assert!(validator.message_expired(&commit_topic::<Block>(0), &[]));
+
+		// the view never moves backwards.
+		assert_eq!(validator.note_round(4, 1), None);
+		assert_eq!(validator.note_set(1), None);
+		assert_eq!(validator.note_set(2), Some(NeighborPacket { round: 0, set_id: 2 }));
+		assert!(validator.message_expired(&message_topic::<Block>(5, 1), &[]));
+	}
+
+	#[test]
+	fn commits_for_blocks_lower_than_the_finalized_one_are_dropped() {
+		let validator = GossipValidator::<Block>::new();
+		let topic = commit_topic::<Block>(1);
+		let commit = |number: u64| (1u64, H256::default(), number).encode();
+		assert_eq!(validator.validate(1, &topic, &commit(5)), ValidationResult::ProcessAndKeep(topic));
+
+		validator.note_commit_finalized(1, 10);
+		assert_eq!(validator.validate(1, &topic, &commit(5)), ValidationResult::Discard);
+		assert!(validator.message_expired(&topic, &commit(5)));
+		assert!(!validator.message_allowed(2, &topic, &commit(5)));
+		assert_eq!(validator.validate(1, &topic, &[1]), ValidationResult::Discard);
+		assert_eq!(validator.validate(1, &topic, &commit(10)), ValidationResult::ProcessAndKeep(topic));
+		assert!(validator.message_allowed(2, &topic, &commit(10)));
+
+		// lower blocks never lower the bar, and other sets are not affected.
+		validator.note_commit_finalized(1, 7);
+		assert_eq!(validator.validate(1, &topic, &commit(8)), ValidationResult::Discard);
+		let topic = commit_topic::<Block>(2);
+		assert_eq!(validator.validate(1, &topic, &commit(5)), ValidationResult::ProcessAndKeep(topic));
+	}
+
+	#[test]
+	fn neighbor_packets_restrict_what_peers_are_sent() {
+		let validator = GossipValidator::<Block>::new();
+		let topic = message_topic::<Block>(10, 1);
+		assert!(validator.new_peer(1, Roles::FULL).is_empty());
+		assert!(validator.message_allowed(1, &topic, &[]));
+
+		let packet = NeighborPacket { round: 2, set_id: 1 }.encode();
+		assert_eq!(
+			validator.validate(1, &neighbor_topic::<Block>(), &packet),
+			ValidationResult::ProcessAndDiscard(neighbor_topic::<Block>()),
+		);
+		assert_eq!(validator.validate(1, &neighbor_topic::<Block>(), &[1]), ValidationResult::Discard);
+
+		assert!(!validator.message_allowed(1, &topic, &[]));
+		assert!(validator.message_allowed(1, &message_topic::<Block>(3, 1), &[]));
+		assert!(validator.message_allowed(2, &topic, &[]));
+
+		validator.peer_disconnected(1);
+		assert!(validator.message_allowed(1, &topic, &[]));
+
+		validator.note_round(3, 1);
+		let packet = NeighborPacket { round: 3, set_id: 1 }.encode();
+		assert_eq!(validator.new_peer(1, Roles::FULL), vec![(neighbor_topic::<Block>(), packet)]);
+	}
+}
@@ -0,0 +1,159 @@
+// Copyright 2018 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Justifications of finalized blocks, built from GRANDPA commits.
+
+use std::collections::{HashMap, HashSet};
+
+use client::{Client, CallExecutor, backend::Backend};
+use client::blockchain::HeaderBackend;
+use client::error::{Error as ClientError, ErrorKind as ClientErrorKind};
+use codec::Decode;
+use runtime_primitives::generic::BlockId;
+use runtime_primitives::traits::{NumberFor, Block as BlockT, Header as HeaderT};
+use substrate_primitives::{AuthorityId, Blake2Hasher, H256};
+
+use Commit;
+use authorities::voter_set_threshold;
+use communication::check_message_sig;
+
+/// A GRANDPA justification for block finality. It includes a commit message and an ancestry
+/// proof: the headers that route all precommit target blocks to the commit target block.
+///
+/// This is the justification that is stored, tagged with `GRANDPA_ENGINE_ID`, alongside the
+/// blocks finalized by GRANDPA.
+#[derive(Encode, Decode)]
+pub(crate) struct GrandpaJustification<Block: BlockT> {
+	round: u64,
+	commit: Commit<Block>,
+	votes_ancestries: Vec<Block::Header>,
+}
+
+fn bad_justification(msg: &str) -> ClientError {
+	ClientErrorKind::BadJustification(msg.to_string()).into()
+}
+
+impl<Block: BlockT<Hash=H256>> GrandpaJustification<Block> {
+	/// Create a GRANDPA justification from the given commit of the given round. This assumes
+	/// that the commit is valid and that all precommit targets are known to the client.
+	pub(crate) fn from_commit<B, E, RA>(
+		client: &Client<B, E, Block, RA>,
+		round: u64,
+		commit: Commit<Block>,
+	) -> Result<GrandpaJustification<Block>, ClientError> where
+		B: Backend<Block, Blake2Hasher>,
+		E: CallExecutor<Block, Blake2Hasher> + Send + Sync,
+		RA: Send + Sync,
+	{
+		let mut votes_ancestries_hashes = HashSet::new();
+		let mut votes_ancestries = Vec::new();
+
+		for signed in commit.precommits.iter() {
+			let mut current_hash = signed.precommit.target_hash;
+			while current_hash != commit.target_hash {
+				let header = client.backend().blockchain().header(BlockId::Hash(current_hash))?
+					.ok_or_else(|| bad_justification("unknown precommit target"))?;
+
+				if *header.number() <= commit.target_number {
+					return Err(bad_justification("precommit target does not descend from commit target"));
+				}
+
+				let parent_hash = *header.parent_hash();
+				if votes_ancestries_hashes.insert(current_hash) {
+					votes_ancestries.push(header);
+				}
+
+				current_hash = parent_hash;
+			}
+		}
+
+		Ok(GrandpaJustification { round, commit, votes_ancestries })
+	}
+
+	/// Decode a GRANDPA justification and verify it against the given authority set.
+	pub(crate) fn decode_and_verify(
+		encoded: &[u8],
+		set_id: u64,
+		voters: &HashMap<AuthorityId, u64>,
+	) -> Result<GrandpaJustification<Block>, ClientError> {
+		let justification = Self::decode(&mut &*encoded)
+			.ok_or_else(|| bad_justification("failed to decode GRANDPA justification"))?;
+
+		justification.verify(set_id, voters)?;
+		Ok(justification)
+	}
+
+	/// Verify the justification against the given authority set: all precommits must be
+	/// signed by voters of the set and target descendants of the commit target, the voters
+	/// must have enough weight to finalize the commit target and the ancestry proof must not
+	/// contain superfluous headers.
+	pub(crate) fn verify(&self, set_id: u64, voters: &HashMap<AuthorityId, u64>) -> Result<(), ClientError> {
+		let threshold = voter_set_threshold(voters.iter().map(|(id, weight)| (id, *weight)))
+			.ok_or_else(|| bad_justification("invalid voter set weights"))?;
+		let ancestry: HashMap<_, _> = self.votes_ancestries.iter()
+			.map(|header| (header.hash(), header))
+			.collect();
+
+		let mut visited_hashes = HashSet::new();
+		let mut signers = HashSet::new();
+		let mut weight = 0u64;
+
+		for signed in self.commit.precommits.iter() {
+			let voter_weight = voters.get(&signed.id)
+				.ok_or_else(|| bad_justification("precommit of unknown voter"))?;
+
+			check_message_sig::<Block>(
+				&::grandpa::Message::Precommit(signed.precommit.clone()),
+				&signed.id,
+				&signed.signature,
+				self.round,
+				set_id,
+			).map_err(|()| bad_justification("invalid signature on precommit"))?;
+
+			let mut current_hash = signed.precommit.target_hash;
+			while current_hash != self.commit.target_hash {
+				let header = ancestry.get(&current_hash)
+					.ok_or_else(|| bad_justification("precommit target not in ancestry proof"))?;
+
+				if *header.number() <= self.commit.target_number {
+					return Err(bad_justification("precommit target does not descend from commit target"));
+				}
+
+				visited_hashes.insert(current_hash);
+				current_hash = *header.parent_hash();
+			}
+
+			if signers.insert(signed.id.clone()) {
+				weight += voter_weight;
+			}
+		}
+
+		if weight < threshold {
+			return Err(bad_justification("precommits do not reach the finalization threshold"));
+		}
+
+		if visited_hashes.len() != ancestry.len() {
+			return Err(bad_justification("superfluous headers in ancestry proof"));
+		}
+
+		Ok(())
+	}
+
+	/// The hash and number of the block this justification finalizes.
+	pub(crate) fn target(&self) -> (Block::Hash, NumberFor<Block>) {
+		(self.commit.target_hash, self.commit.target_number)
+	}
+}
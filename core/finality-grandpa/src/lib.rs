@@ -30,6 +30,10 @@
 //! `Network` implementation. The returned future should be driven to completion and
 //! will finalize blocks in the background.
 //!
+//! Nodes without a local key, or whose key is not part of the current authority set, run
+//! an observer instead of a voter: it does not vote, but follows the commit messages of
+//! the set and finalizes blocks accordingly.
+//!
 //! # Changing authority sets
 //!
 //! The rough idea behind changing authority sets in GRANDPA is that at some point,
@@ -49,9 +53,19 @@
 //! (this is num(signal) + N). When finalizing a block, we either apply or prune
 //! any signaled changes based on whether the signaling block is included in the
 //! newly-finalized chain.
+//!
+//! # Justifications
+//!
+//! Every block finalized by the voter is stored with a justification: the commit that
+//! finalized it together with the headers proving that all precommits target descendants
+//! of the finalized block. Syncing nodes verify and import these justifications through the
+//! block import wrapper, which also implements `JustificationImport`. Blocks that enact an
+//! authority set change must be finalized before the next set is known, so their
+//! justification is requested from the network if the block was imported without one.
 
 extern crate finality_grandpa as grandpa;
 extern crate futures;
+#[cfg_attr(test, macro_use)]
 extern crate substrate_client as client;
 extern crate sr_primitives as runtime_primitives;
 extern crate substrate_consensus_common as consensus_common;
@@ -66,6 +80,11 @@ extern crate rand;
 #[macro_use]
 extern crate log;
 
+#[macro_use]
+extern crate substrate_telemetry;
+#[macro_use]
+extern crate slog;	// needed until we can reexport `slog_info` from `substrate_telemetry`
+
 #[cfg(feature="service-integration")]
 extern crate substrate_service as service;
 
@@ -87,14 +106,14 @@ use client::{
 	Client, error::Error as ClientError, backend::Backend, CallExecutor, BlockchainEvents
 };
 use client::blockchain::HeaderBackend;
-use client::runtime_api::TaggedTransactionQueue;
+use client::runtime_api::{TaggedTransactionQueue, ApiExt};
 use codec::{Encode, Decode};
-use consensus_common::{BlockImport, ImportBlock, ImportResult, Authorities};
+use consensus_common::{BlockImport, JustificationImport, ImportBlock, ImportResult, Authorities};
 use runtime_primitives::traits::{
-	NumberFor, Block as BlockT, Header as HeaderT, DigestFor, ProvideRuntimeApi, Hash as HashT,
+	NumberFor, Block as BlockT, Header as HeaderT, DigestFor, ProvideRuntimeApi, Hash as HashT, As,
 };
-use fg_primitives::GrandpaApi;
-use runtime_primitives::generic::BlockId;
+use fg_primitives::{GrandpaApi, GRANDPA_ENGINE_ID};
+use runtime_primitives::{generic::BlockId, Justification, justification_for_engine};
 use substrate_primitives::{ed25519, H256, AuthorityId, Blake2Hasher};
 use tokio::timer::Delay;
 
@@ -109,12 +128,19 @@ use std::sync::Arc;
 use std::time::{Instant, Duration};
 
 use authorities::SharedAuthoritySet;
+use gossip::{GossipValidator, NeighborPacket, neighbor_topic};
+use justification::GrandpaJustification;
+use metrics::CommitLatency;
 use until_imported::{UntilCommitBlocksImported, UntilVoteTargetImported};
 
-pub use fg_primitives::ScheduledChange;
+pub use fg_primitives::{ScheduledChange, EquivocationProof, SignedVote};
 
 mod authorities;
 mod communication;
+mod gossip;
+mod justification;
+mod metrics;
+mod observer;
 mod until_imported;
 
 #[cfg(feature="service-integration")]
@@ -127,10 +153,23 @@ mod tests;
 
 const LAST_COMPLETED_KEY: &[u8] = b"grandpa_completed_round";
 const AUTHORITY_SET_KEY: &[u8] = b"grandpa_voters";
+const CURRENT_ROUND_VOTES_KEY: &[u8] = b"grandpa_current_round_votes";
+const AUTHORITY_SET_CHANGES_KEY: &[u8] = b"grandpa_set_changes";
+const VERSION_KEY: &[u8] = b"grandpa_schema_version";
+
+/// The version of the layout of the data kept in the aux db. Version 1 stores the forced changes
+/// along with the authority set.
+const CURRENT_VERSION: u32 = 1;
+
+/// The maximum number of headers included in a finality proof.
+pub const MAX_FINALITY_PROOF_HEADERS: usize = 1024;
 
 /// round-number, round-state
 type LastCompleted<H, N> = (u64, RoundState<H, N>);
 
+/// set-id, round-number, votes cast by the local voter in that round
+type CurrentRoundVotes<Block> = (u64, u64, Vec<SignedMessage<Block>>);
+
 /// A GRANDPA message for a substrate chain.
 pub type Message<Block> = grandpa::Message<<Block as BlockT>::Hash, NumberFor<Block>>;
 /// A signed message.
@@ -159,21 +198,85 @@ pub type CompactCommit<Block> = grandpa::CompactCommit<
 	AuthorityId
 >;
 
+/// The shortest gossip duration allowed. Shorter ones would time rounds out before votes of
+/// even well-connected voters arrive.
+pub const MIN_GOSSIP_DURATION: Duration = Duration::from_millis(100);
+
+/// The longest gossip duration allowed. Longer ones would stall finality for minutes on every
+/// round where some voter is offline.
+pub const MAX_GOSSIP_DURATION: Duration = Duration::from_secs(60);
+
+/// The largest multiple of the gossip duration a round timer may be set to.
+pub const MAX_ROUND_TIMER: u32 = 16;
+
+/// The timers of a round, in multiples of the gossip duration. They start when the round does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RoundTimers {
+	/// The time after which the voter prevotes, even without a primary proposal.
+	pub prevote: u32,
+	/// The time after which the voter precommits, even if the prevotes are not final yet.
+	pub precommit: u32,
+}
+
+impl Default for RoundTimers {
+	fn default() -> Self {
+		RoundTimers { prevote: 2, precommit: 4 }
+	}
+}
+
 /// Configuration for the GRANDPA service.
 #[derive(Clone)]
-pub struct Config {
+pub struct Config<Block: BlockT> {
 	/// The expected duration for a message to be gossiped across the network.
 	pub gossip_duration: Duration,
+	/// The timers of every round.
+	pub round_timers: RoundTimers,
 	/// The local signing key.
 	pub local_key: Option<Arc<ed25519::Pair>>,
 	/// Some local identifier of the voter.
 	pub name: Option<String>,
+	/// Where to report equivocations of other voters to, e.g. to have them slashed.
+	pub equivocation_reporter: Option<Arc<ReportEquivocation<Block>>>,
+}
+
+/// A handler for equivocations detected by the voter. Reports submitted to the runtime should
+/// come with a proof of ownership of the offender's key, see `prove_key_ownership`.
+pub trait ReportEquivocation<Block: BlockT>: Send + Sync {
+	/// Report an authority for signing two votes of the same kind for different blocks in the
+	/// same round.
+	fn report_equivocation(&self, proof: EquivocationProof<Block::Hash, NumberFor<Block>>);
 }
 
-impl Config {
+impl<Block: BlockT> Config<Block> {
 	fn name(&self) -> &str {
 		self.name.as_ref().map(|s| s.as_str()).unwrap_or("<unknown>")
 	}
+
+	/// Ensure the timing of the configuration is sane, see `check_timing`.
+	pub fn check(&self) -> Result<(), String> {
+		check_timing(self.gossip_duration, self.round_timers)
+	}
+}
+
+/// Ensure the gossip duration and round timers are within bounds, and that the precommit timer
+/// expires after the prevote one.
+pub fn check_timing(gossip_duration: Duration, round_timers: RoundTimers) -> Result<(), String> {
+	if gossip_duration < MIN_GOSSIP_DURATION || gossip_duration > MAX_GOSSIP_DURATION {
+		return Err(format!(
+			"GRANDPA gossip duration must be between {:?} and {:?}, got {:?}",
+			MIN_GOSSIP_DURATION, MAX_GOSSIP_DURATION, gossip_duration,
+		));
+	}
+
+	let RoundTimers { prevote, precommit } = round_timers;
+	if prevote == 0 || precommit <= prevote || precommit > MAX_ROUND_TIMER {
+		return Err(format!(
+			"GRANDPA round timers must satisfy 0 < prevote < precommit <= {}, got {:?}",
+			MAX_ROUND_TIMER, round_timers,
+		));
+	}
+
+	Ok(())
 }
 
 /// Errors that can occur while voting in GRANDPA.
@@ -212,33 +315,58 @@ pub trait Network: Clone {
 	/// Send a message at a specific round out.
 	fn send_message(&self, round: u64, set_id: u64, message: Vec<u8>);
 
-	/// Clean up messages for a round.
-	fn drop_messages(&self, round: u64, set_id: u64);
-
 	/// Get a stream of commit messages for a specific set-id. This stream
 	/// should never logically conclude.
 	fn commit_messages(&self, set_id: u64) -> Self::In;
 
 	/// Send message over the commit channel.
 	fn send_commit(&self, set_id: u64, message: Vec<u8>);
+
+	/// Download the given block, the target of a commit, ahead of other blocks as it is
+	/// required for finality. Nothing happens if the block is already known.
+	fn sync_finality_target(&self, hash: H256, number: u64);
+
+	/// Note that the block with given number was finalized through a commit of the given set,
+	/// so that commits of the set for lower blocks need not be relayed anymore.
+	fn note_commit_finalized(&self, set_id: u64, number: u64);
 }
 
 ///  Bridge between NetworkService, gossiping consensus messages and Grandpa
+///
+/// Messages are validated by a `GossipValidator` registered with the consensus gossip:
+/// messages of rounds and sets the local node has moved past are dropped, and peers are
+/// only sent messages of the rounds they announced being in.
 pub struct NetworkBridge<B: BlockT, S: network::specialization::NetworkSpecialization<B>, H: ExHashT> {
-	service: Arc<NetworkService<B, S, H>>
+	service: Arc<NetworkService<B, S, H>>,
+	validator: Arc<GossipValidator<B>>,
 }
 
 impl<B: BlockT, S: network::specialization::NetworkSpecialization<B>, H: ExHashT> NetworkBridge<B, S, H> {
 	/// Create a new NetworkBridge to the given NetworkService
 	pub fn new(service: Arc<NetworkService<B, S, H>>) -> Self {
-		NetworkBridge { service }
+		let validator = Arc::new(GossipValidator::new());
+		service.consensus_gossip().write().register_validator(validator.clone());
+		NetworkBridge { service, validator }
+	}
+
+	/// Announce a new local view to all peers and drop the messages outside of it.
+	fn note_view(&self, packet: Option<NeighborPacket>) {
+		if let Some(packet) = packet {
+			let gossip = self.service.consensus_gossip();
+			self.service.with_spec(|_, context| {
+				let mut gossip = gossip.write();
+				gossip.send_to_all(context, neighbor_topic::<B>(), packet.encode());
+				gossip.collect_garbage(|_| true);
+			});
+		}
 	}
 }
 
 impl<B: BlockT, S: network::specialization::NetworkSpecialization<B>, H: ExHashT> Clone for NetworkBridge<B, S, H> {
 	fn clone(&self) -> Self {
 		NetworkBridge {
-			service: Arc::clone(&self.service)
+			service: Arc::clone(&self.service),
+			validator: Arc::clone(&self.validator),
 		}
 	}
 }
@@ -251,9 +379,10 @@ fn commit_topic<B: BlockT>(set_id: u64) -> B::Hash {
 	<<B::Header as HeaderT>::Hashing as HashT>::hash(format!("{}-COMMITS", set_id).as_bytes())
 }
 
-impl<B: BlockT, S: network::specialization::NetworkSpecialization<B>, H: ExHashT> Network for NetworkBridge<B, S, H> {
+impl<B: BlockT<Hash=H256>, S: network::specialization::NetworkSpecialization<B>, H: ExHashT> Network for NetworkBridge<B, S, H> {
 	type In = mpsc::UnboundedReceiver<ConsensusMessage>;
 	fn messages_for(&self, round: u64, set_id: u64) -> Self::In {
+		self.note_view(self.validator.note_round(round, set_id));
 		self.service.consensus_gossip().write().messages_for(message_topic::<B>(round, set_id))
 	}
 
@@ -262,12 +391,8 @@ impl<B: BlockT, S: network::specialization::NetworkSpecialization<B>, H: ExHashT
 		self.service.gossip_consensus_message(topic, message);
 	}
 
-	fn drop_messages(&self, round: u64, set_id: u64) {
-		let topic = message_topic::<B>(round, set_id);
-		self.service.consensus_gossip().write().collect_garbage(|t| t == &topic);
-	}
-
 	fn commit_messages(&self, set_id: u64) -> Self::In {
+		self.note_view(self.validator.note_set(set_id));
 		self.service.consensus_gossip().write().messages_for(commit_topic::<B>(set_id))
 	}
 
@@ -275,6 +400,15 @@ impl<B: BlockT, S: network::specialization::NetworkSpecialization<B>, H: ExHashT
 		let topic = commit_topic::<B>(set_id);
 		self.service.gossip_consensus_message(topic, message);
 	}
+
+	fn sync_finality_target(&self, hash: H256, number: u64) {
+		self.service.request_finality_target(hash, As::sa(number));
+	}
+
+	fn note_commit_finalized(&self, set_id: u64, number: u64) {
+		self.validator.note_commit_finalized(set_id, number);
+		self.service.consensus_gossip().write().collect_garbage(|_| true);
+	}
 }
 
 /// Something which can determine if a block is known.
@@ -301,10 +435,36 @@ impl<B, E, Block: BlockT<Hash=H256>, RA> BlockStatus<Block> for Arc<Client<B, E,
 struct Environment<B, E, Block: BlockT, N: Network, RA> {
 	inner: Arc<Client<B, E, Block, RA>>,
 	voters: Arc<HashMap<AuthorityId, u64>>,
-	config: Config,
+	config: Config<Block>,
 	authority_set: SharedAuthoritySet<Block::Hash, NumberFor<Block>>,
 	network: N,
 	set_id: u64,
+	commit_latency: Arc<CommitLatency<Block::Hash, NumberFor<Block>>>,
+}
+
+impl<B, E, Block: BlockT, N: Network, RA> Environment<B, E, Block, N, RA> where
+	B: Backend<Block, Blake2Hasher>,
+	E: CallExecutor<Block, Blake2Hasher>,
+{
+	/// Returns the votes the local voter cast in the given round of the current set before a
+	/// restart.
+	fn restore_votes(&self, round: u64) -> Result<Vec<SignedMessage<Block>>, ClientError> {
+		let raw = match self.inner.backend().get_aux(CURRENT_ROUND_VOTES_KEY)? {
+			Some(raw) => raw,
+			None => return Ok(Vec::new()),
+		};
+
+		let (set_id, votes_round, votes) = <CurrentRoundVotes<Block>>::decode(&mut &raw[..])
+			.ok_or_else(|| ::client::error::ErrorKind::Backend(
+				format!("GRANDPA votes of current round kept in invalid format")
+			))?;
+
+		if set_id == self.set_id && votes_round == round {
+			Ok(votes)
+		} else {
+			Ok(Vec::new())
+		}
+	}
 }
 
 impl<Block: BlockT<Hash=H256>, B, E, N, RA> grandpa::Chain<Block::Hash, NumberFor<Block>> for Environment<B, E, Block, N, RA> where
@@ -446,8 +606,8 @@ impl<B, E, Block: BlockT<Hash=H256>, N, RA> voter::Environment<Block::Hash, Numb
 		round: u64
 	) -> voter::RoundData<Self::Timer, Self::In, Self::Out> {
 		let now = Instant::now();
-		let prevote_timer = Delay::new(now + self.config.gossip_duration * 2);
-		let precommit_timer = Delay::new(now + self.config.gossip_duration * 4);
+		let prevote_timer = Delay::new(now + self.config.gossip_duration * self.config.round_timers.prevote);
+		let precommit_timer = Delay::new(now + self.config.gossip_duration * self.config.round_timers.precommit);
 
 		// TODO: dispatch this with `mpsc::spawn`.
 		let incoming = ::communication::checked_message_stream::<Block, _>(
@@ -457,12 +617,36 @@ impl<B, E, Block: BlockT<Hash=H256>, N, RA> voter::Environment<Block::Hash, Numb
 			self.voters.clone(),
 		);
 
-		let (out_rx, outgoing) = ::communication::outgoing_messages::<Block, _>(
+		// the votes of the round are timed from when they are first seen, before waiting for
+		// their blocks.
+		let latency = self.commit_latency.clone();
+		let incoming = incoming.inspect(move |signed| latency.note_vote(&signed.message));
+
+		let restored_votes = match self.restore_votes(round) {
+			Ok(votes) => votes,
+			Err(e) => {
+				warn!(target: "afg", "Failed to restore votes of round {}: {:?}", round, e);
+				Vec::new()
+			}
+		};
+
+		let client = self.inner.clone();
+		let set_id = self.set_id;
+		let persist_votes = move |votes: &[SignedMessage<Block>]| {
+			let encoded = (set_id, round, votes).encode();
+			client.backend()
+				.insert_aux(&[(CURRENT_ROUND_VOTES_KEY, &encoded[..])], &[])
+				.map_err(Error::Client)
+		};
+
+		let (out_rx, outgoing) = ::communication::outgoing_messages::<Block, _, _>(
 			round,
 			self.set_id,
 			self.config.local_key.clone(),
 			self.voters.clone(),
 			self.network.clone(),
+			restored_votes,
+			persist_votes,
 		);
 
 		// schedule incoming messages from the network to be held until
@@ -474,6 +658,8 @@ impl<B, E, Block: BlockT<Hash=H256>, N, RA> voter::Environment<Block::Hash, Numb
 		);
 
 		// join incoming network messages with locally originating ones.
+		let latency = self.commit_latency.clone();
+		let out_rx = out_rx.inspect(move |signed| latency.note_vote(&signed.message));
 		let incoming = Box::new(out_rx.select(incoming).map_err(Into::into));
 
 		// schedule network message cleanup when sink drops.
@@ -508,78 +694,27 @@ impl<B, E, Block: BlockT<Hash=H256>, N, RA> voter::Environment<Block::Hash, Numb
 		}
 	}
 
-	fn finalize_block(&self, hash: Block::Hash, number: NumberFor<Block>, _commit: Commit<Block>) -> Result<(), Self::Error> {
-		// ideally some handle to a synchronization oracle would be used
-		// to avoid unconditionally notifying.
-		if let Err(e) = self.inner.finalize_block(BlockId::Hash(hash), true) {
-			warn!(target: "afg", "Error applying finality to block {:?}: {:?}", (hash, number), e);
-
-			// we return without error because not being able to finalize (temporarily) is
-			// non-fatal.
-			return Ok(());
-		}
-
-		debug!(target: "afg", "Finalizing blocks up to ({:?}, {})", number, hash);
-
-		// lock must be held through writing to DB to avoid race
-		let mut authority_set = self.authority_set.inner().write();
-		let client = &self.inner;
-		let status = authority_set.apply_changes(number, |canon_number| {
-			client.block_hash_from_id(&BlockId::number(canon_number))
-				.map(|h| h.expect("given number always less than newly-finalized number; \
-					thus there is a block with that number finalized already; qed"))
-		})?;
-
-		if status.changed {
-			// write new authority set state to disk.
-			let encoded_set = authority_set.encode();
-
-			let write_result = if let Some((ref canon_hash, ref canon_number)) = status.new_set_block {
-				// we also overwrite the "last completed round" entry with a blank slate
-				// because from the perspective of the finality gadget, the chain has
-				// reset.
-				let round_state = RoundState::genesis((*canon_hash, *canon_number));
-				let last_completed: LastCompleted<_, _> = (0, round_state);
-				let encoded = last_completed.encode();
-
-				client.backend().insert_aux(
-					&[
-						(AUTHORITY_SET_KEY, &encoded_set[..]),
-						(LAST_COMPLETED_KEY, &encoded[..]),
-					],
-					&[]
-				)
-			} else {
-				client.backend().insert_aux(&[(AUTHORITY_SET_KEY, &encoded_set[..])], &[])
-			};
-
-			if let Err(e) = write_result {
-				warn!(target: "finality", "Failed to write updated authority set to disk. Bailing.");
-				warn!(target: "finality", "Node is in a potentially inconsistent state.");
-
-				return Err(e.into());
-			}
-		}
-
-		if let Some((canon_hash, canon_number)) = status.new_set_block {
-			// the authority set has changed.
-			let (new_id, set_ref) = authority_set.current();
-
-			if set_ref.len() > 16 {
-				info!("Applying GRANDPA set change to new set with {} authorities", set_ref.len());
-			} else {
-				info!("Applying GRANDPA set change to new set {:?}", set_ref);
+	fn finalize_block(
+		&self,
+		hash: Block::Hash,
+		number: NumberFor<Block>,
+		round: u64,
+		commit: Commit<Block>,
+	) -> Result<(), Self::Error> {
+		let justification = match GrandpaJustification::from_commit(&self.inner, round, commit) {
+			Ok(justification) => Some(justification),
+			Err(e) => {
+				// not being able to build a justification is non-fatal: the block is
+				// finalized regardless.
+				warn!(target: "afg", "Error creating justification for block {:?}: {:?}", (hash, number), e);
+				None
 			}
+		};
 
-			Err(ExitOrError::AuthoritiesChanged(NewAuthoritySet {
-				canon_hash,
-				canon_number,
-				set_id: new_id,
-				authorities: set_ref.to_vec(),
-			}))
-		} else {
-			Ok(())
-		}
+		finalize_block(&*self.inner, &self.authority_set, hash, number, justification)?;
+		self.commit_latency.report_finalized(&hash, number);
+		self.network.note_commit_finalized(self.set_id, number.as_());
+		Ok(())
 	}
 
 	fn round_commit_timer(&self) -> Self::Timer {
@@ -598,7 +733,16 @@ impl<B, E, Block: BlockT<Hash=H256>, N, RA> voter::Environment<Block::Hash, Numb
 		equivocation: ::grandpa::Equivocation<Self::Id, Prevote<Block>, Self::Signature>
 	) {
 		warn!(target: "afg", "Detected prevote equivocation in the finality worker: {:?}", equivocation);
-		// nothing yet; this could craft misbehavior reports of some kind.
+		let Equivocation { round_number, identity, first, second } = equivocation;
+		self.report_equivocation(round_number, identity, false, SignedVote {
+			target_hash: (first.0).target_hash,
+			target_number: (first.0).target_number,
+			signature: first.1,
+		}, SignedVote {
+			target_hash: (second.0).target_hash,
+			target_number: (second.0).target_number,
+			signature: second.1,
+		});
 	}
 
 	fn precommit_equivocation(
@@ -607,7 +751,146 @@ impl<B, E, Block: BlockT<Hash=H256>, N, RA> voter::Environment<Block::Hash, Numb
 		equivocation: Equivocation<Self::Id, Precommit<Block>, Self::Signature>
 	) {
 		warn!(target: "afg", "Detected precommit equivocation in the finality worker: {:?}", equivocation);
-		// nothing yet
+		let Equivocation { round_number, identity, first, second } = equivocation;
+		self.report_equivocation(round_number, identity, true, SignedVote {
+			target_hash: (first.0).target_hash,
+			target_number: (first.0).target_number,
+			signature: first.1,
+		}, SignedVote {
+			target_hash: (second.0).target_hash,
+			target_number: (second.0).target_number,
+			signature: second.1,
+		});
+	}
+}
+
+impl<B, E, Block: BlockT, N: Network, RA> Environment<B, E, Block, N, RA> {
+	/// Hand a proof of the equivocation of `offender` in the given round to the configured
+	/// reporter, if any.
+	fn report_equivocation(
+		&self,
+		round: u64,
+		offender: AuthorityId,
+		precommits: bool,
+		first: SignedVote<Block::Hash, NumberFor<Block>>,
+		second: SignedVote<Block::Hash, NumberFor<Block>>,
+	) {
+		let reporter = match self.config.equivocation_reporter {
+			Some(ref reporter) => reporter,
+			None => return,
+		};
+
+		reporter.report_equivocation(EquivocationProof {
+			set_id: self.set_id,
+			round,
+			offender,
+			precommits,
+			first,
+			second,
+		});
+	}
+}
+
+/// Finalize the given block and apply any authority set changes it enacts. The justification,
+/// if any, is stored alongside the block.
+///
+/// Returns `ExitOrError::AuthoritiesChanged` when the authority set changed.
+fn finalize_block<B, Block: BlockT<Hash=H256>, E, RA>(
+	client: &Client<B, E, Block, RA>,
+	authority_set: &SharedAuthoritySet<Block::Hash, NumberFor<Block>>,
+	hash: Block::Hash,
+	number: NumberFor<Block>,
+	justification: Option<GrandpaJustification<Block>>,
+) -> Result<(), ExitOrError<Block::Hash, NumberFor<Block>>> where
+	B: Backend<Block, Blake2Hasher>,
+	E: CallExecutor<Block, Blake2Hasher> + Send + Sync,
+	RA: Send + Sync,
+{
+	// keep the justifications of other consensus engines stored with the block.
+	let justification = match justification {
+		Some(justification) => {
+			let mut stored = client.justification(&BlockId::Hash(hash))?.unwrap_or_default();
+			stored.retain(|&(ref id, _)| id != &GRANDPA_ENGINE_ID);
+			stored.push((GRANDPA_ENGINE_ID, justification.encode()));
+			Some(stored)
+		}
+		None => None,
+	};
+
+	// ideally some handle to a synchronization oracle would be used
+	// to avoid unconditionally notifying.
+	if let Err(e) = client.finalize_block(BlockId::Hash(hash), justification, true) {
+		warn!(target: "afg", "Error applying finality to block {:?}: {:?}", (hash, number), e);
+
+		// we return without error because not being able to finalize (temporarily) is
+		// non-fatal.
+		return Ok(());
+	}
+
+	debug!(target: "afg", "Finalizing blocks up to ({:?}, {})", number, hash);
+
+	// lock must be held through writing to DB to avoid race
+	let mut authority_set = authority_set.inner().write();
+	let status = authority_set.apply_changes(number, |canon_number| {
+		client.block_hash_from_id(&BlockId::number(canon_number))
+			.map(|h| h.expect("given number always less than newly-finalized number; \
+				thus there is a block with that number finalized already; qed"))
+	})?;
+
+	if status.changed {
+		// write new authority set state to disk.
+		let encoded_set = authority_set.encode();
+
+		let write_result = if let Some((ref canon_hash, ref canon_number)) = status.new_set_block {
+			// we also overwrite the "last completed round" entry with a blank slate
+			// because from the perspective of the finality gadget, the chain has
+			// reset.
+			let round_state = RoundState::genesis((*canon_hash, *canon_number));
+			let last_completed: LastCompleted<_, _> = (0, round_state);
+			let encoded = last_completed.encode();
+
+			let mut set_changes = load_set_changes(client)?;
+			set_changes.push(*canon_number);
+			let encoded_changes = set_changes.encode();
+
+			client.backend().insert_aux(
+				&[
+					(AUTHORITY_SET_KEY, &encoded_set[..]),
+					(LAST_COMPLETED_KEY, &encoded[..]),
+					(AUTHORITY_SET_CHANGES_KEY, &encoded_changes[..]),
+				],
+				&[]
+			)
+		} else {
+			client.backend().insert_aux(&[(AUTHORITY_SET_KEY, &encoded_set[..])], &[])
+		};
+
+		if let Err(e) = write_result {
+			warn!(target: "finality", "Failed to write updated authority set to disk. Bailing.");
+			warn!(target: "finality", "Node is in a potentially inconsistent state.");
+
+			return Err(e.into());
+		}
+	}
+
+	if let Some((canon_hash, canon_number)) = status.new_set_block {
+		// the authority set has changed.
+		let (new_id, set_ref) = authority_set.current();
+
+		if set_ref.len() > 16 {
+			info!("Applying GRANDPA set change to new set with {} authorities", set_ref.len());
+		} else {
+			info!("Applying GRANDPA set change to new set {:?}", set_ref);
+		}
+
+		Err(ExitOrError::AuthoritiesChanged(NewAuthoritySet {
+			canon_hash,
+			canon_number,
+			set_id: new_id,
+			authorities: set_ref.to_vec(),
+		}))
+	} else {
+		Ok(())
 	}
 }
 
@@ -616,9 +899,18 @@ impl<B, E, Block: BlockT<Hash=H256>, N, RA> voter::Environment<Block::Hash, Numb
 /// This scans each imported block for signals of changing authority set.
 /// When using GRANDPA, the block import worker should be using this block import
 /// object.
+///
+/// Imported blocks that carry a GRANDPA justification are verified and finalized. Blocks
+/// which enact an authority set change but come without a justification are reported as
+/// needing one, so that it can be requested from the network and imported through the
+/// `JustificationImport` implementation.
+///
+/// Forced authority set changes are enacted on import of the block at the depth they were
+/// scheduled for in the best chain, and the background voter is restarted with the new set.
 pub struct GrandpaBlockImport<B, E, Block: BlockT<Hash=H256>, RA, PRA> {
 	inner: Arc<Client<B, E, Block, RA>>,
 	authority_set: SharedAuthoritySet<Block::Hash, NumberFor<Block>>,
+	authority_set_change: mpsc::UnboundedSender<NewAuthoritySet<Block::Hash, NumberFor<Block>>>,
 	api: Arc<PRA>,
 }
 
@@ -636,32 +928,122 @@ impl<B, E, Block: BlockT<Hash=H256>, RA, PRA> BlockImport<Block>
 	fn import_block(&self, mut block: ImportBlock<Block>, new_authorities: Option<Vec<AuthorityId>>)
 		-> Result<ImportResult, Self::Error>
 	{
-		use authorities::PendingChange;
+		use authorities::{DelayKind, PendingChange};
+
+		let hash = block.post_header().hash();
+		let number = block.header.number().clone();
+
+		// verify the justification before importing the block, since the client stores it. a bad
+		// justification doesn't make the block itself invalid, so it is imported without it.
+		let verified = justification_for_engine(&block.justification, &GRANDPA_ENGINE_ID)
+			.map(|encoded| self.verify_justification(hash, number, encoded));
+		let justification = match verified {
+			Some(Ok(justification)) => Some(justification),
+			Some(Err(e)) => {
+				warn!(target: "afg", "Importing block #{} ({}) without its bad GRANDPA justification: {:?}",
+					number, hash, e);
+				block.justification.retain(|&(ref id, _)| id != &GRANDPA_ENGINE_ID);
+				None
+			},
+			None => None,
+		};
 
-		let maybe_change = self.api.runtime_api().grandpa_pending_change(
-			&BlockId::hash(*block.header.parent_hash()),
-			&block.header.digest().clone(),
-		)?;
+		let api = self.api.runtime_api();
+		let parent = BlockId::hash(*block.header.parent_hash());
+
+		// chains whose runtime doesn't implement the GRANDPA api can't schedule changes. Any
+		// version of the api can signal them.
+		let (mut maybe_change, mut maybe_forced_change) = if api.has_api_with::<GrandpaApi<Block>, _>(&parent, |v| v >= 1)? {
+			let digest = block.header.digest().clone();
+			(
+				api.grandpa_pending_change(&parent, &digest)?,
+				api.grandpa_forced_change(&parent, &digest)?,
+			)
+		} else {
+			(None, None)
+		};
 
 		// when we update the authorities, we need to hold the lock
 		// until the block is written to prevent a race if we need to restore
 		// the old authority set on error.
-		let just_in_case = maybe_change.map(|change| {
-			let hash = block.post_header().hash();
-			let number = block.header.number().clone();
+		let mut authorities = self.authority_set.inner().write();
+		let old_set = authorities.clone();
+		let mut changed = false;
 
-			let mut authorities = self.authority_set.inner().write();
-			let old_set = authorities.clone();
+		// an invalid set can't vote, but refusing the block would halt the chain. The change is
+		// ignored instead and the current set keeps voting.
+		maybe_change = maybe_change.filter(|change| is_valid_scheduled_set(&change.next_authorities, &hash));
+		maybe_forced_change = maybe_forced_change.filter(|(_, change)| is_valid_scheduled_set(&change.next_authorities, &hash));
+
+		if let Some(change) = maybe_change {
 			authorities.add_pending_change(PendingChange {
 				next_authorities: change.next_authorities,
-				finalization_depth: change.delay,
+				delay: change.delay,
 				canon_height: number,
 				canon_hash: hash,
+				delay_kind: DelayKind::Finalized,
 			});
+			changed = true;
+		}
 
+		if let Some((median_last_finalized, change)) = maybe_forced_change {
+			authorities.add_pending_change(PendingChange {
+				next_authorities: change.next_authorities,
+				delay: change.delay,
+				canon_height: number,
+				canon_hash: hash,
+				delay_kind: DelayKind::Best { median_last_finalized },
+			});
+			changed = true;
+		}
+
+		let parent_hash = *block.header.parent_hash();
+		let forced_set = authorities.apply_forced_changes(number, |canon_number| {
+			ancestor_hash(&*self.inner, (hash, number), parent_hash, canon_number)
+		})?;
+
+		let new_set = match forced_set {
+			Some((median_last_finalized, new_set)) => {
+				// the new set starts voting on top of the given block on this chain, from
+				// the perspective of the finality gadget the chain has reset.
+				let canon_hash = ancestor_hash(&*self.inner, (hash, number), parent_hash, median_last_finalized)?
+					.ok_or_else(|| ::client::error::ErrorKind::Backend(
+						format!("Forced GRANDPA change starts voting on top of unknown block #{}", median_last_finalized)
+					))?;
+				let last_completed: LastCompleted<_, _> =
+					(0, RoundState::genesis((canon_hash, median_last_finalized)));
+				block.auxiliary.push((LAST_COMPLETED_KEY.to_vec(), Some(last_completed.encode())));
+
+				let mut set_changes = load_set_changes(&*self.inner)?;
+				set_changes.push(median_last_finalized);
+				block.auxiliary.push((AUTHORITY_SET_CHANGES_KEY.to_vec(), Some(set_changes.encode())));
+
+				*authorities = new_set;
+				changed = true;
+
+				let (set_id, current) = authorities.current();
+				Some(NewAuthoritySet {
+					canon_hash,
+					canon_number: median_last_finalized,
+					set_id,
+					authorities: current.to_vec(),
+				})
+			}
+			None => None,
+		};
+
+		let just_in_case = if changed {
 			block.auxiliary.push((AUTHORITY_SET_KEY.to_vec(), Some(authorities.encode())));
-			(old_set, authorities)
-		});
+			Some((old_set, authorities))
+		} else {
+			drop(authorities);
+			None
+		};
+
+		let enacts_change = match just_in_case {
+			Some((_, ref authorities)) => authorities.enacts_change(number),
+			None => self.authority_set.inner().read().enacts_change(number),
+		};
 
 		let result = self.inner.import_block(block, new_authorities);
 		if let Err(ref e) = result {
@@ -671,7 +1053,112 @@ impl<B, E, Block: BlockT<Hash=H256>, RA, PRA> BlockImport<Block>
 			}
 		}
 
-		result
+		// release the lock on the authority set, finalizing the block needs it.
+		drop(just_in_case);
+
+		let mut aux = match result {
+			Ok(ImportResult::Queued(aux)) => aux,
+			r => return r,
+		};
+
+		if let Some(new_set) = new_set {
+			info!(target: "afg", "Imported block #{} enacting a forced change to set {}", number, new_set.set_id);
+
+			// the voter is gone if it was never started, e.g. on non-authority nodes.
+			let _ = self.authority_set_change.unbounded_send(new_set);
+		}
+
+		match justification {
+			// the justification was stored by the client when importing the block.
+			Some(_) => self.finalize_justified_block(hash, number, None)?,
+			None if enacts_change => {
+				trace!(target: "afg", "Imported block #{} enacting an authority set change without justification", number);
+				aux.needs_justification = true;
+			},
+			None => {},
+		}
+
+		Ok(ImportResult::Queued(aux))
+	}
+}
+
+impl<B, E, Block: BlockT<Hash=H256>, RA, PRA> JustificationImport<Block>
+	for GrandpaBlockImport<B, E, Block, RA, PRA> where
+		B: Backend<Block, Blake2Hasher> + 'static,
+		E: CallExecutor<Block, Blake2Hasher> + 'static + Clone + Send + Sync,
+		RA: Send + Sync,
+{
+	type Error = ClientError;
+
+	fn import_justification(
+		&self,
+		hash: Block::Hash,
+		number: NumberFor<Block>,
+		justification: Justification,
+	) -> Result<(), Self::Error> {
+		let encoded = justification_for_engine(&justification, &GRANDPA_ENGINE_ID)
+			.ok_or_else(|| ::client::error::ErrorKind::BadJustification(
+				format!("no GRANDPA justification for block {:?}", hash)
+			))?;
+
+		let justification = self.verify_justification(hash, number, encoded)?;
+		self.finalize_justified_block(hash, number, Some(justification))
+	}
+}
+
+impl<B, E, Block: BlockT<Hash=H256>, RA, PRA> GrandpaBlockImport<B, E, Block, RA, PRA> where
+	B: Backend<Block, Blake2Hasher> + 'static,
+	E: CallExecutor<Block, Blake2Hasher> + 'static + Clone + Send + Sync,
+	RA: Send + Sync,
+{
+	/// Verify the given encoded justification of the given block against the current
+	/// authority set.
+	fn verify_justification(
+		&self,
+		hash: Block::Hash,
+		number: NumberFor<Block>,
+		encoded: &[u8],
+	) -> Result<GrandpaJustification<Block>, ClientError> {
+		let (set_id, voters) = {
+			let authorities = self.authority_set.inner().read();
+			let (set_id, voters) = authorities.current();
+			(set_id, voters.iter().cloned().collect::<HashMap<_, _>>())
+		};
+
+		let justification = GrandpaJustification::<Block>::decode_and_verify(encoded, set_id, &voters)?;
+
+		if justification.target() != (hash, number) {
+			return Err(::client::error::ErrorKind::BadJustification(
+				format!("GRANDPA justification does not finalize block {:?}", hash)
+			).into());
+		}
+
+		Ok(justification)
+	}
+
+	/// Finalize a block with a verified justification and apply the authority set changes it
+	/// enacts. Blocks are imported in order, so a justification of a block enacting a change
+	/// is verified against the old set and the set is switched before the next block comes in.
+	fn finalize_justified_block(
+		&self,
+		hash: Block::Hash,
+		number: NumberFor<Block>,
+		justification: Option<GrandpaJustification<Block>>,
+	) -> Result<(), ClientError> {
+		match finalize_block(&*self.inner, &self.authority_set, hash, number, justification) {
+			Ok(()) => Ok(()),
+			Err(ExitOrError::AuthoritiesChanged(new)) => {
+				info!(target: "afg", "Imported justification for block #{} enacting change to set {}",
+					number, new.set_id);
+
+				// the voter restarts with the new set. it is gone if it was never started,
+				// e.g. on non-authority nodes.
+				let _ = self.authority_set_change.unbounded_send(new);
+				Ok(())
+			},
+			Err(ExitOrError::Error(Error::Client(e))) => Err(e),
+			Err(ExitOrError::Error(e)) => Err(::client::error::ErrorKind::Backend(format!("{:?}", e)).into()),
+		}
 	}
 }
 
@@ -693,23 +1180,280 @@ where
 pub struct LinkHalf<B, E, Block: BlockT<Hash=H256>, RA> {
 	client: Arc<Client<B, E, Block, RA>>,
 	authority_set: SharedAuthoritySet<Block::Hash, NumberFor<Block>>,
+	authority_set_change: mpsc::UnboundedReceiver<NewAuthoritySet<Block::Hash, NumberFor<Block>>>,
 }
-impl<B, E, Block: BlockT<Hash=H256>, RA> Clone for LinkHalf<B, E, Block, RA>
-where
-	B: Backend<Block, Blake2Hasher> + 'static,
-	E: CallExecutor<Block, Blake2Hasher> + 'static + Clone + Send + Sync,
-	RA: TaggedTransactionQueue<Block>, // necessary for client to import `BlockImport`.
+
+/// The hash of the ancestor with the given number of a block, which need not be imported
+/// yet as long as its parent is. `None` if the block is not that high.
+fn ancestor_hash<B, E, Block: BlockT<Hash=H256>, RA>(
+	client: &Client<B, E, Block, RA>,
+	(hash, number): (Block::Hash, NumberFor<Block>),
+	parent_hash: Block::Hash,
+	ancestor_number: NumberFor<Block>,
+) -> Result<Option<Block::Hash>, ClientError> where
+	B: Backend<Block, Blake2Hasher>,
+	E: CallExecutor<Block, Blake2Hasher> + Send + Sync,
 {
-	fn clone(&self) -> Self {
-		LinkHalf {
-			client: self.client.clone(),
-			authority_set: self.authority_set.clone()
+	if ancestor_number > number {
+		return Ok(None);
+	}
+
+	if ancestor_number == number {
+		return Ok(Some(hash));
+	}
+
+	let mut current = parent_hash;
+	loop {
+		let header = match client.header(&BlockId::Hash(current))? {
+			Some(header) => header,
+			None => return Ok(None),
+		};
+
+		if *header.number() == ancestor_number {
+			return Ok(Some(current));
 		}
+
+		current = *header.parent_hash();
 	}
 }
 
+/// The encoded GRANDPA authority set of the node, i.e. the set id, its voters and the pending
+/// changes to it. Light clients can verify justifications from the last finalized block on
+/// with it.
+pub fn finality_sync_state<B, E, Block: BlockT<Hash=H256>, RA>(
+	client: &Client<B, E, Block, RA>,
+) -> Result<Vec<u8>, ClientError> where
+	B: Backend<Block, Blake2Hasher>,
+	E: CallExecutor<Block, Blake2Hasher>,
+{
+	let raw = client.backend().get_aux(AUTHORITY_SET_KEY)?
+		.ok_or_else(|| ::client::error::ErrorKind::Backend(
+			format!("No GRANDPA authority set stored yet")
+		))?;
+
+	::authorities::AuthoritySet::<Block::Hash, NumberFor<Block>>::decode(&mut &raw[..])
+		.ok_or_else(|| ::client::error::ErrorKind::Backend(
+			format!("GRANDPA authority set kept in invalid format")
+		))?;
+
+	Ok(raw)
+}
+
+/// The GRANDPA authority set enacted by the runtime at some block, with a read proof of it
+/// against the state root of that block.
+#[derive(Debug, Clone, Encode, Decode, PartialEq)]
+pub struct AuthoritySetProof {
+	/// The id of the set.
+	pub set_id: u64,
+	/// The voters of the set and their weights.
+	pub authorities: Vec<(AuthorityId, u64)>,
+	/// The trie nodes proving the set id and voters.
+	pub proof: Vec<Vec<u8>>,
+}
+
+/// Prove the GRANDPA authority set enacted by the runtime at the given block. Bridges and
+/// finality proof servers can hand this out instead of the chain of changes since genesis.
+///
+/// Fails if the runtime implements a version of the `GrandpaApi` before 2, or if it never
+/// initialised the set id. Chains started before the id was tracked have to initialise it with
+/// the set id of the voter, logged when the voter starts.
+pub fn prove_authority_set<B, E, Block: BlockT<Hash=H256>, RA, PRA>(
+	client: &Client<B, E, Block, RA>,
+	api: &PRA,
+	at: &BlockId<Block>,
+) -> Result<AuthoritySetProof, ClientError> where
+	B: Backend<Block, Blake2Hasher>,
+	E: CallExecutor<Block, Blake2Hasher>,
+	PRA: ProvideRuntimeApi,
+	PRA::Api: GrandpaApi<Block>,
+{
+	let runtime_api = api.runtime_api();
+	if !runtime_api.has_api_with::<GrandpaApi<Block>, _>(at, |v| v >= 2)? {
+		return Err(::client::error::ErrorKind::Msg(
+			format!("Runtime at {} can't prove its GRANDPA authority set", at)
+		).into());
+	}
+	let set_id = runtime_api.current_set_id(at)?
+		.ok_or_else(|| ::client::error::ErrorKind::Msg(
+			format!("Runtime at {} didn't initialise the GRANDPA set id", at)
+		))?;
+	let authorities = runtime_api.grandpa_authorities(at)?;
+
+	let mut seen = ::std::collections::HashSet::new();
+	let mut proof = Vec::new();
+	for key in runtime_api.authority_set_keys(at)? {
+		for node in client.read_proof(at, &key)? {
+			if seen.insert(node.clone()) {
+				proof.push(node);
+			}
+		}
+	}
+
+	Ok(AuthoritySetProof { set_id, authorities, proof })
+}
+
+/// Prove, at the given block, that the key of the offender of an equivocation is owned by a
+/// validator of the session of that block. Reporters submit the proof along with the
+/// equivocation so that the runtime can attribute it to the owner of the key.
+///
+/// Returns `None` if the runtime can't prove the ownership, e.g. if the offender is no
+/// validator anymore.
+pub fn prove_key_ownership<Block: BlockT, PRA>(
+	api: &PRA,
+	at: &BlockId<Block>,
+	equivocation: &EquivocationProof<Block::Hash, NumberFor<Block>>,
+) -> Result<Option<Vec<u8>>, ClientError> where
+	PRA: ProvideRuntimeApi,
+	PRA::Api: GrandpaApi<Block>,
+{
+	api.runtime_api().generate_key_ownership_proof(at, equivocation.offender.clone())
+}
+
+/// Load the numbers of the blocks after which a new authority set took over, in the order the
+/// changes were applied.
+fn load_set_changes<B, E, Block: BlockT<Hash=H256>, RA>(
+	client: &Client<B, E, Block, RA>,
+) -> Result<Vec<NumberFor<Block>>, ClientError> where
+	B: Backend<Block, Blake2Hasher>,
+	E: CallExecutor<Block, Blake2Hasher>,
+{
+	Ok(client.backend().get_aux(AUTHORITY_SET_CHANGES_KEY)?
+		.and_then(|encoded| Vec::decode(&mut &encoded[..]))
+		.unwrap_or_default())
+}
+
+/// Proof of finality of a block to a verifier following GRANDPA from an earlier finalized block.
+#[derive(Debug, Clone, Encode, Decode, PartialEq)]
+pub struct FinalityProof<Header> {
+	/// The hash of the block proven final.
+	pub block: H256,
+	/// The encoded GRANDPA justification of the block.
+	pub justification: Vec<u8>,
+	/// The headers the verifier doesn't know yet, from the child of its last finalized block up
+	/// to the block proven final.
+	pub unknown_headers: Vec<Header>,
+}
+
+/// Prove the finality of the blocks after `begin` up to `end`, both being finalized, to a
+/// verifier that knows the authority set of `begin`. Returns the hash of the block proven final
+/// along with the encoded `FinalityProof`.
+///
+/// The proof is made for the first block from `end` on with a stored justification. If the
+/// authority set changes before that block, it is made for the first block with a stored
+/// justification from the change on instead, which may come before `end`. That block is still
+/// finalized by the set of `begin`. The verifier then has to learn the new set, e.g. through
+/// `prove_authority_set`, and ask for the rest of the range.
+///
+/// A proof includes at most `MAX_FINALITY_PROOF_HEADERS` headers. If `end` lies further away,
+/// the proof is made for the first block with a stored justification within that many blocks
+/// after `begin`, and the verifier asks for the rest of the range from there.
+///
+/// Returns `None` if `end` is not finalized yet, doesn't come after `begin` or no block can be
+/// proven final yet.
+pub fn prove_finality<B, E, Block: BlockT<Hash=H256>, RA>(
+	client: &Client<B, E, Block, RA>,
+	begin: Block::Hash,
+	end: Block::Hash,
+) -> Result<Option<(Block::Hash, Vec<u8>)>, ClientError> where
+	B: Backend<Block, Blake2Hasher>,
+	E: CallExecutor<Block, Blake2Hasher>,
+{
+	use runtime_primitives::traits::One;
+
+	let number_of = |hash: Block::Hash| -> Result<Option<NumberFor<Block>>, ClientError> {
+		Ok(client.header(&BlockId::Hash(hash))?.map(|header| *header.number()))
+	};
+
+	let finalized_number = client.info()?.chain.finalized_number;
+	let (begin_number, end_number) = match (number_of(begin)?, number_of(end)?) {
+		(Some(begin_number), Some(end_number)) => (begin_number, end_number),
+		_ => return Ok(None),
+	};
+	if begin_number >= end_number || end_number > finalized_number {
+		return Ok(None);
+	}
+	if client.block_hash_from_id(&BlockId::Number(begin_number))? != Some(begin) {
+		return Err(::client::error::ErrorKind::Msg(
+			format!("Block {:?} is not finalized", begin)
+		).into());
+	}
+
+	// the first block after `begin` enacting a change is the last one finalized by its set.
+	let next_set_change = load_set_changes(client)?.into_iter()
+		.filter(|change| *change > begin_number)
+		.min();
+	let window_end = begin_number + As::sa(MAX_FINALITY_PROOF_HEADERS as u64);
+
+	let mut unknown_headers = Vec::new();
+	let mut number = begin_number + One::one();
+	while number <= finalized_number && number <= window_end {
+		let header = client.header(&BlockId::Number(number))?
+			.ok_or_else(|| ::client::error::ErrorKind::UnknownBlock(format!("{}", number)))?;
+		let hash = header.hash();
+		unknown_headers.push(header);
+
+		let set_changed = next_set_change.map_or(false, |change| number >= change);
+		if number >= end_number || end_number > window_end || set_changed {
+			let justification = client.justification(&BlockId::Hash(hash))?
+				.and_then(|justification| justification_for_engine(&justification, &GRANDPA_ENGINE_ID).cloned());
+			if let Some(justification) = justification {
+				let proof = FinalityProof { block: hash, justification, unknown_headers };
+				return Ok(Some((hash, proof.encode())));
+			}
+		}
+
+		number += One::one();
+	}
+
+	Ok(None)
+}
+
+/// Migrate the data kept in the aux db by a node started with an earlier version of its layout
+/// to the current one.
+fn migrate_aux_schema<B, E, Block: BlockT<Hash=H256>, RA>(
+	client: &Client<B, E, Block, RA>,
+) -> Result<(), ClientError> where
+	B: Backend<Block, Blake2Hasher>,
+	E: CallExecutor<Block, Blake2Hasher>,
+{
+	let version = match client.backend().get_aux(VERSION_KEY)? {
+		Some(raw) => u32::decode(&mut &raw[..])
+			.ok_or_else(|| ::client::error::ErrorKind::Backend(
+				format!("GRANDPA schema version kept in invalid format")
+			))?,
+		None => 0,
+	};
+
+	match version {
+		CURRENT_VERSION => return Ok(()),
+		0 => {},
+		_ => return Err(::client::error::ErrorKind::Backend(
+			format!("Unsupported GRANDPA schema version {}", version)
+		).into()),
+	}
+
+	let mut updates = Vec::new();
+	if let Some(raw) = client.backend().get_aux(AUTHORITY_SET_KEY)? {
+		info!(target: "afg", "Migrating the GRANDPA authority set to schema version {}", CURRENT_VERSION);
+
+		let set: ::authorities::AuthoritySet<Block::Hash, NumberFor<Block>> =
+			::authorities::OldAuthoritySet::decode(&mut &raw[..])
+				.ok_or_else(|| ::client::error::ErrorKind::Backend(
+					format!("GRANDPA authority set kept in invalid format")
+				))?
+				.into();
+		updates.push((AUTHORITY_SET_KEY, set.encode()));
+	}
+	updates.push((VERSION_KEY, CURRENT_VERSION.encode()));
+
+	let updates: Vec<_> = updates.iter().map(|&(key, ref value)| (key, &value[..])).collect();
+	client.backend().insert_aux(&updates, &[])
+}
+
 /// Make block importer and link half necessary to tie the background voter
 /// to it.
+///
+/// Migrates the data kept by nodes started with an earlier version of GRANDPA first.
 pub fn block_import<B, E, Block: BlockT<Hash=H256>, RA, PRA>(
 	client: Arc<Client<B, E, Block, RA>>,
 	api: Arc<PRA>
@@ -722,6 +1466,9 @@ pub fn block_import<B, E, Block: BlockT<Hash=H256>, RA, PRA>(
 		PRA::Api: GrandpaApi<Block>
 {
 	use runtime_primitives::traits::Zero;
+
+	migrate_aux_schema(&*client)?;
+
 	let authority_set = match client.backend().get_aux(AUTHORITY_SET_KEY)? {
 		None => {
 			info!(target: "afg", "Loading GRANDPA authorities \
@@ -732,6 +1479,7 @@ pub fn block_import<B, E, Block: BlockT<Hash=H256>, RA, PRA>(
 			// are unsupported for following GRANDPA directly.
 			let genesis_authorities = api.runtime_api()
 				.grandpa_authorities(&BlockId::number(Zero::zero()))?;
+			check_voter_set(&genesis_authorities)?;
 
 			let authority_set = SharedAuthoritySet::genesis(genesis_authorities);
 			let encoded = authority_set.inner().read().encode();
@@ -746,13 +1494,16 @@ pub fn block_import<B, E, Block: BlockT<Hash=H256>, RA, PRA>(
 			.into(),
 	};
 
+	let (authority_set_change_tx, authority_set_change_rx) = mpsc::unbounded();
+
 	Ok((
 		GrandpaBlockImport {
 			inner: client.clone(),
 			authority_set: authority_set.clone(),
+			authority_set_change: authority_set_change_tx,
 			api
 		},
-		LinkHalf { client, authority_set },
+		LinkHalf { client, authority_set, authority_set_change: authority_set_change_rx },
 	))
 }
 
@@ -761,6 +1512,7 @@ fn committer_communication<Block: BlockT<Hash=H256>, B, E, N, RA>(
 	voters: &Arc<HashMap<AuthorityId, u64>>,
 	client: &Arc<Client<B, E, Block, RA>>,
 	network: &N,
+	commit_latency: &Arc<CommitLatency<H256, NumberFor<Block>>>,
 ) -> (
 	impl Stream<
 		Item = (u64, ::grandpa::CompactCommit<H256, NumberFor<Block>, ed25519::Signature, AuthorityId>),
@@ -784,6 +1536,15 @@ fn committer_communication<Block: BlockT<Hash=H256>, B, E, N, RA>(
 		voters.clone(),
 	);
 
+	// commits are timed from when they are first seen, before waiting for their blocks,
+	// which are downloaded ahead of the best chain.
+	let latency = commit_latency.clone();
+	let sync_network = network.clone();
+	let commit_in = commit_in.inspect(move |&(_, ref commit)| {
+		latency.note_commit(commit.target_hash, commit.target_number);
+		sync_network.sync_finality_target(commit.target_hash, commit.target_number.as_());
+	});
+
 	// block commit messages until relevant blocks are imported.
 	let commit_in = UntilCommitBlocksImported::new(
 		client.import_notification_stream(),
@@ -791,10 +1552,14 @@ fn committer_communication<Block: BlockT<Hash=H256>, B, E, N, RA>(
 		commit_in,
 	);
 
+	let latency = commit_latency.clone();
 	let commit_out = ::communication::CommitsOut::<Block, _>::new(
 		network.clone(),
 		set_id,
-	);
+	).with(move |(round, commit): (u64, Commit<Block>)| {
+		latency.note_commit(commit.target_hash, commit.target_number);
+		Ok::<_, Error>((round, commit))
+	});
 
 	let commit_in = commit_in.map_err(Into::into);
 	let commit_out = commit_out.sink_map_err(Into::into);
@@ -802,10 +1567,42 @@ fn committer_communication<Block: BlockT<Hash=H256>, B, E, N, RA>(
 	(commit_in, commit_out)
 }
 
+/// Ensure GRANDPA can vote with the given authorities and weights.
+fn check_voter_set(voters: &[(AuthorityId, u64)]) -> Result<(), ClientError> {
+	match ::authorities::voter_set_threshold(voters.iter().map(|(id, weight)| (id, *weight))) {
+		Some(_) => Ok(()),
+		None => Err(::client::error::ErrorKind::Msg(
+			format!("Invalid GRANDPA voter set: {:?}", voters)
+		).into()),
+	}
+}
+
+/// Whether a voter set scheduled by the runtime in the block with the given hash can vote, logging
+/// it if it can't.
+fn is_valid_scheduled_set<H: ::std::fmt::Debug>(voters: &[(AuthorityId, u64)], hash: &H) -> bool {
+	match check_voter_set(voters) {
+		Ok(()) => true,
+		Err(e) => {
+			warn!(target: "afg", "Ignoring GRANDPA authority set change scheduled in block {:?}: {}", hash, e);
+			false
+		}
+	}
+}
+
+/// Whether the local key of the given configuration is part of the given authority set.
+fn is_voter<Block: BlockT>(config: &Config<Block>, voters: &HashMap<AuthorityId, u64>) -> bool {
+	config.local_key.as_ref()
+		.map_or(false, |pair| voters.contains_key(&AuthorityId(pair.public().0)))
+}
+
 /// Run a GRANDPA voter as a task. Provide configuration and a link to a
 /// block import worker that has already been instantiated with `block_import`.
+///
+/// While the local key is not part of the current authority set, only an observer of
+/// the set's commit messages is run. The voter is started as soon as a set containing
+/// the local key is enacted, be it on finality or through a forced change on import.
 pub fn run_grandpa<B, E, Block: BlockT<Hash=H256>, N, RA>(
-	config: Config,
+	config: Config<Block>,
 	link: LinkHalf<B, E, Block, RA>,
 	network: N,
 ) -> ::client::error::Result<impl Future<Item=(),Error=()> + Send + 'static> where
@@ -821,7 +1618,9 @@ pub fn run_grandpa<B, E, Block: BlockT<Hash=H256>, N, RA>(
 	use futures::future::{self, Loop as FutureLoop};
 	use runtime_primitives::traits::Zero;
 
-	let LinkHalf { client, authority_set } = link;
+	config.check().map_err(::client::error::ErrorKind::Msg)?;
+
+	let LinkHalf { client, authority_set, authority_set_change } = link;
 	let chain_info = client.info()?;
 	let genesis_hash = chain_info.chain.genesis_hash;
 
@@ -837,6 +1636,8 @@ pub fn run_grandpa<B, E, Block: BlockT<Hash=H256>, N, RA>(
 		.cloned()
 		.collect();
 
+	let commit_latency = Arc::new(CommitLatency::new());
+
 	let initial_environment = Arc::new(Environment {
 		inner: client.clone(),
 		config: config.clone(),
@@ -844,11 +1645,19 @@ pub fn run_grandpa<B, E, Block: BlockT<Hash=H256>, N, RA>(
 		network: network.clone(),
 		set_id: authority_set.set_id(),
 		authority_set: authority_set.clone(),
+		commit_latency: commit_latency.clone(),
 	});
 
-	let work = future::loop_fn((initial_environment, last_round_number, last_state), move |params| {
-		let (env, last_round_number, last_state) = params;
-		debug!(target: "afg", "{}: Starting new voter with set ID {}", config.name(), env.set_id);
+	let initial_state = (
+		initial_environment,
+		last_round_number,
+		last_state,
+		authority_set_change.into_future(),
+	);
+
+	let work = future::loop_fn(initial_state, move |params| {
+		let (env, last_round_number, last_state, authority_set_change) = params;
+		info!(target: "afg", "{}: Starting new voter with set ID {}", config.name(), env.set_id);
 
 		let chain_info = match client.info() {
 			Ok(i) => i,
@@ -860,49 +1669,80 @@ pub fn run_grandpa<B, E, Block: BlockT<Hash=H256>, N, RA>(
 			chain_info.chain.finalized_number,
 		);
 
-		let committer_data = committer_communication(
-			env.set_id,
-			&env.voters,
-			&client,
-			&network,
-		);
-
-		let voters = (*env.voters).clone();
+		let voter_or_observer = if is_voter(&config, &env.voters) {
+			let committer_data = committer_communication(
+				env.set_id,
+				&env.voters,
+				&client,
+				&network,
+				&env.commit_latency,
+			);
+
+			let voters = (*env.voters).clone();
+
+			future::Either::A(voter::Voter::new(
+				env,
+				voters,
+				committer_data,
+				last_round_number,
+				last_state,
+				last_finalized,
+			))
+		} else {
+			debug!(target: "afg", "{}: Local key not in set {}, observing commits only", config.name(), env.set_id);
+
+			future::Either::B(observer::observe(
+				client.clone(),
+				authority_set.clone(),
+				env.set_id,
+				env.voters.clone(),
+				&network,
+				env.commit_latency.clone(),
+			))
+		};
 
-		let voter = voter::Voter::new(
-			env,
-			voters,
-			committer_data,
-			last_round_number,
-			last_state,
-			last_finalized,
-		);
 		let client = client.clone();
 		let config = config.clone();
 		let network = network.clone();
 		let authority_set = authority_set.clone();
-		future::Either::A(voter.then(move |res| match res {
-			// voters don't conclude naturally; this could reasonably be an error.
-			Ok(()) => Ok(FutureLoop::Break(())),
-			Err(ExitOrError::Error(e)) => Err(e),
-			Err(ExitOrError::AuthoritiesChanged(new)) => {
-				let env = Arc::new(Environment {
-					inner: client,
-					config,
-					voters: Arc::new(new.authorities.into_iter().collect()),
-					set_id: new.set_id,
-					network,
-					authority_set,
-				});
-
-				// start the new authority set using the block where the
-				// set changed (not where the signal happened!) as the base.
-				Ok(FutureLoop::Continue((
-					env,
-					0, // always start at round 0 when changing sets.
-					RoundState::genesis((new.canon_hash, new.canon_number)),
-				)))
+		let commit_latency = commit_latency.clone();
+		let restart = move |new: NewAuthoritySet<_, _>, authority_set_change|
+			-> Result<FutureLoop<(), _>, Error>
+		{
+			let env = Arc::new(Environment {
+				inner: client,
+				config,
+				voters: Arc::new(new.authorities.into_iter().collect()),
+				set_id: new.set_id,
+				network,
+				authority_set,
+				commit_latency,
+			});
+
+			// start the new authority set using the block where the
+			// set changed (not where the signal happened!) as the base.
+			Ok(FutureLoop::Continue((
+				env,
+				0, // always start at round 0 when changing sets.
+				RoundState::genesis((new.canon_hash, new.canon_number)),
+				authority_set_change,
+			)))
+		};
+
+		// forced changes are enacted by block import and interrupt the current voter.
+		future::Either::A(voter_or_observer.select2(authority_set_change).then(move |res| match res {
+			// voters and observers don't conclude naturally; this could reasonably be an error.
+			Ok(future::Either::A(((), _))) => Ok(FutureLoop::Break(())),
+			Err(future::Either::A((ExitOrError::Error(e), _))) => Err(e),
+			Err(future::Either::A((ExitOrError::AuthoritiesChanged(new), authority_set_change))) =>
+				restart(new, authority_set_change),
+			Ok(future::Either::B(((Some(new), authority_set_change), _))) => {
+				debug!(target: "afg", "Restarting voter with forced set {}", new.set_id);
+				restart(new, authority_set_change.into_future())
 			}
+			// the block import is gone, and unbounded receivers don't fail.
+			Ok(future::Either::B(((None, _), _))) | Err(future::Either::B(_)) =>
+				Ok(FutureLoop::Break(())),
 		}))
 	});
 
@@ -0,0 +1,192 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Latency between a commit being created by the local voter or first seen on the network,
+//! and the block it commits to being finalized locally.
+//!
+//! Voters finalize blocks as soon as enough precommits for them are seen, before they create
+//! or receive a commit. They time blocks from the first precommit seen for them instead.
+//!
+//! Latencies are collected into a histogram with fixed buckets which is reported through
+//! telemetry on every finalization, so that regressions of finality latency across the
+//! network become visible.
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+
+/// Upper bounds of the histogram buckets, in milliseconds. Latencies above the last bound
+/// are counted in an additional bucket.
+pub(crate) const BUCKET_BOUNDS_MS: [u64; 8] = [100, 250, 500, 1_000, 2_500, 5_000, 10_000, 30_000];
+
+/// A histogram of latencies with the buckets of `BUCKET_BOUNDS_MS`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Histogram {
+	/// The number of latencies in every bucket; the last one counts those above all bounds.
+	pub(crate) buckets: [u64; 9],
+	/// The number of latencies observed.
+	pub(crate) count: u64,
+	/// The sum of all latencies observed, in milliseconds.
+	pub(crate) sum_ms: u64,
+}
+
+impl Histogram {
+	/// Add a latency to the histogram.
+	pub(crate) fn observe(&mut self, latency: Duration) {
+		let ms = as_millis(latency);
+		let bucket = BUCKET_BOUNDS_MS.iter()
+			.position(|&bound| ms <= bound)
+			.unwrap_or(BUCKET_BOUNDS_MS.len());
+
+		self.buckets[bucket] += 1;
+		self.count += 1;
+		self.sum_ms = self.sum_ms.saturating_add(ms);
+	}
+}
+
+fn as_millis(duration: Duration) -> u64 {
+	duration.as_secs().saturating_mul(1_000) + u64::from(duration.subsec_millis())
+}
+
+/// Commits are only tracked until this many targets are pending finalization; a node that
+/// lags behind doesn't keep every commit it has seen around.
+const MAX_PENDING: usize = 256;
+
+/// Tracks when commits were first seen, until the blocks they commit to are finalized.
+pub(crate) struct CommitLatency<H, N> {
+	pending: Mutex<HashMap<H, (N, Instant)>>,
+	histogram: Mutex<Histogram>,
+}
+
+impl<H: Hash + Eq, N: PartialOrd + Copy> CommitLatency<H, N> {
+	pub(crate) fn new() -> Self {
+		CommitLatency {
+			pending: Mutex::new(HashMap::new()),
+			histogram: Mutex::new(Histogram::default()),
+		}
+	}
+
+	/// Note a commit for the given target, created locally or received from the network.
+	/// Only the first commit seen for a target counts.
+	pub(crate) fn note_commit(&self, hash: H, number: N) {
+		let mut pending = self.pending.lock();
+		if pending.len() >= MAX_PENDING && !pending.contains_key(&hash) {
+			return;
+		}
+
+		pending.entry(hash).or_insert_with(|| (number, Instant::now()));
+	}
+
+	/// Note a vote of a round the local voter takes part in, created locally or received from
+	/// the network. Precommits count like commits for their target, prevotes are ignored.
+	pub(crate) fn note_vote(&self, message: &::grandpa::Message<H, N>) where H: Clone {
+		if let ::grandpa::Message::Precommit(ref precommit) = *message {
+			self.note_commit(precommit.target_hash.clone(), precommit.target_number);
+		}
+	}
+
+	/// Note that the given block was finalized. Returns the latency since a commit for it was
+	/// first seen, if any was, together with the histogram of all latencies so far.
+	///
+	/// Commits targeting the block or any lower block are no longer tracked afterwards.
+	pub(crate) fn note_finalized(&self, hash: &H, number: N) -> Option<(Duration, Histogram)> {
+		let seen = {
+			let mut pending = self.pending.lock();
+			let seen = pending.remove(hash).map(|(_, seen)| seen);
+			pending.retain(|_, &mut (target_number, _)| target_number > number);
+			seen
+		};
+
+		seen.map(|seen| {
+			let latency = seen.elapsed();
+			let mut histogram = self.histogram.lock();
+			histogram.observe(latency);
+			(latency, histogram.clone())
+		})
+	}
+}
+
+impl<H: Hash + Eq + Debug, N: PartialOrd + Copy + Debug> CommitLatency<H, N> {
+	/// Note that the given block was finalized and report the latency since a commit for it
+	/// was first seen through telemetry.
+	pub(crate) fn report_finalized(&self, hash: &H, number: N) {
+		if let Some((latency, histogram)) = self.note_finalized(hash, number) {
+			let latency_ms = as_millis(latency);
+			debug!(target: "afg", "Finalized block {:?} {}ms after first seeing a commit for it", (hash, number), latency_ms);
+			telemetry!("afg.finality_latency";
+				"number" => ?number,
+				"hash" => ?hash,
+				"latency_ms" => latency_ms,
+				"buckets" => ?histogram.buckets,
+				"count" => histogram.count,
+				"sum_ms" => histogram.sum_ms
+			);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn latencies_are_sorted_into_buckets() {
+		let mut histogram = Histogram::default();
+		histogram.observe(Duration::from_millis(100));
+		histogram.observe(Duration::from_millis(101));
+		histogram.observe(Duration::from_secs(3));
+		histogram.observe(Duration::from_secs(60));
+
+		assert_eq!(histogram.buckets, [1, 1, 0, 0, 0, 1, 0, 0, 1]);
+		assert_eq!(histogram.count, 4);
+		assert_eq!(histogram.sum_ms, 63_201);
+	}
+
+	#[test]
+	fn only_commits_seen_before_finalization_are_measured() {
+		let latency = CommitLatency::<u64, u64>::new();
+		latency.note_commit(10, 5);
+		latency.note_commit(11, 6);
+		latency.note_commit(12, 7);
+
+		// not seen.
+		assert!(latency.note_finalized(&9, 4).is_none());
+
+		let (_, histogram) = latency.note_finalized(&11, 6).unwrap();
+		assert_eq!(histogram.count, 1);
+
+		// the commit for the lower block is dropped, the higher one still measured.
+		assert!(latency.note_finalized(&10, 5).is_none());
+		let (_, histogram) = latency.note_finalized(&12, 7).unwrap();
+		assert_eq!(histogram.count, 2);
+	}
+
+	#[test]
+	fn precommits_are_measured_like_commits() {
+		use grandpa::{Message, Precommit, Prevote};
+
+		let latency = CommitLatency::<u64, u64>::new();
+		latency.note_vote(&Message::Prevote(Prevote { target_hash: 10, target_number: 5 }));
+		latency.note_vote(&Message::Precommit(Precommit { target_hash: 11, target_number: 6 }));
+
+		assert!(latency.note_finalized(&10, 5).is_none());
+		let (_, histogram) = latency.note_finalized(&11, 6).unwrap();
+		assert_eq!(histogram.count, 1);
+	}
+}
@@ -0,0 +1,107 @@
+// Copyright 2018 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Following GRANDPA finality without voting, for nodes that are not part of the
+//! current authority set.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use futures::prelude::*;
+use client::{Client, CallExecutor, BlockchainEvents, backend::Backend};
+use grandpa::{self, BlockNumberOps};
+use runtime_primitives::traits::{NumberFor, Block as BlockT, As};
+use substrate_primitives::{H256, AuthorityId, Blake2Hasher};
+
+use {Commit, ExitOrError, Network, finalize_block};
+use authorities::SharedAuthoritySet;
+use justification::GrandpaJustification;
+use metrics::CommitLatency;
+use until_imported::UntilCommitBlocksImported;
+
+/// Observe the commit messages of the given authority set and finalize the blocks they
+/// commit to. Commits are only acted upon once all the blocks they reference are imported
+/// and they are verified to be signed by a supermajority of the set.
+///
+/// Resolves with `ExitOrError::AuthoritiesChanged` once a block enacting an authority set
+/// change is finalized, as commits of the next set must be observed from then on.
+pub(crate) fn observe<B, E, Block: BlockT<Hash=H256>, N, RA>(
+	client: Arc<Client<B, E, Block, RA>>,
+	authority_set: SharedAuthoritySet<Block::Hash, NumberFor<Block>>,
+	set_id: u64,
+	voters: Arc<HashMap<AuthorityId, u64>>,
+	network: &N,
+	commit_latency: Arc<CommitLatency<H256, NumberFor<Block>>>,
+) -> impl Future<Item=(), Error=ExitOrError<H256, NumberFor<Block>>> where
+	B: Backend<Block, Blake2Hasher>,
+	E: CallExecutor<Block, Blake2Hasher> + Send + Sync,
+	N: Network,
+	RA: Send + Sync,
+	NumberFor<Block>: BlockNumberOps,
+{
+	// only the signatures are checked here.
+	let commits = ::communication::checked_commit_stream::<Block, _>(
+		set_id,
+		network.commit_messages(set_id),
+		voters.clone(),
+	);
+
+	let latency = commit_latency.clone();
+	let sync_network = network.clone();
+	let commits = commits.inspect(move |&(_, ref commit)| {
+		latency.note_commit(commit.target_hash, commit.target_number);
+		sync_network.sync_finality_target(commit.target_hash, commit.target_number.as_());
+	});
+
+	let commits = UntilCommitBlocksImported::new(
+		client.import_notification_stream(),
+		client.clone(),
+		commits,
+	);
+
+	commits.map_err(Into::into).for_each(move |(round, commit)| {
+		let finalized_number = client.info()?.chain.finalized_number;
+		if commit.target_number <= finalized_number {
+			return Ok(());
+		}
+
+		let commit = Commit::<Block> {
+			target_hash: commit.target_hash,
+			target_number: commit.target_number,
+			precommits: commit.precommits.into_iter()
+				.zip(commit.auth_data)
+				.map(|(precommit, (signature, id))| grandpa::SignedPrecommit { precommit, signature, id })
+				.collect(),
+		};
+
+		let (hash, number) = (commit.target_hash, commit.target_number);
+		let justification = GrandpaJustification::from_commit(&client, round, commit)
+			.and_then(|justification| justification.verify(set_id, &voters).map(|()| justification));
+
+		match justification {
+			Ok(justification) => {
+				debug!(target: "afg", "Observed commit for block {:?} in round {}", (hash, number), round);
+				finalize_block(&*client, &authority_set, hash, number, Some(justification))?;
+				commit_latency.report_finalized(&hash, number);
+				Ok(())
+			}
+			Err(e) => {
+				debug!(target: "afg", "Ignoring invalid commit for block {:?}: {:?}", (hash, number), e);
+				Ok(())
+			}
+		}
+	})
+}
@@ -18,19 +18,16 @@
 
 use super::*;
 use network::test::{Block, Hash, TestNetFactory, Peer, PeersClient};
-use network::import_queue::{PassThroughVerifier};
+use network::import_queue::{PassThroughVerifier, SharedBlockImport, SharedJustificationImport};
 use network::config::{ProtocolConfig, Roles};
 use parking_lot::Mutex;
 use tokio::runtime::current_thread;
 use keyring::Keyring;
-use client::{
-	BlockchainEvents, runtime_api::{Core, RuntimeVersion, ApiExt, ConstructRuntimeApi, CallApiAt},
-	error::Result
-};
+use client::BlockchainEvents;
 use test_client::{self, runtime::BlockNumber};
 use codec::Decode;
 use consensus_common::BlockOrigin;
-use std::{collections::HashSet, result};
+use std::collections::HashSet;
 use runtime_primitives::traits::{ApiRef, ProvideRuntimeApi};
 use runtime_primitives::generic::BlockId;
 
@@ -89,6 +86,7 @@ impl TestNetFactory for GrandpaTestNet {
 		// the authority role ensures gossip hits all nodes here.
 		ProtocolConfig {
 			roles: Roles::AUTHORITY,
+			..Default::default()
 		}
 	}
 
@@ -99,13 +97,14 @@ impl TestNetFactory for GrandpaTestNet {
 	}
 
 	fn make_block_import(&self, client: Arc<PeersClient>)
-		-> (Arc<BlockImport<Block,Error=ClientError> + Send + Sync>, PeerData)
+		-> (SharedBlockImport<Block>, Option<SharedJustificationImport<Block>>, PeerData)
 	{
 		let (import, link) = block_import(
 			client,
 			Arc::new(self.test_config.clone())
 		).expect("Could not create block import for fresh peer.");
-		(Arc::new(import), Mutex::new(Some(link)))
+		let shared_import = Arc::new(import);
+		(shared_import.clone(), Some(shared_import), Mutex::new(Some(link)))
 	}
 
 	fn peer(&self, i: usize) -> &GrandpaPeer {
@@ -196,16 +195,6 @@ impl Network for MessageRouting {
 		inner.route_until_complete();
 	}
 
-	fn drop_messages(&self, round: u64, set_id: u64) {
-		let topic = make_topic(round, set_id);
-		let inner = self.inner.lock();
-		let peer = inner.peer(self.peer_id);
-		let mut gossip = peer.consensus_gossip().write();
-		peer.with_spec(move |_, _| {
-			gossip.collect_garbage(|t| t == &topic)
-		});
-	}
-
 	fn commit_messages(&self, set_id: u64) -> Self::In {
 		let inner = self.inner.lock();
 		let peer = inner.peer(self.peer_id);
@@ -226,12 +215,19 @@ impl Network for MessageRouting {
 		inner.peer(self.peer_id).gossip_message(make_commit_topic(set_id), message);
 		inner.route_until_complete();
 	}
+
+	fn sync_finality_target(&self, _hash: H256, _number: u64) {}
+
+	fn note_commit_finalized(&self, _set_id: u64, _number: u64) {}
 }
 
 #[derive(Default, Clone)]
 struct TestApi {
 	genesis_authorities: Vec<(AuthorityId, u64)>,
 	scheduled_changes: Arc<Mutex<HashMap<Hash, ScheduledChange<BlockNumber>>>>,
+	forced_changes: Arc<Mutex<HashMap<Hash, (BlockNumber, ScheduledChange<BlockNumber>)>>>,
+	set_id: Option<u64>,
+	authority_set_keys: Vec<Vec<u8>>,
 }
 
 impl TestApi {
@@ -239,6 +235,9 @@ impl TestApi {
 		TestApi {
 			genesis_authorities,
 			scheduled_changes: Arc::new(Mutex::new(HashMap::new())),
+			forced_changes: Arc::new(Mutex::new(HashMap::new())),
+			set_id: Some(0),
+			authority_set_keys: Vec::new(),
 		}
 	}
 }
@@ -255,66 +254,51 @@ impl ProvideRuntimeApi for TestApi {
 	}
 }
 
-impl Core<Block> for RuntimeApi {
-	fn version(&self, _: &BlockId<Block>) -> Result<RuntimeVersion> {
-		unimplemented!("Not required for testing!")
-	}
-
-	fn authorities(&self, _: &BlockId<Block>) -> Result<Vec<AuthorityId>> {
-		unimplemented!("Not required for testing!")
-	}
+mock_impl_runtime_apis! {
+	impl GrandpaApi<Block> for RuntimeApi {
+		fn grandpa_authorities() -> Vec<(AuthorityId, u64)> {
+			if at == &BlockId::Number(0) {
+				self.inner.genesis_authorities.clone()
+			} else {
+				panic!("should generally only request genesis authorities")
+			}
+		}
 
-	fn execute_block(&self, _: &BlockId<Block>, _: &Block) -> Result<()> {
-		unimplemented!("Not required for testing!")
-	}
+		fn grandpa_pending_change(_digest: DigestFor<Block>)
+			-> Option<ScheduledChange<NumberFor<Block>>>
+		{
+			let parent_hash = match at {
+				&BlockId::Hash(at) => at,
+				_ => panic!("not requested by block hash!!"),
+			};
+
+			// we take only scheduled changes at given block number where there are no
+			// extrinsics.
+			self.inner.scheduled_changes.lock().get(&parent_hash).map(|c| c.clone())
+		}
 
-	fn initialise_block(
-		&self,
-		_: &BlockId<Block>,
-		_: &<Block as BlockT>::Header
-	) -> Result<()> {
-		unimplemented!("Not required for testing!")
-	}
-}
+		fn grandpa_forced_change(_digest: DigestFor<Block>)
+			-> Option<(NumberFor<Block>, ScheduledChange<NumberFor<Block>>)>
+		{
+			let parent_hash = match at {
+				&BlockId::Hash(at) => at,
+				_ => panic!("not requested by block hash!!"),
+			};
 
-impl ApiExt for RuntimeApi {
-	fn map_api_result<F: FnOnce(&Self) -> result::Result<R, E>, R, E>(
-		&self,
-		_: F
-	) -> result::Result<R, E> {
-		unimplemented!("Not required for testing!")
-	}
-}
-
-impl ConstructRuntimeApi<Block> for RuntimeApi {
-	fn construct_runtime_api<'a, T: CallApiAt<Block>>(_: &'a T) -> ApiRef<'a, Self> {
-		unimplemented!("Not required for testing!")
-	}
-}
+			self.inner.forced_changes.lock().get(&parent_hash).map(|c| c.clone())
+		}
 
-impl GrandpaApi<Block> for RuntimeApi {
-	fn grandpa_authorities(
-		&self,
-		at: &BlockId<Block>
-	) -> Result<Vec<(AuthorityId, u64)>> {
-		if at == &BlockId::Number(0) {
-			Ok(self.inner.genesis_authorities.clone())
-		} else {
-			panic!("should generally only request genesis authorities")
+		fn current_set_id() -> Option<u64> {
+			self.inner.set_id
 		}
-	}
 
-	fn grandpa_pending_change(&self, at: &BlockId<Block>, _: &DigestFor<Block>)
-		-> Result<Option<ScheduledChange<NumberFor<Block>>>>
-	{
-		let parent_hash = match at {
-			&BlockId::Hash(at) => at,
-			_ => panic!("not requested by block hash!!"),
-		};
+		fn authority_set_keys() -> Vec<Vec<u8>> {
+			self.inner.authority_set_keys.clone()
+		}
 
-		// we take only scheduled changes at given block number where there are no
-		// extrinsics.
-		Ok(self.inner.scheduled_changes.lock().get(&parent_hash).map(|c| c.clone()))
+		fn generate_key_ownership_proof(_authority: AuthorityId) -> Option<Vec<u8>> {
+			None
+		}
 	}
 }
 
@@ -367,8 +351,10 @@ fn finalize_3_voters_no_observers() {
 		let voter = run_grandpa(
 			Config {
 				gossip_duration: TEST_GOSSIP_DURATION,
+				round_timers: Default::default(),
 				local_key: Some(Arc::new(key.clone().into())),
 				name: Some(format!("peer#{}", peer_id)),
+				equivocation_reporter: None,
 			},
 			link,
 			MessageRouting::new(net.clone(), peer_id),
@@ -384,12 +370,86 @@ fn finalize_3_voters_no_observers() {
 		.map(|_| ())
 		.map_err(|_| ());
 
-	let drive_to_completion = ::tokio::timer::Interval::new_interval(TEST_ROUTING_INTERVAL)
-		.for_each(move |_| { net.lock().route_until_complete(); Ok(()) })
-		.map(|_| ())
-		.map_err(|_| ());
+	let drive_to_completion = {
+		let net = net.clone();
+		::tokio::timer::Interval::new_interval(TEST_ROUTING_INTERVAL)
+			.for_each(move |_| { net.lock().route_until_complete(); Ok(()) })
+			.map(|_| ())
+			.map_err(|_| ())
+	};
 
 	runtime.block_on(wait_for.select(drive_to_completion).map_err(|_| ())).unwrap();
+
+	// the finalized block is stored with a GRANDPA justification.
+	let voters: HashMap<_, _> = make_ids(peers).into_iter().collect();
+	for i in 0..3 {
+		let justification = net.lock().peer(i).client()
+			.justification(&BlockId::Number(20)).unwrap()
+			.expect("block 20 finalized with a justification; qed");
+		let encoded = justification_for_engine(&justification, &GRANDPA_ENGINE_ID)
+			.expect("justification contains a GRANDPA entry; qed");
+
+		let justification = GrandpaJustification::<Block>::decode_and_verify(encoded, 0, &voters).unwrap();
+		assert_eq!(justification.target().1, 20);
+	}
+}
+
+#[test]
+fn finalize_with_one_voter_holding_the_threshold_weight() {
+	let peers = &[Keyring::Alice, Keyring::Bob, Keyring::Charlie];
+	// out of a total weight of 7, Alice alone reaches the threshold of 5.
+	let voters: Vec<_> = make_ids(peers).into_iter()
+		.zip(&[5, 1, 1])
+		.map(|((id, _), weight)| (id, *weight))
+		.collect();
+
+	let mut net = GrandpaTestNet::new(TestApi::new(voters.clone()), 3);
+	net.peer(0).push_blocks(20, false);
+	net.sync();
+
+	let net = Arc::new(Mutex::new(net));
+	let mut runtime = current_thread::Runtime::new().unwrap();
+
+	let (client, link) = {
+		let mut net = net.lock();
+		let link = net.peers[0].data.lock().take().expect("link initialized at startup; qed");
+		(net.peers[0].client().clone(), link)
+	};
+	let finality_notifications = client.finality_notification_stream()
+		.take_while(|n| Ok(n.header.number() < &20))
+		.for_each(|_| Ok(()));
+
+	let voter = run_grandpa(
+		Config {
+			gossip_duration: TEST_GOSSIP_DURATION,
+			round_timers: Default::default(),
+			local_key: Some(Arc::new(Keyring::Alice.into())),
+			name: Some("peer#0".to_string()),
+			equivocation_reporter: None,
+		},
+		link,
+		MessageRouting::new(net.clone(), 0),
+	).expect("all in order with client and network");
+	runtime.spawn(voter);
+
+	let drive_to_completion = {
+		let net = net.clone();
+		::tokio::timer::Interval::new_interval(TEST_ROUTING_INTERVAL)
+			.for_each(move |_| { net.lock().route_until_complete(); Ok(()) })
+			.map(|_| ())
+			.map_err(|_| ())
+	};
+
+	runtime.block_on(finality_notifications.map_err(|_| ()).select(drive_to_completion).map_err(|_| ())).unwrap();
+
+	let justification = net.lock().peer(0).client()
+		.justification(&BlockId::Number(20)).unwrap()
+		.expect("block 20 finalized with a justification; qed");
+	let encoded = justification_for_engine(&justification, &GRANDPA_ENGINE_ID)
+		.expect("justification contains a GRANDPA entry; qed");
+	let voters: HashMap<_, _> = voters.into_iter().collect();
+	let justification = GrandpaJustification::<Block>::decode_and_verify(encoded, 0, &voters).unwrap();
+	assert_eq!(justification.target().1, 20);
 }
 
 #[test]
@@ -427,8 +487,10 @@ fn finalize_3_voters_1_observer() {
 		let voter = run_grandpa(
 			Config {
 				gossip_duration: TEST_GOSSIP_DURATION,
+				round_timers: Default::default(),
 				local_key,
 				name: Some(format!("peer#{}", peer_id)),
+				equivocation_reporter: None,
 			},
 			link,
 			MessageRouting::new(net.clone(), peer_id),
@@ -563,8 +625,107 @@ fn transition_3_voters_twice_1_observer() {
 		let voter = run_grandpa(
 			Config {
 				gossip_duration: TEST_GOSSIP_DURATION,
+				round_timers: Default::default(),
 				local_key,
 				name: Some(format!("peer#{}", peer_id)),
+				equivocation_reporter: None,
+			},
+			link,
+			MessageRouting::new(net.clone(), peer_id),
+		).expect("all in order with client and network");
+
+		runtime.spawn(voter);
+	}
+
+	// wait for all finalized on each.
+	let wait_for = ::futures::future::join_all(finality_notifications)
+		.map(|_| ())
+		.map_err(|_| ());
+
+	let drive_to_completion = ::tokio::timer::Interval::new_interval(TEST_ROUTING_INTERVAL)
+		.for_each(move |_| { net.lock().route_until_complete(); Ok(()) })
+		.map(|_| ())
+		.map_err(|_| ());
+
+	runtime.block_on(wait_for.select(drive_to_completion).map_err(|_| ())).unwrap();
+}
+
+#[test]
+fn force_change_to_new_set() {
+	// two of the genesis voters are offline, so finality stalls until the forced change.
+	let genesis_voters = &[
+		Keyring::Alice,
+		Keyring::Bob,
+		Keyring::Charlie,
+		Keyring::Dave,
+		Keyring::Eve,
+	];
+
+	let peers_a = &[Keyring::Alice, Keyring::Bob, Keyring::Charlie];
+
+	let api = TestApi::new(make_ids(genesis_voters));
+	let transitions = api.scheduled_changes.clone();
+	let forced_transitions = api.forced_changes.clone();
+
+	let mut net = GrandpaTestNet::new(api, 3);
+
+	// forced transition signalled at block 2 and applied at 12, voting on top of genesis.
+	// the standard transition signalled along with it is discarded.
+	net.peer(0).push_blocks(1, false);
+	net.peer(0).generate_blocks(1, BlockOrigin::File, |builder| {
+		let block = builder.bake().unwrap();
+		let parent_hash = *block.header.parent_hash();
+		forced_transitions.lock().insert(parent_hash, (0, ScheduledChange {
+			next_authorities: make_ids(peers_a),
+			delay: 10,
+		}));
+		transitions.lock().insert(parent_hash, ScheduledChange {
+			next_authorities: make_ids(genesis_voters),
+			delay: 5,
+		});
+
+		block
+	});
+	net.peer(0).push_blocks(24, false);
+	net.sync();
+
+	for (i, peer) in net.peers().iter().enumerate() {
+		assert_eq!(peer.client().info().unwrap().chain.best_number, 26,
+			"Peer #{} failed to sync", i);
+
+		let set_raw = peer.client().backend().get_aux(::AUTHORITY_SET_KEY).unwrap().unwrap();
+		let set = AuthoritySet::<Hash, BlockNumber>::decode(&mut &set_raw[..]).unwrap();
+
+		assert_eq!(set.current(), (1, make_ids(peers_a).as_slice()));
+		assert!(set.pending_changes().is_empty());
+	}
+
+	let net = Arc::new(Mutex::new(net));
+	let mut finality_notifications = Vec::new();
+
+	let mut runtime = current_thread::Runtime::new().unwrap();
+
+	for (peer_id, key) in peers_a.iter().enumerate() {
+		let (client, link) = {
+			let mut net = net.lock();
+			let link = net.peers[peer_id].data.lock().take().expect("link initialized at startup; qed");
+			(
+				net.peers[peer_id].client().clone(),
+				link,
+			)
+		};
+		finality_notifications.push(
+			client.finality_notification_stream()
+				.take_while(|n| Ok(n.header.number() < &26))
+				.for_each(|_| Ok(()))
+		);
+		let voter = run_grandpa(
+			Config {
+				gossip_duration: TEST_GOSSIP_DURATION,
+				round_timers: Default::default(),
+				local_key: Some(Arc::new(key.clone().into())),
+				name: Some(format!("peer#{}", peer_id)),
+				equivocation_reporter: None,
 			},
 			link,
 			MessageRouting::new(net.clone(), peer_id),
@@ -585,3 +746,539 @@ fn transition_3_voters_twice_1_observer() {
 
 	runtime.block_on(wait_for.select(drive_to_completion).map_err(|_| ())).unwrap();
 }
+
+#[derive(Clone)]
+struct NoNetwork;
+
+impl Network for NoNetwork {
+	type In = Box<Stream<Item=Vec<u8>,Error=()> + Send>;
+
+	fn messages_for(&self, _round: u64, _set_id: u64) -> Self::In {
+		Box::new(::futures::stream::empty())
+	}
+
+	fn send_message(&self, _round: u64, _set_id: u64, _message: Vec<u8>) {}
+
+
+	fn commit_messages(&self, _set_id: u64) -> Self::In {
+		Box::new(::futures::stream::empty())
+	}
+
+	fn send_commit(&self, _set_id: u64, _message: Vec<u8>) {}
+
+	fn sync_finality_target(&self, _hash: H256, _number: u64) {}
+
+	fn note_commit_finalized(&self, _set_id: u64, _number: u64) {}
+}
+
+#[test]
+fn votes_cast_before_restart_are_sent_again() {
+	let key: Arc<ed25519::Pair> = Arc::new(Keyring::Alice.into());
+	let voters = Arc::new(make_ids(&[Keyring::Alice]).into_iter().collect::<HashMap<_, _>>());
+	let persisted = Arc::new(Mutex::new(Vec::new()));
+	let persist_votes = {
+		let persisted = persisted.clone();
+		move |votes: &[SignedMessage<Block>]| {
+			*persisted.lock() = votes.to_vec();
+			Ok(())
+		}
+	};
+	let prevote = |target_number| ::grandpa::Message::Prevote(::grandpa::Prevote {
+		target_hash: Hash::default(),
+		target_number,
+	});
+
+	// cast a prevote, which is persisted.
+	let (_, mut outgoing) = ::communication::outgoing_messages::<Block, _, _>(
+		1,
+		0,
+		Some(key.clone()),
+		voters.clone(),
+		NoNetwork,
+		Vec::new(),
+		persist_votes.clone(),
+	);
+	outgoing.start_send(prevote(1)).unwrap();
+	assert_eq!(persisted.lock().len(), 1);
+
+	// after a restart, the voter tries to prevote for a different block.
+	let restored_votes = persisted.lock().clone();
+	let (incoming, mut outgoing) = ::communication::outgoing_messages::<Block, _, _>(
+		1,
+		0,
+		Some(key),
+		voters,
+		NoNetwork,
+		restored_votes,
+		persist_votes,
+	);
+	outgoing.start_send(prevote(2)).unwrap();
+
+	let (sent, _) = incoming.into_future().wait().map_err(|_| ()).unwrap();
+	assert_eq!(sent.unwrap().message, prevote(1));
+	assert_eq!(persisted.lock().len(), 1);
+}
+
+#[test]
+fn equivocation_proofs_are_checked() {
+	let key: ed25519::Pair = Keyring::Alice.into();
+	let vote = |message: Message<Block>| {
+		let signature = key.sign(&(&message, 1u64, 0u64).encode()[..]);
+		let (target_hash, target_number) = match message {
+			::grandpa::Message::Prevote(prevote) => (prevote.target_hash, prevote.target_number),
+			::grandpa::Message::Precommit(precommit) => (precommit.target_hash, precommit.target_number),
+		};
+		SignedVote { target_hash, target_number, signature }
+	};
+	let prevote = |target_hash, target_number| vote(::grandpa::Message::Prevote(::grandpa::Prevote {
+		target_hash,
+		target_number,
+	}));
+	let precommit = |target_hash, target_number| vote(::grandpa::Message::Precommit(::grandpa::Precommit {
+		target_hash,
+		target_number,
+	}));
+
+	let proof = EquivocationProof {
+		set_id: 0,
+		round: 1,
+		offender: AuthorityId(Keyring::Alice.to_raw_public()),
+		precommits: false,
+		first: prevote(Hash::from([1; 32]), 1),
+		second: prevote(Hash::from([2; 32]), 1),
+	};
+	assert!(proof.is_valid());
+
+	// votes for the same block are no equivocation.
+	assert!(!EquivocationProof { second: prevote(Hash::from([1; 32]), 1), ..proof.clone() }.is_valid());
+	// a prevote and a precommit are no equivocation.
+	assert!(!EquivocationProof { second: precommit(Hash::from([2; 32]), 1), ..proof.clone() }.is_valid());
+	assert!(!EquivocationProof { precommits: true, ..proof.clone() }.is_valid());
+	assert!(EquivocationProof {
+		precommits: true,
+		first: precommit(Hash::from([1; 32]), 1),
+		second: precommit(Hash::from([2; 32]), 1),
+		..proof.clone()
+	}.is_valid());
+	// the signatures are bound to the round.
+	assert!(!EquivocationProof { round: 2, ..proof }.is_valid());
+}
+
+#[test]
+fn config_bounds_gossip_duration_and_round_timers() {
+	let config = |gossip_duration, prevote, precommit| Config::<Block> {
+		gossip_duration,
+		round_timers: RoundTimers { prevote, precommit },
+		local_key: None,
+		name: None,
+		equivocation_reporter: None,
+	};
+
+	assert!(config(TEST_GOSSIP_DURATION, 2, 4).check().is_ok());
+	assert!(config(MIN_GOSSIP_DURATION, 1, MAX_ROUND_TIMER).check().is_ok());
+	assert!(config(MAX_GOSSIP_DURATION + Duration::from_millis(1), 2, 4).check().is_err());
+	assert!(config(Duration::from_millis(10), 2, 4).check().is_err());
+	assert!(config(TEST_GOSSIP_DURATION, 0, 4).check().is_err());
+	assert!(config(TEST_GOSSIP_DURATION, 4, 4).check().is_err());
+	assert!(config(TEST_GOSSIP_DURATION, 2, MAX_ROUND_TIMER + 1).check().is_err());
+}
+
+#[test]
+fn finality_is_proven_with_the_first_justification_from_the_end_on() {
+	use test_client::TestClient;
+	use test_client::runtime::Header;
+
+	let client = test_client::new();
+	let genesis_hash = client.info().unwrap().chain.genesis_hash;
+	let hashes: Vec<_> = (0..3).map(|_| {
+		let block = client.new_block().unwrap().bake().unwrap();
+		let hash = block.header.hash();
+		client.justify_and_import(BlockOrigin::Own, block).unwrap();
+		hash
+	}).collect();
+	client.finalize_block(BlockId::Hash(hashes[1]), Some(vec![(GRANDPA_ENGINE_ID, vec![42])]), true).unwrap();
+
+	let (proven, proof) = prove_finality(&client, genesis_hash, hashes[0]).unwrap().unwrap();
+	let proof = FinalityProof::<Header>::decode(&mut &proof[..]).unwrap();
+	assert_eq!(proven, hashes[1]);
+	assert_eq!(proof.block, hashes[1]);
+	assert_eq!(proof.justification, vec![42]);
+	assert_eq!(
+		proof.unknown_headers.iter().map(|header| header.hash()).collect::<Vec<_>>(),
+		hashes[..2].to_vec(),
+	);
+
+	// the end of the range is not finalized yet.
+	assert_eq!(prove_finality(&client, genesis_hash, hashes[2]).unwrap(), None);
+	// the range is empty.
+	assert_eq!(prove_finality(&client, hashes[1], hashes[0]).unwrap(), None);
+}
+
+#[test]
+fn authority_set_is_proven_against_the_state_of_the_block() {
+	use substrate_primitives::storage::well_known_keys::{CODE, HEAP_PAGES};
+
+	let client = test_client::new();
+	let genesis = BlockId::Number(0);
+	let voters = make_ids(&[Keyring::Alice, Keyring::Bob]);
+	let mut api = TestApi::new(voters.clone());
+	// the test runtime doesn't keep a set, so any keys of its genesis state do.
+	api.set_id = Some(3);
+	api.authority_set_keys = vec![CODE.to_vec(), HEAP_PAGES.to_vec()];
+
+	let proof = prove_authority_set(&client, &api, &genesis).unwrap();
+	assert_eq!(proof.set_id, 3);
+	assert_eq!(proof.authorities, voters);
+	// the proof holds the nodes of the reads of all keys, each once.
+	for key in &api.authority_set_keys {
+		for node in client.read_proof(&genesis, key).unwrap() {
+			assert_eq!(proof.proof.iter().filter(|n| **n == node).count(), 1);
+		}
+	}
+
+	// the set can't be proven by a runtime that didn't initialise its id.
+	api.set_id = None;
+	assert!(prove_authority_set(&client, &api, &genesis).is_err());
+}
+
+#[test]
+fn finality_proof_stops_at_authority_set_change() {
+	use test_client::TestClient;
+	use test_client::runtime::Header;
+
+	let client = test_client::new();
+	let genesis_hash = client.info().unwrap().chain.genesis_hash;
+	let hashes: Vec<_> = (0..3).map(|_| {
+		let block = client.new_block().unwrap().bake().unwrap();
+		let hash = block.header.hash();
+		client.justify_and_import(BlockOrigin::Own, block).unwrap();
+		hash
+	}).collect();
+	client.finalize_block(BlockId::Hash(hashes[0]), Some(vec![(GRANDPA_ENGINE_ID, vec![1])]), true).unwrap();
+	client.finalize_block(BlockId::Hash(hashes[2]), Some(vec![(GRANDPA_ENGINE_ID, vec![3])]), true).unwrap();
+
+	// without a set change, the end of the range is proven final at once.
+	let (proven, _) = prove_finality(&client, genesis_hash, hashes[2]).unwrap().unwrap();
+	assert_eq!(proven, hashes[2]);
+
+	// block #1 enacted a set change, so the verifier learns the new set first.
+	client.backend().insert_aux(&[(::AUTHORITY_SET_CHANGES_KEY, &vec![1u64].encode()[..])], &[]).unwrap();
+	let (proven, proof) = prove_finality(&client, genesis_hash, hashes[2]).unwrap().unwrap();
+	let proof = FinalityProof::<Header>::decode(&mut &proof[..]).unwrap();
+	assert_eq!(proven, hashes[0]);
+	assert_eq!(proof.justification, vec![1]);
+	assert_eq!(proof.unknown_headers.len(), 1);
+
+	// the rest of the range is proven from the change on.
+	let (proven, _) = prove_finality(&client, hashes[0], hashes[2]).unwrap().unwrap();
+	assert_eq!(proven, hashes[2]);
+}
+
+/// Build an encoded justification of the given block, with precommits of the given voters.
+fn make_justification(round: u64, set_id: u64, target: (Hash, BlockNumber), keys: &[Keyring]) -> Vec<u8> {
+	let (target_hash, target_number) = target;
+	let precommits = keys.iter().map(|key| {
+		let pair: ed25519::Pair = key.clone().into();
+		let precommit = ::grandpa::Precommit { target_hash, target_number };
+		let message: Message<Block> = ::grandpa::Message::Precommit(precommit.clone());
+		let signature = pair.sign(&(&message, round, set_id).encode()[..]);
+		::grandpa::SignedPrecommit { precommit, signature, id: AuthorityId(key.to_raw_public()) }
+	}).collect();
+	let commit: Commit<Block> = ::grandpa::Commit { target_hash, target_number, precommits };
+
+	(round, commit, Vec::<test_client::runtime::Header>::new()).encode()
+}
+
+/// Wrap the given block for import by the GRANDPA block import.
+fn make_import_block(block: test_client::runtime::Block, justification: Justification) -> ImportBlock<Block> {
+	ImportBlock {
+		origin: BlockOrigin::File,
+		header: block.header,
+		justification,
+		post_digests: Vec::new(),
+		body: Some(block.extrinsics),
+		finalized: false,
+		auxiliary: Vec::new(),
+		storage_changes: None,
+	}
+}
+
+#[test]
+fn bad_justification_is_dropped_on_block_import() {
+	let api = TestApi::new(make_ids(&[Keyring::Alice]));
+	let transitions = api.scheduled_changes.clone();
+	let client = Arc::new(test_client::new());
+	let (block_import, _link) = block_import(client.clone(), Arc::new(api)).unwrap();
+
+	let block = client.new_block().unwrap().bake().unwrap();
+	let hash = block.header.hash();
+	transitions.lock().insert(*block.header.parent_hash(), ScheduledChange {
+		next_authorities: make_ids(&[Keyring::Bob]),
+		delay: 0,
+	});
+
+	// the block is imported without the justification, which is requested again since the
+	// block enacts a change.
+	let justification = vec![(GRANDPA_ENGINE_ID, make_justification(1, 0, (hash, 1), &[Keyring::Bob]))];
+	match block_import.import_block(make_import_block(block, justification), None).unwrap() {
+		ImportResult::Queued(aux) => assert!(aux.needs_justification),
+		r => panic!("Unexpected import result: {:?}", r),
+	}
+
+	assert_eq!(client.info().unwrap().chain.best_hash, hash);
+	assert_eq!(client.info().unwrap().chain.finalized_number, 0);
+	assert_eq!(client.justification(&BlockId::Hash(hash)).unwrap(), None);
+}
+
+#[test]
+fn invalid_scheduled_change_is_ignored_on_block_import() {
+	let voters = make_ids(&[Keyring::Alice]);
+	let api = TestApi::new(voters.clone());
+	let transitions = api.scheduled_changes.clone();
+	let client = Arc::new(test_client::new());
+	let (block_import, link) = block_import(client.clone(), Arc::new(api)).unwrap();
+
+	let block = client.new_block().unwrap().bake().unwrap();
+	let hash = block.header.hash();
+	transitions.lock().insert(*block.header.parent_hash(), ScheduledChange {
+		next_authorities: Vec::new(),
+		delay: 0,
+	});
+
+	// the block is imported, but the empty set is never scheduled.
+	match block_import.import_block(make_import_block(block, Vec::new()), None).unwrap() {
+		ImportResult::Queued(aux) => assert!(!aux.needs_justification),
+		r => panic!("Unexpected import result: {:?}", r),
+	}
+
+	assert_eq!(client.info().unwrap().chain.best_hash, hash);
+	let set = link.authority_set.inner().read();
+	assert_eq!(set.current(), (0, &voters[..]));
+	assert!(set.pending_changes().is_empty());
+}
+
+#[test]
+fn authority_set_of_earlier_schema_is_migrated() {
+	use authorities::DelayKind;
+
+	let voters = make_ids(&[Keyring::Alice, Keyring::Bob]);
+	let client = Arc::new(test_client::new());
+
+	// the set as stored before forced changes were supported: the set id and the pending
+	// changes without their kind of delay.
+	let old_set = (
+		voters.clone(),
+		5u64,
+		vec![(make_ids(&[Keyring::Charlie]), 10 as BlockNumber, 3 as BlockNumber, Hash::from([1; 32]))],
+	).encode();
+	client.backend().insert_aux(&[(::AUTHORITY_SET_KEY, &old_set[..])], &[]).unwrap();
+
+	let (_, link) = block_import(client.clone(), Arc::new(TestApi::new(voters.clone()))).unwrap();
+	let set = link.authority_set.inner().read();
+	assert_eq!(set.current(), (5, &voters[..]));
+	assert_eq!(set.pending_changes().len(), 1);
+	assert_eq!(set.pending_changes()[0].delay, 10);
+	assert_eq!(set.pending_changes()[0].canon_height, 3);
+	assert_eq!(set.pending_changes()[0].delay_kind, DelayKind::Finalized);
+	assert!(set.pending_forced_changes().is_empty());
+
+	assert_eq!(
+		client.backend().get_aux(::VERSION_KEY).unwrap(),
+		Some(::CURRENT_VERSION.encode()),
+	);
+}
+
+#[test]
+fn imported_justification_enacting_change_restarts_the_voter() {
+	let api = TestApi::new(make_ids(&[Keyring::Alice]));
+	let transitions = api.scheduled_changes.clone();
+	let client = Arc::new(test_client::new());
+	let (block_import, link) = block_import(client.clone(), Arc::new(api)).unwrap();
+
+	let block = client.new_block().unwrap().bake().unwrap();
+	let hash = block.header.hash();
+	transitions.lock().insert(*block.header.parent_hash(), ScheduledChange {
+		next_authorities: make_ids(&[Keyring::Bob]),
+		delay: 0,
+	});
+	block_import.import_block(make_import_block(block, Vec::new()), None).unwrap();
+
+	let justification = vec![(GRANDPA_ENGINE_ID, make_justification(1, 0, (hash, 1), &[Keyring::Alice]))];
+	block_import.import_justification(hash, 1, justification).unwrap();
+	assert_eq!(client.info().unwrap().chain.finalized_number, 1);
+
+	// the stream of new sets ends once the block import is gone.
+	drop(block_import);
+	let new_sets = link.authority_set_change.collect().wait().unwrap();
+	assert_eq!(new_sets.len(), 1);
+	assert_eq!(new_sets[0].set_id, 1);
+	assert_eq!(new_sets[0].canon_hash, hash);
+	assert_eq!(new_sets[0].authorities, make_ids(&[Keyring::Bob]));
+}
+
+/// Routes messages like `MessageRouting`, delaying every incoming message by a random latency.
+/// Messages are delayed independently of each other, so they may be received out of order.
+#[derive(Clone)]
+struct LatentRouting {
+	inner: MessageRouting,
+	rng: Arc<Mutex<::rand::rngs::StdRng>>,
+	max_latency_ms: u64,
+}
+
+impl LatentRouting {
+	fn delayed(&self, messages: <MessageRouting as Network>::In) -> <Self as Network>::In {
+		use rand::Rng;
+
+		// more messages than this in flight would make the latencies pile up again.
+		const MAX_IN_FLIGHT: usize = 1024;
+
+		let rng = self.rng.clone();
+		let max_latency_ms = self.max_latency_ms;
+		Box::new(messages.map(move |message| {
+			let latency = Duration::from_millis(rng.lock().gen_range(0, max_latency_ms + 1));
+			Delay::new(Instant::now() + latency)
+				.map(move |_| message)
+				.map_err(|e| panic!("Timer failed: {:?}", e))
+		}).buffer_unordered(MAX_IN_FLIGHT))
+	}
+}
+
+impl Network for LatentRouting {
+	type In = Box<Stream<Item=Vec<u8>,Error=()> + Send>;
+
+	fn messages_for(&self, round: u64, set_id: u64) -> Self::In {
+		self.delayed(self.inner.messages_for(round, set_id))
+	}
+
+	fn send_message(&self, round: u64, set_id: u64, message: Vec<u8>) {
+		self.inner.send_message(round, set_id, message)
+	}
+
+	fn commit_messages(&self, set_id: u64) -> Self::In {
+		self.delayed(self.inner.commit_messages(set_id))
+	}
+
+	fn send_commit(&self, set_id: u64, message: Vec<u8>) {
+		self.inner.send_commit(set_id, message)
+	}
+
+	fn sync_finality_target(&self, _hash: H256, _number: u64) {}
+
+	fn note_commit_finalized(&self, _set_id: u64, _number: u64) {}
+}
+
+/// Collects the equivocations reported by a voter.
+#[derive(Default)]
+struct CollectEquivocations(Mutex<Vec<EquivocationProof<Hash, BlockNumber>>>);
+
+impl ReportEquivocation<Block> for CollectEquivocations {
+	fn report_equivocation(&self, proof: EquivocationProof<Hash, BlockNumber>) {
+		self.0.lock().push(proof);
+	}
+}
+
+/// Run `voter_count` voters of equal weight, seeded with `seed`, until all online ones finalize
+/// the last of `block_count` blocks. As many voters as the set tolerates stay offline, so the
+/// online ones make up exactly the threshold. The others start after a random delay of up to
+/// `max_stagger_ms` and receive every message after a random latency of up to `max_latency_ms`.
+fn run_soak_test(seed: u8, voter_count: usize, block_count: u64, max_latency_ms: u64, max_stagger_ms: u64) {
+	use rand::{Rng, SeedableRng, rngs::StdRng, seq::SliceRandom};
+
+	let mut rng = StdRng::from_seed([seed; 32]);
+	let keys: Vec<_> = (0..voter_count)
+		.map(|i| {
+			let mut key_seed = [seed; 32];
+			key_seed[0] = i as u8;
+			key_seed[1] = (i >> 8) as u8;
+			Arc::new(ed25519::Pair::from_seed(&key_seed))
+		})
+		.collect();
+	let voters: Vec<_> = keys.iter().map(|key| (AuthorityId(key.public().0), 1)).collect();
+
+	let mut offline: Vec<_> = (0..voter_count).collect();
+	offline.shuffle(&mut rng);
+	offline.truncate((voter_count - 1) / 3);
+
+	let mut net = GrandpaTestNet::new(TestApi::new(voters), voter_count);
+	net.peer(0).push_blocks(block_count as usize, false);
+	net.sync();
+
+	let net = Arc::new(Mutex::new(net));
+	let routing_rng = Arc::new(Mutex::new(StdRng::from_seed(rng.gen())));
+	let mut runtime = current_thread::Runtime::new().unwrap();
+	let mut finality_notifications = Vec::new();
+	let mut reporters = Vec::new();
+
+	for (peer_id, key) in keys.into_iter().enumerate() {
+		if offline.contains(&peer_id) {
+			continue;
+		}
+
+		let (client, link) = {
+			let mut net = net.lock();
+			let link = net.peers[peer_id].data.lock().take().expect("link initialized at startup; qed");
+			(net.peers[peer_id].client().clone(), link)
+		};
+		finality_notifications.push(
+			client.finality_notification_stream()
+				.take_while(move |n| Ok(n.header.number() < &block_count))
+				.for_each(|_| Ok(()))
+		);
+
+		let reporter = Arc::new(CollectEquivocations::default());
+		reporters.push(reporter.clone());
+
+		let voter = run_grandpa(
+			Config {
+				gossip_duration: TEST_GOSSIP_DURATION,
+				round_timers: Default::default(),
+				local_key: Some(key),
+				name: Some(format!("peer#{}", peer_id)),
+				equivocation_reporter: Some(reporter),
+			},
+			link,
+			LatentRouting {
+				inner: MessageRouting::new(net.clone(), peer_id),
+				rng: routing_rng.clone(),
+				max_latency_ms,
+			},
+		).expect("all in order with client and network");
+
+		let stagger = Duration::from_millis(rng.gen_range(0, max_stagger_ms + 1));
+		runtime.spawn(Delay::new(Instant::now() + stagger).map_err(|_| ()).and_then(move |_| voter));
+	}
+
+	let wait_for = ::futures::future::join_all(finality_notifications)
+		.map(|_| ())
+		.map_err(|_| ());
+
+	let drive_to_completion = {
+		let net = net.clone();
+		::tokio::timer::Interval::new_interval(TEST_ROUTING_INTERVAL)
+			.for_each(move |_| { net.lock().route_until_complete(); Ok(()) })
+			.map(|_| ())
+			.map_err(|_| ())
+	};
+
+	runtime.block_on(wait_for.select(drive_to_completion).map_err(|_| ())).unwrap();
+
+	for reporter in reporters {
+		assert!(reporter.0.lock().is_empty(), "no voter equivocates; qed");
+	}
+}
+
+// these take minutes and are meant for nightly runs: `cargo test -- --ignored`.
+
+#[test]
+#[ignore]
+fn soak_25_voters_at_the_threshold_with_random_latencies() {
+	for seed in 0..4 {
+		run_soak_test(seed, 25, 30, 300, 2000);
+	}
+}
+
+#[test]
+#[ignore]
+fn soak_49_voters_at_the_threshold_with_random_latencies() {
+	run_soak_test(42, 49, 20, 500, 5000);
+}
@@ -15,6 +15,9 @@
 // along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
 
 //! Support code for the runtime. A set of test accounts.
+//!
+//! Besides the named accounts, any number of deterministic accounts can be created with
+//! `Keyring::numeric` and derived from others with derivation paths like `"//stash"`.
 
 #[macro_use] extern crate hex_literal;
 #[macro_use] extern crate lazy_static;
@@ -70,6 +73,24 @@ impl Keyring {
 		Pair::from(self).sign(msg)
 	}
 
+	/// Derive the pair at `path` from this account, e.g. `"//stash"` or `"//1//controller"`.
+	pub fn derive(self, path: &str) -> Pair {
+		derive_pair(self.pair(), path)
+	}
+
+	/// The pair of the `n`th numbered test account, seeded with `n` the way the named accounts
+	/// are seeded with their names.
+	pub fn numeric(n: u32) -> Pair {
+		let mut seed = [0u8; 32];
+		seed.copy_from_slice(format!("{:<32}", n).as_bytes());
+		Pair::from_seed(&seed)
+	}
+
+	/// The pairs of the first `count` numbered test accounts.
+	pub fn numeric_range(count: u32) -> Vec<Pair> {
+		(0..count).map(Self::numeric).collect()
+	}
+
 	pub fn pair(self) -> Pair {
 		match self {
 			Keyring::Alice => Pair::from_seed(b"Alice                           "),
@@ -84,6 +105,16 @@ impl Keyring {
 	}
 }
 
+/// Derive the pair at `path` from `pair`.
+///
+/// The path is a sequence of junctions, each introduced by `//`. A child pair is derived for
+/// each junction in turn, so `"//a//b"` from a pair is `"//b"` from its `"//a"` child.
+pub fn derive_pair(pair: Pair, path: &str) -> Pair {
+	path.split("//")
+		.filter(|junction| !junction.is_empty())
+		.fold(pair, |pair, junction| pair.derive_child_probably_bad(junction.as_bytes()))
+}
+
 impl From<Keyring> for &'static str {
 	fn from(k: Keyring) -> Self {
 		match k {
@@ -172,4 +203,32 @@ mod tests {
 		assert!(!Keyring::Alice.sign(b"I am Alice!").verify(b"I am Bob!", Keyring::Alice));
 		assert!(!Keyring::Alice.sign(b"I am Alice!").verify(b"I am Alice!", Keyring::Bob));
 	}
+
+	#[test]
+	fn numeric_accounts_are_deterministic_and_distinct() {
+		assert_eq!(Keyring::numeric(7).public(), Keyring::numeric(7).public());
+		assert!(Keyring::numeric(7).public() != Keyring::numeric(8).public());
+
+		let publics: ::std::collections::HashSet<_> = Keyring::numeric_range(300).iter()
+			.map(|pair| pair.public())
+			.collect();
+		assert_eq!(publics.len(), 300);
+		assert!(publics.iter().all(|public| Keyring::from_public(public.clone()).is_none()));
+	}
+
+	#[test]
+	fn derivation_paths_work() {
+		let stash = Keyring::Alice.derive("//stash");
+		assert_eq!(stash.public(), Keyring::Alice.derive("//stash").public());
+		assert!(stash.public() != Keyring::Alice.public());
+		assert!(stash.public() != Keyring::Bob.derive("//stash").public());
+		assert!(stash.public() != Keyring::Alice.derive("//controller").public());
+
+		assert_eq!(
+			Keyring::Alice.derive("//stash//1").public(),
+			derive_pair(stash, "//1").public()
+		);
+		assert_eq!(Keyring::Alice.derive("").public(), Keyring::Alice.public());
+		assert!(Keyring::numeric(1).sign(b"msg").verify(b"msg", Keyring::numeric(1).public()));
+	}
 }
@@ -33,14 +33,18 @@ extern crate error_chain;
 extern crate tempdir;
 
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::fs::{self, File};
 use std::io::{self, Write};
 
-use substrate_primitives::{hashing::blake2_256, ed25519::{Pair, Public, PKCS_LEN}};
+use substrate_primitives::{hashing::blake2_256, ed25519::{Pair, Public, Signature, PKCS_LEN}};
+use substrate_primitives::crypto::{key_types, KeyTypeId, Keystore};
 
 pub use crypto::KEY_ITERATIONS;
 
+/// Message signed by the `node` key to derive the network secret from.
+const NODE_SECRET_CONTEXT: &[u8] = b"substrate-node-secret";
+
 error_chain! {
 	foreign_links {
 		Io(io::Error);
@@ -156,10 +160,21 @@ impl Store {
 
 	/// Generate a new key, placing it into the store.
 	pub fn generate(&self, password: &str) -> Result<Pair> {
+		Self::generate_in(&self.path, password)
+	}
+
+	/// Generate a new key of the given type, placing it into the store.
+	pub fn generate_by_type(&self, key_type: KeyTypeId, password: &str) -> Result<Pair> {
+		let dir = self.key_type_path(key_type);
+		fs::create_dir_all(&dir)?;
+		Self::generate_in(&dir, password)
+	}
+
+	fn generate_in(dir: &Path, password: &str) -> Result<Pair> {
 		let (pair, pkcs_bytes) = Pair::generate_with_pkcs8();
 		let key_file = EncryptedKey::encrypt(&pkcs_bytes, password, KEY_ITERATIONS as u32);
 
-		let mut file = File::create(self.key_file_path(&pair.public()))?;
+		let mut file = File::create(Self::key_file_path_in(dir, &pair.public()))?;
 		::serde_json::to_writer(&file, &key_file)?;
 
 		file.flush()?;
@@ -183,7 +198,15 @@ impl Store {
 			let pair = Pair::from_seed(seed);
 			return Ok(pair);
 		}
-		let path = self.key_file_path(public);
+		Self::load_from(&self.key_file_path(public), password)
+	}
+
+	/// Load a key file of the given type with given public key.
+	pub fn load_by_type(&self, key_type: KeyTypeId, public: &Public, password: &str) -> Result<Pair> {
+		Self::load_from(&Self::key_file_path_in(&self.key_type_path(key_type), public), password)
+	}
+
+	fn load_from(path: &Path, password: &str) -> Result<Pair> {
 		let file = File::open(path)?;
 
 		let encrypted_key: EncryptedKey = ::serde_json::from_reader(&file)?;
@@ -192,10 +215,39 @@ impl Store {
 		Pair::from_pkcs8(&pkcs_bytes[..]).map_err(|_| ErrorKind::InvalidPKCS8.into())
 	}
 
+	/// Get the secret of the network identity of the node, derived from the key of type
+	/// `node`. A key is generated if there is none yet; if there are several, the one with the
+	/// lowest public key is used.
+	///
+	/// Signatures are deterministic, so the secret stays the same as long as the key is kept,
+	/// and backing up or rotating the key does the same for the network identity.
+	pub fn node_secret(&self) -> Result<[u8; 32]> {
+		let pair = match self.contents_by_type(key_types::NODE)?.into_iter().min_by_key(|public| public.0) {
+			Some(public) => self.load_by_type(key_types::NODE, &public, "")?,
+			None => self.generate_by_type(key_types::NODE, "")?,
+		};
+		Ok(blake2_256(pair.sign(NODE_SECRET_CONTEXT).as_ref()))
+	}
+
 	/// Get public keys of all stored keys.
 	pub fn contents(&self) -> Result<Vec<Public>> {
 		let mut public_keys: Vec<Public> = self.additional.keys().cloned().collect();
-		for entry in fs::read_dir(&self.path)? {
+		public_keys.extend(Self::contents_of(&self.path)?);
+		Ok(public_keys)
+	}
+
+	/// Get public keys of all stored keys of the given type.
+	pub fn contents_by_type(&self, key_type: KeyTypeId) -> Result<Vec<Public>> {
+		let dir = self.key_type_path(key_type);
+		if !dir.exists() {
+			return Ok(Vec::new());
+		}
+		Self::contents_of(&dir)
+	}
+
+	fn contents_of(dir: &Path) -> Result<Vec<Public>> {
+		let mut public_keys = Vec::new();
+		for entry in fs::read_dir(dir)? {
 			let entry = entry?;
 			let path = entry.path();
 
@@ -219,10 +271,32 @@ impl Store {
 	}
 
 	fn key_file_path(&self, public: &Public) -> PathBuf {
-		let mut buf = self.path.clone();
+		Self::key_file_path_in(&self.path, public)
+	}
+
+	fn key_file_path_in(dir: &Path, public: &Public) -> PathBuf {
+		let mut buf = dir.to_path_buf();
 		buf.push(hex::encode(public.as_slice()));
 		buf
 	}
+
+	/// Keys of a given type are kept in a sub-directory named after the hex-encoded type.
+	fn key_type_path(&self, key_type: KeyTypeId) -> PathBuf {
+		let mut buf = self.path.clone();
+		buf.push(hex::encode(&key_type[..]));
+		buf
+	}
+}
+
+/// Keys of applications are stored without a password, like the session key of the node.
+impl Keystore for Store {
+	fn public_keys(&self, key_type: KeyTypeId) -> Vec<Public> {
+		self.contents_by_type(key_type).unwrap_or_default()
+	}
+
+	fn sign(&self, key_type: KeyTypeId, public: &Public, msg: &[u8]) -> Option<Signature> {
+		self.load_by_type(key_type, public, "").ok().map(|pair| pair.sign(msg))
+	}
 }
 
 #[cfg(test)]
@@ -275,6 +349,44 @@ mod tests {
 		assert_eq!(store.contents().unwrap()[0], key.public());
 	}
 
+	#[test]
+	fn keys_are_kept_apart_by_type() {
+		use substrate_primitives::{crypto::key_types, ed25519};
+
+		let temp_dir = TempDir::new("keystore").unwrap();
+		let store = Store::open(temp_dir.path().to_owned()).unwrap();
+
+		let key = store.generate_by_type(key_types::IM_ONLINE, "").unwrap();
+		assert!(store.contents().unwrap().is_empty());
+		assert!(store.public_keys(key_types::AURA).is_empty());
+		assert_eq!(store.public_keys(key_types::IM_ONLINE), vec![key.public()]);
+
+		let signature = store.sign(key_types::IM_ONLINE, &key.public(), b"hello").unwrap();
+		assert!(ed25519::verify_strong(&signature, b"hello", key.public()));
+		assert!(store.sign(key_types::AURA, &key.public(), b"hello").is_none());
+	}
+
+	#[test]
+	fn node_secret_is_derived_from_the_node_key() {
+		let temp_dir = TempDir::new("keystore").unwrap();
+		let store = Store::open(temp_dir.path().to_owned()).unwrap();
+
+		let secret = store.node_secret().unwrap();
+		assert_eq!(store.public_keys(key_types::NODE).len(), 1);
+		assert_eq!(store.node_secret().unwrap(), secret);
+
+		// restoring a backup of the key restores the secret.
+		let other_dir = TempDir::new("keystore").unwrap();
+		let other_store = Store::open(other_dir.path().to_owned()).unwrap();
+		let node_dir = other_store.key_type_path(key_types::NODE);
+		fs::create_dir_all(&node_dir).unwrap();
+		for entry in fs::read_dir(store.key_type_path(key_types::NODE)).unwrap() {
+			let entry = entry.unwrap();
+			fs::copy(entry.path(), node_dir.join(entry.file_name())).unwrap();
+		}
+		assert_eq!(other_store.node_secret().unwrap(), secret);
+	}
+
 	#[test]
 	fn test_generate_from_seed() {
 		let temp_dir = TempDir::new("keystore").unwrap();
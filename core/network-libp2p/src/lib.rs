@@ -25,6 +25,7 @@ extern crate tokio;
 extern crate tokio_io;
 extern crate tokio_timer;
 extern crate libc;
+extern crate net2;
 #[macro_use]
 extern crate libp2p;
 extern crate rand;
@@ -19,7 +19,7 @@ use custom_proto::{RegisteredProtocol, RegisteredProtocols};
 use fnv::{FnvHashMap, FnvHashSet};
 use futures::{prelude::*, task, Stream};
 use futures::sync::{oneshot, mpsc};
-use libp2p::{Multiaddr, PeerId};
+use libp2p::{Multiaddr, PeerId, multiaddr::Protocol};
 use libp2p::core::{Endpoint, PublicKey};
 use libp2p::core::nodes::ConnectedPoint;
 use libp2p::kad::{KadSystem, KadSystemConfig, KadConnecController, KadPeer};
@@ -29,7 +29,7 @@ use rand;
 use secret::obtain_private_key;
 use std::io::{Error as IoError, ErrorKind as IoErrorKind};
 use std::iter;
-use std::net::SocketAddr;
+use std::net::{SocketAddr, ToSocketAddrs};
 use std::path::Path;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
@@ -61,16 +61,33 @@ where TProtos: IntoIterator<Item = RegisteredProtocol> {
 	let registered_custom = RegisteredProtocols(registered_custom.into_iter().collect());
 	let mut swarm = swarm::start_swarm(registered_custom, local_private_key)?;
 
-	// Listen on multiaddresses.
+	// Listen on multiaddresses. Failing to listen on some of them (e.g. IPv6 on a host
+	// without IPv6 support) is fine, as long as we listen on at least one.
+	let mut num_listeners = 0;
 	for addr in &config.listen_addresses {
-		match swarm.listen_on(addr.clone()) {
-			Ok(new_addr) => debug!(target: "sub-libp2p", "Libp2p listening on {}", new_addr),
-			Err(_) => {
-				warn!(target: "sub-libp2p", "Can't listen on {}, protocol not supported", addr);
-				return Err(ErrorKind::BadProtocol.into())
+		let resolved = match resolve_listen_address(addr) {
+			Ok(resolved) => resolved,
+			Err(err) => {
+				warn!(target: "sub-libp2p", "Can't resolve listen address {}: {}", addr, err);
+				continue;
 			},
+		};
+		for resolved_addr in resolved {
+			match swarm.listen_on(resolved_addr.clone()) {
+				Ok(new_addr) => {
+					debug!(target: "sub-libp2p", "Libp2p listening on {}", new_addr);
+					num_listeners += 1;
+				},
+				Err(_) if resolved_addr == *addr =>
+					warn!(target: "sub-libp2p", "Can't listen on {}, protocol not supported or address unavailable", addr),
+				Err(_) =>
+					warn!(target: "sub-libp2p", "Can't listen on {}, resolved from {}, protocol not supported or address unavailable", resolved_addr, addr),
+			}
 		}
 	}
+	if num_listeners == 0 && !config.listen_addresses.is_empty() {
+		return Err(ErrorKind::BadProtocol.into())
+	}
 
 	// Register the external addresses provided by the user.
 	for addr in &config.public_addresses {
@@ -108,15 +125,18 @@ where TProtos: IntoIterator<Item = RegisteredProtocol> {
 			},
 			Err(_) => {
 				// If the format of the bootstrap node is not a multiaddr, try to parse it as
-				// a `SocketAddr`. This corresponds to the format `IP:PORT`.
-				let addr = match bootnode.parse::<SocketAddr>() { 
-					Ok(SocketAddr::V4(socket)) => multiaddr![Ip4(*socket.ip()), Tcp(socket.port())],
-					Ok(SocketAddr::V6(socket)) => multiaddr![Ip6(*socket.ip()), Tcp(socket.port())],
+				// a `SocketAddr`. This corresponds to the format `IP:PORT` or `HOST:PORT`.
+				let socket = match bootnode.to_socket_addrs().map(|mut addrs| addrs.next()) {
+					Ok(Some(socket)) => socket,
 					_ => {
 						warn!(target: "sub-libp2p", "Not a valid bootnode address: {}", bootnode);
 						continue;
 					}
 				};
+				let addr = match socket {
+					SocketAddr::V4(socket) => multiaddr![Ip4(*socket.ip()), Tcp(socket.port())],
+					SocketAddr::V6(socket) => multiaddr![Ip6(*socket.ip()), Tcp(socket.port())],
+				};
 
 				debug!(target: "sub-libp2p", "Dialing {} with no peer id", addr);
 				if let Err(addr) = swarm.dial(addr) {
@@ -156,6 +176,7 @@ where TProtos: IntoIterator<Item = RegisteredProtocol> {
 		nodes_addresses: Default::default(),
 		disabled_peers: Default::default(),
 		reserved_peers,
+		priority_groups: Default::default(),
 		reserved_only: config.non_reserved_mode == NonReservedPeerMode::Deny,
 		kad_system,
 		kad_pending_ctrls: Default::default(),
@@ -170,6 +191,40 @@ where TProtos: IntoIterator<Item = RegisteredProtocol> {
 	})
 }
 
+/// Resolves the `/dns4` and `/dns6` prefix of a listen address into the matching IP
+/// addresses, as the transport is only able to listen on IP addresses. Other addresses are
+/// returned unchanged.
+fn resolve_listen_address(addr: &Multiaddr) -> Result<Vec<Multiaddr>, IoError> {
+	let mut iter = addr.iter();
+	let (host, want_v6) = match iter.next() {
+		Some(Protocol::Dns4(host)) => (host.to_string(), false),
+		Some(Protocol::Dns6(host)) => (host.to_string(), true),
+		_ => return Ok(vec![addr.clone()]),
+	};
+	let port = match iter.next() {
+		Some(Protocol::Tcp(port)) => port,
+		_ => return Err(IoError::new(IoErrorKind::InvalidInput, "DNS listen address must be followed by a TCP port")),
+	};
+	let rest: Vec<_> = iter.collect();
+
+	let resolved: Vec<Multiaddr> = (host.as_str(), port).to_socket_addrs()?
+		.filter_map(|socket| match socket {
+			SocketAddr::V4(socket) if !want_v6 => Some(Protocol::Ip4(*socket.ip())),
+			SocketAddr::V6(socket) if want_v6 => Some(Protocol::Ip6(*socket.ip())),
+			_ => None,
+		})
+		.map(|ip| iter::once(ip)
+			.chain(iter::once(Protocol::Tcp(port)))
+			.chain(rest.iter().cloned())
+			.collect())
+		.collect();
+
+	if resolved.is_empty() {
+		return Err(IoError::new(IoErrorKind::NotFound, "DNS listen address resolved to no IP address"));
+	}
+	Ok(resolved)
+}
+
 /// Event produced by the service.
 #[derive(Debug)]
 pub enum ServiceEvent {
@@ -223,6 +278,63 @@ pub enum ServiceEvent {
 	},
 }
 
+/// Group of peers given priority over random connections.
+struct PriorityGroup {
+	/// Members of the group.
+	peers: FnvHashSet<PeerId>,
+	/// Number of connections to members of the group that don't count towards the incoming
+	/// and outgoing connection limits.
+	slots: usize,
+}
+
+/// Named groups of peers given priority over random connections.
+#[derive(Default)]
+struct PriorityGroups {
+	groups: FnvHashMap<String, PriorityGroup>,
+}
+
+impl PriorityGroups {
+	/// Sets the members of a group, replacing the previous ones if the group already exists.
+	fn set(&mut self, group_id: String, peers: FnvHashSet<PeerId>, slots: usize) {
+		self.groups.insert(group_id, PriorityGroup { peers, slots });
+	}
+
+	/// Removes a group.
+	fn remove(&mut self, group_id: &str) {
+		self.groups.remove(group_id);
+	}
+
+	/// Returns the members of all the groups.
+	fn peers<'a>(&'a self) -> impl Iterator<Item = &'a PeerId> + 'a {
+		self.groups.values().flat_map(|group| group.peers.iter())
+	}
+
+	/// Returns true if `peer_id` is a member of any group.
+	fn contains(&self, peer_id: &PeerId) -> bool {
+		self.groups.values().any(|group| group.peers.contains(peer_id))
+	}
+
+	/// Returns true if a connection with `peer_id` can use one of the slots of a group it
+	/// belongs to, given the other peers we are `connected` to.
+	fn has_free_slot(&self, peer_id: &PeerId, connected: &[PeerId]) -> bool {
+		self.groups.values()
+			.filter(|group| group.peers.contains(peer_id))
+			.any(|group| connected.iter().filter(|p| group.peers.contains(p)).count() < group.slots)
+	}
+
+	/// Returns the peers among `connected` whose connection uses a slot of a group.
+	fn peers_using_slots(&self, connected: &[PeerId]) -> FnvHashSet<PeerId> {
+		let mut using = FnvHashSet::default();
+		for group in self.groups.values() {
+			let members = connected.iter()
+				.filter(|p| group.peers.contains(p))
+				.take(group.slots);
+			using.extend(members.cloned());
+		}
+		using
+	}
+}
+
 /// Network service. Must be polled regularly in order for the networking to work.
 pub struct Service {
 	/// Stream of events of the swarm.
@@ -243,6 +355,10 @@ pub struct Service {
 	/// List of the IDs of the reserved peers.
 	reserved_peers: FnvHashSet<PeerId>,
 
+	/// Named groups of peers that are given priority over random connections, set at runtime
+	/// by other subsystems.
+	priority_groups: PriorityGroups,
+
 	/// List of the IDs of disabled peers, and when the ban expires.
 	/// Purged at a regular interval.
 	disabled_peers: FnvHashMap<PeerId, Instant>,
@@ -326,6 +442,53 @@ impl Service {
 		None
 	}
 
+	/// Sets the members of a priority group, replacing the previous ones if the group already
+	/// exists.
+	///
+	/// Up to `slots` connections to members of the group are exempt from the connection
+	/// limits, and we always try to stay connected to them. Members are also accepted when
+	/// we only accept reserved peers.
+	pub fn set_priority_group(&mut self, group_id: String, peers: Vec<(PeerId, Multiaddr)>, slots: usize) {
+		let mut members = FnvHashSet::default();
+		for (peer_id, addr) in peers {
+			self.topology.add_bootstrap_addr(&peer_id, addr);
+			members.insert(peer_id);
+		}
+		debug!(target: "sub-libp2p", "Priority group {} set to {} peers with {} slots", group_id, members.len(), slots);
+		self.priority_groups.set(group_id, members, slots);
+		self.connect_to_nodes();
+	}
+
+	/// Removes a priority group. Peers that were connected thanks to it stay connected.
+	pub fn remove_priority_group(&mut self, group_id: &str) {
+		self.priority_groups.remove(group_id);
+	}
+
+	/// Returns true if a connection with `peer_id` can use one of the slots reserved for a
+	/// priority group it belongs to. The connection with `exclude` is not taken into account.
+	fn has_priority_slot(&self, peer_id: &PeerId, exclude: Option<NodeIndex>) -> bool {
+		self.priority_groups.has_free_slot(peer_id, &self.connected_peers(exclude))
+	}
+
+	/// Returns the peers whose connection currently uses a slot reserved for a priority group.
+	fn priority_connected_peers(&self) -> FnvHashSet<PeerId> {
+		self.priority_groups.peers_using_slots(&self.connected_peers(None))
+	}
+
+	/// Returns true if `peer_id` is a member of any priority group.
+	fn is_priority_peer(&self, peer_id: &PeerId) -> bool {
+		self.priority_groups.contains(peer_id)
+	}
+
+	/// Returns the peers we are connected to, except through `exclude`.
+	fn connected_peers(&self, exclude: Option<NodeIndex>) -> Vec<PeerId> {
+		self.swarm.nodes()
+			.filter(|&n| Some(n) != exclude)
+			.filter_map(|n| self.swarm.peer_id_of_node(n))
+			.cloned()
+			.collect()
+	}
+
 	/// Start accepting all peers again if we weren't.
 	pub fn accept_unreserved_peers(&mut self) {
 		if self.reserved_only {
@@ -344,7 +507,7 @@ impl Service {
 			.filter(|&n| {
 				let peer_id = self.swarm.peer_id_of_node(n)
 					.expect("swarm.nodes() always returns valid node indices");
-				!self.reserved_peers.contains(peer_id)
+				!self.reserved_peers.contains(peer_id) && !self.is_priority_peer(peer_id)
 			})
 			.collect();
 
@@ -441,17 +604,24 @@ impl Service {
 
 	/// Counts the number of non-reserved ingoing connections.
 	fn num_ingoing_connections(&self) -> usize {
-		self.swarm.nodes()
-			.filter(|&i| self.swarm.node_endpoint(i) == Some(Endpoint::Listener) &&
-				!self.reserved_peers.contains(&self.swarm.peer_id_of_node(i).unwrap()))
-			.count()
+		self.num_limited_connections(Endpoint::Listener)
 	}
 
 	/// Counts the number of non-reserved outgoing connections.
 	fn num_outgoing_connections(&self) -> usize {
+		self.num_limited_connections(Endpoint::Dialer)
+	}
+
+	/// Counts the connections of the given direction that are neither reserved nor using a
+	/// priority group slot.
+	fn num_limited_connections(&self, endpoint: Endpoint) -> usize {
+		let priority_connected = self.priority_connected_peers();
 		self.swarm.nodes()
-			.filter(|&i| self.swarm.node_endpoint(i) == Some(Endpoint::Dialer) &&
-				!self.reserved_peers.contains(&self.swarm.peer_id_of_node(i).unwrap()))
+			.filter(|&i| self.swarm.node_endpoint(i) == Some(endpoint))
+			.filter(|&i| {
+				let peer_id = self.swarm.peer_id_of_node(i).unwrap();
+				!self.reserved_peers.contains(peer_id) && !priority_connected.contains(peer_id)
+			})
 			.count()
 	}
 
@@ -460,8 +630,10 @@ impl Service {
 	/// Also updates `next_connect_to_nodes` with the earliest known moment when we need to
 	/// update connections again.
 	fn connect_to_nodes(&mut self) {
-		// Make sure we are connected or connecting to all the reserved nodes.
-		for reserved in self.reserved_peers.iter() {
+		// Make sure we are connected or connecting to all the reserved nodes and priority
+		// group members.
+		let priority_peers = self.priority_groups.peers();
+		for reserved in self.reserved_peers.iter().chain(priority_peers) {
 			let addrs = self.topology.addrs_of_peer(&reserved);
 			for (addr, _) in addrs {
 				let _ = self.swarm.ensure_connection(reserved.clone(), addr.clone());
@@ -616,7 +788,7 @@ impl Service {
 
 		// Reject non-reserved nodes if we're in reserved mode.
 		let is_reserved = self.reserved_peers.contains(&peer_id);
-		if self.reserved_only && !is_reserved {
+		if self.reserved_only && !is_reserved && !self.is_priority_peer(&peer_id) {
 			debug!(target: "sub-libp2p", "Rejected non-reserved peer {:?}", peer_id);
 			assert_eq!(self.swarm.drop_node(node_index), Ok(Vec::new()));
 			if let ConnectedPoint::Dialer { ref address } = endpoint {
@@ -639,7 +811,9 @@ impl Service {
 
 		match endpoint {
 			ConnectedPoint::Listener { ref listen_addr, ref send_back_addr } => {
-				if is_reserved || self.num_ingoing_connections() < self.max_incoming_connections {
+				if is_reserved || self.has_priority_slot(&peer_id, Some(node_index)) ||
+					self.num_ingoing_connections() < self.max_incoming_connections
+				{
 					debug!(target: "sub-libp2p", "Connected to {:?} through {} on listener {}",
 						peer_id, send_back_addr, listen_addr);
 				} else {
@@ -649,7 +823,9 @@ impl Service {
 				}
 			},
 			ConnectedPoint::Dialer { ref address } => {
-				if is_reserved || self.num_outgoing_connections() < self.max_outgoing_connections {
+				if is_reserved || self.has_priority_slot(&peer_id, Some(node_index)) ||
+					self.num_outgoing_connections() < self.max_outgoing_connections
+				{
 					debug!(target: "sub-libp2p", "Connected to {:?} through {}", peer_id, address);
 					self.topology.report_connected(address, &peer_id);
 				} else {
@@ -992,3 +1168,61 @@ impl Stream for Service {
 		Ok(Async::NotReady)
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::PriorityGroups;
+	use libp2p::PeerId;
+	use libp2p::core::PublicKey;
+
+	fn peer_id(n: u8) -> PeerId {
+		PublicKey::Ed25519(vec![n; 32]).into_peer_id()
+	}
+
+	#[test]
+	fn priority_group_members_are_known() {
+		let mut groups = PriorityGroups::default();
+		groups.set("a".to_owned(), vec![peer_id(1), peer_id(2)].into_iter().collect(), 1);
+		assert!(groups.contains(&peer_id(1)));
+		assert!(!groups.contains(&peer_id(3)));
+		assert_eq!(groups.peers().count(), 2);
+
+		groups.set("a".to_owned(), vec![peer_id(3)].into_iter().collect(), 1);
+		assert!(!groups.contains(&peer_id(1)));
+		assert!(groups.contains(&peer_id(3)));
+
+		groups.remove("a");
+		assert!(!groups.contains(&peer_id(3)));
+		assert_eq!(groups.peers().count(), 0);
+	}
+
+	#[test]
+	fn priority_group_slots_are_limited() {
+		let mut groups = PriorityGroups::default();
+		groups.set("a".to_owned(), vec![peer_id(1), peer_id(2), peer_id(3)].into_iter().collect(), 2);
+
+		assert!(groups.has_free_slot(&peer_id(1), &[]));
+		assert!(groups.has_free_slot(&peer_id(2), &[peer_id(1), peer_id(4)]));
+		assert!(!groups.has_free_slot(&peer_id(3), &[peer_id(1), peer_id(2)]));
+		assert!(!groups.has_free_slot(&peer_id(4), &[]));
+
+		let using = groups.peers_using_slots(&[peer_id(1), peer_id(4), peer_id(2), peer_id(3)]);
+		assert_eq!(using.len(), 2);
+		assert!(using.contains(&peer_id(1)));
+		assert!(using.contains(&peer_id(2)));
+	}
+
+	#[test]
+	fn priority_group_slots_are_counted_per_group() {
+		let mut groups = PriorityGroups::default();
+		groups.set("a".to_owned(), vec![peer_id(1), peer_id(2)].into_iter().collect(), 1);
+		groups.set("b".to_owned(), vec![peer_id(2), peer_id(3)].into_iter().collect(), 1);
+
+		// Group "a" is full, but peer 2 can still use the slot of group "b".
+		assert!(groups.has_free_slot(&peer_id(2), &[peer_id(1)]));
+		assert!(!groups.has_free_slot(&peer_id(2), &[peer_id(1), peer_id(3)]));
+
+		let using = groups.peers_using_slots(&[peer_id(1), peer_id(3)]);
+		assert_eq!(using.len(), 2);
+	}
+}
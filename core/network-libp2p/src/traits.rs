@@ -14,7 +14,7 @@
 // You should have received a copy of the GNU General Public License
 // along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::{fmt, iter, net::Ipv4Addr, str};
+use std::{fmt, iter, net::{Ipv4Addr, Ipv6Addr}, str};
 use libp2p::{multiaddr::Protocol, Multiaddr, PeerId};
 
 /// Protocol / handler id
@@ -36,9 +36,10 @@ pub struct NetworkConfiguration {
 	pub config_path: Option<String>,
 	/// Directory path to store network-specific configuration. None means nothing will be saved
 	pub net_config_path: Option<String>,
-	/// Multiaddresses to listen for incoming connections.
+	/// Multiaddresses to listen for incoming connections. `/dns4` and `/dns6` addresses are
+	/// resolved when the service starts.
 	pub listen_addresses: Vec<Multiaddr>,
-	/// Multiaddresses to advertise. Detected automatically if empty.
+	/// Multiaddresses to advertise, in addition to the ones detected automatically.
 	pub public_addresses: Vec<Multiaddr>,
 	/// List of initial node addresses
 	pub boot_nodes: Vec<String>,
@@ -71,7 +72,10 @@ impl NetworkConfiguration {
 			listen_addresses: vec![
 				iter::once(Protocol::Ip4(Ipv4Addr::new(0, 0, 0, 0)))
 					.chain(iter::once(Protocol::Tcp(30333)))
-					.collect()
+					.collect(),
+				iter::once(Protocol::Ip6(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0)))
+					.chain(iter::once(Protocol::Tcp(30333)))
+					.collect(),
 			],
 			public_addresses: Vec::new(),
 			boot_nodes: Vec::new(),
@@ -14,13 +14,27 @@
 // You should have received a copy of the GNU General Public License
 // along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
 
-use libp2p::{self, PeerId, Transport, mplex, secio, yamux};
+use futures::{future::{self, FutureResult}, prelude::*};
+use libp2p::{self, Multiaddr, PeerId, Transport, mplex, secio, yamux};
 use libp2p::core::{either, upgrade, transport::boxed::Boxed, muxing::StreamMuxerBox};
+use libp2p::dns::DnsConfig;
+use libp2p::multiaddr::Protocol;
+use libp2p::tcp::TcpConfig;
 use libp2p::transport_timeout::TransportTimeout;
+use libp2p::websocket::WsConfig;
+use net2::TcpBuilder;
+use std::io::Error as IoError;
+use std::iter;
+use std::net::{self, IpAddr, SocketAddr};
 use std::time::Duration;
 use std::usize;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::reactor::Handle;
 
 /// Builds the transport that serves as a common ground for all connections.
+///
+/// This is the same as `libp2p::CommonTransport`, that is TCP and websockets over TCP with DNS
+/// resolution, except that IPv6 TCP listeners are bound with `IPV6_V6ONLY` set.
 pub fn build_transport(
 	local_private_key: secio::SecioKeyPair
 ) -> Boxed<(PeerId, StreamMuxerBox)> {
@@ -28,7 +42,9 @@ pub fn build_transport(
 	mplex_config.max_buffer_len_behaviour(mplex::MaxBufferBehaviour::Block);
 	mplex_config.max_buffer_len(usize::MAX);
 
-	let base = libp2p::CommonTransport::new()
+	let with_dns = DnsConfig::new(TcpV6OnlyConfig { inner: TcpConfig::new() });
+	let with_ws = WsConfig::new(with_dns.clone());
+	let base = with_dns.or_transport(with_ws)
 		.with_upgrade(secio::SecioConfig::new(local_private_key))
 		.and_then(move |out, endpoint| {
 			let upgrade = upgrade::or(
@@ -44,3 +60,125 @@ pub fn build_transport(
 	TransportTimeout::new(base, Duration::from_secs(20))
 		.boxed()
 }
+
+/// TCP transport whose IPv6 listeners only accept IPv6 connections.
+///
+/// On systems where IPv6 sockets also accept IPv4 connections by default (`IPV6_V6ONLY` unset),
+/// listening on both the IPv4 and the IPv6 wildcard addresses on the same port would conflict
+/// otherwise.
+#[derive(Debug, Clone)]
+struct TcpV6OnlyConfig {
+	inner: TcpConfig,
+}
+
+impl Transport for TcpV6OnlyConfig {
+	type Output = TcpStream;
+	type Listener = Box<Stream<Item = (Self::ListenerUpgrade, Multiaddr), Error = IoError> + Send>;
+	type ListenerUpgrade = FutureResult<TcpStream, IoError>;
+	type Dial = <TcpConfig as Transport>::Dial;
+
+	fn listen_on(self, addr: Multiaddr) -> Result<(Self::Listener, Multiaddr), (Self, Multiaddr)> {
+		let socket_addr = match ipv6_socket_addr(&addr) {
+			Some(socket_addr) => socket_addr,
+			None => return match self.inner.listen_on(addr) {
+				Ok((listener, new_addr)) => Ok((Box::new(listener), new_addr)),
+				Err((inner, addr)) => Err((TcpV6OnlyConfig { inner }, addr)),
+			},
+		};
+
+		// Failing to bind (e.g. on a host without IPv6 support) must fail `listen_on`, so that
+		// the caller doesn't count the address as listened on.
+		let listener = match bind_only_v6(&socket_addr)
+			.and_then(|listener| TcpListener::from_std(listener, &Handle::current()))
+		{
+			Ok(listener) => listener,
+			Err(err) => {
+				debug!(target: "sub-libp2p", "Failed to bind on {}: {}", addr, err);
+				return Err((self, addr));
+			},
+		};
+		let new_addr = listener.local_addr().map(socket_addr_to_multiaddr).unwrap_or(addr);
+
+		let incoming = listener.incoming().filter_map(|stream| match stream.peer_addr() {
+			Ok(remote) => Some((future::ok(stream), socket_addr_to_multiaddr(remote))),
+			Err(err) => {
+				debug!(target: "sub-libp2p", "Dropping incoming connection of unknown address: {}", err);
+				None
+			},
+		});
+		Ok((Box::new(incoming), new_addr))
+	}
+
+	fn dial(self, addr: Multiaddr) -> Result<Self::Dial, (Self, Multiaddr)> {
+		self.inner.dial(addr).map_err(|(inner, addr)| (TcpV6OnlyConfig { inner }, addr))
+	}
+
+	fn nat_traversal(&self, server: &Multiaddr, observed: &Multiaddr) -> Option<Multiaddr> {
+		self.inner.nat_traversal(server, observed)
+	}
+}
+
+/// Returns the socket address of `/ip6/.../tcp/...` multiaddresses.
+fn ipv6_socket_addr(addr: &Multiaddr) -> Option<SocketAddr> {
+	let mut iter = addr.iter();
+	match (iter.next(), iter.next(), iter.next()) {
+		(Some(Protocol::Ip6(ip)), Some(Protocol::Tcp(port)), None) => Some(SocketAddr::new(ip.into(), port)),
+		_ => None,
+	}
+}
+
+fn socket_addr_to_multiaddr(addr: SocketAddr) -> Multiaddr {
+	let ip = match addr.ip() {
+		IpAddr::V4(ip) => Protocol::Ip4(ip),
+		IpAddr::V6(ip) => Protocol::Ip6(ip),
+	};
+	iter::once(ip).chain(iter::once(Protocol::Tcp(addr.port()))).collect()
+}
+
+/// Binds a listener on the given IPv6 address that doesn't accept IPv4 connections.
+fn bind_only_v6(addr: &SocketAddr) -> Result<net::TcpListener, IoError> {
+	let builder = TcpBuilder::new_v6()?;
+	builder.only_v6(true)?;
+	if cfg!(unix) {
+		builder.reuse_address(true)?;
+	}
+	builder.bind(addr)?;
+	builder.listen(1024)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::net::Ipv6Addr;
+
+	#[test]
+	fn only_ipv6_tcp_addresses_are_listened_on_by_hand() {
+		let v6: Multiaddr = "/ip6/::/tcp/30333".parse().unwrap();
+		let v4: Multiaddr = "/ip4/0.0.0.0/tcp/30333".parse().unwrap();
+		let ws: Multiaddr = "/ip6/::/tcp/30333/ws".parse().unwrap();
+		assert_eq!(ipv6_socket_addr(&v6), Some(SocketAddr::new(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0).into(), 30333)));
+		assert_eq!(ipv6_socket_addr(&v4), None);
+		assert_eq!(ipv6_socket_addr(&ws), None);
+	}
+
+	#[test]
+	fn ipv6_listener_leaves_the_ipv4_port_available() {
+		// hosts without IPv6 support can't run this test.
+		if net::TcpListener::bind("[::1]:0").is_err() {
+			return;
+		}
+
+		let v4 = net::TcpListener::bind("0.0.0.0:0").unwrap();
+		let port = v4.local_addr().unwrap().port();
+		let v6 = bind_only_v6(&SocketAddr::new(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0).into(), port)).unwrap();
+		assert_eq!(v6.local_addr().unwrap().port(), port);
+	}
+
+	#[test]
+	fn failing_to_bind_fails_listen_on() {
+		// an address of the documentation range, which isn't assigned to any interface.
+		let unavailable: Multiaddr = "/ip6/2001:db8::1/tcp/0".parse().unwrap();
+		let transport = TcpV6OnlyConfig { inner: TcpConfig::new() };
+		assert!(transport.listen_on(unavailable).is_err());
+	}
+}
@@ -17,6 +17,8 @@
 //! Configuration for the networking layer of Substrate.
 
 pub use network_libp2p::{NonReservedPeerMode, NetworkConfiguration};
+pub use request_responses::{RequestResponseConfig, IncomingRequest, RequestFailure};
+pub use notifications::{NotificationsConfig, HandshakeValidator};
 
 use chain::Client;
 use codec;
@@ -41,17 +43,36 @@ pub struct Params<B: BlockT, S, H: ExHashT> {
 	pub specialization: S,
 }
 
+/// Default maximum encoded size of a transaction accepted from or gossiped to peers, in bytes.
+pub const DEFAULT_MAX_TRANSACTION_SIZE: usize = 4 * 1024 * 1024;
+
+/// Default maximum encoded size of the transactions gossiped to a peer in one message, in bytes.
+pub const DEFAULT_MAX_TRANSACTIONS_MESSAGE_SIZE: usize = 256 * 1024;
+
 /// Configuration for the Substrate-specific part of the networking layer.
 #[derive(Clone)]
 pub struct ProtocolConfig {
 	/// Assigned roles.
 	pub roles: Roles,
+	/// Chain-specific request-response protocols.
+	pub request_response_protocols: Vec<RequestResponseConfig>,
+	/// Chain-specific notifications protocols.
+	pub notifications_protocols: Vec<NotificationsConfig>,
+	/// Maximum encoded size of a transaction accepted from or gossiped to peers, in bytes.
+	pub max_transaction_size: usize,
+	/// Maximum encoded size of the transactions gossiped to a peer in one message, in bytes.
+	/// Transactions larger than this are sent on their own.
+	pub max_transactions_message_size: usize,
 }
 
 impl Default for ProtocolConfig {
 	fn default() -> ProtocolConfig {
 		ProtocolConfig {
 			roles: Roles::FULL,
+			request_response_protocols: Vec::new(),
+			notifications_protocols: Vec::new(),
+			max_transaction_size: DEFAULT_MAX_TRANSACTION_SIZE,
+			max_transactions_message_size: DEFAULT_MAX_TRANSACTIONS_MESSAGE_SIZE,
 		}
 	}
 }
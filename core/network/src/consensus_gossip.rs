@@ -18,6 +18,7 @@
 //! Handles chain-specific and standard BFT messages.
 
 use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 use futures::sync::mpsc;
 use std::time::{Instant, Duration};
 use rand::{self, Rng};
@@ -30,10 +31,89 @@ use config::Roles;
 
 // FIXME: Add additional spam/DoS attack protection: https://github.com/paritytech/substrate/issues/1115
 const MESSAGE_LIFETIME: Duration = Duration::from_secs(600);
+/// Duration of the window in which the messages received from a peer are counted.
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(10);
+/// Maximum number of messages a peer can send us on a single topic within a window. Further
+/// messages are ignored until the window ends.
+const MAX_MESSAGES_PER_TOPIC: usize = 512;
+/// Interval at which the messages selected by the validator are sent again to all peers.
+const REBROADCAST_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Outcome of the validation of a gossip message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationResult<H> {
+	/// The message is valid: deliver it to the topic, keep it and propagate it.
+	ProcessAndKeep(H),
+	/// The message is valid: deliver it to the topic, but neither keep nor propagate it.
+	ProcessAndDiscard(H),
+	/// The message is invalid and is dropped.
+	Discard,
+}
+
+/// Validator of the messages of a gossip engine.
+///
+/// The topic returned by the validation is the one the message is delivered and kept under,
+/// which lets engines group messages independently of the topic they were sent with.
+pub trait Validator<B: BlockT>: Send + Sync {
+	/// Called when a peer connects. Returns the messages to send to that peer only, such as the
+	/// state it needs to catch up with us.
+	fn new_peer(&self, _who: NodeIndex, _roles: Roles) -> Vec<(B::Hash, ConsensusMessage)> {
+		Vec::new()
+	}
+
+	/// Validate a message received from a peer.
+	fn validate(&self, who: NodeIndex, topic: &B::Hash, message: &[u8]) -> ValidationResult<B::Hash>;
 
-struct PeerConsensus<H> {
-	known_messages: HashSet<H>,
+	/// Whether a kept message is no longer relevant and should be dropped.
+	fn message_expired(&self, _topic: &B::Hash, _message: &[u8]) -> bool {
+		false
+	}
+
+	/// Whether a kept message should be sent again to all peers, including those already
+	/// knowing it. Called at every periodic rebroadcast.
+	fn should_rebroadcast(&self, _topic: &B::Hash, _message: &[u8]) -> bool {
+		false
+	}
+
+	/// Whether a kept message may be sent to the given peer. Messages the peer isn't interested
+	/// in are neither propagated nor rebroadcast to it.
+	fn message_allowed(&self, _who: NodeIndex, _topic: &B::Hash, _message: &[u8]) -> bool {
+		true
+	}
+
+	/// Called when a peer disconnects.
+	fn peer_disconnected(&self, _who: NodeIndex) {}
+}
+
+struct PeerConsensus<T, H> {
+	known_messages: HashSet<(T, H)>,
 	is_authority: bool,
+	/// Number of messages received from the peer per topic in the current window.
+	received: HashMap<T, usize>,
+	window_start: Instant,
+}
+
+impl<T: ::std::hash::Hash + Eq, H: ::std::hash::Hash + Eq> PeerConsensus<T, H> {
+	fn new(known_messages: HashSet<(T, H)>, is_authority: bool) -> Self {
+		PeerConsensus {
+			known_messages,
+			is_authority,
+			received: HashMap::new(),
+			window_start: Instant::now(),
+		}
+	}
+
+	/// Note a message received on `topic`. Returns `false` if the peer went over its allowance
+	/// for the current window.
+	fn note_received(&mut self, topic: T, now: Instant) -> bool {
+		if now.duration_since(self.window_start) >= RATE_LIMIT_WINDOW {
+			self.window_start = now;
+			self.received.clear();
+		}
+		let count = self.received.entry(topic).or_insert(0);
+		*count += 1;
+		*count <= MAX_MESSAGES_PER_TOPIC
+	}
 }
 
 struct MessageEntry<B: BlockT> {
@@ -45,11 +125,13 @@ struct MessageEntry<B: BlockT> {
 
 /// Consensus network protocol handler. Manages statements and candidate requests.
 pub struct ConsensusGossip<B: BlockT> {
-	peers: HashMap<NodeIndex, PeerConsensus<(B::Hash, B::Hash)>>,
+	peers: HashMap<NodeIndex, PeerConsensus<B::Hash, B::Hash>>,
 	live_message_sinks: HashMap<B::Hash, mpsc::UnboundedSender<ConsensusMessage>>,
 	messages: Vec<MessageEntry<B>>,
 	known_messages: HashSet<(B::Hash, B::Hash)>,
 	session_start: Option<B::Hash>,
+	validator: Option<Arc<Validator<B>>>,
+	next_rebroadcast: Instant,
 }
 
 impl<B: BlockT> ConsensusGossip<B> {
@@ -60,10 +142,18 @@ impl<B: BlockT> ConsensusGossip<B> {
 			live_message_sinks: HashMap::new(),
 			messages: Default::default(),
 			known_messages: Default::default(),
-			session_start: None
+			session_start: None,
+			validator: None,
+			next_rebroadcast: Instant::now() + REBROADCAST_INTERVAL,
 		}
 	}
 
+	/// Register the validator of incoming messages. Replaces the default validation, which only
+	/// accepts messages whose topic isn't an ancient block.
+	pub fn register_validator(&mut self, validator: Arc<Validator<B>>) {
+		self.validator = Some(validator);
+	}
+
 	/// Closes all notification streams.
 	pub fn abort(&mut self) {
 		self.live_message_sinks.clear();
@@ -76,20 +166,28 @@ impl<B: BlockT> ConsensusGossip<B> {
 			// Send out all known messages to authorities.
 			// TODO: limit by size
 			let mut known_messages = HashSet::new();
-			for entry in self.messages.iter() {
+			let validator = &self.validator;
+			let allowed = |entry: &&MessageEntry<B>| {
+				validator.as_ref().map_or(true, |v| v.message_allowed(who, &entry.topic, &entry.message))
+			};
+			for entry in self.messages.iter().filter(allowed) {
 				known_messages.insert((entry.topic, entry.message_hash));
 				protocol.send_message(who, Message::Consensus(entry.topic.clone(), entry.message.clone()));
 			}
-			self.peers.insert(who, PeerConsensus {
-				known_messages,
-				is_authority: true,
-			});
+			self.peers.insert(who, PeerConsensus::new(known_messages, true));
 		}
 		else if roles.intersects(Roles::FULL) {
-			self.peers.insert(who, PeerConsensus {
-				known_messages: HashSet::new(),
-				is_authority: false,
-			});
+			self.peers.insert(who, PeerConsensus::new(HashSet::new(), false));
+		}
+
+		if let Some(validator) = self.validator.clone() {
+			for (topic, message) in validator.new_peer(who, roles) {
+				let message_hash = HashFor::<B>::hash(&message);
+				if let Some(peer) = self.peers.get_mut(&who) {
+					peer.known_messages.insert((topic, message_hash));
+				}
+				protocol.send_message(who, Message::Consensus(topic, message));
+			}
 		}
 	}
 
@@ -102,6 +200,11 @@ impl<B: BlockT> ConsensusGossip<B> {
 	)
 		where F: Fn() -> ConsensusMessage,
 	{
+		let validator = self.validator.clone();
+		let allowed = |id: NodeIndex| {
+			validator.as_ref().map_or(true, |v| v.message_allowed(id, &topic, &get_message()))
+		};
+
 		let mut non_authorities: Vec<_> = self.peers.iter()
 			.filter_map(|(id, ref peer)| if !peer.is_authority && !peer.known_messages.contains(&(topic, message_hash)) { Some(*id) } else { None })
 			.collect();
@@ -114,6 +217,10 @@ impl<B: BlockT> ConsensusGossip<B> {
 		};
 
 		for (id, ref mut peer) in self.peers.iter_mut() {
+			if !allowed(*id) {
+				continue;
+			}
+
 			if peer.is_authority {
 				if peer.known_messages.insert((topic.clone(), message_hash.clone())) {
 					let message = get_message();
@@ -145,18 +252,24 @@ impl<B: BlockT> ConsensusGossip<B> {
 	/// Call when a peer has been disconnected to stop tracking gossip status.
 	pub fn peer_disconnected(&mut self, _protocol: &mut Context<B>, who: NodeIndex) {
 		self.peers.remove(&who);
+		if let Some(ref validator) = self.validator {
+			validator.peer_disconnected(who);
+		}
 	}
 
 	/// Prune old or no longer relevant consensus messages. Provide a predicate
 	/// for pruning, which returns `false` when the items with a given topic should be pruned.
+	/// Messages the validator considers expired are pruned too.
 	pub fn collect_garbage<P: Fn(&B::Hash) -> bool>(&mut self, predicate: P) {
 		self.live_message_sinks.retain(|_, sink| !sink.is_closed());
 
 		let hashes = &mut self.known_messages;
+		let validator = &self.validator;
 		let before = self.messages.len();
 		let now = Instant::now();
 		self.messages.retain(|entry| {
-			if entry.instant + MESSAGE_LIFETIME >= now && predicate(&entry.topic) {
+			let expired = validator.as_ref().map_or(false, |v| v.message_expired(&entry.topic, &entry.message));
+			if entry.instant + MESSAGE_LIFETIME >= now && predicate(&entry.topic) && !expired {
 				true
 			} else {
 				hashes.remove(&(entry.topic, entry.message_hash));
@@ -169,6 +282,33 @@ impl<B: BlockT> ConsensusGossip<B> {
 		}
 	}
 
+	/// Perform time based maintenance: prune stale messages and periodically send the messages
+	/// selected by the validator again to all peers.
+	pub fn tick(&mut self, protocol: &mut Context<B>) {
+		self.collect_garbage(|_| true);
+
+		let now = Instant::now();
+		if now < self.next_rebroadcast {
+			return;
+		}
+		self.next_rebroadcast = now + REBROADCAST_INTERVAL;
+
+		let validator = match self.validator {
+			Some(ref validator) => validator,
+			None => return,
+		};
+		for entry in self.messages.iter().filter(|e| validator.should_rebroadcast(&e.topic, &e.message)) {
+			for (id, peer) in self.peers.iter_mut() {
+				if !validator.message_allowed(*id, &entry.topic, &entry.message) {
+					continue;
+				}
+				trace!(target:"gossip", "Rebroadcasting to {}: {:?}", id, entry.message);
+				peer.known_messages.insert((entry.topic, entry.message_hash));
+				protocol.send_message(*id, Message::Consensus(entry.topic, entry.message.clone()));
+			}
+		}
+	}
+
 	/// Get all incoming messages for a topic.
 	pub fn messages_for(&mut self, topic: B::Hash) -> mpsc::UnboundedReceiver<ConsensusMessage> {
 		let (tx, rx) = mpsc::unbounded();
@@ -181,9 +321,11 @@ impl<B: BlockT> ConsensusGossip<B> {
 	}
 
 	/// Handle an incoming ConsensusMessage for topic by who via protocol. Discard message if topic
-	/// already known, the message is old, its source peers isn't a registered peer or the connection
-	/// to them is broken. Return `Some(topic, message)` if it was added to the internal queue, `None`
-	/// in all other cases.
+	/// already known, the message is old, its source peers isn't a registered peer, the peer is
+	/// flooding the topic or the connection to them is broken. Return `Some(topic, message)` if it
+	/// was added to the internal queue, `None` in all other cases.
+	///
+	/// The message is never sent back to the peer it came from, even if it was already known.
 	pub fn on_incoming(
 		&mut self,
 		protocol: &mut Context<B>,
@@ -193,29 +335,54 @@ impl<B: BlockT> ConsensusGossip<B> {
 	) -> Option<(B::Hash, ConsensusMessage)> {
 		let message_hash = HashFor::<B>::hash(&message[..]);
 
+		match self.peers.get_mut(&who) {
+			Some(peer) => {
+				if !peer.note_received(topic, Instant::now()) {
+					trace!(target:"gossip", "Ignored message from {} in {}: topic rate limit reached", who, topic);
+					return None;
+				}
+				peer.known_messages.insert((topic, message_hash));
+			},
+			None => {
+				trace!(target:"gossip", "Ignored statement from unregistered peer {}", who);
+				return None;
+			},
+		}
+
 		if self.known_messages.contains(&(topic, message_hash)) {
 			trace!(target:"gossip", "Ignored already known message from {} in {}", who, topic);
 			return None;
 		}
 
-		match (protocol.client().info(), protocol.client().header(&BlockId::Hash(topic))) {
-			(_, Err(e)) | (Err(e), _) => {
-				debug!(target:"gossip", "Error reading blockchain: {:?}", e);
-				return None;
-			},
-			(Ok(info), Ok(Some(header))) => {
-				if header.number() < &info.chain.best_number {
-					trace!(target:"gossip", "Ignored ancient message from {}, hash={}", who, topic);
+		let (topic, keep) = match self.validator {
+			Some(ref validator) => match validator.validate(who, &topic, &message) {
+				ValidationResult::ProcessAndKeep(topic) => (topic, true),
+				ValidationResult::ProcessAndDiscard(topic) => (topic, false),
+				ValidationResult::Discard => {
+					trace!(target:"gossip", "Discarded invalid message from {} in {}", who, topic);
 					return None;
+				},
+			},
+			None => {
+				match (protocol.client().info(), protocol.client().header(&BlockId::Hash(topic))) {
+					(_, Err(e)) | (Err(e), _) => {
+						debug!(target:"gossip", "Error reading blockchain: {:?}", e);
+						return None;
+					},
+					(Ok(info), Ok(Some(header))) => {
+						if header.number() < &info.chain.best_number {
+							trace!(target:"gossip", "Ignored ancient message from {}, hash={}", who, topic);
+							return None;
+						}
+					},
+					(Ok(_), Ok(None)) => {},
 				}
+				(topic, true)
 			},
-			(Ok(_), Ok(None)) => {},
-		}
-
+		};
 
-		if let Some(ref mut peer) = self.peers.get_mut(&who) {
+		{
 			use std::collections::hash_map::Entry;
-			peer.known_messages.insert((topic, message_hash));
 			if let Entry::Occupied(mut entry) = self.live_message_sinks.entry(topic) {
 				debug!(target: "gossip", "Pushing consensus message to sink for {}.", topic);
 				if let Err(e) = entry.get().unbounded_send(message.clone()) {
@@ -226,15 +393,23 @@ impl<B: BlockT> ConsensusGossip<B> {
 					entry.remove_entry();
 				}
 			}
-		} else {
-			trace!(target:"gossip", "Ignored statement from unregistered peer {}", who);
-			return None;
 		}
 
-		self.multicast_inner(protocol, message_hash, topic, || message.clone());
+		if keep {
+			self.multicast_inner(protocol, message_hash, topic, || message.clone());
+		}
 		Some((topic, message))
 	}
 
+	/// Send a message to all connected peers, without keeping or propagating it. Meant for
+	/// messages describing the local node, such as its view of a consensus protocol.
+	pub fn send_to_all(&mut self, protocol: &mut Context<B>, topic: B::Hash, message: ConsensusMessage) {
+		for id in self.peers.keys() {
+			trace!(target:"gossip", "Sending to {}: {:?}", id, message);
+			protocol.send_message(*id, Message::Consensus(topic, message.clone()));
+		}
+	}
+
 	/// Multicast a message to all peers.
 	pub fn multicast(&mut self, protocol: &mut Context<B>, topic: B::Hash, message: ConsensusMessage) {
 		let message_hash = HashFor::<B>::hash(&message);
@@ -345,4 +520,130 @@ mod tests {
 
 		assert_eq!(consensus.messages.len(), 2);
 	}
+
+	#[test]
+	fn peer_rate_limited_per_topic_and_window() {
+		let mut peer = PeerConsensus::<H256, H256>::new(HashSet::new(), false);
+		let now = Instant::now();
+		let topic_a = H256::random();
+		let topic_b = H256::random();
+
+		for _ in 0..MAX_MESSAGES_PER_TOPIC {
+			assert!(peer.note_received(topic_a, now));
+		}
+		assert!(!peer.note_received(topic_a, now));
+		assert!(peer.note_received(topic_b, now));
+
+		// a new window resets the allowance.
+		assert!(peer.note_received(topic_a, now + RATE_LIMIT_WINDOW));
+	}
+
+	#[test]
+	fn validator_decides_what_is_kept_and_sent_to_new_peers() {
+		use network_libp2p::Severity;
+		use protocol::PeerInfo;
+
+		struct TestContext(Vec<(NodeIndex, ::message::Message<Block>)>);
+		impl Context<Block> for TestContext {
+			fn client(&self) -> &::chain::Client<Block> { unimplemented!() }
+			fn report_peer(&mut self, _: NodeIndex, _: Severity) {}
+			fn peer_info(&self, _: NodeIndex) -> Option<PeerInfo<Block>> { None }
+			fn send_message(&mut self, who: NodeIndex, data: ::message::Message<Block>) { self.0.push((who, data)); }
+		}
+
+		struct TestValidator;
+		impl Validator<Block> for TestValidator {
+			fn new_peer(&self, _who: NodeIndex, _roles: Roles) -> Vec<(H256, ConsensusMessage)> {
+				vec![(Default::default(), vec![9])]
+			}
+
+			fn validate(&self, _who: NodeIndex, topic: &H256, message: &[u8]) -> ValidationResult<H256> {
+				match message[0] {
+					0 => ValidationResult::Discard,
+					1 => ValidationResult::ProcessAndDiscard(*topic),
+					_ => ValidationResult::ProcessAndKeep(*topic),
+				}
+			}
+
+			fn message_expired(&self, _topic: &H256, message: &[u8]) -> bool {
+				message[0] == 3
+			}
+		}
+
+		let mut consensus = ConsensusGossip::<Block>::new();
+		consensus.register_validator(Arc::new(TestValidator));
+		let mut context = TestContext(Vec::new());
+		let topic = H256::random();
+
+		consensus.new_peer(&mut context, 1, Roles::FULL);
+		assert_eq!(context.0.len(), 1);
+		match context.0[0] {
+			(1, Message::Consensus(ref t, ref m)) => assert_eq!((*t, &m[..]), (H256::default(), &[9u8][..])),
+			_ => panic!("unexpected message"),
+		}
+
+		assert!(consensus.on_incoming(&mut context, 1, topic, vec![0]).is_none());
+		assert!(consensus.on_incoming(&mut context, 1, topic, vec![1]).is_some());
+		assert!(consensus.messages.is_empty());
+		assert!(consensus.on_incoming(&mut context, 1, topic, vec![2]).is_some());
+		assert!(consensus.on_incoming(&mut context, 1, topic, vec![3]).is_some());
+		assert_eq!(consensus.messages.len(), 2);
+
+		consensus.collect_garbage(|_| true);
+		assert_eq!(consensus.messages.len(), 1);
+		assert_eq!(consensus.messages[0].message, vec![2]);
+	}
+
+	#[test]
+	fn messages_only_sent_to_peers_allowed_by_validator() {
+		use network_libp2p::Severity;
+		use protocol::PeerInfo;
+
+		struct TestContext(Vec<(NodeIndex, ::message::Message<Block>)>);
+		impl Context<Block> for TestContext {
+			fn client(&self) -> &::chain::Client<Block> { unimplemented!() }
+			fn report_peer(&mut self, _: NodeIndex, _: Severity) {}
+			fn peer_info(&self, _: NodeIndex) -> Option<PeerInfo<Block>> { None }
+			fn send_message(&mut self, who: NodeIndex, data: ::message::Message<Block>) { self.0.push((who, data)); }
+		}
+
+		// peer 1 only wants messages starting with 1.
+		struct TestValidator;
+		impl Validator<Block> for TestValidator {
+			fn validate(&self, _who: NodeIndex, topic: &H256, _message: &[u8]) -> ValidationResult<H256> {
+				ValidationResult::ProcessAndKeep(*topic)
+			}
+
+			fn message_allowed(&self, who: NodeIndex, _topic: &H256, message: &[u8]) -> bool {
+				who != 1 || message[0] == 1
+			}
+		}
+
+		let mut consensus = ConsensusGossip::<Block>::new();
+		consensus.register_validator(Arc::new(TestValidator));
+		let mut context = TestContext(Vec::new());
+		let topic = H256::random();
+
+		consensus.new_peer(&mut context, 1, Roles::AUTHORITY);
+		consensus.new_peer(&mut context, 2, Roles::AUTHORITY);
+
+		consensus.multicast(&mut context, topic, vec![1]);
+		consensus.multicast(&mut context, topic, vec![2]);
+		{
+			let sent_to = |who| context.0.iter().filter(|&&(id, _)| id == who).count();
+			assert_eq!((sent_to(1), sent_to(2)), (1, 2));
+		}
+
+		// messages sent to all peers are not kept.
+		context.0.clear();
+		consensus.send_to_all(&mut context, topic, vec![3]);
+		assert_eq!(context.0.len(), 2);
+		assert_eq!(consensus.messages.len(), 2);
+
+		// peers connecting later only receive the messages they are allowed to.
+		context.0.clear();
+		consensus.peer_disconnected(&mut context, 1);
+		consensus.new_peer(&mut context, 1, Roles::AUTHORITY);
+		assert_eq!(context.0.len(), 1);
+	}
 }
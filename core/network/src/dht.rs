@@ -0,0 +1,585 @@
+// Copyright 2018 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! DHT records.
+//!
+//! Records are key-value pairs replicated on the connected peers whose identity is the closest
+//! to the key, using the Kademlia XOR metric. This is not a Kademlia DHT: there is no routing
+//! table and no iterative lookup through peers we aren't connected to. A record is only found
+//! by nodes sharing some of the closest connected peers with the node that put it, which holds
+//! in small and densely connected networks, e.g. among validators.
+//!
+//! Records are signed by the node putting them and stored once per signer: a record can only be
+//! replaced by its signer, with a record of a greater sequence number, so that an old signed record
+//! can't be replayed over a newer one. Getting a key returns the records of every signer. Values
+//! are not interpreted in any way: subsystems such as authority discovery pick the records of the
+//! signers they trust.
+//!
+//! Stores are acknowledged by the peers, and a put only succeeds if at least one of them stored
+//! the record.
+
+use std::cmp;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use codec::Encode;
+use futures::sync::mpsc;
+use network_libp2p::{NodeIndex, Severity};
+use primitives::{blake2_256, ed25519};
+use message::{DhtGetValue, DhtPutValue, DhtPutValueResponse, DhtRecord, DhtValue, RequestId};
+
+/// Number of peers a record is replicated on.
+const REPLICATION_FACTOR: usize = 20;
+/// Duration after which a stored record is discarded if it hasn't been put again.
+const RECORD_TTL: Duration = Duration::from_secs(36 * 60 * 60);
+/// Duration after which a query is considered finished.
+const QUERY_TIMEOUT: Duration = Duration::from_secs(30);
+/// Maximum number of records stored on behalf of other nodes.
+const MAX_RECORDS: usize = 1024;
+/// Maximum number of records of different signers stored for the same key.
+const MAX_RECORDS_PER_KEY: usize = 16;
+/// Maximum size, in bytes, of a record value.
+const MAX_VALUE_SIZE: usize = 64 * 1024;
+/// Number of timed out queries whose late answers are still recognized, so that slow but honest
+/// peers aren't treated as misbehaving.
+const MAX_TIMED_OUT_QUERIES: usize = 1024;
+
+/// Event generated by the DHT.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DhtEvent {
+	/// The records found for a key, with valid signatures.
+	ValueFound(Vec<DhtRecord>),
+	/// No record could be found for a key.
+	ValueNotFound(Vec<u8>),
+	/// A record has been stored by at least one peer.
+	ValuePut(Vec<u8>),
+	/// A record hasn't been stored by any peer.
+	ValuePutFailed(Vec<u8>),
+}
+
+impl DhtRecord {
+	/// Create a record with the given sequence number, signed with the given key.
+	pub fn new(key: Vec<u8>, value: Vec<u8>, seq: u64, signer: &ed25519::Pair) -> Self {
+		let signature = signer.sign(&(&key, &value, seq).encode());
+		DhtRecord {
+			key,
+			value,
+			seq,
+			signer: signer.public(),
+			signature,
+		}
+	}
+
+	/// Check the signature of the record.
+	pub fn verify(&self) -> bool {
+		ed25519::verify_strong(&self.signature, &(&self.key, &self.value, self.seq).encode(), &self.signer)
+	}
+}
+
+struct Record {
+	record: DhtRecord,
+	expires: Instant,
+}
+
+enum QueryKind {
+	/// Looking for the records of the key, along with those found so far.
+	Get(Vec<DhtRecord>),
+	/// Putting a record, along with whether a peer stored it so far.
+	Put { stored: bool },
+}
+
+struct PendingQuery {
+	key: Vec<u8>,
+	kind: QueryKind,
+	waiting: HashSet<NodeIndex>,
+	started: Instant,
+}
+
+/// Local view of the DHT.
+pub(crate) struct Dht {
+	records: HashMap<(Vec<u8>, ed25519::Public), Record>,
+	queries: HashMap<RequestId, PendingQuery>,
+	next_query_id: RequestId,
+	/// Sequence number of the last record we have put.
+	last_seq: u64,
+	/// Peers that hadn't answered a query when it timed out, oldest first.
+	timed_out: VecDeque<(NodeIndex, RequestId)>,
+	timed_out_set: HashSet<(NodeIndex, RequestId)>,
+	event_sinks: Vec<mpsc::UnboundedSender<DhtEvent>>,
+}
+
+/// Returns the `count` peers whose identity is the closest to `key`.
+///
+/// `peers` is a list of connected peers along with the bytes of their `PeerId`.
+pub(crate) fn closest_peers(key: &[u8], peers: Vec<(NodeIndex, Vec<u8>)>, count: usize) -> Vec<NodeIndex> {
+	let target = blake2_256(key);
+	let mut peers: Vec<_> = peers.into_iter()
+		.map(|(who, id)| {
+			let mut distance = blake2_256(&id);
+			for (d, t) in distance.iter_mut().zip(target.iter()) {
+				*d ^= *t;
+			}
+			(distance, who)
+		})
+		.collect();
+	peers.sort();
+	peers.into_iter().take(count).map(|(_, who)| who).collect()
+}
+
+impl Dht {
+	/// Create a new, empty, instance.
+	pub fn new() -> Self {
+		Dht {
+			records: HashMap::new(),
+			queries: HashMap::new(),
+			next_query_id: 0,
+			last_seq: 0,
+			timed_out: VecDeque::new(),
+			timed_out_set: HashSet::new(),
+			event_sinks: Vec::new(),
+		}
+	}
+
+	/// Returns a stream of DHT events.
+	pub fn event_stream(&mut self) -> mpsc::UnboundedReceiver<DhtEvent> {
+		let (tx, rx) = mpsc::unbounded();
+		self.event_sinks.push(tx);
+		rx
+	}
+
+	fn dispatch(&mut self, event: DhtEvent) {
+		self.event_sinks.retain(|sink| sink.unbounded_send(event.clone()).is_ok());
+	}
+
+	fn start_query(&mut self, key: Vec<u8>, kind: QueryKind, targets: &[NodeIndex]) -> RequestId {
+		let id = self.next_query_id;
+		self.next_query_id += 1;
+		self.queries.insert(id, PendingQuery {
+			key,
+			kind,
+			waiting: targets.iter().cloned().collect(),
+			started: Instant::now(),
+		});
+		id
+	}
+
+	/// Start putting a record signed with the given key. Returns the messages to send to each of
+	/// the given peers.
+	pub fn put_value(
+		&mut self,
+		key: Vec<u8>,
+		value: Vec<u8>,
+		signer: &ed25519::Pair,
+		peers: Vec<(NodeIndex, Vec<u8>)>,
+	) -> Vec<(NodeIndex, DhtPutValue)> {
+		let targets = closest_peers(&key, peers, REPLICATION_FACTOR);
+		if targets.is_empty() || value.len() > MAX_VALUE_SIZE {
+			self.dispatch(DhtEvent::ValuePutFailed(key));
+			return Vec::new();
+		}
+
+		let record = DhtRecord::new(key, value, self.next_seq(), signer);
+		let id = self.start_query(record.key.clone(), QueryKind::Put { stored: false }, &targets);
+		targets.into_iter()
+			.map(|who| (who, DhtPutValue { id, record: record.clone() }))
+			.collect()
+	}
+
+	// Returns a sequence number greater than the ones of the records we have put so far. It is
+	// based on the system time so that it keeps increasing across restarts.
+	fn next_seq(&mut self) -> u64 {
+		let now = SystemTime::now().duration_since(UNIX_EPOCH)
+			.map(|elapsed| elapsed.as_secs() * 1_000 + u64::from(elapsed.subsec_millis()))
+			.unwrap_or(0);
+		self.last_seq = cmp::max(now, self.last_seq + 1);
+		self.last_seq
+	}
+
+	/// Start looking for the records of a key. Returns the messages to send to each of the
+	/// given peers.
+	pub fn get_value(&mut self, key: Vec<u8>, peers: Vec<(NodeIndex, Vec<u8>)>) -> Vec<(NodeIndex, DhtGetValue)> {
+		let targets = closest_peers(&key, peers, REPLICATION_FACTOR);
+		if targets.is_empty() {
+			self.dispatch(DhtEvent::ValueNotFound(key));
+			return Vec::new();
+		}
+
+		let id = self.start_query(key.clone(), QueryKind::Get(Vec::new()), &targets);
+		targets.into_iter()
+			.map(|who| (who, DhtGetValue { id, key: key.clone() }))
+			.collect()
+	}
+
+	/// Called when a peer asks us to store a record. Returns the acknowledgement to send back.
+	pub fn on_put_value(&mut self, who: NodeIndex, put: DhtPutValue) -> Result<DhtPutValueResponse, Severity<'static>> {
+		if put.record.value.len() > MAX_VALUE_SIZE {
+			return Err(Severity::Bad("Peer tried to store an oversized DHT record"));
+		}
+		if !put.record.verify() {
+			return Err(Severity::Bad("Peer tried to store a DHT record with a bad signature"));
+		}
+
+		let key = put.record.key.clone();
+		let stored = self.store(put.record);
+		if !stored {
+			trace!(target: "sync", "Ignoring DHT record from {}: store is full or record is outdated", who);
+		}
+
+		Ok(DhtPutValueResponse { id: put.id, key, stored })
+	}
+
+	// Store a record with a valid signature, replacing the one of the same signer if it is newer.
+	// Putting the same record again only refreshes it. Returns whether the record has been stored.
+	fn store(&mut self, record: DhtRecord) -> bool {
+		let slot = (record.key.clone(), record.signer.clone());
+		match self.records.get(&slot) {
+			Some(stored) => if stored.record.seq > record.seq ||
+				(stored.record.seq == record.seq && stored.record != record)
+			{
+				return false;
+			},
+			None => {
+				let records_for_key = self.records.keys().filter(|other| other.0 == record.key).count();
+				if self.records.len() >= MAX_RECORDS || records_for_key >= MAX_RECORDS_PER_KEY {
+					return false;
+				}
+			},
+		}
+
+		self.records.insert(slot, Record {
+			record,
+			expires: Instant::now() + RECORD_TTL,
+		});
+		true
+	}
+
+	/// Called when a peer asks us for the records of a key. Returns the answer to send back.
+	pub fn on_get_value(&self, get: DhtGetValue) -> DhtValue {
+		let now = Instant::now();
+		let records = self.records.values()
+			.filter(|stored| stored.record.key == get.key && stored.expires > now)
+			.map(|stored| stored.record.clone())
+			.collect();
+		DhtValue {
+			id: get.id,
+			key: get.key,
+			records,
+		}
+	}
+
+	/// Called when a peer answers one of our get queries.
+	pub fn on_value(&mut self, who: NodeIndex, response: DhtValue) -> Result<(), Severity<'static>> {
+		let finished = {
+			let query = match self.queries.get_mut(&response.id) {
+				Some(query) => query,
+				None if self.timed_out_set.remove(&(who, response.id)) => {
+					trace!(target: "sync", "Ignoring late DHT value for query {} from {}", response.id, who);
+					return Ok(());
+				},
+				None => return Err(Severity::Bad("Unexpected DHT value received from peer")),
+			};
+			if query.key != response.key || !query.waiting.contains(&who) {
+				return Err(Severity::Bad("Unexpected DHT value received from peer"));
+			}
+
+			match query.kind {
+				QueryKind::Get(ref mut records) => for record in response.records {
+					if record.key != response.key || !record.verify() {
+						return Err(Severity::Bad("Peer sent a DHT record with a bad signature"));
+					}
+					if !records.contains(&record) {
+						records.push(record);
+					}
+				},
+				QueryKind::Put { .. } => return Err(Severity::Bad("Unexpected DHT value received from peer")),
+			}
+
+			query.waiting.remove(&who);
+			query.waiting.is_empty()
+		};
+
+		if finished {
+			self.finish_query(response.id);
+		}
+		Ok(())
+	}
+
+	/// Called when a peer acknowledges one of our put queries.
+	pub fn on_put_value_response(&mut self, who: NodeIndex, response: DhtPutValueResponse) -> Result<(), Severity<'static>> {
+		let finished = {
+			let query = match self.queries.get_mut(&response.id) {
+				Some(query) => query,
+				None if self.timed_out_set.remove(&(who, response.id)) => {
+					trace!(target: "sync", "Ignoring late DHT store acknowledgement for query {} from {}", response.id, who);
+					return Ok(());
+				},
+				None => return Err(Severity::Bad("Unexpected DHT store acknowledgement received from peer")),
+			};
+			if query.key != response.key || !query.waiting.contains(&who) {
+				return Err(Severity::Bad("Unexpected DHT store acknowledgement received from peer"));
+			}
+
+			match query.kind {
+				QueryKind::Put { ref mut stored } => *stored |= response.stored,
+				QueryKind::Get(_) => return Err(Severity::Bad("Unexpected DHT store acknowledgement received from peer")),
+			}
+
+			query.waiting.remove(&who);
+			query.waiting.is_empty()
+		};
+
+		if finished {
+			self.finish_query(response.id);
+		}
+		Ok(())
+	}
+
+	/// Called when a peer disconnects.
+	pub fn peer_disconnected(&mut self, who: NodeIndex) {
+		let finished: Vec<_> = self.queries.iter_mut()
+			.filter_map(|(id, query)| if query.waiting.remove(&who) && query.waiting.is_empty() {
+				Some(*id)
+			} else {
+				None
+			})
+			.collect();
+		for id in finished {
+			self.finish_query(id);
+		}
+		self.timed_out.retain(|key| key.0 != who);
+		self.timed_out_set.retain(|key| key.0 != who);
+	}
+
+	/// Perform time-based maintenance: finish timed-out queries and drop expired records.
+	pub fn maintain(&mut self) {
+		let now = Instant::now();
+		self.records.retain(|_, record| record.expires > now);
+
+		let expired: Vec<_> = self.queries.iter()
+			.filter(|(_, query)| now.duration_since(query.started) > QUERY_TIMEOUT)
+			.map(|(id, _)| *id)
+			.collect();
+		for id in expired {
+			let waiting: Vec<_> = match self.queries.get(&id) {
+				Some(query) => query.waiting.iter().cloned().collect(),
+				None => continue,
+			};
+			for who in waiting {
+				self.note_timed_out((who, id));
+			}
+			self.finish_query(id);
+		}
+	}
+
+	/// Remember a peer that hasn't answered a timed out query, forgetting the oldest one if
+	/// there are too many.
+	fn note_timed_out(&mut self, key: (NodeIndex, RequestId)) {
+		if self.timed_out.len() >= MAX_TIMED_OUT_QUERIES {
+			if let Some(oldest) = self.timed_out.pop_front() {
+				self.timed_out_set.remove(&oldest);
+			}
+		}
+		self.timed_out.push_back(key);
+		self.timed_out_set.insert(key);
+	}
+
+	fn finish_query(&mut self, id: RequestId) {
+		if let Some(query) = self.queries.remove(&id) {
+			let event = match query.kind {
+				QueryKind::Get(ref records) if records.is_empty() => DhtEvent::ValueNotFound(query.key),
+				QueryKind::Get(records) => DhtEvent::ValueFound(records),
+				QueryKind::Put { stored: true } => DhtEvent::ValuePut(query.key),
+				QueryKind::Put { stored: false } => DhtEvent::ValuePutFailed(query.key),
+			};
+			self.dispatch(event);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use futures::{Future, Stream};
+
+	fn peers(count: usize) -> Vec<(NodeIndex, Vec<u8>)> {
+		(0..count).map(|i| (i, vec![i as u8; 8])).collect()
+	}
+
+	fn signer(seed: u8) -> ed25519::Pair {
+		ed25519::Pair::from_seed(&[seed; 32])
+	}
+
+	#[test]
+	fn closest_peers_are_deterministic_and_bounded() {
+		let first = closest_peers(b"key", peers(30), REPLICATION_FACTOR);
+		let second = closest_peers(b"key", peers(30).into_iter().rev().collect(), REPLICATION_FACTOR);
+		assert_eq!(first.len(), REPLICATION_FACTOR);
+		assert_eq!(first, second);
+	}
+
+	#[test]
+	fn put_and_get_value() {
+		let mut local = Dht::new();
+		let mut remote = Dht::new();
+		let events = local.event_stream();
+
+		let puts = local.put_value(b"key".to_vec(), b"value".to_vec(), &signer(1), peers(1));
+		assert_eq!(puts.len(), 1);
+		let record = puts[0].1.record.clone();
+		for (who, put) in puts {
+			let response = remote.on_put_value(who, put).unwrap();
+			assert!(response.stored);
+			local.on_put_value_response(who, response).unwrap();
+		}
+
+		let gets = local.get_value(b"key".to_vec(), peers(1));
+		assert_eq!(gets.len(), 1);
+		for (who, get) in gets {
+			local.on_value(who, remote.on_get_value(get)).unwrap();
+		}
+
+		let missing = local.get_value(b"missing".to_vec(), peers(1));
+		for (who, get) in missing {
+			local.on_value(who, remote.on_get_value(get)).unwrap();
+		}
+		drop(local);
+
+		assert_eq!(events.collect().wait().unwrap(), vec![
+			DhtEvent::ValuePut(b"key".to_vec()),
+			DhtEvent::ValueFound(vec![record]),
+			DhtEvent::ValueNotFound(b"missing".to_vec()),
+		]);
+	}
+
+	#[test]
+	fn records_are_only_replaced_by_their_signer() {
+		let mut dht = Dht::new();
+		let put = |id, value: &[u8], seed| DhtPutValue {
+			id,
+			record: DhtRecord::new(b"key".to_vec(), value.to_vec(), id, &signer(seed)),
+		};
+
+		assert!(dht.on_put_value(0, put(0, b"first", 1)).unwrap().stored);
+		assert!(dht.on_put_value(1, put(0, b"other", 2)).unwrap().stored);
+		assert!(dht.on_put_value(0, put(1, b"second", 1)).unwrap().stored);
+
+		let mut values: Vec<_> = dht.on_get_value(DhtGetValue { id: 0, key: b"key".to_vec() })
+			.records
+			.into_iter()
+			.map(|record| record.value)
+			.collect();
+		values.sort();
+		assert_eq!(values, vec![b"other".to_vec(), b"second".to_vec()]);
+	}
+
+	#[test]
+	fn outdated_records_are_not_stored() {
+		let mut dht = Dht::new();
+		let put = |seq, value: &[u8]| DhtPutValue {
+			id: 0,
+			record: DhtRecord::new(b"key".to_vec(), value.to_vec(), seq, &signer(1)),
+		};
+
+		assert!(dht.on_put_value(0, put(2, b"newer")).unwrap().stored);
+		// replaying an older record or another record with the same sequence number is refused.
+		assert!(!dht.on_put_value(0, put(1, b"older")).unwrap().stored);
+		assert!(!dht.on_put_value(0, put(2, b"other")).unwrap().stored);
+		// putting the same record again refreshes it.
+		assert!(dht.on_put_value(0, put(2, b"newer")).unwrap().stored);
+
+		let records = dht.on_get_value(DhtGetValue { id: 0, key: b"key".to_vec() }).records;
+		assert_eq!(records, vec![put(2, b"newer").record]);
+	}
+
+	#[test]
+	fn own_records_have_increasing_sequence_numbers() {
+		let mut dht = Dht::new();
+		let first = dht.put_value(b"key".to_vec(), b"first".to_vec(), &signer(1), peers(1));
+		let second = dht.put_value(b"key".to_vec(), b"second".to_vec(), &signer(1), peers(1));
+		assert!(second[0].1.record.seq > first[0].1.record.seq);
+	}
+
+	#[test]
+	fn records_with_bad_signatures_are_rejected() {
+		let mut local = Dht::new();
+		let mut remote = Dht::new();
+
+		let mut record = DhtRecord::new(b"key".to_vec(), b"value".to_vec(), 0, &signer(1));
+		record.value = b"forged".to_vec();
+		assert!(remote.on_put_value(0, DhtPutValue { id: 0, record: record.clone() }).is_err());
+
+		let gets = local.get_value(b"key".to_vec(), peers(1));
+		let response = DhtValue { id: gets[0].1.id, key: b"key".to_vec(), records: vec![record] };
+		assert!(local.on_value(0, response).is_err());
+	}
+
+	#[test]
+	fn put_fails_without_acknowledged_store() {
+		let mut dht = Dht::new();
+		let events = dht.event_stream();
+
+		// one peer refuses the record, the other one disconnects.
+		let puts = dht.put_value(b"key".to_vec(), b"value".to_vec(), &signer(1), peers(2));
+		assert_eq!(puts.len(), 2);
+		let response = DhtPutValueResponse { id: puts[0].1.id, key: b"key".to_vec(), stored: false };
+		dht.on_put_value_response(puts[0].0, response).unwrap();
+		dht.peer_disconnected(puts[1].0);
+		drop(dht);
+
+		assert_eq!(events.collect().wait().unwrap(), vec![
+			DhtEvent::ValuePutFailed(b"key".to_vec()),
+		]);
+	}
+
+	#[test]
+	fn query_finishes_when_peers_disconnect() {
+		let mut dht = Dht::new();
+		let events = dht.event_stream();
+
+		assert!(dht.put_value(b"key".to_vec(), b"value".to_vec(), &signer(1), Vec::new()).is_empty());
+		let gets = dht.get_value(b"key".to_vec(), peers(2));
+		assert_eq!(gets.len(), 2);
+		assert!(dht.on_value(5, DhtValue { id: gets[0].1.id, key: b"key".to_vec(), records: Vec::new() }).is_err());
+		dht.peer_disconnected(0);
+		dht.peer_disconnected(1);
+		drop(dht);
+
+		assert_eq!(events.collect().wait().unwrap(), vec![
+			DhtEvent::ValuePutFailed(b"key".to_vec()),
+			DhtEvent::ValueNotFound(b"key".to_vec()),
+		]);
+	}
+
+	#[test]
+	fn late_answers_to_timed_out_queries_are_ignored() {
+		let mut dht = Dht::new();
+		let events = dht.event_stream();
+
+		let gets = dht.get_value(b"key".to_vec(), peers(1));
+		let id = gets[0].1.id;
+		dht.queries.get_mut(&id).unwrap().started = Instant::now() - QUERY_TIMEOUT - Duration::from_secs(1);
+		dht.maintain();
+
+		let response = || DhtValue { id, key: b"key".to_vec(), records: Vec::new() };
+		assert!(dht.on_value(gets[0].0, response()).is_ok());
+		// the late answer is only expected once.
+		assert!(dht.on_value(gets[0].0, response()).is_err());
+		drop(dht);
+
+		assert_eq!(events.collect().wait().unwrap(), vec![
+			DhtEvent::ValueNotFound(b"key".to_vec()),
+		]);
+	}
+}
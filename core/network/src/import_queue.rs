@@ -23,6 +23,14 @@
 //! The `ImportQueue` trait allows such verification strategies to be instantiated.
 //! The `BasicQueue` and `BasicVerifier` traits allow serial queues to be
 //! instantiated simply.
+//!
+//! The `BasicQueue` verifies and imports both blocks and justifications on its own thread, so
+//! that heavy verification or runtime execution never stalls the network. Results are reported
+//! back to sync through the `Link`.
+//!
+//! The `BasicQueue` has a bounded capacity, reported through its status. Scheduling blocks
+//! never blocks: callers are expected to stop scheduling new blocks while the queue is full, so
+//! that a slow disk slows down sync instead of letting the queue grow without bound.
 
 use std::collections::{HashSet, VecDeque};
 use std::sync::{Arc, Weak};
@@ -31,6 +39,7 @@ use parking_lot::{Condvar, Mutex, RwLock};
 use network_libp2p::{NodeIndex, Severity};
 use primitives::AuthorityId;
 
+use runtime_primitives::Justification;
 use runtime_primitives::traits::{Block as BlockT, Header as HeaderT, NumberFor, Zero};
 
 pub use blocks::BlockData;
@@ -40,11 +49,17 @@ use protocol::Context;
 use service::ExecuteInContext;
 use sync::ChainSync;
 
-pub use consensus::{ImportBlock, BlockImport, ImportResult, BlockOrigin};
+pub use consensus::{ImportBlock, BlockImport, ImportResult, ImportedAux, BlockOrigin, JustificationImport};
 
 /// Shared block import struct used by the queue.
 pub type SharedBlockImport<B> = Arc<dyn BlockImport<B,Error=ClientError> + Send + Sync>;
 
+/// Shared justification import struct used by the queue.
+pub type SharedJustificationImport<B> = Arc<dyn JustificationImport<B,Error=ClientError> + Send + Sync>;
+
+/// The default number of blocks a `BasicQueue` holds before reporting itself full.
+pub const MAX_IMPORTING_BLOCKS: usize = 2048;
+
 #[cfg(any(test, feature = "test-helpers"))]
 use std::cell::RefCell;
 
@@ -57,11 +72,19 @@ pub trait Verifier<B: BlockT>: Send + Sync + Sized {
 		&self,
 		origin: BlockOrigin,
 		header: B::Header,
-		justification: Vec<u8>,
+		justification: Justification,
 		body: Option<Vec<B::Extrinsic>>
 	) -> Result<(ImportBlock<B>, Option<Vec<AuthorityId>>), String>;
 }
 
+/// Work scheduled on an import queue.
+pub enum ImportWork<B: BlockT> {
+	/// Import the given blocks.
+	Blocks(BlockOrigin, Vec<BlockData<B>>),
+	/// Import the justification of an already imported block, provided by the given peer.
+	Justification(NodeIndex, B::Hash, NumberFor<B>, Justification),
+}
+
 /// Blocks import queue API.
 pub trait ImportQueue<B: BlockT>: Send + Sync {
 	/// Start background work for the queue as necessary.
@@ -82,18 +105,30 @@ pub trait ImportQueue<B: BlockT>: Send + Sync {
 	fn status(&self) -> ImportQueueStatus<B>;
 	/// Is block with given hash currently in the queue.
 	fn is_importing(&self, hash: &B::Hash) -> bool;
-	/// Import bunch of blocks.
+	/// Import bunch of blocks. Never blocks, even if the queue is full.
 	fn import_blocks(&self, origin: BlockOrigin, blocks: Vec<BlockData<B>>);
+	/// Import the justification of an already imported block, provided by the given peer. The
+	/// result is reported through `Link::justification_imported`.
+	fn import_justification(&self, who: NodeIndex, hash: B::Hash, number: NumberFor<B>, justification: Justification);
 }
 
 /// Import queue status. It isn't completely accurate.
 pub struct ImportQueueStatus<B: BlockT> {
 	/// Number of blocks that are currently in the queue.
 	pub importing_count: usize,
+	/// Number of blocks the queue holds before it is full.
+	pub max_importing_count: usize,
 	/// The number of the best block that was ever in the queue since start/last failure.
 	pub best_importing_number: <<B as BlockT>::Header as HeaderT>::Number,
 }
 
+impl<B: BlockT> ImportQueueStatus<B> {
+	/// Whether no more blocks should be scheduled until the queue drains.
+	pub fn is_full(&self) -> bool {
+		self.importing_count >= self.max_importing_count
+	}
+}
+
 /// Basic block import queue that is importing blocks sequentially in a separate thread,
 /// with pluggable verification.
 pub struct BasicQueue<B: BlockT, V: 'static + Verifier<B>> {
@@ -101,33 +136,54 @@ pub struct BasicQueue<B: BlockT, V: 'static + Verifier<B>> {
 	data: Arc<AsyncImportQueueData<B>>,
 	verifier: Arc<V>,
 	block_import: SharedBlockImport<B>,
+	justification_import: Option<SharedJustificationImport<B>>,
 }
 
 /// Locks order: queue, queue_blocks, best_importing_number
 struct AsyncImportQueueData<B: BlockT> {
+	/// Signalled when blocks are queued or the queue is stopping.
 	signal: Condvar,
-	queue: Mutex<VecDeque<(BlockOrigin, Vec<BlockData<B>>)>>,
+	max_importing_count: usize,
+	queue: Mutex<VecDeque<ImportWork<B>>>,
 	queue_blocks: RwLock<HashSet<B::Hash>>,
 	best_importing_number: RwLock<<<B as BlockT>::Header as HeaderT>::Number>,
 	is_stopping: AtomicBool,
 }
 
 impl<B: BlockT, V: Verifier<B>> BasicQueue<B, V> {
-	/// Instantiate a new basic queue, with given verifier.
-	pub fn new(verifier: Arc<V>, block_import: SharedBlockImport<B>) -> Self {
+	/// Instantiate a new basic queue, with given verifier. Justifications of already imported
+	/// blocks are given to `justification_import`, if any.
+	pub fn new(
+		verifier: Arc<V>,
+		block_import: SharedBlockImport<B>,
+		justification_import: Option<SharedJustificationImport<B>>,
+	) -> Self {
+		Self::with_capacity(verifier, block_import, justification_import, MAX_IMPORTING_BLOCKS)
+	}
+
+	/// Instantiate a new basic queue reporting itself full once it holds `max_importing_count`
+	/// blocks.
+	pub fn with_capacity(
+		verifier: Arc<V>,
+		block_import: SharedBlockImport<B>,
+		justification_import: Option<SharedJustificationImport<B>>,
+		max_importing_count: usize,
+	) -> Self {
 		Self {
 			handle: Mutex::new(None),
-			data: Arc::new(AsyncImportQueueData::new()),
+			data: Arc::new(AsyncImportQueueData::new(max_importing_count)),
 			verifier,
 			block_import,
+			justification_import,
 		}
 	}
 }
 
 impl<B: BlockT> AsyncImportQueueData<B> {
-	fn new() -> Self {
+	fn new(max_importing_count: usize) -> Self {
 		Self {
 			signal: Default::default(),
+			max_importing_count,
 			queue: Mutex::new(VecDeque::new()),
 			queue_blocks: RwLock::new(HashSet::new()),
 			best_importing_number: RwLock::new(Zero::zero()),
@@ -146,8 +202,9 @@ impl<B: BlockT, V: 'static + Verifier<B>> ImportQueue<B> for BasicQueue<B, V> {
 		let qdata = self.data.clone();
 		let verifier = self.verifier.clone();
 		let block_import = self.block_import.clone();
+		let justification_import = self.justification_import.clone();
 		*self.handle.lock() = Some(::std::thread::Builder::new().name("ImportQueue".into()).spawn(move || {
-			import_thread(block_import, link, qdata, verifier)
+			import_thread(block_import, justification_import, link, qdata, verifier)
 		}).map_err(|err| Error::from(ErrorKind::Io(err)))?);
 		Ok(())
 	}
@@ -157,7 +214,11 @@ impl<B: BlockT, V: 'static + Verifier<B>> ImportQueue<B> for BasicQueue<B, V> {
 		let mut queue_blocks = self.data.queue_blocks.write();
 		let mut best_importing_number = self.data.best_importing_number.write();
 		queue_blocks.clear();
-		queue.clear();
+		// justifications are for blocks that are already imported, so they are still relevant.
+		queue.retain(|work| match *work {
+			ImportWork::Blocks(..) => false,
+			ImportWork::Justification(..) => true,
+		});
 		*best_importing_number = Zero::zero();
 	}
 
@@ -178,6 +239,7 @@ impl<B: BlockT, V: 'static + Verifier<B>> ImportQueue<B> for BasicQueue<B, V> {
 	fn status(&self) -> ImportQueueStatus<B> {
 		ImportQueueStatus {
 			importing_count: self.data.queue_blocks.read().len(),
+			max_importing_count: self.data.max_importing_count,
 			best_importing_number: *self.data.best_importing_number.read(),
 		}
 	}
@@ -201,7 +263,17 @@ impl<B: BlockT, V: 'static + Verifier<B>> ImportQueue<B> for BasicQueue<B, V> {
 		if new_best_importing_number > *best_importing_number {
 			*best_importing_number = new_best_importing_number;
 		}
-		queue.push_back((origin, blocks));
+		queue.push_back(ImportWork::Blocks(origin, blocks));
+		self.data.signal.notify_one();
+	}
+
+	fn import_justification(&self, who: NodeIndex, hash: B::Hash, number: NumberFor<B>, justification: Justification) {
+		trace!(target: "sync", "Scheduling justification of block {}: {:?} for import", number, hash);
+
+		// justifications don't count towards the capacity of the queue: they are small, and
+		// holding them back would delay finality.
+		let mut queue = self.data.queue.lock();
+		queue.push_back(ImportWork::Justification(who, hash, number, justification));
 		self.data.signal.notify_one();
 	}
 }
@@ -215,13 +287,14 @@ impl<B: BlockT, V: 'static + Verifier<B>> Drop for BasicQueue<B, V> {
 /// Blocks import thread.
 fn import_thread<B: BlockT, L: Link<B>, V: Verifier<B>>(
 	block_import: SharedBlockImport<B>,
+	justification_import: Option<SharedJustificationImport<B>>,
 	link: L,
 	qdata: Arc<AsyncImportQueueData<B>>,
 	verifier: Arc<V>
 ) {
 	trace!(target: "sync", "Starting import thread");
 	loop {
-		let new_blocks = {
+		let work = {
 			let mut queue_lock = qdata.queue.lock();
 
 			// We are holding the same lock that `stop` takes so here we either see that stop flag
@@ -235,17 +308,21 @@ fn import_thread<B: BlockT, L: Link<B>, V: Verifier<B>>(
 			}
 
 			match queue_lock.pop_front() {
-				Some(new_blocks) => new_blocks,
+				Some(work) => work,
 				None => break,
 			}
 		};
 
-		let blocks_hashes: Vec<B::Hash> = new_blocks.1.iter().map(|b| b.block.hash.clone()).collect();
-		if !import_many_blocks(
+		let blocks_hashes: Vec<B::Hash> = match work {
+			ImportWork::Blocks(_, ref blocks) => blocks.iter().map(|b| b.block.hash.clone()).collect(),
+			ImportWork::Justification(..) => Vec::new(),
+		};
+		if !import_work(
 			&*block_import,
+			justification_import.as_ref(),
 			&link,
 			Some(&*qdata),
-			new_blocks,
+			work,
 			verifier.clone(),
 		) {
 			break;
@@ -273,6 +350,10 @@ pub trait Link<B: BlockT>: Send {
 	fn note_useless_and_restart_sync(&self, _who: NodeIndex, _reason: &str) { }
 	/// Restart sync.
 	fn restart(&self) { }
+	/// Request a justification for the given block.
+	fn request_justification(&self, _hash: &B::Hash, _number: NumberFor<B>) { }
+	/// Justification provided by the given peer was imported, successfully or not.
+	fn justification_imported(&self, _who: NodeIndex, _hash: &B::Hash, _number: NumberFor<B>, _success: bool) { }
 }
 
 /// A link implementation that does nothing.
@@ -323,6 +404,14 @@ impl<B: BlockT, E: ExecuteInContext<B>> Link<B> for NetworkLink<B, E> {
 	fn restart(&self) {
 		self.with_sync(|sync, protocol| sync.restart(protocol))
 	}
+
+	fn request_justification(&self, hash: &B::Hash, number: NumberFor<B>) {
+		self.with_sync(|sync, protocol| sync.request_justification(hash, number, protocol))
+	}
+
+	fn justification_imported(&self, who: NodeIndex, hash: &B::Hash, number: NumberFor<B>, success: bool) {
+		self.with_sync(|sync, protocol| sync.justification_import_result(who, hash, number, success, protocol))
+	}
 }
 
 /// Block import successful result.
@@ -331,7 +420,7 @@ enum BlockImportResult<H: ::std::fmt::Debug + PartialEq, N: ::std::fmt::Debug +
 	/// Imported known block.
 	ImportedKnown(H, N),
 	/// Imported unknown block.
-	ImportedUnknown(H, N),
+	ImportedUnknown(H, N, ImportedAux),
 }
 
 /// Block import error.
@@ -351,6 +440,26 @@ enum BlockImportError {
 	Error,
 }
 
+/// Do the given import work, reporting the results through the link. Returns `false` when the
+/// queue is stopping.
+fn import_work<B: BlockT, V: Verifier<B>>(
+	block_import: &BlockImport<B, Error=ClientError>,
+	justification_import: Option<&SharedJustificationImport<B>>,
+	link: &Link<B>,
+	qdata: Option<&AsyncImportQueueData<B>>,
+	work: ImportWork<B>,
+	verifier: Arc<V>,
+) -> bool {
+	match work {
+		ImportWork::Blocks(origin, blocks) => import_many_blocks(block_import, link, qdata, (origin, blocks), verifier),
+		ImportWork::Justification(who, hash, number, justification) => {
+			let success = import_justification(justification_import, hash, number, justification);
+			link.justification_imported(who, &hash, number, success);
+			true
+		},
+	}
+}
+
 /// Import a bunch of blocks.
 fn import_many_blocks<'a, B: BlockT, V: Verifier<B>>(
 	import_handle: &BlockImport<B, Error=ClientError>,
@@ -452,9 +561,9 @@ fn import_single_block<B: BlockT, V: Verifier<B>>(
 			trace!(target: "sync", "Block already queued {}: {:?}", number, hash);
 			Ok(BlockImportResult::ImportedKnown(hash, number))
 		},
-		Ok(ImportResult::Queued) => {
+		Ok(ImportResult::Queued(aux)) => {
 			trace!(target: "sync", "Block queued {}: {:?}", number, hash);
-			Ok(BlockImportResult::ImportedUnknown(hash, number))
+			Ok(BlockImportResult::ImportedUnknown(hash, number, aux))
 		},
 		Ok(ImportResult::UnknownParent) => {
 			debug!(target: "sync", "Block with unknown parent {}: {:?}, parent: {:?}", number, hash, parent);
@@ -482,8 +591,12 @@ fn process_import_result<B: BlockT>(
 			link.block_imported(&hash, number);
 			1
 		},
-		Ok(BlockImportResult::ImportedUnknown(hash, number)) => {
+		Ok(BlockImportResult::ImportedUnknown(hash, number, aux)) => {
 			link.block_imported(&hash, number);
+			if aux.needs_justification {
+				trace!(target: "sync", "Block imported but requires justification {}: {:?}", number, hash);
+				link.request_justification(&hash, number);
+			}
 			1
 		},
 		Err(BlockImportError::IncompleteJustification(who)) => {
@@ -517,9 +630,36 @@ fn process_import_result<B: BlockT>(
 	}
 }
 
+/// Import the justification of an already imported block with the given justification import,
+/// if any.
+fn import_justification<B: BlockT>(
+	justification_import: Option<&SharedJustificationImport<B>>,
+	hash: B::Hash,
+	number: NumberFor<B>,
+	justification: Justification,
+) -> bool {
+	let justification_import = match justification_import {
+		Some(justification_import) => justification_import,
+		None => {
+			debug!(target: "sync", "Justification for block {}: {:?} can't be imported", number, hash);
+			return false;
+		}
+	};
+
+	match justification_import.import_justification(hash, number, justification) {
+		Ok(()) => {
+			trace!(target: "sync", "Imported justification for block {}: {:?}", number, hash);
+			true
+		},
+		Err(e) => {
+			debug!(target: "sync", "Error importing justification for block {}: {:?}: {:?}", number, hash, e);
+			false
+		},
+	}
+}
 
 #[cfg(any(test, feature = "test-helpers"))]
-struct ImportCB<B: BlockT>(RefCell<Option<Box<dyn Fn(BlockOrigin, Vec<BlockData<B>>) -> bool>>>);
+struct ImportCB<B: BlockT>(RefCell<Option<Box<dyn Fn(ImportWork<B>) -> bool>>>);
 
 #[cfg(any(test, feature = "test-helpers"))]
 impl<B: BlockT> ImportCB<B> {
@@ -527,13 +667,13 @@ impl<B: BlockT> ImportCB<B> {
 		ImportCB(RefCell::new(None))
 	}
 	fn set<F>(&self, cb: Box<F>)
-		where F: 'static + Fn(BlockOrigin, Vec<BlockData<B>>) -> bool
+		where F: 'static + Fn(ImportWork<B>) -> bool
 	{
 		*self.0.borrow_mut() = Some(cb);
 	}
-	fn call(&self, origin: BlockOrigin, data: Vec<BlockData<B>>) -> bool {
+	fn call(&self, work: ImportWork<B>) -> bool {
 		let b = self.0.borrow();
-		b.as_ref().expect("The Callback has been set before. qed.")(origin, data)
+		b.as_ref().expect("The Callback has been set before. qed.")(work)
 	}
 }
 
@@ -555,7 +695,7 @@ impl<B: BlockT> Verifier<B> for PassThroughVerifier {
 		&self,
 		origin: BlockOrigin,
 		header: B::Header,
-		justification: Vec<u8>,
+		justification: Justification,
 		body: Option<Vec<B::Extrinsic>>
 	) -> Result<(ImportBlock<B>, Option<Vec<AuthorityId>>), String> {
 		Ok((ImportBlock {
@@ -566,6 +706,7 @@ impl<B: BlockT> Verifier<B> for PassThroughVerifier {
 			justification: justification,
 			post_digests: vec![],
 			auxiliary: Vec::new(),
+			storage_changes: None,
 		}, None))
 	}
 }
@@ -577,28 +718,36 @@ pub struct SyncImportQueue<B: BlockT, V: Verifier<B>> {
 	verifier: Arc<V>,
 	link: ImportCB<B>,
 	block_import: SharedBlockImport<B>,
+	justification_import: Option<SharedJustificationImport<B>>,
 }
 
 #[cfg(any(test, feature = "test-helpers"))]
 impl<B: 'static + BlockT, V: 'static + Verifier<B>> SyncImportQueue<B, V> {
-	/// Create a new SyncImportQueue wrapping the given Verifier and block import
-	/// handle.
-	pub fn new(verifier: Arc<V>, block_import: SharedBlockImport<B>) -> Self {
+	/// Create a new SyncImportQueue wrapping the given Verifier, block import
+	/// and justification import handles.
+	pub fn new(
+		verifier: Arc<V>,
+		block_import: SharedBlockImport<B>,
+		justification_import: Option<SharedJustificationImport<B>>,
+	) -> Self {
 		let queue = SyncImportQueue {
 			verifier,
 			link: ImportCB::new(),
 			block_import,
+			justification_import,
 		};
 
 		let v = queue.verifier.clone();
 		let import_handle = queue.block_import.clone();
-		queue.link.set(Box::new(move |origin, new_blocks| {
+		let justification_import = queue.justification_import.clone();
+		queue.link.set(Box::new(move |work| {
 			let verifier = v.clone();
-			import_many_blocks(
+			import_work(
 				&*import_handle,
+				justification_import.as_ref(),
 				&NoopLink,
 				None,
-				(origin, new_blocks),
+				work,
 				verifier,
 			)
 		}));
@@ -616,13 +765,15 @@ impl<B: 'static + BlockT, V: 'static + Verifier<B>> ImportQueue<B> for SyncImpor
 	) -> Result<(), Error> {
 		let v = self.verifier.clone();
 		let import_handle = self.block_import.clone();
-		self.link.set(Box::new(move |origin, new_blocks| {
+		let justification_import = self.justification_import.clone();
+		self.link.set(Box::new(move |work| {
 			let verifier = v.clone();
-			import_many_blocks(
+			import_work(
 				&*import_handle,
+				justification_import.as_ref(),
 				&link,
 				None,
-				(origin, new_blocks),
+				work,
 				verifier,
 			)
 		}));
@@ -635,6 +786,7 @@ impl<B: 'static + BlockT, V: 'static + Verifier<B>> ImportQueue<B> for SyncImpor
 	fn status(&self) -> ImportQueueStatus<B> {
 		ImportQueueStatus {
 			importing_count: 0,
+			max_importing_count: MAX_IMPORTING_BLOCKS,
 			best_importing_number: Zero::zero(),
 		}
 	}
@@ -644,7 +796,11 @@ impl<B: 'static + BlockT, V: 'static + Verifier<B>> ImportQueue<B> for SyncImpor
 	}
 
 	fn import_blocks(&self, origin: BlockOrigin, blocks: Vec<BlockData<B>>) {
-		self.link.call(origin, blocks);
+		self.link.call(ImportWork::Blocks(origin, blocks));
+	}
+
+	fn import_justification(&self, who: NodeIndex, hash: B::Hash, number: NumberFor<B>, justification: Justification) {
+		self.link.call(ImportWork::Justification(who, hash, number, justification));
 	}
 }
 
@@ -663,6 +819,7 @@ pub mod tests {
 		maintains: Cell<usize>,
 		disconnects: Cell<usize>,
 		restarts: Cell<usize>,
+		justification_requests: Cell<usize>,
 	}
 
 	impl TestLink {
@@ -672,11 +829,13 @@ pub mod tests {
 				maintains: Cell::new(0),
 				disconnects: Cell::new(0),
 				restarts: Cell::new(0),
+				justification_requests: Cell::new(0),
 			}
 		}
 
 		fn total(&self) -> usize {
 			self.imported.get() + self.maintains.get() + self.disconnects.get() + self.restarts.get()
+				+ self.justification_requests.get()
 		}
 	}
 
@@ -697,6 +856,9 @@ pub mod tests {
 		fn restart(&self) {
 			self.restarts.set(self.restarts.get() + 1);
 		}
+		fn request_justification(&self, _hash: &Hash, _number: NumberFor<Block>) {
+			self.justification_requests.set(self.justification_requests.get() + 1);
+		}
 	}
 
 	fn prepare_good_block() -> (client::Client<test_client::Backend, test_client::Executor, Block, test_client::runtime::RuntimeApi>, Hash, u64, BlockData<Block>) {
@@ -722,7 +884,7 @@ pub mod tests {
 		let (_, hash, number, block) = prepare_good_block();
 		assert_eq!(
 			import_single_block(&test_client::new(), BlockOrigin::File, block, Arc::new(PassThroughVerifier(true))),
-			Ok(BlockImportResult::ImportedUnknown(hash, number))
+			Ok(BlockImportResult::ImportedUnknown(hash, number, Default::default()))
 		);
 	}
 
@@ -767,10 +929,17 @@ pub mod tests {
 		assert_eq!(link.imported.get(), 1);
 
 		let link = TestLink::new();
-		assert_eq!(process_import_result::<Block>(&link, Ok(BlockImportResult::ImportedUnknown(Default::default(), 0))), 1);
+		assert_eq!(process_import_result::<Block>(&link, Ok(BlockImportResult::ImportedUnknown(Default::default(), 0, Default::default()))), 1);
 		assert_eq!(link.total(), 1);
 		assert_eq!(link.imported.get(), 1);
 
+		let link = TestLink::new();
+		let aux = ImportedAux { needs_justification: true };
+		assert_eq!(process_import_result::<Block>(&link, Ok(BlockImportResult::ImportedUnknown(Default::default(), 0, aux))), 1);
+		assert_eq!(link.total(), 2);
+		assert_eq!(link.imported.get(), 1);
+		assert_eq!(link.justification_requests.get(), 1);
+
 		let link = TestLink::new();
 		assert_eq!(process_import_result::<Block>(&link, Err(BlockImportError::IncompleteHeader(Some(0)))), 0);
 		assert_eq!(link.total(), 1);
@@ -795,7 +964,7 @@ pub mod tests {
 	#[test]
 	fn import_many_blocks_stops_when_stopping() {
 		let (_, _, _, block) = prepare_good_block();
-		let qdata = AsyncImportQueueData::new();
+		let qdata = AsyncImportQueueData::new(MAX_IMPORTING_BLOCKS);
 		let verifier = Arc::new(PassThroughVerifier(true));
 		qdata.is_stopping.store(true, Ordering::SeqCst);
 		let client = test_client::new();
@@ -808,12 +977,68 @@ pub mod tests {
 		));
 	}
 
+	#[test]
+	fn full_queue_accepts_blocks_without_blocking() {
+		let (_, _, _, block) = prepare_good_block();
+		let queue = BasicQueue::with_capacity(Arc::new(PassThroughVerifier(true)), Arc::new(test_client::new()), None, 1);
+		assert!(!queue.status().is_full());
+
+		// the import thread isn't started, so nothing makes room in the queue.
+		queue.import_blocks(BlockOrigin::File, vec![block.clone()]);
+		assert!(queue.status().is_full());
+
+		let mut other = block;
+		other.block.hash = [1; 32].into();
+		queue.import_blocks(BlockOrigin::File, vec![other]);
+		assert_eq!(queue.status().importing_count, 2);
+		assert!(queue.status().is_full());
+	}
+
+	#[test]
+	fn justifications_are_imported_on_the_import_thread() {
+		use std::sync::mpsc;
+
+		struct JustificationLink(mpsc::Sender<(NodeIndex, Hash, bool)>);
+
+		impl Link<Block> for JustificationLink {
+			fn justification_imported(&self, who: NodeIndex, hash: &Hash, _number: NumberFor<Block>, success: bool) {
+				let _ = self.0.send((who, *hash, success));
+			}
+		}
+
+		let (_, hash, number, _) = prepare_good_block();
+		let (tx, rx) = mpsc::channel();
+		let queue = BasicQueue::new(Arc::new(PassThroughVerifier(true)), Arc::new(test_client::new()), None);
+		queue.start(JustificationLink(tx)).unwrap();
+		queue.import_justification(7, hash, number, vec![1, 2, 3]);
+
+		// there is no justification import, so the import fails.
+		assert_eq!(rx.recv_timeout(::std::time::Duration::from_secs(5)), Ok((7, hash, false)));
+	}
+
+	#[test]
+	fn clearing_queue_keeps_justifications() {
+		let (_, hash, number, block) = prepare_good_block();
+		let queue = BasicQueue::new(Arc::new(PassThroughVerifier(true)), Arc::new(test_client::new()), None);
+		queue.import_blocks(BlockOrigin::File, vec![block]);
+		queue.import_justification(7, hash, number, vec![1, 2, 3]);
+		queue.clear();
+
+		let queued = queue.data.queue.lock();
+		assert_eq!(queued.len(), 1);
+		match queued[0] {
+			ImportWork::Justification(7, h, n, _) => assert_eq!((h, n), (hash, number)),
+			_ => panic!("only the justification is kept"),
+		}
+		assert!(!queue.is_importing(&hash));
+	}
+
 	#[test]
 	fn async_import_queue_drops() {
 		// Perform this test multiple times since it exhibits non-deterministic behavior.
 		for _ in 0..100 {
 			let verifier = Arc::new(PassThroughVerifier(true));
-			let queue = BasicQueue::new(verifier, Arc::new(test_client::new()));
+			let queue = BasicQueue::new(verifier, Arc::new(test_client::new()), None);
 			queue.start(TestLink::new()).unwrap();
 			drop(queue);
 		}
@@ -54,6 +54,9 @@ mod io;
 mod chain;
 mod blocks;
 mod on_demand;
+mod request_responses;
+mod notifications;
+mod dht;
 pub mod config;
 pub mod import_queue;
 pub mod consensus_gossip;
@@ -75,5 +78,9 @@ pub use network_libp2p::{
 pub use message::{generic as generic_message, RequestId, Status as StatusMessage};
 pub use error::Error;
 pub use on_demand::{OnDemand, OnDemandService, RemoteResponse};
+pub use request_responses::{RequestResponseConfig, IncomingRequest, RequestFailure, ResponseFuture};
+pub use notifications::{NotificationsConfig, NotificationEvent, NotificationSink, HandshakeValidator};
+pub use dht::DhtEvent;
+pub use message::DhtRecord;
 #[doc(hidden)]
 pub use runtime_primitives::traits::Block as BlockT;
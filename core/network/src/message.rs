@@ -18,6 +18,7 @@
 
 use runtime_primitives::traits::{Block as BlockT, Header as HeaderT};
 use codec::{Encode, Decode, Input, Output};
+use primitives::ed25519;
 pub use self::generic::{
 	BlockAnnounce, RemoteCallRequest, RemoteReadRequest,
 	RemoteHeaderRequest, RemoteHeaderResponse,
@@ -122,13 +123,116 @@ pub struct RemoteReadResponse {
 	pub proof: Vec<Vec<u8>>,
 }
 
+#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
+/// Request on a registered request-response protocol.
+pub struct GenericRequest {
+	/// Unique request id.
+	pub id: RequestId,
+	/// Name of the protocol.
+	pub protocol: String,
+	/// Opaque request payload.
+	pub data: Vec<u8>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
+/// Response on a registered request-response protocol.
+pub struct GenericResponse {
+	/// Id of a request this response was made for.
+	pub id: RequestId,
+	/// Name of the protocol.
+	pub protocol: String,
+	/// Opaque response payload. `None` if the request has been refused.
+	pub data: Option<Vec<u8>>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
+/// A DHT record, signed by the node that put it.
+pub struct DhtRecord {
+	/// Record key.
+	pub key: Vec<u8>,
+	/// Record value.
+	pub value: Vec<u8>,
+	/// Sequence number. Only records with a greater sequence number replace it.
+	pub seq: u64,
+	/// The key the record is signed with. Only records signed with the same key replace it.
+	pub signer: ed25519::Public,
+	/// Signature of the encoded key, value and sequence number.
+	pub signature: ed25519::Signature,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
+/// Request to store a DHT record.
+pub struct DhtPutValue {
+	/// Unique request id.
+	pub id: RequestId,
+	/// The record to store.
+	pub record: DhtRecord,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
+/// Response to `DhtPutValue`.
+pub struct DhtPutValueResponse {
+	/// Id of a request this response was made for.
+	pub id: RequestId,
+	/// Record key.
+	pub key: Vec<u8>,
+	/// Whether the record has been stored.
+	pub stored: bool,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
+/// Request for the value of a DHT record.
+pub struct DhtGetValue {
+	/// Unique request id.
+	pub id: RequestId,
+	/// Record key.
+	pub key: Vec<u8>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
+/// Response to `DhtGetValue`.
+pub struct DhtValue {
+	/// Id of a request this response was made for.
+	pub id: RequestId,
+	/// Record key.
+	pub key: Vec<u8>,
+	/// The records known for the key, at most one per signer.
+	pub records: Vec<DhtRecord>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
+/// Handshake opening a notifications substream.
+pub struct NotificationsHandshake {
+	/// Name of the protocol.
+	pub protocol: String,
+	/// Opaque handshake payload.
+	pub handshake: Vec<u8>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
+/// Acceptance of the handshake we have sent for a notifications substream.
+pub struct NotificationsAccepted {
+	/// Name of the protocol.
+	pub protocol: String,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
+/// Notification on an open notifications substream.
+pub struct Notification {
+	/// Name of the protocol.
+	pub protocol: String,
+	/// Opaque notification payload.
+	pub data: Vec<u8>,
+}
+
 /// Generic types.
 pub mod generic {
 	use runtime_primitives::Justification;
 	use config::Roles;
 	use super::{
 		BlockAttributes, RemoteCallResponse, RemoteReadResponse,
-		RequestId, Transactions, Direction
+		GenericRequest, GenericResponse, Notification, NotificationsHandshake, NotificationsAccepted,
+		DhtPutValue, DhtPutValueResponse, DhtGetValue, DhtValue, RequestId, Transactions, Direction
 	};
 	/// Consensus is opaque to us
 	pub type ConsensusMessage = Vec<u8>;
@@ -190,6 +294,24 @@ pub mod generic {
 		RemoteChangesRequest(RemoteChangesRequest<Hash>),
 		/// Remote changes reponse.
 		RemoteChangesResponse(RemoteChangesResponse<Number, Hash>),
+		/// Request on a registered request-response protocol.
+		GenericRequest(GenericRequest),
+		/// Response on a registered request-response protocol.
+		GenericResponse(GenericResponse),
+		/// Handshake opening a notifications substream.
+		NotificationsHandshake(NotificationsHandshake),
+		/// Notification on an open notifications substream.
+		Notification(Notification),
+		/// Store a DHT record.
+		DhtPutValue(DhtPutValue),
+		/// Get the value of a DHT record.
+		DhtGetValue(DhtGetValue),
+		/// DHT record value.
+		DhtValue(DhtValue),
+		/// Acknowledgement of a DHT record store.
+		DhtPutValueResponse(DhtPutValueResponse),
+		/// Acceptance of a notifications substream handshake.
+		NotificationsAccepted(NotificationsAccepted),
 		/// Chain-specific message
 		#[codec(index = "255")]
 		ChainSpecific(Vec<u8>),
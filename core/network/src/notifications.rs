@@ -0,0 +1,382 @@
+// Copyright 2018 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Notifications protocols.
+//!
+//! A notifications protocol is a named, one-way messaging channel with each connected
+//! peer. When a peer connects, both sides send the handshake of every protocol they
+//! support, and confirm the handshakes their validator accepts. The substream is open
+//! towards a peer once we have accepted its handshake and it has accepted ours. Custom
+//! gossip engines can then send notifications to peers through a `NotificationSink` and
+//! receive `NotificationEvent`s for the protocol.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use futures::{Async, Stream, sync::mpsc};
+use network_libp2p::{NodeIndex, Severity};
+use message::{Notification, NotificationsAccepted, NotificationsHandshake};
+
+/// Validator of the handshake sent by a remote when opening a notifications substream.
+pub type HandshakeValidator = Arc<Fn(NodeIndex, &[u8]) -> bool + Send + Sync>;
+
+/// Configuration of a single notifications protocol.
+#[derive(Clone)]
+pub struct NotificationsConfig {
+	/// Name of the protocol. Must be unique among the registered protocols.
+	pub name: String,
+	/// Handshake sent to peers when opening the substream.
+	pub handshake: Vec<u8>,
+	/// Maximum allowed size, in bytes, of a notification.
+	pub max_notification_size: usize,
+	/// Decides whether the substream is opened towards a peer given its handshake.
+	pub handshake_validator: HandshakeValidator,
+}
+
+/// Event happening on a notifications protocol.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NotificationEvent {
+	/// The substream with a peer has been opened.
+	Opened {
+		/// Peer on the other side of the substream.
+		who: NodeIndex,
+		/// Handshake sent by the peer.
+		handshake: Vec<u8>,
+	},
+	/// A peer has sent us a notification.
+	Notification {
+		/// Peer which sent the notification.
+		who: NodeIndex,
+		/// Notification payload.
+		message: Vec<u8>,
+	},
+	/// The substream with a peer has been closed.
+	Closed {
+		/// Peer on the other side of the substream.
+		who: NodeIndex,
+	},
+}
+
+/// Sends notifications to a single peer on a single protocol.
+///
+/// Notifications are queued and sent asynchronously by the network thread. Notifications
+/// sent after the substream has been closed are silently discarded.
+#[derive(Clone)]
+pub struct NotificationSink {
+	who: NodeIndex,
+	protocol: String,
+	sender: mpsc::UnboundedSender<(NodeIndex, String, Vec<u8>)>,
+}
+
+impl NotificationSink {
+	/// Peer this sink sends notifications to.
+	pub fn who(&self) -> NodeIndex {
+		self.who
+	}
+
+	/// Queue a notification for sending. Returns an error if the network has shut down.
+	pub fn send(&self, message: Vec<u8>) -> Result<(), ()> {
+		self.sender.unbounded_send((self.who, self.protocol.clone(), message)).map_err(|_| ())
+	}
+}
+
+struct ProtocolState {
+	config: NotificationsConfig,
+	/// Peers whose handshake we have accepted, with their handshake. They may send us
+	/// notifications.
+	accepted: HashMap<NodeIndex, Vec<u8>>,
+	/// Peers which have accepted our handshake.
+	confirmed: HashSet<NodeIndex>,
+	/// Peers with which both handshakes have been accepted. We may send them notifications.
+	open_peers: HashSet<NodeIndex>,
+	event_sinks: Vec<mpsc::UnboundedSender<NotificationEvent>>,
+}
+
+impl ProtocolState {
+	fn dispatch(&mut self, event: NotificationEvent) {
+		self.event_sinks.retain(|sink| sink.unbounded_send(event.clone()).is_ok());
+	}
+
+	/// Open the substream with `who` if both handshakes have been accepted.
+	fn maybe_open(&mut self, who: NodeIndex) {
+		if self.open_peers.contains(&who) || !self.confirmed.contains(&who) {
+			return;
+		}
+		let handshake = match self.accepted.get(&who) {
+			Some(handshake) => handshake.clone(),
+			None => return,
+		};
+
+		trace!(target: "sync", "Opened notifications substream {} with {}", self.config.name, who);
+		self.open_peers.insert(who);
+		self.dispatch(NotificationEvent::Opened { who, handshake });
+	}
+}
+
+/// State of all the registered notifications protocols.
+pub(crate) struct Notifications {
+	protocols: HashMap<String, ProtocolState>,
+	outgoing_tx: mpsc::UnboundedSender<(NodeIndex, String, Vec<u8>)>,
+	outgoing_rx: mpsc::UnboundedReceiver<(NodeIndex, String, Vec<u8>)>,
+}
+
+impl Notifications {
+	/// Create a new instance from the registered protocols.
+	pub fn new(configs: Vec<NotificationsConfig>) -> Self {
+		let mut protocols = HashMap::new();
+		for config in configs {
+			if protocols.contains_key(&config.name) {
+				warn!(target: "sync", "Notifications protocol {} registered twice", config.name);
+			}
+			protocols.insert(config.name.clone(), ProtocolState {
+				config,
+				accepted: HashMap::new(),
+				confirmed: HashSet::new(),
+				open_peers: HashSet::new(),
+				event_sinks: Vec::new(),
+			});
+		}
+
+		let (outgoing_tx, outgoing_rx) = mpsc::unbounded();
+		Notifications {
+			protocols,
+			outgoing_tx,
+			outgoing_rx,
+		}
+	}
+
+	/// Handshakes to send to a newly-connected peer.
+	pub fn handshakes(&self) -> Vec<NotificationsHandshake> {
+		self.protocols.values()
+			.map(|state| NotificationsHandshake {
+				protocol: state.config.name.clone(),
+				handshake: state.config.handshake.clone(),
+			})
+			.collect()
+	}
+
+	/// Called when a peer has sent us its handshake for a protocol. Returns the acceptance to
+	/// send back if the handshake is accepted.
+	pub fn on_handshake(&mut self, who: NodeIndex, handshake: NotificationsHandshake) -> Option<NotificationsAccepted> {
+		let state = match self.protocols.get_mut(&handshake.protocol) {
+			Some(state) => state,
+			None => {
+				trace!(target: "sync", "Ignoring handshake from {} for unknown protocol {}", who, handshake.protocol);
+				return None;
+			}
+		};
+		if state.accepted.contains_key(&who) {
+			trace!(target: "sync", "Ignoring duplicate handshake from {} for {}", who, handshake.protocol);
+			return None;
+		}
+		if !(state.config.handshake_validator)(who, &handshake.handshake) {
+			debug!(target: "sync", "Handshake from {} for {} was refused", who, handshake.protocol);
+			return None;
+		}
+
+		state.accepted.insert(who, handshake.handshake);
+		state.maybe_open(who);
+		Some(NotificationsAccepted { protocol: handshake.protocol })
+	}
+
+	/// Called when a peer has accepted the handshake we sent for a protocol.
+	pub fn on_accepted(&mut self, who: NodeIndex, accepted: NotificationsAccepted) {
+		let state = match self.protocols.get_mut(&accepted.protocol) {
+			Some(state) => state,
+			None => {
+				trace!(target: "sync", "Ignoring acceptance from {} for unknown protocol {}", who, accepted.protocol);
+				return;
+			}
+		};
+		state.confirmed.insert(who);
+		state.maybe_open(who);
+	}
+
+	/// Called when a peer has sent us a notification.
+	pub fn on_notification(&mut self, who: NodeIndex, notification: Notification) -> Result<(), Severity<'static>> {
+		let state = match self.protocols.get_mut(&notification.protocol) {
+			Some(state) => state,
+			None => return Err(Severity::Bad("Peer sent a notification on an unknown protocol")),
+		};
+		// the peer may send notifications as soon as it knows we have accepted its handshake.
+		if !state.accepted.contains_key(&who) {
+			return Err(Severity::Bad("Peer sent a notification on a closed substream"));
+		}
+		if notification.data.len() > state.config.max_notification_size {
+			return Err(Severity::Bad("Peer sent a notification exceeding the protocol size limit"));
+		}
+
+		state.dispatch(NotificationEvent::Notification { who, message: notification.data });
+		Ok(())
+	}
+
+	/// Called when a peer disconnects. Closes all its substreams.
+	pub fn peer_disconnected(&mut self, who: NodeIndex) {
+		for state in self.protocols.values_mut() {
+			state.accepted.remove(&who);
+			state.confirmed.remove(&who);
+			if state.open_peers.remove(&who) {
+				state.dispatch(NotificationEvent::Closed { who });
+			}
+		}
+	}
+
+	/// Returns a stream of events for the given protocol, or `None` if the protocol isn't
+	/// registered. The stream starts with an `Opened` event for every peer the substream
+	/// is already open with.
+	pub fn event_stream(&mut self, protocol: &str) -> Option<mpsc::UnboundedReceiver<NotificationEvent>> {
+		let state = self.protocols.get_mut(protocol)?;
+		let (tx, rx) = mpsc::unbounded();
+		for who in state.open_peers.iter() {
+			let handshake = state.accepted.get(who).cloned().unwrap_or_default();
+			let _ = tx.unbounded_send(NotificationEvent::Opened { who: *who, handshake });
+		}
+		state.event_sinks.push(tx);
+		Some(rx)
+	}
+
+	/// Returns a sink to send notifications to `who`, or `None` if the substream isn't open.
+	pub fn sink(&self, who: NodeIndex, protocol: &str) -> Option<NotificationSink> {
+		let state = self.protocols.get(protocol)?;
+		if !state.open_peers.contains(&who) {
+			return None;
+		}
+		Some(NotificationSink {
+			who,
+			protocol: protocol.to_owned(),
+			sender: self.outgoing_tx.clone(),
+		})
+	}
+
+	/// Collect the notifications queued through the sinks that can be sent out.
+	///
+	/// Must be called from within a task, which is notified when new notifications are queued.
+	pub fn poll_outgoing(&mut self) -> Vec<(NodeIndex, Notification)> {
+		let mut ready = Vec::new();
+		while let Ok(Async::Ready(Some((who, protocol, data)))) = self.outgoing_rx.poll() {
+			let is_open = match self.protocols.get(&protocol) {
+				Some(state) => state.open_peers.contains(&who),
+				None => false,
+			};
+			if !is_open {
+				trace!(target: "sync", "Discarding notification to {} on closed substream {}", who, protocol);
+				continue;
+			}
+			ready.push((who, Notification { protocol, data }));
+		}
+		ready
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use futures::{Future, future};
+
+	fn config(name: &str) -> NotificationsConfig {
+		NotificationsConfig {
+			name: name.into(),
+			handshake: vec![1],
+			max_notification_size: 8,
+			handshake_validator: Arc::new(|_: NodeIndex, handshake: &[u8]| handshake == &[1u8][..]),
+		}
+	}
+
+	fn handshake(protocol: &str, handshake: Vec<u8>) -> NotificationsHandshake {
+		NotificationsHandshake { protocol: protocol.into(), handshake }
+	}
+
+	fn accepted(protocol: &str) -> NotificationsAccepted {
+		NotificationsAccepted { protocol: protocol.into() }
+	}
+
+	#[test]
+	fn substream_opens_only_on_valid_handshake() {
+		let mut notifications = Notifications::new(vec![config("test")]);
+		let events = notifications.event_stream("test").unwrap();
+
+		assert_eq!(notifications.on_handshake(0, handshake("test", vec![2])), None);
+		notifications.on_accepted(0, accepted("test"));
+		assert!(notifications.sink(0, "test").is_none());
+		assert!(notifications.on_notification(0, Notification { protocol: "test".into(), data: vec![] }).is_err());
+
+		assert_eq!(notifications.on_handshake(1, handshake("test", vec![1])), Some(accepted("test")));
+		notifications.on_accepted(1, accepted("test"));
+		assert!(notifications.sink(1, "test").is_some());
+		assert!(notifications.on_notification(1, Notification { protocol: "test".into(), data: vec![5] }).is_ok());
+		assert!(notifications.on_notification(1, Notification { protocol: "test".into(), data: vec![0; 9] }).is_err());
+		notifications.peer_disconnected(1);
+		drop(notifications);
+
+		assert_eq!(events.collect().wait().unwrap(), vec![
+			NotificationEvent::Opened { who: 1, handshake: vec![1] },
+			NotificationEvent::Notification { who: 1, message: vec![5] },
+			NotificationEvent::Closed { who: 1 },
+		]);
+	}
+
+	#[test]
+	fn substream_opens_once_both_handshakes_are_accepted() {
+		let mut notifications = Notifications::new(vec![config("test")]);
+
+		// the remote hasn't accepted our handshake yet: we may receive but not send.
+		assert!(notifications.on_handshake(1, handshake("test", vec![1])).is_some());
+		assert!(notifications.sink(1, "test").is_none());
+		assert!(notifications.on_notification(1, Notification { protocol: "test".into(), data: vec![5] }).is_ok());
+
+		// the acceptance may also arrive before the remote's handshake.
+		notifications.on_accepted(2, accepted("test"));
+		assert!(notifications.sink(2, "test").is_none());
+		assert!(notifications.on_handshake(2, handshake("test", vec![1])).is_some());
+		assert!(notifications.sink(2, "test").is_some());
+
+		notifications.on_accepted(1, accepted("test"));
+		assert!(notifications.sink(1, "test").is_some());
+
+		// late subscribers learn about the open substreams with the handshake of the peer.
+		let events = notifications.event_stream("test").unwrap();
+		drop(notifications);
+		let mut opened = events.collect().wait().unwrap();
+		opened.sort_by_key(|event| match *event {
+			NotificationEvent::Opened { who, .. } => who,
+			_ => panic!("only opened events are expected"),
+		});
+		assert_eq!(opened, vec![
+			NotificationEvent::Opened { who: 1, handshake: vec![1] },
+			NotificationEvent::Opened { who: 2, handshake: vec![1] },
+		]);
+	}
+
+	#[test]
+	fn sinks_queue_notifications_for_open_substreams() {
+		let mut notifications = Notifications::new(vec![config("test")]);
+		notifications.on_handshake(1, handshake("test", vec![1]));
+		notifications.on_accepted(1, accepted("test"));
+		let sink = notifications.sink(1, "test").unwrap();
+		sink.send(vec![1, 2]).unwrap();
+		sink.send(vec![3]).unwrap();
+
+		let outgoing = future::lazy(|| Ok::<_, ()>(notifications.poll_outgoing())).wait().unwrap();
+		assert_eq!(outgoing, vec![
+			(1, Notification { protocol: "test".into(), data: vec![1, 2] }),
+			(1, Notification { protocol: "test".into(), data: vec![3] }),
+		]);
+
+		notifications.peer_disconnected(1);
+		sink.send(vec![4]).unwrap();
+		let outgoing = future::lazy(|| Ok::<_, ()>(notifications.poll_outgoing())).wait().unwrap();
+		assert!(outgoing.is_empty());
+	}
+}
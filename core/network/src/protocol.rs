@@ -15,15 +15,19 @@
 // along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
 
 use std::collections::{HashMap, HashSet, BTreeMap};
+use std::hash::Hash;
 use std::{mem, cmp};
 use std::sync::Arc;
 use std::time;
+use futures::sync::{mpsc, oneshot};
+use linked_hash_map::LinkedHashMap;
 use parking_lot::RwLock;
 use rustc_hex::ToHex;
 use runtime_primitives::traits::{Block as BlockT, Header as HeaderT, NumberFor, As, Zero};
 use runtime_primitives::generic::BlockId;
 use network_libp2p::{NodeIndex, Severity};
 use codec::{Encode, Decode};
+use primitives::ed25519;
 
 use message::{self, Message};
 use message::generic::Message as GenericMessage;
@@ -31,18 +35,21 @@ use consensus_gossip::ConsensusGossip;
 use specialization::NetworkSpecialization;
 use sync::{ChainSync, Status as SyncStatus, SyncState};
 use service::{TransactionPool, ExHashT};
-use import_queue::ImportQueue;
+use import_queue::{ImportQueue, ImportWork};
 use config::{ProtocolConfig, Roles};
 use chain::Client;
 use client::light::fetcher::ChangesProof;
 use on_demand::OnDemandService;
+use request_responses::{RequestResponses, RequestFailure};
+use notifications::{Notifications, NotificationEvent, NotificationSink};
+use dht::{Dht, DhtEvent};
 use io::SyncIo;
 use error;
 
 const REQUEST_TIMEOUT_SEC: u64 = 40;
 
 /// Current protocol version.
-pub (crate) const CURRENT_VERSION: u32 = 1;
+pub (crate) const CURRENT_VERSION: u32 = 2;
 
 // Maximum allowed entries in `BlockResponse`
 const MAX_BLOCK_DATA_RESPONSE: u32 = 128;
@@ -50,6 +57,9 @@ const MAX_BLOCK_DATA_RESPONSE: u32 = 128;
 /// for at least `LIGHT_MAXIMAL_BLOCKS_DIFFERENCE` blocks, we consider it unuseful
 /// and disconnect to free connection slot.
 const LIGHT_MAXIMAL_BLOCKS_DIFFERENCE: u64 = 8192;
+/// Number of the most recent transactions remembered per peer, so that they aren't sent to it
+/// again.
+const MAX_KNOWN_EXTRINSICS: usize = 4096;
 
 // Lock must always be taken in order declared here.
 pub struct Protocol<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> {
@@ -63,6 +73,9 @@ pub struct Protocol<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> {
 	// Connected peers pending Status message.
 	handshaking_peers: RwLock<HashMap<NodeIndex, time::Instant>>,
 	transaction_pool: Arc<TransactionPool<H, B>>,
+	request_responses: RwLock<RequestResponses>,
+	notifications: RwLock<Notifications>,
+	dht: RwLock<Dht>,
 }
 /// Syncing status and statistics
 #[derive(Clone)]
@@ -89,14 +102,61 @@ struct Peer<B: BlockT, H: ExHashT> {
 	block_request: Option<message::BlockRequest<B>>,
 	/// Request timestamp
 	request_timestamp: Option<time::Instant>,
-	/// Holds a set of transactions known to this peer.
-	known_extrinsics: HashSet<H>,
+	/// Holds a set of the most recent transactions known to this peer.
+	known_extrinsics: LruHashSet<H>,
 	/// Holds a set of blocks known to this peer.
 	known_blocks: HashSet<B::Hash>,
 	/// Request counter,
 	next_request_id: message::RequestId,
 }
 
+/// A set remembering only the `limit` most recently inserted items.
+struct LruHashSet<T: Hash + Eq> {
+	set: LinkedHashMap<T, ()>,
+	limit: usize,
+}
+
+impl<T: Hash + Eq> LruHashSet<T> {
+	fn new(limit: usize) -> Self {
+		LruHashSet {
+			set: LinkedHashMap::new(),
+			limit,
+		}
+	}
+
+	/// Insert an item, forgetting the oldest one if the set is full. Returns `true` if the item
+	/// wasn't in the set.
+	fn insert(&mut self, item: T) -> bool {
+		if self.set.insert(item, ()).is_some() {
+			return false;
+		}
+		if self.set.len() > self.limit {
+			self.set.pop_front();
+		}
+		true
+	}
+}
+
+/// Split items with the given encoded sizes into batches whose total size doesn't exceed
+/// `max_batch_size`. Items larger than that are put into batches of their own.
+fn batch_by_size<T>(items: Vec<(T, usize)>, max_batch_size: usize) -> Vec<Vec<T>> {
+	let mut batches = Vec::new();
+	let mut batch = Vec::new();
+	let mut batch_size = 0;
+	for (item, size) in items {
+		if !batch.is_empty() && batch_size + size > max_batch_size {
+			batches.push(mem::replace(&mut batch, Vec::new()));
+			batch_size = 0;
+		}
+		batch.push(item);
+		batch_size += size;
+	}
+	if !batch.is_empty() {
+		batches.push(batch);
+	}
+	batches
+}
+
 /// Info about a peer's known state.
 #[derive(Debug)]
 pub struct PeerInfo<B: BlockT> {
@@ -199,6 +259,8 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 	) -> error::Result<Self> {
 		let info = chain.info()?;
 		let sync = ChainSync::new(config.roles, &info, import_queue);
+		let request_responses = RequestResponses::new(config.request_response_protocols.clone());
+		let notifications = Notifications::new(config.notifications_protocols.clone());
 		let protocol = Protocol {
 			config: config,
 			context_data: ContextData {
@@ -212,6 +274,9 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 			consensus_gossip: RwLock::new(ConsensusGossip::new()),
 			handshaking_peers: RwLock::new(HashMap::new()),
 			transaction_pool: transaction_pool,
+			request_responses: RwLock::new(request_responses),
+			notifications: RwLock::new(notifications),
+			dht: RwLock::new(Dht::new()),
 		};
 		Ok(protocol)
 	}
@@ -285,6 +350,41 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 			GenericMessage::RemoteHeaderResponse(response) => self.on_remote_header_response(io, who, response),
 			GenericMessage::RemoteChangesRequest(request) => self.on_remote_changes_request(io, who, request),
 			GenericMessage::RemoteChangesResponse(response) => self.on_remote_changes_response(io, who, response),
+			GenericMessage::GenericRequest(request) => self.on_generic_request(io, who, request),
+			GenericMessage::GenericResponse(response) => self.on_generic_response(io, who, response),
+			GenericMessage::NotificationsHandshake(handshake) => {
+				let accepted = self.notifications.write().on_handshake(who, handshake);
+				if let Some(accepted) = accepted {
+					self.send_message(io, who, GenericMessage::NotificationsAccepted(accepted));
+				}
+			},
+			GenericMessage::NotificationsAccepted(accepted) => self.notifications.write().on_accepted(who, accepted),
+			GenericMessage::Notification(notification) => {
+				if let Err(severity) = self.notifications.write().on_notification(who, notification) {
+					io.report_peer(who, severity);
+				}
+			},
+			GenericMessage::DhtPutValue(put) => {
+				let outcome = self.dht.write().on_put_value(who, put);
+				match outcome {
+					Ok(response) => self.send_message(io, who, GenericMessage::DhtPutValueResponse(response)),
+					Err(severity) => io.report_peer(who, severity),
+				}
+			},
+			GenericMessage::DhtGetValue(get) => {
+				let response = self.dht.read().on_get_value(get);
+				self.send_message(io, who, GenericMessage::DhtValue(response));
+			},
+			GenericMessage::DhtValue(response) => {
+				if let Err(severity) = self.dht.write().on_value(who, response) {
+					io.report_peer(who, severity);
+				}
+			},
+			GenericMessage::DhtPutValueResponse(response) => {
+				if let Err(severity) = self.dht.write().on_put_value_response(who, response) {
+					io.report_peer(who, severity);
+				}
+			},
 			GenericMessage::Consensus(topic, msg) => {
 				self.consensus_gossip.write().on_incoming(&mut ProtocolContext::new(&self.context_data, io), who, topic, msg);	
 			},
@@ -331,6 +431,9 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 			sync.peer_disconnected(&mut context, peer);
 			spec.on_disconnect(&mut context, peer);
 			self.on_demand.as_ref().map(|s| s.on_disconnect(peer));
+			self.request_responses.write().peer_disconnected(peer);
+			self.notifications.write().peer_disconnected(peer);
+			self.dht.write().peer_disconnected(peer);
 		}
 	}
 
@@ -395,14 +498,18 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 
 		// import_queue.import_blocks also acquires sync.write();
 		// Break the cycle by doing these separately from the outside;
-		let new_blocks = {
+		let import = {
 			let mut sync = self.sync.write();
 			sync.on_block_data(&mut ProtocolContext::new(&self.context_data, io), peer, request, response)
 		};
 
-		if let Some((origin, new_blocks)) = new_blocks {
+		if let Some(import) = import {
 			let import_queue = self.sync.read().import_queue();
-			import_queue.import_blocks(origin, new_blocks);
+			match import {
+				ImportWork::Blocks(origin, new_blocks) => import_queue.import_blocks(origin, new_blocks),
+				ImportWork::Justification(who, hash, number, justification) =>
+					import_queue.import_justification(who, hash, number, justification),
+			}
 		}
 
 
@@ -410,8 +517,9 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 
 	/// Perform time based maintenance.
 	pub fn tick(&self, io: &mut SyncIo) {
-		self.consensus_gossip.write().collect_garbage(|_| true);
+		self.consensus_gossip.write().tick(&mut ProtocolContext::new(&self.context_data, io));
 		self.maintain_peers(io);
+		self.sync.write().tick(&mut ProtocolContext::new(&self.context_data, io));
 		self.on_demand.as_ref().map(|s| s.maintain_peers(io));
 	}
 
@@ -431,6 +539,9 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 			}
 		}
 
+		self.request_responses.write().maintain();
+		self.dht.write().maintain();
+
 		self.specialization.write().maintain_peers(&mut ProtocolContext::new(&self.context_data, io));
 		for p in aborting {
 			io.report_peer(p, Severity::Timeout);
@@ -486,7 +597,7 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 				best_number: status.best_number,
 				block_request: None,
 				request_timestamp: None,
-				known_extrinsics: HashSet::new(),
+				known_extrinsics: LruHashSet::new(MAX_KNOWN_EXTRINSICS),
 				known_blocks: HashSet::new(),
 				next_request_id: 0,
 			};
@@ -495,6 +606,11 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 			debug!(target: "sync", "Connected {} {}", who, io.peer_debug_info(who));
 		}
 
+		let handshakes = self.notifications.read().handshakes();
+		for handshake in handshakes {
+			self.send_message(io, who, GenericMessage::NotificationsHandshake(handshake));
+		}
+
 		let mut context = ProtocolContext::new(&self.context_data, io);
 		self.on_demand.as_ref().map(|s| s.on_connect(who, status.roles, status.best_number));
 		self.sync.write().new_peer(&mut context, who);
@@ -513,6 +629,10 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 		let mut peers = self.context_data.peers.write();
 		if let Some(ref mut peer) = peers.get_mut(&who) {
 			for t in extrinsics {
+				if t.encode().len() > self.config.max_transaction_size {
+					trace!(target: "sync", "Ignoring oversized extrinsic from {}", who);
+					continue;
+				}
 				if let Some(hash) = self.transaction_pool.import(&t) {
 					peer.known_extrinsics.insert(hash);
 				} else {
@@ -531,15 +651,28 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 			return;
 		}
 
-		let extrinsics = self.transaction_pool.transactions();
+		let max_transaction_size = self.config.max_transaction_size;
+		let extrinsics: Vec<_> = self.transaction_pool.transactions()
+			.into_iter()
+			.map(|(hash, extrinsic)| {
+				let size = extrinsic.encode().len();
+				(hash, extrinsic, size)
+			})
+			.filter(|&(ref hash, _, size)| if size > max_transaction_size {
+				trace!(target: "sync", "Not propagating oversized extrinsic {:?}", hash);
+				false
+			} else {
+				true
+			})
+			.collect();
 
 		let mut propagated_to = HashMap::new();
 		let mut peers = self.context_data.peers.write();
 		for (who, ref mut peer) in peers.iter_mut() {
 			let (hashes, to_send): (Vec<_>, Vec<_>) = extrinsics
 				.iter()
-				.filter(|&(ref hash, _)| peer.known_extrinsics.insert(hash.clone()))
-				.cloned()
+				.filter(|&(ref hash, _, _)| peer.known_extrinsics.insert(hash.clone()))
+				.map(|&(ref hash, ref extrinsic, size)| (hash.clone(), (extrinsic.clone(), size)))
 				.unzip();
 
 			if !to_send.is_empty() {
@@ -549,8 +682,10 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 						propagated_to.entry(hash).or_insert_with(Vec::new).push(id.clone());
 					}
 				}
-				trace!(target: "sync", "Sending {} transactions to {}", to_send.len(), who);
-				self.send_message(io, *who, GenericMessage::Transactions(to_send));
+				for batch in batch_by_size(to_send, self.config.max_transactions_message_size) {
+					trace!(target: "sync", "Sending {} transactions to {}", batch.len(), who);
+					self.send_message(io, *who, GenericMessage::Transactions(batch));
+				}
 			}
 		}
 		self.transaction_pool.on_broadcasted(propagated_to);
@@ -606,6 +741,16 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 		self.sync.write().on_block_announce(&mut ProtocolContext::new(&self.context_data, io), who, hash, &header);
 	}
 
+	/// Download the given block, which is required for finality, ahead of the best chain.
+	pub fn request_finality_target(&self, io: &mut SyncIo, hash: B::Hash, number: NumberFor<B>) {
+		self.sync.write().request_finality_target(&hash, number, &mut ProtocolContext::new(&self.context_data, io));
+	}
+
+	/// Drop all pending block requests and start syncing from all peers anew.
+	pub fn restart_sync(&self, io: &mut SyncIo) {
+		self.sync.write().restart(&mut ProtocolContext::new(&self.context_data, io));
+	}
+
 	pub fn on_block_imported(&self, io: &mut SyncIo, hash: B::Hash, header: &B::Header) {
 		self.sync.write().update_chain_info(&header);
 		self.specialization.write().on_block_imported(
@@ -725,6 +870,102 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 		self.on_demand.as_ref().map(|s| s.on_remote_changes_response(io, who, response));
 	}
 
+	fn on_generic_request(&self, io: &mut SyncIo, who: NodeIndex, request: message::GenericRequest) {
+		trace!(target: "sync", "Generic request {} from {} on protocol {}", request.id, who, request.protocol);
+		match self.request_responses.write().on_request(who, request) {
+			Ok(Some(response)) => self.send_message(io, who, GenericMessage::GenericResponse(response)),
+			Ok(None) => (),
+			Err(severity) => io.report_peer(who, severity),
+		}
+	}
+
+	fn on_generic_response(&self, io: &mut SyncIo, who: NodeIndex, response: message::GenericResponse) {
+		trace!(target: "sync", "Generic response {} from {} on protocol {}", response.id, who, response.protocol);
+		if let Err(severity) = self.request_responses.write().on_response(who, response) {
+			io.report_peer(who, severity);
+		}
+	}
+
+	/// Send a request on a registered request-response protocol.
+	pub fn send_request(
+		&self,
+		io: &mut SyncIo,
+		who: NodeIndex,
+		protocol: &str,
+		payload: Vec<u8>,
+		sender: oneshot::Sender<Result<Vec<u8>, RequestFailure>>,
+	) {
+		if !self.context_data.peers.read().contains_key(&who) {
+			let _ = sender.send(Err(RequestFailure::NotConnected));
+			return;
+		}
+
+		let request = self.request_responses.write().start_request(who, protocol, payload, sender);
+		if let Some(request) = request {
+			self.send_message(io, who, GenericMessage::GenericRequest(request));
+		}
+	}
+
+	/// Send the responses produced by the request-response handlers.
+	///
+	/// Must be called from within a task, which is notified when new responses are ready.
+	pub fn poll_request_responses(&self, io: &mut SyncIo) {
+		let responses = self.request_responses.write().poll_responses();
+		for (who, response) in responses {
+			self.send_message(io, who, GenericMessage::GenericResponse(response));
+		}
+	}
+
+	/// Returns a stream of events for a registered notifications protocol.
+	pub fn notifications_stream(&self, protocol: &str) -> Option<mpsc::UnboundedReceiver<NotificationEvent>> {
+		self.notifications.write().event_stream(protocol)
+	}
+
+	/// Returns a sink to send notifications to a peer, if the substream is open.
+	pub fn notification_sink(&self, who: NodeIndex, protocol: &str) -> Option<NotificationSink> {
+		self.notifications.read().sink(who, protocol)
+	}
+
+	/// Send the notifications queued through the notification sinks.
+	///
+	/// Must be called from within a task, which is notified when new notifications are queued.
+	pub fn poll_notifications(&self, io: &mut SyncIo) {
+		let notifications = self.notifications.write().poll_outgoing();
+		for (who, notification) in notifications {
+			self.send_message(io, who, GenericMessage::Notification(notification));
+		}
+	}
+
+	/// Connected peers along with the bytes of their identity, used to find DHT replicas.
+	fn dht_peers(&self, io: &SyncIo) -> Vec<(NodeIndex, Vec<u8>)> {
+		self.context_data.peers.read().keys()
+			.filter_map(|who| io.peer_id(*who).map(|id| (*who, id.as_bytes().to_vec())))
+			.collect()
+	}
+
+	/// Store a record signed with the given key in the DHT. The outcome is reported on the DHT
+	/// event stream.
+	pub fn put_value(&self, io: &mut SyncIo, key: Vec<u8>, value: Vec<u8>, signer: &ed25519::Pair) {
+		let peers = self.dht_peers(io);
+		let puts = self.dht.write().put_value(key, value, signer, peers);
+		for (who, put) in puts {
+			self.send_message(io, who, GenericMessage::DhtPutValue(put));
+		}
+	}
+
+	/// Look for the records of a key in the DHT. The outcome is reported on the DHT event stream.
+	pub fn get_value(&self, io: &mut SyncIo, key: Vec<u8>) {
+		let peers = self.dht_peers(io);
+		let gets = self.dht.write().get_value(key, peers);
+		for (who, get) in gets {
+			self.send_message(io, who, GenericMessage::DhtGetValue(get));
+		}
+	}
+
+	/// Returns a stream of DHT events.
+	pub fn dht_event_stream(&self) -> mpsc::UnboundedReceiver<DhtEvent> {
+		self.dht.write().event_stream()
+	}
 
 	/// Execute a closure with access to a network context and specialization.
 	pub fn with_spec<F, U>(&self, io: &mut SyncIo, f: F) -> U
@@ -848,3 +1089,28 @@ macro_rules! construct_simple_protocol {
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::{batch_by_size, LruHashSet};
+
+	#[test]
+	fn known_set_forgets_oldest_items() {
+		let mut known = LruHashSet::new(2);
+		assert!(known.insert(1));
+		assert!(known.insert(2));
+		assert!(!known.insert(2));
+		assert!(known.insert(3));
+
+		// 1 was inserted first, so it was forgotten.
+		assert!(known.insert(1));
+		assert!(!known.insert(3));
+	}
+
+	#[test]
+	fn transactions_are_batched_by_size() {
+		let items = vec![(1, 40), (2, 50), (3, 20), (4, 200), (5, 10)];
+		assert_eq!(batch_by_size(items, 100), vec![vec![1, 2], vec![3], vec![4], vec![5]]);
+		assert!(batch_by_size(Vec::<(u8, usize)>::new(), 100).is_empty());
+	}
+}
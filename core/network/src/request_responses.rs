@@ -0,0 +1,416 @@
+// Copyright 2018 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Generic request-response protocols.
+//!
+//! Node builders can register any number of named protocols through the
+//! `ProtocolConfig`. Requests and responses are carried as opaque payloads over the
+//! Substrate protocol, so chain-specific protocols don't need to touch the networking
+//! crate. Incoming requests are dispatched to the handler channel registered for the
+//! protocol, which answers through the `oneshot` sender attached to each request.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
+use futures::{Async, Future, sync::{mpsc, oneshot}};
+use network_libp2p::{NodeIndex, Severity};
+use message::{GenericRequest, GenericResponse, RequestId};
+
+/// Number of timed out requests whose late responses are still recognized, so that slow but
+/// honest peers aren't treated as misbehaving.
+const MAX_TIMED_OUT_REQUESTS: usize = 1024;
+
+/// Configuration of a single request-response protocol.
+#[derive(Clone)]
+pub struct RequestResponseConfig {
+	/// Name of the protocol. Must be unique among the registered protocols.
+	pub name: String,
+	/// Maximum allowed size, in bytes, of a request.
+	pub max_request_size: usize,
+	/// Maximum allowed size, in bytes, of a response.
+	pub max_response_size: usize,
+	/// Duration after which a request we have sent is considered failed.
+	pub request_timeout: Duration,
+	/// Channel on which incoming requests are dispatched. `None` if we only emit
+	/// requests on this protocol and never answer them.
+	pub inbound_queue: Option<mpsc::Sender<IncomingRequest>>,
+}
+
+/// A request received from a remote, waiting for an answer.
+pub struct IncomingRequest {
+	/// Node which sent the request.
+	pub who: NodeIndex,
+	/// Request payload.
+	pub payload: Vec<u8>,
+	/// Channel to send the response to. Dropping it refuses the request.
+	pub pending_response: oneshot::Sender<Vec<u8>>,
+}
+
+/// Reason why an outgoing request has failed.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum RequestFailure {
+	/// No protocol with this name has been registered.
+	UnknownProtocol,
+	/// The request payload is larger than the configured maximum.
+	TooLarge,
+	/// The remote is not connected or has disconnected before answering.
+	NotConnected,
+	/// The remote has not answered in time.
+	Timeout,
+	/// The remote has refused to answer the request.
+	Refused,
+}
+
+/// Future resolving to the response of an outgoing request.
+pub type ResponseFuture = oneshot::Receiver<Result<Vec<u8>, RequestFailure>>;
+
+struct PendingRequest {
+	protocol: String,
+	started_at: Instant,
+	sender: oneshot::Sender<Result<Vec<u8>, RequestFailure>>,
+}
+
+struct PendingResponse {
+	who: NodeIndex,
+	id: RequestId,
+	protocol: String,
+	receiver: oneshot::Receiver<Vec<u8>>,
+}
+
+/// State of all the registered request-response protocols.
+pub(crate) struct RequestResponses {
+	protocols: HashMap<String, RequestResponseConfig>,
+	next_request_id: RequestId,
+	pending_requests: HashMap<(NodeIndex, RequestId), PendingRequest>,
+	pending_responses: Vec<PendingResponse>,
+	/// Requests that have timed out, oldest first, whose responses may still arrive.
+	timed_out: VecDeque<(NodeIndex, RequestId)>,
+	timed_out_set: HashSet<(NodeIndex, RequestId)>,
+}
+
+impl RequestResponses {
+	/// Create a new instance from the registered protocols.
+	pub fn new(configs: Vec<RequestResponseConfig>) -> Self {
+		let mut protocols = HashMap::new();
+		for config in configs {
+			if protocols.contains_key(&config.name) {
+				warn!(target: "sync", "Request-response protocol {} registered twice", config.name);
+			}
+			protocols.insert(config.name.clone(), config);
+		}
+
+		RequestResponses {
+			protocols,
+			next_request_id: 0,
+			pending_requests: HashMap::new(),
+			pending_responses: Vec::new(),
+			timed_out: VecDeque::new(),
+			timed_out_set: HashSet::new(),
+		}
+	}
+
+	/// Start a new request. Returns the message to send to `who`, or `None` if the request
+	/// has failed right away, in which case `sender` has already been notified.
+	pub fn start_request(
+		&mut self,
+		who: NodeIndex,
+		protocol: &str,
+		payload: Vec<u8>,
+		sender: oneshot::Sender<Result<Vec<u8>, RequestFailure>>,
+	) -> Option<GenericRequest> {
+		let max_request_size = match self.protocols.get(protocol) {
+			Some(config) => config.max_request_size,
+			None => {
+				let _ = sender.send(Err(RequestFailure::UnknownProtocol));
+				return None;
+			}
+		};
+		if payload.len() > max_request_size {
+			let _ = sender.send(Err(RequestFailure::TooLarge));
+			return None;
+		}
+
+		let id = self.next_request_id;
+		self.next_request_id += 1;
+		self.pending_requests.insert((who, id), PendingRequest {
+			protocol: protocol.to_owned(),
+			started_at: Instant::now(),
+			sender,
+		});
+
+		Some(GenericRequest {
+			id,
+			protocol: protocol.to_owned(),
+			data: payload,
+		})
+	}
+
+	/// Called when a request is received from `who`. Returns a response to send back
+	/// immediately if the request can't be dispatched to a handler.
+	pub fn on_request(&mut self, who: NodeIndex, request: GenericRequest) -> Result<Option<GenericResponse>, Severity<'static>> {
+		let refused = GenericResponse {
+			id: request.id,
+			protocol: request.protocol.clone(),
+			data: None,
+		};
+
+		let config = match self.protocols.get_mut(&request.protocol) {
+			Some(config) => config,
+			None => {
+				trace!(target: "sync", "Request {} from {} on unknown protocol {}", request.id, who, request.protocol);
+				return Ok(Some(refused));
+			}
+		};
+		if request.data.len() > config.max_request_size {
+			return Err(Severity::Bad("Peer sent a request exceeding the protocol size limit"));
+		}
+
+		let inbound_queue = match config.inbound_queue {
+			Some(ref mut inbound_queue) => inbound_queue,
+			None => return Ok(Some(refused)),
+		};
+
+		let (tx, rx) = oneshot::channel();
+		let incoming = IncomingRequest {
+			who,
+			payload: request.data,
+			pending_response: tx,
+		};
+		if let Err(err) = inbound_queue.try_send(incoming) {
+			debug!(target: "sync", "Dropping request {} from {} on protocol {}: handler is {}",
+				request.id, who, request.protocol, if err.is_full() { "overloaded" } else { "gone" });
+			return Ok(Some(refused));
+		}
+
+		self.pending_responses.push(PendingResponse {
+			who,
+			id: request.id,
+			protocol: request.protocol,
+			receiver: rx,
+		});
+		Ok(None)
+	}
+
+	/// Called when a response is received from `who`.
+	pub fn on_response(&mut self, who: NodeIndex, response: GenericResponse) -> Result<(), Severity<'static>> {
+		let pending = match self.pending_requests.remove(&(who, response.id)) {
+			Some(pending) => pending,
+			None if self.timed_out_set.remove(&(who, response.id)) => {
+				trace!(target: "sync", "Ignoring late response to request {} from {}", response.id, who);
+				return Ok(());
+			},
+			None => return Err(Severity::Bad("Unexpected generic response received from peer")),
+		};
+		if pending.protocol != response.protocol {
+			let _ = pending.sender.send(Err(RequestFailure::Refused));
+			return Err(Severity::Bad("Peer answered a request on a different protocol"));
+		}
+
+		let max_response_size = self.protocols.get(&pending.protocol)
+			.map(|config| config.max_response_size)
+			.unwrap_or(0);
+		match response.data {
+			Some(ref data) if data.len() > max_response_size => {
+				let _ = pending.sender.send(Err(RequestFailure::Refused));
+				Err(Severity::Bad("Peer sent a response exceeding the protocol size limit"))
+			},
+			Some(data) => {
+				let _ = pending.sender.send(Ok(data));
+				Ok(())
+			},
+			None => {
+				let _ = pending.sender.send(Err(RequestFailure::Refused));
+				Ok(())
+			},
+		}
+	}
+
+	/// Called when a peer disconnects. Fails all the requests that were sent to it.
+	pub fn peer_disconnected(&mut self, who: NodeIndex) {
+		let keys: Vec<_> = self.pending_requests.keys().filter(|k| k.0 == who).cloned().collect();
+		for key in keys {
+			if let Some(pending) = self.pending_requests.remove(&key) {
+				let _ = pending.sender.send(Err(RequestFailure::NotConnected));
+			}
+		}
+		self.pending_responses.retain(|pending| pending.who != who);
+		self.timed_out.retain(|key| key.0 != who);
+		self.timed_out_set.retain(|key| key.0 != who);
+	}
+
+	/// Fail the requests that have timed out.
+	pub fn maintain(&mut self) {
+		let now = Instant::now();
+		let expired: Vec<_> = {
+			let protocols = &self.protocols;
+			self.pending_requests.iter()
+				.filter(|(_, pending)| protocols.get(&pending.protocol)
+					.map_or(true, |config| now.duration_since(pending.started_at) > config.request_timeout))
+				.map(|(key, _)| *key)
+				.collect()
+		};
+
+		for key in expired {
+			if let Some(pending) = self.pending_requests.remove(&key) {
+				trace!(target: "sync", "Request {} to {} on protocol {} has timed out", key.1, key.0, pending.protocol);
+				let _ = pending.sender.send(Err(RequestFailure::Timeout));
+				self.note_timed_out(key);
+			}
+		}
+	}
+
+	/// Remember a timed out request, forgetting the oldest one if there are too many.
+	fn note_timed_out(&mut self, key: (NodeIndex, RequestId)) {
+		if self.timed_out.len() >= MAX_TIMED_OUT_REQUESTS {
+			if let Some(oldest) = self.timed_out.pop_front() {
+				self.timed_out_set.remove(&oldest);
+			}
+		}
+		self.timed_out.push_back(key);
+		self.timed_out_set.insert(key);
+	}
+
+	/// Collect the responses that the handlers have produced so far.
+	///
+	/// Must be called from within a task, which is notified when new responses are ready.
+	pub fn poll_responses(&mut self) -> Vec<(NodeIndex, GenericResponse)> {
+		let mut ready = Vec::new();
+		let mut index = 0;
+		while index < self.pending_responses.len() {
+			let data = match self.pending_responses[index].receiver.poll() {
+				Ok(Async::NotReady) => {
+					index += 1;
+					continue;
+				},
+				Ok(Async::Ready(data)) => Some(data),
+				Err(_) => None,
+			};
+
+			let pending = self.pending_responses.swap_remove(index);
+			let max_response_size = self.protocols.get(&pending.protocol)
+				.map(|config| config.max_response_size)
+				.unwrap_or(0);
+			let data = match data {
+				Some(ref data) if data.len() > max_response_size => {
+					warn!(target: "sync", "Handler of protocol {} produced an oversized response", pending.protocol);
+					None
+				},
+				data => data,
+			};
+			ready.push((pending.who, GenericResponse {
+				id: pending.id,
+				protocol: pending.protocol,
+				data,
+			}));
+		}
+		ready
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use futures::{Stream, future};
+
+	fn config(name: &str, inbound_queue: Option<mpsc::Sender<IncomingRequest>>) -> RequestResponseConfig {
+		RequestResponseConfig {
+			name: name.into(),
+			max_request_size: 16,
+			max_response_size: 16,
+			request_timeout: Duration::from_secs(10),
+			inbound_queue,
+		}
+	}
+
+	#[test]
+	fn unknown_protocol_and_oversized_requests_fail_immediately() {
+		let mut protocols = RequestResponses::new(vec![config("test", None)]);
+
+		let (tx, rx) = oneshot::channel();
+		assert!(protocols.start_request(0, "unknown", vec![1], tx).is_none());
+		assert_eq!(rx.wait().unwrap(), Err(RequestFailure::UnknownProtocol));
+
+		let (tx, rx) = oneshot::channel();
+		assert!(protocols.start_request(0, "test", vec![0; 17], tx).is_none());
+		assert_eq!(rx.wait().unwrap(), Err(RequestFailure::TooLarge));
+	}
+
+	#[test]
+	fn request_is_answered_by_handler() {
+		let (inbound_tx, inbound_rx) = mpsc::channel(8);
+		let mut client = RequestResponses::new(vec![config("test", None)]);
+		let mut server = RequestResponses::new(vec![config("test", Some(inbound_tx))]);
+
+		let (tx, rx) = oneshot::channel();
+		let request = client.start_request(1, "test", vec![1, 2, 3], tx).unwrap();
+		assert_eq!(server.on_request(0, request), Ok(None));
+
+		let incoming = inbound_rx.wait().next().unwrap().unwrap();
+		assert_eq!(incoming.who, 0);
+		assert_eq!(incoming.payload, vec![1, 2, 3]);
+		incoming.pending_response.send(vec![4, 5, 6]).unwrap();
+
+		let responses = future::lazy(|| Ok::<_, ()>(server.poll_responses())).wait().unwrap();
+		assert_eq!(responses.len(), 1);
+		let (who, response) = responses.into_iter().next().unwrap();
+		assert_eq!(who, 0);
+		assert_eq!(client.on_response(1, response), Ok(()));
+		assert_eq!(rx.wait().unwrap(), Ok(vec![4, 5, 6]));
+	}
+
+	#[test]
+	fn pending_requests_fail_on_disconnect() {
+		let mut protocols = RequestResponses::new(vec![config("test", None)]);
+
+		let (tx, rx) = oneshot::channel();
+		let request = protocols.start_request(3, "test", vec![1], tx).unwrap();
+		protocols.peer_disconnected(3);
+		assert_eq!(rx.wait().unwrap(), Err(RequestFailure::NotConnected));
+
+		let response = GenericResponse { id: request.id, protocol: "test".into(), data: Some(vec![]) };
+		assert!(protocols.on_response(3, response).is_err());
+	}
+
+	#[test]
+	fn late_responses_to_timed_out_requests_are_ignored() {
+		let mut protocols = RequestResponses::new(vec![RequestResponseConfig {
+			request_timeout: Duration::from_millis(0),
+			..config("test", None)
+		}]);
+
+		let (tx, rx) = oneshot::channel();
+		let request = protocols.start_request(3, "test", vec![1], tx).unwrap();
+		::std::thread::sleep(Duration::from_millis(1));
+		protocols.maintain();
+		assert_eq!(rx.wait().unwrap(), Err(RequestFailure::Timeout));
+
+		// the late response isn't held against the peer, but only once.
+		let response = GenericResponse { id: request.id, protocol: "test".into(), data: Some(vec![]) };
+		assert_eq!(protocols.on_response(3, response.clone()), Ok(()));
+		assert!(protocols.on_response(3, response).is_err());
+	}
+
+	#[test]
+	fn requests_without_handler_are_refused() {
+		let mut client = RequestResponses::new(vec![config("test", None)]);
+		let mut server = RequestResponses::new(vec![config("test", None)]);
+
+		let (tx, rx) = oneshot::channel();
+		let request = client.start_request(1, "test", vec![1], tx).unwrap();
+		let response = server.on_request(0, request).unwrap().unwrap();
+		assert_eq!(client.on_response(1, response), Ok(()));
+		assert_eq!(rx.wait().unwrap(), Err(RequestFailure::Refused));
+	}
+}
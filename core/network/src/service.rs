@@ -18,19 +18,23 @@ use std::collections::HashMap;
 use std::sync::Arc;
 use std::{io, thread};
 use std::time::Duration;
-use futures::{self, Future, Stream, stream, sync::oneshot};
+use futures::{self, Async, Future, Stream, future, stream, sync::{mpsc, oneshot}};
 use parking_lot::{Mutex, RwLock};
-use network_libp2p::{ProtocolId, PeerId, NetworkConfiguration, ErrorKind};
+use network_libp2p::{ProtocolId, PeerId, NodeIndex, NetworkConfiguration, ErrorKind};
 use network_libp2p::{start_service, Service as NetworkService, ServiceEvent as NetworkServiceEvent};
 use network_libp2p::{RegisteredProtocol, parse_str_addr, Protocol as Libp2pProtocol};
+use primitives::ed25519;
 use io::NetSyncIo;
 use consensus_gossip::ConsensusGossip;
 use protocol::{self, Protocol, ProtocolContext, Context, ProtocolStatus};
+use request_responses::ResponseFuture;
+use notifications::{NotificationEvent, NotificationSink};
+use dht::DhtEvent;
 use config::Params;
 use error::Error;
 use specialization::NetworkSpecialization;
 use import_queue::ImportQueue;
-use runtime_primitives::traits::{Block as BlockT};
+use runtime_primitives::traits::{Block as BlockT, NumberFor};
 use tokio::{runtime::Runtime, timer::Interval};
 
 /// Type that represents fetch completion future.
@@ -121,6 +125,17 @@ impl<B: BlockT + 'static, S: NetworkSpecialization<B>, H: ExHashT> Service<B, S,
 		self.handler.on_block_imported(&mut NetSyncIo::new(&self.network, self.protocol_id), hash, header)
 	}
 
+	/// Download the given block, which is required for finality, along with its missing
+	/// ancestors ahead of the best chain.
+	pub fn request_finality_target(&self, hash: B::Hash, number: NumberFor<B>) {
+		self.handler.request_finality_target(&mut NetSyncIo::new(&self.network, self.protocol_id), hash, number)
+	}
+
+	/// Drop all pending block requests and start syncing from all peers anew.
+	pub fn restart_sync(&self) {
+		self.handler.restart_sync(&mut NetSyncIo::new(&self.network, self.protocol_id))
+	}
+
 	/// Called when new transactons are imported by the client.
 	pub fn trigger_repropagate(&self) {
 		self.handler.propagate_extrinsics(&mut NetSyncIo::new(&self.network, self.protocol_id));
@@ -140,6 +155,56 @@ impl<B: BlockT + 'static, S: NetworkSpecialization<B>, H: ExHashT> Service<B, S,
 		self.handler.with_spec(&mut NetSyncIo::new(&self.network, self.protocol_id), f)
 	}
 
+	/// Send a request to a peer on a registered request-response protocol.
+	///
+	/// The returned future resolves to the response, or to the reason why the request has
+	/// failed.
+	pub fn request(&self, who: NodeIndex, protocol: &str, payload: Vec<u8>) -> ResponseFuture {
+		let (tx, rx) = oneshot::channel();
+		self.handler.send_request(
+			&mut NetSyncIo::new(&self.network, self.protocol_id),
+			who,
+			protocol,
+			payload,
+			tx,
+		);
+		rx
+	}
+
+	/// Returns a stream of events happening on a registered notifications protocol, or `None`
+	/// if no protocol with this name has been registered.
+	pub fn notifications_stream(&self, protocol: &str) -> Option<mpsc::UnboundedReceiver<NotificationEvent>> {
+		self.handler.notifications_stream(protocol)
+	}
+
+	/// Returns a sink to send notifications to a peer on a registered notifications protocol,
+	/// or `None` if the substream with this peer isn't open.
+	pub fn notification_sink(&self, who: NodeIndex, protocol: &str) -> Option<NotificationSink> {
+		self.handler.notification_sink(who, protocol)
+	}
+
+	/// Start storing a record signed with `signer` in the DHT. The record replaces the one
+	/// previously put under the same key by the same signer, if any.
+	///
+	/// A `DhtEvent::ValuePut` is later produced on the DHT event streams if at least one peer
+	/// acknowledged the store, and a `DhtEvent::ValuePutFailed` otherwise.
+	pub fn put_value(&self, key: Vec<u8>, value: Vec<u8>, signer: &ed25519::Pair) {
+		self.handler.put_value(&mut NetSyncIo::new(&self.network, self.protocol_id), key, value, signer)
+	}
+
+	/// Start looking for the records of a key in the DHT, one per signer.
+	///
+	/// A `DhtEvent::ValueFound` or `DhtEvent::ValueNotFound` is later produced on the DHT
+	/// event streams.
+	pub fn get_value(&self, key: Vec<u8>) {
+		self.handler.get_value(&mut NetSyncIo::new(&self.network, self.protocol_id), key)
+	}
+
+	/// Returns a stream of the events produced by the DHT.
+	pub fn dht_event_stream(&self) -> mpsc::UnboundedReceiver<DhtEvent> {
+		self.handler.dht_event_stream()
+	}
+
 	/// access the underlying consensus gossip handler
 	pub fn consensus_gossip<'a>(&'a self) -> &'a RwLock<ConsensusGossip<B>> {
 		self.handler.consensus_gossip()
@@ -189,6 +254,12 @@ pub trait ManageNetwork: Send + Sync {
 	fn add_reserved_peer(&self, peer: String) -> Result<(), String>;
 	/// Returns a user-friendly identifier of our node.
 	fn node_id(&self) -> Option<String>;
+	/// Set the members of a named priority group, given as multiaddresses ending with
+	/// `/p2p/<peer id>`. Up to `slots` connections to the members are exempt from the
+	/// connection limits.
+	fn set_priority_group(&self, group_id: String, peers: Vec<String>, slots: usize) -> Result<(), String>;
+	/// Remove a named priority group.
+	fn remove_priority_group(&self, group_id: &str);
 }
 
 impl<B: BlockT + 'static, S: NetworkSpecialization<B>, H: ExHashT> ManageNetwork for Service<B, S, H> {
@@ -222,6 +293,18 @@ impl<B: BlockT + 'static, S: NetworkSpecialization<B>, H: ExHashT> ManageNetwork
 		Ok(())
 	}
 
+	fn set_priority_group(&self, group_id: String, peers: Vec<String>, slots: usize) -> Result<(), String> {
+		let peers = peers.iter()
+			.map(|peer| parse_str_addr(peer).map_err(|e| format!("{:?}", e)))
+			.collect::<Result<Vec<_>, _>>()?;
+		self.network.lock().set_priority_group(group_id, peers, slots);
+		Ok(())
+	}
+
+	fn remove_priority_group(&self, group_id: &str) {
+		self.network.lock().remove_priority_group(group_id);
+	}
+
 	fn node_id(&self) -> Option<String> {
 		let network = self.network.lock();
 		let ret = network
@@ -319,6 +402,26 @@ fn run_thread<B: BlockT + 'static, S: NetworkSpecialization<B>, H: ExHashT>(
 			Ok(())
 		});
 
+	// Sends the answers produced by the request-response handlers.
+	let responses = future::poll_fn({
+		let protocol = protocol.clone();
+		let network_service = network_service.clone();
+		move || -> Result<Async<()>, io::Error> {
+			protocol.poll_request_responses(&mut NetSyncIo::new(&network_service, protocol_id));
+			Ok(Async::NotReady)
+		}
+	});
+
+	// Sends the notifications queued through the notification sinks.
+	let notifications = future::poll_fn({
+		let protocol = protocol.clone();
+		let network_service = network_service.clone();
+		move || -> Result<Async<()>, io::Error> {
+			protocol.poll_notifications(&mut NetSyncIo::new(&network_service, protocol_id));
+			Ok(Async::NotReady)
+		}
+	});
+
 	// The network service produces events about what happens on the network. Let's process them.
 	let network_service2 = network_service.clone();
 	let network = stream::poll_fn(move || network_service2.lock().poll()).for_each(move |event| {
@@ -356,7 +459,9 @@ fn run_thread<B: BlockT + 'static, S: NetworkSpecialization<B>, H: ExHashT>(
 	let futures: Vec<Box<Future<Item = (), Error = io::Error> + Send>> = vec![
 		Box::new(tick) as Box<_>,
 		Box::new(propagate) as Box<_>,
-		Box::new(network) as Box<_>
+		Box::new(network) as Box<_>,
+		Box::new(responses) as Box<_>,
+		Box::new(notifications) as Box<_>,
 	];
 
 	futures::select_all(futures)
@@ -14,8 +14,9 @@
 // You should have received a copy of the GNU General Public License
 // along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use protocol::Context;
 use network_libp2p::{Severity, NodeIndex};
 use client::{BlockStatus, ClientInfo};
@@ -26,12 +27,14 @@ use runtime_primitives::traits::{Block as BlockT, Header as HeaderT, As, NumberF
 use runtime_primitives::generic::BlockId;
 use message::{self, generic::Message as GenericMessage};
 use config::Roles;
-use import_queue::ImportQueue;
+use import_queue::{ImportQueue, ImportWork};
 
 // Maximum blocks to request in a single packet.
 const MAX_BLOCKS_TO_REQUEST: usize = 128;
-// Maximum blocks to store in the import queue.
-const MAX_IMPORTING_BLOCKS: usize = 2048;
+// Maximum blocks to download for a finality target whose ancestry doesn't connect to our chain.
+const MAX_FINALITY_TARGET_ANCESTRY: usize = 4 * MAX_BLOCKS_TO_REQUEST;
+// Time to wait before asking again the peers that all failed to provide a justification.
+const JUSTIFICATION_RETRY_WAIT: Duration = Duration::from_secs(10);
 
 struct PeerSync<B: BlockT> {
 	pub common_hash: B::Hash,
@@ -47,6 +50,8 @@ enum PeerSyncState<B: BlockT> {
 	Available,
 	DownloadingNew(NumberFor<B>),
 	DownloadingStale(B::Hash),
+	DownloadingJustification(B::Hash),
+	DownloadingFinalityTarget(B::Hash),
 }
 
 /// Relay chain sync strategy.
@@ -58,6 +63,22 @@ pub struct ChainSync<B: BlockT> {
 	best_queued_hash: B::Hash,
 	required_block_attributes: message::BlockAttributes,
 	import_queue: Arc<ImportQueue<B>>,
+	/// Blocks whose justification is requested, along with their number, the peers that
+	/// failed to provide it and the time at which all known peers had failed.
+	justification_requests: HashMap<B::Hash, (NumberFor<B>, HashSet<NodeIndex>, Option<Instant>)>,
+	/// Blocks whose justification is being imported by the import queue.
+	importing_justifications: HashSet<B::Hash>,
+	/// Blocks required for finality. They are downloaded together with their missing ancestors
+	/// ahead of the best chain.
+	finality_targets: HashMap<B::Hash, FinalityTarget<B>>,
+}
+
+/// A block required for finality.
+struct FinalityTarget<B: BlockT> {
+	number: NumberFor<B>,
+	/// The target and its ancestors downloaded so far, from the target down, while their
+	/// ancestry doesn't connect to our chain.
+	downloaded: Vec<message::BlockData<B>>,
 }
 
 /// Reported sync state.
@@ -76,6 +97,8 @@ pub struct Status<B: BlockT> {
 	pub state: SyncState,
 	/// Target sync block number.
 	pub best_seen_block: Option<NumberFor<B>>,
+	/// Number of blocks waiting in the import queue.
+	pub queued_blocks: usize,
 }
 
 impl<B: BlockT> Status<B> {
@@ -105,6 +128,9 @@ impl<B: BlockT> ChainSync<B> {
 			best_queued_number: info.best_queued_number.unwrap_or(info.chain.best_number),
 			required_block_attributes,
 			import_queue,
+			justification_requests: HashMap::new(),
+			importing_justifications: HashSet::new(),
+			finality_targets: HashMap::new(),
 		}
 	}
 
@@ -127,6 +153,7 @@ impl<B: BlockT> ChainSync<B> {
 		Status {
 			state: state,
 			best_seen_block: best_seen,
+			queued_blocks: self.import_queue.status().importing_count,
 		}
 	}
 
@@ -189,7 +216,16 @@ impl<B: BlockT> ChainSync<B> {
 		who: NodeIndex,
 		_request: message::BlockRequest<B>,
 		response: message::BlockResponse<B>
-	) -> Option<(BlockOrigin, Vec<blocks::BlockData<B>>)> {
+	) -> Option<ImportWork<B>> {
+		let justification_hash = match self.peers.get(&who).map(|p| &p.state) {
+			Some(&PeerSyncState::DownloadingJustification(hash)) => Some(hash),
+			_ => None,
+		};
+
+		if let Some(hash) = justification_hash {
+			return self.on_justification_data(protocol, who, hash, response);
+		}
+
 		let new_blocks = if let Some(ref mut peer) = self.peers.get_mut(&who) {
 			match peer.state {
 				PeerSyncState::DownloadingNew(start_block) => {
@@ -206,6 +242,52 @@ impl<B: BlockT> ChainSync<B> {
 						block: b
 					}).collect()
 				},
+				PeerSyncState::DownloadingFinalityTarget(hash) => {
+					peer.state = PeerSyncState::Available;
+					match self.finality_targets.remove(&hash) {
+						Some(mut target) => {
+							// the blocks are sent from the requested one down to its ancestors.
+							let expected = target.downloaded.last()
+								.and_then(|b| b.header.as_ref())
+								.map_or(hash, |h| *h.parent_hash());
+							if response.blocks.first().map_or(true, |b| b.hash != expected) {
+								trace!(target: "sync", "Finality target {} is not provided by {}", hash, who);
+								Vec::new()
+							} else {
+								let import_queue = &self.import_queue;
+								let mut connected = false;
+								for block in response.blocks {
+									connected = block.header.as_ref().map_or(false, |h| {
+										block_status(&*protocol.client(), &**import_queue, *h.parent_hash())
+											.ok()
+											.map_or(false, |s| s != BlockStatus::Unknown)
+									});
+									target.downloaded.push(block);
+									if connected {
+										break;
+									}
+								}
+
+								if connected {
+									trace!(target: "sync", "Downloaded finality target {} with {} blocks from {}", hash, target.downloaded.len(), who);
+									target.downloaded.into_iter().rev().map(|b| blocks::BlockData {
+										origin: Some(who),
+										block: b
+									}).collect()
+								} else if target.downloaded.len() >= MAX_FINALITY_TARGET_ANCESTRY {
+									trace!(target: "sync", "Finality target {} does not connect to our chain within {} blocks", hash, target.downloaded.len());
+									Vec::new()
+								} else {
+									// the ancestry of the target is searched further down.
+									trace!(target: "sync", "Finality target {} from {} does not connect to our chain yet", hash, who);
+									self.finality_targets.insert(hash, target);
+									Vec::new()
+								}
+							}
+						},
+						None => Vec::new(),
+					}
+				},
 				PeerSyncState::AncestorSearch(n) => {
 					match response.blocks.get(0) {
 						Some(ref block) => {
@@ -245,7 +327,7 @@ impl<B: BlockT> ChainSync<B> {
 						}
 					}
 				},
-				PeerSyncState::Available => Vec::new(),
+				PeerSyncState::Available | PeerSyncState::DownloadingJustification(_) => Vec::new(),
 			}
 		} else {
 			vec![]
@@ -263,14 +345,228 @@ impl<B: BlockT> ChainSync<B> {
 			}
 		}
 		self.maintain_sync(protocol);
-		Some((origin, new_blocks))
+		Some(ImportWork::Blocks(origin, new_blocks))
+	}
+
+	fn on_justification_data(
+		&mut self,
+		protocol: &mut Context<B>,
+		who: NodeIndex,
+		hash: B::Hash,
+		response: message::BlockResponse<B>,
+	) -> Option<ImportWork<B>> {
+		if let Some(ref mut peer) = self.peers.get_mut(&who) {
+			peer.state = PeerSyncState::Available;
+		}
+
+		let justification = response.blocks.into_iter()
+			.find(|b| b.hash == hash)
+			.and_then(|b| b.justification)
+			.filter(|j| !j.is_empty());
+
+		let number = match self.justification_requests.get(&hash) {
+			Some(&(number, _, _)) => number,
+			// the request was already served by some other peer.
+			None => return None,
+		};
+
+		// the justification is imported by the import queue, which reports the result back.
+		let import = match justification {
+			Some(justification) => {
+				trace!(target: "sync", "Scheduling import of justification for block {} ({}) from {}", hash, number, who);
+				self.importing_justifications.insert(hash);
+				Some(ImportWork::Justification(who, hash, number, justification))
+			},
+			None => {
+				trace!(target: "sync", "Peer {} did not provide justification for block {}", who, hash);
+				if let Some(&mut (_, ref mut tried, _)) = self.justification_requests.get_mut(&hash) {
+					tried.insert(who);
+				}
+				None
+			},
+		};
+
+		self.dispatch_justification_requests(protocol);
+		import
+	}
+
+	/// Handle the result of the import of a justification provided by the given peer.
+	pub(crate) fn justification_import_result(
+		&mut self,
+		who: NodeIndex,
+		hash: &B::Hash,
+		number: NumberFor<B>,
+		success: bool,
+		protocol: &mut Context<B>,
+	) {
+		self.importing_justifications.remove(hash);
+		if success {
+			trace!(target: "sync", "Imported justification for block {} ({}) from {}", hash, number, who);
+			self.justification_requests.remove(hash);
+		} else {
+			protocol.report_peer(who, Severity::Useless("Sent an invalid justification"));
+			if let Some(&mut (_, ref mut tried, _)) = self.justification_requests.get_mut(hash) {
+				tried.insert(who);
+			}
+		}
+
+		self.dispatch_justification_requests(protocol);
 	}
 
 	pub fn maintain_sync(&mut self, protocol: &mut Context<B>) {
+		// finality targets are downloaded by idle peers before anything else.
+		self.dispatch_finality_target_requests(protocol);
 		let peers: Vec<NodeIndex> = self.peers.keys().map(|p| *p).collect();
 		for peer in peers {
 			self.download_new(protocol, peer);
 		}
+		self.dispatch_justification_requests(protocol);
+	}
+
+	/// Perform time based maintenance.
+	pub(crate) fn tick(&mut self, protocol: &mut Context<B>) {
+		self.dispatch_justification_requests(protocol);
+	}
+
+	/// Request the justification of the given block from the network. This is used for blocks
+	/// that were imported without the justification they require to be finalized.
+	pub(crate) fn request_justification(&mut self, hash: &B::Hash, number: NumberFor<B>, protocol: &mut Context<B>) {
+		self.justification_requests.entry(*hash).or_insert_with(|| (number, HashSet::new(), None));
+		self.dispatch_justification_requests(protocol);
+	}
+
+	/// Send pending justification requests to idle peers that could know the justification.
+	fn dispatch_justification_requests(&mut self, protocol: &mut Context<B>) {
+		// requests for blocks that were finalized by other means are no longer needed.
+		if let Ok(info) = protocol.client().info() {
+			let finalized_number = info.chain.finalized_number;
+			self.justification_requests.retain(|_, &mut (number, _, _)| number > finalized_number);
+		}
+
+		let now = Instant::now();
+		for (hash, &mut (number, ref mut tried, ref mut exhausted_at)) in self.justification_requests.iter_mut() {
+			let already_requested = self.importing_justifications.contains(hash) || self.peers.values()
+				.any(|p| p.state == PeerSyncState::DownloadingJustification(*hash));
+			if already_requested {
+				continue;
+			}
+
+			// peers that failed to provide the justification are asked again after a while,
+			// since they may have learned it in the meantime.
+			if let Some(at) = *exhausted_at {
+				if now.duration_since(at) < JUSTIFICATION_RETRY_WAIT {
+					continue;
+				}
+				trace!(target: "sync", "Retrying justification request for block {} ({})", hash, number);
+				tried.clear();
+				*exhausted_at = None;
+			}
+
+			let exhausted = !tried.is_empty() && self.peers.iter()
+				.all(|(who, peer)| peer.best_number < number || tried.contains(who));
+			if exhausted {
+				*exhausted_at = Some(now);
+				continue;
+			}
+
+			let peer = self.peers.iter_mut()
+				.find(|&(who, ref peer)| peer.state == PeerSyncState::Available
+					&& peer.best_number >= number
+					&& !tried.contains(who));
+
+			if let Some((who, peer)) = peer {
+				trace!(target: "sync", "Requesting justification for block {} ({}) from {}", hash, number, who);
+				let request = message::generic::BlockRequest {
+					id: 0,
+					fields: message::BlockAttributes::JUSTIFICATION,
+					from: message::FromBlock::Hash(*hash),
+					to: None,
+					direction: message::Direction::Ascending,
+					max: Some(1),
+				};
+				peer.state = PeerSyncState::DownloadingJustification(*hash);
+				protocol.send_message(*who, GenericMessage::BlockRequest(request));
+			}
+		}
+	}
+
+	/// Download the given block, which is required for finality, along with its missing
+	/// ancestors ahead of the ordinary download of the best chain.
+	pub(crate) fn request_finality_target(&mut self, hash: &B::Hash, number: NumberFor<B>, protocol: &mut Context<B>) {
+		if self.is_known_or_already_downloading(protocol, hash) {
+			return;
+		}
+		trace!(target: "sync", "Block {} ({}) is required for finality", hash, number);
+		self.finality_targets.entry(*hash).or_insert_with(|| FinalityTarget { number, downloaded: Vec::new() });
+		self.dispatch_finality_target_requests(protocol);
+	}
+
+	/// Send pending finality target requests to idle peers that could know the target.
+	///
+	/// Targets too far ahead of the best queued block are left to the ordinary download until
+	/// the target and its missing ancestors fit into one request. Targets that turn out not to
+	/// connect to our chain, e.g. because they are on a fork, have their ancestry downloaded
+	/// from the lowest block downloaded so far until it does.
+	fn dispatch_finality_target_requests(&mut self, protocol: &mut Context<B>) {
+		if self.finality_targets.is_empty() {
+			return;
+		}
+
+		let finalized_number = protocol.client().info().map(|info| info.chain.finalized_number).ok();
+		let targets: Vec<_> = self.finality_targets.iter()
+			.map(|(hash, target)| {
+				let lowest_parent = target.downloaded.last()
+					.and_then(|b| b.header.as_ref())
+					.map(|h| *h.parent_hash());
+				(*hash, target.number, lowest_parent)
+			})
+			.collect();
+		for (hash, number, lowest_parent) in targets {
+			let obsolete = finalized_number.map_or(false, |finalized| number <= finalized)
+				|| block_status(&*protocol.client(), &*self.import_queue, hash).ok().map_or(false, |s| s != BlockStatus::Unknown);
+			if obsolete {
+				self.finality_targets.remove(&hash);
+				continue;
+			}
+
+			let already_requested = self.peers.values()
+				.any(|p| p.state == PeerSyncState::DownloadingFinalityTarget(hash));
+			if already_requested {
+				continue;
+			}
+
+			let (from, max) = match lowest_parent {
+				Some(parent) => (parent, MAX_BLOCKS_TO_REQUEST),
+				None => {
+					let missing = if number > self.best_queued_number {
+						(number - self.best_queued_number).as_() as usize
+					} else {
+						1
+					};
+					if missing > MAX_BLOCKS_TO_REQUEST {
+						continue;
+					}
+					(hash, missing)
+				},
+			};
+
+			let peer = self.peers.iter_mut()
+				.find(|&(_, ref peer)| peer.state == PeerSyncState::Available && peer.best_number >= number);
+
+			if let Some((who, peer)) = peer {
+				trace!(target: "sync", "Requesting {} blocks from {} down for finality target {} ({}) from {}", max, from, hash, number, who);
+				let request = message::generic::BlockRequest {
+					id: 0,
+					fields: self.required_block_attributes.clone(),
+					from: message::FromBlock::Hash(from),
+					to: None,
+					direction: message::Direction::Descending,
+					max: Some(max as u32),
+				};
+				peer.state = PeerSyncState::DownloadingFinalityTarget(hash);
+				protocol.send_message(*who, GenericMessage::BlockRequest(request));
+			}
+		}
 	}
 
 	pub fn block_imported(&mut self, hash: &B::Hash, number: NumberFor<B>) {
@@ -326,7 +622,8 @@ impl<B: BlockT> ChainSync<B> {
 	}
 
 	fn is_known_or_already_downloading(&self, protocol: &mut Context<B>, hash: &B::Hash) -> bool {
-		self.peers.iter().any(|(_, p)| p.state == PeerSyncState::DownloadingStale(*hash))
+		self.peers.iter().any(|(_, p)| p.state == PeerSyncState::DownloadingStale(*hash)
+				|| p.state == PeerSyncState::DownloadingFinalityTarget(*hash))
 			|| block_status(&*protocol.client(), &*self.import_queue, *hash).ok().map_or(false, |s| s != BlockStatus::Unknown)
 	}
 
@@ -359,6 +656,7 @@ impl<B: BlockT> ChainSync<B> {
 	pub(crate) fn clear(&mut self) {
 		self.blocks.clear();
 		self.peers.clear();
+		self.finality_targets.clear();
 	}
 
 	// Download old block.
@@ -386,8 +684,8 @@ impl<B: BlockT> ChainSync<B> {
 	fn download_new(&mut self, protocol: &mut Context<B>, who: NodeIndex) {
 		if let Some(ref mut peer) = self.peers.get_mut(&who) {
 			let import_status = self.import_queue.status();
-			// when there are too many blocks in the queue => do not try to download new blocks
-			if import_status.importing_count > MAX_IMPORTING_BLOCKS {
+			// when the queue is full => do not try to download new blocks until it drains
+			if import_status.is_full() {
 				trace!(target: "sync", "Too many blocks in the queue.");
 				return;
 			}
@@ -0,0 +1,114 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+
+use client::ChainHead;
+use consensus_gossip::{Validator, ValidationResult};
+use futures::{Future, Stream};
+use super::*;
+
+fn best_number(net: &TestNet, i: usize) -> u64 {
+	net.peer(i).client.info().unwrap().chain.best_number
+}
+
+/// Accepts the gossip messages whose last byte is the sum of the others, and records the peers
+/// whose messages it rejected.
+#[derive(Default)]
+struct ChecksumValidator {
+	rejected: RwLock<Vec<NodeIndex>>,
+}
+
+impl Validator<Block> for ChecksumValidator {
+	fn validate(&self, who: NodeIndex, topic: &Hash, message: &[u8]) -> ValidationResult<Hash> {
+		let valid = message.split_last()
+			.map_or(false, |(sum, data)| data.iter().fold(0u8, |acc, b| acc.wrapping_add(*b)) == *sum);
+		if valid {
+			ValidationResult::ProcessAndKeep(*topic)
+		} else {
+			self.rejected.write().push(who);
+			ValidationResult::Discard
+		}
+	}
+}
+
+#[test]
+fn withheld_responses_stall_sync_from_that_peer() {
+	::env_logger::init().ok();
+	let mut net = TestNet::new(2);
+	net.peer(1).push_blocks(10, false);
+	net.set_malicious(1, MaliciousPeer { withhold_responses: true, ..Default::default() });
+	net.sync();
+	assert_eq!(best_number(&net, 0), 0);
+}
+
+#[test]
+fn invalid_blocks_are_not_imported() {
+	::env_logger::init().ok();
+	let mut net = TestNet::new(2);
+	net.peer(1).push_blocks(10, false);
+	net.set_malicious(1, MaliciousPeer { invalid_blocks: true, ..Default::default() });
+	// the failed imports restart sync, which asks for the blocks again: never done.
+	net.sync_steps(20);
+	assert_eq!(best_number(&net, 0), 0);
+	assert!(net.peer(0).has_reported(1));
+
+	// the blocks are asked for again, until they are sent valid.
+	net.set_malicious(1, Default::default());
+	net.sync_steps(20);
+	assert_eq!(best_number(&net, 0), 10);
+}
+
+#[test]
+fn equivocating_peer_makes_others_import_both_blocks() {
+	::env_logger::init().ok();
+	let mut net = TestNet::new(2);
+	net.sync();
+	net.set_malicious(0, MaliciousPeer { equivocate: true, ..Default::default() });
+	net.peer(0).push_blocks(1, false);
+	net.send_import_notifications();
+	net.sync();
+
+	assert_eq!(net.peer(0).client.leaves().unwrap().len(), 2);
+	assert_eq!(net.peer(1).client.leaves().unwrap().len(), 2);
+	assert_eq!(best_number(&net, 1), 1);
+	// both blocks are valid, so the equivocation isn't held against the peer by the network.
+	assert!(!net.peer(1).has_reported(0));
+}
+
+#[test]
+fn garbled_gossip_is_rejected() {
+	::env_logger::init().ok();
+	let mut net = TestNet::new(2);
+	let topic: Hash = [1; 32].into();
+	let validator = Arc::new(ChecksumValidator::default());
+	net.peer(1).consensus_gossip().write().register_validator(validator.clone());
+	net.sync();
+	let messages = net.peer(1).consensus_gossip().write().messages_for(topic);
+
+	net.set_malicious(0, MaliciousPeer { garble_gossip: true, ..Default::default() });
+	net.peer(0).gossip_message(topic, vec![1, 2, 3]);
+	net.sync();
+	assert_eq!(*validator.rejected.read(), vec![0]);
+
+	// the garbled message was never delivered: the first message received is the next valid one.
+	net.set_malicious(0, Default::default());
+	net.peer(0).gossip_message(topic, vec![2, 3, 5]);
+	net.sync();
+	assert_eq!(*validator.rejected.read(), vec![0]);
+
+	let (message, _) = messages.into_future().wait().map_err(|_| ()).unwrap();
+	assert_eq!(message, Some(vec![2, 3, 5]));
+}
@@ -16,6 +16,8 @@
 
 #![allow(missing_docs)]
 
+#[cfg(test)]
+mod malicious;
 #[cfg(test)]
 mod sync;
 
@@ -33,27 +35,36 @@ use config::ProtocolConfig;
 use service::TransactionPool;
 use network_libp2p::{NodeIndex, PeerId, Severity};
 use keyring::Keyring;
-use codec::Encode;
+use codec::{Decode, Encode};
 use import_queue::{SyncImportQueue, PassThroughVerifier, Verifier};
 use consensus::BlockOrigin;
+use message::{self, generic::Message as GenericMessage};
 use specialization::NetworkSpecialization;
 use consensus_gossip::ConsensusGossip;
-use import_queue::{BlockImport, ImportQueue};
+use import_queue::{BlockImport, ImportQueue, SharedJustificationImport};
 use service::ExecuteInContext;
 use test_client;
 
 pub use test_client::runtime::{Block, Hash, Transfer, Extrinsic};
 pub use test_client::TestClient;
+use test_client::runtime::Header;
 
-struct DummyContextExecutor(Arc<Protocol<Block, DummySpecialization, Hash>>, Arc<RwLock<VecDeque<TestPacket>>>);
+struct DummyContextExecutor(
+	Arc<Protocol<Block, DummySpecialization, Hash>>,
+	Arc<RwLock<VecDeque<TestPacket>>>,
+	Arc<RwLock<HashSet<NodeIndex>>>,
+);
 unsafe impl Send for DummyContextExecutor {}
 unsafe impl Sync for DummyContextExecutor {}
 
 impl ExecuteInContext<Block> for DummyContextExecutor {
 	fn execute_in_context<F: Fn(&mut Context<Block>)>(&self, closure: F) {
 		let mut io = TestIo::new(&self.1, None);
-		let mut context = ProtocolContext::new(&self.0.context_data(), &mut io);
-		closure(&mut context);
+		{
+			let mut context = ProtocolContext::new(&self.0.context_data(), &mut io);
+			closure(&mut context);
+		}
+		self.2.write().extend(io.to_disconnect.drain());
 	}
 }
 
@@ -123,6 +134,20 @@ impl<'p> SyncIo for TestIo<'p> {
 	}
 }
 
+/// Misbehavior of a malicious peer, applied to the messages it sends. The default is an honest
+/// peer.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MaliciousPeer {
+	/// Announce, after each block, a conflicting block of the same parent, imported beforehand.
+	pub equivocate: bool,
+	/// Add an extrinsic with an invalid signature to the blocks sent in block responses.
+	pub invalid_blocks: bool,
+	/// Flip the bits of the consensus messages gossiped.
+	pub garble_gossip: bool,
+	/// Drop block responses and the responses to remote requests instead of sending them.
+	pub withhold_responses: bool,
+}
+
 /// Mocked subprotocol packet
 pub struct TestPacket {
 	data: Vec<u8>,
@@ -137,6 +162,10 @@ pub struct Peer<V: Verifier<Block>, D> {
 	pub queue: Arc<RwLock<VecDeque<TestPacket>>>,
 	import_queue: Arc<SyncImportQueue<Block, V>>,
 	executor: Arc<DummyContextExecutor>,
+	malicious: RwLock<MaliciousPeer>,
+	/// The peers this peer has reported, be it on receipt of their messages or on import of
+	/// their blocks.
+	reported: Arc<RwLock<HashSet<NodeIndex>>>,
 	/// Some custom data set up at initialization time.
 	pub data: D,
 }
@@ -149,8 +178,9 @@ impl<V: 'static + Verifier<Block>, D> Peer<V, D> {
 		import_queue: Arc<SyncImportQueue<Block, V>>,
 		data: D,
 	) -> Self {
-		let executor = Arc::new(DummyContextExecutor(sync.clone(), queue.clone()));
-		Peer { client, sync, queue, import_queue, executor, data }
+		let reported = Arc::new(RwLock::new(HashSet::new()));
+		let executor = Arc::new(DummyContextExecutor(sync.clone(), queue.clone(), reported.clone()));
+		Peer { client, sync, queue, import_queue, executor, malicious: Default::default(), reported, data }
 	}
 	/// Called after blockchain has been populated to updated current state.
 	fn start(&self) {
@@ -186,18 +216,110 @@ impl<V: 'static + Verifier<Block>, D> Peer<V, D> {
 		let mut io = TestIo::new(&self.queue, Some(from));
 		self.sync.handle_packet(&mut io, from, &msg.data);
 		self.flush();
+		self.reported.write().extend(io.to_disconnect.iter().cloned());
 		io.to_disconnect.clone()
 	}
 
+	/// Whether this peer has reported the given one as misbehaving or useless.
+	pub fn has_reported(&self, who: NodeIndex) -> bool {
+		self.reported.read().contains(&who)
+	}
+
 	fn with_io<'a, F, U>(&'a self, f: F) -> U where F: FnOnce(&mut TestIo<'a>) -> U {
 		let mut io = TestIo::new(&self.queue, None);
 		f(&mut io)
 	}
 
+	/// Set the misbehavior of this peer, from now on.
+	pub fn set_malicious(&self, behavior: MaliciousPeer) {
+		*self.malicious.write() = behavior;
+	}
+
 	/// Produce the next pending message to send to another peer.
 	fn pending_message(&self) -> Option<TestPacket> {
 		self.flush();
-		self.queue.write().pop_front()
+		loop {
+			let packet = self.queue.write().pop_front()?;
+			if let Some(packet) = self.tamper(packet) {
+				return Some(packet);
+			}
+		}
+	}
+
+	/// Apply the misbehavior of this peer to a message it sends. `None` if it is withheld.
+	fn tamper(&self, mut packet: TestPacket) -> Option<TestPacket> {
+		let behavior = self.malicious.read().clone();
+		if behavior == MaliciousPeer::default() {
+			return Some(packet);
+		}
+
+		let mut message: message::Message<Block> = match Decode::decode(&mut &packet.data[..]) {
+			Some(message) => message,
+			None => return Some(packet),
+		};
+		let response = match message {
+			GenericMessage::BlockResponse(_) |
+			GenericMessage::RemoteCallResponse(_) |
+			GenericMessage::RemoteReadResponse(_) |
+			GenericMessage::RemoteHeaderResponse(_) |
+			GenericMessage::RemoteChangesResponse(_) |
+			GenericMessage::GenericResponse(_) => true,
+			_ => false,
+		};
+		if response && behavior.withhold_responses {
+			return None;
+		}
+
+		match message {
+			GenericMessage::BlockResponse(ref mut response) => if behavior.invalid_blocks {
+				for block in response.blocks.iter_mut() {
+					if let Some(ref mut body) = block.body {
+						body.push(invalid_extrinsic());
+					}
+				}
+			},
+			GenericMessage::Consensus(_, ref mut data) => if behavior.garble_gossip {
+				for byte in data.iter_mut() {
+					*byte = !*byte;
+				}
+			},
+			GenericMessage::BlockAnnounce(ref announce) => if behavior.equivocate {
+				if let Some(header) = self.equivocate(&announce.header) {
+					self.queue.write().push_back(TestPacket {
+						data: GenericMessage::BlockAnnounce(message::BlockAnnounce { header }).encode(),
+						recipient: packet.recipient,
+					});
+				}
+			},
+			_ => {},
+		}
+
+		packet.data = message.encode();
+		Some(packet)
+	}
+
+	/// Import a block conflicting with the one of the given header, of the same parent. Returns
+	/// its header, `None` if the given block is the conflicting one.
+	fn equivocate(&self, header: &Header) -> Option<Header> {
+		// the conflicting block always differs by the same transfer, so it is built again the
+		// same when announced in turn.
+		let mut builder = self.client.new_block_at(&BlockId::Hash(header.parent_hash)).unwrap();
+		let transfer = Transfer {
+			from: Keyring::Ferdie.to_raw_public().into(),
+			to: Keyring::Ferdie.to_raw_public().into(),
+			amount: 0,
+			nonce: 0,
+		};
+		let signature = Keyring::Ferdie.sign(&transfer.encode()).into();
+		builder.push(Extrinsic { transfer, signature }).unwrap();
+		let block = builder.bake().unwrap();
+		if block.header == *header {
+			return None;
+		}
+
+		let conflicting = block.header.clone();
+		self.import_block(BlockOrigin::Own, block);
+		Some(conflicting)
 	}
 
 	/// Whether this peer is done syncing (has no messages to send).
@@ -236,32 +358,33 @@ impl<V: 'static + Verifier<Block>, D> Peer<V, D> {
 	pub fn generate_blocks<F>(&self, count: usize, origin: BlockOrigin, mut edit_block: F)
 		where F: FnMut(BlockBuilder<Block, (), PeersClient>) -> Block
 	{
-		use blocks::BlockData;
-
 		for _  in 0..count {
 			let builder = self.client.new_block().unwrap();
 			let block = edit_block(builder);
-			let hash = block.header.hash();
-			trace!("Generating {}, (#{}, parent={})", hash, block.header.number, block.header.parent_hash);
-			let header = block.header.clone();
-
-			// NOTE: if we use a non-synchronous queue in the test-net in the future,
-			// this may not work.
-		 	self.import_queue.import_blocks(origin, vec![BlockData {
-				origin: None,
-				block: ::message::BlockData::<Block> {
-					hash,
-					header: Some(header),
-					body: Some(block.extrinsics),
-					receipt: None,
-					message_queue: None,
-					justification: Some(Vec::new()),
-				},
-			}]);
+			trace!("Generating {}, (#{}, parent={})", block.header.hash(), block.header.number, block.header.parent_hash);
+			self.import_block(origin, block);
 		}
 
 	}
 
+	fn import_block(&self, origin: BlockOrigin, block: Block) {
+		use blocks::BlockData;
+
+		// NOTE: if we use a non-synchronous queue in the test-net in the future,
+		// this may not work.
+		self.import_queue.import_blocks(origin, vec![BlockData {
+			origin: None,
+			block: ::message::BlockData::<Block> {
+				hash: block.header.hash(),
+				header: Some(block.header),
+				body: Some(block.extrinsics),
+				receipt: None,
+				message_queue: None,
+				justification: Some(Vec::new()),
+			},
+		}]);
+	}
+
 	/// Push blocks to the peer (simplified: with or without a TX)
 	pub fn push_blocks(&self, count: usize, with_tx: bool) {
 		let mut nonce = 0;
@@ -296,6 +419,19 @@ impl<V: 'static + Verifier<Block>, D> Peer<V, D> {
 	}
 }
 
+/// An extrinsic whose signature is invalid, making any block including it invalid.
+fn invalid_extrinsic() -> Extrinsic {
+	Extrinsic {
+		transfer: Transfer {
+			from: Keyring::Alice.to_raw_public().into(),
+			to: Keyring::Bob.to_raw_public().into(),
+			amount: 1,
+			nonce: 0,
+		},
+		signature: Default::default(),
+	}
+}
+
 pub struct EmptyTransactionPool;
 
 impl TransactionPool<Hash, Block> for EmptyTransactionPool {
@@ -327,11 +463,16 @@ pub trait TestNetFactory: Sized {
 	fn started(&self) -> bool;
 	fn set_started(&mut self, now: bool);
 
-	/// Get custom block import handle for fresh client, along with peer data.
+	/// Get custom block import handle for fresh client, along with an optional justification
+	/// import handle and peer data.
 	fn make_block_import(&self, client: Arc<PeersClient>)
-		-> (Arc<BlockImport<Block,Error=ClientError> + Send + Sync>, Self::PeerData)
+		-> (
+			Arc<BlockImport<Block,Error=ClientError> + Send + Sync>,
+			Option<SharedJustificationImport<Block>>,
+			Self::PeerData,
+		)
 	{
-		(client, Default::default())
+		(client, None, Default::default())
 	}
 
 	fn default_config() -> ProtocolConfig {
@@ -354,9 +495,9 @@ pub trait TestNetFactory: Sized {
 		let client = Arc::new(test_client::new());
 		let tx_pool = Arc::new(EmptyTransactionPool);
 		let verifier = self.make_verifier(client.clone(), config);
-		let (block_import, data) = self.make_block_import(client.clone());
+		let (block_import, justification_import, data) = self.make_block_import(client.clone());
 
-		let import_queue = Arc::new(SyncImportQueue::new(verifier, block_import));
+		let import_queue = Arc::new(SyncImportQueue::new(verifier, block_import, justification_import));
 		let specialization = DummySpecialization { };
 		let sync = Protocol::new(
 			config.clone(),
@@ -455,6 +596,11 @@ pub trait TestNetFactory: Sized {
 		self.peers()[i].restart_sync();
 	}
 
+	/// Make a peer misbehave, or behave again when given the default `MaliciousPeer`.
+	fn set_malicious(&mut self, i: usize, behavior: MaliciousPeer) {
+		self.peers()[i].set_malicious(behavior);
+	}
+
 	/// Perform synchronization until complete.
 	fn sync(&mut self) -> u32 {
 		self.start();
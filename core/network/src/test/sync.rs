@@ -89,6 +89,57 @@ fn sync_after_fork_works() {
 	assert!(net.peer(2).client.backend().blockchain().canon_equals_to(&peer1_chain));
 }
 
+#[test]
+fn finality_target_on_a_fork_is_downloaded_with_its_ancestry() {
+	::env_logger::init().ok();
+	let mut net = TestNet::new(2);
+	net.peer(1).push_blocks(60, false);
+
+	// fork the chain of peer 1 at block 30.
+	let mut parent = BlockId::Number(30);
+	let mut target = None;
+	for nonce in 0..10 {
+		let mut builder = net.peer(1).client().new_block_at(&parent).unwrap();
+		let transfer = Transfer {
+			from: Keyring::Alice.to_raw_public().into(),
+			to: Keyring::Alice.to_raw_public().into(),
+			amount: 1,
+			nonce,
+		};
+		let signature = Keyring::Alice.sign(&transfer.encode()).into();
+		builder.push(Extrinsic { transfer, signature }).unwrap();
+		let block = builder.bake().unwrap();
+		parent = BlockId::Hash(block.header.hash());
+		target = Some(block.header.clone());
+		net.peer(1).import_block(BlockOrigin::File, block);
+	}
+	let target = target.unwrap();
+
+	// the best chain is synced, but not the fork.
+	net.sync();
+	assert_eq!(net.peer(0).client().info().unwrap().chain.best_number, 60);
+	assert!(net.peer(0).client().header(&BlockId::Hash(target.hash())).unwrap().is_none());
+
+	// the target doesn't connect to the best chain: its ancestry is searched down to the fork.
+	net.peer(0).with_io(|io| net.peer(0).sync.request_finality_target(io, target.hash(), target.number));
+	net.sync();
+	assert_eq!(net.peer(0).client().header(&BlockId::Hash(target.hash())).unwrap(), Some(target));
+	assert_eq!(net.peer(0).client().info().unwrap().chain.best_number, 60);
+}
+
+#[test]
+fn unknown_finality_target_is_dropped() {
+	::env_logger::init().ok();
+	let mut net = TestNet::new(2);
+	net.peer(1).push_blocks(20, false);
+	net.sync();
+
+	net.peer(0).with_io(|io| net.peer(0).sync.request_finality_target(io, [1; 32].into(), 10));
+	net.sync();
+	assert_eq!(net.peer(0).client().info().unwrap().chain.best_number, 20);
+	assert_eq!(net.peer(0).sync.status().sync.state, SyncState::Idle);
+}
+
 #[test]
 fn own_blocks_are_announced() {
 	::env_logger::init().ok();
@@ -0,0 +1,145 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Application-specific keys.
+//!
+//! The keystore of a node holds the keys of several applications, e.g. block authoring,
+//! finality voting or heartbeats. Every application is identified by a `KeyTypeId` and wraps
+//! raw ed25519 public keys and signatures in its own types, so that a key or signature of one
+//! application can't be passed where those of another one are expected.
+
+use rstd::marker::PhantomData;
+use codec::{Encode, Decode, Input, Output};
+use {AuthorityId, H512};
+
+/// An identifier for a type of key, e.g. `*b"aura"`.
+pub type KeyTypeId = [u8; 4];
+
+/// Key types of the applications known to Substrate.
+pub mod key_types {
+	use super::KeyTypeId;
+
+	/// Keys used for authoring blocks with Aura.
+	pub const AURA: KeyTypeId = *b"aura";
+	/// Keys used for voting in GRANDPA.
+	pub const GRANDPA: KeyTypeId = *b"gran";
+	/// Keys used for signing heartbeats of online validators.
+	pub const IM_ONLINE: KeyTypeId = *b"imon";
+	/// Keys the network identity of the node is derived from.
+	pub const NODE: KeyTypeId = *b"node";
+}
+
+/// An application that has keys of its own in the keystore.
+pub trait AppKey {
+	/// The key type of the application.
+	const ID: KeyTypeId;
+}
+
+/// A public key of the application `A`.
+pub struct AppPublic<A>(AuthorityId, PhantomData<A>);
+
+/// A signature made with a key of the application `A`.
+pub struct AppSignature<A>(H512, PhantomData<A>);
+
+impl<A: AppKey> AppPublic<A> {
+	/// Wrap a raw public key.
+	pub fn from_raw(raw: [u8; 32]) -> Self {
+		AppPublic(AuthorityId(raw), PhantomData)
+	}
+
+	/// The raw public key.
+	pub fn into_raw(self) -> [u8; 32] {
+		(self.0).0
+	}
+}
+
+impl<A: AppKey> AppSignature<A> {
+	/// Wrap a raw signature.
+	pub fn from_raw(raw: [u8; 64]) -> Self {
+		AppSignature(raw.into(), PhantomData)
+	}
+
+	/// The raw signature.
+	pub fn into_raw(self) -> [u8; 64] {
+		let mut raw = [0u8; 64];
+		raw.copy_from_slice(self.0.as_ref());
+		raw
+	}
+}
+
+impl<A> AsRef<[u8]> for AppPublic<A> {
+	fn as_ref(&self) -> &[u8] {
+		self.0.as_ref()
+	}
+}
+
+impl<A> AsRef<[u8]> for AppSignature<A> {
+	fn as_ref(&self) -> &[u8] {
+		self.0.as_ref()
+	}
+}
+
+// `derive` would require `A` itself to implement these traits.
+macro_rules! impl_app_wrapper {
+	( $name:ident ) => {
+		impl<A> Clone for $name<A> {
+			fn clone(&self) -> Self {
+				$name(self.0.clone(), PhantomData)
+			}
+		}
+
+		impl<A> PartialEq for $name<A> {
+			fn eq(&self, other: &Self) -> bool {
+				self.0 == other.0
+			}
+		}
+
+		impl<A> Eq for $name<A> {}
+
+		impl<A> Encode for $name<A> {
+			fn encode_to<T: Output>(&self, dest: &mut T) {
+				self.0.encode_to(dest)
+			}
+		}
+
+		impl<A> Decode for $name<A> {
+			fn decode<I: Input>(input: &mut I) -> Option<Self> {
+				Decode::decode(input).map(|raw| $name(raw, PhantomData))
+			}
+		}
+
+		#[cfg(feature = "std")]
+		impl<A> ::std::fmt::Debug for $name<A> {
+			fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+				::std::fmt::Debug::fmt(&self.0, f)
+			}
+		}
+	}
+}
+
+impl_app_wrapper!(AppPublic);
+impl_app_wrapper!(AppSignature);
+
+/// Access to the keys of the node, by key type.
+#[cfg(feature = "std")]
+pub trait Keystore: Send + Sync {
+	/// Public keys of the given type.
+	fn public_keys(&self, key_type: KeyTypeId) -> Vec<::ed25519::Public>;
+
+	/// Sign `msg` with the key of the given type and public key. Returns `None` if there is
+	/// no such key.
+	fn sign(&self, key_type: KeyTypeId, public: &::ed25519::Public, msg: &[u8]) -> Option<::ed25519::Signature>;
+}
@@ -14,7 +14,7 @@
 // You should have received a copy of the GNU General Public License
 // along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
 
-//! Substrate Blake2b Hasher implementation
+//! Substrate Blake2b and Keccak Hasher implementations
 
 use hash_db::Hasher;
 use hash256_std_hasher::Hash256StdHasher;
@@ -51,3 +51,48 @@ pub mod blake2 {
 		}
 	}
 }
+
+pub mod keccak {
+	use super::{Hasher, Hash256StdHasher, H256};
+	#[cfg(feature = "std")]
+	use hashing::keccak_256;
+
+	#[cfg(not(feature = "std"))]
+	extern "C" {
+		fn ext_keccak_256(data: *const u8, len: u32, out: *mut u8);
+	}
+	#[cfg(not(feature = "std"))]
+	fn keccak_256(data: &[u8]) -> [u8; 32] {
+		let mut result: [u8; 32] = Default::default();
+		unsafe {
+			ext_keccak_256(data.as_ptr(), data.len() as u32, result.as_mut_ptr());
+		}
+		result
+	}
+
+	/// Concrete implementation of Hasher using Keccak 256-bit hashes, as used by Ethereum
+	#[derive(Debug)]
+	pub struct KeccakHasher;
+
+	impl Hasher for KeccakHasher {
+		type Out = H256;
+		type StdHasher = Hash256StdHasher;
+		const LENGTH: usize = 32;
+		fn hash(x: &[u8]) -> Self::Out {
+			keccak_256(x).into()
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn keccak_hasher_matches_known_hash() {
+		assert_eq!(
+			keccak::KeccakHasher::hash(b""),
+			H256::from(hex!("c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470"))
+		);
+	}
+}
@@ -42,6 +42,8 @@ extern crate twox_hash;
 #[cfg(feature = "std")]
 extern crate blake2_rfc;
 #[cfg(feature = "std")]
+extern crate tiny_keccak;
+#[cfg(feature = "std")]
 extern crate ring;
 #[cfg(feature = "std")]
 extern crate base58;
@@ -88,13 +90,14 @@ pub mod bytes;
 #[cfg(feature = "std")]
 pub mod hashing;
 #[cfg(feature = "std")]
-pub use hashing::{blake2_256, twox_128, twox_256};
+pub use hashing::{blake2_256, keccak_256, twox_128, twox_256};
 #[cfg(feature = "std")]
 pub mod hexdisplay;
 #[cfg(feature = "std")]
 pub mod ed25519;
 
 pub mod u32_trait;
+pub mod crypto;
 
 pub mod hash;
 mod hasher;
@@ -116,6 +119,7 @@ pub use hash_db::Hasher;
 // Switch back to Blake after PoC-3 is out
 // pub use self::hasher::blake::BlakeHasher;
 pub use self::hasher::blake2::Blake2Hasher;
+pub use self::hasher::keccak::KeccakHasher;
 
 /// A 512-bit value interpreted as a signature.
 pub type Signature = hash::H512;
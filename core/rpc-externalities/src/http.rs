@@ -0,0 +1,112 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Remote storage fetched over the HTTP RPC interface of a node.
+
+use std::cell::RefCell;
+
+use futures::{Future, Stream};
+use hyper::{Body, Client, Method, Request, Uri};
+use hyper::client::HttpConnector;
+use hyper::header::{self, HeaderValue};
+use primitives::H256;
+use primitives::storage::{StorageData, StorageKey};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json;
+use tokio::runtime::current_thread::Runtime;
+
+use {ErrorKind, RemoteStorage, Result};
+
+#[derive(Serialize)]
+struct RpcRequest<P> {
+	jsonrpc: &'static str,
+	id: u64,
+	method: &'static str,
+	params: P,
+}
+
+#[derive(Deserialize)]
+struct RpcResponse<R> {
+	result: Option<R>,
+	error: Option<RpcError>,
+}
+
+#[derive(Deserialize)]
+struct RpcError {
+	message: String,
+}
+
+/// Storage of a node, fetched with `state_getStorage` calls over its HTTP RPC interface.
+///
+/// The calls are blocking.
+pub struct HttpStorage {
+	uri: Uri,
+	at: Option<H256>,
+	client: Client<HttpConnector>,
+	runtime: RefCell<Runtime>,
+}
+
+impl HttpStorage {
+	/// Fetch the storage of the node at `uri`, at the block with the given hash or at the best
+	/// block of the node at the time of each call.
+	///
+	/// Unless the block is given, reads may observe the state of different blocks.
+	pub fn new(uri: Uri, at: Option<H256>) -> ::std::io::Result<Self> {
+		Ok(HttpStorage {
+			uri,
+			at,
+			client: Client::new(),
+			runtime: RefCell::new(Runtime::new()?),
+		})
+	}
+
+	/// Fetch the header of the block the storage is read at, with `chain_getHeader`.
+	pub fn header<H: DeserializeOwned>(&self) -> Result<Option<H>> {
+		self.call("chain_getHeader", (&self.at,))
+	}
+
+	fn call<P: Serialize, R: DeserializeOwned>(&self, method: &'static str, params: P) -> Result<Option<R>> {
+		let body = serde_json::to_vec(&RpcRequest {
+			jsonrpc: "2.0",
+			id: 1,
+			method,
+			params,
+		})?;
+
+		let mut request = Request::new(Body::from(body));
+		*request.method_mut() = Method::POST;
+		*request.uri_mut() = self.uri.clone();
+		request.headers_mut().insert(header::CONTENT_TYPE, HeaderValue::from_static("application/json"));
+
+		let response = self.client.request(request)
+			.and_then(|response| response.into_body().concat2());
+		let response = self.runtime.borrow_mut().block_on(response)?;
+		let response: RpcResponse<R> = serde_json::from_slice(&response)?;
+
+		match response.error {
+			Some(error) => Err(ErrorKind::Rpc(error.message).into()),
+			None => Ok(response.result),
+		}
+	}
+}
+
+impl RemoteStorage for HttpStorage {
+	fn storage(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+		let data: Option<StorageData> = self.call("state_getStorage", (StorageKey(key.to_vec()), &self.at))?;
+		Ok(data.map(|data| data.0))
+	}
+}
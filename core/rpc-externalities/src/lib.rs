@@ -0,0 +1,286 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Externalities backed by the state of a remote node.
+//!
+//! Storage is fetched on demand with `state_getStorage` calls and cached, while all changes are
+//! kept locally. This allows to run runtime code against the state of a live chain, e.g. for
+//! debugging or analytics, without syncing it.
+//!
+//! Neither storage roots nor child storage can be served this way: the remote state is never
+//! complete locally and the RPC does not expose child tries.
+//!
+//! On top of them, runtime upgrades can be rehearsed with `try_upgrade`, to check what the
+//! migrations of a new runtime change and how much they weigh before the upgrade is enacted.
+
+#![warn(missing_docs)]
+
+extern crate futures;
+extern crate hyper;
+extern crate parity_codec as codec;
+extern crate serde;
+extern crate serde_json;
+extern crate substrate_primitives as primitives;
+extern crate substrate_state_machine as state_machine;
+extern crate tokio;
+
+#[macro_use]
+extern crate error_chain;
+#[macro_use]
+extern crate log;
+#[macro_use]
+extern crate serde_derive;
+
+mod http;
+mod try_runtime;
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+
+use primitives::Blake2Hasher;
+use primitives::H256;
+use state_machine::Externalities;
+
+pub use http::HttpStorage;
+pub use try_runtime::{try_upgrade, StorageDiff, UpgradeReport};
+
+error_chain! {
+	foreign_links {
+		Hyper(hyper::Error) #[doc = "Request to the remote node failed"];
+		Json(serde_json::Error) #[doc = "Response of the remote node could not be decoded"];
+	}
+
+	errors {
+		/// The remote node answered with an error.
+		Rpc(message: String) {
+			description("remote node returned an error"),
+			display("Remote node returned an error: {}", message),
+		}
+
+		/// The runtime failed to execute.
+		Execution(message: String) {
+			description("runtime execution failed"),
+			display("Runtime execution failed: {}", message),
+		}
+	}
+}
+
+/// Storage of a remote node.
+pub trait RemoteStorage {
+	/// Fetch the value stored under `key`.
+	fn storage(&self, key: &[u8]) -> Result<Option<Vec<u8>>>;
+}
+
+/// Externalities reading the storage of a remote node and keeping all changes locally.
+pub struct RemoteExternalities<S> {
+	remote: S,
+	cache: RefCell<HashMap<Vec<u8>, Option<Vec<u8>>>>,
+	changes: HashMap<Vec<u8>, Option<Vec<u8>>>,
+	cleared_prefixes: Vec<Vec<u8>>,
+	reads: Cell<u64>,
+	writes: u64,
+}
+
+impl<S: RemoteStorage> RemoteExternalities<S> {
+	/// Create externalities on top of the given remote storage.
+	pub fn new(remote: S) -> Self {
+		RemoteExternalities {
+			remote,
+			cache: Default::default(),
+			changes: Default::default(),
+			cleared_prefixes: Vec::new(),
+			reads: Cell::new(0),
+			writes: 0,
+		}
+	}
+
+	/// The changes made so far, `None` values being removals.
+	///
+	/// Entries removed by clearing a prefix are only included if they were read or changed.
+	pub fn changes(&self) -> &HashMap<Vec<u8>, Option<Vec<u8>>> {
+		&self.changes
+	}
+
+	/// The number of storage reads made so far.
+	pub fn reads(&self) -> u64 {
+		self.reads.get()
+	}
+
+	/// The number of storage writes made so far. Clearing a prefix counts as a single write.
+	pub fn writes(&self) -> u64 {
+		self.writes
+	}
+
+	/// Drop all changes made so far and reset the counts of reads and writes, keeping the cached
+	/// remote storage.
+	pub fn discard_changes(&mut self) {
+		self.changes.clear();
+		self.cleared_prefixes.clear();
+		self.reads.set(0);
+		self.writes = 0;
+	}
+
+	fn remote_storage(&self, key: &[u8]) -> Option<Vec<u8>> {
+		if let Some(value) = self.cache.borrow().get(key) {
+			return value.clone();
+		}
+
+		let value = self.remote.storage(key)
+			.unwrap_or_else(|e| panic!("Failed to fetch storage from the remote node: {}", e));
+		self.cache.borrow_mut().insert(key.to_vec(), value.clone());
+		value
+	}
+}
+
+impl<S: RemoteStorage> Externalities<Blake2Hasher> for RemoteExternalities<S> {
+	fn storage(&self, key: &[u8]) -> Option<Vec<u8>> {
+		self.reads.set(self.reads.get() + 1);
+		if let Some(value) = self.changes.get(key) {
+			return value.clone();
+		}
+		if self.cleared_prefixes.iter().any(|prefix| key.starts_with(prefix)) {
+			return None;
+		}
+
+		self.remote_storage(key)
+	}
+
+	fn child_storage(&self, _storage_key: &[u8], _key: &[u8]) -> Option<Vec<u8>> {
+		None
+	}
+
+	fn place_storage(&mut self, key: Vec<u8>, value: Option<Vec<u8>>) {
+		self.writes += 1;
+		self.changes.insert(key, value);
+	}
+
+	fn place_child_storage(&mut self, _storage_key: Vec<u8>, _key: Vec<u8>, _value: Option<Vec<u8>>) -> bool {
+		false
+	}
+
+	fn kill_child_storage(&mut self, _storage_key: &[u8]) { }
+
+	fn clear_prefix(&mut self, prefix: &[u8]) {
+		self.writes += 1;
+		for (key, value) in self.changes.iter_mut() {
+			if key.starts_with(prefix) {
+				*value = None;
+			}
+		}
+		self.cleared_prefixes.push(prefix.to_vec());
+	}
+
+	/// The keys of the remote node can't be enumerated, so the prefix is always cleared as a
+	/// whole. Only the entries known locally are counted.
+	fn clear_prefix_limited(&mut self, prefix: &[u8], _limit: u32, _cursor: Option<&[u8]>) -> (u32, Option<Vec<u8>>) {
+		let removed = self.changes.iter()
+			.filter(|(key, value)| key.starts_with(prefix) && value.is_some())
+			.count();
+		self.clear_prefix(prefix);
+		(removed as u32, None)
+	}
+
+	fn chain_id(&self) -> u64 { 42 }
+
+	fn storage_root(&mut self) -> H256 {
+		warn!(target: "rpc-externalities", "Storage root requested, which can't be computed from remote storage");
+		Default::default()
+	}
+
+	fn child_storage_root(&mut self, _storage_key: &[u8]) -> Option<Vec<u8>> {
+		None
+	}
+
+	fn storage_changes_root(&mut self, _parent: H256, _parent_num: u64) -> Option<H256> {
+		None
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::cell::Cell;
+
+	#[derive(Default)]
+	struct MockStorage {
+		storage: HashMap<Vec<u8>, Vec<u8>>,
+		fetches: Cell<usize>,
+	}
+
+	impl RemoteStorage for MockStorage {
+		fn storage(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+			self.fetches.set(self.fetches.get() + 1);
+			Ok(self.storage.get(key).cloned())
+		}
+	}
+
+	fn remote() -> RemoteExternalities<MockStorage> {
+		let mut storage = MockStorage::default();
+		storage.storage.insert(b"aa".to_vec(), b"1".to_vec());
+		storage.storage.insert(b"ab".to_vec(), b"2".to_vec());
+		storage.storage.insert(b"b".to_vec(), b"3".to_vec());
+		RemoteExternalities::new(storage)
+	}
+
+	#[test]
+	fn remote_storage_is_fetched_once() {
+		let ext = remote();
+		assert_eq!(ext.storage(b"aa"), Some(b"1".to_vec()));
+		assert_eq!(ext.storage(b"aa"), Some(b"1".to_vec()));
+		assert_eq!(ext.storage(b"c"), None);
+		assert_eq!(ext.storage(b"c"), None);
+		assert_eq!(ext.remote.fetches.get(), 2);
+	}
+
+	#[test]
+	fn changes_are_kept_locally() {
+		let mut ext = remote();
+		ext.set_storage(b"aa".to_vec(), b"4".to_vec());
+		ext.clear_storage(b"b");
+		assert_eq!(ext.storage(b"aa"), Some(b"4".to_vec()));
+		assert_eq!(ext.storage(b"b"), None);
+		assert_eq!(ext.remote.storage.get(&b"aa"[..]), Some(&b"1".to_vec()));
+
+		ext.discard_changes();
+		assert_eq!(ext.storage(b"aa"), Some(b"1".to_vec()));
+	}
+
+	#[test]
+	fn cleared_prefixes_hide_remote_storage() {
+		let mut ext = remote();
+		ext.set_storage(b"ac".to_vec(), b"5".to_vec());
+		assert_eq!(ext.clear_prefix_limited(b"a", 1, None), (1, None));
+		assert_eq!(ext.storage(b"aa"), None);
+		assert_eq!(ext.storage(b"ac"), None);
+		assert_eq!(ext.storage(b"b"), Some(b"3".to_vec()));
+
+		ext.set_storage(b"ab".to_vec(), b"6".to_vec());
+		assert_eq!(ext.storage(b"ab"), Some(b"6".to_vec()));
+	}
+
+	#[test]
+	fn reads_and_writes_are_counted() {
+		let mut ext = remote();
+		ext.storage(b"aa");
+		ext.storage(b"aa");
+		ext.set_storage(b"b".to_vec(), b"4".to_vec());
+		ext.clear_prefix(b"a");
+		assert_eq!((ext.reads(), ext.writes()), (2, 2));
+
+		ext.discard_changes();
+		assert_eq!((ext.reads(), ext.writes()), (0, 0));
+	}
+}
@@ -0,0 +1,184 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Rehearsing runtime upgrades against the state of a remote node.
+//!
+//! Runtimes migrate their storage when the first block after an upgrade is initialised, before
+//! anything else is done in the block. An upgrade is thus rehearsed by setting the new code and
+//! initialising a block with it on top of the remote state.
+
+use codec::Decode;
+use primitives::Blake2Hasher;
+use primitives::storage::well_known_keys;
+use state_machine::{CodeExecutor, Externalities};
+
+use {ErrorKind, RemoteExternalities, RemoteStorage, Result};
+
+/// The heap pages of the runtime, unless the remote state sets them.
+const DEFAULT_HEAP_PAGES: u64 = 1024;
+
+/// A storage entry changed by an upgrade.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StorageDiff {
+	/// The key of the entry.
+	pub key: Vec<u8>,
+	/// The value of the remote node.
+	pub before: Option<Vec<u8>>,
+	/// The value after the upgrade, `None` if it was removed.
+	pub after: Option<Vec<u8>>,
+}
+
+/// What a rehearsed upgrade did.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpgradeReport {
+	/// The entries changed by the upgrade, ordered by key. The runtime code is left out, and so
+	/// are the entries removed by clearing a prefix without being read or written.
+	pub diffs: Vec<StorageDiff>,
+	/// The number of storage reads made while initialising the block.
+	pub reads: u64,
+	/// The number of storage writes made while initialising the block.
+	pub writes: u64,
+}
+
+impl UpgradeReport {
+	/// The weight consumed by the upgrade: like calls are weighed, the number of storage items it
+	/// reads and writes.
+	pub fn weight(&self) -> u64 {
+		self.reads + self.writes
+	}
+}
+
+/// Rehearse an upgrade to the wasm runtime `code`, initialising the block with the given encoded
+/// header on top of the remote storage.
+///
+/// Any changes made to `ext` before are discarded. The changes of the upgrade are kept, so that
+/// e.g. blocks can be executed on top of them.
+pub fn try_upgrade<S, E>(
+	ext: &mut RemoteExternalities<S>,
+	executor: &E,
+	code: &[u8],
+	header: &[u8],
+) -> Result<UpgradeReport>
+where
+	S: RemoteStorage,
+	E: CodeExecutor<Blake2Hasher>,
+{
+	ext.discard_changes();
+	ext.set_storage(well_known_keys::CODE.to_vec(), code.to_vec());
+	let heap_pages = ext.storage(well_known_keys::HEAP_PAGES)
+		.and_then(|v| u64::decode(&mut &v[..]))
+		.unwrap_or(DEFAULT_HEAP_PAGES);
+	let (reads, writes) = (ext.reads(), ext.writes());
+
+	executor.call(ext, heap_pages as usize, code, "initialise_block", header, false).0
+		.map_err(|e| ErrorKind::Execution(e.to_string()))?;
+
+	let mut diffs: Vec<StorageDiff> = ext.changes().iter()
+		.filter(|&(key, _)| &key[..] != well_known_keys::CODE)
+		.map(|(key, after)| StorageDiff {
+			key: key.clone(),
+			before: ext.remote_storage(key),
+			after: after.clone(),
+		})
+		.filter(|diff| diff.before != diff.after)
+		.collect();
+	diffs.sort_by(|a, b| a.key.cmp(&b.key));
+
+	Ok(UpgradeReport {
+		diffs,
+		reads: ext.reads() - reads,
+		writes: ext.writes() - writes,
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::collections::HashMap;
+	use state_machine::{ExecutionError, ExecutionLimits};
+
+	struct MockStorage(HashMap<Vec<u8>, Vec<u8>>);
+
+	impl RemoteStorage for MockStorage {
+		fn storage(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+			Ok(self.0.get(key).cloned())
+		}
+	}
+
+	/// Moves the value of `old` to `new` on initialisation, unless the code is empty.
+	struct MigratingExecutor;
+
+	impl CodeExecutor<Blake2Hasher> for MigratingExecutor {
+		type Error = ExecutionError;
+
+		fn call_with_limits<E: Externalities<Blake2Hasher>>(
+			&self,
+			ext: &mut E,
+			_heap_pages: usize,
+			code: &[u8],
+			method: &str,
+			data: &[u8],
+			_use_native: bool,
+			_limits: ExecutionLimits,
+		) -> (::std::result::Result<Vec<u8>, Self::Error>, bool) {
+			assert_eq!(method, "initialise_block");
+			assert_eq!(data, b"header");
+			if code.is_empty() {
+				return (Err(ExecutionError::CodeEntryDoesNotExist), false);
+			}
+			if let Some(value) = ext.storage(b"old") {
+				ext.clear_storage(b"old");
+				ext.set_storage(b"new".to_vec(), value);
+			}
+			ext.set_storage(b"unchanged".to_vec(), b"1".to_vec());
+			(Ok(Vec::new()), false)
+		}
+	}
+
+	fn remote() -> RemoteExternalities<MockStorage> {
+		let mut storage = HashMap::new();
+		storage.insert(well_known_keys::CODE.to_vec(), b"old code".to_vec());
+		storage.insert(b"old".to_vec(), b"value".to_vec());
+		storage.insert(b"unchanged".to_vec(), b"1".to_vec());
+		RemoteExternalities::new(MockStorage(storage))
+	}
+
+	#[test]
+	fn upgrade_reports_changed_entries_and_weight() {
+		let mut ext = remote();
+		ext.set_storage(b"discarded".to_vec(), b"2".to_vec());
+
+		let report = try_upgrade(&mut ext, &MigratingExecutor, b"new code", b"header").unwrap();
+		assert_eq!(report, UpgradeReport {
+			diffs: vec![
+				StorageDiff { key: b"new".to_vec(), before: None, after: Some(b"value".to_vec()) },
+				StorageDiff { key: b"old".to_vec(), before: Some(b"value".to_vec()), after: None },
+			],
+			reads: 1,
+			writes: 3,
+		});
+		assert_eq!(report.weight(), 4);
+		assert_eq!(ext.storage(b"new"), Some(b"value".to_vec()));
+		assert_eq!(ext.storage(well_known_keys::CODE), Some(b"new code".to_vec()));
+		assert_eq!(ext.storage(b"discarded"), None);
+	}
+
+	#[test]
+	fn failed_upgrade_is_an_error() {
+		let mut ext = remote();
+		assert!(try_upgrade(&mut ext, &MigratingExecutor, b"", b"header").is_err());
+	}
+}
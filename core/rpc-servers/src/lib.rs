@@ -38,10 +38,12 @@ pub type HttpServer = http::Server;
 pub type WsServer = ws::Server;
 
 /// Construct rpc `IoHandler`
-pub fn rpc_handler<Block: BlockT, ExHash, S, C, A, Y>(
+pub fn rpc_handler<Block: BlockT, ExHash, S, C, A, F, E, Y>(
 	state: S,
 	chain: C,
 	author: A,
+	finality: F,
+	events: E,
 	system: Y,
 ) -> RpcHandler where
 	Block: BlockT + 'static,
@@ -50,12 +52,16 @@ pub fn rpc_handler<Block: BlockT, ExHash, S, C, A, Y>(
 	S: apis::state::StateApi<Block::Hash, Metadata=Metadata>,
 	C: apis::chain::ChainApi<Block::Hash, Block::Header, NumberFor<Block>, SignedBlock<Block>, Metadata=Metadata>,
 	A: apis::author::AuthorApi<ExHash, Block::Hash, Metadata=Metadata>,
+	F: apis::finality::FinalityApi<Block::Hash, Metadata=Metadata>,
+	E: apis::events::EventsApi<Block::Hash>,
 	Y: apis::system::SystemApi,
 {
 	let mut io = pubsub::PubSubHandler::default();
 	io.extend_with(state.to_delegate());
 	io.extend_with(chain.to_delegate());
 	io.extend_with(author.to_delegate());
+	io.extend_with(finality.to_delegate());
+	io.extend_with(events.to_delegate());
 	io.extend_with(system.to_delegate());
 	io
 }
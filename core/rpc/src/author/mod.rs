@@ -22,11 +22,11 @@ use client::{self, Client};
 use codec::{Encode, Decode};
 use transaction_pool::{
 	txpool::{
-		ChainApi as PoolChainApi,
-		BlockHash,
-		ExHash,
 		IntoPoolError,
-		Pool,
+		PoolBlockHash,
+		PoolExtrinsic,
+		PoolStatus,
+		TransactionPool,
 		watcher::Status,
 	},
 };
@@ -57,6 +57,10 @@ build_rpc_trait! {
 		#[rpc(name = "author_pendingExtrinsics")]
 		fn pending_extrinsics(&self) -> Result<Vec<Bytes>>;
 
+		/// Returns the status of the transaction pool.
+		#[rpc(name = "author_poolStatus")]
+		fn pool_status(&self) -> Result<PoolStatus>;
+
 		#[pubsub(name = "author_extrinsicUpdate")] {
 			/// Submit an extrinsic to watch.
 			#[rpc(name = "author_submitAndWatchExtrinsic")]
@@ -71,20 +75,20 @@ build_rpc_trait! {
 }
 
 /// Authoring API
-pub struct Author<B, E, P, RA> where P: PoolChainApi + Sync + Send + 'static {
+pub struct Author<B, E, P, RA> where P: TransactionPool + 'static {
 	/// Substrate client
-	client: Arc<Client<B, E, <P as PoolChainApi>::Block, RA>>,
+	client: Arc<Client<B, E, <P as TransactionPool>::Block, RA>>,
 	/// Extrinsic pool
-	pool: Arc<Pool<P>>,
+	pool: Arc<P>,
 	/// Subscriptions manager
 	subscriptions: Subscriptions,
 }
 
-impl<B, E, P, RA> Author<B, E, P, RA> where P: PoolChainApi + Sync + Send + 'static {
+impl<B, E, P, RA> Author<B, E, P, RA> where P: TransactionPool + 'static {
 	/// Create new instance of Authoring API.
 	pub fn new(
-		client: Arc<Client<B, E, <P as PoolChainApi>::Block, RA>>,
-		pool: Arc<Pool<P>>,
+		client: Arc<Client<B, E, <P as TransactionPool>::Block, RA>>,
+		pool: Arc<P>,
 		subscriptions: Subscriptions,
 	) -> Self {
 		Author {
@@ -95,17 +99,17 @@ impl<B, E, P, RA> Author<B, E, P, RA> where P: PoolChainApi + Sync + Send + 'sta
 	}
 }
 
-impl<B, E, P, RA> AuthorApi<ExHash<P>, BlockHash<P>> for Author<B, E, P, RA> where
-	B: client::backend::Backend<<P as PoolChainApi>::Block, Blake2Hasher> + Send + Sync + 'static,
-	E: client::CallExecutor<<P as PoolChainApi>::Block, Blake2Hasher> + Send + Sync + 'static,
-	P: PoolChainApi + Sync + Send + 'static,
+impl<B, E, P, RA> AuthorApi<P::Hash, PoolBlockHash<P>> for Author<B, E, P, RA> where
+	B: client::backend::Backend<<P as TransactionPool>::Block, Blake2Hasher> + Send + Sync + 'static,
+	E: client::CallExecutor<<P as TransactionPool>::Block, Blake2Hasher> + Send + Sync + 'static,
+	P: TransactionPool + 'static,
 	P::Block: traits::Block<Hash=H256>,
 	P::Error: 'static,
 	RA: Send + Sync + 'static
 {
 	type Metadata = ::metadata::Metadata;
 
-	fn submit_extrinsic(&self, ext: Bytes) -> Result<ExHash<P>> {
+	fn submit_extrinsic(&self, ext: Bytes) -> Result<P::Hash> {
 		let xt = Decode::decode(&mut &ext[..]).ok_or(error::Error::from(error::ErrorKind::BadFormat))?;
 		let best_block_hash = self.client.info()?.chain.best_hash;
 		self.pool
@@ -120,10 +124,14 @@ impl<B, E, P, RA> AuthorApi<ExHash<P>, BlockHash<P>> for Author<B, E, P, RA> whe
 		Ok(self.pool.ready().map(|tx| tx.data.encode().into()).collect())
 	}
 
-	fn watch_extrinsic(&self, _metadata: Self::Metadata, subscriber: pubsub::Subscriber<Status<ExHash<P>, BlockHash<P>>>, xt: Bytes) {
+	fn pool_status(&self) -> Result<PoolStatus> {
+		Ok(self.pool.pool_status())
+	}
+
+	fn watch_extrinsic(&self, _metadata: Self::Metadata, subscriber: pubsub::Subscriber<Status<P::Hash, PoolBlockHash<P>>>, xt: Bytes) {
 		let submit = || -> Result<_> {
 			let best_block_hash = self.client.info()?.chain.best_hash;
-			let dxt = <<P as PoolChainApi>::Block as traits::Block>::Extrinsic::decode(&mut &xt[..]).ok_or(error::Error::from(error::ErrorKind::BadFormat))?;
+			let dxt = <PoolExtrinsic<P> as Decode>::decode(&mut &xt[..]).ok_or(error::Error::from(error::ErrorKind::BadFormat))?;
 			self.pool
 				.submit_and_watch(&generic::BlockId::hash(best_block_hash), dxt)
 				.map_err(|e| e.into_pool_error()
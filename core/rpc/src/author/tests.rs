@@ -137,3 +137,21 @@ fn should_return_pending_extrinsics() {
 		Ok(ref expected) if *expected == vec![Bytes(ex.encode())]
 	);
 }
+
+#[test]
+fn should_return_pool_status() {
+	let runtime = runtime::Runtime::new().unwrap();
+	let client = Arc::new(test_client::new());
+	let pool = Arc::new(Pool::new(Default::default(), ChainApi::new(client.clone())));
+	let p = Author {
+		client,
+		pool: pool.clone(),
+		subscriptions: Subscriptions::new(runtime.executor()),
+	};
+	let ex = uxt(Keyring::Alice, 0);
+	AuthorApi::submit_extrinsic(&p, ex.encode().into()).unwrap();
+	assert_matches!(
+		p.pool_status(),
+		Ok(ref status) if status.ready == 1 && status.ready_bytes == ex.encode().len() && status.validations == 1
+	);
+}
@@ -32,10 +32,17 @@ error_chain! {
 	}
 }
 
+const ERROR: i64 = 3000;
+
 impl From<Error> for rpc::Error {
 	fn from(e: Error) -> Self {
 		match e {
 			Error(ErrorKind::Unimplemented, _) => errors::unimplemented(),
+			Error(ErrorKind::Client(client::error::ErrorKind::HeaderPruned(number, cht_root)), _) => rpc::Error {
+				code: rpc::ErrorCode::ServerError(ERROR + 1),
+				message: format!("Header of block #{} has been pruned.", number),
+				data: Some(cht_root.into()),
+			},
 			e => errors::internal(e),
 		}
 	}
@@ -28,6 +28,7 @@ use runtime_primitives::generic::{BlockId, SignedBlock};
 use runtime_primitives::traits::{Block as BlockT, Header, NumberFor};
 use runtime_version::RuntimeVersion;
 use primitives::{Blake2Hasher, storage};
+use codec::Decode;
 
 use subscriptions::Subscriptions;
 
@@ -37,12 +38,25 @@ mod tests;
 
 use self::error::Result;
 
+/// A header imported by the node, along with whether its block became the new best block.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportedHeader<Header> {
+	/// The imported header.
+	pub header: Header,
+	/// Whether the block became the new best block.
+	pub is_new_best: bool,
+}
+
 build_rpc_trait! {
 	/// Substrate blockchain API
 	pub trait ChainApi<Hash, Header, Number, SignedBlock> {
 		type Metadata;
 
 		/// Get header of a relay chain block.
+		///
+		/// Light clients don't fetch the headers they have pruned from the network. An error with
+		/// the root of the CHT proving the header is returned instead.
 		#[rpc(name = "chain_getHeader")]
 		fn header(&self, Trailing<Hash>) -> Result<Option<Header>>;
 
@@ -64,6 +78,12 @@ build_rpc_trait! {
 		#[rpc(name = "chain_getRuntimeVersion")]
 		fn runtime_version(&self, Trailing<Hash>) -> Result<RuntimeVersion>;
 
+		/// Get whether calls are paused at the given block, by default the best one, i.e.
+		/// whether the chain is in maintenance mode, only accepting the calls the runtime
+		/// keeps enabled, e.g. those of governance.
+		#[rpc(name = "chain_getCallsPaused")]
+		fn calls_paused(&self, Trailing<Hash>) -> Result<bool>;
+
 		#[pubsub(name = "chain_newHead")] {
 			/// New head subscription
 			#[rpc(name = "chain_subscribeNewHead", alias = ["subscribe_newHead", ])]
@@ -74,6 +94,17 @@ build_rpc_trait! {
 			fn unsubscribe_new_head(&self, SubscriptionId) -> RpcResult<bool>;
 		}
 
+		#[pubsub(name = "chain_allHead")] {
+			/// Subscription to every imported header, including the ones of blocks that did
+			/// not become the new best block.
+			#[rpc(name = "chain_subscribeAllHeads")]
+			fn subscribe_all_heads(&self, Self::Metadata, pubsub::Subscriber<ImportedHeader<Header>>);
+
+			/// Unsubscribe from all heads subscription.
+			#[rpc(name = "chain_unsubscribeAllHeads")]
+			fn unsubscribe_all_heads(&self, SubscriptionId) -> RpcResult<bool>;
+		}
+
 		#[pubsub(name = "chain_finalisedHead")] {
 			/// New head subscription
 			#[rpc(name = "chain_subscribeFinalisedHeads")]
@@ -200,6 +231,13 @@ impl<B, E, Block, RA> ChainApi<Block::Hash, Block::Header, NumberFor<Block>, Sig
 		Ok(self.client.runtime_version_at(&BlockId::Hash(at))?)
 	}
 
+	fn calls_paused(&self, at: Trailing<Block::Hash>) -> Result<bool> {
+		let at = self.unwrap_or_best(at)?;
+		let paused = self.client.storage(&BlockId::Hash(at), &::helpers::system_storage_key(b"CallsPaused"))?;
+		// the flag is not stored until calls are first paused.
+		Ok(paused.and_then(|paused| Decode::decode(&mut &paused.0[..])).unwrap_or(false))
+	}
+
 	fn subscribe_new_head(&self, _metadata: Self::Metadata, subscriber: pubsub::Subscriber<Block::Header>) {
 		self.subscribe_headers(
 			subscriber,
@@ -214,6 +252,31 @@ impl<B, E, Block, RA> ChainApi<Block::Hash, Block::Header, NumberFor<Block>, Sig
 		Ok(self.subscriptions.cancel(id))
 	}
 
+	fn subscribe_all_heads(
+		&self,
+		_metadata: Self::Metadata,
+		subscriber: pubsub::Subscriber<ImportedHeader<Block::Header>>,
+	) {
+		let stream = self.client.import_notification_stream()
+			.map(|notification| Ok(ImportedHeader {
+				header: notification.header,
+				is_new_best: notification.is_new_best,
+			}))
+			.map_err(|e| warn!("Block notification stream error: {:?}", e));
+
+		self.subscriptions.add(subscriber, |sink| {
+			sink
+				.sink_map_err(|e| warn!("Error sending notifications: {:?}", e))
+				.send_all(stream)
+				// we ignore the resulting Stream (if the first stream is over we are unsubscribed)
+				.map(|_| ())
+		});
+	}
+
+	fn unsubscribe_all_heads(&self, id: SubscriptionId) -> RpcResult<bool> {
+		Ok(self.subscriptions.cancel(id))
+	}
+
 	fn subscribe_finalised_heads(&self, _meta: Self::Metadata, subscriber: pubsub::Subscriber<Block::Header>) {
 		self.subscribe_headers(
 			subscriber,
@@ -16,8 +16,9 @@
 
 use super::*;
 use jsonrpc_macros::pubsub;
-use test_client::{self, TestClient};
-use test_client::runtime::{Block, Header};
+use test_client::{self, TestClient, BlockBuilderExt};
+use test_client::keyring::Keyring;
+use test_client::runtime::{Block, Header, Transfer};
 use consensus::BlockOrigin;
 
 #[test]
@@ -178,7 +179,7 @@ fn should_return_finalised_hash() {
 	);
 
 	// finalise
-	client.client.finalize_block(BlockId::number(1), true).unwrap();
+	client.client.finalize_block(BlockId::number(1), None, true).unwrap();
 	assert_matches!(
 		client.finalised_head(),
 		Ok(ref x) if x == &client.client.block_hash(1).unwrap().unwrap()
@@ -216,6 +217,46 @@ fn should_notify_about_latest_block() {
 	assert_eq!(core.block_on(next.into_future()).unwrap().0, None);
 }
 
+#[test]
+fn should_notify_about_all_imported_blocks() {
+	let mut core = ::tokio::runtime::Runtime::new().unwrap();
+	let remote = core.executor();
+	let (subscriber, id, transport) = pubsub::Subscriber::new_test("test");
+
+	{
+		let api = Chain {
+			client: Arc::new(test_client::new()),
+			subscriptions: Subscriptions::new(remote),
+		};
+
+		api.subscribe_all_heads(Default::default(), subscriber);
+
+		// assert id assigned
+		assert_eq!(core.block_on(id), Ok(Ok(SubscriptionId::Number(1))));
+
+		let genesis = BlockId::Number(0);
+		let best = api.client.new_block_at(&genesis).unwrap().bake().unwrap();
+		api.client.justify_and_import(BlockOrigin::Own, best).unwrap();
+
+		// a competing block of the same height does not become the new best block.
+		let mut builder = api.client.new_block_at(&genesis).unwrap();
+		builder.push_transfer(Transfer {
+			from: Keyring::Alice.to_raw_public().into(),
+			to: Keyring::Ferdie.to_raw_public().into(),
+			amount: 42,
+			nonce: 0,
+		}).unwrap();
+		api.client.justify_and_import(BlockOrigin::Own, builder.bake().unwrap()).unwrap();
+	}
+
+	let (notification, next) = core.block_on(transport.into_future()).unwrap();
+	assert!(notification.unwrap().contains(r#""isNewBest":true"#));
+	let (notification, next) = core.block_on(next.into_future()).unwrap();
+	assert!(notification.unwrap().contains(r#""isNewBest":false"#));
+	// no more notifications on this channel
+	assert_eq!(core.block_on(next.into_future()).unwrap().0, None);
+}
+
 #[test]
 fn should_notify_about_finalised_block() {
 	let mut core = ::tokio::runtime::Runtime::new().unwrap();
@@ -235,7 +276,7 @@ fn should_notify_about_finalised_block() {
 
 		let builder = api.client.new_block().unwrap();
 		api.client.justify_and_import(BlockOrigin::Own, builder.bake().unwrap()).unwrap();
-		api.client.finalize_block(BlockId::number(1), true).unwrap();
+		api.client.finalize_block(BlockId::number(1), None, true).unwrap();
 	}
 
 	// assert initial head sent.
@@ -270,3 +311,26 @@ fn should_return_runtime_version() {
 		}
 	);
 }
+
+#[test]
+fn pruned_header_error_carries_cht_root() {
+	let pruned: client::error::Error = client::error::ErrorKind::HeaderPruned("5".into(), "0x01".into()).into();
+	let error: ::rpc::Error = error::Error::from(pruned).into();
+
+	assert_eq!(error.code, ::rpc::ErrorCode::ServerError(3001));
+	assert_eq!(error.data, Some("0x01".into()));
+}
+
+#[test]
+fn should_return_calls_paused() {
+	let core = ::tokio::runtime::Runtime::new().unwrap();
+	let remote = core.executor();
+
+	let client = Chain {
+		client: Arc::new(test_client::new()),
+		subscriptions: Subscriptions::new(remote),
+	};
+
+	// the test runtime never pauses calls.
+	assert_matches!(client.calls_paused(None.into()), Ok(false));
+}
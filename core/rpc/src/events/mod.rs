@@ -0,0 +1,395 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+
+//! Substrate runtime events API.
+//!
+//! Lets clients find the runtime events concerning a module or an account without fetching the
+//! events of every block. The index is built by the node from the blocks it imports, forks
+//! included, and kept in memory: it only covers the most recent blocks imported since the node
+//! started, and forgets the blocks retracted by finality.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+
+use client::{self, Client, CallExecutor, BlockchainEvents};
+use codec::Decode;
+use jsonrpc_macros::Trailing;
+use parking_lot::{Mutex, RwLock};
+use primitives::{Blake2Hasher, Bytes, OpaqueMetadata};
+use primitives::storage::StorageKey;
+use rpc::futures::{Future, Stream};
+use runtime_primitives::generic::BlockId;
+use runtime_primitives::traits::{As, Block as BlockT, Header as HeaderT};
+use runtime_version::RuntimeVersion;
+
+mod error;
+#[cfg(test)]
+mod tests;
+
+use self::error::Result;
+
+/// Maximal number of events kept in the index. The events of the oldest blocks are dropped
+/// past it.
+pub const MAX_INDEXED_EVENTS: usize = 1_000_000;
+
+/// Maximal number of events returned by a single query.
+pub const MAX_EVENTS_PER_PAGE: usize = 256;
+
+/// What an event is indexed by.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventKeys {
+	/// Name of the module which deposited the event, as in the runtime metadata.
+	pub module: String,
+	/// The encoded accounts the event concerns.
+	pub accounts: Vec<Vec<u8>>,
+}
+
+/// Decodes the events deposited in a block, which only the runtime knows the types of.
+pub trait EventDecoder: Send + Sync {
+	/// Decode the encoded `System::events()` of a block into the keys of each event, in order,
+	/// given the encoded metadata of the runtime of the block. `None` if they can't be decoded.
+	fn decode(&self, metadata: &[u8], events: &[u8]) -> Option<Vec<EventKeys>>;
+}
+
+impl<F> EventDecoder for F where F: Fn(&[u8], &[u8]) -> Option<Vec<EventKeys>> + Send + Sync {
+	fn decode(&self, metadata: &[u8], events: &[u8]) -> Option<Vec<EventKeys>> {
+		(self)(metadata, events)
+	}
+}
+
+/// An event of a block.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventRef<Hash> {
+	/// Hash of the block.
+	pub block: Hash,
+	/// Index of the event in the events of the block.
+	pub index: u32,
+}
+
+/// A page of the indexed events, in import order.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventsPage<Hash> {
+	/// The events.
+	pub events: Vec<EventRef<Hash>>,
+	/// The cursor to query the next page from, if there are more events.
+	pub next: Option<u64>,
+}
+
+/// The events of a key, each with its position in the whole index.
+type KeyEvents<Hash> = VecDeque<(u64, EventRef<Hash>)>;
+
+/// An indexed block, with the keys its events are indexed by.
+struct IndexedBlock<Hash> {
+	hash: Hash,
+	number: u64,
+	finalized: bool,
+	events: usize,
+	modules: Vec<String>,
+	accounts: Vec<Vec<u8>>,
+}
+
+struct Index<Hash> {
+	by_module: HashMap<String, KeyEvents<Hash>>,
+	by_account: HashMap<Vec<u8>, KeyEvents<Hash>>,
+	/// The indexed blocks, in import order.
+	blocks: VecDeque<IndexedBlock<Hash>>,
+	/// Number of events in the index.
+	events: usize,
+	/// Position of the next indexed event.
+	next: u64,
+}
+
+/// Remove the events of the given block from the events of a key, dropping the key when it has
+/// none left.
+fn remove_events<K, Hash>(map: &mut HashMap<K, KeyEvents<Hash>>, key: &K, block: &Hash) where
+	K: ::std::hash::Hash + Eq,
+	Hash: PartialEq,
+{
+	let empty = match map.get_mut(key) {
+		Some(events) => {
+			events.retain(|&(_, ref event)| &event.block != block);
+			events.is_empty()
+		},
+		None => false,
+	};
+	if empty {
+		map.remove(key);
+	}
+}
+
+impl<Hash: PartialEq> Index<Hash> {
+	fn remove(&mut self, block: IndexedBlock<Hash>) {
+		for module in &block.modules {
+			remove_events(&mut self.by_module, module, &block.hash);
+		}
+		for account in &block.accounts {
+			remove_events(&mut self.by_account, account, &block.hash);
+		}
+		self.events -= block.events;
+	}
+}
+
+fn page<Hash: Clone>(events: Option<&KeyEvents<Hash>>, from: u64) -> EventsPage<Hash> {
+	let events = match events {
+		Some(events) => events,
+		None => return EventsPage { events: Vec::new(), next: None },
+	};
+	let page: Vec<_> = events.iter()
+		.skip_while(|&&(position, _)| position < from)
+		.take(MAX_EVENTS_PER_PAGE + 1)
+		.collect();
+	let next = page.get(MAX_EVENTS_PER_PAGE).map(|&&(position, _)| position);
+	EventsPage {
+		events: page.into_iter().take(MAX_EVENTS_PER_PAGE).map(|&(_, ref event)| event.clone()).collect(),
+		next,
+	}
+}
+
+/// In-memory index of the events of the blocks imported by the node.
+pub struct EventIndex<Hash> {
+	decoder: Arc<EventDecoder>,
+	max_events: usize,
+	index: RwLock<Index<Hash>>,
+}
+
+impl<Hash: Clone + PartialEq> EventIndex<Hash> {
+	/// Create an empty index, decoding events with the given decoder.
+	pub fn new(decoder: Arc<EventDecoder>) -> Self {
+		Self::with_max_events(decoder, MAX_INDEXED_EVENTS)
+	}
+
+	/// Create an empty index keeping at most `max_events` events.
+	pub fn with_max_events(decoder: Arc<EventDecoder>, max_events: usize) -> Self {
+		EventIndex {
+			decoder,
+			max_events,
+			index: RwLock::new(Index {
+				by_module: HashMap::new(),
+				by_account: HashMap::new(),
+				blocks: VecDeque::new(),
+				events: 0,
+				next: 0,
+			}),
+		}
+	}
+
+	/// Index the encoded `System::events()` of the given block, decoding them with the encoded
+	/// metadata of the runtime of the block.
+	pub fn index_events(&self, block: Hash, number: u64, metadata: &[u8], events: &[u8]) {
+		let keys = match self.decoder.decode(metadata, events) {
+			Some(keys) => keys,
+			None => {
+				warn!("Failed to decode the events of an imported block, not indexing them");
+				return;
+			}
+		};
+
+		let mut index = self.index.write();
+		let mut indexed = IndexedBlock {
+			hash: block.clone(),
+			number,
+			finalized: false,
+			events: keys.len(),
+			modules: Vec::new(),
+			accounts: Vec::new(),
+		};
+		for (i, mut keys) in keys.into_iter().enumerate() {
+			let position = index.next;
+			index.next += 1;
+			let event = EventRef { block: block.clone(), index: i as u32 };
+			keys.accounts.sort();
+			keys.accounts.dedup();
+			for account in keys.accounts {
+				index.by_account.entry(account.clone()).or_insert_with(VecDeque::new).push_back((position, event.clone()));
+				indexed.accounts.push(account);
+			}
+			index.by_module.entry(keys.module.clone()).or_insert_with(VecDeque::new).push_back((position, event));
+			indexed.modules.push(keys.module);
+		}
+		indexed.modules.sort();
+		indexed.modules.dedup();
+		indexed.accounts.sort();
+		indexed.accounts.dedup();
+		index.events += indexed.events;
+		index.blocks.push_back(indexed);
+
+		while index.events > self.max_events {
+			match index.blocks.pop_front() {
+				Some(oldest) => index.remove(oldest),
+				None => break,
+			}
+		}
+	}
+
+	/// Forget the events of the blocks retracted by the finalization of the block with the given
+	/// number: the blocks up to it which `is_canonical` rejects.
+	pub fn finalize<F>(&self, number: u64, is_canonical: F) where F: Fn(u64, &Hash) -> bool {
+		let mut index = self.index.write();
+		let blocks = ::std::mem::replace(&mut index.blocks, VecDeque::new());
+		for mut block in blocks {
+			if block.finalized || block.number > number {
+				index.blocks.push_back(block);
+			} else if is_canonical(block.number, &block.hash) {
+				block.finalized = true;
+				index.blocks.push_back(block);
+			} else {
+				index.remove(block);
+			}
+		}
+	}
+
+	/// The indexed events deposited by the given module, in import order, from the given cursor.
+	pub fn by_module(&self, module: &str, from: u64) -> EventsPage<Hash> {
+		page(self.index.read().by_module.get(module), from)
+	}
+
+	/// The indexed events concerning the given encoded account, in import order, from the given
+	/// cursor.
+	pub fn by_account(&self, account: &[u8], from: u64) -> EventsPage<Hash> {
+		page(self.index.read().by_account.get(account), from)
+	}
+}
+
+/// The storage key of the events deposited in a block, `System::events()`.
+pub fn events_key() -> StorageKey {
+	::helpers::system_storage_key(b"Events")
+}
+
+/// The encoded metadata of the runtime of the given block, fetched once per runtime version.
+fn metadata_at<B, E, Block, RA>(
+	client: &Client<B, E, Block, RA>,
+	cache: &Mutex<Option<(RuntimeVersion, Vec<u8>)>>,
+	at: &BlockId<Block>,
+) -> client::error::Result<Vec<u8>> where
+	Block: BlockT,
+	B: client::backend::Backend<Block, Blake2Hasher>,
+	E: CallExecutor<Block, Blake2Hasher>,
+{
+	let version = client.runtime_version_at(at)?;
+	let mut cache = cache.lock();
+	if let Some((ref cached, ref metadata)) = *cache {
+		if cached == &version {
+			return Ok(metadata.clone());
+		}
+	}
+
+	let encoded = client.executor().call(at, "Metadata_metadata", &[])?.return_data;
+	let metadata = OpaqueMetadata::decode(&mut &encoded[..])
+		.map(|metadata| Bytes::from(metadata).0)
+		.ok_or_else(|| client::error::ErrorKind::CallResultDecode("Metadata_metadata"))?;
+	*cache = Some((version, metadata.clone()));
+	Ok(metadata)
+}
+
+/// Index the events of every block imported by the client from now on, and forget those of the
+/// blocks retracted by finality.
+pub fn index_imported_blocks<B, E, Block, RA>(
+	client: Arc<Client<B, E, Block, RA>>,
+	index: Arc<EventIndex<Block::Hash>>,
+) -> Box<Future<Item=(), Error=()> + Send> where
+	Block: BlockT + 'static,
+	B: client::backend::Backend<Block, Blake2Hasher> + Send + Sync + 'static,
+	E: client::CallExecutor<Block, Blake2Hasher> + Send + Sync + 'static,
+	RA: Send + Sync + 'static,
+{
+	let key = events_key();
+	let metadata = Mutex::new(None);
+	let imported = {
+		let client = client.clone();
+		let index = index.clone();
+		client.import_notification_stream().for_each(move |notification| {
+			let at = BlockId::hash(notification.hash);
+			let events = client.storage(&at, &key)
+				.and_then(|events| match events {
+					Some(events) => metadata_at(&*client, &metadata, &at).map(|metadata| Some((metadata, events))),
+					None => Ok(None),
+				});
+			match events {
+				Ok(Some((metadata, events))) => index.index_events(
+					notification.hash,
+					notification.header.number().as_(),
+					&metadata,
+					&events.0,
+				),
+				Ok(None) => (),
+				Err(e) => warn!("Failed to read the events of block {:?}: {:?}", notification.hash, e),
+			}
+			Ok(())
+		})
+	};
+
+	let finalized = client.finality_notification_stream().for_each(move |notification| {
+		index.finalize(notification.header.number().as_(), |number, hash| {
+			match client.block_hash(As::sa(number)) {
+				Ok(canonical) => canonical.as_ref() == Some(hash),
+				// keep the events if we can't tell.
+				Err(_) => true,
+			}
+		});
+		Ok(())
+	});
+
+	Box::new(imported.join(finalized).map(|_| ()))
+}
+
+build_rpc_trait! {
+	/// Substrate runtime events API
+	pub trait EventsApi<Hash> {
+		/// Get a page of the events deposited by the given module in the blocks imported by the
+		/// node, from the given cursor or the oldest indexed event.
+		#[rpc(name = "events_byModule")]
+		fn events_by_module(&self, String, Trailing<u64>) -> Result<EventsPage<Hash>>;
+
+		/// Get a page of the events concerning the given encoded account in the blocks imported by
+		/// the node, from the given cursor or the oldest indexed event.
+		#[rpc(name = "events_byAccount")]
+		fn events_by_account(&self, Bytes, Trailing<u64>) -> Result<EventsPage<Hash>>;
+	}
+}
+
+/// Runtime events API.
+pub struct Events<Hash> {
+	/// The index of the events, if the node indexes them.
+	index: Option<Arc<EventIndex<Hash>>>,
+}
+
+impl<Hash> Events<Hash> {
+	/// Create new Events API RPC handler.
+	pub fn new(index: Option<Arc<EventIndex<Hash>>>) -> Self {
+		Events { index }
+	}
+
+	fn index(&self) -> Result<&EventIndex<Hash>> {
+		self.index.as_ref().map(|index| &**index).ok_or_else(|| self::error::ErrorKind::Unimplemented.into())
+	}
+}
+
+impl<Hash> EventsApi<Hash> for Events<Hash> where
+	Hash: Clone + PartialEq + Send + Sync + 'static,
+{
+	fn events_by_module(&self, module: String, from: Trailing<u64>) -> Result<EventsPage<Hash>> {
+		let from = Option::from(from).unwrap_or(0);
+		Ok(self.index()?.by_module(&module, from))
+	}
+
+	fn events_by_account(&self, account: Bytes, from: Trailing<u64>) -> Result<EventsPage<Hash>> {
+		let from = Option::from(from).unwrap_or(0);
+		Ok(self.index()?.by_account(&account.0, from))
+	}
+}
@@ -0,0 +1,142 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+
+use super::*;
+use primitives::H256;
+
+/// Decodes each byte as an event of module `even` or `odd`, suffixed by the metadata, concerning
+/// the account made of that byte, twice. Events starting with `0xff` can't be decoded.
+fn decoder() -> Arc<EventDecoder> {
+	Arc::new(|metadata: &[u8], events: &[u8]| -> Option<Vec<EventKeys>> {
+		if events.first() == Some(&0xff) {
+			return None;
+		}
+		let suffix = String::from_utf8(metadata.to_vec()).ok()?;
+		Some(events.iter().map(|event| EventKeys {
+			module: format!("{}{}", if event % 2 == 0 { "even" } else { "odd" }, suffix),
+			accounts: vec![vec![*event], vec![*event]],
+		}).collect())
+	})
+}
+
+fn event(block: u8, index: u32) -> EventRef<H256> {
+	EventRef { block: [block; 32].into(), index }
+}
+
+fn events(events: Vec<EventRef<H256>>) -> EventsPage<H256> {
+	EventsPage { events, next: None }
+}
+
+#[test]
+fn should_fail_without_index() {
+	let api = Events::<H256>::new(None);
+
+	assert_matches!(api.events_by_module("even".into(), None.into()), Err(_));
+	assert_matches!(api.events_by_account(vec![1].into(), None.into()), Err(_));
+}
+
+#[test]
+fn should_return_indexed_events() {
+	let index = Arc::new(EventIndex::new(decoder()));
+	index.index_events([1; 32].into(), 1, b"", &[1, 2, 3]);
+	index.index_events([2; 32].into(), 2, b"", &[3]);
+	let api = Events::new(Some(index));
+
+	assert_eq!(
+		api.events_by_module("odd".into(), None.into()).unwrap(),
+		events(vec![event(1, 0), event(1, 2), event(2, 0)])
+	);
+	assert_eq!(api.events_by_module("even".into(), None.into()).unwrap(), events(vec![event(1, 1)]));
+	assert_eq!(api.events_by_module("none".into(), None.into()).unwrap(), events(vec![]));
+	assert_eq!(api.events_by_account(vec![3].into(), None.into()).unwrap(), events(vec![event(1, 2), event(2, 0)]));
+	assert_eq!(api.events_by_account(vec![4].into(), None.into()).unwrap(), events(vec![]));
+}
+
+#[test]
+fn should_decode_with_the_metadata_of_the_block() {
+	let index = EventIndex::new(decoder());
+	index.index_events([1; 32].into(), 1, b"", &[1]);
+	index.index_events([2; 32].into(), 2, b"2", &[1]);
+
+	assert_eq!(index.by_module("odd", 0), events(vec![event(1, 0)]));
+	assert_eq!(index.by_module("odd2", 0), events(vec![event(2, 0)]));
+}
+
+#[test]
+fn should_skip_undecodable_events() {
+	let index = EventIndex::new(decoder());
+	index.index_events([1; 32].into(), 1, b"", &[0xff, 1]);
+	index.index_events([2; 32].into(), 2, &[0xff], &[1]);
+
+	assert_eq!(index.by_module("odd", 0), events(vec![]));
+	assert_eq!(index.by_account(&[1], 0), events(vec![]));
+}
+
+#[test]
+fn should_paginate_events() {
+	let index = Arc::new(EventIndex::new(decoder()));
+	let block = vec![1; MAX_EVENTS_PER_PAGE + 1];
+	index.index_events([1; 32].into(), 1, b"", &block);
+	let api = Events::new(Some(index));
+
+	let first = api.events_by_module("odd".into(), None.into()).unwrap();
+	assert_eq!(first.events.len(), MAX_EVENTS_PER_PAGE);
+	assert_eq!(first.next, Some(MAX_EVENTS_PER_PAGE as u64));
+
+	let second = api.events_by_module("odd".into(), first.next.into()).unwrap();
+	assert_eq!(second, events(vec![event(1, MAX_EVENTS_PER_PAGE as u32)]));
+}
+
+#[test]
+fn should_forget_retracted_blocks_on_finality() {
+	let index = EventIndex::new(decoder());
+	index.index_events([1; 32].into(), 1, b"", &[1]);
+	index.index_events([2; 32].into(), 1, b"", &[1, 3]);
+	index.index_events([3; 32].into(), 2, b"", &[1]);
+
+	// block 2 is retracted by the finalization of block 1.
+	index.finalize(1, |_, hash| hash != &[2; 32].into());
+
+	assert_eq!(index.by_module("odd", 0), events(vec![event(1, 0), event(3, 0)]));
+	assert_eq!(index.by_account(&[3], 0), events(vec![]));
+
+	// blocks above the finalized one are kept until they are finalized.
+	index.finalize(1, |_, _| false);
+	assert_eq!(index.by_module("odd", 0), events(vec![event(1, 0), event(3, 0)]));
+	index.finalize(2, |_, _| false);
+	assert_eq!(index.by_module("odd", 0), events(vec![event(1, 0)]));
+}
+
+#[test]
+fn should_drop_the_oldest_blocks_past_the_limit() {
+	let index = EventIndex::with_max_events(decoder(), 3);
+	index.index_events([1; 32].into(), 1, b"", &[1, 2]);
+	index.index_events([2; 32].into(), 2, b"", &[1]);
+	index.index_events([3; 32].into(), 3, b"", &[1]);
+
+	assert_eq!(index.by_module("odd", 0), events(vec![event(2, 0), event(3, 0)]));
+	assert_eq!(index.by_module("even", 0), events(vec![]));
+	assert_eq!(index.by_account(&[2], 0), events(vec![]));
+}
+
+#[test]
+fn should_serialize_event_refs() {
+	assert_eq!(
+		serde_json::to_string(&event(1, 2)).unwrap(),
+		format!("{{\"block\":\"0x{}\",\"index\":2}}", "01".repeat(32)),
+	);
+}
@@ -0,0 +1,193 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Substrate finality proof API.
+//!
+//! Lets external verifiers following the finality gadget of the chain, e.g. bridge relayers,
+//! fetch proofs of finality of the blocks after the last one they know final.
+
+use std::sync::Arc;
+
+use client::{self, Client, BlockchainEvents};
+use jsonrpc_macros::pubsub;
+use jsonrpc_pubsub::SubscriptionId;
+use rpc::Result as RpcResult;
+use rpc::futures::{stream, Future, Sink, Stream};
+use primitives::{Blake2Hasher, Bytes, H256};
+use runtime_primitives::traits::Block as BlockT;
+
+use subscriptions::Subscriptions;
+
+mod error;
+#[cfg(test)]
+mod tests;
+
+use self::error::Result;
+
+/// The maximum number of proofs sent for a single finality notification. Subscribers get the
+/// rest of the range along with the next notifications.
+const MAX_PROOFS_PER_NOTIFICATION: usize = 16;
+
+/// Proves the finality of blocks to verifiers that know an earlier finalized block.
+pub trait FinalityProofProvider<Block: BlockT>: Send + Sync {
+	/// Prove the finality of the blocks after `begin` up to `end`. Returns the hash of the
+	/// block proven final, which may come before `end` if the verifier needs to learn something
+	/// first, e.g. a new authority set, along with the encoded proof. `None` if no block can be
+	/// proven final yet.
+	fn prove_finality(
+		&self,
+		begin: Block::Hash,
+		end: Block::Hash,
+	) -> client::error::Result<Option<(Block::Hash, Vec<u8>)>>;
+}
+
+impl<Block: BlockT, F> FinalityProofProvider<Block> for F where
+	F: Fn(Block::Hash, Block::Hash) -> client::error::Result<Option<(Block::Hash, Vec<u8>)>> + Send + Sync,
+{
+	fn prove_finality(
+		&self,
+		begin: Block::Hash,
+		end: Block::Hash,
+	) -> client::error::Result<Option<(Block::Hash, Vec<u8>)>> {
+		(self)(begin, end)
+	}
+}
+
+build_rpc_trait! {
+	/// Substrate finality proof API
+	pub trait FinalityApi<Hash> {
+		type Metadata;
+
+		/// Prove the finality of the blocks after the first one up to the second one, both
+		/// being finalized. The proof may end before the second block, e.g. when the authority
+		/// set changes in between, in which case the rest has to be asked for separately.
+		#[rpc(name = "grandpa_proveFinality")]
+		fn prove_finality(&self, Hash, Hash) -> Result<Option<Bytes>>;
+
+		#[pubsub(name = "grandpa_finalityProof")] {
+			/// Subscribe to proofs of finality of the blocks after the given one, each proof
+			/// following on the block proven final by the previous one.
+			#[rpc(name = "grandpa_subscribeFinalityProofs")]
+			fn subscribe_finality_proofs(&self, Self::Metadata, pubsub::Subscriber<Bytes>, Hash);
+
+			/// Unsubscribe from finality proofs subscription.
+			#[rpc(name = "grandpa_unsubscribeFinalityProofs")]
+			fn unsubscribe_finality_proofs(&self, SubscriptionId) -> RpcResult<bool>;
+		}
+	}
+}
+
+/// Finality proof API with subscriptions support.
+pub struct Finality<B, E, Block: BlockT, RA> {
+	/// Substrate client.
+	client: Arc<Client<B, E, Block, RA>>,
+	/// The provider of the proofs, if the chain has a finality gadget.
+	provider: Option<Arc<FinalityProofProvider<Block>>>,
+	/// Current subscriptions.
+	subscriptions: Subscriptions,
+}
+
+impl<B, E, Block: BlockT, RA> Finality<B, E, Block, RA> {
+	/// Create new Finality API RPC handler.
+	pub fn new(
+		client: Arc<Client<B, E, Block, RA>>,
+		provider: Option<Arc<FinalityProofProvider<Block>>>,
+		subscriptions: Subscriptions,
+	) -> Self {
+		Self {
+			client,
+			provider,
+			subscriptions,
+		}
+	}
+
+	fn provider(&self) -> Result<Arc<FinalityProofProvider<Block>>> {
+		self.provider.clone().ok_or_else(|| self::error::ErrorKind::Unimplemented.into())
+	}
+}
+
+/// Prove the finality of the blocks after `begin` up to `end`, as many proofs as needed up to
+/// `MAX_PROOFS_PER_NOTIFICATION`. `begin` is moved to the last block proven final.
+fn prove_range<Block: BlockT>(
+	provider: &FinalityProofProvider<Block>,
+	begin: &mut Block::Hash,
+	end: Block::Hash,
+) -> Vec<Bytes> {
+	let mut proofs = Vec::new();
+	while *begin != end && proofs.len() < MAX_PROOFS_PER_NOTIFICATION {
+		match provider.prove_finality(*begin, end) {
+			Ok(Some((proven, proof))) => {
+				*begin = proven;
+				proofs.push(proof.into());
+			},
+			Ok(None) => break,
+			Err(e) => {
+				warn!("Failed to prove finality of block {:?}: {:?}", end, e);
+				break;
+			},
+		}
+	}
+	proofs
+}
+
+impl<B, E, Block, RA> FinalityApi<Block::Hash> for Finality<B, E, Block, RA> where
+	Block: BlockT<Hash=H256> + 'static,
+	B: client::backend::Backend<Block, Blake2Hasher> + Send + Sync + 'static,
+	E: client::CallExecutor<Block, Blake2Hasher> + Send + Sync + 'static,
+	RA: Send + Sync + 'static
+{
+	type Metadata = ::metadata::Metadata;
+
+	fn prove_finality(&self, begin: Block::Hash, end: Block::Hash) -> Result<Option<Bytes>> {
+		Ok(self.provider()?.prove_finality(begin, end)?.map(|(_, proof)| proof.into()))
+	}
+
+	fn subscribe_finality_proofs(
+		&self,
+		_metadata: Self::Metadata,
+		subscriber: pubsub::Subscriber<Bytes>,
+		begin: Block::Hash,
+	) {
+		let provider = match self.provider() {
+			Ok(provider) => provider,
+			Err(err) => {
+				let _ = subscriber.reject(err.into());
+				return;
+			}
+		};
+
+		let mut last_proven = begin;
+		let stream = self.client.finality_notification_stream()
+			.map(move |notification| stream::iter_ok::<_, ()>(
+				prove_range(&*provider, &mut last_proven, notification.hash)
+			))
+			.flatten()
+			.map(|proof| Ok(proof))
+			.map_err(|e| warn!("Finality notification stream error: {:?}", e));
+
+		self.subscriptions.add(subscriber, |sink| {
+			sink
+				.sink_map_err(|e| warn!("Error sending notifications: {:?}", e))
+				.send_all(stream)
+				// we ignore the resulting Stream (if the first stream is over we are unsubscribed)
+				.map(|_| ())
+		});
+	}
+
+	fn unsubscribe_finality_proofs(&self, id: SubscriptionId) -> RpcResult<bool> {
+		Ok(self.subscriptions.cancel(id))
+	}
+}
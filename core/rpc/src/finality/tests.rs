@@ -0,0 +1,104 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+use super::*;
+use jsonrpc_macros::pubsub;
+use test_client::{self, TestClient};
+use test_client::runtime::Block;
+use consensus::BlockOrigin;
+use runtime_primitives::generic::BlockId;
+
+/// Proves every block final on its own, as long as it is not the genesis block.
+fn provider(genesis_hash: H256) -> Arc<FinalityProofProvider<Block>> {
+	Arc::new(move |begin: H256, end: H256| -> client::error::Result<Option<(H256, Vec<u8>)>> {
+		if end == genesis_hash {
+			return Ok(None);
+		}
+		let mut proof = begin.as_ref().to_vec();
+		proof.extend(end.as_ref());
+		Ok(Some((end, proof)))
+	})
+}
+
+#[test]
+fn should_fail_without_provider() {
+	let core = ::tokio::runtime::Runtime::new().unwrap();
+	let remote = core.executor();
+
+	let api = Finality::new(Arc::new(test_client::new()), None, Subscriptions::new(remote));
+	let genesis_hash = api.client.genesis_hash();
+
+	assert_matches!(api.prove_finality(genesis_hash, genesis_hash), Err(_));
+}
+
+#[test]
+fn should_return_finality_proof() {
+	let core = ::tokio::runtime::Runtime::new().unwrap();
+	let remote = core.executor();
+
+	let client = Arc::new(test_client::new());
+	let genesis_hash = client.genesis_hash();
+	let api = Finality::new(client, Some(provider(genesis_hash)), Subscriptions::new(remote));
+
+	let end: H256 = [1; 32].into();
+	let mut expected = genesis_hash.as_ref().to_vec();
+	expected.extend(end.as_ref());
+	assert_eq!(api.prove_finality(genesis_hash, end).unwrap(), Some(expected.into()));
+	assert_eq!(api.prove_finality(genesis_hash, genesis_hash).unwrap(), None);
+}
+
+#[test]
+fn should_cap_proofs_of_a_range() {
+	// proves one block after `begin` at a time, never reaching the end of the range.
+	let provider = |begin: H256, _end: H256| -> client::error::Result<Option<(H256, Vec<u8>)>> {
+		let mut next = begin.as_ref().to_vec();
+		next[0] += 1;
+		Ok(Some((H256::from_slice(&next), Vec::new())))
+	};
+
+	let mut begin = H256::default();
+	let proofs = prove_range::<Block>(&provider, &mut begin, [0xff; 32].into());
+	assert_eq!(proofs.len(), MAX_PROOFS_PER_NOTIFICATION);
+	assert_eq!(begin.as_ref()[0], MAX_PROOFS_PER_NOTIFICATION as u8);
+}
+
+#[test]
+fn should_notify_about_finality_proofs() {
+	let mut core = ::tokio::runtime::Runtime::new().unwrap();
+	let remote = core.executor();
+	let (subscriber, id, transport) = pubsub::Subscriber::new_test("test");
+
+	{
+		let client = Arc::new(test_client::new());
+		let genesis_hash = client.genesis_hash();
+		let api = Finality::new(client, Some(provider(genesis_hash)), Subscriptions::new(remote));
+
+		api.subscribe_finality_proofs(Default::default(), subscriber, genesis_hash);
+
+		// assert id assigned
+		assert_eq!(core.block_on(id), Ok(Ok(SubscriptionId::Number(1))));
+
+		let builder = api.client.new_block().unwrap();
+		api.client.justify_and_import(BlockOrigin::Own, builder.bake().unwrap()).unwrap();
+		api.client.finalize_block(BlockId::number(1), None, true).unwrap();
+	}
+
+	// assert notification sent to transport
+	let (notification, next) = core.block_on(transport.into_future()).unwrap();
+	assert!(notification.is_some());
+	// no more notifications on this channel
+	assert_eq!(core.block_on(next.into_future()).unwrap().0, None);
+}
@@ -14,6 +14,14 @@
 // You should have received a copy of the GNU General Public License
 // along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
 
+use primitives::storage::StorageKey;
+use runtime_support::storage::generator::storage_prefix;
+
+/// The storage key of a value of the system module, as declared with `decl_storage!`.
+pub fn system_storage_key(item: &[u8]) -> StorageKey {
+	StorageKey(storage_prefix(b"System", item))
+}
+
 /// Unwraps the trailing parameter or falls back with the closure result.
 pub fn unwrap_or_else<F, H, E>(or_else: F, optional: ::jsonrpc_macros::Trailing<H>) -> Result<H, E> where
 	F: FnOnce() -> Result<H, E>,
@@ -21,12 +21,14 @@
 extern crate jsonrpc_core as rpc;
 extern crate jsonrpc_pubsub;
 extern crate parking_lot;
+extern crate serde;
 extern crate parity_codec as codec;
 extern crate substrate_client as client;
 extern crate substrate_transaction_pool as transaction_pool;
 extern crate substrate_primitives as primitives;
 extern crate sr_primitives as runtime_primitives;
 extern crate sr_version as runtime_version;
+extern crate srml_support as runtime_support;
 extern crate tokio;
 
 #[macro_use]
@@ -35,6 +37,8 @@ extern crate error_chain;
 extern crate jsonrpc_macros;
 #[macro_use]
 extern crate log;
+#[macro_use]
+extern crate serde_derive;
 
 #[cfg(test)]
 #[macro_use]
@@ -57,6 +61,8 @@ pub use subscriptions::Subscriptions;
 
 pub mod author;
 pub mod chain;
+pub mod events;
+pub mod finality;
 pub mod metadata;
 pub mod state;
 pub mod system;
@@ -19,9 +19,10 @@
 use std::{
 	collections::HashMap,
 	sync::Arc,
+	time::Duration,
 };
 
-use client::{self, Client, CallExecutor, BlockchainEvents, runtime_api::Metadata};
+use client::{self, Client, CallExecutor, BlockchainEvents, runtime_api::{Metadata, ExecutionLimits}};
 use jsonrpc_macros::Trailing;
 use jsonrpc_macros::pubsub;
 use jsonrpc_pubsub::SubscriptionId;
@@ -31,8 +32,10 @@ use primitives::storage::{StorageKey, StorageData, StorageChangeSet};
 use primitives::{Blake2Hasher, Bytes};
 use rpc::Result as RpcResult;
 use rpc::futures::{stream, Future, Sink, Stream};
+use codec::Decode;
 use runtime_primitives::generic::BlockId;
 use runtime_primitives::traits::{Block as BlockT, Header, ProvideRuntimeApi};
+use runtime_primitives::weights::ClassWeights;
 
 use subscriptions::Subscriptions;
 
@@ -42,6 +45,29 @@ mod tests;
 
 use self::error::Result;
 
+/// The limits of the runtime calls made with `state_call`, so that a single call can't stall
+/// the node.
+fn call_limits() -> ExecutionLimits {
+	ExecutionLimits {
+		max_duration: Some(Duration::from_secs(10)),
+		max_fuel: None,
+		max_memory_growth: Some(1024),
+	}
+}
+
+/// The weight consumed by the calls of the extrinsics of a block, per dispatch class, as
+/// accounted for by the runtime in `System::block_weight()`.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockWeight {
+	/// The weight of the normal calls, e.g. transfers.
+	pub normal: u32,
+	/// The weight of the operational calls, e.g. inherents.
+	pub operational: u32,
+	/// The weight of all calls.
+	pub total: u32,
+}
+
 build_rpc_trait! {
 	/// Substrate state API
 	pub trait StateApi<Hash> {
@@ -67,6 +93,10 @@ build_rpc_trait! {
 		#[rpc(name = "state_getMetadata")]
 		fn metadata(&self, Trailing<Hash>) -> Result<Bytes>;
 
+		/// Returns the weight consumed by the extrinsics of a block, per dispatch class.
+		#[rpc(name = "state_getBlockWeight")]
+		fn block_weight(&self, Trailing<Hash>) -> Result<BlockWeight>;
+
 		/// Query historical storage entries (by key) starting from a block given as the second parameter.
 		///
 		/// NOTE This first returned result contains the initial state of storage for all keys.
@@ -127,9 +157,10 @@ impl<B, E, Block, RA> StateApi<Block::Hash> for State<B, E, Block, RA> where
 		trace!(target: "rpc", "Calling runtime at {:?} for method {} ({})", block, method, HexDisplay::from(&data.0));
 		let return_data = self.client
 			.executor()
-			.call(
+			.call_with_limits(
 				&BlockId::Hash(block),
-				&method, &data.0
+				&method, &data.0,
+				call_limits(),
 			)?
 			.return_data;
 		Ok(Bytes(return_data))
@@ -155,6 +186,19 @@ impl<B, E, Block, RA> StateApi<Block::Hash> for State<B, E, Block, RA> where
 		self.client.runtime_api().metadata(&BlockId::Hash(block)).map(Into::into).map_err(Into::into)
 	}
 
+	fn block_weight(&self, block: Trailing<Block::Hash>) -> Result<BlockWeight> {
+		let block = self.unwrap_or_best(block)?;
+		let weights = self.client.storage(&BlockId::Hash(block), &::helpers::system_storage_key(b"BlockWeight"))?
+			.and_then(|weights| ClassWeights::decode(&mut &weights.0[..]))
+			// nothing is stored for blocks without extrinsics.
+			.unwrap_or_default();
+		Ok(BlockWeight {
+			normal: weights.normal,
+			operational: weights.operational,
+			total: weights.total(),
+		})
+	}
+
 	fn query_storage(&self, keys: Vec<StorageKey>, from: Block::Hash, to: Trailing<Block::Hash>) -> Result<Vec<StorageChangeSet<Block::Hash>>> {
 		let to = self.unwrap_or_best(to)?;
 
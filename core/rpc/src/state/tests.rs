@@ -48,6 +48,30 @@ fn should_call_contract() {
 	)
 }
 
+#[test]
+fn should_call_runtime_within_limits() {
+	let core = ::tokio::runtime::Runtime::new().unwrap();
+	let client = Arc::new(test_client::new());
+	let genesis_hash = client.genesis_hash();
+	let client = State::new(client, Subscriptions::new(core.executor()));
+
+	assert!(client.call("version".into(), Bytes(vec![]), Some(genesis_hash).into()).is_ok());
+}
+
+#[test]
+fn should_return_block_weight() {
+	let core = ::tokio::runtime::Runtime::new().unwrap();
+	let client = Arc::new(test_client::new());
+	let genesis_hash = client.genesis_hash();
+	let client = State::new(client, Subscriptions::new(core.executor()));
+
+	// the test runtime doesn't record any weight.
+	assert_eq!(
+		client.block_weight(Some(genesis_hash).into()).unwrap(),
+		BlockWeight { normal: 0, operational: 0, total: 0 }
+	);
+}
+
 #[test]
 fn should_notify_about_storage_changes() {
 	let mut core = ::tokio::runtime::Runtime::new().unwrap();
@@ -38,6 +38,10 @@ build_rpc_trait! {
 		#[rpc(name = "system_chain")]
 		fn system_chain(&self) -> Result<String>;
 
+		/// Get the type of the chain: `Development`, `Local`, `Live` or a custom type.
+		#[rpc(name = "system_chainType")]
+		fn system_chain_type(&self) -> Result<String>;
+
 		/// Get a custom set of properties as a JSON object, defined in the chain spec.
 		#[rpc(name = "system_properties")]
 		fn system_properties(&self) -> Result<serde_json::map::Map<String, serde_json::Value>>;
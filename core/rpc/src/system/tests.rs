@@ -27,6 +27,9 @@ impl SystemApi for () {
 	fn system_chain(&self) -> Result<String> {
 		Ok("testchain".into())
 	}
+	fn system_chain_type(&self) -> Result<String> {
+		Ok("Development".into())
+	}
 	fn system_properties(&self) -> Result<serde_json::map::Map<String, serde_json::Value>> {
 		Ok(serde_json::map::Map::new())
 	}
@@ -56,6 +59,14 @@ fn system_chain_works() {
 	);
 }
 
+#[test]
+fn system_chain_type_works() {
+	assert_eq!(
+		SystemApi::system_chain_type(&()).unwrap(),
+		"Development".to_owned()
+	);
+}
+
 #[test]
 fn system_properties_works() {
 	assert_eq!(
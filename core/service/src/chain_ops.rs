@@ -21,15 +21,24 @@ use futures::Future;
 
 use runtime_primitives::generic::{SignedBlock, BlockId};
 use runtime_primitives::traits::{As, Block, Header};
+use runtime_primitives::{BuildStorage, StorageMap, ChildrenStorageMap};
+use primitives::{Blake2Hasher, H256, blake2_256};
+use primitives::storage::{StorageKey, well_known_keys};
+use client::{self, backend::{Backend as ClientBackend, BlockImportOperation}};
+use client::snapshot::{self, SnapshotHeader, StateChunk};
+use client_db;
 use network::import_queue::{ImportQueue, Link, BlockData};
 use network::message;
 
 use consensus_common::BlockOrigin;
-use components::{self, Components, ServiceFactory, FactoryFullConfiguration, FactoryBlockNumber, RuntimeGenesis};
+use components::{self, Components, ServiceFactory, FactoryFullConfiguration, FactoryBlock, FactoryBlockNumber, RuntimeGenesis};
+use rpc_externalities::{self, HttpStorage, RemoteExternalities, UpgradeReport};
+use substrate_executor::NativeExecutor;
 use new_client;
 use codec::{Decode, Encode};
 use error;
-use chain_spec::ChainSpec;
+use chain_spec::{ChainSpec, LightSyncState};
+use serde_json;
 
 /// Export a range of blocks to a binary stream.
 pub fn export_blocks<F, E, W>(config: FactoryFullConfiguration<F>, exit: E, mut output: W, from: FactoryBlockNumber<F>, to: Option<FactoryBlockNumber<F>>, json: bool) -> error::Result<()>
@@ -107,7 +116,7 @@ pub fn import_blocks<F, E, R>(mut config: FactoryFullConfiguration<F>, exit: E,
 	let count: u32 = Decode::decode(&mut input).ok_or("Error reading file")?;
 	info!("Importing {} blocks", count);
 	let mut block_count = 0;
-	for b in 0 .. count {
+	'blocks: for b in 0 .. count {
 		if exit_recv.try_recv().is_ok() {
 			break;
 		}
@@ -122,6 +131,13 @@ pub fn import_blocks<F, E, R>(mut config: FactoryFullConfiguration<F>, exit: E,
 				receipt: None,
 				message_queue: None
 			};
+			// reading the file is much faster than importing, so hold on while the queue is full.
+			while queue.status().is_full() {
+				if exit_recv.try_recv().is_ok() {
+					break 'blocks;
+				}
+				::std::thread::sleep(::std::time::Duration::from_millis(10));
+			}
 			// import queue handles verification and importing it into the client
 			queue.import_blocks(BlockOrigin::File, vec![BlockData::<F::Block> { block, origin: None }]);
 		} else {
@@ -139,6 +155,103 @@ pub fn import_blocks<F, E, R>(mut config: FactoryFullConfiguration<F>, exit: E,
 	Ok(())
 }
 
+/// Export the state of a finalized block to a snapshot. Defaults to the last finalized block.
+pub fn export_state<F, W>(config: FactoryFullConfiguration<F>, mut output: W, block: Option<FactoryBlockNumber<F>>) -> error::Result<()>
+	where F: ServiceFactory, W: Write,
+{
+	let client = new_client::<F>(&config)?;
+	let info = client.info()?.chain;
+
+	// the importing node trusts the state root of the block, so it has to be final.
+	let number = block.unwrap_or(info.finalized_number);
+	if number > info.finalized_number {
+		return Err(format!("Block #{} is not finalized", number).into());
+	}
+	let header = client.header(&BlockId::number(number))?
+		.ok_or_else(|| format!("Unknown block #{}", number))?;
+	let hash = header.hash();
+
+	info!("Exporting state of block #{} ({})", number, hash);
+	let state = client.state_at(&BlockId::Hash(hash))?;
+	let chunks = snapshot::build_chunks::<Blake2Hasher, _>(&state, snapshot::DEFAULT_CHUNK_SIZE)?;
+	let snapshot_header = SnapshotHeader::new::<Blake2Hasher>(info.genesis_hash, header, &chunks);
+	output.write_all(&snapshot_header.encode())?;
+	for chunk in &chunks {
+		output.write_all(&chunk.encode())?;
+	}
+	info!("Exported {} state chunks", chunks.len());
+	Ok(())
+}
+
+/// Import a state snapshot into an empty database. The node then starts from the snapshot block.
+pub fn import_state<F, R>(config: FactoryFullConfiguration<F>, mut input: R) -> error::Result<()>
+	where F: ServiceFactory, R: Read,
+{
+	let snapshot_header: SnapshotHeader<<F::Block as Block>::Header, H256> = Decode::decode(&mut input)
+		.ok_or("Error reading snapshot header")?;
+	snapshot_header.check_version()?;
+
+	let genesis_hash = genesis_hash::<F>(&config)?;
+	if snapshot_header.genesis_hash != genesis_hash {
+		return Err(format!(
+			"Snapshot belongs to another chain: genesis {}, expected {}", snapshot_header.genesis_hash, genesis_hash
+		).into());
+	}
+
+	let number = *snapshot_header.header.number();
+	info!("Importing state of block #{} ({}) from {} chunks", number, snapshot_header.header.hash(), snapshot_header.chunks.len());
+	let mut top = StorageMap::new();
+	let mut children = ChildrenStorageMap::new();
+	for index in 0 .. snapshot_header.chunks.len() {
+		let chunk = StateChunk::decode(&mut input).ok_or_else(|| format!("Error reading state chunk #{}", index))?;
+		snapshot_header.verify_chunk::<Blake2Hasher>(index, &chunk)?;
+		snapshot::apply_chunk(chunk, &mut top, &mut children)?;
+	}
+
+	let db_settings = client_db::DatabaseSettings {
+		cache_size: None,
+		path: config.database_path.as_str().into(),
+		pruning: config.pruning.clone(),
+	};
+	let backend = client_db::Backend::<F::Block>::new(db_settings, client_db::CANONICALIZATION_DELAY)?;
+	backend.import_state(genesis_hash, snapshot_header.header, top, children)?;
+	info!("Imported state. Best: #{}", number);
+	Ok(())
+}
+
+// computes the hash of the genesis block of the configured chain.
+fn genesis_hash<F: ServiceFactory>(config: &FactoryFullConfiguration<F>) -> error::Result<H256> {
+	let (storage, children) = (&config.chain_spec).build_storage()?;
+	let backend = client::in_mem::Backend::<F::Block, Blake2Hasher>::new();
+	let mut op = backend.begin_operation(BlockId::Hash(Default::default()))?;
+	let state_root = op.reset_storage(storage, children)?;
+	Ok(client::genesis::construct_genesis_block::<F::Block>(state_root).header().hash())
+}
+
+/// Export the finality state of the last finalized block as JSON, to be embedded as
+/// `lightSyncState` into the chain spec.
+pub fn export_sync_state<F, W>(config: FactoryFullConfiguration<F>, mut output: W) -> error::Result<()>
+	where F: ServiceFactory, W: Write,
+{
+	let client = new_client::<F>(&config)?;
+	let info = client.info()?.chain;
+	let header = client.header(&BlockId::Hash(info.finalized_hash))?
+		.ok_or_else(|| format!("Unknown finalized block {}", info.finalized_hash))?;
+
+	info!("Exporting sync state of block #{} ({})", info.finalized_number, info.finalized_hash);
+	let state = LightSyncState {
+		finalized_block_header: header.encode().into(),
+		finality_state: F::finality_sync_state(&client)?.map(Into::into),
+	};
+	if state.finality_state.is_none() {
+		warn!("The chain has no finality gadget; light clients can only trust the exported header.");
+	}
+
+	serde_json::to_writer_pretty(&mut output, &state).map_err(|e| format!("Error writing sync state: {}", e))?;
+	output.write_all(b"\n")?;
+	Ok(())
+}
+
 /// Revert the chain.
 pub fn revert_chain<F>(config: FactoryFullConfiguration<F>, blocks: FactoryBlockNumber<F>) -> error::Result<()>
 	where F: ServiceFactory,
@@ -150,9 +263,139 @@ pub fn revert_chain<F>(config: FactoryFullConfiguration<F>, blocks: FactoryBlock
 	Ok(())
 }
 
+/// Rehearse an upgrade to the runtime `wasm` against the state of the node serving HTTP RPC at
+/// `url`, as of the block with the given hash or its best block. Nothing is written to the node.
+///
+/// The upgrade is rehearsed by initialising the next block with the new runtime, which runs its
+/// migrations.
+pub fn try_runtime<F>(url: &str, at: Option<H256>, wasm: &[u8]) -> error::Result<UpgradeReport>
+	where F: ServiceFactory,
+{
+	let storage = HttpStorage::new(url.parse().map_err(|_| format!("Invalid RPC url {}", url))?, at)?;
+	let parent: <FactoryBlock<F> as Block>::Header = storage.header()?
+		.ok_or_else(|| format!("Unknown block {:?}", at))?;
+	info!("Rehearsing the upgrade on top of block #{} ({})", parent.number(), parent.hash());
+	let header = <<FactoryBlock<F> as Block>::Header as Header>::new(
+		*parent.number() + As::sa(1),
+		Default::default(),
+		Default::default(),
+		parent.hash(),
+		Default::default(),
+	);
+
+	let mut ext = RemoteExternalities::new(storage);
+	let executor = NativeExecutor::<F::RuntimeDispatch>::new();
+	Ok(rpc_externalities::try_upgrade(&mut ext, &executor, wasm, &header.encode())?)
+}
+
+/// Outcome of comparing the runtime code of a block with a locally built wasm blob.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WasmVerdict {
+	/// The blobs are byte-for-byte identical.
+	Identical,
+	/// The blobs only differ in custom sections, i.e. names and debug info.
+	SameCode,
+	/// The code differs.
+	Different,
+}
+
+/// Compare the runtime code of the given block, the best block by default, with `wasm`.
+pub fn verify_wasm<F>(config: FactoryFullConfiguration<F>, wasm: &[u8], block: Option<FactoryBlockNumber<F>>) -> error::Result<WasmVerdict>
+	where F: ServiceFactory,
+{
+	let client = new_client::<F>(&config)?;
+	let number = match block {
+		Some(number) => number,
+		None => client.info()?.chain.best_number,
+	};
+	let code = client.storage(&BlockId::number(number), &StorageKey(well_known_keys::CODE.to_vec()))?
+		.ok_or_else(|| format!("No runtime code at block #{}", number))?;
+
+	info!("Runtime code of block #{}: {} bytes, blake2 {}", number, code.0.len(), H256::from(blake2_256(&code.0)));
+	info!("Local wasm: {} bytes, blake2 {}", wasm.len(), H256::from(blake2_256(wasm)));
+	compare_wasm(&code.0, wasm)
+}
+
+fn compare_wasm(on_chain: &[u8], local: &[u8]) -> error::Result<WasmVerdict> {
+	if on_chain == local {
+		return Ok(WasmVerdict::Identical);
+	}
+	Ok(if strip_custom_sections(on_chain)? == strip_custom_sections(local)? {
+		WasmVerdict::SameCode
+	} else {
+		WasmVerdict::Different
+	})
+}
+
+// custom sections carry names and debug info, which depend on the build environment but are
+// ignored when the module is executed.
+fn strip_custom_sections(wasm: &[u8]) -> error::Result<Vec<u8>> {
+	const HEADER: &[u8] = b"\0asm\x01\0\0\0";
+	const CUSTOM_SECTION: u8 = 0;
+
+	if !wasm.starts_with(HEADER) {
+		return Err("Not a wasm module".into());
+	}
+
+	let mut stripped = HEADER.to_vec();
+	let mut rest = &wasm[HEADER.len()..];
+	while let Some((&id, after_id)) = rest.split_first() {
+		let (size, size_len) = read_leb128_u32(after_id).ok_or("Invalid wasm section size")?;
+		let end = 1 + size_len + size as usize;
+		if end > rest.len() {
+			return Err("Truncated wasm section".into());
+		}
+		if id != CUSTOM_SECTION {
+			stripped.extend_from_slice(&rest[..end]);
+		}
+		rest = &rest[end..];
+	}
+	Ok(stripped)
+}
+
+// reads an unsigned LEB128 integer, returning it together with its encoded length.
+fn read_leb128_u32(input: &[u8]) -> Option<(u32, usize)> {
+	let mut value = 0u32;
+	for (i, byte) in input.iter().take(5).enumerate() {
+		value |= ((byte & 0x7f) as u32) << (7 * i);
+		if byte & 0x80 == 0 {
+			return Some((value, i + 1));
+		}
+	}
+	None
+}
+
 /// Build a chain spec json
 pub fn build_spec<G>(spec: ChainSpec<G>, raw: bool) -> error::Result<String>
 	where G: RuntimeGenesis,
 {
 	Ok(spec.to_json(raw)?)
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn module(sections: &[(u8, &[u8])]) -> Vec<u8> {
+		let mut wasm = b"\0asm\x01\0\0\0".to_vec();
+		for &(id, payload) in sections {
+			wasm.push(id);
+			wasm.push(payload.len() as u8);
+			wasm.extend_from_slice(payload);
+		}
+		wasm
+	}
+
+	#[test]
+	fn custom_sections_are_ignored_when_comparing_wasm() {
+		let code = module(&[(1, &[1, 2, 3]), (10, &[4, 5])]);
+		let with_names = module(&[(1, &[1, 2, 3]), (0, b"\x04name"), (10, &[4, 5])]);
+		let other_code = module(&[(1, &[1, 2, 3]), (10, &[4, 6])]);
+
+		assert_eq!(compare_wasm(&code, &code).unwrap(), WasmVerdict::Identical);
+		assert_eq!(compare_wasm(&with_names, &code).unwrap(), WasmVerdict::SameCode);
+		assert_eq!(compare_wasm(&with_names, &other_code).unwrap(), WasmVerdict::Different);
+		assert!(compare_wasm(&code, b"not wasm").is_err());
+		assert!(compare_wasm(&code, &code[..code.len() - 1]).is_err());
+	}
+}
@@ -17,8 +17,10 @@
 //! Substrate chain configurations.
 
 use std::collections::HashMap;
+use std::fmt;
 use std::fs::File;
 use std::path::PathBuf;
+use primitives::{Bytes, H256};
 use primitives::storage::{StorageKey, StorageData};
 use runtime_primitives::{BuildStorage, StorageMap, ChildrenStorageMap};
 use serde_json as json;
@@ -80,16 +82,78 @@ enum Genesis<G> {
 	Raw(HashMap<StorageKey, StorageData>),
 }
 
+/// The type of a chain.
+///
+/// Defaults which are only safe for chains running locally, such as exposing RPCs or
+/// disabling telemetry, depend on it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ChainType {
+	/// A development chain that runs mainly on one node.
+	Development,
+	/// A local chain that runs locally on multiple nodes for testing purposes.
+	Local,
+	/// A live chain.
+	Live,
+	/// Some custom chain type.
+	Custom(String),
+}
+
+impl Default for ChainType {
+	fn default() -> Self {
+		ChainType::Live
+	}
+}
+
+impl ChainType {
+	/// Whether the chain only runs locally, for development or testing purposes.
+	pub fn is_local(&self) -> bool {
+		match *self {
+			ChainType::Development | ChainType::Local => true,
+			ChainType::Live | ChainType::Custom(_) => false,
+		}
+	}
+}
+
+impl fmt::Display for ChainType {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			ChainType::Development => write!(f, "Development"),
+			ChainType::Local => write!(f, "Local"),
+			ChainType::Live => write!(f, "Live"),
+			ChainType::Custom(ref name) => write!(f, "{}", name),
+		}
+	}
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 struct ChainSpecFile {
 	pub name: String,
 	pub id: String,
+	#[serde(default)]
+	pub chain_type: ChainType,
 	pub boot_nodes: Vec<String>,
 	pub telemetry_url: Option<String>,
 	pub protocol_id: Option<String>,
 	pub consensus_engine: Option<String>,
 	pub properties: Option<Properties>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub light_sync_state: Option<LightSyncState>,
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub bad_blocks: Vec<H256>,
+}
+
+/// The finality state of a chain at a finalized block, from which light clients can start
+/// syncing instead of verifying finality from genesis on.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+#[serde(deny_unknown_fields)]
+pub struct LightSyncState {
+	/// The encoded header of the finalized block.
+	pub finalized_block_header: Bytes,
+	/// The encoded state of the finality gadget at that block, e.g. the GRANDPA authority set.
+	pub finality_state: Option<Bytes>,
 }
 
 /// Arbitrary properties defined in chain spec as a JSON object
@@ -123,6 +187,10 @@ impl<G: RuntimeGenesis> ChainSpec<G> {
 		&self.spec.id
 	}
 
+	pub fn chain_type(&self) -> &ChainType {
+		&self.spec.chain_type
+	}
+
 	pub fn telemetry_url(&self) -> Option<&str> {
 		self.spec.telemetry_url.as_ref().map(String::as_str)
 	}
@@ -135,6 +203,21 @@ impl<G: RuntimeGenesis> ChainSpec<G> {
 		self.spec.consensus_engine.as_ref().map(String::as_str)
 	}
 
+	/// The finality state light clients may start syncing from, if any.
+	pub fn light_sync_state(&self) -> Option<&LightSyncState> {
+		self.spec.light_sync_state.as_ref()
+	}
+
+	/// Embed the finality state light clients may start syncing from.
+	pub fn set_light_sync_state(&mut self, state: LightSyncState) {
+		self.spec.light_sync_state = Some(state);
+	}
+
+	/// Hashes of blocks known to be bad, which are never imported nor built on.
+	pub fn bad_blocks(&self) -> &[H256] {
+		&self.spec.bad_blocks
+	}
+
 	pub fn properties(&self) -> Properties {
 		// Return an empty JSON object if 'properties' not defined in config
 		self.spec.properties.as_ref().unwrap_or(&json::map::Map::new()).clone()
@@ -167,6 +250,7 @@ impl<G: RuntimeGenesis> ChainSpec<G> {
 	pub fn from_genesis(
 		name: &str,
 		id: &str,
+		chain_type: ChainType,
 		constructor: fn() -> G,
 		boot_nodes: Vec<String>,
 		telemetry_url: Option<&str>,
@@ -178,11 +262,14 @@ impl<G: RuntimeGenesis> ChainSpec<G> {
 		let spec = ChainSpecFile {
 			name: name.to_owned(),
 			id: id.to_owned(),
+			chain_type,
 			boot_nodes: boot_nodes,
 			telemetry_url: telemetry_url.map(str::to_owned),
 			protocol_id: protocol_id.map(str::to_owned),
 			consensus_engine: consensus_engine.map(str::to_owned),
 			properties,
+			light_sync_state: None,
+			bad_blocks: Vec::new(),
 		};
 		ChainSpec {
 			spec,
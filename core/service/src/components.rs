@@ -25,11 +25,12 @@ use client::{self, Client, runtime_api::{TaggedTransactionQueue, Metadata}};
 use {error, Service, RpcConfig, maybe_start_server, TransactionPoolAdapter};
 use network::{self, OnDemand, import_queue::ImportQueue};
 use substrate_executor::{NativeExecutor, NativeExecutionDispatch};
-use transaction_pool::txpool::{self, Options as TransactionPoolOptions, Pool as TransactionPool};
+use transaction_pool::txpool::{self, Options as TransactionPoolOptions};
 use runtime_primitives::{traits::Block as BlockT, traits::Header as HeaderT, BuildStorage, generic::SignedBlock};
 use config::Configuration;
 use primitives::{Blake2Hasher, H256};
-use rpc;
+use primitives::crypto::Keystore;
+use rpc::{self, apis::events::EventDecoder};
 
 // Type aliases.
 // These exist mainly to avoid typing `<F as Factory>::Foo` all over the code.
@@ -104,13 +105,19 @@ pub type ComponentClient<C> = Client<
 pub type ComponentBlock<C> = <<C as Components>::Factory as ServiceFactory>::Block;
 
 /// Extrinsic hash type for `Components`
-pub type ComponentExHash<C> = <<C as Components>::TransactionPoolApi as txpool::ChainApi>::Hash;
+pub type ComponentExHash<C> = <<C as Components>::TransactionPool as txpool::TransactionPool>::Hash;
 
 /// Extrinsic type.
 pub type ComponentExtrinsic<C> = <ComponentBlock<C> as BlockT>::Extrinsic;
 
-/// Extrinsic pool API type for `Components`.
-pub type PoolApi<C> = <C as Components>::TransactionPoolApi;
+/// Extrinsic pool type for `Components`.
+pub type ComponentTransactionPool<C> = <C as Components>::TransactionPool;
+
+/// Provider of finality proofs for the RPC, for `Components`.
+pub type FinalityProofProvider<C> = rpc::apis::finality::FinalityProofProvider<ComponentBlock<C>>;
+
+/// Index of the runtime events of the imported blocks for the RPC, for `Components`.
+pub type EventIndex<C> = rpc::apis::events::EventIndex<<ComponentBlock<C> as BlockT>::Hash>;
 
 /// A set of traits for the runtime genesis config.
 pub trait RuntimeGenesis: Serialize + DeserializeOwned + BuildStorage {}
@@ -121,13 +128,16 @@ pub trait StartRPC<C: Components> {
 	fn start_rpc(
 		client: Arc<Client<C::Backend, C::Executor, ComponentBlock<C>, C::RuntimeApi>>,
 		chain_name: String,
+		chain_type: String,
 		impl_name: &'static str,
 		impl_version: &'static str,
 		rpc_http: Option<SocketAddr>,
 		rpc_ws: Option<SocketAddr>,
 		properties: Properties,
 		task_executor: TaskExecutor,
-		transaction_pool: Arc<TransactionPool<C::TransactionPoolApi>>,
+		transaction_pool: Arc<C::TransactionPool>,
+		finality_proof_provider: Option<Arc<FinalityProofProvider<C>>>,
+		event_index: Option<Arc<EventIndex<C>>>,
 	) -> Result<(Option<rpc::HttpServer>, Option<rpc::WsServer>), error::Error>;
 }
 
@@ -138,28 +148,37 @@ impl<T: Components> StartRPC<Self> for T where
 	fn start_rpc(
 		client: Arc<Client<T::Backend, T::Executor, ComponentBlock<T>, T::RuntimeApi>>,
 		chain_name: String,
+		chain_type: String,
 		impl_name: &'static str,
 		impl_version: &'static str,
 		rpc_http: Option<SocketAddr>,
 		rpc_ws: Option<SocketAddr>,
 		properties: Properties,
 		task_executor: TaskExecutor,
-		transaction_pool: Arc<TransactionPool<T::TransactionPoolApi>>,
+		transaction_pool: Arc<T::TransactionPool>,
+		finality_proof_provider: Option<Arc<FinalityProofProvider<T>>>,
+		event_index: Option<Arc<EventIndex<T>>>,
 	) -> Result<(Option<rpc::HttpServer>, Option<rpc::WsServer>), error::Error> {
-		let rpc_config = RpcConfig { properties, chain_name, impl_name, impl_version };
+		let rpc_config = RpcConfig { properties, chain_name, chain_type, impl_name, impl_version };
 
 		let handler = || {
 			let client = client.clone();
 			let subscriptions = rpc::apis::Subscriptions::new(task_executor.clone());
 			let chain = rpc::apis::chain::Chain::new(client.clone(), subscriptions.clone());
 			let state = rpc::apis::state::State::new(client.clone(), subscriptions.clone());
+			let finality = rpc::apis::finality::Finality::new(
+				client.clone(), finality_proof_provider.clone(), subscriptions.clone()
+			);
+			let events = rpc::apis::events::Events::new(event_index.clone());
 			let author = rpc::apis::author::Author::new(
 				client.clone(), transaction_pool.clone(), subscriptions
 			);
-			rpc::rpc_handler::<ComponentBlock<T>, ComponentExHash<T>, _, _, _, _>(
+			rpc::rpc_handler::<ComponentBlock<T>, ComponentExHash<T>, _, _, _, _, _, _>(
 				state,
 				chain,
 				author,
+				finality,
+				events,
 				rpc_config.clone(),
 			)
 		};
@@ -175,7 +194,7 @@ impl<T: Components> StartRPC<Self> for T where
 pub trait CreateNetworkParams<C: Components> {
 	fn create_network_params<S>(
 		client: Arc<Client<C::Backend, C::Executor, ComponentBlock<C>, C::RuntimeApi>>,
-		roles: network::config::Roles,
+		protocol_config: network::config::ProtocolConfig,
 		network_config: network::config::NetworkConfiguration,
 		on_demand: Option<Arc<OnDemand<FactoryBlock<C::Factory>, NetworkService<C::Factory>>>>,
 		transaction_pool_adapter: TransactionPoolAdapter<C>,
@@ -188,14 +207,14 @@ impl<T: Components> CreateNetworkParams<Self> for T where
 {
 	fn create_network_params<S>(
 		client: Arc<Client<T::Backend, T::Executor, ComponentBlock<T>, T::RuntimeApi>>,
-		roles: network::config::Roles,
+		protocol_config: network::config::ProtocolConfig,
 		network_config: network::config::NetworkConfiguration,
 		on_demand: Option<Arc<OnDemand<FactoryBlock<T::Factory>, NetworkService<T::Factory>>>>,
 		transaction_pool_adapter: TransactionPoolAdapter<T>,
 		specialization: S,
 	) -> network::config::Params<ComponentBlock<T>, S, ComponentExHash<T>> {
 		network::config::Params {
-			config: network::config::ProtocolConfig { roles },
+			config: protocol_config,
 			network_config,
 			chain: client,
 			on_demand: on_demand.map(|d| d as Arc<network::OnDemandService<ComponentBlock<T>>>),
@@ -228,10 +247,10 @@ pub trait ServiceFactory: 'static + Sized {
 	type NetworkProtocol: network::specialization::NetworkSpecialization<Self::Block>;
 	/// Chain runtime.
 	type RuntimeDispatch: NativeExecutionDispatch + Send + Sync + 'static;
-	/// Extrinsic pool backend type for the full client.
-	type FullTransactionPoolApi: txpool::ChainApi<Hash = <Self::Block as BlockT>::Hash, Block = Self::Block> + Send + 'static;
-	/// Extrinsic pool backend type for the light client.
-	type LightTransactionPoolApi: txpool::ChainApi<Hash = <Self::Block as BlockT>::Hash, Block = Self::Block> + 'static;
+	/// Extrinsic pool type for the full client.
+	type FullTransactionPool: txpool::TransactionPool<Hash = <Self::Block as BlockT>::Hash, Block = Self::Block> + 'static;
+	/// Extrinsic pool type for the light client.
+	type LightTransactionPool: txpool::TransactionPool<Hash = <Self::Block as BlockT>::Hash, Block = Self::Block> + 'static;
 	/// Genesis configuration for the runtime.
 	type Genesis: RuntimeGenesis;
 	/// Other configuration for service members.
@@ -248,10 +267,10 @@ pub trait ServiceFactory: 'static + Sized {
 	//TODO: replace these with a constructor trait. that TransactionPool implements.
 	/// Extrinsic pool constructor for the full client.
 	fn build_full_transaction_pool(config: TransactionPoolOptions, client: Arc<FullClient<Self>>)
-		-> Result<TransactionPool<Self::FullTransactionPoolApi>, error::Error>;
+		-> Result<Self::FullTransactionPool, error::Error>;
 	/// Extrinsic pool constructor for the light client.
 	fn build_light_transaction_pool(config: TransactionPoolOptions, client: Arc<LightClient<Self>>)
-		-> Result<TransactionPool<Self::LightTransactionPoolApi>, error::Error>;
+		-> Result<Self::LightTransactionPool, error::Error>;
 
 	/// Build network protocol.
 	fn build_network_protocol(config: &FactoryFullConfiguration<Self>)
@@ -293,6 +312,26 @@ pub trait ServiceFactory: 'static + Sized {
 			Err("Chain Specification doesn't contain any consensus_engine name".into())
 		}
 	}
+
+	/// The state of the finality gadget a light client needs to verify finality from the last
+	/// finalized block on, encoded. `None` if the chain has no finality gadget.
+	fn finality_sync_state(_client: &FullClient<Self>) -> Result<Option<Vec<u8>>, error::Error> {
+		Ok(None)
+	}
+
+	/// Proves the finality of blocks to external verifiers over RPC. `None` if the chain has no
+	/// finality gadget.
+	fn finality_proof_provider(
+		_client: Arc<FullClient<Self>>,
+	) -> Result<Option<Arc<FinalityProofProvider<FullComponents<Self>>>>, error::Error> {
+		Ok(None)
+	}
+
+	/// Decodes the runtime events of the imported blocks, for full nodes to index them. `None`
+	/// if the runtime events can't be decoded by the node.
+	fn event_decoder() -> Option<Arc<EventDecoder>> {
+		None
+	}
 }
 
 /// A collection of types and function to generalise over full / light client type.
@@ -304,7 +343,7 @@ pub trait Components: Sized + 'static {
 	/// Client executor.
 	type Executor: 'static + client::CallExecutor<FactoryBlock<Self::Factory>, Blake2Hasher> + Send + Sync + Clone;
 	/// Extrinsic pool type.
-	type TransactionPoolApi: 'static + txpool::ChainApi<
+	type TransactionPool: 'static + txpool::TransactionPool<
 		Hash = <<Self::Factory as ServiceFactory>::Block as BlockT>::Hash,
 		Block = FactoryBlock<Self::Factory>
 	>;
@@ -318,10 +357,11 @@ pub trait Components: Sized + 'static {
 	/// Our Import Queue
 	type ImportQueue: ImportQueue<FactoryBlock<Self::Factory>> + 'static;
 
-	/// Create client.
+	/// Create client. Offchain workers of a full client get access to `keystore`, if any.
 	fn build_client(
 		config: &FactoryFullConfiguration<Self::Factory>,
 		executor: CodeExecutor<Self::Factory>,
+		keystore: Option<Arc<Keystore>>,
 	) -> Result<
 		(
 			Arc<ComponentClient<Self>>,
@@ -332,13 +372,21 @@ pub trait Components: Sized + 'static {
 
 	/// Create extrinsic pool.
 	fn build_transaction_pool(config: TransactionPoolOptions, client: Arc<ComponentClient<Self>>)
-		-> Result<TransactionPool<Self::TransactionPoolApi>, error::Error>;
+		-> Result<Self::TransactionPool, error::Error>;
 
 	/// instance of import queue for clients
 	fn build_import_queue(
 		config: &mut FactoryFullConfiguration<Self::Factory>,
 		client: Arc<ComponentClient<Self>>
 	) -> Result<Self::ImportQueue, error::Error>;
+
+	/// Provider of finality proofs for the RPC, if any.
+	fn build_finality_proof_provider(
+		client: Arc<ComponentClient<Self>>
+	) -> Result<Option<Arc<FinalityProofProvider<Self>>>, error::Error>;
+
+	/// Decoder of the runtime events of the imported blocks, if they can be indexed.
+	fn build_event_decoder() -> Option<Arc<EventDecoder>>;
 }
 
 /// A struct that implement `Components` for the full client.
@@ -379,7 +427,7 @@ impl<Factory: ServiceFactory> Components for FullComponents<Factory> {
 	type Factory = Factory;
 	type Executor = FullExecutor<Factory>;
 	type Backend = FullBackend<Factory>;
-	type TransactionPoolApi = <Factory as ServiceFactory>::FullTransactionPoolApi;
+	type TransactionPool = <Factory as ServiceFactory>::FullTransactionPool;
 	type ImportQueue = Factory::FullImportQueue;
 	type RuntimeApi = Factory::RuntimeApi;
 	type RPC = Factory::FullService;
@@ -388,6 +436,7 @@ impl<Factory: ServiceFactory> Components for FullComponents<Factory> {
 	fn build_client(
 		config: &FactoryFullConfiguration<Factory>,
 		executor: CodeExecutor<Self::Factory>,
+		keystore: Option<Arc<Keystore>>,
 	)
 		-> Result<(
 			Arc<ComponentClient<Self>>,
@@ -399,17 +448,22 @@ impl<Factory: ServiceFactory> Components for FullComponents<Factory> {
 			path: config.database_path.as_str().into(),
 			pruning: config.pruning.clone(),
 		};
-		Ok((Arc::new(client_db::new_client(
+		let client = client_db::new_client(
 			db_settings,
 			executor,
 			&config.chain_spec,
 			config.block_execution_strategy,
 			config.api_execution_strategy,
-		)?), None))
+			keystore,
+		)?;
+		let client = client
+			.with_bad_blocks(config.chain_spec.bad_blocks().iter().cloned().collect())
+			.with_max_reorg_depth(config.max_reorg_depth);
+		Ok((Arc::new(client), None))
 	}
 
 	fn build_transaction_pool(config: TransactionPoolOptions, client: Arc<ComponentClient<Self>>)
-		-> Result<TransactionPool<Self::TransactionPoolApi>, error::Error>
+		-> Result<Self::TransactionPool, error::Error>
 	{
 		Factory::build_full_transaction_pool(config, client)
 	}
@@ -420,6 +474,16 @@ impl<Factory: ServiceFactory> Components for FullComponents<Factory> {
 	) -> Result<Self::ImportQueue, error::Error> {
 		Factory::build_full_import_queue(config, client)
 	}
+
+	fn build_finality_proof_provider(
+		client: Arc<ComponentClient<Self>>
+	) -> Result<Option<Arc<FinalityProofProvider<Self>>>, error::Error> {
+		Factory::finality_proof_provider(client)
+	}
+
+	fn build_event_decoder() -> Option<Arc<EventDecoder>> {
+		Factory::event_decoder()
+	}
 }
 
 /// A struct that implement `Components` for the light client.
@@ -454,7 +518,7 @@ impl<Factory: ServiceFactory> Components for LightComponents<Factory> {
 	type Factory = Factory;
 	type Executor = LightExecutor<Factory>;
 	type Backend = LightBackend<Factory>;
-	type TransactionPoolApi = <Factory as ServiceFactory>::LightTransactionPoolApi;
+	type TransactionPool = <Factory as ServiceFactory>::LightTransactionPool;
 	type ImportQueue = <Factory as ServiceFactory>::LightImportQueue;
 	type RuntimeApi = Factory::RuntimeApi;
 	type RPC = Factory::LightService;
@@ -463,6 +527,7 @@ impl<Factory: ServiceFactory> Components for LightComponents<Factory> {
 	fn build_client(
 		config: &FactoryFullConfiguration<Factory>,
 		executor: CodeExecutor<Self::Factory>,
+		_keystore: Option<Arc<Keystore>>,
 	)
 		-> Result<
 			(
@@ -480,12 +545,14 @@ impl<Factory: ServiceFactory> Components for LightComponents<Factory> {
 		let fetch_checker = Arc::new(client::light::new_fetch_checker::<_, Blake2Hasher, _, _, _>(light_blockchain.clone(), executor));
 		let fetcher = Arc::new(network::OnDemand::new(fetch_checker));
 		let client_backend = client::light::new_light_backend(light_blockchain, fetcher.clone());
-		let client = client::light::new_light(client_backend, fetcher.clone(), &config.chain_spec)?;
+		let client = client::light::new_light(client_backend, fetcher.clone(), &config.chain_spec)?
+			.with_bad_blocks(config.chain_spec.bad_blocks().iter().cloned().collect())
+			.with_max_reorg_depth(config.max_reorg_depth);
 		Ok((Arc::new(client), Some(fetcher)))
 	}
 
 	fn build_transaction_pool(config: TransactionPoolOptions, client: Arc<ComponentClient<Self>>)
-		-> Result<TransactionPool<Self::TransactionPoolApi>, error::Error>
+		-> Result<Self::TransactionPool, error::Error>
 	{
 		Factory::build_light_transaction_pool(config, client)
 	}
@@ -496,4 +563,16 @@ impl<Factory: ServiceFactory> Components for LightComponents<Factory> {
 	) -> Result<Self::ImportQueue, error::Error> {
 		Factory::build_light_import_queue(config, client)
 	}
+
+	fn build_finality_proof_provider(
+		_client: Arc<ComponentClient<Self>>
+	) -> Result<Option<Arc<FinalityProofProvider<Self>>>, error::Error> {
+		// light clients don't keep the justifications needed.
+		Ok(None)
+	}
+
+	fn build_event_decoder() -> Option<Arc<EventDecoder>> {
+		// light clients don't have the state of the imported blocks.
+		None
+	}
 }
@@ -21,10 +21,11 @@ use transaction_pool;
 use chain_spec::ChainSpec;
 pub use client::ExecutionStrategy;
 pub use client_db::PruningMode;
-pub use network::config::{NetworkConfiguration, Roles};
+pub use network::config::{NetworkConfiguration, Roles, RequestResponseConfig, NotificationsConfig};
 use runtime_primitives::BuildStorage;
 use serde::{Serialize, de::DeserializeOwned};
 use target_info::Target;
+pub use watchdog::WatchdogConfig;
 
 /// Service configuration.
 #[derive(Clone)]
@@ -41,8 +42,15 @@ pub struct Configuration<C, G: Serialize + DeserializeOwned + BuildStorage> {
 	pub transaction_pool: transaction_pool::txpool::Options,
 	/// Network configuration.
 	pub network: NetworkConfiguration,
+	/// Chain-specific request-response protocols to register with the network.
+	pub request_response_protocols: Vec<RequestResponseConfig>,
+	/// Chain-specific notifications protocols to register with the network.
+	pub notifications_protocols: Vec<NotificationsConfig>,
 	/// Path to key files.
 	pub keystore_path: String,
+	/// Derive the network node key from the `node` key of the keystore, unless the network
+	/// configuration gives one.
+	pub node_key_from_keystore: bool,
 	/// Path to the database.
 	pub database_path: String,
 	/// Pruning settings.
@@ -59,6 +67,13 @@ pub struct Configuration<C, G: Serialize + DeserializeOwned + BuildStorage> {
 	pub block_execution_strategy: ExecutionStrategy,
 	/// Runtime API execution strategy.
 	pub api_execution_strategy: ExecutionStrategy,
+	/// Maximum number of blocks of the best chain an imported block may revert. `None` if
+	/// unlimited.
+	pub max_reorg_depth: Option<u64>,
+	/// Index the runtime events of the imported blocks, to be looked up over RPC.
+	pub index_events: bool,
+	/// Watchdog warning about stalled block production and finality.
+	pub watchdog: WatchdogConfig,
 	/// RPC over HTTP binding address. `None` if disabled.
 	pub rpc_http: Option<SocketAddr>,
 	/// RPC over Websockets binding address. `None` if disabled.
@@ -79,13 +94,19 @@ impl<C: Default, G: Serialize + DeserializeOwned + BuildStorage> Configuration<C
 			roles: Roles::FULL,
 			transaction_pool: Default::default(),
 			network: Default::default(),
+			request_response_protocols: Default::default(),
+			notifications_protocols: Default::default(),
 			keystore_path: Default::default(),
+			node_key_from_keystore: false,
 			database_path: Default::default(),
 			keys: Default::default(),
 			custom: Default::default(),
 			pruning: PruningMode::default(),
 			block_execution_strategy: ExecutionStrategy::Both,
 			api_execution_strategy: ExecutionStrategy::Both,
+			max_reorg_depth: None,
+			index_events: false,
+			watchdog: Default::default(),
 			rpc_http: None,
 			rpc_ws: None,
 			telemetry_url: None,
@@ -19,18 +19,21 @@
 // FIXME: move this into substrate-consensus-common - https://github.com/paritytech/substrate/issues/1021
 
 use std::sync::Arc;
+use std::thread;
 use std::time::{self, Duration, Instant};
 use std;
 
 use client::{self, error, Client as SubstrateClient, CallExecutor};
-use client::{block_builder::api::BlockBuilder as BlockBuilderApi, runtime_api::{id::BLOCK_BUILDER, Core}};
+use client::block_builder::api::BlockBuilder as BlockBuilderApi;
+use client::runtime_api::{ApiExt, Core, ExecutionContext, OverlayedChanges};
 use codec::{Decode, Encode};
-use consensus_common::{self, evaluation, offline_tracker::OfflineTracker};
+use consensus_common::{self, evaluation, offline_tracker::OfflineTracker, Proposal};
+use futures::{future, Future, sync::oneshot};
 use primitives::{H256, AuthorityId, ed25519, Blake2Hasher};
 use runtime_primitives::traits::{Block as BlockT, Hash as HashT, Header as HeaderT, ProvideRuntimeApi};
 use runtime_primitives::generic::BlockId;
 use runtime_primitives::BasicInherentData;
-use transaction_pool::txpool::{self, Pool as TransactionPool};
+use transaction_pool::txpool::TransactionPool;
 
 use parking_lot::RwLock;
 
@@ -56,13 +59,14 @@ pub trait AuthoringApi: Send + Sync + ProvideRuntimeApi where
 	/// The error used by this API type.
 	type Error: std::error::Error;
 
-	/// Build a block on top of the given, with inherent extrinsics pre-pushed.
+	/// Build a block on top of the given, with inherent extrinsics pre-pushed. The block is
+	/// returned along with the storage changes made by building it.
 	fn build_block<F: FnMut(&mut BlockBuilder<Self::Block>) -> ()>(
 		&self,
 		at: &BlockId<Self::Block>,
 		inherent_data: BasicInherentData,
 		build_ctx: F,
-	) -> Result<Self::Block, error::Error>;
+	) -> Result<(Self::Block, OverlayedChanges), error::Error>;
 }
 
 impl<'a, B, E, Block, RA> BlockBuilder<Block>
@@ -92,27 +96,26 @@ impl<B, E, Block, RA> AuthoringApi for SubstrateClient<B, E, Block, RA> where
 		at: &BlockId<Self::Block>,
 		inherent_data: BasicInherentData,
 		mut build_ctx: F,
-	) -> Result<Self::Block, error::Error> {
-		let runtime_version = self.runtime_version_at(at)?;
-
+	) -> Result<(Self::Block, OverlayedChanges), error::Error> {
 		let mut block_builder = self.new_block_at(at)?;
-		if runtime_version.has_api(BLOCK_BUILDER, 1) {
-			self.runtime_api().inherent_extrinsics(at, &inherent_data)?
+		if self.runtime_api().has_api::<BlockBuilderApi<Block, BasicInherentData>>(at)? {
+			self.runtime_api()
+				.inherent_extrinsics_with_context(at, ExecutionContext::BlockConstruction, &inherent_data)?
 				.into_iter().try_for_each(|i| block_builder.push(i))?;
 		}
 
 		build_ctx(&mut block_builder);
 
-		block_builder.bake().map_err(Into::into)
+		block_builder.bake_with_changes().map_err(Into::into)
 	}
 }
 
 /// Proposer factory.
-pub struct ProposerFactory<C, A> where A: txpool::ChainApi {
+pub struct ProposerFactory<C, A> where A: TransactionPool {
 	/// The client instance.
 	pub client: Arc<C>,
 	/// The transaction pool.
-	pub transaction_pool: Arc<TransactionPool<A>>,
+	pub transaction_pool: Arc<A>,
 	/// Offline-tracker.
 	pub offline: SharedOfflineTracker,
 	/// Force delay in evaluation this long.
@@ -120,9 +123,9 @@ pub struct ProposerFactory<C, A> where A: txpool::ChainApi {
 }
 
 impl<C, A> consensus_common::Environment<<C as AuthoringApi>::Block> for ProposerFactory<C, A> where
-	C: AuthoringApi,
+	C: AuthoringApi + 'static,
 	<C as ProvideRuntimeApi>::Api: BlockBuilderApi<<C as AuthoringApi>::Block, BasicInherentData>,
-	A: txpool::ChainApi<Block=<C as AuthoringApi>::Block>,
+	A: TransactionPool<Block=<C as AuthoringApi>::Block> + 'static,
 	client::error::Error: From<<C as AuthoringApi>::Error>
 {
 	type Proposer = Proposer<<C as AuthoringApi>::Block, C, A>;
@@ -161,29 +164,79 @@ impl<C, A> consensus_common::Environment<<C as AuthoringApi>::Block> for Propose
 }
 
 /// The proposer logic.
-pub struct Proposer<Block: BlockT, C, A: txpool::ChainApi> {
+pub struct Proposer<Block: BlockT, C, A: TransactionPool> {
 	client: Arc<C>,
 	start: Instant,
 	parent_hash: <Block as BlockT>::Hash,
 	parent_id: BlockId<Block>,
 	parent_number: <<Block as BlockT>::Header as HeaderT>::Number,
-	transaction_pool: Arc<TransactionPool<A>>,
+	transaction_pool: Arc<A>,
 	offline: SharedOfflineTracker,
 	authorities: Vec<AuthorityId>,
 	minimum_timestamp: u64,
 }
 
+impl<Block: BlockT, C, A: TransactionPool> Clone for Proposer<Block, C, A> {
+	fn clone(&self) -> Self {
+		Proposer {
+			client: self.client.clone(),
+			start: self.start,
+			parent_hash: self.parent_hash,
+			parent_id: self.parent_id.clone(),
+			parent_number: self.parent_number.clone(),
+			transaction_pool: self.transaction_pool.clone(),
+			offline: self.offline.clone(),
+			authorities: self.authorities.clone(),
+			minimum_timestamp: self.minimum_timestamp,
+		}
+	}
+}
+
 impl<Block, C, A> consensus_common::Proposer<<C as AuthoringApi>::Block> for Proposer<Block, C, A> where
 	Block: BlockT,
-	C: AuthoringApi<Block=Block>,
+	C: AuthoringApi<Block=Block> + 'static,
 	<C as ProvideRuntimeApi>::Api: BlockBuilderApi<Block, BasicInherentData>,
-	A: txpool::ChainApi<Block=Block>,
+	A: TransactionPool<Block=Block> + 'static,
 	client::error::Error: From<<C as AuthoringApi>::Error>
 {
-	type Create = Result<<C as AuthoringApi>::Block, error::Error>;
+	type Create = Box<Future<Item=Proposal<<C as AuthoringApi>::Block>, Error=error::Error> + Send>;
 	type Error = error::Error;
 
-	fn propose(&self) -> Result<<C as AuthoringApi>::Block, error::Error> {
+	fn propose(&self, deadline: Instant) -> Self::Create {
+		let proposer = self.clone();
+		let (sender, receiver) = oneshot::channel();
+
+		// block construction runs on its own thread, so that it doesn't hold up the caller.
+		let spawned = thread::Builder::new()
+			.name("proposer".into())
+			.spawn(move || {
+				let _ = sender.send(proposer.propose_with(deadline));
+			});
+
+		if let Err(e) = spawned {
+			return Box::new(future::err(error::Error::from(format!("Unable to spawn proposer thread: {}", e))));
+		}
+
+		Box::new(receiver.then(|res| match res {
+			Ok(res) => res,
+			Err(_) => Err("Proposer thread terminated before finishing the block".into()),
+		}))
+	}
+
+	fn would_be_empty(&self) -> bool {
+		self.transaction_pool.ready().next().is_none()
+			&& self.offline.read().reports(&self.authorities[..]).is_empty()
+	}
+}
+
+impl<Block, C, A> Proposer<Block, C, A> where
+	Block: BlockT,
+	C: AuthoringApi<Block=Block>,
+	<C as ProvideRuntimeApi>::Api: BlockBuilderApi<Block, BasicInherentData>,
+	A: TransactionPool<Block=Block>,
+	client::error::Error: From<<C as AuthoringApi>::Error>
+{
+	fn propose_with(&self, deadline: Instant) -> Result<Proposal<<C as AuthoringApi>::Block>, error::Error> {
 		use runtime_primitives::traits::BlakeTwo256;
 
 		const MAX_VOTE_OFFLINE_SECONDS: Duration = Duration::from_secs(60);
@@ -205,7 +258,7 @@ impl<Block, C, A> consensus_common::Proposer<<C as AuthoringApi>::Block> for Pro
 
 		let inherent_data = BasicInherentData::new(timestamp, offline_indices);
 
-		let block = self.client.build_block(
+		let (block, storage_changes) = self.client.build_block(
 			&self.parent_id,
 			inherent_data,
 			|block_builder| {
@@ -214,6 +267,11 @@ impl<Block, C, A> consensus_common::Proposer<<C as AuthoringApi>::Block> for Pro
 				let pending_iterator = self.transaction_pool.ready();
 
 				for pending in pending_iterator {
+					if Instant::now() >= deadline {
+						debug!("Deadline reached while including transactions; proposing what we have");
+						break;
+					}
+
 					// TODO [ToDr] Probably get rid of it, and validate in runtime.
 					let encoded_size = pending.data.encode().len();
 					if pending_size + encoded_size >= MAX_TRANSACTIONS_SIZE { break }
@@ -251,7 +309,7 @@ impl<Block, C, A> consensus_common::Proposer<<C as AuthoringApi>::Block> for Pro
 			self.parent_number,
 		).is_ok());
 
-		Ok(substrate_block)
+		Ok(Proposal { block: substrate_block, storage_changes: Some(storage_changes) })
 	}
 }
 
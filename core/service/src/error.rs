@@ -19,6 +19,7 @@
 use client;
 use network;
 use keystore;
+use rpc_externalities;
 
 error_chain! {
 	foreign_links {
@@ -28,6 +29,7 @@ error_chain! {
 		Client(client::error::Error, client::error::ErrorKind) #[doc="Client error"];
 		Network(network::error::Error, network::error::ErrorKind) #[doc="Network error"];
 		Keystore(keystore::Error, keystore::ErrorKind) #[doc="Keystore error"];
+		RemoteExternalities(rpc_externalities::Error, rpc_externalities::ErrorKind) #[doc="Remote externalities error"];
 	}
 
 	errors {
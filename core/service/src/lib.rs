@@ -36,6 +36,7 @@ extern crate parity_codec as codec;
 extern crate substrate_transaction_pool as transaction_pool;
 extern crate substrate_rpc;
 extern crate substrate_rpc_servers as rpc;
+extern crate substrate_rpc_externalities as rpc_externalities;
 extern crate target_info;
 extern crate tokio;
 
@@ -56,10 +57,12 @@ mod chain_spec;
 pub mod config;
 pub mod chain_ops;
 pub mod consensus;
+pub mod watchdog;
 
 use std::io;
 use std::net::SocketAddr;
 use std::collections::HashMap;
+use std::time::{Duration, Instant};
 #[doc(hidden)]
 pub use std::{ops::Deref, result::Result, sync::Arc};
 use futures::prelude::*;
@@ -73,33 +76,36 @@ use exit_future::Signal;
 pub use tokio::runtime::TaskExecutor;
 use substrate_executor::NativeExecutor;
 use codec::{Encode, Decode};
+use tokio::timer::Interval;
 
 pub use self::error::{ErrorKind, Error};
-pub use config::{Configuration, Roles, PruningMode};
-pub use chain_spec::{ChainSpec, Properties};
-pub use transaction_pool::txpool::{self, Pool as TransactionPool, Options as TransactionPoolOptions, ChainApi, IntoPoolError};
+pub use config::{Configuration, Roles, PruningMode, WatchdogConfig};
+pub use chain_spec::{ChainSpec, ChainType, Properties, LightSyncState};
+pub use transaction_pool::txpool::{self, Pool, TransactionPool, Options as TransactionPoolOptions, ChainApi, IntoPoolError};
 pub use client::ExecutionStrategy;
 
 pub use consensus::ProposerFactory;
 pub use components::{ServiceFactory, FullBackend, FullExecutor, LightBackend,
-	LightExecutor, Components, PoolApi, ComponentClient,
+	LightExecutor, Components, ComponentTransactionPool, ComponentClient,
 	ComponentBlock, FullClient, LightClient, FullComponents, LightComponents,
 	CodeExecutor, NetworkService, FactoryChainSpec, FactoryBlock,
 	FactoryFullConfiguration, RuntimeGenesis, FactoryGenesis,
-	ComponentExHash, ComponentExtrinsic, FactoryExtrinsic
+	ComponentExHash, ComponentExtrinsic, FactoryExtrinsic, FinalityProofProvider, EventIndex
 };
+pub use rpc::apis::events::{EventDecoder, EventKeys};
 use components::{StartRPC, CreateNetworkParams};
 #[doc(hidden)]
 pub use network::OnDemand;
 
 const DEFAULT_PROTOCOL_ID: &'static str = "sup";
+const WATCHDOG_INTERVAL_MS: u64 = 1000;
 
 /// Substrate service.
 pub struct Service<Components: components::Components> {
 	client: Arc<ComponentClient<Components>>,
 	network: Option<Arc<components::NetworkService<Components::Factory>>>,
-	transaction_pool: Arc<TransactionPool<Components::TransactionPoolApi>>,
-	keystore: Keystore,
+	transaction_pool: Arc<Components::TransactionPool>,
+	keystore: Arc<Keystore>,
 	exit: ::exit_future::Exit,
 	signal: Option<Signal>,
 	/// Configuration of this Service
@@ -117,6 +123,7 @@ pub fn new_client<Factory: components::ServiceFactory>(config: &FactoryFullConfi
 	let (client, _) = components::FullComponents::<Factory>::build_client(
 		config,
 		executor,
+		None,
 	)?;
 	Ok(client)
 }
@@ -158,7 +165,12 @@ impl<Components> Service<Components>
 			}
 		};
 
-		let (client, on_demand) = Components::build_client(&config, executor)?;
+		if config.node_key_from_keystore && config.network.use_secret.is_none() {
+			config.network.use_secret = Some(keystore.node_secret()?);
+		}
+
+		let keystore = Arc::new(keystore);
+		let (client, on_demand) = Components::build_client(&config, executor, Some(keystore.clone()))?;
 		let import_queue = Arc::new(Components::build_import_queue(&mut config, client.clone())?);
 		let best_header = client.best_block_header()?;
 
@@ -176,9 +188,15 @@ impl<Components> Service<Components>
 			client: client.clone(),
 		 };
 
+		let protocol_config = network::config::ProtocolConfig {
+			roles: config.roles,
+			request_response_protocols: config.request_response_protocols.clone(),
+			notifications_protocols: config.notifications_protocols.clone(),
+			..Default::default()
+		};
 		let network_params = Components::CreateNetworkParams::create_network_params(
 			client.clone(),
-			config.roles,
+			protocol_config,
 			config.network.clone(),
 			on_demand.clone(),
 			transaction_pool_adapter,
@@ -222,6 +240,21 @@ impl<Components> Service<Components>
 			task_executor.spawn(events);
 		}
 
+		{
+			// finality notifications
+			let txpool = transaction_pool.clone();
+
+			let events = client.finality_notification_stream()
+				.for_each(move |notification| {
+					txpool.on_finalized(&BlockId::hash(notification.hash), notification.tree_route)
+						.map_err(|e| warn!("Error handling finalized block in the transaction pool: {:?}", e))?;
+					Ok(())
+				})
+				.select(exit.clone())
+				.then(|_| Ok(()));
+			task_executor.spawn(events);
+		}
+
 		{
 			// extrinsic notifications
 			let network = Arc::downgrade(&network);
@@ -239,12 +272,74 @@ impl<Components> Service<Components>
 			task_executor.spawn(events);
 		}
 
+		// stalled block production and finality
+		if config.watchdog.is_enabled() {
+			let watchdog = Arc::new(Mutex::new(watchdog::Watchdog::new(&config.watchdog, Instant::now())));
+			let watchdog_config = Arc::new(config.watchdog.clone());
+
+			let (note_best, note_finalized) = {
+				let note = |subject| {
+					let watchdog = watchdog.clone();
+					let watchdog_config = watchdog_config.clone();
+					let network = Arc::downgrade(&network);
+					move || {
+						if let Some(report) = watchdog.lock().note(subject, Instant::now()) {
+							watchdog::report(&watchdog_config, report, &network, |network| network.restart_sync());
+						}
+					}
+				};
+				(note(watchdog::Subject::BestBlock), note(watchdog::Subject::Finality))
+			};
+
+			let best_blocks = client.import_notification_stream()
+				.filter(|notification| notification.is_new_best)
+				.for_each(move |_| { note_best(); Ok(()) });
+			let finalized_blocks = client.finality_notification_stream()
+				.for_each(move |_| { note_finalized(); Ok(()) });
+
+			let network = Arc::downgrade(&network);
+			let interval = Duration::from_millis(WATCHDOG_INTERVAL_MS);
+			let checks = Interval::new(Instant::now() + interval, interval)
+				.map_err(|e| debug!("Watchdog timer error: {:?}", e))
+				.for_each(move |now| {
+					for report in watchdog.lock().check(now) {
+						watchdog::report(&watchdog_config, report, &network, |network| network.restart_sync());
+					}
+					Ok(())
+				});
+
+			task_executor.spawn(best_blocks.select(exit.clone()).then(|_| Ok(())));
+			task_executor.spawn(finalized_blocks.select(exit.clone()).then(|_| Ok(())));
+			task_executor.spawn(checks.select(exit.clone()).then(|_| Ok(())));
+		}
+
+		// runtime events index
+		let event_index = if config.index_events {
+			match Components::build_event_decoder() {
+				Some(decoder) => {
+					let index = Arc::new(EventIndex::<Components>::new(decoder));
+					let events = rpc::apis::events::index_imported_blocks(client.clone(), index.clone())
+						.select(exit.clone())
+						.then(|_| Ok(()));
+					task_executor.spawn(events);
+					Some(index)
+				},
+				None => {
+					warn!("Indexing of runtime events requested, but this node can't decode them");
+					None
+				},
+			}
+		} else {
+			None
+		};
 
 		// RPC
+		let finality_proof_provider = Components::build_finality_proof_provider(client.clone())?;
 		let (rpc_http, rpc_ws) = Components::RPC::start_rpc(
-			client.clone(), config.chain_spec.name().to_string(), config.impl_name,
+			client.clone(), config.chain_spec.name().to_string(),
+			config.chain_spec.chain_type().to_string(), config.impl_name,
 			config.impl_version, config.rpc_http, config.rpc_ws, config.chain_spec.properties(),
-			task_executor.clone(), transaction_pool.clone()
+			task_executor.clone(), transaction_pool.clone(), finality_proof_provider, event_index,
 		)?;
 
 		// Telemetry
@@ -314,7 +409,7 @@ impl<Components> Service<Components> where Components: components::Components {
 	}
 
 	/// Get shared extrinsic pool instance.
-	pub fn transaction_pool(&self) -> Arc<TransactionPool<Components::TransactionPoolApi>> {
+	pub fn transaction_pool(&self) -> Arc<Components::TransactionPool> {
 		self.transaction_pool.clone()
 	}
 
@@ -363,6 +458,7 @@ fn maybe_start_server<T, F>(address: Option<SocketAddr>, start: F) -> Result<Opt
 #[derive(Clone)]
 struct RpcConfig {
 	chain_name: String,
+	chain_type: String,
 	properties: Properties,
 	impl_name: &'static str,
 	impl_version: &'static str,
@@ -381,6 +477,10 @@ impl substrate_rpc::system::SystemApi for RpcConfig {
 		Ok(self.chain_name.clone())
 	}
 
+	fn system_chain_type(&self) -> substrate_rpc::system::error::Result<String> {
+		Ok(self.chain_type.clone())
+	}
+
 	fn system_properties(&self) -> substrate_rpc::system::error::Result<Properties> {
 		Ok(self.properties.clone())
 	}
@@ -389,7 +489,7 @@ impl substrate_rpc::system::SystemApi for RpcConfig {
 /// Transaction pool adapter.
 pub struct TransactionPoolAdapter<C: Components> {
 	imports_external_transactions: bool,
-	pool: Arc<TransactionPool<C::TransactionPoolApi>>,
+	pool: Arc<C::TransactionPool>,
 	client: Arc<ComponentClient<C>>,
 }
 
@@ -468,10 +568,10 @@ impl<C: Components> network::TransactionPool<ComponentExHash<C>, ComponentBlock<
 ///         // Declare the network protocol and give an initializer.
 /// 		NetworkProtocol = NodeProtocol { |config| Ok(NodeProtocol::new()) },
 /// 		RuntimeDispatch = node_executor::Executor,
-/// 		FullTransactionPoolApi = transaction_pool::ChainApi<FullBackend<Self>, FullExecutor<Self>, Block>
-/// 			{ |config, client| Ok(TransactionPool::new(config, transaction_pool::ChainApi::new(client))) },
-/// 		LightTransactionPoolApi = transaction_pool::ChainApi<LightBackend<Self>, LightExecutor<Self>, Block>
-/// 			{ |config, client| Ok(TransactionPool::new(config, transaction_pool::ChainApi::new(client))) },
+/// 		FullTransactionPool = Pool<transaction_pool::ChainApi<FullBackend<Self>, FullExecutor<Self>, Block>>
+/// 			{ |config, client| Ok(Pool::new(config, transaction_pool::ChainApi::new(client))) },
+/// 		LightTransactionPool = Pool<transaction_pool::ChainApi<LightBackend<Self>, LightExecutor<Self>, Block>>
+/// 			{ |config, client| Ok(Pool::new(config, transaction_pool::ChainApi::new(client))) },
 /// 		Genesis = GenesisConfig,
 /// 		Configuration = (),
 /// 		FullService = Service<FullComponents<Self>>
@@ -485,8 +585,8 @@ impl<C: Components> network::TransactionPool<ComponentExHash<C>, ComponentBlock<
 ///         // The first one is for the initializing the full import queue and the second for the
 ///         // light import queue.
 /// 		ImportQueue = BasicQueue<Block, NoneVerifier>
-/// 			{ |_, client| Ok(BasicQueue::new(Arc::new(NoneVerifier {}, client))) }
-/// 			{ |_, client| Ok(BasicQueue::new(Arc::new(NoneVerifier {}, client))) },
+/// 			{ |_, client| Ok(BasicQueue::new(Arc::new(NoneVerifier {}), client, None)) }
+/// 			{ |_, client| Ok(BasicQueue::new(Arc::new(NoneVerifier {}), client, None)) },
 /// 	}
 /// }
 /// ```
@@ -499,8 +599,8 @@ macro_rules! construct_service_factory {
 			RuntimeApi = $runtime_api:ty,
 			NetworkProtocol = $protocol:ty { $( $protocol_init:tt )* },
 			RuntimeDispatch = $dispatch:ty,
-			FullTransactionPoolApi = $full_transaction:ty { $( $full_transaction_init:tt )* },
-			LightTransactionPoolApi = $light_transaction:ty { $( $light_transaction_init:tt )* },
+			FullTransactionPool = $full_transaction:ty { $( $full_transaction_init:tt )* },
+			LightTransactionPool = $light_transaction:ty { $( $light_transaction_init:tt )* },
 			Genesis = $genesis:ty,
 			Configuration = $config:ty,
 			FullService = $full_service:ty { $( $full_service_init:tt )* },
@@ -510,6 +610,9 @@ macro_rules! construct_service_factory {
 				{ $( $full_import_queue_init:tt )* },
 			LightImportQueue = $light_import_queue:ty
 				{ $( $light_import_queue_init:tt )* },
+			$( FinalitySyncState = { $( $finality_sync_state:tt )* }, )*
+			$( FinalityProofProvider = { $( $finality_proof_provider:tt )* }, )*
+			$( EventDecoder = { $( $event_decoder:tt )* }, )*
 		}
 	) => {
 		$( #[$attr] )*
@@ -521,8 +624,8 @@ macro_rules! construct_service_factory {
 			type RuntimeApi = $runtime_api;
 			type NetworkProtocol = $protocol;
 			type RuntimeDispatch = $dispatch;
-			type FullTransactionPoolApi = $full_transaction;
-			type LightTransactionPoolApi = $light_transaction;
+			type FullTransactionPool = $full_transaction;
+			type LightTransactionPool = $light_transaction;
 			type Genesis = $genesis;
 			type Configuration = $config;
 			type FullService = $full_service;
@@ -533,7 +636,7 @@ macro_rules! construct_service_factory {
 			fn build_full_transaction_pool(
 				config: $crate::TransactionPoolOptions,
 				client: $crate::Arc<$crate::FullClient<Self>>
-			) -> $crate::Result<$crate::TransactionPool<Self::FullTransactionPoolApi>, $crate::Error>
+			) -> $crate::Result<Self::FullTransactionPool, $crate::Error>
 			{
 				( $( $full_transaction_init )* ) (config, client)
 			}
@@ -541,7 +644,7 @@ macro_rules! construct_service_factory {
 			fn build_light_transaction_pool(
 				config: $crate::TransactionPoolOptions,
 				client: $crate::Arc<$crate::LightClient<Self>>
-			) -> $crate::Result<$crate::TransactionPool<Self::LightTransactionPoolApi>, $crate::Error>
+			) -> $crate::Result<Self::LightTransactionPool, $crate::Error>
 			{
 				( $( $light_transaction_init )* ) (config, client)
 			}
@@ -566,6 +669,31 @@ macro_rules! construct_service_factory {
 				( $( $light_import_queue_init )* ) (config, client)
 			}
 
+			$(
+				fn finality_sync_state(
+					client: &$crate::FullClient<Self>,
+				) -> $crate::Result<Option<Vec<u8>>, $crate::Error> {
+					( $( $finality_sync_state )* ) (client)
+				}
+			)*
+
+			$(
+				fn finality_proof_provider(
+					client: $crate::Arc<$crate::FullClient<Self>>,
+				) -> $crate::Result<
+					Option<$crate::Arc<$crate::FinalityProofProvider<$crate::FullComponents<Self>>>>,
+					$crate::Error
+				> {
+					( $( $finality_proof_provider )* ) (client)
+				}
+			)*
+
+			$(
+				fn event_decoder() -> Option<$crate::Arc<$crate::EventDecoder>> {
+					( $( $event_decoder )* ) ()
+				}
+			)*
+
 			fn new_light(
 				config: $crate::FactoryFullConfiguration<Self>,
 				executor: $crate::TaskExecutor
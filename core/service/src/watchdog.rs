@@ -0,0 +1,214 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+
+//! Watchdog warning about stalled block production and finality.
+//!
+//! Every stall is reported once when it starts and once when it ends, as a log warning and a
+//! telemetry event.
+
+use std::sync::Weak;
+use std::time::{Duration, Instant};
+
+/// Configuration of the watchdog. Both checks are disabled by default.
+#[derive(Clone, Debug, Default)]
+pub struct WatchdogConfig {
+	/// Warn when no new best block has been imported for this long. `None` if disabled.
+	pub best_block_stall: Option<Duration>,
+	/// Warn when no block has been finalized for this long. `None` if disabled.
+	pub finality_stall: Option<Duration>,
+	/// Restart the sync when the best block stalls.
+	pub restart_sync: bool,
+}
+
+impl WatchdogConfig {
+	/// Whether any check is enabled.
+	pub fn is_enabled(&self) -> bool {
+		self.best_block_stall.is_some() || self.finality_stall.is_some()
+	}
+}
+
+/// What the watchdog checks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Subject {
+	/// Import of new best blocks.
+	BestBlock,
+	/// Finalization of blocks.
+	Finality,
+}
+
+impl Subject {
+	fn name(&self) -> &'static str {
+		match *self {
+			Subject::BestBlock => "best_block",
+			Subject::Finality => "finality",
+		}
+	}
+}
+
+/// A change of the state of a subject found by a check of the watchdog.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Report {
+	/// Nothing was observed of the subject for the given duration.
+	Stalled(Subject, Duration),
+	/// The subject was observed again after a stall.
+	Resumed(Subject),
+}
+
+struct Timer {
+	limit: Duration,
+	last_seen: Instant,
+	stalled: bool,
+}
+
+impl Timer {
+	fn new(limit: Duration, now: Instant) -> Self {
+		Timer { limit, last_seen: now, stalled: false }
+	}
+
+	fn note(&mut self, now: Instant) -> bool {
+		self.last_seen = now;
+		::std::mem::replace(&mut self.stalled, false)
+	}
+
+	fn check(&mut self, now: Instant) -> Option<Duration> {
+		let elapsed = now.duration_since(self.last_seen);
+		if self.stalled || elapsed < self.limit {
+			return None;
+		}
+		self.stalled = true;
+		Some(elapsed)
+	}
+}
+
+/// Keeps track of when new best and finalized blocks were last observed.
+pub struct Watchdog {
+	best_block: Option<Timer>,
+	finality: Option<Timer>,
+}
+
+impl Watchdog {
+	/// Create a watchdog, considering both subjects observed at `now`.
+	pub fn new(config: &WatchdogConfig, now: Instant) -> Self {
+		Watchdog {
+			best_block: config.best_block_stall.map(|limit| Timer::new(limit, now)),
+			finality: config.finality_stall.map(|limit| Timer::new(limit, now)),
+		}
+	}
+
+	/// Note that the subject was observed at `now`, reporting whether it resumed after a stall.
+	pub fn note(&mut self, subject: Subject, now: Instant) -> Option<Report> {
+		let timer = match subject {
+			Subject::BestBlock => self.best_block.as_mut(),
+			Subject::Finality => self.finality.as_mut(),
+		};
+		match timer.map(|timer| timer.note(now)) {
+			Some(true) => Some(Report::Resumed(subject)),
+			_ => None,
+		}
+	}
+
+	/// Check for subjects which stalled since the last check.
+	pub fn check(&mut self, now: Instant) -> Vec<Report> {
+		let mut reports = Vec::new();
+		if let Some(elapsed) = self.best_block.as_mut().and_then(|timer| timer.check(now)) {
+			reports.push(Report::Stalled(Subject::BestBlock, elapsed));
+		}
+		if let Some(elapsed) = self.finality.as_mut().and_then(|timer| timer.check(now)) {
+			reports.push(Report::Stalled(Subject::Finality, elapsed));
+		}
+		reports
+	}
+}
+
+/// Log the report and send it to the telemetry. Restarts the sync through `restart_sync` if
+/// the best block stalled and the configuration asks for it.
+pub fn report<N, F>(config: &WatchdogConfig, report: Report, network: &Weak<N>, restart_sync: F) where
+	F: Fn(&N),
+{
+	match report {
+		Report::Stalled(subject, elapsed) => {
+			warn!("No {} progress for {} seconds", subject.name().replace('_', " "), elapsed.as_secs());
+			telemetry!("watchdog.stalled"; "subject" => subject.name(), "secs" => elapsed.as_secs());
+			if subject == Subject::BestBlock && config.restart_sync {
+				if let Some(network) = network.upgrade() {
+					info!("Restarting sync after stalled block import");
+					restart_sync(&network);
+				}
+			}
+		},
+		Report::Resumed(subject) => {
+			info!("{} progress resumed", subject.name().replace('_', " "));
+			telemetry!("watchdog.resumed"; "subject" => subject.name());
+		},
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn config() -> WatchdogConfig {
+		WatchdogConfig {
+			best_block_stall: Some(Duration::from_secs(10)),
+			finality_stall: Some(Duration::from_secs(60)),
+			restart_sync: false,
+		}
+	}
+
+	#[test]
+	fn stalls_are_reported_once() {
+		let start = Instant::now();
+		let mut watchdog = Watchdog::new(&config(), start);
+
+		assert!(watchdog.check(start + Duration::from_secs(9)).is_empty());
+		assert_eq!(
+			watchdog.check(start + Duration::from_secs(11)),
+			vec![Report::Stalled(Subject::BestBlock, Duration::from_secs(11))]
+		);
+		assert!(watchdog.check(start + Duration::from_secs(12)).is_empty());
+		assert_eq!(
+			watchdog.check(start + Duration::from_secs(60)),
+			vec![Report::Stalled(Subject::Finality, Duration::from_secs(60))]
+		);
+	}
+
+	#[test]
+	fn observations_reset_the_timers() {
+		let start = Instant::now();
+		let mut watchdog = Watchdog::new(&config(), start);
+
+		assert_eq!(watchdog.note(Subject::BestBlock, start + Duration::from_secs(5)), None);
+		assert!(watchdog.check(start + Duration::from_secs(11)).is_empty());
+		assert_eq!(watchdog.check(start + Duration::from_secs(15)).len(), 1);
+
+		assert_eq!(
+			watchdog.note(Subject::BestBlock, start + Duration::from_secs(16)),
+			Some(Report::Resumed(Subject::BestBlock))
+		);
+		assert!(watchdog.check(start + Duration::from_secs(25)).is_empty());
+		assert_eq!(watchdog.check(start + Duration::from_secs(26)).len(), 1);
+	}
+
+	#[test]
+	fn disabled_checks_never_report() {
+		let start = Instant::now();
+		let mut watchdog = Watchdog::new(&Default::default(), start);
+
+		assert!(watchdog.check(start + Duration::from_secs(3600)).is_empty());
+		assert_eq!(watchdog.note(Subject::Finality, start), None);
+	}
+}
@@ -46,6 +46,7 @@ use service::{
 	FactoryChainSpec,
 	Roles,
 	FactoryExtrinsic,
+	TransactionPool,
 };
 use network::{Protocol, SyncProvider, ManageNetwork};
 use network::config::{NetworkConfiguration, NonReservedPeerMode};
@@ -121,7 +122,10 @@ fn node_config<F: ServiceFactory> (
 		roles: role,
 		transaction_pool: Default::default(),
 		network: network_config,
+		request_response_protocols: Vec::new(),
+		notifications_protocols: Vec::new(),
 		keystore_path: root.join("key").to_str().unwrap().into(),
+		node_key_from_keystore: false,
 		database_path: root.join("db").to_str().unwrap().into(),
 		pruning: Default::default(),
 		keys: keys,
@@ -130,6 +134,9 @@ fn node_config<F: ServiceFactory> (
 		name: format!("Node {}", index),
 		block_execution_strategy: ExecutionStrategy::NativeWhenPossible,
 		api_execution_strategy: ExecutionStrategy::NativeWhenPossible,
+		max_reorg_depth: None,
+		index_events: false,
+		watchdog: Default::default(),
 		rpc_http: None,
 		rpc_ws: None,
 		telemetry_url: None,
@@ -15,6 +15,8 @@
 // along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
 
 //! Compile fail tests.
+//!
+//! Tests checking the error reported by `impl_runtime_apis!` are in `tests/ui`.
 
 mod declaring_own_block {
 	/*!
@@ -406,3 +408,101 @@ mod impl_incorrect_method_signature {
 	```
 	*/
 }
+
+mod impl_missing_method {
+	/*!
+	```compile_fail,E0046
+		#[macro_use]
+		extern crate substrate_client;
+		extern crate sr_primitives as runtime_primitives;
+		extern crate substrate_primitives as primitives;
+		#[macro_use]
+		extern crate parity_codec_derive;
+		extern crate serde;
+		extern crate core;
+
+		use primitives::hash::H256;
+		use runtime_primitives::traits::{BlakeTwo256, GetNodeBlockType, Extrinsic as ExtrinsicT};
+
+		// All the stuff we need to declare our `Block`
+		pub type BlockNumber = u64;
+		pub type DigestItem = runtime_primitives::generic::DigestItem<H256, u64>;
+		pub type Digest = runtime_primitives::generic::Digest<DigestItem>;
+		#[derive(Clone, PartialEq, Eq, Encode, Decode, Debug)]
+		pub struct Extrinsic {}
+		impl serde::Serialize for Extrinsic
+		{
+			fn serialize<S>(&self, seq: S) -> Result<S::Ok, S::Error> where S: ::serde::Serializer {
+				unimplemented!()
+			}
+		}
+		impl ExtrinsicT for Extrinsic {
+			fn is_signed(&self) -> Option<bool> {
+				unimplemented!()
+			}
+		}
+		pub type Header = runtime_primitives::generic::Header<BlockNumber, BlakeTwo256, DigestItem>;
+		pub type Block = runtime_primitives::generic::Block<Header, Extrinsic>;
+
+		/// The declaration of the `Runtime` type and the implementation of the `GetNodeBlockType`
+		/// trait are done by the `construct_runtime!` macro in a real runtime.
+		struct Runtime {}
+		impl GetNodeBlockType for Runtime {
+			type NodeBlock = Block;
+		}
+
+		decl_runtime_apis! {
+			pub trait Api {
+				fn test(data: u64);
+				fn test2(data: u64);
+			}
+		}
+
+		impl_runtime_apis! {
+			impl self::Api<Block> for Runtime {
+				fn test(data: u64) {}
+			}
+		}
+
+		fn main() {}
+	```
+	*/
+}
+
+mod invalid_api_version {
+	/*!
+	```compile_fail
+		#[macro_use]
+		extern crate substrate_client;
+		extern crate sr_primitives as runtime_primitives;
+
+		decl_runtime_apis! {
+			#[api_version]
+			pub trait Api {
+				fn test(data: u64);
+			}
+		}
+
+		fn main() {}
+	```
+	*/
+}
+
+mod invalid_api_version_2 {
+	/*!
+	```compile_fail
+		#[macro_use]
+		extern crate substrate_client;
+		extern crate sr_primitives as runtime_primitives;
+
+		decl_runtime_apis! {
+			#[api_version("1")]
+			pub trait Api {
+				fn test(data: u64);
+			}
+		}
+
+		fn main() {}
+	```
+	*/
+}
@@ -16,7 +16,8 @@
 
 use utils::{
 	generate_crate_access, generate_hidden_includes, generate_runtime_mod_name_for_trait,
-	fold_fn_decl_for_client_side
+	fold_fn_decl_for_client_side, unwrap_or_error, generate_unique_pattern,
+	generate_method_with_context_name, add_execution_context_to_fn_decl
 };
 
 use proc_macro;
@@ -27,12 +28,18 @@ use quote::quote;
 use syn::{
 	spanned::Spanned, parse_macro_input, parse::{Parse, ParseStream, Result, Error},
 	fold::{self, Fold}, FnDecl, parse_quote, ItemTrait, Generics, GenericParam, Attribute,
-	visit::{Visit, self}, FnArg, Pat, TraitBound, Type
+	visit::{Visit, self}, FnArg, Pat, TraitBound, Type, Meta, NestedMeta, Lit, TraitItem,
+	TraitItemMethod,
 };
 
+use blake2_rfc;
+
 /// Unique identifier used to make the hidden includes unique for this macro.
 const HIDDEN_INCLUDES_ID: &str = "DECL_RUNTIME_APIS";
 
+/// The name of the attribute that declares the version of a runtime api.
+const API_VERSION_ATTRIBUTE: &str = "api_version";
+
 /// The structure used for parsing the runtime api declarations.
 struct RuntimeApiDecls {
 	decls: Vec<ItemTrait>,
@@ -70,8 +77,57 @@ fn remove_core_trait_attribute(attrs: &mut Vec<Attribute>) -> bool {
 	found
 }
 
+// Extract the version given by the `api_version` attribute and remove the attribute.
+// Returns `1` if the attribute is not present.
+fn remove_api_version_attribute(attrs: &mut Vec<Attribute>) -> Result<u32> {
+	let mut version = None;
+	let mut error = None;
+
+	attrs.retain(|attr| {
+		if !attr.path.is_ident(API_VERSION_ATTRIBUTE) {
+			return true;
+		}
+
+		let parsed = match attr.parse_meta() {
+			Ok(Meta::List(ref list)) if list.nested.len() == 1 => match list.nested.first().map(|v| v.into_value()) {
+				Some(NestedMeta::Literal(Lit::Int(ref i))) => Some(i.value()),
+				_ => None,
+			},
+			_ => None,
+		};
+
+		match parsed {
+			Some(_) if version.is_some() => error = Some(
+				Error::new(attr.span(), "Only one `api_version` attribute is allowed per trait.")
+			),
+			Some(v) if v <= u32::max_value() as u64 => version = Some(v as u32),
+			_ => error = Some(
+				Error::new(
+					attr.span(),
+					"Unexpected `api_version` attribute. The supported format is `api_version(1)`."
+				)
+			),
+		}
+
+		false
+	});
+
+	match error {
+		Some(e) => Err(e),
+		None => Ok(version.unwrap_or(1)),
+	}
+}
+
+/// Generate the unique identifier of a runtime api, which is the blake2 hash of its name.
+fn generate_runtime_api_id(trait_name: &str) -> TokenStream {
+	let mut res = [0; 8];
+	res.copy_from_slice(blake2_rfc::blake2b::blake2b(8, &[], trait_name.as_bytes()).as_bytes());
+
+	quote!( [ #( #res ),* ] )
+}
+
 /// Generate the decleration of the trait for the runtime.
-fn generate_runtime_decls(decls: &[ItemTrait]) -> TokenStream {
+fn generate_runtime_decls(decls: &[ItemTrait]) -> Result<TokenStream> {
 	let mut result = Vec::new();
 
 	for decl in decls {
@@ -79,6 +135,8 @@ fn generate_runtime_decls(decls: &[ItemTrait]) -> TokenStream {
 		extend_generics_with_block(&mut decl.generics);
 		let mod_name = generate_runtime_mod_name_for_trait(&decl.ident);
 		remove_core_trait_attribute(&mut decl.attrs);
+		let version = remove_api_version_attribute(&mut decl.attrs)?;
+		let id = generate_runtime_api_id(&decl.ident.to_string());
 
 		result.push(quote!(
 			#[doc(hidden)]
@@ -86,11 +144,17 @@ fn generate_runtime_decls(decls: &[ItemTrait]) -> TokenStream {
 				use super::*;
 
 				#decl
+
+				/// The identifier of the runtime api.
+				pub const ID: [u8; 8] = #id;
+
+				/// The version of the runtime api.
+				pub const VERSION: u32 = #version;
 			}
 		));
 	}
 
-	quote!( #( #result )* )
+	Ok(quote!( #( #result )* ))
 }
 
 /// Modify the given runtime api declaration to be usable on the client side.
@@ -99,6 +163,45 @@ struct ToClientSideDecl<'a> {
 	crate_: &'a TokenStream,
 }
 
+impl<'a> ToClientSideDecl<'a> {
+	/// Takes the given client side method and generates the variant of it that takes an
+	/// `ExecutionContext`. The given method gets a default implementation that calls this variant
+	/// with `ExecutionContext::Other`.
+	///
+	/// The variant itself stays required, so that an implementation missing a method fails to
+	/// compile instead of failing when the method is called.
+	fn generate_method_with_context(&self, method: &mut TraitItemMethod) -> TraitItemMethod {
+		let crate_ = self.crate_;
+		let mut method_with_context = method.clone();
+		method_with_context.sig.ident = generate_method_with_context_name(&method.sig.ident);
+		add_execution_context_to_fn_decl(&mut method_with_context.sig.decl, crate_);
+		method_with_context.attrs.push(parse_quote!(
+			#[doc = "Same as the method without `_with_context`, but takes the `ExecutionContext`."]
+		));
+
+		let mut generated_name_counter = 0;
+		// Skip `&self` and `at`.
+		let arg_names = method.sig.decl.inputs.iter_mut().skip(2).filter_map(|i| match i {
+			FnArg::Captured(ref mut arg) => Some(&mut arg.pat),
+			_ => None,
+		}).map(|p| {
+			*p = generate_unique_pattern(p.clone(), &mut generated_name_counter);
+			p
+		});
+		let name_with_context = &method_with_context.sig.ident;
+
+		method.default = Some(parse_quote!( {
+			self.#name_with_context(
+				at,
+				#crate_::runtime_api::ExecutionContext::Other,
+				#( #arg_names ),*
+			)
+		} ));
+
+		method_with_context
+	}
+}
+
 impl<'a> Fold for ToClientSideDecl<'a> {
 	fn fold_fn_decl(&mut self, input: FnDecl) -> FnDecl {
 		let input = fold_fn_decl_for_client_side(
@@ -124,7 +227,7 @@ impl<'a> Fold for ToClientSideDecl<'a> {
 				+ Send
 				+ Sync
 				+ #crate_::runtime_api::ConstructRuntimeApi<Block>
-				+ #crate_::runtime_api::ApiExt
+				+ #crate_::runtime_api::ApiExt<Block>
 			);
 		} else {
 			// Add the `Core` runtime api as super trait.
@@ -135,25 +238,59 @@ impl<'a> Fold for ToClientSideDecl<'a> {
 		// The client side trait is only required when compiling with the feature `std` or `test`.
 		input.attrs.push(parse_quote!( #[cfg(any(feature = "std", test))] ));
 
-		fold::fold_item_trait(self, input)
+		let mut input = fold::fold_item_trait(self, input);
+
+		let mut items = Vec::with_capacity(input.items.len() * 2);
+		for mut item in input.items.drain(..) {
+			if let TraitItem::Method(ref mut method) = item {
+				items.push(TraitItem::Method(self.generate_method_with_context(method)));
+			}
+
+			items.push(item);
+		}
+		input.items = items;
+
+		input
 	}
 }
 
+/// Generate the implementation of `RuntimeApiInfo` for the client side declaration of a trait.
+fn generate_runtime_info_impl(decl: &ItemTrait) -> TokenStream {
+	let crate_ = generate_crate_access(HIDDEN_INCLUDES_ID);
+	let trait_name = &decl.ident;
+	let mod_name = generate_runtime_mod_name_for_trait(trait_name);
+	let (impl_generics, ty_generics, where_clause) = decl.generics.split_for_impl();
+
+	quote!(
+		#[cfg(any(feature = "std", test))]
+		impl #impl_generics #crate_::runtime_api::RuntimeApiInfo
+			for #trait_name #ty_generics #where_clause
+		{
+			const ID: #crate_::runtime_api::ApiId = #mod_name::ID;
+			const VERSION: u32 = #mod_name::VERSION;
+		}
+	)
+}
+
 /// Generate the decleration of the trait for the client side.
-fn generate_client_side_decls(decls: &[ItemTrait]) -> TokenStream {
+fn generate_client_side_decls(decls: &[ItemTrait]) -> Result<TokenStream> {
 	let mut result = Vec::new();
 
 	for decl in decls {
 		let mut decl = decl.clone();
+		remove_api_version_attribute(&mut decl.attrs)?;
 
 		let crate_ = generate_crate_access(HIDDEN_INCLUDES_ID);
 		let block_id = quote!( #crate_::runtime_api::BlockId<Block> );
 		let mut to_client_side = ToClientSideDecl { crate_: &crate_, block_id: &block_id };
 
-		result.push(to_client_side.fold_item_trait(decl));
+		let decl = to_client_side.fold_item_trait(decl);
+		let runtime_info_impl = generate_runtime_info_impl(&decl);
+
+		result.push(quote!( #decl #runtime_info_impl ));
 	}
 
-	quote!( #( #result )* )
+	Ok(quote!( #( #result )* ))
 }
 
 /// Checks that a trait declaration is in the format we expect.
@@ -175,6 +312,15 @@ impl<'ast> Visit<'ast> for CheckTraitDecl {
 							)
 						)
 					},
+					Pat::Ident(ref pat) if pat.ident == "context" => {
+						self.errors.push(
+							Error::new(
+								pat.span(),
+								"`decl_runtime_apis!` adds automatically a parameter \
+								`context: ExecutionContext`. Please rename/remove your parameter."
+							)
+						)
+					},
 					_ => {}
 				}
 
@@ -265,8 +411,8 @@ pub fn decl_runtime_apis_impl(input: proc_macro::TokenStream) -> proc_macro::Tok
 	}
 
 	let hidden_includes = generate_hidden_includes(HIDDEN_INCLUDES_ID);
-	let runtime_decls = generate_runtime_decls(&api_decls);
-	let client_side_decls = generate_client_side_decls(&api_decls);
+	let runtime_decls = unwrap_or_error(generate_runtime_decls(&api_decls));
+	let client_side_decls = unwrap_or_error(generate_client_side_decls(&api_decls));
 
 	quote!(
 		#hidden_includes
@@ -16,7 +16,9 @@
 
 use utils::{
 	unwrap_or_error, generate_crate_access, generate_hidden_includes,
-	generate_runtime_mod_name_for_trait, fold_fn_decl_for_client_side
+	generate_runtime_mod_name_for_trait, fold_fn_decl_for_client_side,
+	extract_block_type_from_trait_path, generate_unique_pattern, generate_method_with_context_name,
+	add_execution_context_to_fn_decl,
 };
 
 use proc_macro;
@@ -26,8 +28,8 @@ use quote::quote;
 
 use syn::{
 	spanned::Spanned, parse_macro_input, Ident, Type, ItemImpl, MethodSig, FnArg, Path,
-	ImplItem, parse::{Parse, ParseStream, Result, Error}, PathArguments, GenericArgument, TypePath,
-	fold::{self, Fold}, FnDecl, parse_quote, Pat
+	ImplItem, parse::{Parse, ParseStream, Result, Error}, TypePath,
+	fold::{self, Fold}, FnDecl, parse_quote
 };
 
 use std::iter;
@@ -35,6 +37,14 @@ use std::iter;
 /// Unique identifier used to make the hidden includes unique for this macro.
 const HIDDEN_INCLUDES_ID: &str = "IMPL_RUNTIME_APIS";
 
+/// The name of the wasm custom section that contains the versions of the implemented apis.
+///
+/// Needs to be kept in sync with `sr_version::RUNTIME_APIS_SECTION`.
+const RUNTIME_APIS_SECTION: &str = "runtime_apis";
+
+/// The size of the information about one api in the custom section, the `ApiId` and the version.
+const RUNTIME_API_INFO_SIZE: usize = 12;
+
 /// The structure used for parsing the runtime api implementations.
 struct RuntimeApiImpls {
 	impls: Vec<ItemImpl>,
@@ -54,6 +64,9 @@ impl Parse for RuntimeApiImpls {
 
 /// Generates the call to the implementation of the requested function.
 /// The generated code includes decoding of the input arguments and encoding of the output.
+///
+/// The generated expression evaluates to `Ok(encoded_output)`, or to `Err(index)` with the index
+/// of the first argument that could not be decoded from the input.
 fn generate_impl_call(
 	signature: &MethodSig,
 	runtime: &Type,
@@ -98,21 +111,23 @@ fn generate_impl_call(
 	let c = generate_crate_access(HIDDEN_INCLUDES_ID);
 	let c_iter = iter::repeat(&c);
 	let fn_name = &signature.ident;
-	let fn_name_str = iter::repeat(fn_name.to_string());
 	let input = iter::repeat(input);
 	let pnames2 = pnames.clone();
+	let pindices = (0..pnames.len() as u32).map(|i| quote!( #i ));
 
 	Ok(
 		quote!(
-			#(
-				let #pnames : #ptypes = match #c_iter::runtime_api::Decode::decode(&mut #input) {
-					Some(input) => input,
-					None => panic!("Bad input data provided to {}", #fn_name_str),
-				};
-			)*
-
-			let output = <#runtime as #impl_trait>::#fn_name(#( #pnames2 ),*);
-			#c::runtime_api::Encode::encode(&output)
+			(|| {
+				#(
+					let #pnames : #ptypes = match #c_iter::runtime_api::Decode::decode(&mut #input) {
+						Some(input) => input,
+						None => return Err(#pindices),
+					};
+				)*
+
+				let output = <#runtime as #impl_trait>::#fn_name(#( #pnames2 ),*);
+				Ok(#c::runtime_api::Encode::encode(&output))
+			})()
 		).into()
 	)
 }
@@ -136,34 +151,6 @@ fn extract_impl_trait<'a>(impl_: &'a ItemImpl) -> Result<&'a Path> {
 	})
 }
 
-/// Extracts the runtime block identifier.
-fn extract_runtime_block_ident(trait_: &Path) -> Result<&TypePath> {
-	let span = trait_.span();
-	let segment = trait_
-		.segments
-		.last()
-		.ok_or_else(
-			|| Error::new(span, "Empty path not supported")
-		)?;
-	let generics = segment.value();
-
-	match &generics.arguments {
-		PathArguments::AngleBracketed(ref args) => {
-			args.args.first().and_then(|v| match v.value() {
-			GenericArgument::Type(Type::Path(block)) => Some(block),
-				_ => None
-			}).ok_or_else(|| Error::new(args.span(), "Missing `Block` generic parameter."))
-		},
-		PathArguments::None => {
-			let span = trait_.segments.last().as_ref().unwrap().value().span();
-			Err(Error::new(span, "Missing `Block` generic parameter."))
-		},
-		PathArguments::Parenthesized(_) => {
-			Err(Error::new(generics.arguments.span(), "Unexpected parentheses in path!"))
-		}
-	}
-}
-
 /// Generate all the implementation calls for the given functions.
 fn generate_impl_calls(impls: &[ItemImpl], input: &Ident) -> Result<Vec<(Ident, TokenStream)>> {
 	let mut impl_calls = Vec::new();
@@ -192,6 +179,9 @@ fn generate_impl_calls(impls: &[ItemImpl], input: &Ident) -> Result<Vec<(Ident,
 }
 
 /// Generate the dispatch function that is used in native to call into the runtime.
+///
+/// Returns `None` for unknown methods and `Some(Err(index))` if the argument at `index` could not
+/// be decoded.
 fn generate_dispatch_function(impls: &[ItemImpl]) -> Result<TokenStream> {
 	let data = Ident::new("data", Span::call_site());
 	let impl_calls = generate_impl_calls(impls, &data)?.into_iter().map(|(fn_name, impl_)| {
@@ -201,7 +191,7 @@ fn generate_dispatch_function(impls: &[ItemImpl]) -> Result<TokenStream> {
 
 	Ok(quote!(
 		#[cfg(feature = "std")]
-		pub fn dispatch(method: &str, mut #data: &[u8]) -> Option<Vec<u8>> {
+		pub fn dispatch(method: &str, mut #data: &[u8]) -> Option<::std::result::Result<Vec<u8>, u32>> {
 			match method {
 				#( #impl_calls )*
 				_ => None,
@@ -211,6 +201,10 @@ fn generate_dispatch_function(impls: &[ItemImpl]) -> Result<TokenStream> {
 }
 
 /// Generate the interface functions that are used to call into the runtime in wasm.
+///
+/// The functions return the pointer to the output in the lower and its length in the upper 32
+/// bits. A null pointer signals that the input could not be decoded, the upper 32 bits then
+/// carry the index of the offending argument.
 fn generate_wasm_interface(impls: &[ItemImpl]) -> Result<TokenStream> {
 	let input = Ident::new("input", Span::call_site());
 	let c = generate_crate_access(HIDDEN_INCLUDES_ID);
@@ -227,7 +221,11 @@ fn generate_wasm_interface(impls: &[ItemImpl]) -> Result<TokenStream> {
 					}
 				};
 
-				let output = { #impl_ };
+				let output: ::core::result::Result<_, u32> = { #impl_ };
+				let output = match output {
+					Ok(output) => output,
+					Err(index) => return (index as u64) << 32,
+				};
 				let res = output.as_ptr() as u64 + ((output.len() as u64) << 32);
 
 				// Leak the output vector to avoid it being freed.
@@ -276,6 +274,14 @@ fn generate_runtime_api_base_structures(impls: &[ItemImpl]) -> Result<TokenStrea
 			commit_on_success: ::std::cell::RefCell<bool>,
 			initialised_block: ::std::cell::RefCell<Option<#block_id>>,
 			changes: ::std::cell::RefCell<#crate_::runtime_api::OverlayedChanges>,
+			snapshots: ::std::cell::RefCell<Vec<Option<#block_id>>>,
+			execution_strategy: ::std::cell::RefCell<
+				Option<#crate_::runtime_api::ExecutionStrategy>
+			>,
+			execution_limits: ::std::cell::RefCell<#crate_::runtime_api::ExecutionLimits>,
+			recorder: Option<
+				::std::rc::Rc<::std::cell::RefCell<#crate_::runtime_api::ProofRecorder<#block>>>
+			>,
 		}
 
 		// `RuntimeApi` itself is not threadsafe. However, an instance is only available in a
@@ -287,11 +293,11 @@ fn generate_runtime_api_base_structures(impls: &[ItemImpl]) -> Result<TokenStrea
 		unsafe impl Sync for RuntimeApi {}
 
 		#[cfg(any(feature = "std", test))]
-		impl #crate_::runtime_api::ApiExt for RuntimeApi {
+		impl #crate_::runtime_api::ApiExt<#block> for RuntimeApi {
 			fn map_api_result<F: FnOnce(&Self) -> ::std::result::Result<R, E>, R, E>(
 				&self,
 				map_call: F
-			) -> ::std::result::Result<R, E> {
+			) -> ::std::result::Result<R, E> where Self: Sized {
 				*self.commit_on_success.borrow_mut() = false;
 				let res = map_call(self);
 				*self.commit_on_success.borrow_mut() = true;
@@ -300,13 +306,123 @@ fn generate_runtime_api_base_structures(impls: &[ItemImpl]) -> Result<TokenStrea
 
 				res
 			}
+
+			fn snapshot(&self) -> #crate_::runtime_api::Snapshot {
+				// each snapshot is a transaction of the changes, keeping the block it was taken
+				// with.
+				self.snapshots.borrow_mut().push(*self.initialised_block.borrow());
+				#crate_::runtime_api::Snapshot::new(self.changes.borrow_mut().start_transaction())
+			}
+
+			fn revert_to(
+				&self,
+				snapshot: #crate_::runtime_api::Snapshot
+			) -> #crate_::error::Result<()> {
+				let mut changes = self.changes.borrow_mut();
+				if snapshot.depth() >= changes.transaction_depth() {
+					return Err(#crate_::error::ErrorKind::InvalidSnapshot.into());
+				}
+
+				while changes.transaction_depth() > snapshot.depth() {
+					changes.rollback_transaction();
+				}
+				let mut snapshots = self.snapshots.borrow_mut();
+				snapshots.truncate(snapshot.depth() + 1);
+				*self.initialised_block.borrow_mut() = snapshots.pop()
+					.expect("there is a block for every open transaction and the snapshot is open; qed");
+				Ok(())
+			}
+
+			fn commit(&self) -> #crate_::error::Result<()> {
+				if !self.changes.borrow_mut().commit_transaction() {
+					return Err(#crate_::error::ErrorKind::InvalidSnapshot.into());
+				}
+				self.snapshots.borrow_mut().pop();
+				Ok(())
+			}
+
+			fn take_storage_changes(&self) -> #crate_::runtime_api::OverlayedChanges {
+				self.snapshots.borrow_mut().clear();
+				self.changes.replace(Default::default())
+			}
+
+			fn take_storage_changes_with_root(
+				&self,
+				parent: &#block_id
+			) -> #crate_::error::Result<#crate_::runtime_api::StorageChanges<#block>> where Self: Sized {
+				let changes = self.take_storage_changes();
+				let storage_root = unsafe { self.call.as_ref().storage_root_at(parent, &changes) }?;
+				Ok(#crate_::runtime_api::StorageChanges { changes, storage_root })
+			}
+
+			fn has_api<A: #crate_::runtime_api::RuntimeApiInfo + ?Sized>(
+				&self,
+				at: &#block_id
+			) -> #crate_::error::Result<bool> where Self: Sized {
+				unsafe { self.call.as_ref().runtime_version_at(at) }
+					.map(|version| version.has_api(A::ID, A::VERSION))
+			}
+
+			fn has_api_with<A: #crate_::runtime_api::RuntimeApiInfo + ?Sized, P: Fn(u32) -> bool>(
+				&self,
+				at: &#block_id,
+				predicate: P,
+			) -> #crate_::error::Result<bool> where Self: Sized {
+				unsafe { self.call.as_ref().runtime_version_at(at) }
+					.map(|version| version.has_api_with(A::ID, predicate))
+			}
+
+			fn with_execution_strategy<F: FnOnce(&Self) -> R, R>(
+				&self,
+				strategy: #crate_::runtime_api::ExecutionStrategy,
+				call: F
+			) -> R where Self: Sized {
+				let old_strategy = self.execution_strategy.replace(Some(strategy));
+				let res = call(self);
+				*self.execution_strategy.borrow_mut() = old_strategy;
+
+				res
+			}
+
+			fn with_execution_limits<F: FnOnce(&Self) -> R, R>(
+				&self,
+				limits: #crate_::runtime_api::ExecutionLimits,
+				call: F
+			) -> R where Self: Sized {
+				let old_limits = self.execution_limits.replace(limits);
+				let res = call(self);
+				*self.execution_limits.borrow_mut() = old_limits;
+
+				res
+			}
+
+			fn record_proof(&mut self) {
+				self.recorder = Some(
+					::std::rc::Rc::new(
+						::std::cell::RefCell::new(#crate_::runtime_api::ProofRecorder::new())
+					)
+				);
+			}
+
+			fn extract_proof(&mut self) -> Option<Vec<Vec<u8>>> {
+				self.recorder
+					.take()
+					.map(|recorder| {
+						recorder
+							.borrow_mut()
+							.drain()
+							.into_iter()
+							.map(|record| record.data.to_vec())
+							.collect()
+					})
+			}
 		}
 
 		#[cfg(any(feature = "std", test))]
 		impl #crate_::runtime_api::ConstructRuntimeApi<#block> for RuntimeApi {
 			fn construct_runtime_api<'a, T: #crate_::runtime_api::CallApiAt<#block>>(
 				call: &'a T
-			) -> #crate_::runtime_api::ApiRef<'a, Self> {
+			) -> #crate_::runtime_api::ApiRef<'a, Self> where Self: Sized {
 				RuntimeApi {
 					call: unsafe {
 						::std::ptr::NonNull::new_unchecked(
@@ -316,6 +432,10 @@ fn generate_runtime_api_base_structures(impls: &[ItemImpl]) -> Result<TokenStrea
 					commit_on_success: true.into(),
 					initialised_block: None.into(),
 					changes: Default::default(),
+					snapshots: Default::default(),
+					execution_strategy: Default::default(),
+					execution_limits: Default::default(),
+					recorder: Default::default(),
 				}.into()
 			}
 		}
@@ -326,7 +446,8 @@ fn generate_runtime_api_base_structures(impls: &[ItemImpl]) -> Result<TokenStrea
 				&self,
 				at: &#block_id,
 				function: &'static str,
-				args: &A
+				args: &A,
+				context: #crate_::runtime_api::ExecutionContext,
 			) -> #crate_::error::Result<R> {
 				let res = unsafe {
 					self.call.as_ref().call_api_at(
@@ -334,7 +455,11 @@ fn generate_runtime_api_base_structures(impls: &[ItemImpl]) -> Result<TokenStrea
 						function,
 						args.encode(),
 						&mut *self.changes.borrow_mut(),
-						&mut *self.initialised_block.borrow_mut()
+						&mut *self.initialised_block.borrow_mut(),
+						context,
+						*self.execution_strategy.borrow(),
+						*self.execution_limits.borrow(),
+						&self.recorder,
 					).and_then(|r|
 						R::decode(&mut &r[..])
 							.ok_or_else(||
@@ -380,6 +505,57 @@ fn extend_with_runtime_decl_path(mut trait_: Path) -> Path {
 	trait_
 }
 
+/// Generates `RUNTIME_API_VERSIONS` that holds all version information about the implemented
+/// runtime apis.
+fn generate_runtime_api_versions(impls: &[ItemImpl]) -> Result<TokenStream> {
+	let c = generate_crate_access(HIDDEN_INCLUDES_ID);
+	let mut result = Vec::with_capacity(impls.len());
+	let mut section_contents = Vec::with_capacity(impls.len());
+
+	for impl_ in impls {
+		let mut path = extend_with_runtime_decl_path(extract_impl_trait(&impl_)?.clone());
+		// Remove the trait, to get the path of the module that contains its declaration.
+		path.segments.pop();
+
+		let id: Path = parse_quote!( #path ID );
+		let version: Path = parse_quote!( #path VERSION );
+
+		result.push(quote!( (#id, #version) ));
+
+		// The id followed by the little endian encoded version, like `(ApiId, u32)` is encoded.
+		let id_bytes = (0..8usize).map(|i| quote!( #id[#i] ));
+		let version_bytes = (0..4u32).map(|i| {
+			let shift = i * 8;
+			quote!( (#version >> #shift) as u8 )
+		});
+		section_contents.push(quote!( #( #id_bytes, )* #( #version_bytes, )* ));
+	}
+
+	let section_len = impls.len() * RUNTIME_API_INFO_SIZE;
+
+	Ok(quote!(
+		/// The versions of all the runtime apis implemented by this runtime, to be put into
+		/// the `apis` field of its `RuntimeVersion`.
+		#[cfg(any(feature = "std", test))]
+		pub const RUNTIME_API_VERSIONS: #c::runtime_api::ApisVec =
+			::std::borrow::Cow::Borrowed(&[ #( #result ),* ]);
+
+		/// The versions of all the runtime apis implemented by this runtime, to be put into
+		/// the `apis` field of its `RuntimeVersion`.
+		#[cfg(not(any(feature = "std", test)))]
+		pub const RUNTIME_API_VERSIONS: #c::runtime_api::ApisVec = &[ #( #result ),* ];
+
+		/// The versions of all the runtime apis implemented by this runtime, put into a custom
+		/// section of the wasm blob. This allows reading them without executing the wasm blob.
+		#[cfg(not(any(feature = "std", test)))]
+		#[used]
+		#[link_section = #RUNTIME_APIS_SECTION]
+		static RUNTIME_API_VERSIONS_SECTION_CONTENTS: [u8; #section_len] = [
+			#( #section_contents )*
+		];
+	))
+}
+
 /// Generates the implementations of the apis for the runtime.
 fn generate_api_impl_for_runtime(impls: &[ItemImpl]) -> Result<TokenStream> {
 	let mut impls_prepared = Vec::new();
@@ -398,22 +574,6 @@ fn generate_api_impl_for_runtime(impls: &[ItemImpl]) -> Result<TokenStream> {
 	Ok(quote!( #( #impls_prepared )* ))
 }
 
-/// Generate an unique pattern based on the given counter, if the given pattern is a `_`.
-fn generate_unique_pattern(pat: Pat, counter: &mut u32) -> Pat {
-	match pat {
-		Pat::Wild(_) => {
-			let generated_name = Ident::new(
-				&format!("impl_runtime_api_generated_name_{}", counter),
-				pat.span()
-			);
-			*counter += 1;
-
-			parse_quote!( #generated_name )
-		},
-		_ => pat,
-	}
-}
-
 /// Auxilariy data structure that is used to convert `impl Api for Runtime` to
 /// `impl Api for RuntimeApi`.
 /// This requires us to replace the runtime `Block` with the node `Block`,
@@ -438,11 +598,9 @@ impl<'a> Fold for ApiRuntimeImplToApiRuntimeApiImpl<'a> {
 	}
 
 	fn fold_fn_decl(&mut self, input: FnDecl) -> FnDecl {
-		let input = fold_fn_decl_for_client_side(
-			input,
-			&self.node_block_id,
-			&generate_crate_access(HIDDEN_INCLUDES_ID)
-		);
+		let crate_ = generate_crate_access(HIDDEN_INCLUDES_ID);
+		let mut input = fold_fn_decl_for_client_side(input, &self.node_block_id, &crate_);
+		add_execution_context_to_fn_decl(&mut input, &crate_);
 
 		fold::fold_fn_decl(self, input)
 	}
@@ -460,9 +618,19 @@ impl<'a> Fold for ApiRuntimeImplToApiRuntimeApiImpl<'a> {
 			let name = input.sig.ident.to_string();
 
 			// Generate the new method implementation that calls into the runime.
-			input.block = parse_quote!( { self.call_api_at(at, #name, &( #( #arg_names ),* )) } );
+			input.block = parse_quote!( {
+				self.call_api_at(
+					at,
+					#name,
+					&( #( #arg_names ),* ),
+					context,
+				)
+			} );
 		}
 
+		// The client side trait only requires the variant that takes the `ExecutionContext`.
+		input.sig.ident = generate_method_with_context_name(&input.sig.ident);
+
 		fold::fold_impl_item_method(self, input)
 	}
 
@@ -482,7 +650,7 @@ fn generate_api_impl_for_runtime_api(impls: &[ItemImpl]) -> Result<TokenStream>
 	let mut result = Vec::with_capacity(impls.len());
 
 	for impl_ in impls {
-		let runtime_block = extract_runtime_block_ident(extract_impl_trait(&impl_)?)?;
+		let runtime_block = extract_block_type_from_trait_path(extract_impl_trait(&impl_)?)?;
 		let (node_block, node_block_id) = generate_node_block_and_block_id_ty(&impl_.self_ty);
 
 		let mut visitor = ApiRuntimeImplToApiRuntimeApiImpl {
@@ -501,14 +669,22 @@ fn generate_api_impl_for_runtime_api(impls: &[ItemImpl]) -> Result<TokenStream>
 pub fn impl_runtime_apis_impl(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
 	// Parse all impl blocks
 	let RuntimeApiImpls { impls: api_impls } = parse_macro_input!(input as RuntimeApiImpls);
-	let dispatch_impl = unwrap_or_error(generate_dispatch_function(&api_impls));
-	let wasm_interface = unwrap_or_error(generate_wasm_interface(&api_impls));
+
+	unwrap_or_error(generate_runtime_api_impls(&api_impls)).into()
+}
+
+/// Generates everything `impl_runtime_apis!` expands to. Only the first error is returned: the
+/// generators fail on the same malformed implementations, so their errors would repeat it.
+fn generate_runtime_api_impls(api_impls: &[ItemImpl]) -> Result<TokenStream> {
+	let dispatch_impl = generate_dispatch_function(api_impls)?;
+	let wasm_interface = generate_wasm_interface(api_impls)?;
 	let hidden_includes = generate_hidden_includes(HIDDEN_INCLUDES_ID);
-	let base_runtime_api = unwrap_or_error(generate_runtime_api_base_structures(&api_impls));
-	let api_impls_for_runtime = unwrap_or_error(generate_api_impl_for_runtime(&api_impls));
-	let api_impls_for_runtime_api = unwrap_or_error(generate_api_impl_for_runtime_api(&api_impls));
+	let base_runtime_api = generate_runtime_api_base_structures(api_impls)?;
+	let api_impls_for_runtime = generate_api_impl_for_runtime(api_impls)?;
+	let api_impls_for_runtime_api = generate_api_impl_for_runtime_api(api_impls)?;
+	let runtime_api_versions = generate_runtime_api_versions(api_impls)?;
 
-	quote!(
+	Ok(quote!(
 		#hidden_includes
 
 		#base_runtime_api
@@ -517,6 +693,8 @@ pub fn impl_runtime_apis_impl(input: proc_macro::TokenStream) -> proc_macro::Tok
 
 		#api_impls_for_runtime_api
 
+		#runtime_api_versions
+
 		pub mod api {
 			use super::*;
 
@@ -524,5 +702,5 @@ pub fn impl_runtime_apis_impl(input: proc_macro::TokenStream) -> proc_macro::Tok
 
 			#wasm_interface
 		}
-	).into()
+	))
 }
@@ -21,11 +21,15 @@ extern crate proc_macro;
 extern crate proc_macro2;
 extern crate quote;
 extern crate syn;
+extern crate blake2_rfc;
+extern crate parity_codec as codec;
 
 use proc_macro::TokenStream;
 
 mod impl_runtime_apis;
 mod decl_runtime_apis;
+mod mock_impl_runtime_apis;
+mod runtime_version;
 mod utils;
 mod compile_fail_tests;
 
@@ -41,6 +45,11 @@ mod compile_fail_tests;
 /// The macro also generates the implementation of the apis for the client side by generating the
 /// `RuntimeApi` type. The `RuntimeApi` is hidden behind a `feature` called `std`.
 ///
+/// The identifiers and versions of all implemented apis are collected in the generated
+/// `RUNTIME_API_VERSIONS` constant, which should be used for the `apis` field of the
+/// `RuntimeVersion` of the runtime. For wasm builds, they are also put into the `runtime_apis`
+/// custom section of the wasm blob.
+///
 /// # Example
 ///
 /// ```rust
@@ -130,8 +139,21 @@ pub fn impl_runtime_apis(input: TokenStream) -> TokenStream {
 /// declaration. Besides one exception, the macro adds an extra generic parameter `Block: BlockT`
 /// to the client side and the runtime side. This generic parameter is usable by the user.
 ///
+/// For each function, the client side declaration also contains a `*_with_context` variant that
+/// takes a `context: ExecutionContext` parameter after `at`. The context tells the client why the
+/// call is done, e.g. for block construction or import, so that it can select the appropriate
+/// execution behavior. The function without the suffix calls the variant with
+/// `ExecutionContext::Other`.
+///
 /// For implementing these macros you should use the `impl_runtime_apis!` macro.
 ///
+/// Each trait can be given a version with the `#[api_version(N)]` attribute, it defaults to `1`.
+/// The version should be increased on every change to the trait that breaks existing clients.
+/// Together with an identifier derived from the trait name, it is available through the
+/// `RuntimeApiInfo` implementation generated for the client side declaration. Clients can use
+/// `ApiExt::has_api` to check that the runtime at a given block implements the trait in the
+/// version they expect.
+///
 /// # Example
 ///
 /// ```rust
@@ -149,6 +171,7 @@ pub fn impl_runtime_apis(input: TokenStream) -> TokenStream {
 ///
 ///     /// You can declare multiple api traits in one macro call.
 ///     /// In one module you can call the macro at maximum one time.
+///     #[api_version(2)]
 ///     pub trait BlockBuilder {
 ///         /// The macro adds an explicit `Block: BlockT` generic parameter for you.
 ///         /// You can use this generic parameter as you would defined it manually.
@@ -162,3 +185,123 @@ pub fn impl_runtime_apis(input: TokenStream) -> TokenStream {
 pub fn decl_runtime_apis(input: TokenStream) -> TokenStream {
 	decl_runtime_apis::decl_runtime_apis_impl(input)
 }
+
+/// Mocks given trait implementations as runtime apis.
+///
+/// Accepts similar syntax as `impl_runtime_apis!` and generates simplified mock implementations of
+/// the given runtime apis. The mock type needs to be given as `Self` type of all implementations.
+/// Each method is written like on the runtime side, but the macro adds `&self` and
+/// `at: &BlockId<Block>` as parameters, converts all parameters into references and wraps the
+/// returned value in `Ok(_)`. So, the body of each method can access `self` and `at`. The method
+/// is implemented as the `*_with_context` variant of the client side declaration, which also gets
+/// the `context` parameter.
+///
+/// Besides the given implementations, the macro generates implementations of `ApiExt` and
+/// `ConstructRuntimeApi`. If `Core` is not implemented by the user, an implementation that panics
+/// on every call is generated as well. `ApiExt::has_api` and `ApiExt::has_api_with` always return
+/// `true`. The mock accesses no storage: `ApiExt::extract_proof` always returns an empty proof.
+///
+/// # Example
+///
+/// ```rust
+/// #[macro_use]
+/// extern crate substrate_client;
+/// # extern crate sr_primitives as runtime_primitives;
+/// # extern crate substrate_primitives as primitives;
+/// # #[macro_use]
+/// # extern crate parity_codec_derive;
+/// # extern crate serde;
+/// # extern crate core;
+/// #
+/// # use primitives::hash::H256;
+/// # use runtime_primitives::traits::{BlakeTwo256, Extrinsic as ExtrinsicT};
+/// #
+/// # // All the stuff we need to declare our `Block`
+/// # pub type BlockNumber = u64;
+/// # pub type DigestItem = runtime_primitives::generic::DigestItem<H256, u64>;
+/// # pub type Digest = runtime_primitives::generic::Digest<DigestItem>;
+/// # #[derive(Clone, PartialEq, Eq, Encode, Decode, Debug)]
+/// # pub struct Extrinsic {}
+/// #
+/// # impl serde::Serialize for Extrinsic {
+/// #     fn serialize<S>(&self, seq: S) -> Result<S::Ok, S::Error> where S: ::serde::Serializer {
+/// #         unimplemented!()
+/// #     }
+/// # }
+/// # impl ExtrinsicT for Extrinsic {
+/// #     fn is_signed(&self) -> Option<bool> {
+/// #         unimplemented!()
+/// #     }
+/// # }
+/// # pub type Header = runtime_primitives::generic::Header<BlockNumber, BlakeTwo256, DigestItem>;
+/// # pub type Block = runtime_primitives::generic::Block<Header, Extrinsic>;
+/// #
+/// # decl_runtime_apis! {
+/// #     /// Declare the api trait.
+/// #     pub trait Balance {
+/// #         /// Get the balance.
+/// #         fn get_balance() -> u64;
+/// #         /// Set the balance.
+/// #         fn set_balance(val: u64);
+/// #     }
+/// # }
+///
+/// struct MockApi {
+///     balance: u64,
+/// }
+///
+/// /// All runtime api mock implementations need to be done in one call of the macro!
+/// mock_impl_runtime_apis! {
+///     impl Balance<Block> for MockApi {
+///         fn get_balance() -> u64 {
+///             self.balance
+///         }
+///         fn set_balance(_bal: u64) {
+///             // Store the balance
+///         }
+///     }
+/// }
+///
+/// # fn main() {}
+/// ```
+#[proc_macro]
+pub fn mock_impl_runtime_apis(input: TokenStream) -> TokenStream {
+	mock_impl_runtime_apis::mock_impl_runtime_apis_impl(input)
+}
+
+/// Puts the runtime version into a custom section of the wasm blob.
+///
+/// The attribute needs to be put on the `RuntimeVersion` constant of the runtime. It encodes all
+/// fields of the runtime version, besides the `apis`, into the `runtime_version` custom section.
+/// The `apis` are put into the `runtime_apis` custom section by `impl_runtime_apis!`. This allows
+/// the executor to read the runtime version of a wasm blob without executing it. The custom
+/// section is only generated for wasm builds, i.e. when the `std` feature is not enabled.
+///
+/// The fields need to be given as literals, strings can also be given with `ver_str!`.
+///
+/// # Example
+///
+/// ```rust
+/// #[macro_use]
+/// extern crate substrate_client;
+/// #[macro_use]
+/// extern crate sr_version;
+///
+/// use sr_version::RuntimeVersion;
+///
+/// #[runtime_version]
+/// pub const VERSION: RuntimeVersion = RuntimeVersion {
+///     spec_name: ver_str!("node"),
+///     impl_name: ver_str!("substrate-node"),
+///     authoring_version: 1,
+///     spec_version: 1,
+///     impl_version: 0,
+///     apis: apis_vec!([]),
+/// };
+///
+/// # fn main() {}
+/// ```
+#[proc_macro_attribute]
+pub fn runtime_version(_: TokenStream, input: TokenStream) -> TokenStream {
+	runtime_version::runtime_version_impl(input)
+}
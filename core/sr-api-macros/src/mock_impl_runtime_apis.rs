@@ -0,0 +1,272 @@
+// Copyright 2018 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+use utils::{
+	unwrap_or_error, generate_crate_access, generate_hidden_includes,
+	fold_fn_decl_for_client_side, extract_block_type_from_trait_path,
+	generate_method_with_context_name, add_execution_context_to_fn_decl
+};
+
+use proc_macro;
+use proc_macro2::{Span, TokenStream};
+
+use quote::quote;
+
+use syn::{
+	spanned::Spanned, parse_macro_input, Type, ItemImpl, Path, ImplItem, TypePath, parse_quote,
+	parse::{Parse, ParseStream, Result, Error},
+};
+
+/// Unique identifier used to make the hidden includes unique for this macro.
+const HIDDEN_INCLUDES_ID: &str = "MOCK_IMPL_RUNTIME_APIS";
+
+/// The structure used for parsing the runtime api mock implementations.
+struct RuntimeApiImpls {
+	impls: Vec<ItemImpl>,
+}
+
+impl Parse for RuntimeApiImpls {
+	fn parse(input: ParseStream) -> Result<Self> {
+		let mut impls = Vec::new();
+
+		while !input.is_empty() {
+			impls.push(ItemImpl::parse(input)?);
+		}
+
+		if impls.is_empty() {
+			Err(Error::new(Span::call_site(), "No api implementation given!"))
+		} else {
+			Ok(Self { impls })
+		}
+	}
+}
+
+/// Extract the trait that is implemented in the given `ItemImpl`.
+fn extract_impl_trait<'a>(impl_: &'a ItemImpl) -> Result<&'a Path> {
+	impl_.trait_.as_ref().map(|v| &v.1).ok_or_else(
+		|| Error::new(impl_.span(), "Only implementation of traits are supported!")
+	)
+}
+
+/// Returns if the given trait path is the `Core` runtime api.
+fn is_core_trait(trait_: &Path) -> bool {
+	trait_.segments.last().map(|s| s.value().ident == "Core").unwrap_or(false)
+}
+
+/// Generates the implementations of `ApiExt` and `ConstructRuntimeApi` for the mock type. If the
+/// user did not implement `Core`, an implementation that panics on every call is generated as well.
+fn generate_runtime_api_base_structures(
+	self_ty: &Type,
+	block: &TypePath,
+	implements_core: bool,
+) -> TokenStream {
+	let crate_ = generate_crate_access(HIDDEN_INCLUDES_ID);
+	let block_id = quote!( #crate_::runtime_api::BlockId<#block> );
+
+	let core_impl = if implements_core {
+		TokenStream::new()
+	} else {
+		quote!(
+			impl #crate_::runtime_api::Core<#block> for #self_ty {
+				fn version_with_context(
+					&self,
+					_: &#block_id,
+					_: #crate_::runtime_api::ExecutionContext,
+				) -> #crate_::error::Result<#crate_::runtime_api::RuntimeVersion> {
+					unimplemented!("`Core::version` is not implemented by the mock!")
+				}
+
+				fn authorities_with_context(
+					&self,
+					_: &#block_id,
+					_: #crate_::runtime_api::ExecutionContext,
+				) -> #crate_::error::Result<Vec<#crate_::runtime_api::AuthorityId>> {
+					unimplemented!("`Core::authorities` is not implemented by the mock!")
+				}
+
+				fn execute_block_with_context(
+					&self,
+					_: &#block_id,
+					_: #crate_::runtime_api::ExecutionContext,
+					_: &#block,
+				) -> #crate_::error::Result<()> {
+					unimplemented!("`Core::execute_block` is not implemented by the mock!")
+				}
+
+				fn initialise_block_with_context(
+					&self,
+					_: &#block_id,
+					_: #crate_::runtime_api::ExecutionContext,
+					_: &<#block as #crate_::runtime_api::BlockT>::Header,
+				) -> #crate_::error::Result<()> {
+					unimplemented!("`Core::initialise_block` is not implemented by the mock!")
+				}
+			}
+		)
+	};
+
+	quote!(
+		#core_impl
+
+		impl #crate_::runtime_api::ApiExt<#block> for #self_ty {
+			fn map_api_result<F: FnOnce(&Self) -> ::std::result::Result<R, E>, R, E>(
+				&self,
+				map_call: F
+			) -> ::std::result::Result<R, E> where Self: Sized {
+				map_call(self)
+			}
+
+			// the mock makes no storage changes, there is nothing to revert or commit.
+			fn snapshot(&self) -> #crate_::runtime_api::Snapshot {
+				#crate_::runtime_api::Snapshot::new(0)
+			}
+
+			fn revert_to(
+				&self,
+				_: #crate_::runtime_api::Snapshot
+			) -> #crate_::error::Result<()> {
+				Ok(())
+			}
+
+			fn commit(&self) -> #crate_::error::Result<()> {
+				Ok(())
+			}
+
+			fn take_storage_changes(&self) -> #crate_::runtime_api::OverlayedChanges {
+				unimplemented!("`ApiExt::take_storage_changes` is not implemented by the mock!")
+			}
+
+			fn take_storage_changes_with_root(
+				&self,
+				_: &#block_id,
+			) -> #crate_::error::Result<#crate_::runtime_api::StorageChanges<#block>> where Self: Sized {
+				unimplemented!("`ApiExt::take_storage_changes_with_root` is not implemented by the mock!")
+			}
+
+			fn has_api<A: #crate_::runtime_api::RuntimeApiInfo + ?Sized>(
+				&self,
+				_: &#block_id,
+			) -> #crate_::error::Result<bool> where Self: Sized {
+				Ok(true)
+			}
+
+			fn has_api_with<A: #crate_::runtime_api::RuntimeApiInfo + ?Sized, P: Fn(u32) -> bool>(
+				&self,
+				_: &#block_id,
+				_: P,
+			) -> #crate_::error::Result<bool> where Self: Sized {
+				Ok(true)
+			}
+
+			fn with_execution_strategy<F: FnOnce(&Self) -> R, R>(
+				&self,
+				_: #crate_::runtime_api::ExecutionStrategy,
+				call: F
+			) -> R where Self: Sized {
+				call(self)
+			}
+
+			fn with_execution_limits<F: FnOnce(&Self) -> R, R>(
+				&self,
+				_: #crate_::runtime_api::ExecutionLimits,
+				call: F
+			) -> R where Self: Sized {
+				call(self)
+			}
+
+			// the mock accesses no storage, so the proof of its calls is always empty.
+			fn record_proof(&mut self) {}
+
+			fn extract_proof(&mut self) -> Option<Vec<Vec<u8>>> {
+				Some(Vec::new())
+			}
+		}
+
+		impl #crate_::runtime_api::ConstructRuntimeApi<#block> for #self_ty {
+			fn construct_runtime_api<'a, T: #crate_::runtime_api::CallApiAt<#block>>(
+				_: &'a T
+			) -> #crate_::runtime_api::ApiRef<'a, Self> where Self: Sized {
+				unimplemented!("`ConstructRuntimeApi` is not implemented by the mock!")
+			}
+		}
+	)
+}
+
+/// Converts the given runtime side implementation into an implementation for the client side.
+///
+/// Every method is implemented as its `_with_context` variant. It gets `&self`,
+/// `at: &BlockId<Block>` and `context: ExecutionContext` as parameters, takes its arguments by
+/// reference and returns its original return value wrapped in `Ok(_)`.
+fn generate_mock_api_impl(mut impl_: ItemImpl, block: &TypePath) -> ItemImpl {
+	let crate_ = generate_crate_access(HIDDEN_INCLUDES_ID);
+	let block_id = quote!( #crate_::runtime_api::BlockId<#block> );
+
+	impl_.items.iter_mut().for_each(|i| match i {
+		ImplItem::Method(ref mut method) => {
+			let mut decl = fold_fn_decl_for_client_side(*method.sig.decl.clone(), &block_id, &crate_);
+			add_execution_context_to_fn_decl(&mut decl, &crate_);
+			method.sig.decl = Box::new(decl);
+			method.sig.ident = generate_method_with_context_name(&method.sig.ident);
+			// `at` and `context` are not used by the mock.
+			method.attrs.push(parse_quote!( #[allow(unused_variables)] ));
+
+			let block = &method.block;
+			method.block = parse_quote!( { Ok(#block) } );
+		},
+		_ => {},
+	});
+
+	impl_
+}
+
+/// The implementation of the `mock_impl_runtime_apis!` macro.
+pub fn mock_impl_runtime_apis_impl(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+	// Parse all impl blocks
+	let RuntimeApiImpls { impls: api_impls } = parse_macro_input!(input as RuntimeApiImpls);
+
+	unwrap_or_error(generate_mock_impls(api_impls)).into()
+}
+
+fn generate_mock_impls(api_impls: Vec<ItemImpl>) -> Result<TokenStream> {
+	let hidden_includes = generate_hidden_includes(HIDDEN_INCLUDES_ID);
+	let self_ty = api_impls[0].self_ty.clone();
+	let block = extract_block_type_from_trait_path(extract_impl_trait(&api_impls[0])?)?.clone();
+	let mut implements_core = false;
+	let mut mock_impls = Vec::with_capacity(api_impls.len());
+
+	for impl_ in api_impls {
+		if impl_.self_ty != self_ty {
+			return Err(Error::new(
+				impl_.self_ty.span(),
+				"All implementations need to be done for the same mock type!"
+			))
+		}
+
+		implements_core |= is_core_trait(extract_impl_trait(&impl_)?);
+		let impl_block = extract_block_type_from_trait_path(extract_impl_trait(&impl_)?)?.clone();
+		mock_impls.push(generate_mock_api_impl(impl_, &impl_block));
+	}
+
+	let base_structures = generate_runtime_api_base_structures(&self_ty, &block, implements_core);
+
+	Ok(quote!(
+		#hidden_includes
+
+		#base_structures
+
+		#( #mock_impls )*
+	))
+}
@@ -0,0 +1,141 @@
+// Copyright 2018 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+use utils::unwrap_or_error;
+
+use proc_macro;
+use proc_macro2::{Span, TokenStream};
+
+use quote::quote;
+
+use syn::{
+	spanned::Spanned, parse_macro_input, ItemConst, Expr, ExprStruct, Lit, LitStr, Member,
+	parse::{Result, Error},
+};
+
+use codec::Encode;
+
+/// The name of the wasm custom section that contains the encoded runtime version.
+///
+/// Needs to be kept in sync with `sr_version::RUNTIME_VERSION_SECTION`.
+const RUNTIME_VERSION_SECTION: &str = "runtime_version";
+
+/// The fields of `RuntimeVersion` that are put into the custom section. The `apis` are put into
+/// their own custom section by `impl_runtime_apis!`.
+#[derive(Default)]
+struct ParsedRuntimeVersion {
+	spec_name: Option<String>,
+	impl_name: Option<String>,
+	authoring_version: Option<u32>,
+	spec_version: Option<u32>,
+	impl_version: Option<u32>,
+}
+
+impl ParsedRuntimeVersion {
+	/// Encode the parsed fields in the same order and format as `RuntimeVersion` is encoded.
+	fn encode(self, span: Span) -> Result<Vec<u8>> {
+		let missing = |field| Error::new(span, format!("Missing field `{}`.", field));
+
+		Ok((
+			self.spec_name.ok_or_else(|| missing("spec_name"))?,
+			self.impl_name.ok_or_else(|| missing("impl_name"))?,
+			self.authoring_version.ok_or_else(|| missing("authoring_version"))?,
+			self.spec_version.ok_or_else(|| missing("spec_version"))?,
+			self.impl_version.ok_or_else(|| missing("impl_version"))?,
+		).encode())
+	}
+}
+
+/// Parse a string field, given as string literal or as `ver_str!("...")`.
+fn parse_string_field(expr: &Expr) -> Result<String> {
+	match expr {
+		Expr::Lit(ref lit) => match lit.lit {
+			Lit::Str(ref s) => return Ok(s.value()),
+			_ => {},
+		},
+		Expr::Macro(ref mac) => return syn::parse2::<LitStr>(mac.mac.tts.clone()).map(|s| s.value()),
+		_ => {},
+	}
+
+	Err(Error::new(expr.span(), "Expected a string literal or `ver_str!(\"...\")`."))
+}
+
+/// Parse an integer field, given as integer literal.
+fn parse_u32_field(expr: &Expr) -> Result<u32> {
+	match expr {
+		Expr::Lit(ref lit) => match lit.lit {
+			Lit::Int(ref i) if i.value() <= u32::max_value() as u64 => return Ok(i.value() as u32),
+			_ => {},
+		},
+		_ => {},
+	}
+
+	Err(Error::new(expr.span(), "Expected an `u32` integer literal."))
+}
+
+fn parse_runtime_version(runtime_version: &ExprStruct) -> Result<ParsedRuntimeVersion> {
+	let mut parsed = ParsedRuntimeVersion::default();
+
+	for field in &runtime_version.fields {
+		let name = match field.member {
+			Member::Named(ref ident) => ident.to_string(),
+			Member::Unnamed(_) => return Err(Error::new(field.span(), "Expected a named field.")),
+		};
+
+		match name.as_str() {
+			"spec_name" => parsed.spec_name = Some(parse_string_field(&field.expr)?),
+			"impl_name" => parsed.impl_name = Some(parse_string_field(&field.expr)?),
+			"authoring_version" => parsed.authoring_version = Some(parse_u32_field(&field.expr)?),
+			"spec_version" => parsed.spec_version = Some(parse_u32_field(&field.expr)?),
+			"impl_version" => parsed.impl_version = Some(parse_u32_field(&field.expr)?),
+			// `apis` are put into their own custom section.
+			"apis" => {},
+			_ => return Err(Error::new(field.span(), "Unknown field of `RuntimeVersion`.")),
+		}
+	}
+
+	Ok(parsed)
+}
+
+fn generate_runtime_version_section(item: &ItemConst) -> Result<TokenStream> {
+	let runtime_version = match *item.expr {
+		Expr::Struct(ref s) => s,
+		_ => return Err(
+			Error::new(item.expr.span(), "Expected the `RuntimeVersion` given as struct expression.")
+		),
+	};
+
+	let encoded = parse_runtime_version(runtime_version)?.encode(runtime_version.span())?;
+	let len = encoded.len();
+
+	Ok(quote!(
+		#item
+
+		/// The encoded runtime version, without the apis, put into a custom section of the wasm
+		/// blob. This allows reading the runtime version without executing the wasm blob.
+		#[cfg(not(feature = "std"))]
+		#[used]
+		#[link_section = #RUNTIME_VERSION_SECTION]
+		static RUNTIME_VERSION_SECTION_CONTENTS: [u8; #len] = [ #( #encoded ),* ];
+	))
+}
+
+/// The implementation of the `runtime_version` attribute.
+pub fn runtime_version_impl(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+	let item = parse_macro_input!(input as ItemConst);
+
+	unwrap_or_error(generate_runtime_version_section(&item)).into()
+}
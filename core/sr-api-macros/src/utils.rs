@@ -15,7 +15,10 @@
 // along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
 
 use proc_macro2::{TokenStream, Span};
-use syn::{Result, Ident, FnDecl, parse_quote, Type, FnArg};
+use syn::{
+	Result, Ident, FnDecl, parse_quote, Type, FnArg, Path, TypePath, PathArguments,
+	GenericArgument, Error, Pat, spanned::Spanned,
+};
 use quote::quote;
 use std::env;
 
@@ -95,3 +98,60 @@ pub fn fold_fn_decl_for_client_side(
 
 	input
 }
+
+/// Generate an unique pattern based on the given counter, if the given pattern is a `_`.
+pub fn generate_unique_pattern(pat: Pat, counter: &mut u32) -> Pat {
+	match pat {
+		Pat::Wild(_) => {
+			let generated_name = Ident::new(
+				&format!("impl_runtime_api_generated_name_{}", counter),
+				pat.span()
+			);
+			*counter += 1;
+
+			parse_quote!( #generated_name )
+		},
+		_ => pat,
+	}
+}
+
+/// Generates the name of the variant of a client side method that takes an `ExecutionContext`.
+pub fn generate_method_with_context_name(method: &Ident) -> Ident {
+	Ident::new(&format!("{}_with_context", method), method.span())
+}
+
+/// Add `context: ExecutionContext` as parameter after `at: &BlockId` to the given client side
+/// `FnDecl`.
+pub fn add_execution_context_to_fn_decl(input: &mut FnDecl, crate_: &TokenStream) {
+	input.inputs.insert(2, parse_quote!( context: #crate_::runtime_api::ExecutionContext ));
+}
+
+/// Extracts the block type from a trait path.
+///
+/// It is expected that the block type is the first type in the generic arguments.
+pub fn extract_block_type_from_trait_path(trait_: &Path) -> Result<&TypePath> {
+	let span = trait_.span();
+	let segment = trait_
+		.segments
+		.last()
+		.ok_or_else(
+			|| Error::new(span, "Empty path not supported")
+		)?;
+	let generics = segment.value();
+
+	match &generics.arguments {
+		PathArguments::AngleBracketed(ref args) => {
+			args.args.first().and_then(|v| match v.value() {
+				GenericArgument::Type(Type::Path(block)) => Some(block),
+				_ => None
+			}).ok_or_else(|| Error::new(args.span(), "Missing `Block` generic parameter."))
+		},
+		PathArguments::None => {
+			let span = trait_.segments.last().as_ref().unwrap().value().span();
+			Err(Error::new(span, "Missing `Block` generic parameter."))
+		},
+		PathArguments::Parenthesized(_) => {
+			Err(Error::new(generics.arguments.span(), "Unexpected parentheses in path!"))
+		}
+	}
+}
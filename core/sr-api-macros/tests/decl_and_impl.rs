@@ -12,7 +12,7 @@ use runtime_primitives::traits::{
 	BlakeTwo256, GetNodeBlockType, Extrinsic as ExtrinsicT, Block as BlockT
 };
 use runtime_primitives::generic::BlockId;
-use substrate_client::runtime_api;
+use substrate_client::runtime_api::{self, RuntimeApiInfo, ExecutionContext};
 use primitives::AuthorityId;
 use substrate_client::error::Result;
 
@@ -52,6 +52,11 @@ decl_runtime_apis! {
 		fn something_with_block(block: Block) -> Block;
 		fn function_with_two_args(data: u64, block: Block);
 	}
+
+	#[api_version(2)]
+	pub trait ApiWithCustomVersion {
+		fn same_name();
+	}
 }
 
 impl_runtime_apis! {
@@ -69,6 +74,10 @@ impl_runtime_apis! {
 		}
 	}
 
+	impl self::ApiWithCustomVersion<Block> for Runtime {
+		fn same_name() {}
+	}
+
 	impl runtime_api::Core<Block> for Runtime {
 		fn version() -> runtime_api::RuntimeVersion {
 			unimplemented!()
@@ -90,4 +99,95 @@ fn test_client_side_function_signature() {
 	let _test: fn(&RuntimeApi, &BlockId<Block>, &u64) -> Result<()>  = RuntimeApi::test;
 	let _something_with_block: fn(&RuntimeApi, &BlockId<Block>, &Block) -> Result<Block> =
 		RuntimeApi::something_with_block;
+	let _test_with_context: fn(&RuntimeApi, &BlockId<Block>, ExecutionContext, &u64) -> Result<()> =
+		RuntimeApi::test_with_context;
+}
+
+#[test]
+fn check_runtime_api_info() {
+	assert_eq!(<Api<Block> as RuntimeApiInfo>::VERSION, 1);
+	assert_eq!(<ApiWithCustomVersion<Block> as RuntimeApiInfo>::VERSION, 2);
+	assert_ne!(<Api<Block> as RuntimeApiInfo>::ID, <ApiWithCustomVersion<Block> as RuntimeApiInfo>::ID);
+}
+
+#[test]
+fn check_runtime_api_versions() {
+	assert_eq!(
+		&RUNTIME_API_VERSIONS[..],
+		&[
+			(<Api<Block> as RuntimeApiInfo>::ID, 1),
+			(<ApiWithCustomVersion<Block> as RuntimeApiInfo>::ID, 2),
+			(<runtime_api::Core<Block> as RuntimeApiInfo>::ID, 1),
+		][..],
+	);
+}
+
+pub struct MockApi {
+	block: Option<Block>,
+}
+
+mock_impl_runtime_apis! {
+	impl Api<Block> for MockApi {
+		fn test(_: u64) {
+			unimplemented!()
+		}
+
+		fn something_with_block(block: Block) -> Block {
+			self.block.clone().unwrap_or_else(|| block.clone())
+		}
+
+		fn function_with_two_args(_: u64, _: Block) {
+			unimplemented!()
+		}
+	}
+
+	impl ApiWithCustomVersion<Block> for MockApi {
+		fn same_name() {}
+	}
+}
+
+#[test]
+fn test_mock_client_side_function_signature() {
+	let _test: fn(&MockApi, &BlockId<Block>, &u64) -> Result<()> = MockApi::test;
+	let _something_with_block: fn(&MockApi, &BlockId<Block>, &Block) -> Result<Block> =
+		MockApi::something_with_block;
+	let _test_with_context: fn(&MockApi, &BlockId<Block>, ExecutionContext, &u64) -> Result<()> =
+		MockApi::test_with_context;
+}
+
+#[test]
+fn check_mock_runtime_api() {
+	use substrate_client::runtime_api::ApiExt;
+
+	let header = Header {
+		parent_hash: Default::default(),
+		number: 1,
+		state_root: Default::default(),
+		extrinsics_root: Default::default(),
+		digest: Default::default(),
+	};
+	let block = Block { header, extrinsics: Vec::new() };
+	let mut mock = MockApi { block: None };
+	let at = BlockId::Number(0);
+
+	assert_eq!(mock.something_with_block(&at, &block).unwrap(), block);
+	assert!(mock.has_api::<ApiWithCustomVersion<Block>>(&at).unwrap());
+	assert!(mock.has_api_with::<ApiWithCustomVersion<Block>, _>(&at, |v| v >= 2).unwrap());
+	mock.same_name(&at).unwrap();
+
+	let snapshot = mock.snapshot();
+	mock.revert_to(snapshot).unwrap();
+	mock.snapshot();
+	mock.commit().unwrap();
+
+	mock.record_proof();
+	mock.same_name(&at).unwrap();
+	assert_eq!(mock.extract_proof(), Some(Vec::new()));
+}
+
+#[test]
+fn wasm_interface_reports_argument_that_failed_to_decode() {
+	let mut input = [0u8; 8];
+	assert_eq!(api::function_with_two_args(input.as_mut_ptr(), 4), 0);
+	assert_eq!(api::function_with_two_args(input.as_mut_ptr(), 8), 1 << 32);
 }
@@ -0,0 +1,9 @@
+extern crate trybuild;
+
+/// Malformed runtime api implementations are checked against the errors in the `.stderr` files
+/// next to them. Run with `TRYBUILD=overwrite` to update them after changing an error.
+#[test]
+fn malformed_implementations_fail_with_their_errors() {
+	let cases = trybuild::TestCases::new();
+	cases.compile_fail("tests/ui/*.rs");
+}
@@ -0,0 +1,17 @@
+#[macro_use]
+extern crate substrate_client;
+extern crate sr_primitives as runtime_primitives;
+
+decl_runtime_apis! {
+	pub trait Api {
+		fn test(data: u64);
+	}
+}
+
+impl_runtime_apis! {
+	impl Runtime {
+		fn test(data: u64) {}
+	}
+}
+
+fn main() {}
@@ -27,12 +27,15 @@ extern crate hash_db;
 #[doc(hidden)]
 pub extern crate parity_codec as codec;
 // re-export hashing functions.
-pub use primitives::{blake2_256, twox_128, twox_256, ed25519};
+pub use primitives::{blake2_256, keccak_256, twox_128, twox_256, ed25519};
 
-pub use primitives::{Blake2Hasher};
+pub use primitives::{Blake2Hasher, KeccakHasher};
 // Switch to this after PoC-3
 // pub use primitives::BlakeHasher;
 pub use substrate_state_machine::{Externalities, TestExternalities};
+pub use primitives::crypto::KeyTypeId;
+use substrate_state_machine::Capabilities;
+use primitives::crypto::Keystore;
 use primitives::hexdisplay::HexDisplay;
 use primitives::H256;
 use hash_db::Hasher;
@@ -121,6 +124,15 @@ pub fn exists_child_storage(storage_key: &[u8], key: &[u8]) -> bool {
 	).unwrap_or(false)
 }
 
+/// Append a SCALE-encoded item to the SCALE-encoded `Vec` stored under `key`.
+///
+/// If there is no `Vec` under `key`, a new one is created with `encoded_item` as its only item.
+pub fn storage_append(key: &[u8], encoded_item: &[u8]) {
+	ext::with(|ext|
+		ext.storage_append(key.to_vec(), encoded_item.to_vec())
+	);
+}
+
 /// Clear the storage entries with a key that starts with the given prefix.
 pub fn clear_prefix(prefix: &[u8]) {
 	ext::with(|ext|
@@ -128,6 +140,17 @@ pub fn clear_prefix(prefix: &[u8]) {
 	);
 }
 
+/// Clear at most `limit` storage entries with a key that starts with the given prefix, starting
+/// from the `cursor` key if any.
+///
+/// Returns the number of removed entries and the cursor to continue from, if any entry with the
+/// prefix is left.
+pub fn clear_prefix_limited(prefix: &[u8], limit: u32, cursor: Option<&[u8]>) -> (u32, Option<Vec<u8>>) {
+	ext::with(|ext|
+		ext.clear_prefix_limited(prefix, limit, cursor)
+	).unwrap_or((0, None))
+}
+
 /// Clear an entire child storage.
 pub fn kill_child_storage(storage_key: &[u8]) {
 	ext::with(|ext|
@@ -200,6 +223,34 @@ pub fn ed25519_verify<P: AsRef<[u8]>>(sig: &[u8; 64], msg: &[u8], pubkey: P) ->
 	ed25519::verify(sig, msg, pubkey)
 }
 
+fn with_keystore<R, F: FnOnce(&Keystore) -> R>(f: F) -> Option<R> {
+	ext::with(|ext| {
+		assert!(
+			ext.capabilities().contains(Capabilities::KEYSTORE),
+			"the keystore is only available to offchain workers"
+		);
+		ext.keystore().map(f)
+	}).and_then(|r| r)
+}
+
+/// Public keys of the given type in the keystore of the node. Offchain workers only.
+pub fn keystore_public_keys(key_type: KeyTypeId) -> Vec<[u8; 32]> {
+	with_keystore(|keystore| keystore.public_keys(key_type).into_iter().map(|public| public.0).collect())
+		.unwrap_or_default()
+}
+
+/// Sign `msg` with the key of the given type and public key from the keystore of the node.
+/// Returns `None` if there is no such key. Offchain workers only.
+pub fn keystore_sign(key_type: KeyTypeId, public: &[u8; 32], msg: &[u8]) -> Option<[u8; 64]> {
+	with_keystore(|keystore| keystore.sign(key_type, &ed25519::Public(*public), msg))
+		.and_then(|signature| signature)
+		.map(|signature| {
+			let mut raw = [0u8; 64];
+			raw.copy_from_slice(signature.as_ref());
+			raw
+		})
+}
+
 /// Execute the given closure with global function available whose functionality routes into the
 /// externalities `ext`. Forwards the value that the closure returns.
 // NOTE: need a concrete hasher here due to limitations of the `environmental!` macro, otherwise a type param would have been fine I think.
@@ -294,4 +345,33 @@ mod std_tests {
 			assert!(storage(b":abc").is_none());
 		});
 	}
+
+	#[test]
+	fn clear_prefix_limited_works() {
+		let mut t = TestExternalities::<Blake2Hasher>::new(map![
+			b":a".to_vec() => b"\x0b\0\0\0Hello world".to_vec(),
+			b":abcd".to_vec() => b"\x0b\0\0\0Hello world".to_vec(),
+			b":abc".to_vec() => b"\x0b\0\0\0Hello world".to_vec(),
+			b":abdd".to_vec() => b"\x0b\0\0\0Hello world".to_vec()
+		]);
+
+		with_externalities(&mut t, || {
+			assert_eq!(clear_prefix_limited(b":ab", 1, None), (1, Some(b":abcd".to_vec())));
+			assert!(storage(b":abc").is_none());
+			assert!(storage(b":abcd").is_some());
+
+			assert_eq!(clear_prefix_limited(b":ab", 5, Some(b":abcd")), (2, None));
+			assert!(storage(b":a").is_some());
+			assert!(storage(b":abdd").is_none());
+		});
+	}
+
+	#[test]
+	#[should_panic(expected = "the keystore is only available to offchain workers")]
+	fn keystore_is_not_available_to_consensus_code() {
+		let mut t = TestExternalities::<Blake2Hasher>::default();
+		with_externalities(&mut t, || {
+			keystore_public_keys(primitives::crypto::key_types::IM_ONLINE);
+		});
+	}
 }
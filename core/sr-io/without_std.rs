@@ -27,7 +27,8 @@ pub extern crate parity_codec as codec;
 use core::intrinsics;
 use rstd::vec::Vec;
 use hash_db::Hasher;
-use primitives::Blake2Hasher;
+use primitives::{Blake2Hasher, KeccakHasher};
+pub use primitives::crypto::KeyTypeId;
 pub use rstd::{mem, slice};
 
 #[panic_handler]
@@ -65,6 +66,8 @@ extern "C" {
 	fn ext_exists_storage(key_data: *const u8, key_len: u32) -> u32;
 	fn ext_exists_child_storage(storage_key_data: *const u8, storage_key_len: u32, key_data: *const u8, key_len: u32) -> u32;
 	fn ext_clear_prefix(prefix_data: *const u8, prefix_len: u32);
+	fn ext_storage_append(key_data: *const u8, key_len: u32, item_data: *const u8, item_len: u32);
+	fn ext_clear_prefix_limited(prefix_data: *const u8, prefix_len: u32, cursor_data: *const u8, cursor_len: u32, limit: u32, removed: *mut u32, next_len: *mut u32) -> *mut u8;
 	fn ext_kill_child_storage(storage_key_data: *const u8, storage_key_len: u32);
 	fn ext_get_allocated_storage(key_data: *const u8, key_len: u32, written_out: *mut u32) -> *mut u8;
 	fn ext_get_allocated_child_storage(storage_key_data: *const u8, storage_key_len: u32, key_data: *const u8, key_len: u32, written_out: *mut u32) -> *mut u8;
@@ -76,9 +79,13 @@ extern "C" {
 	fn ext_blake2_256_enumerated_trie_root(values_data: *const u8, lens_data: *const u32, lens_len: u32, result: *mut u8);
 	fn ext_chain_id() -> u64;
 	fn ext_blake2_256(data: *const u8, len: u32, out: *mut u8);
+	fn ext_keccak_256(data: *const u8, len: u32, out: *mut u8);
+	fn ext_keccak_256_enumerated_trie_root(values_data: *const u8, lens_data: *const u32, lens_len: u32, result: *mut u8);
 	fn ext_twox_128(data: *const u8, len: u32, out: *mut u8);
 	fn ext_twox_256(data: *const u8, len: u32, out: *mut u8);
 	fn ext_ed25519_verify(msg_data: *const u8, msg_len: u32, sig_data: *const u8, pubkey_data: *const u8) -> u32;
+	fn ext_keystore_public_keys(key_type_data: *const u8, written_out: *mut u32) -> *mut u8;
+	fn ext_keystore_sign(key_type_data: *const u8, pubkey_data: *const u8, msg_data: *const u8, msg_len: u32, sig_out: *mut u8) -> u32;
 }
 
 /// Ensures we use the right crypto when calling into native
@@ -104,6 +111,24 @@ impl ExternTrieCrypto for Blake2Hasher {
 	}
 }
 
+// Ensures we use a Keccak-flavoured Hasher when calling into native
+impl ExternTrieCrypto for KeccakHasher {
+	fn enumerated_trie_root(values: &[&[u8]]) -> [u8; 32] {
+		let lengths = values.iter().map(|v| (v.len() as u32).to_le()).collect::<Vec<_>>();
+		let values = values.iter().fold(Vec::new(), |mut acc, sl| { acc.extend_from_slice(sl); acc });
+		let mut result: [u8; 32] = Default::default();
+		unsafe {
+			ext_keccak_256_enumerated_trie_root(
+				values.as_ptr(),
+				lengths.as_ptr(),
+				lengths.len() as u32,
+				result.as_mut_ptr()
+			);
+		}
+		result
+	}
+}
+
 /// Get `key` from storage and return a `Vec`, empty if there's a problem.
 pub fn storage(key: &[u8]) -> Option<Vec<u8>> {
 	let mut length: u32 = 0;
@@ -193,6 +218,18 @@ pub fn exists_child_storage(storage_key: &[u8], key: &[u8]) -> bool {
 	}
 }
 
+/// Append a SCALE-encoded item to the SCALE-encoded `Vec` stored under `key`.
+///
+/// If there is no `Vec` under `key`, a new one is created with `encoded_item` as its only item.
+pub fn storage_append(key: &[u8], encoded_item: &[u8]) {
+	unsafe {
+		ext_storage_append(
+			key.as_ptr(), key.len() as u32,
+			encoded_item.as_ptr(), encoded_item.len() as u32
+		);
+	}
+}
+
 /// Clear the storage entries key of which starts with the given prefix.
 pub fn clear_prefix(prefix: &[u8]) {
 	unsafe {
@@ -203,6 +240,38 @@ pub fn clear_prefix(prefix: &[u8]) {
 	}
 }
 
+/// Clear at most `limit` storage entries key of which starts with the given prefix, starting from
+/// the `cursor` key if any.
+///
+/// Returns the number of removed entries and the cursor to continue from, if any entry with the
+/// prefix is left.
+pub fn clear_prefix_limited(prefix: &[u8], limit: u32, cursor: Option<&[u8]>) -> (u32, Option<Vec<u8>>) {
+	let (cursor_data, cursor_len) = match cursor {
+		Some(cursor) => (cursor.as_ptr(), cursor.len() as u32),
+		None => (core::ptr::null(), u32::max_value()),
+	};
+	let mut removed = 0u32;
+	let mut next_len = 0u32;
+	unsafe {
+		let ptr = ext_clear_prefix_limited(
+			prefix.as_ptr(),
+			prefix.len() as u32,
+			cursor_data,
+			cursor_len,
+			limit,
+			&mut removed,
+			&mut next_len
+		);
+		if next_len == u32::max_value() {
+			(removed, None)
+		} else {
+			let next = slice::from_raw_parts(ptr, next_len as usize).to_vec();
+			ext_free(ptr);
+			(removed, Some(next))
+		}
+	}
+}
+
 /// Clear an entire child storage.
 pub fn kill_child_storage(storage_key: &[u8]) {
 	unsafe {
@@ -326,6 +395,15 @@ pub fn blake2_256(data: &[u8]) -> [u8; 32] {
 	result
 }
 
+/// Conduct a 256-bit Keccak hash.
+pub fn keccak_256(data: &[u8]) -> [u8; 32] {
+	let mut result: [u8; 32] = Default::default();
+	unsafe {
+		ext_keccak_256(data.as_ptr(), data.len() as u32, result.as_mut_ptr());
+	}
+	result
+}
+
 /// Conduct four XX hashes to give a 256-bit result.
 pub fn twox_256(data: &[u8]) -> [u8; 32] {
 	let mut result: [u8; 32] = Default::default();
@@ -351,6 +429,34 @@ pub fn ed25519_verify<P: AsRef<[u8]>>(sig: &[u8; 64], msg: &[u8], pubkey: P) ->
 	}
 }
 
+/// Public keys of the given type in the keystore of the node. Offchain workers only.
+pub fn keystore_public_keys(key_type: KeyTypeId) -> Vec<[u8; 32]> {
+	let mut length: u32 = 0;
+	unsafe {
+		let ptr = ext_keystore_public_keys(key_type.as_ptr(), &mut length);
+		let keys = slice::from_raw_parts(ptr, length as usize)
+			.chunks(32)
+			.map(|raw| {
+				let mut public = [0u8; 32];
+				public.copy_from_slice(raw);
+				public
+			})
+			.collect();
+		ext_free(ptr);
+		keys
+	}
+}
+
+/// Sign `msg` with the key of the given type and public key from the keystore of the node.
+/// Returns `None` if there is no such key. Offchain workers only.
+pub fn keystore_sign(key_type: KeyTypeId, public: &[u8; 32], msg: &[u8]) -> Option<[u8; 64]> {
+	let mut signature = [0u8; 64];
+	let signed = unsafe {
+		ext_keystore_sign(key_type.as_ptr(), public.as_ptr(), msg.as_ptr(), msg.len() as u32, signature.as_mut_ptr()) == 0
+	};
+	if signed { Some(signature) } else { None }
+}
+
 /// Trait for things which can be printed.
 pub trait Printable {
 	fn print(self);
@@ -20,8 +20,7 @@ use rstd::prelude::*;
 
 use codec::{Decode, Encode, Codec, Input};
 use traits::{self, Member, DigestItem as DigestItemT, MaybeSerializeDebug};
-
-use substrate_primitives::hash::H512 as Signature;
+use ConsensusEngineId;
 
 #[derive(PartialEq, Eq, Clone, Encode, Decode)]
 #[cfg_attr(feature = "std", derive(Debug, Serialize))]
@@ -66,8 +65,16 @@ pub enum DigestItem<Hash, AuthorityId> {
 	/// block. It is created for every block iff runtime supports changes
 	/// trie creation.
 	ChangesTrieRoot(Hash),
-	/// Put a Seal on it
-	Seal(u64, Signature),
+	/// A message from the consensus engine to the runtime, put into the header by the block
+	/// author. It is an input to the execution of the block, e.g. the slot the block was
+	/// authored in.
+	PreRuntime(ConsensusEngineId, Vec<u8>),
+	/// A message from the runtime to the consensus engine.
+	Consensus(ConsensusEngineId, Vec<u8>),
+	/// A seal of the consensus engine, e.g. a signature of the block author. Seals are put
+	/// into the header after the block was executed and are stripped before importing it,
+	/// so they are never seen by the runtime.
+	Seal(ConsensusEngineId, Vec<u8>),
 	/// Any 'non-system' digest item, opaque to the native code.
 	Other(Vec<u8>),
 }
@@ -82,8 +89,12 @@ pub enum DigestItemRef<'a, Hash: 'a, AuthorityId: 'a> {
 	AuthoritiesChange(&'a [AuthorityId]),
 	/// Reference to `DigestItem::ChangesTrieRoot`.
 	ChangesTrieRoot(&'a Hash),
-	/// A sealed signature for testing
-	Seal(&'a u64, &'a Signature),
+	/// Reference to `DigestItem::PreRuntime`.
+	PreRuntime(&'a ConsensusEngineId, &'a Vec<u8>),
+	/// Reference to `DigestItem::Consensus`.
+	Consensus(&'a ConsensusEngineId, &'a Vec<u8>),
+	/// Reference to `DigestItem::Seal`.
+	Seal(&'a ConsensusEngineId, &'a Vec<u8>),
 	/// Any 'non-system' digest item, opaque to the native code.
 	/// Reference to `DigestItem::Other`.
 	Other(&'a Vec<u8>),
@@ -100,6 +111,8 @@ enum DigestItemType {
 	AuthoritiesChange,
 	ChangesTrieRoot,
 	Seal,
+	PreRuntime,
+	Consensus,
 }
 
 impl<Hash, AuthorityId> DigestItem<Hash, AuthorityId> {
@@ -116,7 +129,9 @@ impl<Hash, AuthorityId> DigestItem<Hash, AuthorityId> {
 		match *self {
 			DigestItem::AuthoritiesChange(ref v) => DigestItemRef::AuthoritiesChange(v),
 			DigestItem::ChangesTrieRoot(ref v) => DigestItemRef::ChangesTrieRoot(v),
-			DigestItem::Seal(ref v, ref s) => DigestItemRef::Seal(v, s),
+			DigestItem::PreRuntime(ref id, ref v) => DigestItemRef::PreRuntime(id, v),
+			DigestItem::Consensus(ref id, ref v) => DigestItemRef::Consensus(id, v),
+			DigestItem::Seal(ref id, ref v) => DigestItemRef::Seal(id, v),
 			DigestItem::Other(ref v) => DigestItemRef::Other(v),
 		}
 	}
@@ -136,6 +151,18 @@ impl<
 	fn as_changes_trie_root(&self) -> Option<&Hash> {
 		self.dref().as_changes_trie_root()
 	}
+
+	fn as_pre_runtime(&self) -> Option<(ConsensusEngineId, &[u8])> {
+		self.dref().as_pre_runtime()
+	}
+
+	fn as_consensus(&self) -> Option<(ConsensusEngineId, &[u8])> {
+		self.dref().as_consensus()
+	}
+
+	fn as_seal(&self) -> Option<(ConsensusEngineId, &[u8])> {
+		self.dref().as_seal()
+	}
 }
 
 impl<Hash: Encode, AuthorityId: Encode> Encode for DigestItem<Hash, AuthorityId> {
@@ -154,8 +181,16 @@ impl<Hash: Decode, AuthorityId: Decode> Decode for DigestItem<Hash, AuthorityId>
 			DigestItemType::ChangesTrieRoot => Some(DigestItem::ChangesTrieRoot(
 				Decode::decode(input)?,
 			)),
+			DigestItemType::PreRuntime => {
+				let vals: (ConsensusEngineId, Vec<u8>) = Decode::decode(input)?;
+				Some(DigestItem::PreRuntime(vals.0, vals.1))
+			},
+			DigestItemType::Consensus => {
+				let vals: (ConsensusEngineId, Vec<u8>) = Decode::decode(input)?;
+				Some(DigestItem::Consensus(vals.0, vals.1))
+			},
 			DigestItemType::Seal => {
-				let vals: (u64, Signature) = Decode::decode(input)?;
+				let vals: (ConsensusEngineId, Vec<u8>) = Decode::decode(input)?;
 				Some(DigestItem::Seal(vals.0, vals.1))
 			},
 			DigestItemType::Other => Some(DigestItem::Other(
@@ -179,6 +214,27 @@ impl<'a, Hash: Codec + Member, AuthorityId: Codec + Member> DigestItemRef<'a, Ha
 			_ => None,
 		}
 	}
+
+	pub fn as_pre_runtime(&self) -> Option<(ConsensusEngineId, &'a [u8])> {
+		match *self {
+			DigestItemRef::PreRuntime(id, data) => Some((*id, data)),
+			_ => None,
+		}
+	}
+
+	pub fn as_consensus(&self) -> Option<(ConsensusEngineId, &'a [u8])> {
+		match *self {
+			DigestItemRef::Consensus(id, data) => Some((*id, data)),
+			_ => None,
+		}
+	}
+
+	pub fn as_seal(&self) -> Option<(ConsensusEngineId, &'a [u8])> {
+		match *self {
+			DigestItemRef::Seal(id, data) => Some((*id, data)),
+			_ => None,
+		}
+	}
 }
 
 impl<'a, Hash: Encode, AuthorityId: Encode> Encode for DigestItemRef<'a, Hash, AuthorityId> {
@@ -194,9 +250,17 @@ impl<'a, Hash: Encode, AuthorityId: Encode> Encode for DigestItemRef<'a, Hash, A
 				DigestItemType::ChangesTrieRoot.encode_to(&mut v);
 				changes_trie_root.encode_to(&mut v);
 			},
-			DigestItemRef::Seal(val, sig) => {
+			DigestItemRef::PreRuntime(id, data) => {
+				DigestItemType::PreRuntime.encode_to(&mut v);
+				(id, data).encode_to(&mut v);
+			},
+			DigestItemRef::Consensus(id, data) => {
+				DigestItemType::Consensus.encode_to(&mut v);
+				(id, data).encode_to(&mut v);
+			},
+			DigestItemRef::Seal(id, data) => {
 				DigestItemType::Seal.encode_to(&mut v);
-				(val, sig).encode_to(&mut v);
+				(id, data).encode_to(&mut v);
 			},
 			DigestItemRef::Other(val) => {
 				DigestItemType::Other.encode_to(&mut v);
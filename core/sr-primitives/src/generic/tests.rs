@@ -54,4 +54,22 @@ fn non_system_digest_item_encoding() {
 
 	let decoded: DigestItem<H256, u32> = Decode::decode(&mut &encoded[..]).unwrap();
 	assert_eq!(item, decoded);
-}
\ No newline at end of file
+}
+#[test]
+fn seal_digest_item_encoding() {
+	let item = DigestItem::Seal::<H256, u32>(*b"test", vec![10, 20, 30]);
+	let encoded = item.encode();
+	assert_eq!(encoded, vec![
+		// type = DigestItemType::Seal
+		3,
+		// consensus engine id
+		b't', b'e', b's', b't',
+		// length of seal data
+		12,
+		// seal data
+		10, 20, 30,
+	]);
+
+	let decoded: DigestItem<H256, u32> = Decode::decode(&mut &encoded[..]).unwrap();
+	assert_eq!(item, decoded);
+}
@@ -20,9 +20,9 @@
 use std::fmt;
 
 use rstd::prelude::*;
-use codec::{Decode, Encode, Input};
+use codec::{Decode, Encode, Input, Output};
 use traits::{self, Member, SimpleArithmetic, MaybeDisplay, CurrentHeight, BlockNumberToHash, Lookup,
-	Checkable, Extrinsic};
+	Checkable, Extrinsic, AdditionalSigned};
 use super::{CheckedExtrinsic, Era};
 
 const TRANSACTION_VERSION: u8 = 1;
@@ -57,6 +57,58 @@ impl<Address, Index, Call, Signature> UncheckedMortalExtrinsic<Address, Index, C
 	}
 }
 
+/// The payload the signer of an `UncheckedMortalExtrinsic` signs, encoded as its signature check
+/// encodes it. Sign extrinsics through this rather than by encoding the payload by hand, so that
+/// their signatures keep matching the check.
+#[derive(PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct SignedPayload<Index, Call, Hash, Additional> {
+	/// The number of extrinsics that have come before from the same signer.
+	pub index: Index,
+	/// The function that should be called.
+	pub function: Call,
+	/// The longevity of the extrinsic.
+	pub era: Era,
+	/// The hash of the block the era starts at, i.e. the genesis hash for immortal extrinsics.
+	pub birth_hash: Hash,
+	/// The data the chain requires signatures to commit to, see `AdditionalSigned`.
+	pub additional: Additional,
+}
+
+impl<Index, Call, Hash, Additional> SignedPayload<Index, Call, Hash, Additional> {
+	/// Create the payload of an extrinsic.
+	pub fn new(index: Index, function: Call, era: Era, birth_hash: Hash, additional: Additional) -> Self {
+		SignedPayload { index, function, era, birth_hash, additional }
+	}
+
+	/// Sign the payload with `sign`, given the encoded payload, and build the extrinsic signed by
+	/// `signed`.
+	pub fn sign<Address, Signature, F>(self, signed: Address, sign: F)
+		-> UncheckedMortalExtrinsic<Address, Index, Call, Signature>
+	where
+		Index: Encode,
+		Call: Encode,
+		Hash: Encode,
+		Additional: Encode,
+		F: FnOnce(&[u8]) -> Signature,
+	{
+		let signature = self.using_encoded(sign);
+		UncheckedMortalExtrinsic::new_signed(self.index, self.function, signed, signature, self.era)
+	}
+}
+
+impl<Index: Encode, Call: Encode, Hash: Encode, Additional: Encode> Encode
+	for SignedPayload<Index, Call, Hash, Additional>
+{
+	fn encode_to<T: Output>(&self, dest: &mut T) {
+		self.index.encode_to(dest);
+		self.function.encode_to(dest);
+		self.era.encode_to(dest);
+		self.birth_hash.encode_to(dest);
+		self.additional.encode_to(dest);
+	}
+}
+
 impl<Address: Encode, Index: Encode, Call: Encode, Signature: Encode> Extrinsic for UncheckedMortalExtrinsic<Address, Index, Call, Signature> {
 	fn is_signed(&self) -> Option<bool> {
 		Some(self.signature.is_some())
@@ -75,7 +127,8 @@ where
 	Hash: Encode,
 	Context: Lookup<Source=Address, Target=AccountId>
 		+ CurrentHeight<BlockNumber=BlockNumber>
-		+ BlockNumberToHash<BlockNumber=BlockNumber, Hash=Hash>,
+		+ BlockNumberToHash<BlockNumber=BlockNumber, Hash=Hash>
+		+ AdditionalSigned,
 {
 	type Checked = CheckedExtrinsic<AccountId, Index, Call>;
 
@@ -84,14 +137,14 @@ where
 			Some((signed, signature, index, era)) => {
 				let h = context.block_number_to_hash(BlockNumber::sa(era.birth(context.current_height().as_())))
 					.ok_or("transaction birth block ancient")?;
-				let payload = (index, self.function, era, h);
+				let payload = SignedPayload::new(index, self.function, era, h, context.additional_signed());
 				let signed = context.lookup(signed)?;
 				if !::verify_encoded_lazy(&signature, &payload, &signed) {
 					return Err("bad signature in extrinsic")
 				}
 				CheckedExtrinsic {
-					signed: Some((signed, payload.0)),
-					function: payload.1,
+					signed: Some((signed, payload.index)),
+					function: payload.function,
 				}
 			}
 			None => CheckedExtrinsic {
@@ -197,6 +250,10 @@ mod tests {
 		type Hash = u64;
 		fn block_number_to_hash(&self, n: u64) -> Option<u64> { Some(n) }
 	}
+	impl AdditionalSigned for TestContext {
+		type AdditionalSigned = (u64, u32);
+		fn additional_signed(&self) -> (u64, u32) { DUMMY_ADDITIONAL }
+	}
 
 	#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize, Encode, Decode)]
 	struct TestSig(u64, Vec<u8>);
@@ -209,6 +266,7 @@ mod tests {
 
 	const DUMMY_FUNCTION: u64 = 0;
 	const DUMMY_ACCOUNTID: u64 = 0;
+	const DUMMY_ADDITIONAL: (u64, u32) = (0, 1);
 
 	type Ex = UncheckedMortalExtrinsic<u64, u64, u64, TestSig>;
 	type CEx = CheckedExtrinsic<u64, u64, u64>;
@@ -222,7 +280,7 @@ mod tests {
 
 	#[test]
 	fn signed_codec_should_work() {
-		let ux = Ex::new_signed(0, DUMMY_FUNCTION, DUMMY_ACCOUNTID, TestSig(DUMMY_ACCOUNTID, (DUMMY_ACCOUNTID, DUMMY_FUNCTION, Era::immortal(), 0u64).encode()), Era::immortal());
+		let ux = Ex::new_signed(0, DUMMY_FUNCTION, DUMMY_ACCOUNTID, TestSig(DUMMY_ACCOUNTID, (DUMMY_ACCOUNTID, DUMMY_FUNCTION, Era::immortal(), 0u64, DUMMY_ADDITIONAL).encode()), Era::immortal());
 		let encoded = ux.encode();
 		assert_eq!(Ex::decode(&mut &encoded[..]), Some(ux));
 	}
@@ -243,39 +301,66 @@ mod tests {
 
 	#[test]
 	fn immortal_signed_check_should_work() {
-		let ux = Ex::new_signed(0, DUMMY_FUNCTION, DUMMY_ACCOUNTID, TestSig(DUMMY_ACCOUNTID, (DUMMY_ACCOUNTID, DUMMY_FUNCTION, Era::immortal(), 0u64).encode()), Era::immortal());
+		let ux = Ex::new_signed(0, DUMMY_FUNCTION, DUMMY_ACCOUNTID, TestSig(DUMMY_ACCOUNTID, (DUMMY_ACCOUNTID, DUMMY_FUNCTION, Era::immortal(), 0u64, DUMMY_ADDITIONAL).encode()), Era::immortal());
 		assert!(ux.is_signed().unwrap_or(false));
 		assert_eq!(<Ex as Checkable<TestContext>>::check(ux, &TestContext), Ok(CEx { signed: Some((DUMMY_ACCOUNTID, 0)), function: DUMMY_FUNCTION }));
 	}
 
 	#[test]
 	fn mortal_signed_check_should_work() {
-		let ux = Ex::new_signed(0, DUMMY_FUNCTION, DUMMY_ACCOUNTID, TestSig(DUMMY_ACCOUNTID, (DUMMY_ACCOUNTID, DUMMY_FUNCTION, Era::mortal(32, 42), 42u64).encode()), Era::mortal(32, 42));
+		let ux = Ex::new_signed(0, DUMMY_FUNCTION, DUMMY_ACCOUNTID, TestSig(DUMMY_ACCOUNTID, (DUMMY_ACCOUNTID, DUMMY_FUNCTION, Era::mortal(32, 42), 42u64, DUMMY_ADDITIONAL).encode()), Era::mortal(32, 42));
 		assert!(ux.is_signed().unwrap_or(false));
 		assert_eq!(<Ex as Checkable<TestContext>>::check(ux, &TestContext), Ok(CEx { signed: Some((DUMMY_ACCOUNTID, 0)), function: DUMMY_FUNCTION }));
 	}
 
 	#[test]
 	fn later_mortal_signed_check_should_work() {
-		let ux = Ex::new_signed(0, DUMMY_FUNCTION, DUMMY_ACCOUNTID, TestSig(DUMMY_ACCOUNTID, (DUMMY_ACCOUNTID, DUMMY_FUNCTION, Era::mortal(32, 11), 11u64).encode()), Era::mortal(32, 11));
+		let ux = Ex::new_signed(0, DUMMY_FUNCTION, DUMMY_ACCOUNTID, TestSig(DUMMY_ACCOUNTID, (DUMMY_ACCOUNTID, DUMMY_FUNCTION, Era::mortal(32, 11), 11u64, DUMMY_ADDITIONAL).encode()), Era::mortal(32, 11));
 		assert!(ux.is_signed().unwrap_or(false));
 		assert_eq!(<Ex as Checkable<TestContext>>::check(ux, &TestContext), Ok(CEx { signed: Some((DUMMY_ACCOUNTID, 0)), function: DUMMY_FUNCTION }));
 	}
 
 	#[test]
 	fn too_late_mortal_signed_check_should_fail() {
-		let ux = Ex::new_signed(0, DUMMY_FUNCTION, DUMMY_ACCOUNTID, TestSig(DUMMY_ACCOUNTID, (DUMMY_ACCOUNTID, DUMMY_FUNCTION, Era::mortal(32, 10), 10u64).encode()), Era::mortal(32, 10));
+		let ux = Ex::new_signed(0, DUMMY_FUNCTION, DUMMY_ACCOUNTID, TestSig(DUMMY_ACCOUNTID, (DUMMY_ACCOUNTID, DUMMY_FUNCTION, Era::mortal(32, 10), 10u64, DUMMY_ADDITIONAL).encode()), Era::mortal(32, 10));
 		assert!(ux.is_signed().unwrap_or(false));
 		assert_eq!(<Ex as Checkable<TestContext>>::check(ux, &TestContext), Err("bad signature in extrinsic"));
 	}
 
 	#[test]
 	fn too_early_mortal_signed_check_should_fail() {
-		let ux = Ex::new_signed(0, DUMMY_FUNCTION, DUMMY_ACCOUNTID, TestSig(DUMMY_ACCOUNTID, (DUMMY_ACCOUNTID, DUMMY_FUNCTION, Era::mortal(32, 43), 43u64).encode()), Era::mortal(32, 43));
+		let ux = Ex::new_signed(0, DUMMY_FUNCTION, DUMMY_ACCOUNTID, TestSig(DUMMY_ACCOUNTID, (DUMMY_ACCOUNTID, DUMMY_FUNCTION, Era::mortal(32, 43), 43u64, DUMMY_ADDITIONAL).encode()), Era::mortal(32, 43));
 		assert!(ux.is_signed().unwrap_or(false));
 		assert_eq!(<Ex as Checkable<TestContext>>::check(ux, &TestContext), Err("bad signature in extrinsic"));
 	}
 
+	#[test]
+	fn signed_without_additional_check_should_fail() {
+		let ux = Ex::new_signed(0, DUMMY_FUNCTION, DUMMY_ACCOUNTID, TestSig(DUMMY_ACCOUNTID, (DUMMY_ACCOUNTID, DUMMY_FUNCTION, Era::immortal(), 0u64).encode()), Era::immortal());
+		assert!(ux.is_signed().unwrap_or(false));
+		assert_eq!(<Ex as Checkable<TestContext>>::check(ux, &TestContext), Err("bad signature in extrinsic"));
+	}
+
+	#[test]
+	fn signed_with_other_additional_check_should_fail() {
+		let ux = Ex::new_signed(0, DUMMY_FUNCTION, DUMMY_ACCOUNTID, TestSig(DUMMY_ACCOUNTID, (DUMMY_ACCOUNTID, DUMMY_FUNCTION, Era::immortal(), 0u64, (0u64, 2u32)).encode()), Era::immortal());
+		assert!(ux.is_signed().unwrap_or(false));
+		assert_eq!(<Ex as Checkable<TestContext>>::check(ux, &TestContext), Err("bad signature in extrinsic"));
+	}
+
+	#[test]
+	fn signed_payload_check_should_work() {
+		let sign = |payload: &[u8]| TestSig(DUMMY_ACCOUNTID, payload.to_vec());
+		let payload = SignedPayload::new(0u64, DUMMY_FUNCTION, Era::mortal(32, 42), 42u64, DUMMY_ADDITIONAL);
+		assert_eq!(payload.encode(), (0u64, DUMMY_FUNCTION, Era::mortal(32, 42), 42u64, DUMMY_ADDITIONAL).encode());
+
+		let ux: Ex = payload.sign(DUMMY_ACCOUNTID, sign);
+		assert_eq!(<Ex as Checkable<TestContext>>::check(ux, &TestContext), Ok(CEx { signed: Some((DUMMY_ACCOUNTID, 0)), function: DUMMY_FUNCTION }));
+
+		let ux: Ex = SignedPayload::new(0u64, DUMMY_FUNCTION, Era::immortal(), 0u64, (0u64, 2u32)).sign(DUMMY_ACCOUNTID, sign);
+		assert_eq!(<Ex as Checkable<TestContext>>::check(ux, &TestContext), Err("bad signature in extrinsic"));
+	}
+
 	#[test]
 	fn encoding_matches_vec() {
 		let ex = Ex::new_unsigned(DUMMY_FUNCTION);
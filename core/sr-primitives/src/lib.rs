@@ -59,8 +59,27 @@ pub mod testing;
 pub mod traits;
 pub mod generic;
 pub mod transaction_validity;
+pub mod weights;
 
-pub type Justification = Vec<u8>;
+/// Consensus engine unique ID.
+pub type ConsensusEngineId = [u8; 4];
+
+/// The encoded justification specific to a consensus engine.
+pub type EncodedJustification = Vec<u8>;
+
+/// Justification of a block.
+///
+/// A list of encoded justifications, each tagged with the id of the consensus engine that
+/// produced it. This allows multiple finality gadgets to attach proofs to the same block.
+pub type Justification = Vec<(ConsensusEngineId, EncodedJustification)>;
+
+/// Returns the encoded justification of the given consensus engine, if there is any.
+pub fn justification_for_engine<'a>(
+	justification: &'a Justification,
+	engine_id: &ConsensusEngineId,
+) -> Option<&'a EncodedJustification> {
+	justification.iter().find(|(id, _)| id == engine_id).map(|(_, j)| j)
+}
 
 use traits::{Verify, Lazy};
 
@@ -398,6 +417,18 @@ macro_rules! impl_outer_log {
 			fn as_changes_trie_root(&self) -> Option<&Self::Hash> {
 				self.dref().and_then(|dref| dref.as_changes_trie_root())
 			}
+
+			fn as_pre_runtime(&self) -> Option<($crate::ConsensusEngineId, &[u8])> {
+				self.dref().and_then(|dref| dref.as_pre_runtime())
+			}
+
+			fn as_consensus(&self) -> Option<($crate::ConsensusEngineId, &[u8])> {
+				self.dref().and_then(|dref| dref.as_consensus())
+			}
+
+			fn as_seal(&self) -> Option<($crate::ConsensusEngineId, &[u8])> {
+				self.dref().and_then(|dref| dref.as_seal())
+			}
 		}
 
 		impl From<$crate::generic::DigestItem<$($genarg),*>> for $name {
@@ -485,7 +516,7 @@ impl BasicInherentData {
 //TODO: https://github.com/paritytech/substrate/issues/1022
 /// Error type used while checking inherents.
 #[derive(Encode)]
-#[cfg_attr(feature = "std", derive(Decode))]
+#[cfg_attr(feature = "std", derive(Decode, Debug))]
 pub enum CheckInherentError {
 	/// The inherents are generally valid but a delay until the given timestamp
 	/// is required.
@@ -494,6 +525,85 @@ pub enum CheckInherentError {
 	Other(RuntimeString),
 }
 
+impl CheckInherentError {
+	/// Is this error fatal?
+	///
+	/// A fatal error renders the block invalid, while a non-fatal error may disappear
+	/// at a later point in time (e.g. when the required timestamp is reached).
+	pub fn is_fatal(&self) -> bool {
+		match self {
+			CheckInherentError::ValidAtTimestamp(_) => false,
+			CheckInherentError::Other(_) => true,
+		}
+	}
+}
+
+/// The result of checking the inherents of a block.
+///
+/// Collects all errors that were encountered while checking the inherents. As soon as a fatal
+/// error is put into the result, no further errors are accepted.
+#[derive(Encode)]
+#[cfg_attr(feature = "std", derive(Decode, Debug))]
+pub struct CheckInherentsResult {
+	/// Did the check succeed?
+	okay: bool,
+	/// Did we encounter a fatal error?
+	fatal_error: bool,
+	/// All the errors that were encountered.
+	errors: Vec<CheckInherentError>,
+}
+
+impl CheckInherentsResult {
+	/// Create a new instance that does not contain any error.
+	pub fn new() -> Self {
+		Self {
+			okay: true,
+			fatal_error: false,
+			errors: Vec::new(),
+		}
+	}
+
+	/// Put an error into the result.
+	///
+	/// Returns `false` if the error was not accepted, because a fatal error was put before.
+	pub fn put_error(&mut self, error: CheckInherentError) -> bool {
+		if self.fatal_error {
+			return false
+		}
+
+		self.okay = false;
+		self.fatal_error = error.is_fatal();
+		self.errors.push(error);
+		true
+	}
+
+	/// Did the check succeed?
+	pub fn ok(&self) -> bool {
+		self.okay
+	}
+
+	/// Did we encounter a fatal error?
+	pub fn fatal_error(&self) -> bool {
+		self.fatal_error
+	}
+
+	/// Returns all the errors that were encountered.
+	pub fn errors(&self) -> &[CheckInherentError] {
+		&self.errors
+	}
+
+	/// Convert into the encountered errors.
+	pub fn into_errors(self) -> Vec<CheckInherentError> {
+		self.errors
+	}
+}
+
+impl Default for CheckInherentsResult {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use substrate_primitives::hash::H256;
@@ -567,4 +677,36 @@ mod tests {
 		// check that as-style methods are not working with regular items
 		assert!(b1.as_authorities_change().is_none());
 	}
+
+	#[test]
+	fn check_inherents_result_stops_at_fatal_error() {
+		use super::{CheckInherentsResult, CheckInherentError};
+
+		let mut result = CheckInherentsResult::new();
+		assert!(result.ok());
+
+		assert!(result.put_error(CheckInherentError::ValidAtTimestamp(10)));
+		assert!(!result.ok());
+		assert!(!result.fatal_error());
+
+		assert!(result.put_error(CheckInherentError::Other("invalid".into())));
+		assert!(result.fatal_error());
+
+		assert!(!result.put_error(CheckInherentError::ValidAtTimestamp(20)));
+		assert_eq!(result.errors().len(), 2);
+
+		let encoded = result.encode();
+		let decoded: CheckInherentsResult = DecodeHidden::decode(&mut &encoded[..]).unwrap();
+		assert!(decoded.fatal_error());
+		assert_eq!(decoded.into_errors().len(), 2);
+	}
+
+	#[test]
+	fn justification_for_engine_works() {
+		let justification: super::Justification = vec![(*b"FRNK", vec![1, 2]), (*b"test", vec![3])];
+
+		assert_eq!(super::justification_for_engine(&justification, b"FRNK"), Some(&vec![1, 2]));
+		assert_eq!(super::justification_for_engine(&justification, b"test"), Some(&vec![3]));
+		assert_eq!(super::justification_for_engine(&justification, b"none"), None);
+	}
 }
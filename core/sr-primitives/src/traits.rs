@@ -22,7 +22,7 @@ use runtime_io;
 #[cfg(feature = "std")] use std::fmt::{Debug, Display};
 #[cfg(feature = "std")] use serde::{Serialize, de::DeserializeOwned};
 use substrate_primitives;
-use substrate_primitives::Blake2Hasher;
+use substrate_primitives::{Blake2Hasher, KeccakHasher};
 use codec::{Codec, Encode, HasCompact};
 pub use integer_sqrt::IntegerSquareRoot;
 pub use num_traits::{Zero, One, Bounded};
@@ -91,15 +91,33 @@ pub trait BlockNumberToHash {
 	}
 }
 
+/// Data that signed extrinsics commit to without carrying it, e.g. the genesis hash of the chain
+/// and the specification version of the runtime. A transaction signed for another chain, or for
+/// a runtime that interprets its call differently, fails its signature check.
+pub trait AdditionalSigned {
+	/// The type of the additional data.
+	type AdditionalSigned: Encode;
+
+	/// Get the additional data signed extrinsics must have been signed with.
+	fn additional_signed(&self) -> Self::AdditionalSigned;
+}
+
 /// Simple payment making trait, operating on a single generic `AccountId` type.
 pub trait MakePayment<AccountId> {
 	/// Make some sort of payment concerning `who` for an extrinsic (transaction) of encoded length
-	/// `encoded_len` bytes. Return true iff the payment was successful.
-	fn make_payment(who: &AccountId, encoded_len: usize) -> Result<(), &'static str>;
+	/// `encoded_len` bytes whose call has the given `weight`. Return true iff the payment was
+	/// successful.
+	fn make_payment(who: &AccountId, encoded_len: usize, weight: u32) -> Result<(), &'static str>;
+
+	/// Refund `who` the part of the payment made for an extrinsic of encoded length `encoded_len`
+	/// bytes that accounts for the `unused_weight` its call didn't consume. If the fee of the
+	/// extrinsic is `waived`, the rest of the payment is refunded as well.
+	fn refund(who: &AccountId, encoded_len: usize, unused_weight: u32, waived: bool);
 }
 
 impl<T> MakePayment<T> for () {
-	fn make_payment(_: &T, _: usize) -> Result<(), &'static str> { Ok(()) }
+	fn make_payment(_: &T, _: usize, _: u32) -> Result<(), &'static str> { Ok(()) }
+	fn refund(_: &T, _: usize, _: u32, _: bool) {}
 }
 
 /// Extensible conversion trait. Generic over both source and destination types.
@@ -315,6 +333,44 @@ impl Hash for BlakeTwo256 {
 	}
 }
 
+/// Keccak-256 Hash implementation, for chains that need an Ethereum-compatible state trie.
+///
+/// The storage root is computed by the node, whose state backend must use `KeccakHasher` too.
+/// Such a runtime has to be executed in wasm, e.g. by the `WasmExecutor`, which is generic over
+/// the state hasher: the native environment only provides a state hashed with `Blake2Hasher`.
+#[derive(PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "std", derive(Debug, Serialize, Deserialize))]
+pub struct Keccak256;
+
+impl Hash for Keccak256 {
+	type Output = substrate_primitives::H256;
+	fn hash(s: &[u8]) -> Self::Output {
+		runtime_io::keccak_256(s).into()
+	}
+	fn enumerated_trie_root(items: &[&[u8]]) -> Self::Output {
+		runtime_io::enumerated_trie_root::<KeccakHasher>(items).into()
+	}
+	fn trie_root<
+		I: IntoIterator<Item = (A, B)>,
+		A: AsRef<[u8]> + Ord,
+		B: AsRef<[u8]>
+	>(input: I) -> Self::Output {
+		runtime_io::trie_root::<KeccakHasher, _, _, _>(input).into()
+	}
+	fn ordered_trie_root<
+		I: IntoIterator<Item = A> + Iterator<Item = A>,
+		A: AsRef<[u8]>
+	>(input: I) -> Self::Output {
+		runtime_io::ordered_trie_root::<KeccakHasher, _, _>(input).into()
+	}
+	fn storage_root() -> Self::Output {
+		runtime_io::storage_root().into()
+	}
+	fn storage_changes_root(parent_hash: Self::Output, parent_number: u64) -> Option<Self::Output> {
+		runtime_io::storage_changes_root(parent_hash.into(), parent_number).map(Into::into)
+	}
+}
+
 /// Something that can be checked for equality and printed out to a debug channel if bad.
 pub trait CheckEqual {
 	fn check_equal(&self, other: &Self);
@@ -576,6 +632,15 @@ pub trait DigestItem: Codec + Member + MaybeSerializeDebugButNotDeserialize {
 
 	/// Returns Some if the entry is the `ChangesTrieRoot` entry.
 	fn as_changes_trie_root(&self) -> Option<&Self::Hash>;
+
+	/// Returns Some if the entry is a `PreRuntime` entry, along with the engine id.
+	fn as_pre_runtime(&self) -> Option<(::ConsensusEngineId, &[u8])>;
+
+	/// Returns Some if the entry is a `Consensus` entry, along with the engine id.
+	fn as_consensus(&self) -> Option<(::ConsensusEngineId, &[u8])>;
+
+	/// Returns Some if the entry is a `Seal` entry, along with the engine id.
+	fn as_seal(&self) -> Option<(::ConsensusEngineId, &[u8])>;
 }
 
 /// Something that provides an inherent for a runtime.
@@ -615,6 +680,12 @@ impl<'a, T> rstd::ops::Deref for ApiRef<'a, T> {
 	}
 }
 
+impl<'a, T> rstd::ops::DerefMut for ApiRef<'a, T> {
+	fn deref_mut(&mut self) -> &mut T {
+		&mut self.0
+	}
+}
+
 /// Something that provides a runtime api.
 pub trait ProvideRuntimeApi {
 	/// The concrete type that provides the api.
@@ -0,0 +1,47 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The weight extrinsics consume in a block, shared by the runtime that accounts for it and the
+//! clients that read it from storage.
+
+/// The class of the call of an extrinsic, as far as the weight it consumes in a block is
+/// concerned.
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum DispatchClass {
+	/// A call made by users of the chain, e.g. a transfer.
+	Normal,
+	/// A call needed to run the chain, e.g. an inherent, a report of misbehaviour or a call
+	/// maintaining the chain.
+	Operational,
+}
+
+/// The weight consumed in a block by the calls of each class.
+#[derive(Encode, Decode, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct ClassWeights {
+	/// The weight of the normal calls.
+	pub normal: u32,
+	/// The weight of the operational calls.
+	pub operational: u32,
+}
+
+impl ClassWeights {
+	/// The weight of all calls.
+	pub fn total(&self) -> u32 {
+		self.normal.saturating_add(self.operational)
+	}
+}
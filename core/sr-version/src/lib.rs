@@ -26,6 +26,8 @@ extern crate serde_derive;
 #[macro_use]
 extern crate sr_std as rstd;
 
+#[cfg(feature = "std")]
+extern crate parity_codec as codec;
 #[macro_use]
 extern crate parity_codec_derive;
 
@@ -35,12 +37,22 @@ extern crate sr_primitives as runtime_primitives;
 use std::fmt;
 #[cfg(feature = "std")]
 use std::collections::HashSet;
+#[cfg(feature = "std")]
+use codec::Decode;
 
 use runtime_primitives::RuntimeString;
 
 /// The identity of a particular API interface that the runtime might provide.
 pub type ApiId = [u8; 8];
 
+/// The name of the wasm custom section that contains the encoded runtime version, without the
+/// `apis`.
+pub const RUNTIME_VERSION_SECTION: &str = "runtime_version";
+
+/// The name of the wasm custom section that contains the versions of the runtime apis. Each api
+/// is stored as its encoded `(ApiId, u32)` tuple.
+pub const RUNTIME_APIS_SECTION: &str = "runtime_apis";
+
 /// A vector of pairs of `ApiId` and a `u32` for version. For `"std"` builds, this
 /// is a `Cow`.
 #[cfg(feature = "std")]
@@ -129,6 +141,34 @@ impl RuntimeVersion {
 	pub fn has_api(&self, api: ApiId, version: u32) -> bool {
 		self.apis.iter().any(|&(ref s, v)| &api == s && version == v)
 	}
+
+	/// Check if this version supports a particular API in a version satisfying the given
+	/// predicate, e.g. any version from the one introducing a method on.
+	pub fn has_api_with<P: Fn(u32) -> bool>(&self, api: ApiId, predicate: P) -> bool {
+		self.apis.iter().any(|&(ref s, v)| &api == s && predicate(v))
+	}
+
+	/// Decode the runtime version from the contents of the `runtime_version` and `runtime_apis`
+	/// custom sections of a wasm blob.
+	pub fn decode_from_custom_sections(version: &[u8], apis: &[u8]) -> Option<RuntimeVersion> {
+		let (spec_name, impl_name, authoring_version, spec_version, impl_version):
+			(String, String, u32, u32, u32) = Decode::decode(&mut &version[..])?;
+
+		let mut apis = apis;
+		let mut decoded_apis = Vec::new();
+		while !apis.is_empty() {
+			decoded_apis.push(<(ApiId, u32)>::decode(&mut apis)?);
+		}
+
+		Some(RuntimeVersion {
+			spec_name: spec_name.into(),
+			impl_name: impl_name.into(),
+			authoring_version,
+			spec_version,
+			impl_version,
+			apis: decoded_apis.into(),
+		})
+	}
 }
 
 #[cfg(feature = "std")]
@@ -234,6 +234,19 @@ impl<BlockHash: Hash, Key: Hash> StateDbSync<BlockHash, Key> {
 		commit
 	}
 
+	pub fn import_canonical<E: fmt::Debug>(&mut self, hash: &BlockHash, number: u64, mut changeset: ChangeSet<Key>) -> Result<CommitSet<Key>, Error<E>> {
+		changeset.deleted.clear();
+		let mut commit = CommitSet {
+			data: changeset,
+			meta: Default::default(),
+		};
+		self.non_canonical.set_last_canonicalized(hash, number, &mut commit)?;
+		if let Some(ref mut pruning) = self.pruning {
+			pruning.reset_pending(number, &mut commit);
+		}
+		Ok(commit)
+	}
+
 	pub fn best_canonical(&self) -> u64 {
 		return self.non_canonical.last_canonicalized_block_number()
 	}
@@ -316,6 +329,12 @@ impl<BlockHash: Hash, Key: Hash> StateDb<BlockHash, Key> {
 		self.db.write().canonicalize_block(hash)
 	}
 
+	/// Insert the state of a block directly as canonical, on an empty database. Used when
+	/// the chain starts at a block other than genesis. Following blocks are inserted on top of it.
+	pub fn import_canonical<E: fmt::Debug>(&self, hash: &BlockHash, number: u64, changeset: ChangeSet<Key>) -> Result<CommitSet<Key>, Error<E>> {
+		self.db.write().import_canonical(hash, number, changeset)
+	}
+
 	/// Prevents pruning of specified block and its descendants.
 	pub fn pin(&self, hash: &BlockHash) {
 		self.db.write().pin(hash)
@@ -460,4 +479,45 @@ mod tests {
 		assert!(!sdb.is_pruned(1));
 		assert!(db.data_eq(&make_db(&[1, 21, 3, 921, 922, 93, 94])));
 	}
+
+	#[test]
+	fn import_canonical_starts_chain_at_block() {
+		let mut db = make_db(&[]);
+		let state_db: StateDb<H256, H256> = StateDb::new(PruningMode::Constrained(Constraints {
+			max_blocks: Some(1),
+			max_mem: None,
+		}), &db).unwrap();
+
+		db.commit(&state_db.import_canonical::<io::Error>(&H256::from_low_u64_be(10), 10, make_changeset(&[10], &[])).unwrap());
+		assert!(state_db.import_canonical::<io::Error>(&H256::from_low_u64_be(10), 10, make_changeset(&[], &[])).is_err());
+		assert_eq!(state_db.best_canonical(), 10);
+		assert!(!state_db.is_pruned(10));
+
+		db.commit(
+			&state_db
+				.insert_block::<io::Error>(
+					&H256::from_low_u64_be(11),
+					11,
+					&H256::from_low_u64_be(10),
+					make_changeset(&[11], &[10]),
+				)
+				.unwrap(),
+		);
+		db.commit(&state_db.canonicalize_block(&H256::from_low_u64_be(11)));
+		db.commit(
+			&state_db
+				.insert_block::<io::Error>(
+					&H256::from_low_u64_be(12),
+					12,
+					&H256::from_low_u64_be(11),
+					make_changeset(&[12], &[]),
+				)
+				.unwrap(),
+		);
+		db.commit(&state_db.canonicalize_block(&H256::from_low_u64_be(12)));
+
+		assert!(state_db.is_pruned(10));
+		assert!(!state_db.is_pruned(11));
+		assert!(db.data_eq(&make_db(&[11, 12])));
+	}
 }
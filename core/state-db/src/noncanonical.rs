@@ -197,6 +197,18 @@ impl<BlockHash: Hash, Key: Hash> NonCanonicalOverlay<BlockHash, Key> {
 		self.last_canonicalized.as_ref().map(|&(_, n)| n).unwrap_or(0)
 	}
 
+	/// Mark a block as the last canonicalized one on an empty overlay, so that the following
+	/// blocks can be inserted on top of it. Adds changes to `commit`.
+	pub fn set_last_canonicalized<E: fmt::Debug>(&mut self, hash: &BlockHash, number: u64, commit: &mut CommitSet<Key>) -> Result<(), Error<E>> {
+		if !self.levels.is_empty() || self.last_canonicalized.is_some() {
+			return Err(Error::NonCanonical);
+		}
+		let last_canonicalized = (hash.clone(), number);
+		commit.meta.inserted.push((to_meta_key(LAST_CANONICAL, &()), last_canonicalized.encode()));
+		self.last_canonicalized = Some(last_canonicalized);
+		Ok(())
+	}
+
 	/// This may be called when the last finalization commit was applied to the database.
 	pub fn clear_overlay(&mut self) {
 		self.last_canonicalized_overlay.clear();
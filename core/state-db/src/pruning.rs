@@ -123,6 +123,15 @@ impl<BlockHash: Hash, Key: Hash> RefWindow<BlockHash, Key> {
 		self.pending_number
 	}
 
+	/// Start the window at block `number` on an empty database. Adds changes to `commit`.
+	pub fn reset_pending(&mut self, number: u64, commit: &mut CommitSet<Key>) {
+		debug_assert!(self.death_rows.is_empty());
+		if number > 0 {
+			commit.meta.inserted.push((to_meta_key(LAST_PRUNED, &()), (number - 1).encode()));
+		}
+		self.pending_number = number;
+	}
+
 	/// Prune next block. Expects at least one block in the window. Adds changes to `commit`.
 	pub fn prune_one(&mut self, commit: &mut CommitSet<Key>) {
 		let pruned = self.death_rows.pop_front().expect("prune_one is only called with a non-empty window");
@@ -63,6 +63,10 @@ pub trait Backend<H: Hasher> {
 	/// call `f` for each of those keys.
 	fn for_keys_with_prefix<F: FnMut(&[u8])>(&self, prefix: &[u8], f: F);
 
+	/// Call `f` for the keys starting with the given prefix which aren't lower than `start`, in
+	/// order, until it returns `false`.
+	fn for_keys_with_prefix_while<F: FnMut(&[u8]) -> bool>(&self, prefix: &[u8], start: &[u8], f: F);
+
 	/// Calculate the storage root, with given delta over what is already stored in
 	/// the backend, and produce a "transaction" that can be used to commit.
 	fn storage_root<I>(&self, delta: I) -> (H::Out, Self::Transaction)
@@ -226,6 +230,20 @@ impl<H: Hasher> Backend<H> for InMemory<H> where H::Out: HeapSizeOf {
 		self.inner.get(&None).map(|map| map.keys().filter(|key| key.starts_with(prefix)).map(|k| &**k).for_each(f));
 	}
 
+	fn for_keys_with_prefix_while<F: FnMut(&[u8]) -> bool>(&self, prefix: &[u8], start: &[u8], mut f: F) {
+		// keys aren't ordered in memory: the ones from `start` on are sorted first.
+		let mut keys: Vec<_> = self.inner.get(&None).into_iter()
+			.flat_map(|map| map.keys())
+			.filter(|key| key.starts_with(prefix) && &key[..] >= start)
+			.collect();
+		keys.sort();
+		for key in keys {
+			if !f(key) {
+				break;
+			}
+		}
+	}
+
 	fn for_keys_in_child_storage<F: FnMut(&[u8])>(&self, storage_key: &[u8], mut f: F) {
 		self.inner.get(&Some(storage_key.to_vec())).map(|map| map.keys().for_each(|k| f(&k)));
 	}
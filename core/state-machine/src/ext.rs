@@ -16,12 +16,13 @@
 
 //! Conrete externalities implementation.
 
-use std::{error, fmt, cmp::Ord};
+use std::{error, fmt, cmp::Ord, collections::BTreeSet};
 use backend::{Backend, Consolidate};
 use changes_trie::{AnchorBlockId, Storage as ChangesTrieStorage, compute_changes_trie_root};
-use {Externalities, OverlayedChanges};
+use {Capabilities, Externalities, OverlayedChanges};
 use hash_db::Hasher;
 use primitives::storage::well_known_keys::is_child_storage_key;
+use primitives::crypto::Keystore;
 use substrate_trie::{MemoryDB, TrieDBMut, TrieMut, default_child_trie_root, is_child_trie_key_valid};
 use heapsize::HeapSizeOf;
 
@@ -80,6 +81,10 @@ where
 	/// `storage_changes_root` is called matters + we need to remember additional
 	/// data at this moment (block number).
 	changes_trie_transaction: Option<(u64, MemoryDB<H>, H::Out)>,
+	/// Offchain capabilities available to the runtime.
+	capabilities: Capabilities,
+	/// The keystore of the node, if the runtime is given access to it.
+	keystore: Option<&'a Keystore>,
 }
 
 impl<'a, H, B, T> Ext<'a, H, B, T>
@@ -97,9 +102,24 @@ where
 			storage_transaction: None,
 			changes_trie_storage,
 			changes_trie_transaction: None,
+			capabilities: Capabilities::none(),
+			keystore: None,
 		}
 	}
 
+	/// Give the runtime the given offchain capabilities.
+	pub fn with_capabilities(mut self, capabilities: Capabilities) -> Self {
+		self.capabilities = capabilities;
+		self
+	}
+
+	/// Give the runtime access to the keystore of the node. It can only be used with the
+	/// `Capabilities::KEYSTORE` capability.
+	pub fn with_keystore(mut self, keystore: &'a Keystore) -> Self {
+		self.keystore = Some(keystore);
+		self
+	}
+
 	/// Get the transaction necessary to update the backend.
 	pub fn transaction(mut self) -> (B::Transaction, Option<MemoryDB<H>>) {
 		let _ = self.storage_root();
@@ -172,6 +192,17 @@ where
 	}
 }
 
+/// Record a live key to remove, unless there are already `limit` of them, in which case it is the
+/// `next` key to remove. Returns whether to look for more keys.
+fn remove_live(to_remove: &mut Vec<Vec<u8>>, next: &mut Option<Vec<u8>>, limit: u32, key: Vec<u8>) -> bool {
+	if to_remove.len() == limit as usize {
+		*next = Some(key);
+		return false;
+	}
+	to_remove.push(key);
+	true
+}
+
 impl<'a, B: 'a, T: 'a, H> Externalities<H> for Ext<'a, H, B, T>
 where
 	H: Hasher,
@@ -249,6 +280,62 @@ where
 		});
 	}
 
+	fn clear_prefix_limited(&mut self, prefix: &[u8], limit: u32, cursor: Option<&[u8]>) -> (u32, Option<Vec<u8>>) {
+		if is_child_storage_key(prefix) {
+			warn!(target: "trie", "Refuse to directly clear prefix that is part of child storage key");
+			// nothing will ever be removed, don't make the caller loop forever.
+			return (0, None);
+		}
+
+		// the keys before the cursor have been removed by previous calls, the scan starts from it
+		// so that they aren't iterated again. The keys changed in the overlay are merged in order
+		// with the keys of the backend, which are only iterated until one past the limit is found.
+		let start = cursor.unwrap_or(prefix);
+		let mut overlay_keys = BTreeSet::new();
+		for changes in &[&self.overlay.committed, &self.overlay.prospective] {
+			overlay_keys.extend(changes.top.keys()
+				.filter(|key| key.starts_with(prefix) && &key[..] >= start)
+				.cloned());
+		}
+
+		let mut to_remove = Vec::new();
+		let mut next_key = None;
+		{
+			let overlay = &self.overlay;
+			let mut overlay_keys = overlay_keys.into_iter().peekable();
+			self.backend.for_keys_with_prefix_while(prefix, start, |key| {
+				while overlay_keys.peek().map_or(false, |next| &next[..] < key) {
+					let next = overlay_keys.next().expect("peeked above; qed");
+					let live = overlay.storage(&next).map_or(false, |value| value.is_some());
+					if live && !remove_live(&mut to_remove, &mut next_key, limit, next) {
+						return false;
+					}
+				}
+				let live = if overlay_keys.peek().map_or(false, |next| &next[..] == key) {
+					let next = overlay_keys.next().expect("peeked above; qed");
+					overlay.storage(&next).map_or(true, |value| value.is_some())
+				} else {
+					true
+				};
+				!live || remove_live(&mut to_remove, &mut next_key, limit, key.to_vec())
+			});
+			if next_key.is_none() {
+				for key in overlay_keys {
+					let live = overlay.storage(&key).map_or(false, |value| value.is_some());
+					if live && !remove_live(&mut to_remove, &mut next_key, limit, key) {
+						break;
+					}
+				}
+			}
+		}
+
+		let removed = to_remove.len() as u32;
+		for key in to_remove {
+			self.place_storage(key, None);
+		}
+		(removed, next_key)
+	}
+
 	fn chain_id(&self) -> u64 {
 		42
 	}
@@ -311,6 +398,14 @@ where
 		self.changes_trie_transaction = root_and_tx;
 		root
 	}
+
+	fn capabilities(&self) -> Capabilities {
+		self.capabilities
+	}
+
+	fn keystore(&self) -> Option<&Keystore> {
+		self.keystore
+	}
 }
 
 #[cfg(test)]
@@ -345,6 +440,7 @@ mod tests {
 				digest_interval: 0,
 				digest_levels: 0,
 			}),
+			..Default::default()
 		}
 	}
 
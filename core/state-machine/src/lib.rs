@@ -30,6 +30,7 @@ extern crate substrate_trie;
 
 extern crate parking_lot;
 extern crate heapsize;
+extern crate rayon;
 #[cfg_attr(test, macro_use)] extern crate substrate_primitives as primitives;
 extern crate parity_codec as codec;
 extern crate substrate_trie as trie;
@@ -37,8 +38,9 @@ extern crate substrate_trie as trie;
 use std::fmt;
 use hash_db::Hasher;
 use heapsize::HeapSizeOf;
-use codec::Decode;
+use codec::{Decode, Encode, Compact};
 use primitives::storage::well_known_keys;
+use primitives::crypto::Keystore;
 
 pub mod backend;
 mod changes_trie;
@@ -49,7 +51,7 @@ mod proving_backend;
 mod trie_backend;
 mod trie_backend_essence;
 
-pub use trie::{TrieMut, TrieDBMut, DBValue, MemoryDB};
+pub use trie::{TrieMut, TrieDBMut, DBValue, MemoryDB, Recorder as ProofRecorder};
 pub use testing::TestExternalities;
 pub use ext::Ext;
 pub use backend::Backend;
@@ -61,7 +63,7 @@ pub use changes_trie::{
 	key_changes, key_changes_proof, key_changes_proof_check,
 	prune as prune_changes_tries};
 pub use overlayed_changes::OverlayedChanges;
-pub use proving_backend::create_proof_check_backend_storage;
+pub use proving_backend::{create_proof_check_backend_storage, ProvingBackend};
 pub use trie_backend_essence::Storage;
 pub use trie_backend::TrieBackend;
 
@@ -71,7 +73,13 @@ const DEFAULT_HEAP_PAGES :u64 = 1024;
 /// State Machine Error bound.
 ///
 /// This should reflect WASM error type bound for future compatibility.
-pub trait Error: 'static + fmt::Debug + fmt::Display + Send {}
+pub trait Error: 'static + fmt::Debug + fmt::Display + Send {
+	/// If the runtime rejected the input data of a call, the called method and the index of
+	/// the argument that could not be decoded.
+	fn bad_input(&self) -> Option<(&str, u32)> {
+		None
+	}
+}
 
 impl Error for ExecutionError {}
 
@@ -140,6 +148,24 @@ pub trait Externalities<H: Hasher> {
 	/// Clear storage entries which keys are start with the given prefix.
 	fn clear_prefix(&mut self, prefix: &[u8]);
 
+	/// Clear at most `limit` storage entries which keys start with the given prefix, in key order,
+	/// starting from the `cursor` key if any.
+	///
+	/// Returns the number of removed entries and the next key to remove, if any entry with the
+	/// prefix is left. Passing it as the cursor of the next call continues where this one stopped,
+	/// without iterating the removed keys again.
+	fn clear_prefix_limited(&mut self, prefix: &[u8], limit: u32, cursor: Option<&[u8]>) -> (u32, Option<Vec<u8>>);
+
+	/// Append an encoded item to the SCALE-encoded `Vec` stored under `key` (effective immediately).
+	///
+	/// Only the length prefix of the stored value is decoded, not the items themselves. If there
+	/// is no value under `key`, or if it isn't an encoded `Vec`, it is replaced with a `Vec`
+	/// containing only the item.
+	fn storage_append(&mut self, key: Vec<u8>, item: Vec<u8>) {
+		let value = append_to_encoded_vec(self.storage(&key), &item);
+		self.place_storage(key, Some(value));
+	}
+
 	/// Set or clear a storage entry (`key`) of current contract being called (effective immediately).
 	fn place_storage(&mut self, key: Vec<u8>, value: Option<Vec<u8>>);
 
@@ -159,6 +185,36 @@ pub trait Externalities<H: Hasher> {
 
 	/// Get the change trie root of the current storage overlay at a block wth given parent.
 	fn storage_changes_root(&mut self, parent: H::Out, parent_num: u64) -> Option<H::Out> where H::Out: Ord;
+
+	/// Offchain capabilities available to the runtime.
+	fn capabilities(&self) -> Capabilities {
+		Capabilities::none()
+	}
+
+	/// The keystore of the node. Host functions must only use it if `capabilities` contains
+	/// `Capabilities::KEYSTORE`.
+	fn keystore(&self) -> Option<&Keystore> {
+		None
+	}
+}
+
+/// Append `item` to the SCALE-encoded `Vec` in `vec`, only re-encoding the length prefix.
+///
+/// Returns a `Vec` with `item` as its single element if `vec` is `None` or doesn't start with
+/// a valid length prefix.
+pub fn append_to_encoded_vec(vec: Option<Vec<u8>>, item: &[u8]) -> Vec<u8> {
+	let existing = vec.as_ref().and_then(|vec| {
+		let mut input = &vec[..];
+		let len = <Compact<u32>>::decode(&mut input)?.0;
+		Some((len.checked_add(1)?, input))
+	});
+	let (len, items) = existing.unwrap_or((1, &[][..]));
+
+	let mut result = Compact(len).encode();
+	result.reserve(items.len() + item.len());
+	result.extend_from_slice(items);
+	result.extend_from_slice(item);
+	result
 }
 
 /// Code execution engine.
@@ -176,9 +232,53 @@ pub trait CodeExecutor<H: Hasher>: Sized + Send + Sync {
 		method: &str,
 		data: &[u8],
 		use_native: bool
+	) -> (Result<Vec<u8>, Self::Error>, bool) {
+		self.call_with_limits(ext, heap_pages, code, method, data, use_native, ExecutionLimits::none())
+	}
+
+	/// Call a given method in the runtime, failing the call once it exceeds any of `limits`.
+	///
+	/// Native execution can't be limited: a call with limits must be executed in wasm, even if
+	/// `use_native` is true.
+	fn call_with_limits<E: Externalities<H>>(
+		&self,
+		ext: &mut E,
+		heap_pages: usize,
+		code: &[u8],
+		method: &str,
+		data: &[u8],
+		use_native: bool,
+		limits: ExecutionLimits,
 	) -> (Result<Vec<u8>, Self::Error>, bool);
 }
 
+/// Limits of a single call into the runtime, so that a misbehaving runtime can't stall the node.
+///
+/// Calls made on import must not be limited: whether a call exceeds its limits depends on the
+/// node executing it, so a block could be valid for some nodes only.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct ExecutionLimits {
+	/// How long the call may take.
+	pub max_duration: Option<::std::time::Duration>,
+	/// How much fuel the call may use. Every wasm instruction uses one unit.
+	pub max_fuel: Option<u64>,
+	/// How many pages the memory of the runtime may grow by during the call, not counting the
+	/// heap pages.
+	pub max_memory_growth: Option<u32>,
+}
+
+impl ExecutionLimits {
+	/// No limit at all.
+	pub fn none() -> Self {
+		Default::default()
+	}
+
+	/// Whether there is no limit at all.
+	pub fn is_none(&self) -> bool {
+		*self == Self::none()
+	}
+}
+
 /// Strategy for executing a call into the runtime.
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
 pub enum ExecutionStrategy {
@@ -190,6 +290,80 @@ pub enum ExecutionStrategy {
 	Both,
 }
 
+/// Reason why a call into the runtime is made.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum ExecutionContext {
+	/// Importing a block received from the network or built locally.
+	Importing,
+	/// Importing a block during initial sync.
+	Syncing,
+	/// Building a new block.
+	BlockConstruction,
+	/// Running an offchain worker.
+	OffchainWorker,
+	/// Any other call, such as an RPC request.
+	Other,
+}
+
+impl ExecutionContext {
+	/// Offchain capabilities available to the runtime when called in this context.
+	pub fn capabilities(&self) -> Capabilities {
+		match *self {
+			ExecutionContext::OffchainWorker => Capabilities::all(),
+			_ => Capabilities::none(),
+		}
+	}
+}
+
+/// Bitmask of the offchain capabilities available to the runtime.
+///
+/// Host functions which reach outside of the state, such as HTTP requests, must check that
+/// `Externalities::capabilities` contains their capability. None is available in consensus
+/// code, so that the same runtime can be safely called both on import and by offchain workers.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct Capabilities(u8);
+
+impl Capabilities {
+	/// Submitting transactions to the pool.
+	pub const TRANSACTION_POOL: Capabilities = Capabilities(1 << 0);
+	/// Performing HTTP requests.
+	pub const HTTP: Capabilities = Capabilities(1 << 1);
+	/// Signing with the keys of the node.
+	pub const KEYSTORE: Capabilities = Capabilities(1 << 2);
+	/// Getting random bytes from the node.
+	pub const RANDOMNESS: Capabilities = Capabilities(1 << 3);
+	/// Reading and writing the local, non-consensus, storage of the node.
+	pub const LOCAL_STORAGE: Capabilities = Capabilities(1 << 4);
+
+	/// No capability at all.
+	pub fn none() -> Self {
+		Capabilities(0)
+	}
+
+	/// Every capability.
+	pub fn all() -> Self {
+		Self::TRANSACTION_POOL | Self::HTTP | Self::KEYSTORE | Self::RANDOMNESS | Self::LOCAL_STORAGE
+	}
+
+	/// Whether all the capabilities of `other` are available.
+	pub fn contains(&self, other: Capabilities) -> bool {
+		self.0 & other.0 == other.0
+	}
+
+	/// Raw bitmask.
+	pub fn bits(&self) -> u8 {
+		self.0
+	}
+}
+
+impl ::std::ops::BitOr for Capabilities {
+	type Output = Capabilities;
+
+	fn bitor(self, other: Capabilities) -> Capabilities {
+		Capabilities(self.0 | other.0)
+	}
+}
+
 /// Like `ExecutionStrategy` only it also stores a handler in case of consensus failure.
 pub enum ExecutionManager<F> {
 	/// Execute with the native equivalent if it is compatible with the given wasm module; otherwise fall back to the wasm.
@@ -251,6 +425,9 @@ where
 		exec,
 		method,
 		call_data,
+		Capabilities::none(),
+		None,
+		ExecutionLimits::none(),
 		match strategy {
 			ExecutionStrategy::AlwaysWasm => ExecutionManager::AlwaysWasm,
 			ExecutionStrategy::NativeWhenPossible => ExecutionManager::NativeWhenPossible,
@@ -266,6 +443,9 @@ where
 /// Produces a state-backend-specific "transaction" which can be used to apply the changes
 /// to the backing store, such as the disk.
 ///
+/// The runtime is given the offchain `capabilities` and, if any, the `keystore` of the node;
+/// `execute` gives neither. The call fails once it exceeds any of `limits`.
+///
 /// On an error, no prospective changes are written to the overlay.
 ///
 /// Note: changes to code will be in place if this call is made again. For running partial
@@ -277,6 +457,9 @@ pub fn execute_using_consensus_failure_handler<H, B, T, Exec, Handler>(
 	exec: &Exec,
 	method: &str,
 	call_data: &[u8],
+	capabilities: Capabilities,
+	keystore: Option<&Keystore>,
+	limits: ExecutionLimits,
 	manager: ExecutionManager<Handler>,
 ) -> Result<(Vec<u8>, B::Transaction, Option<MemoryDB<H>>), Box<Error>>
 where
@@ -317,9 +500,13 @@ where
 
 		let (result, was_native, storage_delta, changes_delta) = {
 			let ((result, was_native), (storage_delta, changes_delta)) = {
-				let mut externalities = ext::Ext::new(overlay, backend, changes_trie_storage);
+				let mut externalities = ext::Ext::new(overlay, backend, changes_trie_storage)
+					.with_capabilities(capabilities);
+				if let Some(keystore) = keystore {
+					externalities = externalities.with_keystore(keystore);
+				}
 				(
-					exec.call(
+					exec.call_with_limits(
 						&mut externalities,
 						heap_pages,
 						&code,
@@ -327,6 +514,7 @@ where
 						call_data,
 						// attempt to run native first, if we're not directed to run wasm only
 						strategy != ExecutionStrategy::AlwaysWasm,
+						limits,
 					),
 					externalities.transaction()
 				)
@@ -342,15 +530,20 @@ where
 
 			let (wasm_result, wasm_storage_delta, wasm_changes_delta) = {
 				let ((result, _), (storage_delta, changes_delta)) = {
-					let mut externalities = ext::Ext::new(overlay, backend, changes_trie_storage);
+					let mut externalities = ext::Ext::new(overlay, backend, changes_trie_storage)
+						.with_capabilities(capabilities);
+					if let Some(keystore) = keystore {
+						externalities = externalities.with_keystore(keystore);
+					}
 					(
-						exec.call(
+						exec.call_with_limits(
 							&mut externalities,
 							heap_pages,
 							&code,
 							method,
 							call_data,
 							false,
+							limits,
 						),
 						externalities.transaction()
 					)
@@ -380,6 +573,33 @@ where
 	result.map_err(|e| Box::new(e) as _)
 }
 
+/// Compute the storage root and the "transactions" applying the given changes, which were made
+/// on top of `backend` while building the block with the given parent, without executing the
+/// block again.
+///
+/// Returns the storage root after the changes, the changes trie root, the storage transaction and
+/// the changes trie transaction. The changes trie root and transaction are only returned if
+/// changes tries are enabled.
+pub fn storage_changes_transaction<H, B, T>(
+	backend: &B,
+	changes_trie_storage: Option<&T>,
+	overlay: &mut OverlayedChanges,
+	parent_hash: H::Out,
+	parent_number: u64,
+) -> (H::Out, Option<H::Out>, B::Transaction, Option<MemoryDB<H>>)
+where
+	H: Hasher,
+	B: Backend<H>,
+	T: ChangesTrieStorage<H>,
+	H::Out: Ord + HeapSizeOf,
+{
+	let mut ext = ext::Ext::new(overlay, backend, changes_trie_storage);
+	let root = ext.storage_root();
+	let changes_trie_root = ext.storage_changes_root(parent_hash, parent_number);
+	let (storage_transaction, changes_trie_transaction) = ext.transaction();
+	(root, changes_trie_root, storage_transaction, changes_trie_transaction)
+}
+
 /// Prove execution using the given state backend, overlayed changes, and call executor.
 /// Produces a state-backend-specific "transaction" which can be used to apply the changes
 /// to the backing store, such as the disk.
@@ -418,7 +638,8 @@ where
 	Ok((result, proof))
 }
 
-/// Check execution proof, generated by `prove_execution` call.
+/// Check execution proof, generated by `prove_execution` call. The call fails once it exceeds
+/// any of `limits`.
 pub fn execution_proof_check<H, Exec>(
 	root: H::Out,
 	proof: Vec<Vec<u8>>,
@@ -426,6 +647,7 @@ pub fn execution_proof_check<H, Exec>(
 	exec: &Exec,
 	method: &str,
 	call_data: &[u8],
+	limits: ExecutionLimits,
 ) -> Result<Vec<u8>, Box<Error>>
 where
 	H: Hasher,
@@ -433,8 +655,18 @@ where
 	H::Out: Ord + HeapSizeOf,
 {
 	let backend = proving_backend::create_proof_check_backend::<H>(root.into(), proof)?;
-	execute::<H, _, changes_trie::InMemoryStorage<H>, _>(&backend, None, overlay, exec, method, call_data, ExecutionStrategy::NativeWhenPossible)
-		.map(|(result, _, _)| result)
+	execute_using_consensus_failure_handler::<H, _, changes_trie::InMemoryStorage<H>, _, _>(
+		&backend,
+		None,
+		overlay,
+		exec,
+		method,
+		call_data,
+		Capabilities::none(),
+		None,
+		limits,
+		native_when_possible(),
+	).map(|(result, _, _)| result)
 }
 
 /// Generate storage read proof.
@@ -554,15 +786,26 @@ mod tests {
 	impl<H: Hasher> CodeExecutor<H> for DummyCodeExecutor {
 		type Error = u8;
 
-		fn call<E: Externalities<H>>(
+		fn call_with_limits<E: Externalities<H>>(
 			&self,
 			ext: &mut E,
 			_heap_pages: usize,
 			_code: &[u8],
-			_method: &str,
+			method: &str,
 			_data: &[u8],
-			use_native: bool
+			use_native: bool,
+			limits: ExecutionLimits,
 		) -> (Result<Vec<u8>, Self::Error>, bool) {
+			if method == "limits" {
+				return (Ok(limits.max_fuel.encode()), use_native);
+			}
+			if method == "capabilities" {
+				return (Ok(vec![ext.capabilities().bits()]), use_native);
+			}
+			if method == "keystore" {
+				return (Ok(vec![ext.keystore().is_some() as u8]), use_native);
+			}
+
 			if self.change_changes_trie_config {
 				ext.place_storage(well_known_keys::CHANGES_TRIE_CONFIG.to_vec(), Some(ChangesTrieConfig {
 					digest_interval: 777,
@@ -614,6 +857,9 @@ mod tests {
 			},
 			"test",
 			&[],
+			Capabilities::none(),
+			None,
+			ExecutionLimits::none(),
 			ExecutionManager::Both(|we, _ne| {
 				consensus_failed = true;
 				println!("HELLO!");
@@ -623,6 +869,101 @@ mod tests {
 		assert!(consensus_failed);
 	}
 
+	#[test]
+	fn capabilities_are_only_given_to_offchain_workers() {
+		let executor = DummyCodeExecutor {
+			change_changes_trie_config: false,
+			native_available: true,
+			native_succeeds: true,
+			fallback_succeeds: true,
+		};
+		let capabilities = |context: ExecutionContext| execute_using_consensus_failure_handler(
+			&trie_backend::tests::test_trie(),
+			Some(&InMemoryChangesTrieStorage::new()),
+			&mut Default::default(),
+			&executor,
+			"capabilities",
+			&[],
+			context.capabilities(),
+			None,
+			ExecutionLimits::none(),
+			native_when_possible(),
+		).unwrap().0;
+
+		assert_eq!(capabilities(ExecutionContext::Importing), vec![0]);
+		assert_eq!(capabilities(ExecutionContext::OffchainWorker), vec![Capabilities::all().bits()]);
+		assert!(Capabilities::all().contains(Capabilities::HTTP | Capabilities::KEYSTORE));
+		assert!(!Capabilities::none().contains(Capabilities::HTTP));
+	}
+
+	#[test]
+	fn keystore_is_given_to_the_externalities() {
+		struct NoKeys;
+
+		impl Keystore for NoKeys {
+			fn public_keys(&self, _key_type: primitives::crypto::KeyTypeId) -> Vec<primitives::ed25519::Public> {
+				Vec::new()
+			}
+
+			fn sign(
+				&self,
+				_key_type: primitives::crypto::KeyTypeId,
+				_public: &primitives::ed25519::Public,
+				_msg: &[u8],
+			) -> Option<primitives::ed25519::Signature> {
+				None
+			}
+		}
+
+		let executor = DummyCodeExecutor {
+			change_changes_trie_config: false,
+			native_available: true,
+			native_succeeds: true,
+			fallback_succeeds: true,
+		};
+		let has_keystore = |keystore: Option<&Keystore>| execute_using_consensus_failure_handler(
+			&trie_backend::tests::test_trie(),
+			Some(&InMemoryChangesTrieStorage::new()),
+			&mut Default::default(),
+			&executor,
+			"keystore",
+			&[],
+			Capabilities::all(),
+			keystore,
+			ExecutionLimits::none(),
+			native_when_possible(),
+		).unwrap().0;
+
+		assert_eq!(has_keystore(None), vec![0]);
+		assert_eq!(has_keystore(Some(&NoKeys)), vec![1]);
+	}
+
+	#[test]
+	fn limits_are_given_to_the_executor() {
+		let executor = DummyCodeExecutor {
+			change_changes_trie_config: false,
+			native_available: true,
+			native_succeeds: true,
+			fallback_succeeds: true,
+		};
+		let max_fuel = |limits: ExecutionLimits| execute_using_consensus_failure_handler(
+			&trie_backend::tests::test_trie(),
+			Some(&InMemoryChangesTrieStorage::new()),
+			&mut Default::default(),
+			&executor,
+			"limits",
+			&[],
+			Capabilities::none(),
+			None,
+			limits,
+			native_when_possible(),
+		).unwrap().0;
+
+		assert_eq!(max_fuel(ExecutionLimits::none()), None::<u64>.encode());
+		assert_eq!(max_fuel(ExecutionLimits { max_fuel: Some(42), ..ExecutionLimits::none() }), Some(42u64).encode());
+		assert!(ExecutionLimits::none().is_none());
+	}
+
 	#[test]
 	fn prove_execution_and_proof_check_works() {
 		let executor = DummyCodeExecutor {
@@ -640,13 +981,34 @@ mod tests {
 
 		// check proof locally
 		let local_result = execution_proof_check::<Blake2Hasher, _>(remote_root, remote_proof,
-			&mut Default::default(), &executor, "test", &[]).unwrap();
+			&mut Default::default(), &executor, "test", &[], ExecutionLimits::none()).unwrap();
 
 		// check that both results are correct
 		assert_eq!(remote_result, vec![66]);
 		assert_eq!(remote_result, local_result);
 	}
 
+	#[test]
+	fn storage_changes_transaction_commits_to_the_changed_root() {
+		let backend = trie_backend::tests::test_trie();
+		let mut overlay = OverlayedChanges::default();
+		overlay.set_storage(b"value3".to_vec(), Some(b"3".to_vec()));
+		overlay.commit_prospective();
+
+		let expected_root = backend.storage_root(vec![(b"value3".to_vec(), Some(b"3".to_vec()))]).0;
+		let (root, changes_trie_root, _, changes_trie_transaction) = storage_changes_transaction::<Blake2Hasher, _, InMemoryChangesTrieStorage<Blake2Hasher>>(
+			&backend,
+			None,
+			&mut overlay,
+			Default::default(),
+			0,
+		);
+
+		assert_eq!(root, expected_root);
+		assert!(changes_trie_root.is_none());
+		assert!(changes_trie_transaction.is_none());
+	}
+
 	#[test]
 	fn clear_prefix_in_ext_works() {
 		let initial: HashMap<_, _> = map![
@@ -689,6 +1051,97 @@ mod tests {
 		);
 	}
 
+	#[test]
+	fn clear_prefix_limited_in_ext_works() {
+		let initial: HashMap<_, _> = map![
+			b"aaa".to_vec() => b"0".to_vec(),
+			b"abb".to_vec() => b"1".to_vec(),
+			b"abc".to_vec() => b"2".to_vec()
+		];
+		let backend = InMemory::<Blake2Hasher>::from(initial).try_into_trie_backend().unwrap();
+		let mut overlay = OverlayedChanges {
+			committed: map![
+				b"aba".to_vec() => OverlayedValue::from(Some(b"1312".to_vec())),
+				b"abd".to_vec() => OverlayedValue::from(None)
+			],
+			..Default::default()
+		};
+
+		let changes_trie_storage = InMemoryChangesTrieStorage::new();
+		let mut ext = Ext::new(&mut overlay, &backend, Some(&changes_trie_storage));
+		assert_eq!(ext.clear_prefix_limited(b"ab", 2, None), (2, Some(b"abc".to_vec())));
+		assert!(ext.storage(b"aba").is_none());
+		assert!(ext.storage(b"abb").is_none());
+		assert!(ext.storage(b"abc").is_some());
+		assert_eq!(ext.clear_prefix_limited(b"ab", 2, Some(b"abc")), (1, None));
+		assert_eq!(ext.clear_prefix_limited(b"ab", 2, None), (0, None));
+		assert!(ext.storage(b"aaa").is_some());
+	}
+
+	#[test]
+	fn clear_prefix_limited_removes_keys_in_order() {
+		let initial: HashMap<_, _> = map![
+			b"ab1".to_vec() => b"1".to_vec(),
+			b"ab3".to_vec() => b"3".to_vec(),
+			b"ab5".to_vec() => b"5".to_vec()
+		];
+		let backend = InMemory::<Blake2Hasher>::from(initial).try_into_trie_backend().unwrap();
+		let mut overlay = OverlayedChanges {
+			prospective: map![
+				b"ab2".to_vec() => OverlayedValue::from(Some(b"2".to_vec())),
+				b"ab3".to_vec() => OverlayedValue::from(None),
+				b"ab6".to_vec() => OverlayedValue::from(Some(b"6".to_vec()))
+			],
+			..Default::default()
+		};
+
+		let changes_trie_storage = InMemoryChangesTrieStorage::new();
+		let mut ext = Ext::new(&mut overlay, &backend, Some(&changes_trie_storage));
+		assert_eq!(ext.clear_prefix_limited(b"ab", 2, None), (2, Some(b"ab5".to_vec())));
+		assert!(ext.storage(b"ab1").is_none());
+		assert!(ext.storage(b"ab2").is_none());
+		assert!(ext.storage(b"ab5").is_some());
+		assert_eq!(ext.clear_prefix_limited(b"ab", 1, Some(b"ab5")), (1, Some(b"ab6".to_vec())));
+		assert!(ext.storage(b"ab5").is_none());
+		assert_eq!(ext.clear_prefix_limited(b"ab", 1, Some(b"ab6")), (1, None));
+		assert!(ext.storage(b"ab6").is_none());
+	}
+
+	#[test]
+	fn clear_prefix_limited_starts_from_the_cursor() {
+		let initial: HashMap<_, _> = map![
+			b"ab1".to_vec() => b"1".to_vec(),
+			b"ab2".to_vec() => b"2".to_vec(),
+			b"ab3".to_vec() => b"3".to_vec()
+		];
+		let backend = InMemory::<Blake2Hasher>::from(initial).try_into_trie_backend().unwrap();
+		let mut overlay = OverlayedChanges::default();
+
+		let changes_trie_storage = InMemoryChangesTrieStorage::new();
+		let mut ext = Ext::new(&mut overlay, &backend, Some(&changes_trie_storage));
+		// nothing is removed without a limit, but the cursor still points at the next key.
+		assert_eq!(ext.clear_prefix_limited(b"ab", 0, None), (0, Some(b"ab1".to_vec())));
+		// keys before the cursor are left alone.
+		assert_eq!(ext.clear_prefix_limited(b"ab", 5, Some(b"ab2")), (2, None));
+		assert!(ext.storage(b"ab1").is_some());
+		assert!(ext.storage(b"ab2").is_none());
+		assert!(ext.storage(b"ab3").is_none());
+	}
+
+	#[test]
+	fn append_to_encoded_vec_works() {
+		let appended = append_to_encoded_vec(None, &1u32.encode());
+		let appended = append_to_encoded_vec(Some(appended), &2u32.encode());
+		assert_eq!(<Vec<u32>>::decode(&mut &appended[..]), Some(vec![1, 2]));
+
+		let long: Vec<u16> = (0..64).collect();
+		let appended = append_to_encoded_vec(Some(long.encode()), &64u16.encode());
+		assert_eq!(<Vec<u16>>::decode(&mut &appended[..]), Some((0..65).collect()));
+
+		let replaced = append_to_encoded_vec(Some(vec![]), &7u32.encode());
+		assert_eq!(<Vec<u32>>::decode(&mut &replaced[..]), Some(vec![7]));
+	}
+
 	#[test]
 	fn set_child_storage_works() {
 		let backend = InMemory::<Blake2Hasher>::default().try_into_trie_backend().unwrap();
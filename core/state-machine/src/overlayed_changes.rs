@@ -18,6 +18,7 @@
 
 #[cfg(test)] use std::iter::FromIterator;
 use std::collections::{HashMap, HashSet};
+use std::collections::hash_map::Entry;
 use codec::Decode;
 use changes_trie::{NO_EXTRINSIC_INDEX, Configuration as ChangesTrieConfig};
 use primitives::storage::well_known_keys::EXTRINSIC_INDEX;
@@ -35,6 +36,28 @@ pub struct OverlayedChanges {
 	/// Changes trie configuration. None by default, but could be installed by the
 	/// runtime if it supports change tries.
 	pub(crate) changes_trie_config: Option<ChangesTrieConfig>,
+	/// The committed changes replaced within each open transaction, innermost last.
+	pub(crate) transactions: Vec<TransactionUndo>,
+}
+
+/// The committed changes a transaction replaced, to restore them if it is rolled back.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct TransactionUndo {
+	/// Previous committed value of the changed keys, `None` if there was none.
+	top: HashMap<Vec<u8>, Option<OverlayedValue>>,
+	/// Previous committed changes of the changed child storages.
+	children: HashMap<Vec<u8>, ChildUndo>,
+	/// Previous changes trie configuration, if it was changed.
+	changes_trie_config: Option<Option<ChangesTrieConfig>>,
+}
+
+/// The committed changes of a child storage a transaction replaced.
+#[derive(Debug, Clone)]
+struct ChildUndo {
+	/// Previous extrinsics of the child change set, `None` if there was no change set.
+	extrinsics: Option<Option<HashSet<u32>>>,
+	/// Previous committed value of the changed keys, `None` if there was none.
+	keys: HashMap<Vec<u8>, Option<Option<Vec<u8>>>>,
 }
 
 /// The storage value, used inside OverlayedChanges.
@@ -94,6 +117,11 @@ impl OverlayedChanges {
 			}
 		}
 
+		if let Some(undo) = self.transactions.last_mut() {
+			if undo.changes_trie_config.is_none() {
+				undo.changes_trie_config = Some(self.changes_trie_config.clone());
+			}
+		}
 		self.changes_trie_config = Some(config);
 		true
 	}
@@ -235,6 +263,24 @@ impl OverlayedChanges {
 
 	/// Commit prospective changes to state.
 	pub fn commit_prospective(&mut self) {
+		if let Some(undo) = self.transactions.last_mut() {
+			let committed = &self.committed;
+			for key in self.prospective.top.keys() {
+				undo.top.entry(key.clone()).or_insert_with(|| committed.top.get(key).cloned());
+			}
+			for (storage_key, map) in self.prospective.children.iter() {
+				let committed_child = committed.children.get(storage_key);
+				let child = undo.children.entry(storage_key.clone()).or_insert_with(|| ChildUndo {
+					extrinsics: committed_child.map(|child| child.0.clone()),
+					keys: HashMap::new(),
+				});
+				for key in map.1.keys() {
+					child.keys.entry(key.clone())
+						.or_insert_with(|| committed_child.and_then(|child| child.1.get(key).cloned()));
+				}
+			}
+		}
+
 		if self.committed.is_empty() {
 			::std::mem::swap(&mut self.prospective, &mut self.committed);
 		} else {
@@ -259,6 +305,88 @@ impl OverlayedChanges {
 		}
 	}
 
+	/// Start a transaction over the committed changes, which can then be rolled back or committed.
+	/// Transactions nest: returns the number of transactions open before this one.
+	pub fn start_transaction(&mut self) -> usize {
+		self.transactions.push(Default::default());
+		self.transactions.len() - 1
+	}
+
+	/// The number of open transactions.
+	pub fn transaction_depth(&self) -> usize {
+		self.transactions.len()
+	}
+
+	/// Restore the committed changes to the state they had when the innermost open transaction
+	/// was started, and close it. Prospective changes are discarded.
+	///
+	/// Returns false if there is no open transaction.
+	pub fn rollback_transaction(&mut self) -> bool {
+		let undo = match self.transactions.pop() {
+			Some(undo) => undo,
+			None => return false,
+		};
+
+		self.prospective.clear();
+		for (key, previous) in undo.top {
+			match previous {
+				Some(value) => { self.committed.top.insert(key, value); },
+				None => { self.committed.top.remove(&key); },
+			}
+		}
+		for (storage_key, child) in undo.children {
+			match child.extrinsics {
+				Some(extrinsics) => {
+					let entry = self.committed.children.entry(storage_key).or_default();
+					entry.0 = extrinsics;
+					for (key, previous) in child.keys {
+						match previous {
+							Some(value) => { entry.1.insert(key, value); },
+							None => { entry.1.remove(&key); },
+						}
+					}
+				},
+				None => { self.committed.children.remove(&storage_key); },
+			}
+		}
+		if let Some(config) = undo.changes_trie_config {
+			self.changes_trie_config = config;
+		}
+		true
+	}
+
+	/// Keep the changes committed within the innermost open transaction, and close it. They are
+	/// still rolled back along with the enclosing transaction, if any.
+	///
+	/// Returns false if there is no open transaction.
+	pub fn commit_transaction(&mut self) -> bool {
+		let undo = match self.transactions.pop() {
+			Some(undo) => undo,
+			None => return false,
+		};
+
+		// the enclosing transaction keeps the older values of the keys both changed.
+		if let Some(parent) = self.transactions.last_mut() {
+			for (key, previous) in undo.top {
+				parent.top.entry(key).or_insert(previous);
+			}
+			for (storage_key, child) in undo.children {
+				match parent.children.entry(storage_key) {
+					Entry::Vacant(entry) => { entry.insert(child); },
+					Entry::Occupied(mut entry) => {
+						for (key, previous) in child.keys {
+							entry.get_mut().keys.entry(key).or_insert(previous);
+						}
+					},
+				}
+			}
+			if parent.changes_trie_config.is_none() {
+				parent.changes_trie_config = undo.changes_trie_config;
+			}
+		}
+		true
+	}
+
 	/// Consume `OverlayedChanges` and take committed set.
 	///
 	/// Panics:
@@ -473,4 +601,45 @@ mod tests {
 		assert_eq!(overlay.prospective,
 			Default::default());
 	}
+
+	#[test]
+	fn transactions_restore_committed_changes() {
+		let mut overlay = OverlayedChanges::default();
+		overlay.set_storage(vec![1], Some(vec![1]));
+		overlay.set_child_storage(vec![10], vec![1], Some(vec![1]));
+		overlay.commit_prospective();
+		let committed = overlay.committed.clone();
+
+		assert_eq!(overlay.start_transaction(), 0);
+		overlay.set_storage(vec![1], Some(vec![2]));
+		overlay.set_storage(vec![2], Some(vec![2]));
+		overlay.set_child_storage(vec![10], vec![1], None);
+		overlay.set_child_storage(vec![20], vec![1], Some(vec![2]));
+		overlay.commit_prospective();
+
+		assert_eq!(overlay.start_transaction(), 1);
+		overlay.set_storage(vec![1], Some(vec![3]));
+		overlay.set_storage(vec![3], Some(vec![3]));
+		overlay.commit_prospective();
+		overlay.set_storage(vec![4], Some(vec![4]));
+
+		// the inner transaction is undone, prospective changes included.
+		assert!(overlay.rollback_transaction());
+		assert_eq!(overlay.storage(&[1]), Some(Some(&[2][..])));
+		assert_eq!(overlay.storage(&[3]), None);
+		assert_eq!(overlay.storage(&[4]), None);
+
+		assert_eq!(overlay.start_transaction(), 1);
+		overlay.set_storage(vec![1], Some(vec![5]));
+		overlay.commit_prospective();
+		assert!(overlay.commit_transaction());
+		assert_eq!(overlay.transaction_depth(), 1);
+		assert_eq!(overlay.storage(&[1]), Some(Some(&[5][..])));
+
+		// the committed inner transaction is undone along with the outer one.
+		assert!(overlay.rollback_transaction());
+		assert_eq!(overlay.committed, committed);
+		assert!(!overlay.rollback_transaction());
+		assert!(!overlay.commit_transaction());
+	}
 }
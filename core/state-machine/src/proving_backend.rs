@@ -17,6 +17,7 @@
 //! Proving state machine backend.
 
 use std::cell::RefCell;
+use std::rc::Rc;
 use hash_db::Hasher;
 use heapsize::HeapSizeOf;
 use hash_db::HashDB;
@@ -86,22 +87,32 @@ impl<'a, S, H> ProvingBackendEssence<'a, S, H>
 /// These can be sent to remote node and used as a proof of execution.
 pub struct ProvingBackend<'a, S: 'a + TrieBackendStorage<H>, H: 'a + Hasher> {
 	backend: &'a TrieBackend<S, H>,
-	proof_recorder: RefCell<Recorder<H::Out>>,
+	proof_recorder: Rc<RefCell<Recorder<H::Out>>>,
 }
 
 impl<'a, S: 'a + TrieBackendStorage<H>, H: 'a + Hasher> ProvingBackend<'a, S, H> {
 	/// Create new proving backend.
 	pub fn new(backend: &'a TrieBackend<S, H>) -> Self {
+		Self::new_with_recorder(backend, Rc::new(RefCell::new(Recorder::new())))
+	}
+
+	/// Create new proving backend that records into the given (shared) recorder.
+	///
+	/// This allows to collect a single proof over multiple executions.
+	pub fn new_with_recorder(
+		backend: &'a TrieBackend<S, H>,
+		proof_recorder: Rc<RefCell<Recorder<H::Out>>>,
+	) -> Self {
 		ProvingBackend {
 			backend,
-			proof_recorder: RefCell::new(Recorder::new()),
+			proof_recorder,
 		}
 	}
 
 	/// Consume the backend, extracting the gathered proof in lexicographical order
 	/// by value.
 	pub fn extract_proof(self) -> Vec<Vec<u8>> {
-		self.proof_recorder.into_inner().drain()
+		self.proof_recorder.borrow_mut().drain()
 			.into_iter()
 			.map(|n| n.data.to_vec())
 			.collect()
@@ -142,6 +153,10 @@ impl<'a, S, H> Backend<H> for ProvingBackend<'a, S, H>
 		self.backend.for_keys_with_prefix(prefix, f)
 	}
 
+	fn for_keys_with_prefix_while<F: FnMut(&[u8]) -> bool>(&self, prefix: &[u8], start: &[u8], f: F) {
+		self.backend.for_keys_with_prefix_while(prefix, start, f)
+	}
+
 	fn pairs(&self) -> Vec<(Vec<u8>, Vec<u8>)> {
 		self.backend.pairs()
 	}
@@ -223,6 +238,22 @@ mod tests {
 		assert!(!backend.extract_proof().is_empty());
 	}
 
+	#[test]
+	fn proof_is_collected_over_backends_sharing_a_recorder() {
+		let trie_backend = test_trie();
+		let recorder = Rc::new(RefCell::new(Recorder::new()));
+
+		let backend = ProvingBackend::new_with_recorder(&trie_backend, recorder.clone());
+		assert_eq!(backend.storage(b"key").unwrap(), Some(b"value".to_vec()));
+
+		let backend = ProvingBackend::new_with_recorder(&trie_backend, recorder.clone());
+		assert_eq!(backend.storage(b"value1").unwrap(), Some(vec![42]));
+
+		let proof = backend.extract_proof();
+		assert!(!proof.is_empty());
+		assert!(recorder.borrow_mut().drain().is_empty());
+	}
+
 	#[test]
 	fn proof_is_invalid_when_does_not_contains_root() {
 		use primitives::H256;
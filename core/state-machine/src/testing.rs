@@ -126,6 +126,22 @@ impl<H: Hasher> Externalities<H> for TestExternalities<H> where H::Out: Ord + He
 		self.inner.retain(|key, _| !key.starts_with(prefix));
 	}
 
+	fn clear_prefix_limited(&mut self, prefix: &[u8], limit: u32, cursor: Option<&[u8]>) -> (u32, Option<Vec<u8>>) {
+		let start = cursor.unwrap_or(prefix);
+		let mut keys: Vec<_> = self.inner.keys()
+			.filter(|key| key.starts_with(prefix) && &key[..] >= start)
+			.cloned()
+			.collect();
+		keys.sort();
+		let next_key = keys.get(limit as usize).cloned();
+		keys.truncate(limit as usize);
+		let removed = keys.len() as u32;
+		for key in keys {
+			self.place_storage(key, None);
+		}
+		(removed, next_key)
+	}
+
 	fn chain_id(&self) -> u64 { 42 }
 
 	fn storage_root(&mut self) -> H::Out {
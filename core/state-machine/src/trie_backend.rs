@@ -19,9 +19,13 @@
 use hash_db::Hasher;
 use heapsize::HeapSizeOf;
 use trie::{TrieDB, TrieError, Trie, MemoryDB, delta_trie_root, default_child_trie_root, child_delta_trie_root};
-use trie_backend_essence::{TrieBackendEssence, TrieBackendStorage, Ephemeral};
+use trie_backend_essence::{TrieBackendEssence, TrieBackendStorage, Ephemeral, CachingStorage, prefetch_trie_paths};
 use {Backend};
 
+/// Minimal number of changed keys for the trie nodes they touch to be read in parallel before
+/// the storage root is computed.
+const PARALLEL_STORAGE_ROOT_THRESHOLD: usize = 1024;
+
 /// Patricia trie-based backend. Transaction type is an overlay of changes to commit.
 pub struct TrieBackend<S: TrieBackendStorage<H>, H: Hasher> {
 	essence: TrieBackendEssence<S, H>,
@@ -77,6 +81,10 @@ impl<S: TrieBackendStorage<H>, H: Hasher> Backend<H> for TrieBackend<S, H> where
 		self.essence.for_keys_with_prefix(prefix, f)
 	}
 
+	fn for_keys_with_prefix_while<F: FnMut(&[u8]) -> bool>(&self, prefix: &[u8], start: &[u8], f: F) {
+		self.essence.for_keys_with_prefix_while(prefix, start, f)
+	}
+
 	fn for_keys_in_child_storage<F: FnMut(&[u8])>(&self, storage_key: &[u8], f: F) {
 		self.essence.for_keys_in_child_storage(storage_key, f)
 	}
@@ -110,17 +118,24 @@ impl<S: TrieBackendStorage<H>, H: Hasher> Backend<H> for TrieBackend<S, H> where
 	{
 		let mut write_overlay = MemoryDB::default();
 		let mut root = *self.essence.root();
+		let delta: Vec<_> = delta.into_iter().collect();
+
+		// large deltas (e.g. migrations) are dominated by reading the touched nodes, which is
+		// done in parallel for every subtree before the sequential update.
+		let result = if delta.len() >= PARALLEL_STORAGE_ROOT_THRESHOLD {
+			let storage = CachingStorage::new(self.essence.backend_storage());
+			prefetch_trie_paths(&storage, &root, delta.iter().map(|(key, _)| &key[..]));
+
+			let mut eph = Ephemeral::new(&storage, &mut write_overlay);
+			delta_trie_root::<H, _, _, _>(&mut eph, root, delta)
+		} else {
+			let mut eph = Ephemeral::new(self.essence.backend_storage(), &mut write_overlay);
+			delta_trie_root::<H, _, _, _>(&mut eph, root, delta)
+		};
 
-		{
-			let mut eph = Ephemeral::new(
-				self.essence.backend_storage(),
-				&mut write_overlay,
-			);
-
-			match delta_trie_root::<H, _, _, _>(&mut eph, root, delta) {
-				Ok(ret) => root = ret,
-				Err(e) => warn!(target: "trie", "Failed to write to trie: {}", e),
-			}
+		match result {
+			Ok(ret) => root = ret,
+			Err(e) => warn!(target: "trie", "Failed to write to trie: {}", e),
 		}
 
 		(root, write_overlay)
@@ -232,6 +247,27 @@ pub mod tests {
 		assert!(new_root != test_trie().storage_root(::std::iter::empty()).0);
 	}
 
+	#[test]
+	fn storage_root_of_large_delta_is_computed_in_parallel() {
+		let delta: Vec<_> = (0..2 * PARALLEL_STORAGE_ROOT_THRESHOLD as u32)
+			.map(|i| (::codec::Encode::encode(&i), if i % 3 == 0 { None } else { Some(vec![i as u8]) }))
+			.chain(vec![(b"key".to_vec(), None), (b"value1".to_vec(), Some(vec![1]))])
+			.collect();
+
+		let (mut mdb, mut expected_root) = test_db();
+		{
+			let mut trie = TrieDBMut::from_existing(&mut mdb, &mut expected_root).unwrap();
+			for (key, value) in &delta {
+				match value {
+					Some(value) => trie.insert(key, value).unwrap(),
+					None => trie.remove(key).unwrap(),
+				};
+			}
+		}
+
+		assert_eq!(test_trie().storage_root(delta).0, expected_root);
+	}
+
 	#[test]
 	fn prefix_walking_works() {
 		let trie = test_trie();
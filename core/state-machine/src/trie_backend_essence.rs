@@ -22,6 +22,8 @@ use std::ops::Deref;
 use std::sync::Arc;
 use hash_db::{self, Hasher};
 use heapsize::HeapSizeOf;
+use parking_lot::Mutex;
+use rayon::prelude::*;
 use trie::{TrieDB, Trie, MemoryDB, DBValue, TrieError, default_child_trie_root, read_trie_value, read_child_trie_value, for_keys_in_child_trie};
 use changes_trie::Storage as ChangesTrieStorage;
 
@@ -112,6 +114,15 @@ impl<S: TrieBackendStorage<H>, H: Hasher> TrieBackendEssence<S, H> where H::Out:
 
 	/// Execute given closure for all keys starting with prefix.
 	pub fn for_keys_with_prefix<F: FnMut(&[u8])>(&self, prefix: &[u8], mut f: F) {
+		self.for_keys_with_prefix_while(prefix, prefix, |key| {
+			f(key);
+			true
+		})
+	}
+
+	/// Execute given closure for the keys starting with the given prefix which aren't lower than
+	/// `start`, in order, until it returns `false`.
+	pub fn for_keys_with_prefix_while<F: FnMut(&[u8]) -> bool>(&self, prefix: &[u8], start: &[u8], mut f: F) {
 		let mut read_overlay = MemoryDB::default();
 		let eph = Ephemeral {
 			storage: &self.storage,
@@ -122,16 +133,14 @@ impl<S: TrieBackendStorage<H>, H: Hasher> TrieBackendEssence<S, H> where H::Out:
 			let trie = TrieDB::<H>::new(&eph, &self.root)?;
 			let mut iter = trie.iter()?;
 
-			iter.seek(prefix)?;
+			iter.seek(if start > prefix { start } else { prefix })?;
 
 			for x in iter {
 				let (key, _) = x?;
 
-				if !key.starts_with(prefix) {
+				if !key.starts_with(prefix) || !f(&key) {
 					break;
 				}
-
-				f(&key);
 			}
 
 			Ok(())
@@ -215,6 +224,62 @@ impl<'a,
 	}
 }
 
+/// Storage keeping in memory the trie nodes read through it.
+pub(crate) struct CachingStorage<'a, S: 'a + TrieBackendStorage<H>, H: Hasher> {
+	storage: &'a S,
+	cache: Mutex<HashMap<H::Out, DBValue>>,
+}
+
+impl<'a, S: 'a + TrieBackendStorage<H>, H: Hasher> CachingStorage<'a, S, H> {
+	pub fn new(storage: &'a S) -> Self {
+		CachingStorage {
+			storage,
+			cache: Mutex::new(HashMap::new()),
+		}
+	}
+}
+
+impl<'a, S: 'a + TrieBackendStorage<H>, H: Hasher> TrieBackendStorage<H> for CachingStorage<'a, S, H> {
+	fn get(&self, key: &H::Out) -> Result<Option<DBValue>, String> {
+		if let Some(node) = self.cache.lock().get(key) {
+			return Ok(Some(node.clone()));
+		}
+
+		let node = self.storage.get(key)?;
+		if let Some(ref node) = node {
+			self.cache.lock().insert(key.clone(), node.clone());
+		}
+		Ok(node)
+	}
+}
+
+/// Read in parallel the trie nodes on the paths to the given keys, so that they are cached by
+/// `storage` when the trie is updated afterwards.
+///
+/// Keys are split by their first nibble and each of the resulting subtrees is walked on its own.
+pub(crate) fn prefetch_trie_paths<'a, S, H, I>(storage: &CachingStorage<S, H>, root: &H::Out, keys: I) where
+	S: TrieBackendStorage<H>,
+	H: Hasher,
+	H::Out: HeapSizeOf,
+	I: IntoIterator<Item=&'a [u8]>,
+{
+	let mut subtrees = vec![Vec::new(); 16];
+	for key in keys {
+		subtrees[key.first().map_or(0, |byte| (byte >> 4) as usize)].push(key);
+	}
+
+	subtrees.into_par_iter().for_each(|keys| {
+		let mut read_overlay = MemoryDB::default();
+		let eph = Ephemeral::new(storage, &mut read_overlay);
+		for key in keys {
+			if let Err(e) = read_trie_value(&eph, root, key) {
+				debug!(target: "trie", "Error while prefetching trie nodes: {}", e);
+				return;
+			}
+		}
+	});
+}
+
 /// Key-value pairs storage that is used by trie backend essence.
 pub trait TrieBackendStorage<H: Hasher>: Send + Sync {
 	fn get(&self, key: &H::Out) -> Result<Option<DBValue>, String>;
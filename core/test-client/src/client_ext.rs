@@ -52,13 +52,14 @@ impl<B, E, RA> TestClient for Client<B, E, runtime::Block, RA>
 			body: Some(block.extrinsics),
 			finalized: false,
 			auxiliary: Vec::new(),
+			storage_changes: None,
 		};
 
 		self.import_block(import, None).map(|_| ())
 	}
 
 	fn finalize_block(&self, id: BlockId<runtime::Block>) -> client::error::Result<()> {
-		self.finalize_block(id, true)
+		self.finalize_block(id, None, true)
 	}
 
 	fn genesis_hash(&self) -> runtime::Hash {
@@ -30,11 +30,13 @@ pub extern crate substrate_consensus_common as consensus;
 extern crate substrate_state_machine as state_machine;
 
 pub mod client_ext;
+pub mod light;
 pub mod trait_tests;
 mod block_builder_ext;
 
 pub use client_ext::TestClient;
 pub use block_builder_ext::BlockBuilderExt;
+pub use light::{new_light, new_light_with_changes_trie, LightClient};
 pub use client::blockchain;
 pub use client::backend;
 pub use executor::NativeExecutor;
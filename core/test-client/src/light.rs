@@ -0,0 +1,119 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Light clients fetching their data from an in-process full node.
+
+use std::sync::Arc;
+
+use client::error::Error as ClientError;
+use client::CallResult;
+use client::light::blockchain::Blockchain;
+use client::light::fetcher::{
+	Fetcher, FetchChecker, LightDataChecker, RemoteCallRequest, RemoteChangesRequest,
+	RemoteHeaderRequest, RemoteReadRequest,
+};
+use primitives::Blake2Hasher;
+use runtime_primitives::generic::BlockId;
+use runtime_primitives::traits::NumberFor;
+use runtime::{Block, Header};
+use {Backend, Executor, LocalExecutor, NativeExecutor};
+
+/// Full client the light client fetches its data from.
+pub type FullClient = client::Client<Backend, Executor, Block, runtime::RuntimeApi>;
+
+/// Storage of the light client blockchain.
+pub type LightStorage = client::in_mem::Blockchain<Block>;
+
+/// Light client backend.
+pub type LightBackend = client::light::backend::Backend<LightStorage, LocalFetcher>;
+
+/// Light client executor.
+pub type LightExecutor = client::light::call_executor::RemoteCallExecutor<
+	Blockchain<LightStorage, LocalFetcher>,
+	LocalFetcher,
+	Blake2Hasher,
+>;
+
+/// Light client.
+pub type LightClient = client::Client<LightBackend, LightExecutor, Block, runtime::RuntimeApi>;
+
+/// Fetcher answering the requests of a light client with proofs of an in-process full node.
+/// The proofs are checked just like the ones received from the network.
+pub struct LocalFetcher {
+	full: Arc<FullClient>,
+	checker: LightDataChecker<NativeExecutor<LocalExecutor>, Blake2Hasher, Block, LightStorage, LocalFetcher>,
+}
+
+impl LocalFetcher {
+	/// The full client the requests are answered by.
+	pub fn full(&self) -> &Arc<FullClient> {
+		&self.full
+	}
+}
+
+impl Fetcher<Block> for LocalFetcher {
+	type RemoteHeaderResult = Result<Header, ClientError>;
+	type RemoteReadResult = Result<Option<Vec<u8>>, ClientError>;
+	type RemoteCallResult = Result<CallResult, ClientError>;
+	type RemoteChangesResult = Result<Vec<(NumberFor<Block>, u32)>, ClientError>;
+
+	fn remote_header(&self, request: RemoteHeaderRequest<Header>) -> Self::RemoteHeaderResult {
+		let (header, proof) = self.full.header_proof(&BlockId::Number(request.block))?;
+		self.checker.check_header_proof(&request, Some(header), proof)
+	}
+
+	fn remote_read(&self, request: RemoteReadRequest<Header>) -> Self::RemoteReadResult {
+		let proof = self.full.read_proof(&BlockId::Hash(request.block), &request.key)?;
+		self.checker.check_read_proof(&request, proof)
+	}
+
+	fn remote_call(&self, request: RemoteCallRequest<Header>) -> Self::RemoteCallResult {
+		let (_, proof) = self.full.execution_proof(&BlockId::Hash(request.block), &request.method, &request.call_data)?;
+		self.checker.check_execution_proof(&request, proof)
+	}
+
+	fn remote_changes(&self, request: RemoteChangesRequest<Header>) -> Self::RemoteChangesResult {
+		let proof = self.full.key_changes_proof(
+			request.first_block.1,
+			request.last_block.1,
+			request.tries_roots.1,
+			request.max_block.1,
+			&request.key,
+		)?;
+		self.checker.check_changes_proof(&request, proof)
+	}
+}
+
+/// Creates a light client along with the full client its requests are answered by.
+pub fn new_light() -> (Arc<FullClient>, LightClient) {
+	new_light_with_full(Arc::new(::new()), false)
+}
+
+/// Creates a light client along with the full client its requests are answered by. Both of
+/// them support changes trie creation.
+pub fn new_light_with_changes_trie() -> (Arc<FullClient>, LightClient) {
+	new_light_with_full(Arc::new(::new_with_changes_trie()), true)
+}
+
+fn new_light_with_full(full: Arc<FullClient>, support_changes_trie: bool) -> (Arc<FullClient>, LightClient) {
+	let blockchain = client::light::new_light_blockchain(LightStorage::new());
+	let checker = client::light::new_fetch_checker(blockchain.clone(), NativeExecutor::new());
+	let fetcher = Arc::new(LocalFetcher { full: full.clone(), checker });
+	let backend = client::light::new_light_backend(blockchain, fetcher.clone());
+	let light = client::light::new_light(backend, fetcher, ::genesis_storage(support_changes_trie))
+		.expect("genesis storage of the test runtime is valid; qed");
+	(full, light)
+}
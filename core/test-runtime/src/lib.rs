@@ -56,7 +56,7 @@ use runtime_primitives::{
 	traits::{
 		BlindCheckable, BlakeTwo256, Block as BlockT, Extrinsic as ExtrinsicT,
 		GetNodeBlockType, GetRuntimeBlockType
-	}, CheckInherentError
+	}, CheckInherentsResult
 };
 use runtime_version::RuntimeVersion;
 pub use primitives::hash::H256;
@@ -66,13 +66,14 @@ use primitives::OpaqueMetadata;
 use runtime_version::NativeVersion;
 
 /// Test runtime version.
+#[runtime_version]
 pub const VERSION: RuntimeVersion = RuntimeVersion {
 	spec_name: ver_str!("test"),
 	impl_name: ver_str!("parity-test"),
 	authoring_version: 1,
 	spec_version: 1,
 	impl_version: 1,
-	apis: apis_vec!([]),
+	apis: RUNTIME_API_VERSIONS,
 };
 
 fn version() -> RuntimeVersion {
@@ -233,7 +234,7 @@ impl_runtime_apis! {
 			unimplemented!()
 		}
 
-		fn check_inherents(_block: Block, _data: ()) -> Result<(), CheckInherentError> {
+		fn check_inherents(_block: Block, _data: ()) -> CheckInherentsResult {
 			unimplemented!()
 		}
 
@@ -21,6 +21,7 @@
 use std::{
 	hash,
 	sync::Arc,
+	time,
 };
 
 use serde::Serialize;
@@ -84,6 +85,8 @@ pub struct PruneStatus<Hash, Ex> {
 pub struct Transaction<Hash, Extrinsic> {
 	/// Raw extrinsic representing that transaction.
 	pub data: Extrinsic,
+	/// Number of bytes of the encoded extrinsic.
+	pub bytes: usize,
 	/// Transaction hash (unique)
 	pub hash: Hash,
 	/// Transaction priority (higher = better)
@@ -110,13 +113,27 @@ pub struct Transaction<Hash, Extrinsic> {
 pub struct BasePool<Hash: hash::Hash + Eq, Ex> {
 	future: FutureTransactions<Hash, Ex>,
 	ready: ReadyTransactions<Hash, Ex>,
+	/// Maximal number of transactions in the Future queue waiting for the same tag.
+	future_limit: usize,
 }
 
 impl<Hash: hash::Hash + Eq, Ex> Default for BasePool<Hash, Ex> {
 	fn default() -> Self {
+		Self::new(usize::max_value())
+	}
+}
+
+impl<Hash: hash::Hash + Eq, Ex> BasePool<Hash, Ex> {
+	/// Create new pool with given limit of Future transactions waiting for the same tag.
+	///
+	/// Transactions of a single sender that are waiting for a nonce gap to be filled
+	/// are all waiting for the tag of the first missing nonce, so this limits the number
+	/// of such transactions per sender.
+	pub fn new(future_limit: usize) -> Self {
 		BasePool {
 			future: Default::default(),
 			ready: Default::default(),
+			future_limit,
 		}
 	}
 }
@@ -143,6 +160,11 @@ impl<Hash: hash::Hash + Member + Serialize, Ex: ::std::fmt::Debug> BasePool<Hash
 
 		// If all tags are not satisfied import to future.
 		if !tx.is_ready() {
+			if tx.missing_tags.iter().any(|tag| self.future.waiting_for(tag) >= self.future_limit) {
+				debug!(target: "txpool", "[{:?}] Too many transactions waiting for the same tags", tx.transaction.hash);
+				bail!(error::ErrorKind::FutureLimitReached)
+			}
+
 			let hash = tx.transaction.hash.clone();
 			self.future.import(tx);
 			return Ok(Imported::Future { hash });
@@ -240,6 +262,14 @@ impl<Hash: hash::Hash + Member + Serialize, Ex: ::std::fmt::Debug> BasePool<Hash
 		removed
 	}
 
+	/// Removes transactions that have been waiting in the Future queue for at least `timeout`.
+	///
+	/// Returns a list of removed transactions.
+	pub fn remove_expired_future(&mut self, now: &time::Instant, timeout: time::Duration) -> Vec<Transaction<Hash, Ex>> {
+		let hashes = self.future.expired(now, timeout);
+		self.future.remove(&hashes)
+	}
+
 	/// Prunes transactions that provide given list of tags.
 	///
 	/// This will cause all transactions that provide these tags to be removed from the pool,
@@ -281,17 +311,24 @@ impl<Hash: hash::Hash + Member + Serialize, Ex: ::std::fmt::Debug> BasePool<Hash
 	pub fn status(&self) -> Status {
 		Status {
 			ready: self.ready.len(),
+			ready_bytes: self.ready.bytes(),
 			future: self.future.len(),
+			future_bytes: self.future.all().map(|tx| tx.bytes).sum(),
 		}
 	}
 }
 
 /// Pool status
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct Status {
 	/// Number of transactions in the ready queue.
 	pub ready: usize,
+	/// Sum of the encoded sizes of the transactions in the ready queue.
+	pub ready_bytes: usize,
 	/// Number of transactions in the future queue.
 	pub future: usize,
+	/// Sum of the encoded sizes of the transactions in the future queue.
+	pub future_bytes: usize,
 }
 
 #[cfg(test)]
@@ -312,6 +349,7 @@ mod tests {
 		// when
 		pool.import(Transaction {
 			data: vec![1u8],
+			bytes: 1,
 			hash: 1u64,
 			priority: 5u64,
 			valid_till: 64u64,
@@ -332,6 +370,7 @@ mod tests {
 		// when
 		pool.import(Transaction {
 			data: vec![1u8],
+			bytes: 1,
 			hash: 1,
 			priority: 5u64,
 			valid_till: 64u64,
@@ -340,6 +379,7 @@ mod tests {
 		}).unwrap();
 		pool.import(Transaction {
 			data: vec![1u8],
+			bytes: 1,
 			hash: 1,
 			priority: 5u64,
 			valid_till: 64u64,
@@ -361,6 +401,7 @@ mod tests {
 		// when
 		pool.import(Transaction {
 			data: vec![1u8],
+			bytes: 1,
 			hash: 1,
 			priority: 5u64,
 			valid_till: 64u64,
@@ -371,6 +412,7 @@ mod tests {
 		assert_eq!(pool.ready.len(), 0);
 		pool.import(Transaction {
 			data: vec![2u8],
+			bytes: 1,
 			hash: 2,
 			priority: 5u64,
 			valid_till: 64u64,
@@ -391,6 +433,7 @@ mod tests {
 		// when
 		pool.import(Transaction {
 			data: vec![1u8],
+			bytes: 1,
 			hash: 1,
 			priority: 5u64,
 			valid_till: 64u64,
@@ -399,6 +442,7 @@ mod tests {
 		}).unwrap();
 		pool.import(Transaction {
 			data: vec![3u8],
+			bytes: 1,
 			hash: 3,
 			priority: 5u64,
 			valid_till: 64u64,
@@ -407,6 +451,7 @@ mod tests {
 		}).unwrap();
 		pool.import(Transaction {
 			data: vec![2u8],
+			bytes: 1,
 			hash: 2,
 			priority: 5u64,
 			valid_till: 64u64,
@@ -415,6 +460,7 @@ mod tests {
 		}).unwrap();
 		pool.import(Transaction {
 			data: vec![4u8],
+			bytes: 1,
 			hash: 4,
 			priority: 1_000u64,
 			valid_till: 64u64,
@@ -426,6 +472,7 @@ mod tests {
 
 		let res = pool.import(Transaction {
 			data: vec![5u8],
+			bytes: 1,
 			hash: 5,
 			priority: 5u64,
 			valid_till: 64u64,
@@ -456,6 +503,7 @@ mod tests {
 		let mut pool = pool();
 		pool.import(Transaction {
 			data: vec![1u8],
+			bytes: 1,
 			hash: 1,
 			priority: 5u64,
 			valid_till: 64u64,
@@ -464,6 +512,7 @@ mod tests {
 		}).unwrap();
 		pool.import(Transaction {
 			data: vec![3u8],
+			bytes: 1,
 			hash: 3,
 			priority: 5u64,
 			valid_till: 64u64,
@@ -476,6 +525,7 @@ mod tests {
 		// when
 		pool.import(Transaction {
 			data: vec![2u8],
+			bytes: 1,
 			hash: 2,
 			priority: 5u64,
 			valid_till: 64u64,
@@ -494,6 +544,7 @@ mod tests {
 		// let's close the cycle with one additional transaction
 		let res = pool.import(Transaction {
 			data: vec![4u8],
+			bytes: 1,
 			hash: 4,
 			priority: 50u64,
 			valid_till: 64u64,
@@ -521,6 +572,7 @@ mod tests {
 		let mut pool = pool();
 		pool.import(Transaction {
 			data: vec![1u8],
+			bytes: 1,
 			hash: 1,
 			priority: 5u64,
 			valid_till: 64u64,
@@ -529,6 +581,7 @@ mod tests {
 		}).unwrap();
 		pool.import(Transaction {
 			data: vec![3u8],
+			bytes: 1,
 			hash: 3,
 			priority: 5u64,
 			valid_till: 64u64,
@@ -541,6 +594,7 @@ mod tests {
 		// when
 		pool.import(Transaction {
 			data: vec![2u8],
+			bytes: 1,
 			hash: 2,
 			priority: 5u64,
 			valid_till: 64u64,
@@ -559,6 +613,7 @@ mod tests {
 		// let's close the cycle with one additional transaction
 		let err = pool.import(Transaction {
 			data: vec![4u8],
+			bytes: 1,
 			hash: 4,
 			priority: 1u64, // lower priority than Tx(2)
 			valid_till: 64u64,
@@ -581,6 +636,7 @@ mod tests {
 		let mut pool = pool();
 		pool.import(Transaction {
 			data: vec![5u8],
+			bytes: 1,
 			hash: 5,
 			priority: 5u64,
 			valid_till: 64u64,
@@ -589,6 +645,7 @@ mod tests {
 		}).unwrap();
 		pool.import(Transaction {
 			data: vec![1u8],
+			bytes: 1,
 			hash: 1,
 			priority: 5u64,
 			valid_till: 64u64,
@@ -597,6 +654,7 @@ mod tests {
 		}).unwrap();
 		pool.import(Transaction {
 			data: vec![3u8],
+			bytes: 1,
 			hash: 3,
 			priority: 5u64,
 			valid_till: 64u64,
@@ -605,6 +663,7 @@ mod tests {
 		}).unwrap();
 		pool.import(Transaction {
 			data: vec![2u8],
+			bytes: 1,
 			hash: 2,
 			priority: 5u64,
 			valid_till: 64u64,
@@ -613,6 +672,7 @@ mod tests {
 		}).unwrap();
 		pool.import(Transaction {
 			data: vec![4u8],
+			bytes: 1,
 			hash: 4,
 			priority: 1_000u64,
 			valid_till: 64u64,
@@ -622,6 +682,7 @@ mod tests {
 		// future
 		pool.import(Transaction {
 			data: vec![6u8],
+			bytes: 1,
 			hash: 6,
 			priority: 1_000u64,
 			valid_till: 64u64,
@@ -647,6 +708,7 @@ mod tests {
 		// future (waiting for 0)
 		pool.import(Transaction {
 			data: vec![5u8],
+			bytes: 1,
 			hash: 5,
 			priority: 5u64,
 			valid_till: 64u64,
@@ -656,6 +718,7 @@ mod tests {
 		// ready
 		pool.import(Transaction {
 			data: vec![1u8],
+			bytes: 1,
 			hash: 1,
 			priority: 5u64,
 			valid_till: 64u64,
@@ -664,6 +727,7 @@ mod tests {
 		}).unwrap();
 		pool.import(Transaction {
 			data: vec![2u8],
+			bytes: 1,
 			hash: 2,
 			priority: 5u64,
 			valid_till: 64u64,
@@ -672,6 +736,7 @@ mod tests {
 		}).unwrap();
 		pool.import(Transaction {
 			data: vec![3u8],
+			bytes: 1,
 			hash: 3,
 			priority: 5u64,
 			valid_till: 64u64,
@@ -680,6 +745,7 @@ mod tests {
 		}).unwrap();
 		pool.import(Transaction {
 			data: vec![4u8],
+			bytes: 1,
 			hash: 4,
 			priority: 1_000u64,
 			valid_till: 64u64,
@@ -708,4 +774,63 @@ mod tests {
 		assert_eq!(pool.ready().count(), 3);
 	}
 
+	#[test]
+	fn should_limit_future_transactions_waiting_for_the_same_tag() {
+		// given
+		let mut pool = BasePool::new(2);
+		for i in 1..3u8 {
+			pool.import(Transaction {
+				data: vec![i],
+				bytes: 1,
+				hash: i as u64,
+				priority: 5u64,
+				valid_till: 64u64,
+				requires: vec![vec![0], vec![i]],
+				provides: vec![vec![i + 1]],
+			}).unwrap();
+		}
+		assert_eq!(pool.future.len(), 2);
+
+		// when
+		let err = pool.import(Transaction {
+			data: vec![3u8],
+			bytes: 1,
+			hash: 3,
+			priority: 5u64,
+			valid_till: 64u64,
+			requires: vec![vec![0], vec![3]],
+			provides: vec![vec![4]],
+		}).unwrap_err();
+
+		// then
+		if let error::ErrorKind::FutureLimitReached = *err.kind() {} else {
+			assert!(false, "Invalid error kind: {:?}", err.kind());
+		}
+		assert_eq!(pool.future.len(), 2);
+	}
+
+	#[test]
+	fn should_remove_expired_future_transactions() {
+		// given
+		let mut pool = pool();
+		pool.import(Transaction {
+			data: vec![1u8],
+			bytes: 1,
+			hash: 1,
+			priority: 5u64,
+			valid_till: 64u64,
+			requires: vec![vec![0]],
+			provides: vec![vec![1]],
+		}).unwrap();
+		let timeout = time::Duration::from_secs(60);
+		assert_eq!(pool.remove_expired_future(&time::Instant::now(), timeout).len(), 0);
+
+		// when
+		let removed = pool.remove_expired_future(&(time::Instant::now() + timeout), timeout);
+
+		// then
+		assert_eq!(removed.len(), 1);
+		assert_eq!(removed[0].hash, 1);
+		assert_eq!(pool.future.len(), 0);
+	}
 }
@@ -45,6 +45,11 @@ error_chain! {
 			description("The priority is too low to replace transactions already in the pool."),
 			display("Too low priority ({} > {})", old, new)
 		}
+		/// Too many transactions in the future queue are waiting for the same tags.
+		FutureLimitReached {
+			description("Too many transactions are waiting for the same transaction to be imported."),
+			display("Future Limit Reached"),
+		}
 		/// Deps cycle detected and we couldn't import transaction.
 		CycleDetected {
 			description("Transaction was not imported because of detected cycle."),
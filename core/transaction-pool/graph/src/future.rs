@@ -17,6 +17,7 @@
 use std::{
 	collections::{HashMap, HashSet},
 	hash,
+	time,
 };
 
 use sr_primitives::transaction_validity::{
@@ -32,6 +33,8 @@ pub struct WaitingTransaction<Hash, Ex> {
 	pub transaction: Transaction<Hash, Ex>,
 	/// Tags that are required and have not been satisfied yet by other transactions in the pool.
 	pub missing_tags: HashSet<Tag>,
+	/// Time of import to the Future queue.
+	pub imported_at: time::Instant,
 }
 
 impl<Hash, Ex> WaitingTransaction<Hash, Ex> {
@@ -49,6 +52,7 @@ impl<Hash, Ex> WaitingTransaction<Hash, Ex> {
 		WaitingTransaction {
 			transaction,
 			missing_tags,
+			imported_at: time::Instant::now(),
 		}
 	}
 
@@ -117,6 +121,20 @@ impl<Hash: hash::Hash + Eq + Clone, Ex> FutureTransactions<Hash, Ex> {
 		self.waiting.contains_key(hash)
 	}
 
+	/// Returns the number of transactions waiting for given tag.
+	pub fn waiting_for(&self, tag: &Tag) -> usize {
+		self.wanted_tags.get(tag).map_or(0, HashSet::len)
+	}
+
+	/// Returns hashes of the transactions that have been waiting for at least `timeout`.
+	pub fn expired(&self, now: &time::Instant, timeout: time::Duration) -> Vec<Hash> {
+		self.waiting
+			.values()
+			.filter(|waiting| waiting.imported_at + timeout <= *now)
+			.map(|waiting| waiting.transaction.hash.clone())
+			.collect()
+	}
+
 	/// Satisfies provided tags in transactions that are waiting for them.
 	///
 	/// Returns (and removes) transactions that became ready after their last tag got
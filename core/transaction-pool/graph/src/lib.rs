@@ -54,4 +54,7 @@ pub mod watcher;
 
 pub use self::error::IntoPoolError;
 pub use self::base_pool::{Transaction, Status};
-pub use self::pool::{Pool, Options, ChainApi, EventStream, ExtrinsicFor, BlockHash, ExHash, NumberFor, TransactionFor};
+pub use self::pool::{
+	Pool, Options, PoolStatus, ReadyLimit, ChainApi, EventStream, ExtrinsicFor, BlockHash, ExHash, NumberFor,
+	TransactionFor, TransactionPool, PoolBlockHash, PoolExtrinsic, PoolTransaction,
+};
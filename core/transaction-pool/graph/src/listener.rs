@@ -24,19 +24,22 @@ use watcher;
 use sr_primitives::traits;
 
 /// Extrinsic pool default listener.
-pub struct Listener<H: hash::Hash + Eq, H2> {
-	watchers: HashMap<H, watcher::Sender<H, H2>>
+pub struct Listener<H: hash::Hash + Eq, H2: hash::Hash + Eq> {
+	watchers: HashMap<H, watcher::Sender<H, H2>>,
+	/// Watched extrinsics included in blocks which are not finalized yet, with the block numbers.
+	finality_watchers: HashMap<H2, (u64, Vec<H>)>,
 }
 
-impl<H: hash::Hash + Eq, H2> Default for Listener<H, H2> {
+impl<H: hash::Hash + Eq, H2: hash::Hash + Eq> Default for Listener<H, H2> {
 	fn default() -> Self {
 		Listener {
 			watchers: Default::default(),
+			finality_watchers: Default::default(),
 		}
 	}
 }
 
-impl<H: hash::Hash + traits::Member + Serialize, H2: Clone> Listener<H, H2> {
+impl<H: hash::Hash + traits::Member + Serialize, H2: hash::Hash + Eq + Clone> Listener<H, H2> {
 	fn fire<F>(&mut self, hash: &H, fun: F) where F: FnOnce(&mut watcher::Sender<H, H2>) {
 		let clean = if let Some(h) = self.watchers.get_mut(hash) {
 			fun(h);
@@ -90,8 +93,43 @@ impl<H: hash::Hash + traits::Member + Serialize, H2: Clone> Listener<H, H2> {
 		self.fire(tx, |watcher| watcher.invalid());
 	}
 
-	/// Transaction was pruned from the pool.
-	pub fn pruned(&mut self, header_hash: H2, tx: &H) {
-		self.fire(tx, |watcher| watcher.finalised(header_hash))
+	/// Transaction was pruned from the pool, being included in the block with given hash and number.
+	pub fn pruned(&mut self, header_hash: H2, header_number: u64, tx: &H) {
+		self.fire(tx, |watcher| watcher.finalised(header_hash.clone()));
+		if self.watchers.contains_key(tx) {
+			self.finality_watchers.entry(header_hash)
+				.or_insert_with(|| (header_number, Vec::new()))
+				.1.push(tx.clone());
+		}
+	}
+
+	/// Blocks with given hashes have been finalized, up to the given number.
+	///
+	/// Transactions included in other blocks at or below that number time out, since these blocks
+	/// can't be finalized anymore.
+	pub fn finalized(&mut self, finalized: impl IntoIterator<Item=H2>, number: u64) {
+		for header_hash in finalized {
+			if let Some((_, txs)) = self.finality_watchers.remove(&header_hash) {
+				for tx in txs {
+					self.fire(&tx, |watcher| watcher.block_finalized(header_hash.clone()));
+				}
+			}
+		}
+		self.finality_timeout(number);
+	}
+
+	/// Transactions included in blocks at or below given number time out waiting for finality.
+	pub fn finality_timeout(&mut self, number: u64) {
+		let timed_out = self.finality_watchers.iter()
+			.filter(|(_, (header_number, _))| *header_number <= number)
+			.map(|(header_hash, _)| header_hash.clone())
+			.collect::<Vec<_>>();
+		for header_hash in timed_out {
+			if let Some((_, txs)) = self.finality_watchers.remove(&header_hash) {
+				for tx in txs {
+					self.fire(&tx, |watcher| watcher.finality_timeout(header_hash.clone()));
+				}
+			}
+		}
 	}
 }
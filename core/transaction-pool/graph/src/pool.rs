@@ -15,9 +15,9 @@
 // along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
 
 use std::{
-	collections::HashMap,
+	collections::{HashMap, HashSet},
 	hash,
-	sync::Arc,
+	sync::{Arc, atomic::{AtomicUsize, Ordering}},
 	time,
 };
 
@@ -31,6 +31,7 @@ use serde::Serialize;
 use futures::sync::mpsc;
 use parking_lot::{Mutex, RwLock};
 use sr_primitives::{
+	codec::Encode,
 	generic::BlockId,
 	traits::{self, As},
 	transaction_validity::{TransactionValidity, TransactionTag as Tag},
@@ -73,8 +74,107 @@ pub trait ChainApi: Send + Sync {
 }
 
 /// Pool configuration options.
-#[derive(Debug, Clone, Default)]
-pub struct Options;
+#[derive(Debug, Clone)]
+pub struct Options {
+	/// Maximal number of transactions in the Future queue waiting for the same tag,
+	/// e.g. transactions of a single sender waiting for a nonce gap to be filled.
+	pub future_limit: usize,
+	/// How long a transaction may wait in the Future queue before it's dropped.
+	pub future_timeout: time::Duration,
+	/// Number of blocks after which the watchers of a transaction included in a block which is
+	/// still not finalized are notified about the finality timeout.
+	pub finality_timeout: u64,
+}
+
+impl Default for Options {
+	fn default() -> Self {
+		Options {
+			future_limit: 64,
+			future_timeout: time::Duration::from_secs(60 * 10),
+			finality_timeout: 512,
+		}
+	}
+}
+
+/// Detailed pool status, meant for introspection and monitoring.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PoolStatus {
+	/// Number of transactions in the ready queue.
+	pub ready: usize,
+	/// Sum of the encoded sizes of the transactions in the ready queue.
+	pub ready_bytes: usize,
+	/// Number of transactions in the future queue.
+	pub future: usize,
+	/// Sum of the encoded sizes of the transactions in the future queue.
+	pub future_bytes: usize,
+	/// Number of temporarily banned transactions.
+	pub banned: usize,
+	/// Total number of transaction validations performed since the pool was created.
+	pub validations: usize,
+}
+
+/// Limits of the transactions returned by `Pool::ready_at`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReadyLimit {
+	/// Maximum number of transactions.
+	pub count: usize,
+	/// Maximum sum of the encoded sizes of the transactions.
+	pub bytes: usize,
+}
+
+/// Block hash type for a `TransactionPool`.
+pub type PoolBlockHash<P> = <<P as TransactionPool>::Block as traits::Block>::Hash;
+/// Extrinsic type for a `TransactionPool`.
+pub type PoolExtrinsic<P> = <<P as TransactionPool>::Block as traits::Block>::Extrinsic;
+/// A type of transaction stored in a `TransactionPool`.
+pub type PoolTransaction<P> = Arc<base::Transaction<<P as TransactionPool>::Hash, PoolExtrinsic<P>>>;
+
+/// Transaction pool interface used by the service, the network propagation and the RPC.
+///
+/// `Pool` is the default implementation. Chains with custom ordering requirements
+/// (e.g. front-running protection) may supply their own.
+pub trait TransactionPool: Send + Sync {
+	/// Block type.
+	type Block: traits::Block;
+	/// Transaction hash type.
+	type Hash: hash::Hash + Eq + traits::Member + Serialize;
+	/// Error type.
+	type Error: From<error::Error> + error::IntoPoolError;
+
+	/// Imports one unverified extrinsic to the pool.
+	fn submit_one(&self, at: &BlockId<Self::Block>, xt: PoolExtrinsic<Self>) -> Result<Self::Hash, Self::Error>;
+
+	/// Imports one unverified extrinsic and starts watching its progress in the pool.
+	fn submit_and_watch(&self, at: &BlockId<Self::Block>, xt: PoolExtrinsic<Self>) -> Result<Watcher<Self::Hash, PoolBlockHash<Self>>, Self::Error>;
+
+	/// Prunes ready transactions that provide given list of tags.
+	fn prune_tags(&self, at: &BlockId<Self::Block>, tags: Vec<Tag>) -> Result<(), Self::Error>;
+
+	/// Notifies the pool about a finalized block and the ancestors finalized along with it.
+	fn on_finalized(&self, at: &BlockId<Self::Block>, tree_route: Vec<PoolBlockHash<Self>>) -> Result<(), Self::Error>;
+
+	/// Removes invalid transactions from the pool, returning the removed ones.
+	fn remove_invalid(&self, hashes: &[Self::Hash]) -> Vec<PoolTransaction<Self>>;
+
+	/// Notifies the pool about the broadcasting status of transactions.
+	fn on_broadcasted(&self, propagated: HashMap<Self::Hash, Vec<String>>);
+
+	/// Returns a stream notified whenever a new transaction is imported to the pool.
+	fn import_notification_stream(&self) -> EventStream;
+
+	/// Returns the ready transactions in the order they should be included in a block.
+	fn ready(&self) -> Box<Iterator<Item=PoolTransaction<Self>>>;
+
+	/// Returns the ready transactions to include in a block built on top of `at`, within `limit`.
+	fn ready_at(&self, at: &BlockId<Self::Block>, limit: ReadyLimit) -> Result<Vec<PoolTransaction<Self>>, Self::Error>;
+
+	/// Returns detailed pool status.
+	fn pool_status(&self) -> PoolStatus;
+
+	/// Returns the hash of given extrinsic.
+	fn hash_of(&self, xt: &PoolExtrinsic<Self>) -> Self::Hash;
+}
 
 /// Extrinsics pool.
 pub struct Pool<B: ChainApi> {
@@ -86,6 +186,9 @@ pub struct Pool<B: ChainApi> {
 	>>,
 	import_notification_sinks: Mutex<Vec<mpsc::UnboundedSender<()>>>,
 	rotator: PoolRotator<ExHash<B>>,
+	validations: AtomicUsize,
+	future_timeout: time::Duration,
+	finality_timeout: u64,
 }
 
 impl<B: ChainApi> Pool<B> {
@@ -105,9 +208,11 @@ impl<B: ChainApi> Pool<B> {
 					bail!(error::Error::from(error::ErrorKind::TemporarilyBanned))
 				}
 
+				self.validations.fetch_add(1, Ordering::Relaxed);
 				match self.api.validate_transaction(at, &xt)? {
 					TransactionValidity::Valid { priority, requires, provides, longevity } => {
 						Ok(base::Transaction {
+							bytes: xt.encode().len(),
 							data:  xt,
 							hash,
 							priority,
@@ -178,9 +283,15 @@ impl<B: ChainApi> Pool<B> {
 		{
 			let header_hash = self.api.block_id_to_hash(at)?
 				.ok_or_else(|| error::ErrorKind::Msg(format!("Invalid block id: {:?}", at)).into())?;
+			let header_number = self.api.block_id_to_number(at)?
+				.ok_or_else(|| error::ErrorKind::Msg(format!("Invalid block id: {:?}", at)).into())?
+				.as_();
 			let mut listener = self.listener.write();
 			for h in hashes {
-				listener.pruned(header_hash, &h)
+				listener.pruned(header_hash, header_number, &h)
+			}
+			if let Some(number) = header_number.checked_sub(self.finality_timeout) {
+				listener.finality_timeout(number);
 			}
 		}
 		// clear old transactions
@@ -188,6 +299,23 @@ impl<B: ChainApi> Pool<B> {
 		Ok(())
 	}
 
+	/// Notifies the pool about a finalized block and the ancestors finalized along with it.
+	///
+	/// Watchers of transactions included in the finalized blocks are notified, while transactions
+	/// included in other blocks at or below the finalized one time out. Transactions are not
+	/// re-validated at the finalized block, since they may depend on blocks that are not finalized
+	/// yet; only the ones whose longevity ends at or below the finalized block are removed.
+	pub fn on_finalized(&self, at: &BlockId<B::Block>, tree_route: Vec<BlockHash<B>>) -> Result<(), B::Error> {
+		let header_hash = self.api.block_id_to_hash(at)?
+			.ok_or_else(|| error::ErrorKind::Msg(format!("Invalid block id: {:?}", at)).into())?;
+		let header_number = self.api.block_id_to_number(at)?
+			.ok_or_else(|| error::ErrorKind::Msg(format!("Invalid block id: {:?}", at)).into())?
+			.as_();
+		self.listener.write().finalized(tree_route.into_iter().chain(::std::iter::once(header_hash)), header_number);
+
+		self.clear_stale(at)
+	}
+
 	/// Removes stale transactions from the pool.
 	///
 	/// Stale transactions are transaction beyond their longevity period.
@@ -217,6 +345,15 @@ impl<B: ChainApi> Pool<B> {
 		// removing old transactions
 		self.remove_invalid(&to_remove);
 		self.remove_invalid(&futures_to_remove);
+		// drop transactions waiting for too long, they may be submitted again
+		{
+			let expired = self.pool.write().remove_expired_future(&now, self.future_timeout);
+			let mut listener = self.listener.write();
+			for tx in &expired {
+				debug!(target: "txpool", "[{:?}] Dropping transaction waiting in Future queue for too long", tx.hash);
+				listener.dropped(&tx.hash, None);
+			}
+		}
 		// clear banned transactions timeouts
 		self.rotator.clear_timeouts(&now);
 
@@ -226,14 +363,16 @@ impl<B: ChainApi> Pool<B> {
 
 impl<B: ChainApi> Pool<B> {
 	/// Create a new transaction pool.
-	/// TODO [ToDr] Options
-	pub fn new(_options: Options, api: B) -> Self {
+	pub fn new(options: Options, api: B) -> Self {
 		Pool {
 			api,
 			listener: Default::default(),
-			pool: Default::default(),
+			pool: RwLock::new(base::BasePool::new(options.future_limit)),
 			import_notification_sinks: Default::default(),
 			rotator: Default::default(),
+			validations: AtomicUsize::new(0),
+			future_timeout: options.future_timeout,
+			finality_timeout: options.finality_timeout,
 		}
 	}
 
@@ -273,23 +412,119 @@ impl<B: ChainApi> Pool<B> {
 		self.pool.read().ready()
 	}
 
+	/// Get the ready transactions to include in a block built on top of `at`, ordered by
+	/// priority and within `limit`.
+	///
+	/// Transactions which don't fit or whose longevity ends before the block are skipped, along
+	/// with the transactions depending on the tags they provide.
+	pub fn ready_at(&self, at: &BlockId<B::Block>, limit: ReadyLimit) -> Result<Vec<TransactionFor<B>>, B::Error> {
+		let block_number: u64 = self.api.block_id_to_number(at)?
+			.ok_or_else(|| error::ErrorKind::Msg(format!("Invalid block id: {:?}", at)).into())?
+			.as_() + 1;
+
+		let mut selected = Vec::new();
+		let mut bytes = 0;
+		let mut skipped_tags = HashSet::new();
+		for tx in self.ready() {
+			if selected.len() >= limit.count {
+				break;
+			}
+
+			let skip = tx.valid_till < block_number
+				|| bytes + tx.bytes > limit.bytes
+				|| tx.requires.iter().any(|tag| skipped_tags.contains(tag));
+			if skip {
+				skipped_tags.extend(tx.provides.iter().cloned());
+				continue;
+			}
+
+			bytes += tx.bytes;
+			selected.push(tx);
+		}
+
+		Ok(selected)
+	}
+
 	/// Returns pool status.
 	pub fn status(&self) -> base::Status {
 		self.pool.read().status()
 	}
 
+	/// Returns detailed pool status, including the counters tracked by the pool.
+	pub fn pool_status(&self) -> PoolStatus {
+		let status = self.status();
+		PoolStatus {
+			ready: status.ready,
+			ready_bytes: status.ready_bytes,
+			future: status.future,
+			future_bytes: status.future_bytes,
+			banned: self.rotator.banned_count(),
+			validations: self.validations.load(Ordering::Relaxed),
+		}
+	}
+
 	/// Returns transaction hash
 	pub fn hash_of(&self, xt: &ExtrinsicFor<B>) -> ExHash<B> {
 		self.api.hash(xt)
 	}
 }
 
+impl<B: ChainApi + 'static> TransactionPool for Pool<B> {
+	type Block = B::Block;
+	type Hash = ExHash<B>;
+	type Error = B::Error;
+
+	fn submit_one(&self, at: &BlockId<B::Block>, xt: ExtrinsicFor<B>) -> Result<ExHash<B>, B::Error> {
+		Pool::submit_one(self, at, xt)
+	}
+
+	fn submit_and_watch(&self, at: &BlockId<B::Block>, xt: ExtrinsicFor<B>) -> Result<Watcher<ExHash<B>, BlockHash<B>>, B::Error> {
+		Pool::submit_and_watch(self, at, xt)
+	}
+
+	fn prune_tags(&self, at: &BlockId<B::Block>, tags: Vec<Tag>) -> Result<(), B::Error> {
+		Pool::prune_tags(self, at, tags)
+	}
+
+	fn on_finalized(&self, at: &BlockId<B::Block>, tree_route: Vec<BlockHash<B>>) -> Result<(), B::Error> {
+		Pool::on_finalized(self, at, tree_route)
+	}
+
+	fn remove_invalid(&self, hashes: &[ExHash<B>]) -> Vec<TransactionFor<B>> {
+		Pool::remove_invalid(self, hashes)
+	}
+
+	fn on_broadcasted(&self, propagated: HashMap<ExHash<B>, Vec<String>>) {
+		Pool::on_broadcasted(self, propagated)
+	}
+
+	fn import_notification_stream(&self) -> EventStream {
+		Pool::import_notification_stream(self)
+	}
+
+	fn ready(&self) -> Box<Iterator<Item=TransactionFor<B>>> {
+		Box::new(Pool::ready(self))
+	}
+
+	fn ready_at(&self, at: &BlockId<B::Block>, limit: ReadyLimit) -> Result<Vec<TransactionFor<B>>, B::Error> {
+		Pool::ready_at(self, at, limit)
+	}
+
+	fn pool_status(&self) -> PoolStatus {
+		Pool::pool_status(self)
+	}
+
+	fn hash_of(&self, xt: &ExtrinsicFor<B>) -> ExHash<B> {
+		Pool::hash_of(self, xt)
+	}
+}
+
 fn fire_events<H, H2, Ex>(
 	listener: &mut Listener<H, H2>,
 	imported: &base::Imported<H, Ex>,
 ) where
 	H: hash::Hash + Eq + traits::Member + Serialize,
-	H2: Clone,
+	H2: hash::Hash + Eq + Clone,
 {
 	match *imported {
 		base::Imported::Ready { ref promoted, ref failed, ref removed, ref hash } => {
@@ -413,6 +648,99 @@ mod tests {
 		assert_matches!(res.unwrap_err().kind(), error::ErrorKind::TemporarilyBanned);
 	}
 
+	#[test]
+	fn should_select_ready_transactions_within_limit() {
+		// given
+		let pool = pool();
+		let transfer = |from: u64, nonce| uxt(Transfer {
+			from: from.into(),
+			to: 2.into(),
+			amount: 5,
+			nonce,
+		});
+		let bytes = transfer(1, 0).encode().len();
+		let first = pool.submit_one(&BlockId::Number(0), transfer(1, 0)).unwrap();
+		let second = pool.submit_one(&BlockId::Number(0), transfer(1, 1)).unwrap();
+		let other = pool.submit_one(&BlockId::Number(0), transfer(3, 0)).unwrap();
+		let limit = |count, bytes| ReadyLimit { count, bytes };
+		let hashes = |txs: Vec<TransactionFor<TestApi>>| txs.into_iter().map(|tx| tx.hash).collect::<HashSet<_>>();
+
+		// then
+		assert_eq!(hashes(pool.ready_at(&BlockId::Number(0), limit(10, 10 * bytes)).unwrap()).len(), 3);
+		assert_eq!(pool.ready_at(&BlockId::Number(0), limit(2, 10 * bytes)).unwrap().len(), 2);
+		let selected = hashes(pool.ready_at(&BlockId::Number(0), limit(10, 2 * bytes)).unwrap());
+		assert_eq!(selected.len(), 2);
+		// the second transaction depends on the first one.
+		assert!(selected.contains(&first));
+		assert!(selected.contains(&second) != selected.contains(&other));
+		// longevity of the transactions ends at block 3.
+		assert!(pool.ready_at(&BlockId::Number(3), limit(10, 10 * bytes)).unwrap().is_empty());
+	}
+
+	#[test]
+	fn should_be_usable_through_transaction_pool_trait() {
+		fn submit_and_remove<P: TransactionPool>(pool: &P, at: &BlockId<P::Block>, xt: PoolExtrinsic<P>) -> Vec<P::Hash> {
+			let hash = pool.submit_one(at, xt).unwrap_or_else(|_| panic!("valid transaction"));
+			assert_eq!(pool.ready().map(|tx| tx.hash.clone()).collect::<Vec<_>>(), vec![hash.clone()]);
+			pool.remove_invalid(&[hash]).into_iter().map(|tx| tx.hash.clone()).collect()
+		}
+
+		// given
+		let pool = pool();
+		let xt = uxt(Transfer {
+			from: 1.into(),
+			to: 2.into(),
+			amount: 5,
+			nonce: 0,
+		});
+		let hash = pool.hash_of(&xt);
+
+		// when
+		let removed = submit_and_remove(&pool, &BlockId::Number(0), xt);
+
+		// then
+		assert_eq!(removed, vec![hash]);
+		assert_eq!(TransactionPool::pool_status(&pool).ready, 0);
+	}
+
+	#[test]
+	fn should_report_detailed_status() {
+		// given
+		let pool = pool();
+		let xt = uxt(Transfer {
+			from: 1.into(),
+			to: 2.into(),
+			amount: 5,
+			nonce: 0,
+		});
+		let bytes = xt.encode().len();
+
+		// when
+		let hash = pool.submit_one(&BlockId::Number(0), xt.clone()).unwrap();
+		pool.submit_one(&BlockId::Number(0), uxt(Transfer {
+			from: 1.into(),
+			to: 2.into(),
+			amount: 5,
+			nonce: 2,
+		})).unwrap();
+		assert_eq!(pool.pool_status(), PoolStatus {
+			ready: 1,
+			ready_bytes: bytes,
+			future: 1,
+			future_bytes: bytes,
+			banned: 0,
+			validations: 2,
+		});
+		pool.remove_invalid(&[hash]);
+
+		// then
+		let status = pool.pool_status();
+		assert_eq!(status.ready, 0);
+		assert_eq!(status.banned, 1);
+		assert!(pool.submit_one(&BlockId::Number(0), xt).is_err());
+		assert_eq!(pool.pool_status().validations, 2);
+	}
+
 	#[test]
 	fn should_notify_about_pool_events() {
 		let stream = {
@@ -489,6 +817,60 @@ mod tests {
 		assert!(pool.rotator.is_banned(&hash3));
 	}
 
+	#[test]
+	fn should_drop_future_transactions_after_timeout() {
+		// given
+		let pool = Pool::new(Options {
+			future_timeout: time::Duration::from_millis(0),
+			..Default::default()
+		}, TestApi::default());
+		let hash = pool.submit_one(&BlockId::Number(0), uxt(Transfer {
+			from: 1.into(),
+			to: 2.into(),
+			amount: 5,
+			nonce: 1,
+		})).unwrap();
+		assert_eq!(pool.status().future, 1);
+
+		// when
+		pool.clear_stale(&BlockId::Number(0)).unwrap();
+
+		// then
+		assert_eq!(pool.status().future, 0);
+		// the transaction may be submitted again
+		assert!(!pool.rotator.is_banned(&hash));
+	}
+
+	#[test]
+	fn should_only_remove_stale_transactions_at_finalized_block() {
+		// given
+		let pool = pool();
+		let transfer = |nonce| uxt(Transfer {
+			from: 1.into(),
+			to: 2.into(),
+			amount: 5,
+			nonce,
+		});
+		let hash1 = pool.submit_one(&BlockId::Number(0), transfer(0)).unwrap();
+		pool.submit_one(&BlockId::Number(0), transfer(1)).unwrap();
+		let validations = pool.validations.load(Ordering::Relaxed);
+
+		// when
+		pool.on_finalized(&BlockId::Number(2), vec![]).unwrap();
+
+		// then
+		assert_eq!(pool.status().ready, 2);
+		assert_eq!(pool.validations.load(Ordering::Relaxed), validations);
+		assert!(!pool.rotator.is_banned(&hash1));
+
+		// when
+		pool.on_finalized(&BlockId::Number(3), vec![]).unwrap();
+
+		// then
+		assert_eq!(pool.status().ready, 0);
+		assert!(pool.rotator.is_banned(&hash1));
+	}
+
 	mod listener {
 		use super::*;
 
@@ -514,6 +896,78 @@ mod tests {
 			let mut stream = watcher.into_stream().wait();
 			assert_eq!(stream.next(), Some(Ok(::watcher::Status::Ready)));
 			assert_eq!(stream.next(), Some(Ok(::watcher::Status::Finalised(2.into()))));
+		}
+
+		#[test]
+		fn should_trigger_finalized() {
+			// given
+			let pool = pool();
+			let watcher = pool.submit_and_watch(&BlockId::Number(0), uxt(Transfer {
+				from: 1.into(),
+				to: 2.into(),
+				amount: 5,
+				nonce: 0,
+			})).unwrap();
+			pool.prune_tags(&BlockId::Number(1), vec![vec![0u8]]).unwrap();
+
+			// when
+			pool.on_finalized(&BlockId::Number(2), vec![1.into()]).unwrap();
+
+			// then
+			let mut stream = watcher.into_stream().wait();
+			assert_eq!(stream.next(), Some(Ok(::watcher::Status::Ready)));
+			assert_eq!(stream.next(), Some(Ok(::watcher::Status::Finalised(1.into()))));
+			assert_eq!(stream.next(), Some(Ok(::watcher::Status::BlockFinalized(1.into()))));
+			assert_eq!(stream.next(), None);
+		}
+
+		#[test]
+		fn should_trigger_finality_timeout_when_another_block_is_finalized() {
+			// given
+			let pool = pool();
+			let watcher = pool.submit_and_watch(&BlockId::Number(0), uxt(Transfer {
+				from: 1.into(),
+				to: 2.into(),
+				amount: 5,
+				nonce: 0,
+			})).unwrap();
+			pool.prune_tags(&BlockId::Number(1), vec![vec![0u8]]).unwrap();
+
+			// when
+			pool.on_finalized(&BlockId::Number(2), vec![]).unwrap();
+
+			// then
+			let mut stream = watcher.into_stream().wait();
+			assert_eq!(stream.next(), Some(Ok(::watcher::Status::Ready)));
+			assert_eq!(stream.next(), Some(Ok(::watcher::Status::Finalised(1.into()))));
+			assert_eq!(stream.next(), Some(Ok(::watcher::Status::FinalityTimeout(1.into()))));
+			assert_eq!(stream.next(), None);
+		}
+
+		#[test]
+		fn should_trigger_finality_timeout_when_finality_lags() {
+			// given
+			let pool = Pool::new(Options {
+				finality_timeout: 2,
+				..Default::default()
+			}, TestApi::default());
+			let watcher = pool.submit_and_watch(&BlockId::Number(0), uxt(Transfer {
+				from: 1.into(),
+				to: 2.into(),
+				amount: 5,
+				nonce: 0,
+			})).unwrap();
+			pool.prune_tags(&BlockId::Number(1), vec![vec![0u8]]).unwrap();
+
+			// when
+			pool.prune_tags(&BlockId::Number(2), vec![]).unwrap();
+			pool.prune_tags(&BlockId::Number(3), vec![]).unwrap();
+
+			// then
+			let mut stream = watcher.into_stream().wait();
+			assert_eq!(stream.next(), Some(Ok(::watcher::Status::Ready)));
+			assert_eq!(stream.next(), Some(Ok(::watcher::Status::Finalised(1.into()))));
+			assert_eq!(stream.next(), Some(Ok(::watcher::Status::FinalityTimeout(1.into()))));
 			assert_eq!(stream.next(), None);
 		}
 
@@ -391,6 +391,11 @@ impl<Hash: hash::Hash + Member + Serialize, Ex> ReadyTransactions<Hash, Ex> {
 		self.ready.read().len()
 	}
 
+	/// Returns the sum of the encoded sizes of the transactions in this queue.
+	pub fn bytes(&self) -> usize {
+		self.ready.read().values().map(|tx| tx.transaction.transaction.bytes).sum()
+	}
+
 }
 
 pub struct BestIterator<Hash, Ex> {
@@ -466,6 +471,7 @@ mod tests {
 	fn tx(id: u8) -> Transaction<u64, Vec<u8>> {
 		Transaction {
 			data: vec![id],
+			bytes: 1,
 			hash: id as u64,
 			priority: 1,
 			valid_till: 2,
@@ -524,6 +530,7 @@ mod tests {
 		tx4.provides = vec![];
 		let tx5 = Transaction {
 			data: vec![5],
+			bytes: 1,
 			hash: 5,
 			priority: 1,
 			valid_till: u64::max_value(),	// use the max_value() here for testing.
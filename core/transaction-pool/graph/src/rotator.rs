@@ -57,6 +57,11 @@ impl<Hash: hash::Hash + Eq + Clone> PoolRotator<Hash> {
 		self.banned_until.read().contains_key(hash)
 	}
 
+	/// Returns the number of currently banned extrinsics.
+	pub fn banned_count(&self) -> usize {
+		self.banned_until.read().len()
+	}
+
 	/// Bans given set of hashes.
 	pub fn ban(&self, now: &Instant, hashes: &[Hash]) {
 		let mut banned = self.banned_until.write();
@@ -113,6 +118,7 @@ mod tests {
 		let hash = 5u64;
 		let tx = Transaction {
 			data: (),
+			bytes: 1,
 			hash: hash.clone(),
 			priority: 5,
 			valid_till: 1,
@@ -177,6 +183,7 @@ mod tests {
 			let hash = i;
 			Transaction {
 				data: (),
+				bytes: 1,
 				hash,
 				priority: 5,
 				valid_till,
@@ -31,6 +31,11 @@ pub enum Status<H, H2> {
 	Ready,
 	/// Extrinsic has been finalised in block with given hash.
 	Finalised(H2),
+	/// The block this extrinsic was included in has been finalized by the finality gadget.
+	BlockFinalized(H2),
+	/// The block this extrinsic was included in has not been finalized in time and most likely
+	/// never will be, e.g. because another block was finalized at its height.
+	FinalityTimeout(H2),
 	/// Some state change (perhaps another extrinsic was included) rendered this extrinsic invalid.
 	Usurped(H),
 	/// The extrinsic has been broadcast to the given peers.
@@ -110,6 +115,18 @@ impl<H: Clone, H2: Clone> Sender<H, H2> {
 	/// Extrinsic has been finalised in block with given hash.
 	pub fn finalised(&mut self, hash: H2) {
 		self.send(Status::Finalised(hash));
+	}
+
+	/// The block this extrinsic was included in has been finalized by the finality gadget.
+	pub fn block_finalized(&mut self, hash: H2) {
+		self.send(Status::BlockFinalized(hash));
+		self.finalised = true;
+	}
+
+	/// The block this extrinsic was included in has not been finalized in time.
+	pub fn finality_timeout(&mut self, hash: H2) {
+		self.send(Status::FinalityTimeout(hash));
+		// there are no more notifications once finality gave up on the block
 		self.finalised = true;
 	}
 
@@ -0,0 +1,167 @@
+// Copyright 2018 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Builds a wasm project from a `build.rs` script.
+//!
+//! The wasm project is compiled for `wasm32-unknown-unknown` in release mode into the `OUT_DIR` of
+//! the calling build, ignoring the compiler flags of the calling build. Afterwards the unused parts
+//! of the blob are removed. The paths to the resulting blobs are exported to the crate calling the
+//! builder as environment variables:
+//!
+//! - `WASM_BINARY` - the compacted wasm blob, which should be used on chain.
+//! - `WASM_BINARY_BLOATY` - the wasm blob as emitted by the compiler.
+//!
+//! They can be embedded with `include_bytes!(env!("WASM_BINARY"))`. This ensures that a binary
+//! always embeds a wasm runtime that was built from the same sources as its native runtime.
+//!
+//! The following environment variables influence the build:
+//!
+//! - `SKIP_WASM_BUILD` - skips building the wasm project. The environment variables are still
+//!   set and point into the `target` directory of the wasm project, so the blobs need to be built
+//!   there by other means, e.g. by `scripts/build.sh`.
+//! - `WASM_BUILD_TOOLCHAIN` - the toolchain used to build the wasm project. Defaults to
+//!   `nightly`.
+
+extern crate wasm_gc;
+
+use std::{env, fs, path::{Path, PathBuf}, process::Command};
+
+/// The target the wasm project is compiled for.
+const WASM_TARGET: &str = "wasm32-unknown-unknown";
+
+/// Environment variables set by the cargo invocation of the calling build that must not leak into
+/// the build of the wasm project.
+const FILTERED_ENV_VARS: &[&str] = &[
+	"RUSTFLAGS", "CARGO_TARGET_DIR", "RUSTUP_TOOLCHAIN", "RUSTC", "RUSTDOC", "CARGO_BUILD_TARGET",
+];
+
+/// Build the wasm project described by the given `Cargo.toml`.
+///
+/// `cargo_manifest` is relative to the directory of the crate calling this function. Panics if
+/// the build fails, which makes the build of the calling crate fail.
+pub fn build_project(cargo_manifest: &str) {
+	let manifest_dir = env::var("CARGO_MANIFEST_DIR")
+		.expect("`build_project` is called from a build script; qed");
+	let cargo_manifest = Path::new(&manifest_dir).join(cargo_manifest);
+	let project = cargo_manifest.parent()
+		.expect("a `Cargo.toml` is always located in a directory; qed")
+		.to_path_buf();
+
+	let skip_build = env::var("SKIP_WASM_BUILD").is_ok();
+	let target_dir = if skip_build {
+		project.join("target")
+	} else {
+		PathBuf::from(env::var("OUT_DIR").expect("`build_project` is called from a build script; qed"))
+			.join("wasm-target")
+	};
+	let (bloaty, compact) = wasm_binary_paths(&project, &target_dir);
+
+	if !skip_build {
+		build_wasm(&cargo_manifest, &target_dir);
+		compact_wasm(&bloaty, &compact);
+	}
+
+	println!("cargo:rustc-env=WASM_BINARY={}", compact.display());
+	println!("cargo:rustc-env=WASM_BINARY_BLOATY={}", bloaty.display());
+
+	rerun_if_changed(&project);
+	println!("cargo:rerun-if-env-changed=SKIP_WASM_BUILD");
+	println!("cargo:rerun-if-env-changed=WASM_BUILD_TOOLCHAIN");
+}
+
+/// Returns the paths of the bloaty and the compact wasm blob of the given project, built into
+/// `target_dir`.
+fn wasm_binary_paths(project: &Path, target_dir: &Path) -> (PathBuf, PathBuf) {
+	let manifest = fs::read_to_string(project.join("Cargo.toml"))
+		.expect("the wasm project has a `Cargo.toml`");
+	let name = manifest.lines()
+		.map(|l| l.trim())
+		.find(|l| l.starts_with("name"))
+		.and_then(|l| l.split('"').nth(1))
+		.expect("the `Cargo.toml` of the wasm project declares a package name")
+		.replace('-', "_");
+
+	let out = target_dir.join(WASM_TARGET).join("release");
+	(out.join(format!("{}.wasm", name)), out.join(format!("{}.compact.wasm", name)))
+}
+
+fn build_wasm(cargo_manifest: &Path, target_dir: &Path) {
+	let toolchain = env::var("WASM_BUILD_TOOLCHAIN").unwrap_or_else(|_| "nightly".into());
+
+	let mut command = Command::new("cargo");
+	command.arg(format!("+{}", toolchain))
+		.args(&["build", "--release", "--target", WASM_TARGET, "--manifest-path"])
+		.arg(cargo_manifest)
+		.arg("--target-dir")
+		.arg(target_dir);
+
+	for var in FILTERED_ENV_VARS {
+		command.env_remove(var);
+	}
+
+	// the flags of the calling build are ignored, so the blob does not depend on how the native
+	// binary is built.
+	command.env("RUSTFLAGS", "").env("CARGO_INCREMENTAL", "0");
+
+	match command.status() {
+		Ok(status) if status.success() => {},
+		Ok(status) => panic!("Building the wasm project `{}` failed: {}", cargo_manifest.display(), status),
+		Err(e) => panic!("Could not run cargo to build the wasm project: {}", e),
+	}
+}
+
+fn compact_wasm(bloaty: &Path, compact: &Path) {
+	let code = fs::read(bloaty).expect("the wasm blob was just built");
+	let compacted = wasm_gc::Config::new()
+		.gc(&code)
+		.unwrap_or_else(|e| panic!("Could not compact `{}`: {:?}", bloaty.display(), e));
+	fs::write(compact, compacted)
+		.unwrap_or_else(|e| panic!("Could not write `{}`: {}", compact.display(), e));
+}
+
+/// Tell cargo to rerun the build script when any source file of the wasm project or of one of its
+/// direct path dependencies changes.
+fn rerun_if_changed(project: &Path) {
+	println!("cargo:rerun-if-changed={}", project.join("Cargo.lock").display());
+
+	let manifest = fs::read_to_string(project.join("Cargo.toml"))
+		.expect("the wasm project has a `Cargo.toml`");
+	let dependencies = manifest.lines()
+		.filter_map(|l| l.split("path = \"").nth(1))
+		.filter_map(|l| l.split('"').next())
+		.map(|path| project.join(path));
+
+	for krate in ::std::iter::once(project.to_path_buf()).chain(dependencies) {
+		println!("cargo:rerun-if-changed={}", krate.join("Cargo.toml").display());
+		rerun_if_changed_recursive(&krate.join("src"));
+	}
+}
+
+fn rerun_if_changed_recursive(dir: &Path) {
+	let entries = match fs::read_dir(dir) {
+		Ok(entries) => entries,
+		Err(_) => return,
+	};
+
+	for entry in entries.filter_map(|e| e.ok()) {
+		let path = entry.path();
+		if path.is_dir() {
+			rerun_if_changed_recursive(&path);
+		} else {
+			println!("cargo:rerun-if-changed={}", path.display());
+		}
+	}
+}
@@ -22,7 +22,7 @@ use node_runtime::{ConsensusConfig, CouncilSeatsConfig, CouncilVotingConfig, Dem
 	SessionConfig, StakingConfig, TimestampConfig, BalancesConfig, TreasuryConfig,
 	UpgradeKeyConfig, ContractConfig, GrandpaConfig, Permill, Perbill};
 pub use node_runtime::GenesisConfig;
-use substrate_service;
+use substrate_service::{self, ChainType};
 
 use substrate_keystore::pad_seed;
 
@@ -57,7 +57,7 @@ fn staging_testnet_config_genesis() -> GenesisConfig {
 
 	GenesisConfig {
 		consensus: Some(ConsensusConfig {
-			code: include_bytes!("../../runtime/wasm/target/wasm32-unknown-unknown/release/node_runtime.compact.wasm").to_vec(),    // TODO change
+			code: ::node_executor::WASM_BINARY.to_vec(),    // TODO change
 			authorities: initial_authorities.clone(),
 			_genesis_phantom_data: Default::default(),
 		}),
@@ -66,6 +66,7 @@ fn staging_testnet_config_genesis() -> GenesisConfig {
 			balances: endowed_accounts.iter().map(|&k| (k, 10_000_000 * DOLLARS)).collect(),
 			transaction_base_fee: 1 * CENTS,
 			transaction_byte_fee: 10 * MILLICENTS,
+			transaction_weight_fee: 1 * MILLICENTS,
 			existential_deposit: 1 * DOLLARS,
 			transfer_fee: 1 * CENTS,
 			creation_fee: 1 * CENTS,
@@ -80,21 +81,26 @@ fn staging_testnet_config_genesis() -> GenesisConfig {
 		staking: Some(StakingConfig {
 			current_era: 0,
 			intentions: initial_authorities.iter().cloned().map(Into::into).collect(),
+			stakers: initial_authorities.iter().cloned().map(|x| (x.into(), x.into())).collect(),
 			offline_slash: Perbill::from_billionths(1_000_000),
-			session_reward: Perbill::from_billionths(2_065),
 			current_offline_slash: 0,
-			current_session_reward: 0,
 			validator_count: 7,
 			sessions_per_era: 12,
 			bonding_duration: 1 * DAYS,
 			offline_slash_grace: 4,
 			minimum_validator_count: 4,
+			solution_deposit: 10 * DOLLARS,
+			ideal_stake_ratio: Perbill::from_percent(50),
+			min_inflation: Perbill::from_percent(2),
+			max_inflation: Perbill::from_percent(10),
 			_genesis_phantom_data: Default::default(),
 		}),
 		democracy: Some(DemocracyConfig {
 			launch_period: 5 * MINUTES,    // 1 day per public referendum
 			voting_period: 5 * MINUTES,    // 3 days to discuss & vote on an active referendum
+			minimum_voting_period: 1 * MINUTES,
 			minimum_deposit: 50 * DOLLARS,    // 12000 as the minimum deposit for a referendum
+			preimage_byte_deposit: 1 * CENTS,
 			_genesis_phantom_data: Default::default(),
 		}),
 		council_seats: Some(CouncilSeatsConfig {
@@ -153,6 +159,7 @@ pub fn staging_testnet_config() -> ChainSpec {
 	ChainSpec::from_genesis(
 		"Staging Testnet",
 		"staging_testnet",
+		ChainType::Live,
 		staging_testnet_config_genesis,
 		boot_nodes,
 		Some(STAGING_TELEMETRY_URL.into()),
@@ -188,7 +195,7 @@ pub fn testnet_genesis(
 	});
 	GenesisConfig {
 		consensus: Some(ConsensusConfig {
-			code: include_bytes!("../../runtime/wasm/target/wasm32-unknown-unknown/release/node_runtime.compact.wasm").to_vec(),
+			code: ::node_executor::WASM_BINARY.to_vec(),
 			authorities: initial_authorities.clone(),
 			_genesis_phantom_data: Default::default(),
 		}),
@@ -196,6 +203,7 @@ pub fn testnet_genesis(
 		balances: Some(BalancesConfig {
 			transaction_base_fee: 1,
 			transaction_byte_fee: 0,
+			transaction_weight_fee: 0,
 			existential_deposit: 500,
 			transfer_fee: 0,
 			creation_fee: 0,
@@ -211,21 +219,26 @@ pub fn testnet_genesis(
 		staking: Some(StakingConfig {
 			current_era: 0,
 			intentions: initial_authorities.iter().cloned().map(Into::into).collect(),
+			stakers: initial_authorities.iter().cloned().map(|x| (x.into(), x.into())).collect(),
 			minimum_validator_count: 1,
 			validator_count: 2,
 			sessions_per_era: 5,
 			bonding_duration: 2 * 60 * 12,
 			offline_slash: Perbill::zero(),
-			session_reward: Perbill::zero(),
 			current_offline_slash: 0,
-			current_session_reward: 0,
 			offline_slash_grace: 0,
+			solution_deposit: 10,
+			ideal_stake_ratio: Perbill::from_percent(50),
+			min_inflation: Perbill::from_percent(2),
+			max_inflation: Perbill::from_percent(10),
 			_genesis_phantom_data: Default::default(),
 		}),
 		democracy: Some(DemocracyConfig {
 			launch_period: 9,
 			voting_period: 18,
+			minimum_voting_period: 3,
 			minimum_deposit: 10,
+			preimage_byte_deposit: 0,
 			_genesis_phantom_data: Default::default(),
 		}),
 		council_seats: Some(CouncilSeatsConfig {
@@ -292,7 +305,7 @@ fn development_config_genesis() -> GenesisConfig {
 
 /// Development config (single validator Alice)
 pub fn development_config() -> ChainSpec {
-	ChainSpec::from_genesis("Development", "development", development_config_genesis, vec![], None, None, None, None)
+	ChainSpec::from_genesis("Development", "development", ChainType::Development, development_config_genesis, vec![], None, None, None, None)
 }
 
 fn local_testnet_genesis() -> GenesisConfig {
@@ -308,7 +321,7 @@ fn local_testnet_genesis() -> GenesisConfig {
 
 /// Local testnet config (multivalidator Alice + Bob)
 pub fn local_testnet_config() -> ChainSpec {
-	ChainSpec::from_genesis("Local Testnet", "local_testnet", local_testnet_genesis, vec![], None, None, None, None)
+	ChainSpec::from_genesis("Local Testnet", "local_testnet", ChainType::Local, local_testnet_genesis, vec![], None, None, None, None)
 }
 
 #[cfg(test)]
@@ -325,7 +338,7 @@ mod tests {
 
 	/// Local testnet config (multivalidator Alice + Bob)
 	pub fn integration_test_config() -> ChainSpec {
-		ChainSpec::from_genesis("Integration Test", "test", local_testnet_genesis_instant, vec![], None, None, None, None)
+		ChainSpec::from_genesis("Integration Test", "test", ChainType::Local, local_testnet_genesis_instant, vec![], None, None, None, None)
 	}
 
 	#[test]
@@ -0,0 +1,136 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+
+//! Decoding of the runtime events of the node, for the service to index them.
+
+use codec::{Decode, Encode};
+use node_primitives::AccountId;
+use node_runtime::{Event, Runtime};
+use srml_support::metadata::{DecodeDifferent, RuntimeMetadata};
+use substrate_service::{EventDecoder, EventKeys};
+use system::EventRecord;
+
+/// Decodes the events of the node runtime, taking the names of the modules from the metadata of
+/// the runtime of the block and the accounts from the events of the modules moving balances.
+///
+/// The events are decoded with the types of the native runtime, so only the blocks of a runtime
+/// depositing the same events as the native one, as described by their metadata, are decoded.
+pub struct NodeEventDecoder {
+	/// The encoded metadata of the events of the native runtime.
+	native_events: Vec<u8>,
+}
+
+impl NodeEventDecoder {
+	/// Create a decoder for the native runtime.
+	pub fn new() -> Self {
+		NodeEventDecoder { native_events: Runtime::metadata().outer_event.encode() }
+	}
+}
+
+/// The accounts an event concerns.
+fn accounts(event: Event) -> Vec<AccountId> {
+	match event {
+		Event::balances(event) => match event {
+			balances::RawEvent::NewAccount(who, _, _) => vec![who],
+			balances::RawEvent::ReapedAccount(who) => vec![who],
+			balances::RawEvent::DustLost(who, _) => vec![who],
+			balances::RawEvent::Transfer(from, to, _, _) => vec![from, to],
+		},
+		Event::staking(event) => match event {
+			staking::RawEvent::OfflineWarning(who, _) => vec![who],
+			staking::RawEvent::OfflineSlash(who, _) => vec![who],
+			_ => vec![],
+		},
+		Event::treasury(event) => match event {
+			treasury::RawEvent::Awarded(_, _, who) => vec![who],
+			_ => vec![],
+		},
+		Event::contract(event) => match event {
+			contract::RawEvent::Transfer(from, to, _) => vec![from, to],
+			contract::RawEvent::Created(creator, contract) => vec![creator, contract],
+			_ => vec![],
+		},
+		_ => vec![],
+	}
+}
+
+impl EventDecoder for NodeEventDecoder {
+	fn decode(&self, metadata: &[u8], events: &[u8]) -> Option<Vec<EventKeys>> {
+		let metadata = RuntimeMetadata::decode(&mut &metadata[..])?;
+		if metadata.outer_event.encode() != self.native_events {
+			return None;
+		}
+		// name of each module depositing events, in the order of the variants of `Event`.
+		let modules: Vec<String> = match metadata.outer_event.events {
+			DecodeDifferent::Encode(events) => events.iter().map(|&(name, _)| name.to_owned()).collect(),
+			DecodeDifferent::Decoded(events) => events.into_iter().map(|(name, _)| name).collect(),
+		};
+
+		let records: Vec<EventRecord<Event>> = Decode::decode(&mut &events[..])?;
+		records.into_iter().map(|record| {
+			// the events of the modules are the variants of `Event`, encoded by their index.
+			let module = modules.get(record.event.encode()[0] as usize)?.clone();
+			let accounts = accounts(record.event).iter().map(Encode::encode).collect();
+			Some(EventKeys { module, accounts })
+		}).collect()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn decodes_events_of_the_node_runtime() {
+		let (alice, bob) = (AccountId::from([1; 32]), AccountId::from([2; 32]));
+		let records = vec![
+			EventRecord { phase: system::Phase::ApplyExtrinsic(0), event: Event::system(system::Event::ExtrinsicSuccess) },
+			EventRecord {
+				phase: system::Phase::ApplyExtrinsic(1),
+				event: Event::balances(balances::RawEvent::Transfer(alice, bob, 10, 1)),
+			},
+			EventRecord {
+				phase: system::Phase::Finalization,
+				event: Event::balances(balances::RawEvent::ReapedAccount(alice)),
+			},
+		];
+
+		let keys = NodeEventDecoder::new().decode(&Runtime::metadata().encode(), &records.encode()).unwrap();
+		assert_eq!(keys, vec![
+			EventKeys { module: "system".into(), accounts: vec![] },
+			EventKeys { module: "balances".into(), accounts: vec![alice.encode(), bob.encode()] },
+			EventKeys { module: "balances".into(), accounts: vec![alice.encode()] },
+		]);
+	}
+
+	#[test]
+	fn fails_on_invalid_events() {
+		assert_eq!(NodeEventDecoder::new().decode(&Runtime::metadata().encode(), &[1, 2, 3]), None);
+	}
+
+	#[test]
+	fn fails_on_events_of_another_runtime() {
+		let records = vec![
+			EventRecord { phase: system::Phase::ApplyExtrinsic(0), event: Event::system(system::Event::ExtrinsicSuccess) },
+		];
+		let mut metadata = Runtime::metadata();
+		metadata.outer_event.events = DecodeDifferent::Decoded(vec![("system".into(), vec![])]);
+
+		assert_eq!(NodeEventDecoder::new().decode(&metadata.encode(), &records.encode()), None);
+		assert_eq!(NodeEventDecoder::new().decode(&[1, 2, 3], &records.encode()), None);
+	}
+}
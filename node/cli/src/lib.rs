@@ -37,10 +37,18 @@ extern crate substrate_consensus_common as consensus_common;
 extern crate substrate_client as client;
 extern crate substrate_finality_grandpa as grandpa;
 extern crate node_primitives;
+extern crate sr_primitives as runtime_primitives;
 #[macro_use]
 extern crate substrate_service;
 extern crate node_executor;
 extern crate substrate_keystore;
+extern crate parity_codec as codec;
+extern crate srml_support;
+extern crate srml_system as system;
+extern crate srml_balances as balances;
+extern crate srml_staking as staking;
+extern crate srml_treasury as treasury;
+extern crate srml_contract as contract;
 
 #[macro_use]
 extern crate log;
@@ -49,6 +57,7 @@ extern crate parking_lot;
 
 pub use cli::error;
 pub mod chain_spec;
+mod events;
 mod service;
 mod params;
 
@@ -137,6 +146,37 @@ pub fn run<I, T, E>(args: I, exit: E, version: cli::VersionInfo) -> error::Resul
 		config.roles = ServiceRoles::AUTHORITY;
 	}
 
+	let properties = config.chain_spec.properties();
+	let timing = |name: &str, property: &str| -> error::Result<Option<u64>> {
+		if let Some(value) = matches.value_of(name) {
+			return value.parse().map(Some)
+				.map_err(|_| error::ErrorKind::Input(format!("Invalid value of --{}", name.replace('_', "-"))).into());
+		}
+		match properties.get(property) {
+			None => Ok(None),
+			Some(value) => value.as_u64().map(Some)
+				.ok_or_else(|| error::ErrorKind::Input(format!("Invalid chain spec property {}", property)).into()),
+		}
+	};
+	if let Some(millis) = timing("grandpa_gossip_duration", "grandpaGossipDuration")? {
+		config.custom.grandpa_gossip_duration = std::time::Duration::from_millis(millis);
+	}
+	// out of range timers are saturated, to be refused by the bounds check below.
+	let saturate = |timer: u64| std::cmp::min(timer, u64::from(u32::max_value())) as u32;
+	if let Some(prevote) = timing("grandpa_prevote_timer", "grandpaPrevoteTimer")? {
+		config.custom.grandpa_round_timers.prevote = saturate(prevote);
+	}
+	if let Some(precommit) = timing("grandpa_precommit_timer", "grandpaPrecommitTimer")? {
+		config.custom.grandpa_round_timers.precommit = saturate(precommit);
+	}
+	grandpa::check_timing(config.custom.grandpa_gossip_duration, config.custom.grandpa_round_timers)
+		.map_err(error::ErrorKind::Input)?;
+
+	if let Some(slots) = matches.value_of("max_skipped_empty_slots") {
+		config.custom.max_skipped_empty_slots = slots.parse()
+			.map_err(|_| error::ErrorKind::Input("Invalid value of --max-skipped-empty-slots".into()))?;
+	}
+
 	match cli::execute_default::<service::Factory, _>(spec, exit, &matches, &config)? {
 		cli::Action::ExecutedInternally => (),
 		cli::Action::RunService(exit) => {
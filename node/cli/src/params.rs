@@ -28,6 +28,22 @@ pub struct Params {
 	#[structopt(long = "grandpa-authority-only", help = "Run Node as a GRANDPA authority only, don't as a usual validator, implies --grandpa-authority")]
 	grandpa_authority_only: bool,
 
+	/// Expected time for a GRANDPA message to be gossiped across the network, in milliseconds
+	#[structopt(long = "grandpa-gossip-duration", value_name = "MILLISECONDS", help = "Expected time for a GRANDPA message to be gossiped across the network, in milliseconds. Overrides the chain spec property `grandpaGossipDuration`")]
+	grandpa_gossip_duration: Option<u64>,
+
+	/// Prevote timer of GRANDPA rounds
+	#[structopt(long = "grandpa-prevote-timer", value_name = "GOSSIP_DURATIONS", help = "Time after which GRANDPA prevotes, in gossip durations. Overrides the chain spec property `grandpaPrevoteTimer`")]
+	grandpa_prevote_timer: Option<u32>,
+
+	/// Precommit timer of GRANDPA rounds
+	#[structopt(long = "grandpa-precommit-timer", value_name = "GOSSIP_DURATIONS", help = "Time after which GRANDPA precommits, in gossip durations. Overrides the chain spec property `grandpaPrecommitTimer`")]
+	grandpa_precommit_timer: Option<u32>,
+
+	/// Maximum number of consecutive slots skipped because the block would be empty
+	#[structopt(long = "max-skipped-empty-slots", value_name = "SLOTS", help = "Maximum number of consecutive slots in which an authority skips authoring because the block would be empty. 0 always authors empty blocks")]
+	max_skipped_empty_slots: Option<u64>,
+
 	#[structopt(flatten)]
 	core: CoreParams
 }
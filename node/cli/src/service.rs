@@ -20,23 +20,118 @@
 
 use std::sync::Arc;
 use transaction_pool::{self, txpool::{Pool as TransactionPool}};
-use node_runtime::{GenesisConfig, RuntimeApi};
-use node_primitives::Block;
+use node_runtime::{GenesisConfig, RuntimeApi, Call, GrandpaCall, RawAddress};
+use node_primitives::{AccountId, Block, BlockNumber, Hash, Index, UncheckedExtrinsic};
 use substrate_service::{
 	FactoryFullConfiguration, LightComponents, FullComponents, FullBackend,
-	FullClient, LightClient, LightBackend, FullExecutor, LightExecutor, TaskExecutor
+	FullClient, LightClient, LightBackend, FullExecutor, LightExecutor, TaskExecutor,
+	FinalityProofProvider, EventDecoder,
 };
 use node_executor;
-use consensus::{import_queue, start_aura, Config as AuraConfig, AuraImportQueue, NothingExtra};
+use consensus::{
+	import_queue, start_aura, Config as AuraConfig, AuraImportQueue, NothingExtra, CheckInherents,
+	ProvideImportInherentData,
+};
+use consensus_common::Authorities;
 use consensus_common::offline_tracker::OfflineTracker;
+use substrate_service::consensus::SharedOfflineTracker;
 use primitives::ed25519::Pair;
 use client;
-use std::time::Duration;
+use std::time::{self, Duration};
 use parking_lot::RwLock;
 use grandpa;
+use events::NodeEventDecoder;
+use runtime_primitives::BasicInherentData;
+use runtime_primitives::generic::{BlockId, Era, SignedPayload};
+use primitives::storage::StorageKey;
+use srml_support::storage::generator::{storage_prefix, storage_map_key};
+use codec::{Encode, Decode};
 
 const AURA_SLOT_DURATION: u64 = 6;
 
+/// The inherent data imported blocks are checked against.
+///
+/// The offline validators are taken from the same tracker the blocks authored by this node
+/// report them from.
+pub struct ImportInherentData<C> {
+	client: Arc<C>,
+	offline: SharedOfflineTracker,
+}
+
+impl<C: Authorities<Block> + Send + Sync> ProvideImportInherentData<Block, BasicInherentData> for ImportInherentData<C> {
+	fn inherent_data(&self, parent: &BlockId<Block>) -> BasicInherentData {
+		let timestamp = time::SystemTime::now().duration_since(time::UNIX_EPOCH)
+			.expect("now always later than unix epoch; qed")
+			.as_secs();
+		let offline = match self.client.authorities(parent) {
+			Ok(authorities) => self.offline.read().reports(&authorities[..]),
+			Err(e) => {
+				warn!("Unable to fetch the authorities at {:?} to check offline reports: {:?}", parent, e);
+				Vec::new()
+			},
+		};
+
+		BasicInherentData::new(timestamp, offline)
+	}
+}
+
+/// Reports the GRANDPA equivocations detected by the voter to the runtime, for the offenders to
+/// be slashed. Each report is a `report_misbehavior` extrinsic signed by the local key, which
+/// carries a proof of ownership of the offender's key at the best block.
+pub struct EquivocationReporter {
+	client: Arc<FullClient<Factory>>,
+	transaction_pool: Arc<TransactionPool<transaction_pool::ChainApi<FullClient<Factory>, Block>>>,
+	key: Arc<Pair>,
+}
+
+impl EquivocationReporter {
+	fn submit_report(&self, proof: grandpa::EquivocationProof<Hash, BlockNumber>) -> Result<(), String> {
+		let info = self.client.info().map_err(|e| format!("{:?}", e))?;
+		let at = BlockId::hash(info.chain.best_hash);
+		let key_owner_proof = grandpa::prove_key_ownership(&*self.client, &at, &proof)
+			.map_err(|e| format!("{:?}", e))?
+			.ok_or_else(|| format!("Ownership of the key of {:?} can't be proven", proof.offender))?;
+
+		// the key owner proof is encoded already: append it to the equivocation.
+		let mut report = proof.encode();
+		report.extend(key_owner_proof);
+		let function = Call::Grandpa(GrandpaCall::report_misbehavior(report));
+
+		let signer: AccountId = self.key.public().0.into();
+		let nonce_key = storage_map_key(&storage_prefix(b"System", b"AccountNonce"), &signer);
+		let index = self.client.storage(&at, &StorageKey(nonce_key))
+			.map_err(|e| format!("{:?}", e))?
+			.and_then(|data| Index::decode(&mut &data.0[..]))
+			.unwrap_or(0);
+		let genesis_hash = info.chain.genesis_hash;
+		let spec_version = self.client.runtime_version_at(&at).map_err(|e| format!("{:?}", e))?.spec_version;
+
+		let xt: node_runtime::UncheckedExtrinsic = SignedPayload::new(
+			index,
+			function,
+			Era::immortal(),
+			genesis_hash,
+			(genesis_hash, spec_version),
+		).sign(RawAddress::Id(signer), |payload| self.key.sign(payload).into());
+		let xt = xt.encode();
+		let xt = UncheckedExtrinsic::decode(&mut &xt[..])
+			.expect("an extrinsic is encoded with its length as a vector of bytes is; qed");
+		self.transaction_pool.submit_one(&at, xt).map_err(|e| format!("{:?}", e))?;
+
+		Ok(())
+	}
+}
+
+impl grandpa::ReportEquivocation<Block> for EquivocationReporter {
+	fn report_equivocation(&self, proof: grandpa::EquivocationProof<Hash, BlockNumber>) {
+		let offender = proof.offender.clone();
+		match self.submit_report(proof) {
+			Ok(()) => info!("Reported GRANDPA equivocation of {:?}", offender),
+			Err(e) => warn!("Unable to report GRANDPA equivocation of {:?}: {}", offender, e),
+		}
+	}
+}
+
 construct_simple_protocol! {
 	/// Demo protocol attachment for substrate.
 	pub struct NodeProtocol where Block = Block { }
@@ -48,6 +143,15 @@ pub struct NodeConfig<F: substrate_service::ServiceFactory> {
 	pub grandpa_authority: bool,
 	/// should run as a grandpa authority only, don't validate as usual
 	pub grandpa_authority_only: bool,
+	/// expected time for a grandpa message to be gossiped across the network
+	pub grandpa_gossip_duration: Duration,
+	/// timers of grandpa rounds, in gossip durations
+	pub grandpa_round_timers: grandpa::RoundTimers,
+	/// maximum number of consecutive slots in which authoring is skipped because the block
+	/// would be empty
+	pub max_skipped_empty_slots: u64,
+	/// offline validator tracker shared by block authoring and import
+	pub offline: SharedOfflineTracker,
 	/// grandpa connection to import block
 
 	// FIXME: rather than putting this on the config, let's have an actual intermediate setup state
@@ -60,6 +164,10 @@ impl<F> Default for NodeConfig<F> where F: substrate_service::ServiceFactory {
 		NodeConfig {
 			grandpa_authority: false,
 			grandpa_authority_only: false,
+			grandpa_gossip_duration: Duration::from_secs(4),
+			grandpa_round_timers: Default::default(),
+			max_skipped_empty_slots: 0,
+			offline: Arc::new(RwLock::new(OfflineTracker::new())),
 			grandpa_import_setup: None,
 		}
 	}
@@ -71,9 +179,9 @@ construct_service_factory! {
 		RuntimeApi = RuntimeApi,
 		NetworkProtocol = NodeProtocol { |config| Ok(NodeProtocol::new()) },
 		RuntimeDispatch = node_executor::Executor,
-		FullTransactionPoolApi = transaction_pool::ChainApi<client::Client<FullBackend<Self>, FullExecutor<Self>, Block, RuntimeApi>, Block>
+		FullTransactionPool = TransactionPool<transaction_pool::ChainApi<client::Client<FullBackend<Self>, FullExecutor<Self>, Block, RuntimeApi>, Block>>
 			{ |config, client| Ok(TransactionPool::new(config, transaction_pool::ChainApi::new(client))) },
-		LightTransactionPoolApi = transaction_pool::ChainApi<client::Client<LightBackend<Self>, LightExecutor<Self>, Block, RuntimeApi>, Block>
+		LightTransactionPool = TransactionPool<transaction_pool::ChainApi<client::Client<LightBackend<Self>, LightExecutor<Self>, Block, RuntimeApi>, Block>>
 			{ |config, client| Ok(TransactionPool::new(config, transaction_pool::ChainApi::new(client))) },
 		Genesis = GenesisConfig,
 		Configuration = NodeConfig<Self>,
@@ -85,32 +193,45 @@ construct_service_factory! {
 				let (block_import, link_half) = service.config.custom.grandpa_import_setup.take()
 					.expect("Link Half and Block Import are present for Full Services or setup failed before. qed");
 
-				if service.config.custom.grandpa_authority {
+				let local_key = if service.config.custom.grandpa_authority {
 					info!("Running Grandpa session as Authority {}", key.public());
-					let grandpa_fut = grandpa::run_grandpa(
-						grandpa::Config {
-							gossip_duration: Duration::new(4, 0), // FIXME: make this available through chainspec?
-							local_key: Some(key.clone()),
-							name: Some(service.config.name.clone())
-						},
-						link_half,
-						grandpa::NetworkBridge::new(service.network())
-					)?;
+					Some(key.clone())
+				} else {
+					info!("Observing Grandpa finality without voting");
+					None
+				};
+
+				let grandpa_fut = grandpa::run_grandpa(
+					grandpa::Config {
+						gossip_duration: service.config.custom.grandpa_gossip_duration,
+						round_timers: service.config.custom.grandpa_round_timers,
+						local_key,
+						name: Some(service.config.name.clone()),
+						equivocation_reporter: Some(Arc::new(EquivocationReporter {
+							client: service.client(),
+							transaction_pool: service.transaction_pool(),
+							key: key.clone(),
+						})),
+					},
+					link_half,
+					grandpa::NetworkBridge::new(service.network())
+				)?;
+
+				executor.spawn(grandpa_fut);
 
-					executor.spawn(grandpa_fut);
-				}
 				if !service.config.custom.grandpa_authority_only {
 					info!("Using authority key {}", key.public());
 					let proposer = Arc::new(substrate_service::ProposerFactory {
 						client: service.client(),
 						transaction_pool: service.transaction_pool(),
-						offline: Arc::new(RwLock::new(OfflineTracker::new())),
+						offline: service.config.custom.offline.clone(),
 						force_delay: 0 // FIXME: allow this to be configured https://github.com/paritytech/substrate/issues/1170
 					});
 					executor.spawn(start_aura(
 						AuraConfig {
 							local_key: Some(key),
 							slot_duration: AURA_SLOT_DURATION,
+							max_skipped_empty_slots: service.config.custom.max_skipped_empty_slots,
 						},
 						service.client(),
 						block_import.clone(),
@@ -123,8 +244,16 @@ construct_service_factory! {
 		},
 		LightService = LightComponents<Self>
 			{ |config, executor| <LightComponents<Factory>>::new(config, executor) },
-		FullImportQueue = AuraImportQueue<Self::Block, grandpa::BlockImportForService<Self>, NothingExtra>
+		FullImportQueue = AuraImportQueue<
+			Self::Block,
+			grandpa::BlockImportForService<Self>,
+			CheckInherents<FullClient<Self>, ImportInherentData<FullClient<Self>>>
+		>
 			{ |config: &mut FactoryFullConfiguration<Self> , client: Arc<FullClient<Self>>| {
+				let check_inherents = CheckInherents::new(
+					client.clone(),
+					ImportInherentData { client: client.clone(), offline: config.custom.offline.clone() },
+				);
 				let (block_import, link_half) = grandpa::block_import::<_, _, _, RuntimeApi, FullClient<Self>>(client.clone(), client)?;
 				let block_import = Arc::new(block_import);
 
@@ -133,22 +262,38 @@ construct_service_factory! {
 				Ok(import_queue(
 					AuraConfig {
 						local_key: None,
-						slot_duration: 5
+						slot_duration: 5,
+						max_skipped_empty_slots: config.custom.max_skipped_empty_slots,
 					},
-					block_import,
-					NothingExtra,
+					block_import.clone(),
+					Some(block_import),
+					check_inherents,
 				))
 			}},
 		LightImportQueue = AuraImportQueue<Self::Block, LightClient<Self>, NothingExtra>
 			{ |ref mut config, client| Ok(
 				import_queue(AuraConfig {
 					local_key: None,
-					slot_duration: 5
+					slot_duration: 5,
+					max_skipped_empty_slots: config.custom.max_skipped_empty_slots,
 				},
 				client,
+				None,
 				NothingExtra,
 			))
 			},
+		FinalitySyncState = {
+			|client: &FullClient<Self>| grandpa::finality_sync_state(client).map(Some).map_err(Into::into)
+		},
+		FinalityProofProvider = {
+			|client: Arc<FullClient<Self>>| {
+				let provider = move |begin, end| grandpa::prove_finality(&*client, begin, end);
+				Ok(Some(Arc::new(provider) as Arc<FinalityProofProvider<FullComponents<Self>>>))
+			}
+		},
+		EventDecoder = {
+			|| Some(Arc::new(NodeEventDecoder::new()) as Arc<EventDecoder>)
+		},
 	}
 }
 
@@ -179,7 +324,7 @@ mod tests {
 				handle: dummy_runtime.executor(),
 			};
 			let (proposer, _, _) = proposer_factory.init(&parent_header, &validators, alice.clone()).unwrap();
-			let block = proposer.propose().expect("Error making test block");
+			let block = proposer.propose(::std::time::Instant::now() + ::std::time::Duration::from_secs(60)).wait().expect("Error making test block").block;
 			ImportBlock {
 				origin: BlockOrigin::File,
 				justification: Vec::new(),
@@ -188,16 +333,19 @@ mod tests {
 				body: Some(block.extrinsics),
 				header: block.header,
 				auxiliary: Vec::new(),
+				storage_changes: None,
 			}
 		};
 		let extrinsic_factory = |service: &<Factory as service::ServiceFactory>::FullService| {
-			let payload = (0, Call::Balances(BalancesCall::transfer(RawAddress::Id(bob.public().0.into()), 69.into())), Era::immortal(), service.client().genesis_hash());
-			let signature = alice.sign(&payload.encode()).into();
-			let id = alice.public().0.into();
-			let xt = UncheckedExtrinsic {
-				signature: Some((RawAddress::Id(id), signature, payload.0, Era::immortal())),
-				function: payload.1,
-			}.encode();
+			let genesis_hash = service.client().genesis_hash();
+			let xt: node_runtime::UncheckedExtrinsic = SignedPayload::new(
+				0,
+				Call::Balances(BalancesCall::transfer(RawAddress::Id(bob.public().0.into()), 69.into())),
+				Era::immortal(),
+				genesis_hash,
+				(genesis_hash, node_runtime::VERSION.spec_version),
+			).sign(RawAddress::Id(alice.public().0.into()), |payload| alice.sign(payload).into());
+			let xt = xt.encode();
 			let v: Vec<u8> = Decode::decode(&mut xt.as_slice()).unwrap();
 			OpaqueExtrinsic(v)
 		};
@@ -46,7 +46,11 @@ extern crate node_runtime;
 #[cfg(test)] extern crate wabt;
 
 pub use substrate_executor::NativeExecutor;
-native_executor_instance!(pub Executor, node_runtime::api::dispatch, node_runtime::native_version, include_bytes!("../../runtime/wasm/target/wasm32-unknown-unknown/release/node_runtime.compact.wasm"));
+
+/// The compact wasm blob of the node runtime, built from the same sources as the native runtime.
+pub const WASM_BINARY: &[u8] = include_bytes!(env!("WASM_BINARY"));
+
+native_executor_instance!(pub Executor, node_runtime::api::dispatch, node_runtime::native_version, WASM_BINARY);
 
 #[cfg(test)]
 mod tests {
@@ -56,22 +60,23 @@ mod tests {
 	use codec::{Encode, Decode, Joiner};
 	use keyring::Keyring;
 	use runtime_support::{Hashable, StorageValue, StorageMap};
+	use runtime_support::traits::Filter;
 	use state_machine::{CodeExecutor, Externalities, TestExternalities};
-	use primitives::{twox_128, Blake2Hasher, ChangesTrieConfiguration,
+	use primitives::{Blake2Hasher, ChangesTrieConfiguration,
 		ed25519::{Public, Pair}};
 	use node_primitives::{Hash, BlockNumber, AccountId};
-	use runtime_primitives::traits::{Header as HeaderT, Digest as DigestT};
-	use runtime_primitives::{generic, generic::Era, ApplyOutcome, ApplyError, ApplyResult, Perbill};
-	use {balances, staking, session, system, consensus, timestamp, treasury, contract};
+	use runtime_primitives::traits::{Header as HeaderT, Digest as DigestT, OnFinalise};
+	use runtime_primitives::{generic, generic::{Era, SignedPayload}, ApplyOutcome, ApplyError, ApplyResult, Perbill};
+	use {balances, staking, session, system, consensus, timestamp, treasury, contract, grandpa};
 	use contract::ContractAddressFor;
 	use system::{EventRecord, Phase};
 	use node_runtime::{Header, Block, UncheckedExtrinsic, CheckedExtrinsic, Call, Runtime, Balances,
 		BuildStorage, GenesisConfig, BalancesConfig, SessionConfig, StakingConfig, System,
-		SystemConfig, GrandpaConfig, Event, Log};
+		SystemConfig, GrandpaConfig, Event, Log, BaseFilter, VERSION};
 	use wabt;
 
-	const BLOATY_CODE: &[u8] = include_bytes!("../../runtime/wasm/target/wasm32-unknown-unknown/release/node_runtime.wasm");
-	const COMPACT_CODE: &[u8] = include_bytes!("../../runtime/wasm/target/wasm32-unknown-unknown/release/node_runtime.compact.wasm");
+	const BLOATY_CODE: &[u8] = include_bytes!(env!("WASM_BINARY_BLOATY"));
+	const COMPACT_CODE: &[u8] = ::WASM_BINARY;
 	const GENESIS_HASH: [u8; 32] = [69u8; 32];
 
 	fn alice() -> AccountId {
@@ -90,13 +95,9 @@ mod tests {
 		match xt.signed {
 			Some((signed, index)) => {
 				let era = Era::mortal(256, 0);
-				let payload = (index, xt.function, era, GENESIS_HASH);
+				let payload = SignedPayload::new(index, xt.function, era, GENESIS_HASH, (GENESIS_HASH, VERSION.spec_version));
 				let pair = Pair::from(Keyring::from_public(Public::from_raw(signed.clone().into())).unwrap());
-				let signature = pair.sign(&payload.encode()).into();
-				UncheckedExtrinsic {
-					signature: Some((balances::address::Address::Id(signed), signature, payload.0, era)),
-					function: payload.1,
-				}
+				payload.sign(balances::address::Address::Id(signed), |payload| pair.sign(payload).into())
 			}
 			None => UncheckedExtrinsic {
 				signature: None,
@@ -123,15 +124,15 @@ mod tests {
 	#[test]
 	fn panic_execution_with_foreign_code_gives_error() {
 		let mut t = TestExternalities::<Blake2Hasher>::new(map![
-			twox_128(&<balances::FreeBalance<Runtime>>::key_for(alice())).to_vec() => vec![69u8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
-			twox_128(<balances::TotalIssuance<Runtime>>::key()).to_vec() => vec![69u8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
-			twox_128(<balances::TransactionBaseFee<Runtime>>::key()).to_vec() => vec![70u8; 16],
-			twox_128(<balances::TransactionByteFee<Runtime>>::key()).to_vec() => vec![0u8; 16],
-			twox_128(<balances::ExistentialDeposit<Runtime>>::key()).to_vec() => vec![0u8; 16],
-			twox_128(<balances::CreationFee<Runtime>>::key()).to_vec() => vec![0u8; 16],
-			twox_128(<balances::TransferFee<Runtime>>::key()).to_vec() => vec![0u8; 16],
-			twox_128(<balances::NextEnumSet<Runtime>>::key()).to_vec() => vec![0u8; 16],
-			twox_128(&<system::BlockHash<Runtime>>::key_for(0)).to_vec() => vec![0u8; 32]
+			<balances::FreeBalance<Runtime>>::key_for(alice()) => vec![69u8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+			<balances::TotalIssuance<Runtime>>::key() => vec![69u8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+			<balances::TransactionBaseFee<Runtime>>::key() => vec![70u8; 16],
+			<balances::TransactionByteFee<Runtime>>::key() => vec![0u8; 16],
+			<balances::ExistentialDeposit<Runtime>>::key() => vec![0u8; 16],
+			<balances::CreationFee<Runtime>>::key() => vec![0u8; 16],
+			<balances::TransferFee<Runtime>>::key() => vec![0u8; 16],
+			<balances::NextEnumSet<Runtime>>::key() => vec![0u8; 16],
+			<system::BlockHash<Runtime>>::key_for(0) => vec![0u8; 32]
 		]);
 
 		let r = executor().call(&mut t, 8, BLOATY_CODE, "initialise_block", &vec![].and(&from_block_number(1u64)), true).0;
@@ -144,15 +145,15 @@ mod tests {
 	#[test]
 	fn bad_extrinsic_with_native_equivalent_code_gives_error() {
 		let mut t = TestExternalities::<Blake2Hasher>::new(map![
-			twox_128(&<balances::FreeBalance<Runtime>>::key_for(alice())).to_vec() => vec![69u8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
-			twox_128(<balances::TotalIssuance<Runtime>>::key()).to_vec() => vec![69u8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
-			twox_128(<balances::TransactionBaseFee<Runtime>>::key()).to_vec() => vec![70u8; 16],
-			twox_128(<balances::TransactionByteFee<Runtime>>::key()).to_vec() => vec![0u8; 16],
-			twox_128(<balances::ExistentialDeposit<Runtime>>::key()).to_vec() => vec![0u8; 16],
-			twox_128(<balances::CreationFee<Runtime>>::key()).to_vec() => vec![0u8; 16],
-			twox_128(<balances::TransferFee<Runtime>>::key()).to_vec() => vec![0u8; 16],
-			twox_128(<balances::NextEnumSet<Runtime>>::key()).to_vec() => vec![0u8; 16],
-			twox_128(&<system::BlockHash<Runtime>>::key_for(0)).to_vec() => vec![0u8; 32]
+			<balances::FreeBalance<Runtime>>::key_for(alice()) => vec![69u8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+			<balances::TotalIssuance<Runtime>>::key() => vec![69u8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+			<balances::TransactionBaseFee<Runtime>>::key() => vec![70u8; 16],
+			<balances::TransactionByteFee<Runtime>>::key() => vec![0u8; 16],
+			<balances::ExistentialDeposit<Runtime>>::key() => vec![0u8; 16],
+			<balances::CreationFee<Runtime>>::key() => vec![0u8; 16],
+			<balances::TransferFee<Runtime>>::key() => vec![0u8; 16],
+			<balances::NextEnumSet<Runtime>>::key() => vec![0u8; 16],
+			<system::BlockHash<Runtime>>::key_for(0) => vec![0u8; 32]
 		]);
 
 		let r = executor().call(&mut t, 8, COMPACT_CODE, "initialise_block", &vec![].and(&from_block_number(1u64)), true).0;
@@ -165,15 +166,15 @@ mod tests {
 	#[test]
 	fn successful_execution_with_native_equivalent_code_gives_ok() {
 		let mut t = TestExternalities::<Blake2Hasher>::new(map![
-			twox_128(&<balances::FreeBalance<Runtime>>::key_for(alice())).to_vec() => vec![111u8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
-			twox_128(<balances::TotalIssuance<Runtime>>::key()).to_vec() => vec![111u8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
-			twox_128(<balances::TransactionBaseFee<Runtime>>::key()).to_vec() => vec![0u8; 16],
-			twox_128(<balances::TransactionByteFee<Runtime>>::key()).to_vec() => vec![0u8; 16],
-			twox_128(<balances::ExistentialDeposit<Runtime>>::key()).to_vec() => vec![0u8; 16],
-			twox_128(<balances::CreationFee<Runtime>>::key()).to_vec() => vec![0u8; 16],
-			twox_128(<balances::TransferFee<Runtime>>::key()).to_vec() => vec![0u8; 16],
-			twox_128(<balances::NextEnumSet<Runtime>>::key()).to_vec() => vec![0u8; 16],
-			twox_128(&<system::BlockHash<Runtime>>::key_for(0)).to_vec() => vec![0u8; 32]
+			<balances::FreeBalance<Runtime>>::key_for(alice()) => vec![111u8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+			<balances::TotalIssuance<Runtime>>::key() => vec![111u8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+			<balances::TransactionBaseFee<Runtime>>::key() => vec![0u8; 16],
+			<balances::TransactionByteFee<Runtime>>::key() => vec![0u8; 16],
+			<balances::ExistentialDeposit<Runtime>>::key() => vec![0u8; 16],
+			<balances::CreationFee<Runtime>>::key() => vec![0u8; 16],
+			<balances::TransferFee<Runtime>>::key() => vec![0u8; 16],
+			<balances::NextEnumSet<Runtime>>::key() => vec![0u8; 16],
+			<system::BlockHash<Runtime>>::key_for(0) => vec![0u8; 32]
 		]);
 
 		let r = executor().call(&mut t, 8, COMPACT_CODE, "initialise_block", &vec![].and(&from_block_number(1u64)), true).0;
@@ -190,15 +191,15 @@ mod tests {
 	#[test]
 	fn successful_execution_with_foreign_code_gives_ok() {
 		let mut t = TestExternalities::<Blake2Hasher>::new(map![
-			twox_128(&<balances::FreeBalance<Runtime>>::key_for(alice())).to_vec() => vec![111u8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
-			twox_128(<balances::TotalIssuance<Runtime>>::key()).to_vec() => vec![111u8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
-			twox_128(<balances::TransactionBaseFee<Runtime>>::key()).to_vec() => vec![0u8; 16],
-			twox_128(<balances::TransactionByteFee<Runtime>>::key()).to_vec() => vec![0u8; 16],
-			twox_128(<balances::ExistentialDeposit<Runtime>>::key()).to_vec() => vec![0u8; 16],
-			twox_128(<balances::CreationFee<Runtime>>::key()).to_vec() => vec![0u8; 16],
-			twox_128(<balances::TransferFee<Runtime>>::key()).to_vec() => vec![0u8; 16],
-			twox_128(<balances::NextEnumSet<Runtime>>::key()).to_vec() => vec![0u8; 16],
-			twox_128(&<system::BlockHash<Runtime>>::key_for(0)).to_vec() => vec![0u8; 32]
+			<balances::FreeBalance<Runtime>>::key_for(alice()) => vec![111u8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+			<balances::TotalIssuance<Runtime>>::key() => vec![111u8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+			<balances::TransactionBaseFee<Runtime>>::key() => vec![0u8; 16],
+			<balances::TransactionByteFee<Runtime>>::key() => vec![0u8; 16],
+			<balances::ExistentialDeposit<Runtime>>::key() => vec![0u8; 16],
+			<balances::CreationFee<Runtime>>::key() => vec![0u8; 16],
+			<balances::TransferFee<Runtime>>::key() => vec![0u8; 16],
+			<balances::NextEnumSet<Runtime>>::key() => vec![0u8; 16],
+			<system::BlockHash<Runtime>>::key_for(0) => vec![0u8; 32]
 		]);
 
 		let r = executor().call(&mut t, 8, BLOATY_CODE, "initialise_block", &vec![].and(&from_block_number(1u64)), true).0;
@@ -231,6 +232,7 @@ mod tests {
 				],
 				transaction_base_fee: 1,
 				transaction_byte_fee: 0,
+				transaction_weight_fee: 0,
 				existential_deposit: 0,
 				transfer_fee: 0,
 				creation_fee: 0,
@@ -246,14 +248,21 @@ mod tests {
 				sessions_per_era: 2,
 				current_era: 0,
 				intentions: vec![alice(), bob(), Charlie.to_raw_public().into()],
+				stakers: vec![
+					(alice(), alice()),
+					(bob(), bob()),
+					(Charlie.to_raw_public().into(), Charlie.to_raw_public().into()),
+				],
 				validator_count: 3,
 				minimum_validator_count: 0,
 				bonding_duration: 0,
 				offline_slash: Perbill::zero(),
-				session_reward: Perbill::zero(),
 				current_offline_slash: 0,
-				current_session_reward: 0,
 				offline_slash_grace: 0,
+				solution_deposit: 0,
+				ideal_stake_ratio: Perbill::zero(),
+				min_inflation: Perbill::zero(),
+				max_inflation: Perbill::zero(),
 				_genesis_phantom_data: Default::default(),
 			}),
 			democracy: Some(Default::default()),
@@ -477,10 +486,6 @@ mod tests {
 					phase: Phase::Finalization,
 					event: Event::session(session::RawEvent::NewSession(1))
 				},
-				EventRecord {
-					phase: Phase::Finalization,
-					event: Event::staking(staking::RawEvent::Reward(0))
-				},
 				EventRecord {
 					phase: Phase::Finalization,
 					event: Event::grandpa(::grandpa::RawEvent::NewAuthorities(vec![
@@ -688,7 +693,8 @@ mod tests {
 
 		runtime_io::with_externalities(&mut t, || {
 			// Verify that the contract constructor worked well and code of TRANSFER contract is actually deployed.
-			assert_eq!(&contract::CodeOf::<Runtime>::get(addr), &code_transfer);
+			let code_hash = contract::Module::<Runtime>::code_hash_of(addr);
+			assert_eq!(code_hash.and_then(contract::Module::<Runtime>::pristine_code), Some(code_transfer));
 		});
 	}
 
@@ -719,18 +725,18 @@ mod tests {
 	#[test]
 	fn panic_execution_gives_error() {
 		let mut t = TestExternalities::<Blake2Hasher>::new(map![
-			twox_128(&<balances::FreeBalance<Runtime>>::key_for(alice())).to_vec() => vec![69u8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
-			twox_128(<balances::TotalIssuance<Runtime>>::key()).to_vec() => vec![69u8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
-			twox_128(<balances::TransactionBaseFee<Runtime>>::key()).to_vec() => vec![70u8; 16],
-			twox_128(<balances::TransactionByteFee<Runtime>>::key()).to_vec() => vec![0u8; 16],
-			twox_128(<balances::ExistentialDeposit<Runtime>>::key()).to_vec() => vec![0u8; 16],
-			twox_128(<balances::CreationFee<Runtime>>::key()).to_vec() => vec![0u8; 16],
-			twox_128(<balances::TransferFee<Runtime>>::key()).to_vec() => vec![0u8; 16],
-			twox_128(<balances::NextEnumSet<Runtime>>::key()).to_vec() => vec![0u8; 16],
-			twox_128(&<system::BlockHash<Runtime>>::key_for(0)).to_vec() => vec![0u8; 32]
+			<balances::FreeBalance<Runtime>>::key_for(alice()) => vec![69u8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+			<balances::TotalIssuance<Runtime>>::key() => vec![69u8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+			<balances::TransactionBaseFee<Runtime>>::key() => vec![70u8; 16],
+			<balances::TransactionByteFee<Runtime>>::key() => vec![0u8; 16],
+			<balances::ExistentialDeposit<Runtime>>::key() => vec![0u8; 16],
+			<balances::CreationFee<Runtime>>::key() => vec![0u8; 16],
+			<balances::TransferFee<Runtime>>::key() => vec![0u8; 16],
+			<balances::NextEnumSet<Runtime>>::key() => vec![0u8; 16],
+			<system::BlockHash<Runtime>>::key_for(0) => vec![0u8; 32]
 		]);
 
-		let foreign_code = include_bytes!("../../runtime/wasm/target/wasm32-unknown-unknown/release/node_runtime.wasm");
+		let foreign_code = include_bytes!(env!("WASM_BINARY_BLOATY"));
 		let r = WasmExecutor::new().call(&mut t, 8, &foreign_code[..], "initialise_block", &vec![].and(&from_block_number(1u64)));
 		assert!(r.is_ok());
 		let r = WasmExecutor::new().call(&mut t, 8, &foreign_code[..], "apply_extrinsic", &vec![].and(&xt())).unwrap();
@@ -741,18 +747,18 @@ mod tests {
 	#[test]
 	fn successful_execution_gives_ok() {
 		let mut t = TestExternalities::<Blake2Hasher>::new(map![
-			twox_128(&<balances::FreeBalance<Runtime>>::key_for(alice())).to_vec() => vec![111u8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
-			twox_128(<balances::TotalIssuance<Runtime>>::key()).to_vec() => vec![111u8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
-			twox_128(<balances::TransactionBaseFee<Runtime>>::key()).to_vec() => vec![0u8; 16],
-			twox_128(<balances::TransactionByteFee<Runtime>>::key()).to_vec() => vec![0u8; 16],
-			twox_128(<balances::ExistentialDeposit<Runtime>>::key()).to_vec() => vec![0u8; 16],
-			twox_128(<balances::CreationFee<Runtime>>::key()).to_vec() => vec![0u8; 16],
-			twox_128(<balances::TransferFee<Runtime>>::key()).to_vec() => vec![0u8; 16],
-			twox_128(<balances::NextEnumSet<Runtime>>::key()).to_vec() => vec![0u8; 16],
-			twox_128(&<system::BlockHash<Runtime>>::key_for(0)).to_vec() => vec![0u8; 32]
+			<balances::FreeBalance<Runtime>>::key_for(alice()) => vec![111u8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+			<balances::TotalIssuance<Runtime>>::key() => vec![111u8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+			<balances::TransactionBaseFee<Runtime>>::key() => vec![0u8; 16],
+			<balances::TransactionByteFee<Runtime>>::key() => vec![0u8; 16],
+			<balances::ExistentialDeposit<Runtime>>::key() => vec![0u8; 16],
+			<balances::CreationFee<Runtime>>::key() => vec![0u8; 16],
+			<balances::TransferFee<Runtime>>::key() => vec![0u8; 16],
+			<balances::NextEnumSet<Runtime>>::key() => vec![0u8; 16],
+			<system::BlockHash<Runtime>>::key_for(0) => vec![0u8; 32]
 		]);
 
-		let foreign_code = include_bytes!("../../runtime/wasm/target/wasm32-unknown-unknown/release/node_runtime.compact.wasm");
+		let foreign_code = ::WASM_BINARY;
 		let r = WasmExecutor::new().call(&mut t, 8, &foreign_code[..], "initialise_block", &vec![].and(&from_block_number(1u64)));
 		assert!(r.is_ok());
 		let r = WasmExecutor::new().call(&mut t, 8, &foreign_code[..], "apply_extrinsic", &vec![].and(&xt())).unwrap();
@@ -765,6 +771,23 @@ mod tests {
 		});
 	}
 
+	#[test]
+	fn only_governance_calls_and_reports_are_allowed_while_calls_are_paused() {
+		let transfer = Call::Balances(balances::Call::transfer::<Runtime>(bob().into(), 69.into()));
+		let report = Call::Grandpa(grandpa::Call::report_misbehavior::<Runtime>(vec![]));
+		let resume = Call::System(system::Call::set_calls_paused::<Runtime>(false));
+
+		runtime_io::with_externalities(&mut new_test_ext(false), || {
+			<system::CallsPaused<Runtime>>::put(true);
+			assert!(!BaseFilter::filter(&transfer));
+			assert!(BaseFilter::filter(&report));
+			assert!(BaseFilter::filter(&resume));
+
+			<system::CallsPaused<Runtime>>::put(false);
+			assert!(BaseFilter::filter(&transfer));
+		});
+	}
+
 	#[test]
 	fn full_native_block_import_works_with_changes_trie() {
 		let mut t = new_test_ext(true);
@@ -781,6 +804,64 @@ mod tests {
 		assert!(t.storage_changes_root(Default::default(), 0).is_some());
 	}
 
+	#[test]
+	fn storage_is_moved_from_unprefixed_keys_on_the_first_block() {
+		// keys of `decl_storage!` items before they were namespaced per module.
+		let old_key = |item: &[u8], key: &[u8]| runtime_io::twox_128(&[item, key].concat()).to_vec();
+		let parent_hash = Hash::from([5u8; 32]);
+		let mut t = TestExternalities::<Blake2Hasher>::new(map![
+			old_key(b"System Number", &[]) => 5u64.encode(),
+			old_key(b"System BlockHash", &4u64.encode()) => parent_hash.encode(),
+			old_key(b"System AccountNonce", &alice().encode()) => 3u64.encode(),
+			old_key(b"Balances NextEnumSet", &[]) => 0u32.encode(),
+			old_key(b"Balances EnumSet", &0u32.encode()) => vec![alice(), bob()].encode(),
+			old_key(b"Balances FreeBalance", &alice().encode()) => 111u128.encode(),
+			old_key(b"Balances FreeBalance", &bob().encode()) => 69u128.encode(),
+			old_key(b"Balances TotalIssuance", &[]) => 180u128.encode(),
+			old_key(b"Contract CodeOf", &alice().encode()) => vec![1u8, 2, 3].encode(),
+			old_key(b"Staking SessionReward", &[]) => Perbill::zero().encode()
+		]);
+
+		executor().call(&mut t, 8, COMPACT_CODE, "initialise_block", &vec![].and(&from_block_number(6u64)), true).0.unwrap();
+
+		runtime_io::with_externalities(&mut t, || {
+			assert!(System::storage_keys_migrated());
+			assert_eq!(System::block_hash(4), parent_hash);
+			assert_eq!(System::account_nonce(alice()), 3);
+			assert_eq!(Balances::enum_set(0), vec![alice(), bob()]);
+			assert_eq!(Balances::free_balance(alice()), 111);
+			assert_eq!(Balances::free_balance(bob()), 69);
+			assert_eq!(Balances::total_issuance(), 180);
+			assert!(runtime_io::storage(&old_key(b"Balances FreeBalance", &alice().encode())).is_none());
+			assert!(runtime_io::storage(&old_key(b"Staking SessionReward", &[])).is_none());
+
+			// legacy items read by the migrations of the modules are moved as well.
+			let code_of = runtime_support::storage::generator::storage_prefix(b"Contract", b"CodeOf");
+			assert_eq!(
+				runtime_io::storage(&runtime_support::storage::generator::storage_map_key(&code_of, &alice())),
+				Some(vec![1u8, 2, 3].encode())
+			);
+			assert!(!Balances::providers_migrated());
+			<Balances as OnFinalise<BlockNumber>>::on_finalise(6);
+			assert_eq!(System::providers(alice()), 1);
+			assert_eq!(System::providers(bob()), 1);
+		});
+	}
+
+	#[test]
+	fn storage_is_not_moved_again() {
+		let mut t = new_test_ext(false);
+		let old_key = runtime_io::twox_128(b"Balances TotalIssuance").to_vec();
+		t.set_storage(old_key.clone(), 1u128.encode());
+
+		executor().call(&mut t, 8, COMPACT_CODE, "initialise_block", &vec![].and(&from_block_number(1u64)), true).0.unwrap();
+
+		runtime_io::with_externalities(&mut t, || {
+			assert_eq!(Balances::total_issuance(), 100_000_111);
+			assert!(runtime_io::storage(&old_key).is_some());
+		});
+	}
+
 	#[cfg(feature = "benchmarks")]
 	mod benches {
 		use super::*;
@@ -59,11 +59,9 @@ use substrate_primitives::u32_trait::{_2, _4};
 use node_primitives::{
 	AccountId, AccountIndex, Balance, BlockNumber, Hash, Index, SessionKey, Signature
 };
-use grandpa::fg_primitives::{self, ScheduledChange, id::*};
-use client::{
-	block_builder::api as block_builder_api, runtime_api::{self as client_api, id::*}
-};
-use runtime_primitives::{ApplyResult, CheckInherentError, BasicInherentData};
+use grandpa::fg_primitives::{self, ScheduledChange};
+use client::{block_builder::api as block_builder_api, runtime_api as client_api};
+use runtime_primitives::{ApplyResult, CheckInherentsResult, BasicInherentData};
 use runtime_primitives::transaction_validity::TransactionValidity;
 use runtime_primitives::generic;
 use runtime_primitives::traits::{
@@ -73,6 +71,7 @@ use version::RuntimeVersion;
 use council::{motions as council_motions, voting as council_voting};
 #[cfg(feature = "std")]
 use council::seats as council_seats;
+use session::historical as session_historical;
 #[cfg(any(feature = "std", test))]
 use version::NativeVersion;
 use substrate_primitives::OpaqueMetadata;
@@ -82,26 +81,27 @@ pub use runtime_primitives::BuildStorage;
 pub use consensus::Call as ConsensusCall;
 pub use timestamp::Call as TimestampCall;
 pub use balances::Call as BalancesCall;
+pub use grandpa::Call as GrandpaCall;
 pub use runtime_primitives::{Permill, Perbill};
 pub use timestamp::BlockPeriod;
 pub use srml_support::{StorageValue, RuntimeMetadata};
+use srml_support::traits::Filter;
+use system::{DispatchClass, WeighCall};
+
+pub mod migration;
 
 const TIMESTAMP_SET_POSITION: u32 = 0;
 const NOTE_OFFLINE_POSITION: u32 = 1;
 
 /// Runtime version.
+#[runtime_version]
 pub const VERSION: RuntimeVersion = RuntimeVersion {
 	spec_name: ver_str!("node"),
 	impl_name: ver_str!("substrate-node"),
 	authoring_version: 1,
-	spec_version: 1,
+	spec_version: 6,
 	impl_version: 0,
-	apis: apis_vec!([
-		(BLOCK_BUILDER, 1),
-		(TAGGED_TRANSACTION_QUEUE, 1),
-		(METADATA, 1),
-		(GRANDPA_API, 1),
-	]),
+	apis: RUNTIME_API_VERSIONS,
 };
 
 /// Native version.
@@ -114,6 +114,7 @@ pub fn native_version() -> NativeVersion {
 }
 
 impl system::Trait for Runtime {
+	const SPEC_VERSION: u32 = VERSION.spec_version;
 	type Origin = Origin;
 	type Index = Index;
 	type BlockNumber = BlockNumber;
@@ -124,6 +125,55 @@ impl system::Trait for Runtime {
 	type Header = generic::Header<BlockNumber, BlakeTwo256, Log>;
 	type Event = Event;
 	type Log = Log;
+	type BaseCallFilter = BaseFilter;
+	type CallWeigher = Weigher;
+}
+
+/// Disables all calls while calls are paused, except for the inherents, the reports of
+/// misbehaving authorities and the calls of the governance modules, so that calls can be resumed
+/// again.
+pub struct BaseFilter;
+impl Filter<Call> for BaseFilter {
+	fn filter(call: &Call) -> bool {
+		if !System::calls_paused() {
+			return true;
+		}
+		match *call {
+			Call::Timestamp(TimestampCall::set(_)) | Call::Consensus(ConsensusCall::note_offline(_)) => true,
+			Call::Grandpa(GrandpaCall::report_misbehavior(_)) => true,
+			Call::System(system::Call::set_calls_paused(_)) => true,
+			Call::Democracy(_) | Call::Council(_) | Call::CouncilVoting(_) | Call::CouncilMotions(_) |
+				Call::UpgradeKey(_) => true,
+			_ => false,
+		}
+	}
+}
+
+/// The weight of the calls that aren't weighed individually.
+const CALL_WEIGHT: u32 = 10;
+
+/// Weighs the calls of the node in roughly the number of storage items they read and write, the
+/// bulk of their cost. The inherents, the reports of misbehaviour and the calls of the modules
+/// maintaining the chain are operational.
+pub struct Weigher;
+impl WeighCall<Call> for Weigher {
+	fn weigh(call: &Call) -> u32 {
+		match *call {
+			Call::Balances(BalancesCall::transfer(..)) => balances::TRANSFER_WEIGHT,
+			// the ownership proof of the offender's key is checked against the historical sessions.
+			Call::Grandpa(GrandpaCall::report_misbehavior(_)) => 2 * CALL_WEIGHT,
+			_ => CALL_WEIGHT,
+		}
+	}
+
+	fn dispatch_class(call: &Call) -> DispatchClass {
+		match *call {
+			Call::Consensus(ConsensusCall::remark(_)) => DispatchClass::Normal,
+			Call::System(_) | Call::Timestamp(_) | Call::Consensus(_) | Call::Grandpa(_) |
+				Call::UpgradeKey(_) => DispatchClass::Operational,
+			_ => DispatchClass::Normal,
+		}
+	}
 }
 
 impl balances::Trait for Runtime {
@@ -131,6 +181,8 @@ impl balances::Trait for Runtime {
 	type AccountIndex = AccountIndex;
 	type OnFreeBalanceZero = (Staking, Contract);
 	type EnsureAccountLiquid = Staking;
+	type DustRemoval = Treasury;
+	type OnChargeTransaction = Balances;
 	type Event = Event;
 }
 
@@ -156,17 +208,41 @@ impl Convert<AccountId, SessionKey> for SessionKeyConversion {
 
 impl session::Trait for Runtime {
 	type ConvertAccountIdToSessionKey = SessionKeyConversion;
-	type OnSessionChange = (Staking, grandpa::SyncedAuthorities<Runtime>);
+	type OnSessionChange = (Staking, grandpa::SyncedAuthorities<Runtime>, SessionHistorical);
 	type Event = Event;
 }
 
+impl session_historical::Trait for Runtime {
+	const HISTORY_DEPTH: u32 = 28;
+}
+
+/// Finds the author of the current block from its aura slot, which is the timestamp divided by
+/// the block period, as the validator of the session whose turn it is.
+pub struct AuraAuthor;
+impl staking::FindAuthor<AccountId> for AuraAuthor {
+	fn find_author() -> Option<AccountId> {
+		let validators = Session::validators();
+		let block_period = Timestamp::block_period();
+		if validators.is_empty() || block_period == 0 {
+			return None;
+		}
+		let slot = Timestamp::now() / block_period;
+		validators.get((slot % validators.len() as u64) as usize).cloned()
+	}
+}
+
 impl staking::Trait for Runtime {
 	type OnRewardMinted = Treasury;
+	type EraPayout = staking::InflationCurve<Runtime>;
+	type RewardRemainder = Treasury;
+	type FindAuthor = AuraAuthor;
 	type Event = Event;
 }
 
 impl democracy::Trait for Runtime {
 	type Proposal = Call;
+	type FastTrackOrigin = council_motions::EnsureMembers<_4>;
+	type CancellationOrigin = council_motions::EnsureRootOrMajority<Runtime>;
 	type Event = Event;
 }
 
@@ -204,6 +280,7 @@ impl grandpa::Trait for Runtime {
 	type SessionKey = SessionKey;
 	type Log = Log;
 	type Event = Event;
+	type KeyOwnerSystem = SessionHistorical;
 }
 
 construct_runtime!(
@@ -217,6 +294,7 @@ construct_runtime!(
 		Consensus: consensus::{Module, Call, Storage, Config<T>, Log(AuthoritiesChange), Inherent},
 		Balances: balances,
 		Session: session,
+		SessionHistorical: session_historical::{Module, Storage},
 		Staking: staking,
 		Democracy: democracy,
 		Council: council::{Module, Call, Storage, Event<T>},
@@ -261,10 +339,12 @@ impl_runtime_apis! {
 		}
 
 		fn execute_block(block: Block) {
+			migration::migrate_storage_keys();
 			Executive::execute_block(block)
 		}
 
 		fn initialise_block(header: <Block as BlockT>::Header) {
+			migration::migrate_storage_keys();
 			Executive::initialise_block(&header)
 		}
 	}
@@ -303,7 +383,7 @@ impl_runtime_apis! {
 			inherent.into_iter().map(|v| v.1).collect()
 		}
 
-		fn check_inherents(block: Block, data: BasicInherentData) -> Result<(), CheckInherentError> {
+		fn check_inherents(block: Block, data: BasicInherentData) -> CheckInherentsResult {
 			Runtime::check_inherents(block, data)
 		}
 
@@ -333,8 +413,34 @@ impl_runtime_apis! {
 			None
 		}
 
+		fn grandpa_forced_change(digest: DigestFor<Block>)
+			-> Option<(NumberFor<Block>, ScheduledChange<NumberFor<Block>>)>
+		{
+			for log in digest.logs.iter().filter_map(|l| match l {
+				Log(InternalLog::grandpa(grandpa_signal)) => Some(grandpa_signal),
+				_ => None
+			}) {
+				if let Some(change) = Grandpa::scrape_digest_forced_change(log) {
+					return Some(change);
+				}
+			}
+			None
+		}
+
 		fn grandpa_authorities() -> Vec<(SessionKey, u64)> {
 			Grandpa::grandpa_authorities()
 		}
+
+		fn current_set_id() -> Option<u64> {
+			Grandpa::current_set_id()
+		}
+
+		fn authority_set_keys() -> Vec<Vec<u8>> {
+			Grandpa::authority_set_keys()
+		}
+
+		fn generate_key_ownership_proof(authority: SessionKey) -> Option<Vec<u8>> {
+			Grandpa::key_ownership_proof(authority)
+		}
 	}
 }
@@ -0,0 +1,155 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Moving the storage of a chain started before storage was namespaced per module.
+//!
+//! All the items are moved on the first block after the upgrade, before anything else is done
+//! in the block, so that the modules and their own migrations only ever see the new keys. The
+//! keys of maps can't be enumerated from their old hashed keys, so they are taken from the other
+//! items that reference them, which are moved first.
+
+use rstd::prelude::*;
+use srml_support::StorageValue;
+use srml_support::storage::migration::{kill_value_by_name, migrate_map_by_name, migrate_value_by_name};
+use node_primitives::{AccountId, Hash};
+use {Runtime, System, Balances, Democracy, CouncilMotions, CouncilVoting, Treasury};
+
+/// The storage values of the modules of the runtime.
+const VALUES: &[(&str, &[&str])] = &[
+	("System", &["ExtrinsicCount", "RandomSeed", "Number", "ParentHash", "ExtrinsicsRoot", "Digest", "Events"]),
+	("Timestamp", &["Now", "BlockPeriod", "DidUpdate"]),
+	("Consensus", &["OriginalAuthorities"]),
+	("Balances", &[
+		"TotalIssuance", "ExistentialDeposit", "ReclaimRebate", "TransferFee", "CreationFee", "NextEnumSet",
+		"ProvidersMigrated", "ProvidersMigrationSet", "TransactionBaseFee", "TransactionByteFee",
+	]),
+	("Session", &[
+		"Validators", "SessionLength", "CurrentIndex", "CurrentStart", "ForcingNewSession", "LastLengthChange",
+		"NextSessionLength",
+	]),
+	("Staking", &[
+		"ValidatorCount", "MinimumValidatorCount", "SessionsPerEra", "OfflineSlash", "OfflineSlashGrace",
+		"BondingDuration", "CurrentEra", "Intentions", "CurrentOfflineSlash", "NextSessionsPerEra",
+		"LastEraLengthChange", "StakeRange", "ForcingNewEra", "QueuedElected", "SolutionDeposit",
+		"IdealStakeRatio", "MinInflation", "MaxInflation", "EraElapsed",
+	]),
+	("Democracy", &[
+		"PublicPropCount", "PublicProps", "LaunchPeriod", "MinimumDeposit", "VotingPeriod", "MinimumVotingPeriod",
+		"ReferendumCount", "NextTally", "PreimageByteDeposit",
+	]),
+	("Council", &[
+		"CandidacyBond", "VotingBond", "PresentSlashPerVoter", "CarryCount", "PresentationDuration",
+		"InactiveGracePeriod", "VotingPeriod", "TermDuration", "DesiredSeats", "ActiveCouncil", "VoteCount",
+		"Voters", "Candidates", "CandidateCount", "NextFinalise", "SnapshotedStakes", "Leaderboard",
+	]),
+	("CouncilVoting", &["CooloffPeriod", "VotingPeriod", "Proposals"]),
+	("CouncilMotions", &["Proposals", "ProposalCount"]),
+	("GrandpaFinality", &["PendingChange"]),
+	("Treasury", &[
+		"ProposalBond", "ProposalBondMinimum", "SpendPeriod", "Burn", "Pot", "ProposalCount", "Approvals",
+	]),
+	("Contract", &[
+		"ContractFee", "CallBaseFee", "CreateBaseFee", "GasPrice", "MaxDepth", "BlockGasLimit", "GasSpent",
+		"CurrentSchedule",
+	]),
+	("UpgradeKey", &["Key"]),
+];
+
+/// The storage values that don't exist anymore, which are only removed.
+const REMOVED_VALUES: &[(&str, &str)] = &[
+	("Staking", "SessionReward"),
+	("Staking", "CurrentSessionReward"),
+];
+
+/// The storage maps keyed by account.
+const ACCOUNT_MAPS: &[(&str, &[&str])] = &[
+	("System", &["AccountNonce", "AccountProviders", "AccountConsumers"]),
+	("Balances", &["FreeBalance", "ReservedBalance"]),
+	("Session", &["NextKeyFor"]),
+	("Staking", &[
+		"ValidatorPreferences", "Bonded", "StashOf", "Payee", "Nominating", "NominatorsFor",
+		"CurrentNominatorsFor", "Bondage", "SlashCount", "EraPoints",
+	]),
+	("Council", &["ApprovalsOf", "RegisterInfoOf", "LastActiveOf"]),
+	("Contract", &["CodeOf"]),
+];
+
+/// Move all the storage items of the runtime to their keys namespaced per module, unless it has
+/// already been done. Must be called at the start of every block, before the modules access
+/// their storage.
+///
+/// Accounts are enumerated through the enumeration sets of the balances module: the maps keyed
+/// by account are moved for every account that ever had a balance. Only the hashes referenced by
+/// the pending proposals and referendums are known, so the vetoes of proposals that aren't
+/// pending anymore and the preimages that no proposal refers to are left behind.
+pub fn migrate_storage_keys() {
+	if System::storage_keys_migrated() {
+		return;
+	}
+
+	for &(module, items) in VALUES {
+		for item in items {
+			migrate_value_by_name(module, item);
+		}
+	}
+	for &(module, item) in REMOVED_VALUES {
+		kill_value_by_name(module, item);
+	}
+
+	migrate_map_by_name("Balances", "EnumSet", 0..=Balances::next_enum_set());
+	let accounts: Vec<AccountId> = (0..=Balances::next_enum_set())
+		.flat_map(|set| Balances::enum_set(set))
+		.collect();
+	for &(module, items) in ACCOUNT_MAPS {
+		for item in items {
+			migrate_map_by_name(module, item, accounts.iter().cloned());
+		}
+	}
+
+	// `ExtrinsicData` is only used within a block, and is empty in between.
+	migrate_map_by_name("System", "BlockHash", 0..System::block_number());
+
+	let public_props = Democracy::public_props();
+	migrate_map_by_name("Democracy", "DepositOf", public_props.iter().map(|prop| prop.0));
+	let referendums = Democracy::next_tally()..Democracy::referendum_count();
+	migrate_map_by_name("Democracy", "ReferendumInfoOf", referendums.clone());
+	migrate_map_by_name("Democracy", "VotersFor", referendums.clone());
+	for index in referendums.clone() {
+		let votes = Democracy::voters_for(index).into_iter().map(|who| (index, who));
+		migrate_map_by_name("Democracy", "VoteOf", votes);
+	}
+
+	let motions = CouncilMotions::proposals();
+	migrate_map_by_name("CouncilMotions", "ProposalOf", motions.iter().cloned());
+	migrate_map_by_name("CouncilMotions", "Voting", motions.iter().cloned());
+
+	let council_proposals: Vec<Hash> = CouncilVoting::proposals().into_iter().map(|(_, hash)| hash).collect();
+	migrate_map_by_name("CouncilVoting", "ProposalVoters", council_proposals.iter().cloned());
+	migrate_map_by_name("CouncilVoting", "VetoedProposal", council_proposals.iter().cloned());
+	for hash in &council_proposals {
+		let votes = CouncilVoting::proposal_voters(hash).into_iter().map(|who| (*hash, who));
+		migrate_map_by_name("CouncilVoting", "CouncilVoteOf", votes);
+	}
+
+	let preimages = public_props.iter().map(|prop| prop.1)
+		.chain(referendums.filter_map(|index| Democracy::referendum_info(index).map(|info| info.1)))
+		.chain(council_proposals);
+	migrate_map_by_name("Democracy", "Preimages", preimages);
+
+	migrate_map_by_name("Treasury", "Proposals", 0..Treasury::proposal_count());
+
+	<::system::StorageKeysMigrated<Runtime>>::put(true);
+}
@@ -159,6 +159,7 @@ mod tests {
 	#[derive(Clone, Eq, PartialEq)]
 	pub struct Test;
 	impl system::Trait for Test {
+		const SPEC_VERSION: u32 = 0;
 		type Origin = Origin;
 		type Index = u64;
 		type BlockNumber = u64;
@@ -169,6 +170,8 @@ mod tests {
 		type Header = Header;
 		type Event = ();
 		type Log = DigestItem;
+		type BaseCallFilter = ();
+		type CallWeigher = ();
 	}
 	impl Trait for Test {
 		type Event = ();
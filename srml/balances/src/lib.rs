@@ -46,8 +46,9 @@ use rstd::{cmp, result};
 use codec::{Encode, Decode, Codec, Input, Output, HasCompact};
 use runtime_support::{StorageValue, StorageMap, Parameter};
 use runtime_support::dispatch::Result;
+use runtime_support::traits::ReservableCurrency;
 use primitives::traits::{Zero, One, SimpleArithmetic, MakePayment,
-	As, Lookup, Member, CheckedAdd, CheckedSub, CurrentHeight, BlockNumberToHash};
+	As, Lookup, Member, CheckedAdd, CheckedSub, CurrentHeight, BlockNumberToHash, AdditionalSigned};
 use address::Address as RawAddress;
 use system::ensure_signed;
 
@@ -62,6 +63,13 @@ const ENUM_SET_SIZE: usize = 64;
 /// The byte to identify intention to reclaim an existing account index.
 const RECLAIM_INDEX_MAGIC: usize = 0x69;
 
+/// The weight of a transfer that creates the destination account, roughly the number of storage
+/// items it reads and writes.
+pub const TRANSFER_WEIGHT: u32 = 16;
+
+/// The part of `TRANSFER_WEIGHT` a transfer to an existing account doesn't consume.
+pub const ACCOUNT_CREATION_WEIGHT: u32 = 6;
+
 pub type Address<T> = RawAddress<<T as system::Trait>::AccountId, <T as Trait>::AccountIndex>;
 
 /// The account with the given id was killed.
@@ -70,20 +78,24 @@ pub trait OnFreeBalanceZero<AccountId> {
 	fn on_free_balance_zero(who: &AccountId);
 }
 
-impl<AccountId> OnFreeBalanceZero<AccountId> for () {
-	fn on_free_balance_zero(_who: &AccountId) {}
-}
-impl<
-	AccountId,
-	X: OnFreeBalanceZero<AccountId>,
-	Y: OnFreeBalanceZero<AccountId>,
-> OnFreeBalanceZero<AccountId> for (X, Y) {
-	fn on_free_balance_zero(who: &AccountId) {
-		X::on_free_balance_zero(who);
-		Y::on_free_balance_zero(who);
+macro_rules! impl_on_free_balance_zero {
+	() => (
+		impl<AccountId> OnFreeBalanceZero<AccountId> for () {
+			fn on_free_balance_zero(_who: &AccountId) {}
+		}
+	);
+
+	( $($t:ident)* ) => {
+		impl<AccountId, $($t: OnFreeBalanceZero<AccountId>),*> OnFreeBalanceZero<AccountId> for ($($t,)*) {
+			fn on_free_balance_zero(who: &AccountId) {
+				$($t::on_free_balance_zero(who);)*
+			}
+		}
 	}
 }
 
+for_each_tuple!(impl_on_free_balance_zero);
+
 /// Trait for a hook to get called when some balance has been minted, causing dilution.
 pub trait OnDilution<Balance> {
 	/// Some `portion` of the total balance just "grew" by `minted`. `portion` is the pre-growth
@@ -91,8 +103,46 @@ pub trait OnDilution<Balance> {
 	fn on_dilution(minted: Balance, portion: Balance);
 }
 
-impl<Balance> OnDilution<Balance> for () {
-	fn on_dilution(_minted: Balance, _portion: Balance) {}
+macro_rules! impl_on_dilution {
+	() => (
+		impl<Balance> OnDilution<Balance> for () {
+			fn on_dilution(_minted: Balance, _portion: Balance) {}
+		}
+	);
+
+	( $($t:ident)* ) => {
+		impl<Balance: Clone, $($t: OnDilution<Balance>),*> OnDilution<Balance> for ($($t,)*) {
+			fn on_dilution(minted: Balance, portion: Balance) {
+				$($t::on_dilution(minted.clone(), portion.clone());)*
+			}
+		}
+	}
+}
+
+for_each_tuple!(impl_on_dilution);
+
+/// Trait for a hook to get called when some balance has disappeared from the total issuance,
+/// e.g. the dust of a reaped account.
+pub trait OnUnbalanced<Balance> {
+	/// The given `amount` was removed from the total issuance.
+	fn on_unbalanced(amount: Balance);
+}
+
+impl<Balance> OnUnbalanced<Balance> for () {
+	fn on_unbalanced(_amount: Balance) {}
+}
+
+/// Handler for withdrawing the fee of a transaction.
+///
+/// Allows runtimes to take fees from something else than the free balance of the transactor,
+/// e.g. another asset or the account of a sponsor, or to account for them differently.
+pub trait OnChargeTransaction<AccountId, Balance> {
+	/// Withdraw the `fee` for a transaction sent by `who`. Returns `Err` if it can't be paid,
+	/// in which case the transaction is invalid.
+	fn charge_transaction(who: &AccountId, fee: Balance) -> Result;
+
+	/// Give back `refund` of the fee withdrawn for a transaction sent by `who`.
+	fn refund_transaction(who: &AccountId, refund: Balance);
 }
 
 /// Determinator for whether a given account is able to transfer balance.
@@ -121,6 +171,14 @@ pub trait Trait: system::Trait {
 	/// A function that returns true iff a given account can transfer its funds to another account.
 	type EnsureAccountLiquid: EnsureAccountLiquid<Self::AccountId>;
 
+	/// Handler for the dust that is lost when an account's balance falls below the existential
+	/// deposit.
+	type DustRemoval: OnUnbalanced<Self::Balance>;
+
+	/// Handler for withdrawing transaction fees. `Module<Self>` takes them from the free balance
+	/// of the transactor.
+	type OnChargeTransaction: OnChargeTransaction<Self::AccountId, Self::Balance>;
+
 	/// The overarching event type.
 	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
 }
@@ -129,6 +187,12 @@ decl_module! {
 	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
 		fn deposit_event() = default;
 
+		fn on_finalise() {
+			if !Self::providers_migrated() {
+				Self::migrate_providers();
+			}
+		}
+
 		/// Transfer some liquid free balance to another staker.
 		pub fn transfer(
 			origin,
@@ -170,6 +234,9 @@ decl_module! {
 				Self::set_free_balance_creating(&dest, new_to_balance);
 				Self::deposit_event(RawEvent::Transfer(transactor, dest, value, fee));
 			}
+			if !would_create {
+				<system::Module<T>>::note_unused_weight(ACCOUNT_CREATION_WEIGHT);
+			}
 
 			Ok(())
 		}
@@ -198,6 +265,9 @@ decl_event!(
 		NewAccount(AccountId, AccountIndex, NewAccountOutcome),
 		/// An account was reaped.
 		ReapedAccount(AccountId),
+		/// The balance of an account fell below the existential deposit and was removed (account,
+		/// amount).
+		DustLost(AccountId, Balance),
 		/// Transfer succeeded (from, to, value, fees).
 		Transfer(AccountId, AccountId, Balance, Balance),
 	}
@@ -224,6 +294,11 @@ decl_storage! {
 		}): T::AccountIndex;
 		/// The enumeration sets.
 		pub EnumSet get(enum_set): map T::AccountIndex => Vec<T::AccountId>;
+		/// Whether all accounts created before `system::AccountProviders` was introduced are noted
+		/// as kept alive by their balance.
+		pub ProvidersMigrated get(providers_migrated) build(|_| true): bool;
+		/// The next enumeration set whose accounts are migrated to `system::AccountProviders`.
+		ProvidersMigrationSet get(providers_migration_set): T::AccountIndex;
 
 		/// The 'free' balance of a given account.
 		///
@@ -236,6 +311,9 @@ decl_storage! {
 		///
 		/// `system::AccountNonce` is also deleted if `ReservedBalance` is also zero (it also gets
 		/// collapsed to zero if it ever becomes less than `ExistentialDeposit`.
+		///
+		/// None of this happens while the account is referenced by other modules (see
+		/// `system::AccountConsumers`); the balance is kept as it is until then.
 		pub FreeBalance get(free_balance) build(|config: &GenesisConfig<T>| config.balances.clone()): map T::AccountId => T::Balance;
 
 		/// The amount of the balance of a given account that is externally reserved; this can still get
@@ -259,15 +337,22 @@ decl_storage! {
 		pub TransactionBaseFee get(transaction_base_fee) config(): T::Balance;
 		/// The fee to be paid for making a transaction; the per-byte portion.
 		pub TransactionByteFee get(transaction_byte_fee) config(): T::Balance;
+		/// The fee to be paid for making a transaction; the portion per unit of weight of its call.
+		pub TransactionWeightFee get(transaction_weight_fee) config(): T::Balance;
 	}
 	add_extra_genesis {
 		config(balances): Vec<(T::AccountId, T::Balance)>;
 		build(|storage: &mut primitives::StorageMap, _: &mut primitives::ChildrenStorageMap, config: &GenesisConfig<T>| {
 			let ids: Vec<_> = config.balances.iter().map(|x| x.0.clone()).collect();
 			for i in 0..(ids.len() + ENUM_SET_SIZE - 1) / ENUM_SET_SIZE {
-				storage.insert(GenesisConfig::<T>::hash(&<EnumSet<T>>::key_for(T::AccountIndex::sa(i))).to_vec(),
+				storage.insert(<EnumSet<T>>::key_for(T::AccountIndex::sa(i)),
 					ids[i * ENUM_SET_SIZE..ids.len().min((i + 1) * ENUM_SET_SIZE)].to_owned().encode());
 			}
+			// the balance keeps every endowed account alive.
+			for id in ids.iter() {
+				storage.insert(<system::AccountProviders<T>>::key_for(id),
+					1u32.encode());
+			}
 		});
 	}
 }
@@ -341,10 +426,10 @@ impl<T: Trait> Module<T> {
 
 	/// Set the free balance of an account to some new value.
 	///
-	/// Will enforce ExistentialDeposit law, anulling the account as needed.
-	/// In that case it will return `AccountKilled`.
+	/// Will enforce ExistentialDeposit law, anulling the account as needed unless it is
+	/// referenced by other modules. In that case it will return `AccountKilled`.
 	pub fn set_reserved_balance(who: &T::AccountId, balance: T::Balance) -> UpdateBalanceOutcome {
-		if balance < Self::existential_deposit() {
+		if balance < Self::existential_deposit() && !<system::Module<T>>::is_referenced(who) {
 			<ReservedBalance<T>>::insert(who, balance);
 			Self::on_reserved_too_low(who);
 			UpdateBalanceOutcome::AccountKilled
@@ -355,7 +440,7 @@ impl<T: Trait> Module<T> {
 	}
 
 	/// Set the free balance of an account to some new value. Will enforce ExistentialDeposit
-	/// law anulling the account as needed.
+	/// law anulling the account as needed, unless the account is referenced by other modules.
 	///
 	/// Doesn't do any preparatory work for creating a new account, so should only be used when it
 	/// is known that the account already exists.
@@ -364,7 +449,7 @@ impl<T: Trait> Module<T> {
 	pub fn set_free_balance(who: &T::AccountId, balance: T::Balance) -> UpdateBalanceOutcome {
 		// Commented out for no - but consider it instructive.
 		// assert!(!Self::total_balance(who).is_zero());
-		if balance < Self::existential_deposit() {
+		if balance < Self::existential_deposit() && !<system::Module<T>>::is_referenced(who) {
 			<FreeBalance<T>>::insert(who, balance);
 			Self::on_free_too_low(who);
 			UpdateBalanceOutcome::AccountKilled
@@ -395,8 +480,7 @@ impl<T: Trait> Module<T> {
 		// value of which makes even the `free_balance` unspendable.
 		// TODO: enforce this for the other balance-altering functions.
 		if balance < ed {
-			Self::set_free_balance(who, balance);
-			UpdateBalanceOutcome::AccountKilled
+			Self::set_free_balance(who, balance)
 		} else {
 			if !<FreeBalance<T>>::exists(who) {
 				let outcome = Self::new_account(&who, balance);
@@ -582,6 +666,7 @@ impl<T: Trait> Module<T> {
 						try_set[item_index] = who.clone();
 						<EnumSet<T>>::insert(set_index, try_set);
 
+						<system::Module<T>>::inc_providers(who);
 						Self::deposit_event(RawEvent::NewAccount(who.clone(), try_index, NewAccountOutcome::GoodHint));
 
 						return NewAccountOutcome::GoodHint
@@ -617,20 +702,49 @@ impl<T: Trait> Module<T> {
 		// write set.
 		<EnumSet<T>>::insert(set_index, set);
 
+		<system::Module<T>>::inc_providers(who);
 		Self::deposit_event(RawEvent::NewAccount(who.clone(), index, ret));
 
 		ret
 	}
 
+	/// Note the accounts of the next enumeration set as kept alive by their balance, if they were
+	/// created before `system::AccountProviders` was introduced. One set is migrated per block.
+	fn migrate_providers() {
+		let set_index = Self::providers_migration_set();
+		if set_index > Self::next_enum_set() {
+			<ProvidersMigrated<T>>::put(true);
+			<ProvidersMigrationSet<T>>::kill();
+			return;
+		}
+
+		for who in Self::enum_set(set_index).iter() {
+			if <system::Module<T>>::providers(who) == 0 && !Self::total_balance(who).is_zero() {
+				<system::Module<T>>::inc_providers(who);
+			}
+		}
+		<ProvidersMigrationSet<T>>::put(set_index + One::one());
+	}
+
 	fn reap_account(who: &T::AccountId) {
-		<system::AccountNonce<T>>::remove(who);
-		Self::deposit_event(RawEvent::ReapedAccount(who.clone()));
+		// accounts referenced by other modules are never reaped, so this cannot fail.
+		if <system::Module<T>>::dec_providers(who).is_ok() {
+			Self::deposit_event(RawEvent::ReapedAccount(who.clone()));
+		}
+	}
+
+	/// Remove the given dust of an account from the total issuance and hand it to `DustRemoval`.
+	fn lose_dust(who: &T::AccountId, dust: T::Balance) {
+		Self::decrease_total_stake_by(dust);
+		if !dust.is_zero() {
+			T::DustRemoval::on_unbalanced(dust);
+			Self::deposit_event(RawEvent::DustLost(who.clone(), dust));
+		}
 	}
 
 	/// Kill an account's free portion.
 	fn on_free_too_low(who: &T::AccountId) {
-		Self::decrease_total_stake_by(Self::free_balance(who));
-		<FreeBalance<T>>::remove(who);
+		Self::lose_dust(who, <FreeBalance<T>>::take(who));
 
 		T::OnFreeBalanceZero::on_free_balance_zero(who);
 
@@ -641,8 +755,7 @@ impl<T: Trait> Module<T> {
 
 	/// Kill an account's reserved portion.
 	fn on_reserved_too_low(who: &T::AccountId) {
-		Self::decrease_total_stake_by(Self::reserved_balance(who));
-		<ReservedBalance<T>>::remove(who);
+		Self::lose_dust(who, <ReservedBalance<T>>::take(who));
 
 		if Self::free_balance(who).is_zero() {
 			Self::reap_account(who);
@@ -700,15 +813,58 @@ impl<T: Trait> BlockNumberToHash for ChainContext<T> {
 	}
 }
 
+impl<T: Trait> AdditionalSigned for ChainContext<T> {
+	type AdditionalSigned = (T::Hash, u32);
+	fn additional_signed(&self) -> Self::AdditionalSigned {
+		(<system::Module<T>>::block_hash(T::BlockNumber::zero()), T::SPEC_VERSION)
+	}
+}
+
 impl<T: Trait> MakePayment<T::AccountId> for Module<T> {
-	fn make_payment(transactor: &T::AccountId, encoded_len: usize) -> Result {
+	fn make_payment(transactor: &T::AccountId, encoded_len: usize, weight: u32) -> Result {
+		let transaction_fee = Self::transaction_base_fee()
+			+ Self::transaction_byte_fee() * <T::Balance as As<u64>>::sa(encoded_len as u64)
+			+ Self::transaction_weight_fee() * <T::Balance as As<u64>>::sa(weight as u64);
+		T::OnChargeTransaction::charge_transaction(transactor, transaction_fee)
+	}
+
+	fn refund(transactor: &T::AccountId, encoded_len: usize, unused_weight: u32, waived: bool) {
+		let mut refund = Self::transaction_weight_fee() * <T::Balance as As<u64>>::sa(unused_weight as u64);
+		if waived {
+			refund = refund + Self::transaction_base_fee()
+				+ Self::transaction_byte_fee() * <T::Balance as As<u64>>::sa(encoded_len as u64);
+		}
+		if !refund.is_zero() {
+			T::OnChargeTransaction::refund_transaction(transactor, refund);
+		}
+	}
+}
+
+impl<T: Trait> ReservableCurrency<T::AccountId> for Module<T> {
+	type Balance = T::Balance;
+
+	fn reserve(who: &T::AccountId, value: T::Balance) -> Result {
+		Self::reserve(who, value)
+	}
+
+	fn unreserve(who: &T::AccountId, value: T::Balance) -> Option<T::Balance> {
+		Self::unreserve(who, value)
+	}
+}
+
+impl<T: Trait> OnChargeTransaction<T::AccountId, T::Balance> for Module<T> {
+	fn charge_transaction(transactor: &T::AccountId, fee: T::Balance) -> Result {
 		let b = Self::free_balance(transactor);
-		let transaction_fee = Self::transaction_base_fee() + Self::transaction_byte_fee() * <T::Balance as As<u64>>::sa(encoded_len as u64);
-		if b < transaction_fee + Self::existential_deposit() {
+		if b < fee + Self::existential_deposit() {
 			return Err("not enough funds for transaction fee");
 		}
-		Self::set_free_balance(transactor, b - transaction_fee);
-		Self::decrease_total_stake_by(transaction_fee);
+		Self::set_free_balance(transactor, b - fee);
+		Self::decrease_total_stake_by(fee);
 		Ok(())
 	}
+
+	fn refund_transaction(transactor: &T::AccountId, refund: T::Balance) {
+		Self::set_free_balance(transactor, Self::free_balance(transactor) + refund);
+		Self::increase_total_stake_by(refund);
+	}
 }
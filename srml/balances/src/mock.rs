@@ -22,7 +22,47 @@ use primitives::BuildStorage;
 use primitives::testing::{Digest, DigestItem, Header};
 use substrate_primitives::{H256, Blake2Hasher};
 use runtime_io;
-use {GenesisConfig, Module, Trait, system};
+use std::cell::RefCell;
+use runtime_support::dispatch::Result;
+use {GenesisConfig, Module, Trait, OnChargeTransaction, OnUnbalanced, system};
+
+thread_local! {
+	static DUST: RefCell<u64> = RefCell::new(0);
+	static FEE_SPONSOR: RefCell<Option<u64>> = RefCell::new(None);
+}
+
+/// Collects all the dust that is lost, to be inspected with `lost_dust`.
+pub struct DustCollector;
+impl OnUnbalanced<u64> for DustCollector {
+	fn on_unbalanced(amount: u64) {
+		DUST.with(|d| *d.borrow_mut() += amount);
+	}
+}
+
+/// The dust that was collected by `DustCollector` since the externalities were built.
+pub fn lost_dust() -> u64 {
+	DUST.with(|d| *d.borrow())
+}
+
+/// Charges transaction fees to the sponsor set with `set_fee_sponsor`, if any, and to the
+/// transactor otherwise.
+pub struct SponsoredFees;
+impl OnChargeTransaction<u64, u64> for SponsoredFees {
+	fn charge_transaction(who: &u64, fee: u64) -> Result {
+		let payer = FEE_SPONSOR.with(|s| *s.borrow()).unwrap_or(*who);
+		Module::<Runtime>::charge_transaction(&payer, fee)
+	}
+
+	fn refund_transaction(who: &u64, refund: u64) {
+		let payer = FEE_SPONSOR.with(|s| *s.borrow()).unwrap_or(*who);
+		Module::<Runtime>::refund_transaction(&payer, refund)
+	}
+}
+
+/// Have all transaction fees paid by `sponsor`.
+pub fn set_fee_sponsor(sponsor: u64) {
+	FEE_SPONSOR.with(|s| *s.borrow_mut() = Some(sponsor));
+}
 
 impl_outer_origin!{
 	pub enum Origin for Runtime {}
@@ -32,6 +72,7 @@ impl_outer_origin!{
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct Runtime;
 impl system::Trait for Runtime {
+	const SPEC_VERSION: u32 = 0;
 	type Origin = Origin;
 	type Index = u64;
 	type BlockNumber = u64;
@@ -42,12 +83,16 @@ impl system::Trait for Runtime {
 	type Header = Header;
 	type Event = ();
 	type Log = DigestItem;
+	type BaseCallFilter = ();
+	type CallWeigher = ();
 }
 impl Trait for Runtime {
 	type Balance = u64;
 	type AccountIndex = u64;
 	type OnFreeBalanceZero = ();
 	type EnsureAccountLiquid = ();
+	type DustRemoval = DustCollector;
+	type OnChargeTransaction = SponsoredFees;
 	type Event = ();
 }
 
@@ -86,6 +131,8 @@ impl ExtBuilder {
 		self
 	}
 	pub fn build(self) -> runtime_io::TestExternalities<Blake2Hasher> {
+		DUST.with(|d| *d.borrow_mut() = 0);
+		FEE_SPONSOR.with(|s| *s.borrow_mut() = None);
 		let mut t = system::GenesisConfig::<Runtime>::default().build_storage().unwrap().0;
 		let balance_factor = if self.existential_deposit > 0 {
 			256
@@ -100,6 +147,7 @@ impl ExtBuilder {
 			},
 			transaction_base_fee: 0,
 			transaction_byte_fee: 0,
+			transaction_weight_fee: 0,
 			existential_deposit: self.existential_deposit,
 			transfer_fee: self.transfer_fee,
 			creation_fee: self.creation_fee,
@@ -19,8 +19,9 @@
 #![cfg(test)]
 
 use super::*;
-use mock::{Balances, ExtBuilder, Runtime, System};
+use mock::{Balances, ExtBuilder, Runtime, System, lost_dust, set_fee_sponsor};
 use runtime_io::with_externalities;
+use primitives::traits::OnFinalise;
 
 #[test]
 fn reward_should_work() {
@@ -144,6 +145,71 @@ fn dust_account_removal_should_work2() {
 	);
 }
 
+#[test]
+fn dust_should_be_handed_to_dust_removal() {
+	with_externalities(
+		&mut ExtBuilder::default()
+			.existential_deposit(256 * 10)
+			.monied(true)
+			.build(),
+		|| {
+			let total_issuance = Balances::total_issuance();
+			assert_ok!(Balances::transfer(Some(2).into(), 5.into(), (256 * 10 + 1).into()));
+
+			assert_eq!(Balances::total_balance(&2), 0);
+			assert_eq!(lost_dust(), 256 * 10 - 1);
+			assert_eq!(Balances::total_issuance(), total_issuance - (256 * 10 - 1));
+		},
+	);
+}
+
+#[test]
+fn referenced_dust_account_should_not_be_removed() {
+	with_externalities(
+		&mut ExtBuilder::default()
+			.existential_deposit(256 * 10)
+			.monied(true)
+			.build(),
+		|| {
+			System::inc_account_nonce(&2);
+			assert_ok!(System::inc_consumers(&2));
+
+			assert_ok!(Balances::transfer(Some(2).into(), 5.into(), (256 * 10 + 1).into()));
+			// the dust is kept while the account is referenced.
+			assert_eq!(Balances::total_balance(&2), 256 * 10 - 1);
+			assert_eq!(System::account_nonce(&2), 1);
+			assert_eq!(lost_dust(), 0);
+
+			// once the reference is gone the account is reaped on the next balance change.
+			System::dec_consumers(&2);
+			assert_ok!(Balances::transfer(Some(2).into(), 5.into(), 1.into()));
+			assert_eq!(Balances::total_balance(&2), 0);
+			assert_eq!(System::account_nonce(&2), 0);
+			assert_eq!(System::providers(&2), 0);
+		},
+	);
+}
+
+#[test]
+fn accounts_created_before_providers_were_counted_are_migrated() {
+	with_externalities(&mut ExtBuilder::default().monied(true).build(), || {
+		// account 1 was created before `system::AccountProviders` was introduced.
+		<system::AccountProviders<Runtime>>::remove(&1);
+		<ProvidersMigrated<Runtime>>::put(false);
+		assert_eq!(System::inc_consumers(&1), Err("account does not exist"));
+
+		// the endowed accounts are all in the first enumeration set.
+		Balances::on_finalise(1);
+		assert_eq!(System::providers(&1), 1);
+		assert_eq!(System::providers(&2), 1);
+		assert!(!Balances::providers_migrated());
+
+		Balances::on_finalise(2);
+		assert!(Balances::providers_migrated());
+		assert_ok!(System::inc_consumers(&1));
+	});
+}
+
 #[test]
 fn reclaim_indexing_on_new_accounts_should_work() {
 	with_externalities(
@@ -498,3 +564,46 @@ fn transfer_overflow_isnt_exploitable() {
 		}
 	);
 }
+
+#[test]
+fn transaction_fees_are_charged_through_the_handler() {
+	with_externalities(&mut ExtBuilder::default().monied(true).build(), || {
+		<TransactionBaseFee<Runtime>>::put(3);
+
+		assert_ok!(Balances::make_payment(&1, 0, 0));
+		assert_eq!(Balances::free_balance(&1), 7);
+
+		set_fee_sponsor(2);
+		assert_ok!(Balances::make_payment(&1, 0, 0));
+		assert_eq!(Balances::free_balance(&1), 7);
+		assert_eq!(Balances::free_balance(&2), 17);
+		assert_eq!(Balances::total_issuance(), 100 - 6);
+	});
+}
+
+#[test]
+fn transfers_to_existing_accounts_report_unused_weight() {
+	with_externalities(&mut ExtBuilder::default().monied(true).build(), || {
+		assert_ok!(Balances::transfer(Some(1).into(), 5.into(), 1.into()));
+		assert_eq!(System::take_post_dispatch_info().unused_weight, 0);
+		assert_ok!(Balances::transfer(Some(1).into(), 2.into(), 1.into()));
+		assert_eq!(System::take_post_dispatch_info().unused_weight, ACCOUNT_CREATION_WEIGHT);
+	});
+}
+
+#[test]
+fn unused_weight_and_waived_fees_are_refunded() {
+	with_externalities(&mut ExtBuilder::default().monied(true).build(), || {
+		<TransactionBaseFee<Runtime>>::put(1);
+		<TransactionByteFee<Runtime>>::put(1);
+		<TransactionWeightFee<Runtime>>::put(1);
+
+		assert_ok!(Balances::make_payment(&1, 2, 4));
+		assert_eq!(Balances::free_balance(&1), 3);
+		Balances::refund(&1, 2, 3, false);
+		assert_eq!(Balances::free_balance(&1), 6);
+		Balances::refund(&1, 2, 1, true);
+		assert_eq!(Balances::free_balance(&1), 10);
+		assert_eq!(Balances::total_issuance(), 100);
+	});
+}
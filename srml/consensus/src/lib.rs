@@ -66,10 +66,24 @@ pub trait OnOfflineValidator {
 	fn on_offline_validator(validator_index: usize);
 }
 
-impl OnOfflineValidator for () {
-	fn on_offline_validator(_validator_index: usize) {}
+macro_rules! impl_on_offline_validator {
+	() => (
+		impl OnOfflineValidator for () {
+			fn on_offline_validator(_validator_index: usize) {}
+		}
+	);
+
+	( $($t:ident)* ) => {
+		impl<$($t: OnOfflineValidator),*> OnOfflineValidator for ($($t,)*) {
+			fn on_offline_validator(validator_index: usize) {
+				$($t::on_offline_validator(validator_index);)*
+			}
+		}
+	}
 }
 
+for_each_tuple!(impl_on_offline_validator);
+
 pub type Log<T> = RawLog<
 	<T as Trait>::SessionKey,
 >;
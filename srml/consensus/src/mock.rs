@@ -37,6 +37,7 @@ impl Trait for Test {
 	type OnOfflineValidator = ();
 }
 impl system::Trait for Test {
+	const SPEC_VERSION: u32 = 0;
 	type Origin = Origin;
 	type Index = u64;
 	type BlockNumber = u64;
@@ -47,6 +48,8 @@ impl system::Trait for Test {
 	type Header = Header;
 	type Event = ();
 	type Log = DigestItem;
+	type BaseCallFilter = ();
+	type CallWeigher = ();
 }
 
 pub fn new_test_ext(authorities: Vec<u64>) -> runtime_io::TestExternalities<Blake2Hasher> {
@@ -16,12 +16,12 @@
 
 //! Auxilliaries to help with managing partial changes to accounts state.
 
-use super::{CodeOf, StorageOf, Trait};
+use super::{StorageOf, Trait};
+use code;
 use double_map::StorageDoubleMap;
 use rstd::cell::RefCell;
 use rstd::collections::btree_map::{BTreeMap, Entry};
 use rstd::prelude::*;
-use runtime_support::StorageMap;
 use {balances, system};
 
 pub struct ChangeEntry<T: Trait> {
@@ -57,7 +57,7 @@ impl<T: Trait> AccountDb<T> for DirectAccountDb {
 		<StorageOf<T>>::get(account.clone(), location.to_vec())
 	}
 	fn get_code(&self, account: &T::AccountId) -> Vec<u8> {
-		<CodeOf<T>>::get(account)
+		code::code_of::<T>(account)
 	}
 	fn get_balance(&self, account: &T::AccountId) -> T::Balance {
 		balances::Module::<T>::free_balance(account)
@@ -69,13 +69,13 @@ impl<T: Trait> AccountDb<T> for DirectAccountDb {
 					balances::Module::<T>::set_free_balance_creating(&address, balance)
 				{
 					// Account killed. This will ultimately lead to calling `OnFreeBalanceZero` callback
-					// which will make removal of CodeOf and StorageOf for this account.
+					// which will make removal of the code and StorageOf for this account.
 					// In order to avoid writing over the deleted properties we `continue` here.
 					continue;
 				}
 			}
 			if let Some(code) = changed.code {
-				<CodeOf<T>>::insert(&address, &code);
+				code::set_code_of::<T>(&address, code);
 			}
 			for (k, v) in changed.storage.into_iter() {
 				if let Some(value) = v {
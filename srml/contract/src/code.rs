@@ -0,0 +1,106 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate. If not, see <http://www.gnu.org/licenses/>.
+
+//! Storage of contract code.
+//!
+//! Code is stored once by its hash, no matter how many contracts share it, and is removed when
+//! the last contract using it is. Along with the code the instrumented version of it is cached,
+//! so that it is only instrumented again once the schedule has been updated.
+
+use rstd::prelude::*;
+use runtime_primitives::traits::Hash;
+use runtime_support::StorageMap;
+use system;
+use vm::{self, Ext, PrefabWasmModule};
+use {CodeCache, CodeHashOf, CodeOf, CodeRefCount, PristineCode, Schedule, Trait};
+
+/// Get the hash of the code of the given account. Code the account still has in the legacy
+/// `CodeOf` map is migrated to storage by hash first.
+fn code_hash_of<T: Trait>(account: &T::AccountId) -> Option<T::Hash> {
+	match <CodeOf<T>>::take(account) {
+		Some(code) => Some(insert_code_of::<T>(account, code)),
+		None => <CodeHashOf<T>>::get(account),
+	}
+}
+
+/// Store the given code as the code of the given account, which must not have any.
+fn insert_code_of<T: Trait>(account: &T::AccountId, code: Vec<u8>) -> T::Hash {
+	let code_hash = T::Hashing::hash(&code);
+	if !<PristineCode<T>>::exists(&code_hash) {
+		<PristineCode<T>>::insert(&code_hash, code);
+	}
+	<CodeRefCount<T>>::mutate(&code_hash, |count| *count += 1);
+	<CodeHashOf<T>>::insert(account, code_hash);
+	code_hash
+}
+
+/// Get the code of the given account, or an empty vector if it has none.
+pub fn code_of<T: Trait>(account: &T::AccountId) -> Vec<u8> {
+	code_hash_of::<T>(account)
+		.and_then(<PristineCode<T>>::get)
+		.unwrap_or_default()
+}
+
+/// Set the code of the given account, replacing the code it had.
+pub fn set_code_of<T: Trait>(account: &T::AccountId, code: Vec<u8>) {
+	if code_hash_of::<T>(account) == Some(T::Hashing::hash(&code)) {
+		return;
+	}
+
+	remove_code_of::<T>(account);
+	insert_code_of::<T>(account, code);
+}
+
+/// Remove the code of the given account, if it has any. The code is dropped from storage once
+/// no account uses it anymore.
+pub fn remove_code_of<T: Trait>(account: &T::AccountId) {
+	<CodeOf<T>>::remove(account);
+	let code_hash = match <CodeHashOf<T>>::take(account) {
+		Some(code_hash) => code_hash,
+		None => return,
+	};
+
+	let count = <CodeRefCount<T>>::get(&code_hash).saturating_sub(1);
+	if count == 0 {
+		<CodeRefCount<T>>::remove(&code_hash);
+		<PristineCode<T>>::remove(&code_hash);
+		<CodeCache<T>>::remove(&code_hash);
+	} else {
+		<CodeRefCount<T>>::insert(&code_hash, count);
+	}
+}
+
+/// Load the instrumented version of the given code.
+///
+/// The cached version is used if it was instrumented with the current version of the schedule.
+/// Otherwise the code is instrumented again, and cached if it is stored.
+pub fn load<E: Ext>(
+	code: &[u8],
+	schedule: &Schedule<<E::T as Trait>::Gas>,
+) -> Result<PrefabWasmModule, vm::Error> {
+	let code_hash = <E::T as system::Trait>::Hashing::hash(code);
+	if let Some(prefab) = <CodeCache<E::T>>::get(&code_hash) {
+		if prefab.schedule_version == schedule.version {
+			return Ok(prefab);
+		}
+	}
+
+	let prefab = vm::prepare::<E>(code, schedule)?;
+	if <PristineCode<E::T>>::exists(&code_hash) {
+		<CodeCache<E::T>>::insert(&code_hash, &prefab);
+	}
+	Ok(prefab)
+}
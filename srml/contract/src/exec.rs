@@ -16,6 +16,7 @@
 
 use super::{ContractAddressFor, Trait, Event, RawEvent, Config};
 use account_db::{AccountDb, OverlayAccountDb};
+use code;
 use gas::GasMeter;
 use vm;
 
@@ -84,8 +85,10 @@ impl<'a, T: Trait> ExecutionContext<'a, T> {
 			}
 
 			if !dest_code.is_empty() {
-				vm::execute(
-					&dest_code,
+				let prefab = code::load::<CallContext<T>>(&dest_code, &self.config.schedule)
+					.map_err(|_| "vm execute returned error while call")?;
+				vm::execute_prefab(
+					&prefab,
 					data,
 					output_data,
 					&mut CallContext {
@@ -29,6 +29,11 @@
 //! after the transaction (regardless of the execution outcome). If all gas is used,
 //! then changes made for the specific call or create are reverted (including balance transfers).
 //!
+//! The gas costs of executing contract code and calling host functions are defined by the
+//! [`Schedule`] kept in storage. The schedule is versioned and only replaced through the
+//! privileged `update_schedule` call, so that costs only change when governance decides so and
+//! not as a side effect of upgrading the runtime.
+//!
 //! Failures are typically not cascading. That, for example, means that if contract A calls B and B errors
 //! somehow, then A can decide if it should proceed or error.
 //!
@@ -49,6 +54,7 @@
 //! code and storage of the account.
 //!
 //! [`Module::execute`]: struct.Module.html#impl-OnFinalise
+//! [`Schedule`]: struct.Schedule.html
 
 #![cfg_attr(not(feature = "std"), no_std)]
 
@@ -84,6 +90,7 @@ extern crate assert_matches;
 extern crate wabt;
 
 mod account_db;
+mod code;
 mod double_map;
 mod exec;
 mod vm;
@@ -98,7 +105,7 @@ use double_map::StorageDoubleMap;
 
 use rstd::prelude::*;
 use rstd::marker::PhantomData;
-use codec::{Codec, HasCompact};
+use codec::{Codec, Decode, HasCompact};
 use runtime_primitives::traits::{Hash, As, SimpleArithmetic};
 use runtime_support::dispatch::Result;
 use runtime_support::{Parameter, StorageMap, StorageValue};
@@ -252,6 +259,20 @@ decl_module! {
 			result.map(|_| ())
 		}
 
+		/// Replace the current cost schedule. The version of the new schedule must be greater
+		/// than the current one.
+		fn update_schedule(schedule: Schedule<T::Gas>) -> Result {
+			ensure!(
+				schedule.version > <Module<T>>::current_schedule().version,
+				"new schedule must have a greater version than the current one"
+			);
+
+			let version = schedule.version;
+			<CurrentSchedule<T>>::put(schedule);
+			Self::deposit_event(RawEvent::ScheduleUpdated(version));
+			Ok(())
+		}
+
 		fn on_finalise() {
 			<GasSpent<T>>::kill();
 		}
@@ -269,6 +290,9 @@ decl_event! {
 
 		/// Contract deployed by address at the specified address.
 		Created(AccountId, AccountId),
+
+		/// The cost schedule was replaced by the one of the given version.
+		ScheduleUpdated(u32),
 	}
 }
 
@@ -289,9 +313,20 @@ decl_storage! {
 		/// Gas spent so far in this block.
 		GasSpent get(gas_spent): T::Gas;
 		/// Current cost schedule for contracts.
-		CurrentSchedule get(current_schedule) config(): Schedule<T::Gas> = Schedule::default();
-		/// The code associated with an account.
-		pub CodeOf: map T::AccountId => Vec<u8>;	// TODO Vec<u8> values should be optimised to not do a length prefix.
+		CurrentSchedule config(current_schedule): Schedule<T::Gas> = Schedule::default();
+		/// The code associated with an account, as stored before code was stored by hash. An entry
+		/// is migrated to `CodeHashOf` and `PristineCode` once the code of its account is accessed.
+		/// On chains started before storage was namespaced per module, the runtime moves the
+		/// entries to their namespaced keys first, see `srml_support::storage::migration`.
+		CodeOf: map T::AccountId => Option<Vec<u8>>;
+		/// The hash of the code associated with an account.
+		pub CodeHashOf get(code_hash_of): map T::AccountId => Option<T::Hash>;
+		/// The code of the given hash, stored once for all accounts using it.
+		pub PristineCode get(pristine_code): map T::Hash => Option<Vec<u8>>;
+		/// The number of accounts using the code of the given hash.
+		CodeRefCount: map T::Hash => u32;
+		/// The code of the given hash, instrumented with some version of the schedule.
+		CodeCache: map T::Hash => Option<vm::PrefabWasmModule>;
 	}
 }
 
@@ -309,9 +344,32 @@ impl<T: Trait> double_map::StorageDoubleMap for StorageOf<T> {
 	type Value = Vec<u8>;
 }
 
+impl<T: Trait> Module<T> {
+	/// Current cost schedule for contracts. A schedule stored before schedules were versioned is
+	/// converted and stored again, with version 0 and the default cost of host function calls.
+	///
+	/// The schedule is only looked for under its namespaced key: on chains started before storage
+	/// was namespaced per module, the runtime moves it there first, see
+	/// `srml_support::storage::migration`.
+	pub fn current_schedule() -> Schedule<T::Gas> {
+		let encoded = match runtime_support::storage::get_raw(&<CurrentSchedule<T>>::key()) {
+			Some(encoded) => encoded,
+			None => return Schedule::default(),
+		};
+
+		Schedule::decode(&mut &encoded[..]).unwrap_or_else(|| {
+			let schedule = LegacySchedule::decode(&mut &encoded[..])
+				.map(Schedule::from)
+				.unwrap_or_default();
+			<CurrentSchedule<T>>::put(&schedule);
+			schedule
+		})
+	}
+}
+
 impl<T: Trait> balances::OnFreeBalanceZero<T::AccountId> for Module<T> {
 	fn on_free_balance_zero(who: &T::AccountId) {
-		<CodeOf<T>>::remove(who);
+		code::remove_code_of::<T>(who);
 		<StorageOf<T>>::remove_prefix(who.clone());
 	}
 }
@@ -348,8 +406,15 @@ impl<T: Trait> Config<T> {
 
 /// Definition of the cost schedule and other parameterizations for wasm vm.
 #[cfg_attr(feature = "std", derive(Serialize, Deserialize, Debug))]
-#[derive(Clone, Encode, Decode)]
+#[derive(Clone, PartialEq, Eq, Encode, Decode)]
 pub struct Schedule<Gas> {
+	/// Version of the schedule, increased with every update of the schedule.
+	pub version: u32,
+
+	/// Gas cost of calling any host function, on top of the cost of the data it reads or
+	/// writes. `gas`, which is injected by metering, is free.
+	pub host_fn_call_cost: Gas,
+
 	/// Gas cost of a growing memory by single page.
 	pub grow_mem_cost: Gas,
 
@@ -379,6 +444,8 @@ pub struct Schedule<Gas> {
 impl<Gas: As<u64>> Default for Schedule<Gas> {
 	fn default() -> Schedule<Gas> {
 		Schedule {
+			version: 0,
+			host_fn_call_cost: Gas::sa(10),
 			grow_mem_cost: Gas::sa(1),
 			regular_op_cost: Gas::sa(1),
 			return_data_per_byte_cost: Gas::sa(1),
@@ -389,3 +456,30 @@ impl<Gas: As<u64>> Default for Schedule<Gas> {
 		}
 	}
 }
+
+/// The cost schedule as stored before schedules were versioned.
+#[derive(Decode)]
+struct LegacySchedule<Gas> {
+	grow_mem_cost: Gas,
+	regular_op_cost: Gas,
+	return_data_per_byte_cost: Gas,
+	sandbox_data_read_cost: Gas,
+	sandbox_data_write_cost: Gas,
+	max_stack_height: u32,
+	max_memory_pages: u32,
+}
+
+impl<Gas: As<u64>> From<LegacySchedule<Gas>> for Schedule<Gas> {
+	fn from(legacy: LegacySchedule<Gas>) -> Schedule<Gas> {
+		Schedule {
+			grow_mem_cost: legacy.grow_mem_cost,
+			regular_op_cost: legacy.regular_op_cost,
+			return_data_per_byte_cost: legacy.return_data_per_byte_cost,
+			sandbox_data_read_cost: legacy.sandbox_data_read_cost,
+			sandbox_data_write_cost: legacy.sandbox_data_write_cost,
+			max_stack_height: legacy.max_stack_height,
+			max_memory_pages: legacy.max_memory_pages,
+			..Schedule::default()
+		}
+	}
+}
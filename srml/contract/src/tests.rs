@@ -17,15 +17,15 @@
 use double_map::StorageDoubleMap;
 use runtime_io::with_externalities;
 use runtime_primitives::testing::{Digest, DigestItem, H256, Header};
-use runtime_primitives::traits::{BlakeTwo256};
+use runtime_primitives::traits::{BlakeTwo256, Hash};
 use runtime_primitives::BuildStorage;
-use runtime_support::StorageMap;
+use runtime_support::{StorageMap, StorageValue};
 use substrate_primitives::{Blake2Hasher};
 use system::{Phase, EventRecord};
 use wabt;
 use {
-	runtime_io, balances, system, CodeOf, ContractAddressFor,
-	GenesisConfig, Module, StorageOf, Trait, RawEvent,
+	runtime_io, balances, code, system, CodeCache, CodeHashOf, CodeOf, CodeRefCount, ContractAddressFor,
+	CurrentSchedule, GenesisConfig, Module, PristineCode, StorageOf, Trait, RawEvent, Schedule,
 };
 
 impl_outer_origin! {
@@ -44,6 +44,7 @@ impl_outer_event! {
 #[derive(Clone, Eq, PartialEq)]
 pub struct Test;
 impl system::Trait for Test {
+	const SPEC_VERSION: u32 = 0;
 	type Origin = Origin;
 	type Index = u64;
 	type BlockNumber = u64;
@@ -54,12 +55,16 @@ impl system::Trait for Test {
 	type Header = Header;
 	type Event = MetaEvent;
 	type Log = DigestItem;
+	type BaseCallFilter = ();
+	type CallWeigher = ();
 }
 impl balances::Trait for Test {
 	type Balance = u64;
 	type AccountIndex = u64;
 	type OnFreeBalanceZero = Contract;
 	type EnsureAccountLiquid = ();
+	type DustRemoval = ();
+	type OnChargeTransaction = Balances;
 	type Event = MetaEvent;
 }
 impl Trait for Test {
@@ -127,6 +132,7 @@ impl ExtBuilder {
 				balances: vec![],
 				transaction_base_fee: 0,
 				transaction_byte_fee: 0,
+				transaction_weight_fee: 0,
 				existential_deposit: self.existential_deposit,
 				transfer_fee: self.transfer_fee,
 				creation_fee: self.creation_fee,
@@ -143,7 +149,8 @@ impl ExtBuilder {
 				gas_price: self.gas_price,
 				max_depth: 100,
 				block_gas_limit: self.block_gas_limit,
-				current_schedule: Default::default(),
+				// the gas figures of these tests leave out the cost of host function calls.
+				current_schedule: Schedule { host_fn_call_cost: 0, ..Default::default() },
 				_genesis_phantom_data: Default::default(),
 			}.build_storage()
 			.unwrap().0,
@@ -195,7 +202,7 @@ fn contract_transfer() {
 	let code_transfer = wabt::wat2wasm(CODE_TRANSFER).unwrap();
 
 	with_externalities(&mut ExtBuilder::default().build(), || {
-		<CodeOf<Test>>::insert(1, code_transfer.to_vec());
+		code::set_code_of::<Test>(&1, code_transfer.to_vec());
 
 		Balances::set_free_balance(&0, 100_000_000);
 		Balances::increase_total_stake_by(100_000_000);
@@ -251,7 +258,7 @@ fn contract_transfer_to_death() {
 	let code_transfer = wabt::wat2wasm(CODE_TRANSFER).unwrap();
 
 	with_externalities(&mut ExtBuilder::default().existential_deposit(5).build(), || {
-		<CodeOf<Test>>::insert(1, code_transfer.to_vec());
+		code::set_code_of::<Test>(&1, code_transfer.to_vec());
 
 		Balances::set_free_balance(&0, 100_000_000);
 		Balances::increase_total_stake_by(100_000_000);
@@ -270,7 +277,7 @@ fn contract_transfer_to_death() {
 			100_000_000 - (2 * 26) - (2 * 135) - (2 * 135),
 		);
 
-		assert!(!<CodeOf<Test>>::exists(1));
+		assert!(!<CodeHashOf<Test>>::exists(1));
 		assert!(!<StorageOf<Test>>::exists(1, b"foo".to_vec()));
 		assert_eq!(Balances::free_balance(&1), 0);
 
@@ -286,7 +293,7 @@ fn contract_transfer_takes_creation_fee() {
 	let code_transfer = wabt::wat2wasm(CODE_TRANSFER).unwrap();
 
 	with_externalities(&mut ExtBuilder::default().creation_fee(105).build(), || {
-		<CodeOf<Test>>::insert(1, code_transfer.to_vec());
+		code::set_code_of::<Test>(&1, code_transfer.to_vec());
 
 		Balances::set_free_balance(&0, 100_000_000);
 		Balances::increase_total_stake_by(100_000_000);
@@ -323,7 +330,7 @@ fn contract_transfer_takes_transfer_fee() {
 	let code_transfer = wabt::wat2wasm(CODE_TRANSFER).unwrap();
 
 	with_externalities(&mut ExtBuilder::default().creation_fee(105).transfer_fee(45).build(), || {
-		<CodeOf<Test>>::insert(1, code_transfer.to_vec());
+		code::set_code_of::<Test>(&1, code_transfer.to_vec());
 
 		Balances::set_free_balance(&0, 100_000_000);
 		Balances::increase_total_stake_by(100_000_000);
@@ -364,7 +371,7 @@ fn contract_transfer_oog() {
 	let code_transfer = wabt::wat2wasm(CODE_TRANSFER).unwrap();
 
 	with_externalities(&mut ExtBuilder::default().build(), || {
-		<CodeOf<Test>>::insert(1, code_transfer.to_vec());
+		code::set_code_of::<Test>(&1, code_transfer.to_vec());
 
 		Balances::set_free_balance(&0, 100_000_000);
 		Balances::increase_total_stake_by(100_000_000);
@@ -403,7 +410,7 @@ fn contract_transfer_max_depth() {
 	let code_transfer = wabt::wat2wasm(CODE_TRANSFER).unwrap();
 
 	with_externalities(&mut ExtBuilder::default().build(), || {
-		<CodeOf<Test>>::insert(CONTRACT_SHOULD_TRANSFER_TO, code_transfer.to_vec());
+		code::set_code_of::<Test>(&CONTRACT_SHOULD_TRANSFER_TO, code_transfer.to_vec());
 
 		Balances::set_free_balance(&0, 100_000_000);
 		Balances::increase_total_stake_by(100_000_000);
@@ -519,7 +526,7 @@ fn contract_create() {
 		Balances::set_free_balance(&9, 30);
 		Balances::increase_total_stake_by(30);
 
-		<CodeOf<Test>>::insert(1, code_create.to_vec());
+		code::set_code_of::<Test>(&1, code_create.to_vec());
 
 		// When invoked, the contract at address `1` must create a contract with 'transfer' code.
 		assert_ok!(Contract::call(Origin::signed(0), 1, 11.into(), 100_000.into(), Vec::new()));
@@ -613,7 +620,7 @@ fn top_level_create() {
 		);
 		assert_eq!(Balances::free_balance(&derived_address), 30 + 11);
 
-		assert_eq!(<CodeOf<Test>>::get(&derived_address), code_transfer);
+		assert_eq!(code::code_of::<Test>(&derived_address), code_transfer);
 
 		assert_eq!(System::events(), vec![
 			EventRecord {
@@ -641,7 +648,7 @@ fn refunds_unused_gas() {
 	let code_nop = wabt::wat2wasm(CODE_NOP).unwrap();
 
 	with_externalities(&mut ExtBuilder::default().build(), || {
-		<CodeOf<Test>>::insert(1, code_nop.to_vec());
+		code::set_code_of::<Test>(&1, code_nop.to_vec());
 
 		Balances::set_free_balance(&0, 100_000_000);
 		Balances::increase_total_stake_by(100_000_000);
@@ -655,7 +662,7 @@ fn refunds_unused_gas() {
 #[test]
 fn call_with_zero_value() {
 	with_externalities(&mut ExtBuilder::default().build(), || {
-		<CodeOf<Test>>::insert(1, vec![]);
+		code::set_code_of::<Test>(&1, vec![]);
 
 		Balances::set_free_balance(&0, 100_000_000);
 		Balances::increase_total_stake_by(100_000_000);
@@ -741,7 +748,7 @@ const CODE_UNREACHABLE: &'static str = r#"
 fn top_level_call_refunds_even_if_fails() {
 	let code_unreachable = wabt::wat2wasm(CODE_UNREACHABLE).unwrap();
 	with_externalities(&mut ExtBuilder::default().gas_price(4).build(), || {
-		<CodeOf<Test>>::insert(1, code_unreachable.to_vec());
+		code::set_code_of::<Test>(&1, code_unreachable.to_vec());
 
 		Balances::set_free_balance(&0, 100_000_000);
 		Balances::increase_total_stake_by(100_000_000);
@@ -773,7 +780,7 @@ fn block_gas_limit() {
 	with_externalities(
 		&mut ExtBuilder::default().block_gas_limit(100_000).build(),
 		|| {
-			<CodeOf<Test>>::insert(1, code_loop.to_vec());
+			code::set_code_of::<Test>(&1, code_loop.to_vec());
 
 			Balances::set_free_balance(&0, 100_000_000);
 			Balances::increase_total_stake_by(100_000_000);
@@ -860,7 +867,7 @@ fn input_data() {
 	with_externalities(
 		&mut ExtBuilder::default().build(),
 		|| {
-			<CodeOf<Test>>::insert(1, code_input_data.to_vec());
+			code::set_code_of::<Test>(&1, code_input_data.to_vec());
 
 			Balances::set_free_balance(&0, 100_000_000);
 			Balances::increase_total_stake_by(100_000_000);
@@ -871,3 +878,110 @@ fn input_data() {
 		},
 	);
 }
+
+#[test]
+fn schedule_stored_before_versioning_is_migrated() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		// the encoding of a schedule before it got a version and a host function call cost.
+		let legacy = (2u64, 1u64, 1u64, 1u64, 1u64, 64u32 * 1024, 16u32);
+		runtime_support::storage::put(&<CurrentSchedule<Test>>::key(), &legacy);
+
+		let schedule = Schedule { grow_mem_cost: 2, ..Default::default() };
+		assert_eq!(Contract::current_schedule(), schedule);
+		assert_eq!(<CurrentSchedule<Test>>::get(), schedule);
+	});
+}
+
+#[test]
+fn schedule_is_only_replaced_by_newer_versions() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		let schedule = Schedule {
+			version: 1,
+			host_fn_call_cost: 2,
+			..Default::default()
+		};
+		assert_ok!(Contract::update_schedule(schedule.clone()));
+		assert_eq!(Contract::current_schedule(), schedule);
+		assert_eq!(System::events(), vec![
+			EventRecord {
+				phase: Phase::ApplyExtrinsic(0),
+				event: MetaEvent::contract(RawEvent::ScheduleUpdated(1)),
+			},
+		]);
+
+		assert_err!(
+			Contract::update_schedule(Schedule { host_fn_call_cost: 3, ..schedule.clone() }),
+			"new schedule must have a greater version than the current one"
+		);
+		assert_eq!(Contract::current_schedule(), schedule);
+	});
+}
+
+#[test]
+fn code_is_stored_once_and_removed_with_its_last_user() {
+	let code_nop = wabt::wat2wasm(CODE_NOP).unwrap();
+	let code_hash = BlakeTwo256::hash(&code_nop);
+
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		code::set_code_of::<Test>(&1, code_nop.to_vec());
+		code::set_code_of::<Test>(&2, code_nop.to_vec());
+		assert_eq!(<CodeHashOf<Test>>::get(1), Some(code_hash));
+		assert_eq!(<CodeHashOf<Test>>::get(2), Some(code_hash));
+		assert_eq!(<CodeRefCount<Test>>::get(code_hash), 2);
+
+		code::remove_code_of::<Test>(&1);
+		assert_eq!(<PristineCode<Test>>::get(code_hash), Some(code_nop.to_vec()));
+		assert_eq!(code::code_of::<Test>(&2), code_nop);
+
+		// replacing the code of the last user drops it.
+		code::set_code_of::<Test>(&2, vec![]);
+		assert!(!<PristineCode<Test>>::exists(code_hash));
+		assert!(!<CodeRefCount<Test>>::exists(code_hash));
+		assert!(code::code_of::<Test>(&1).is_empty());
+	});
+}
+
+#[test]
+fn code_stored_by_account_is_migrated_on_access() {
+	let code_nop = wabt::wat2wasm(CODE_NOP).unwrap();
+	let code_hash = BlakeTwo256::hash(&code_nop);
+
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		<CodeOf<Test>>::insert(1, code_nop.to_vec());
+		<CodeOf<Test>>::insert(2, code_nop.to_vec());
+
+		assert_eq!(code::code_of::<Test>(&1), code_nop);
+		assert!(!<CodeOf<Test>>::exists(1));
+		assert_eq!(<CodeHashOf<Test>>::get(1), Some(code_hash));
+		assert_eq!(<CodeRefCount<Test>>::get(code_hash), 1);
+
+		// legacy code is dropped along with its account.
+		code::remove_code_of::<Test>(&2);
+		assert!(!<CodeOf<Test>>::exists(2));
+		assert_eq!(<CodeRefCount<Test>>::get(code_hash), 1);
+	});
+}
+
+#[test]
+fn instrumented_code_is_cached_until_the_schedule_changes() {
+	let code_nop = wabt::wat2wasm(CODE_NOP).unwrap();
+	let code_hash = BlakeTwo256::hash(&code_nop);
+
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		code::set_code_of::<Test>(&1, code_nop.to_vec());
+
+		Balances::set_free_balance(&0, 100_000_000);
+		Balances::increase_total_stake_by(100_000_000);
+
+		assert!(!<CodeCache<Test>>::exists(code_hash));
+		assert_ok!(Contract::call(Origin::signed(0), 1, 0.into(), 100_000.into(), Vec::new()));
+		assert_eq!(<CodeCache<Test>>::get(code_hash).unwrap().schedule_version, 0);
+
+		assert_ok!(Contract::update_schedule(Schedule { version: 1, ..Default::default() }));
+		assert_ok!(Contract::call(Origin::signed(0), 1, 0.into(), 100_000.into(), Vec::new()));
+		assert_eq!(<CodeCache<Test>>::get(code_hash).unwrap().schedule_version, 1);
+
+		code::remove_code_of::<Test>(&1);
+		assert!(!<CodeCache<Test>>::exists(code_hash));
+	});
+}
@@ -31,9 +31,11 @@ mod prepare;
 mod env_def;
 mod runtime;
 
-use self::prepare::{prepare_contract, PreparedContract};
+use self::prepare::prepare_contract;
 use self::runtime::{to_execution_result, Runtime};
 
+pub use self::prepare::PrefabWasmModule;
+
 /// An interface that provides an access to the external environment in which the
 /// smart-contract is executed.
 ///
@@ -111,6 +113,15 @@ pub enum Error {
 	Memory,
 }
 
+/// Check the given code and instrument it according to the `schedule`.
+pub fn prepare<E: Ext>(
+	code: &[u8],
+	schedule: &Schedule<<E::T as Trait>::Gas>,
+) -> Result<PrefabWasmModule, Error> {
+	let env = runtime::init_env::<E>();
+	prepare_contract(code, schedule, &env)
+}
+
 /// Execute the given code as a contract.
 pub fn execute<'a, E: Ext>(
 	code: &[u8],
@@ -120,12 +131,22 @@ pub fn execute<'a, E: Ext>(
 	schedule: &Schedule<<E::T as Trait>::Gas>,
 	gas_meter: &mut GasMeter<E::T>,
 ) -> Result<(), Error> {
-	let env = runtime::init_env();
+	let prefab = prepare::<E>(code, schedule)?;
+	execute_prefab(&prefab, input_data, output_data, ext, schedule, gas_meter)
+}
 
-	let PreparedContract {
-		instrumented_code,
-		memory,
-	} = prepare_contract(code, &schedule, &env)?;
+/// Execute a contract which has already been prepared with `prepare`.
+pub fn execute_prefab<'a, E: Ext>(
+	prefab: &PrefabWasmModule,
+	input_data: &[u8],
+	output_data: &mut Vec<u8>,
+	ext: &'a mut E,
+	schedule: &Schedule<<E::T as Trait>::Gas>,
+	gas_meter: &mut GasMeter<E::T>,
+) -> Result<(), Error> {
+	let env = runtime::init_env::<E>();
+	let memory = sandbox::Memory::new(prefab.initial, Some(prefab.maximum))
+		.map_err(|_| Error::Memory)?;
 
 	let mut imports = sandbox::EnvironmentDefinitionBuilder::new();
 	for (func_name, ext_func) in &env.funcs {
@@ -136,7 +157,7 @@ pub fn execute<'a, E: Ext>(
 	let mut runtime = Runtime::new(ext, input_data, output_data, &schedule, memory, gas_meter);
 
 	// Instantiate the instance from the instrumented module code.
-	match sandbox::Instance::new(&instrumented_code, &imports, &mut runtime) {
+	match sandbox::Instance::new(&prefab.code, &imports, &mut runtime) {
 		// No errors or traps were generated on instantiation! That
 		// means we can now invoke the contract entrypoint.
 		Ok(mut instance) => {
@@ -286,11 +307,32 @@ mod tests {
 				data: vec![
 					1, 2, 3, 4,
 				],
-				gas_left: 49970,
+				gas_left: 49960,
 			}]
 		);
 	}
 
+	#[test]
+	fn host_function_calls_are_metered() {
+		let code_transfer = wabt::wat2wasm(CODE_TRANSFER).unwrap();
+		let schedule = Schedule {
+			host_fn_call_cost: 5,
+			..Schedule::<u64>::default()
+		};
+
+		let mut mock_ext = MockExt::default();
+		execute(
+			&code_transfer,
+			&[],
+			&mut Vec::new(),
+			&mut mock_ext,
+			&schedule,
+			&mut GasMeter::with_limit(50_000, 1),
+		).unwrap();
+
+		assert_eq!(mock_ext.transfers[0].gas_left, 49970 - 5);
+	}
+
 	const CODE_CREATE: &str = r#"
 (module
 	;; ext_create(
@@ -349,7 +391,7 @@ mod tests {
 				data: vec![
 					1, 2, 3, 4,
 				],
-				gas_left: 49970,
+				gas_left: 49960,
 			}]
 		);
 	}
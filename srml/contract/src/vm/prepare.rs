@@ -24,7 +24,6 @@ use parity_wasm::elements::{self, External, MemoryType, Type};
 use pwasm_utils;
 use pwasm_utils::rules;
 use runtime_primitives::traits::As;
-use sandbox;
 use {Trait, Schedule};
 
 struct ContractModule<'a, Gas: 'a> {
@@ -156,9 +155,19 @@ impl<'a, Gas: 'a + As<u32> + Clone> ContractModule<'a, Gas> {
 	}
 }
 
-pub(super) struct PreparedContract {
-	pub instrumented_code: Vec<u8>,
-	pub memory: sandbox::Memory,
+/// A contract module checked and instrumented against a particular version of the schedule,
+/// ready to be instantiated.
+#[cfg_attr(feature = "std", derive(Debug))]
+#[derive(Clone, PartialEq, Eq, Encode, Decode)]
+pub struct PrefabWasmModule {
+	/// Version of the schedule the code was instrumented with.
+	pub schedule_version: u32,
+	/// Initial number of pages of the imported memory.
+	pub initial: u32,
+	/// Maximum number of pages of the imported memory.
+	pub maximum: u32,
+	/// The instrumented code.
+	pub code: Vec<u8>,
 }
 
 /// Loads the given module given in `original_code`, performs some checks on it and
@@ -175,13 +184,13 @@ pub(super) fn prepare_contract<E: Ext>(
 	original_code: &[u8],
 	schedule: &Schedule<<E::T as Trait>::Gas>,
 	env: &HostFunctionSet<E>,
-) -> Result<PreparedContract, Error> {
+) -> Result<PrefabWasmModule, Error> {
 	let mut contract_module = ContractModule::new(original_code, schedule)?;
 	contract_module.ensure_no_internal_memory()?;
 	contract_module.inject_gas_metering()?;
 	contract_module.inject_stack_height_metering()?;
 
-	let memory = if let Some(memory_type) = contract_module.scan_imports(env)? {
+	let (initial, maximum) = if let Some(memory_type) = contract_module.scan_imports(env)? {
 		// Inspect the module to extract the initial and maximum page count.
 		let limits = memory_type.limits();
 		match (limits.initial(), limits.maximum()) {
@@ -199,35 +208,29 @@ pub(super) fn prepare_contract<E: Ext>(
 				// to configured maximum.
 				return Err(Error::Memory);
 			}
-			(initial, maximum) => sandbox::Memory::new(initial, maximum),
+			(initial, Some(maximum)) => (initial, maximum),
 		}
 	} else {
 		// If none memory imported then just crate an empty placeholder.
 		// Any access to it will lead to out of bounds trap.
-		sandbox::Memory::new(0, Some(0))
+		(0, 0)
 	};
-	let memory = memory.map_err(|_| Error::Memory)?;
 
-	Ok(PreparedContract {
-		instrumented_code: contract_module.into_wasm_code()?,
-		memory,
+	Ok(PrefabWasmModule {
+		schedule_version: schedule.version,
+		initial,
+		maximum,
+		code: contract_module.into_wasm_code()?,
 	})
 }
 
 #[cfg(test)]
 mod tests {
 	use super::*;
-	use std::fmt;
 	use vm::tests::MockExt;
 	use wabt;
 
-	impl fmt::Debug for PreparedContract {
-		fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-			write!(f, "PreparedContract {{ .. }}")
-		}
-	}
-
-	fn parse_and_prepare_wat(wat: &str) -> Result<PreparedContract, Error> {
+	fn parse_and_prepare_wat(wat: &str) -> Result<PrefabWasmModule, Error> {
 		let wasm = wabt::Wat2Wasm::new().validate(false).convert(wat).unwrap();
 		let schedule = Schedule::<u64>::default();
 		let env = ::vm::runtime::init_env();
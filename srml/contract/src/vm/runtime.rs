@@ -93,6 +93,8 @@ pub(crate) fn to_execution_result<E: Ext>(
 
 /// Charge the specified amount of gas.
 ///
+/// Every host function but `gas` first charges `host_fn_call_cost` of the schedule.
+///
 /// Returns `Err` if there is not enough gas.
 fn charge_gas<T: Trait>(
 	gas_meter: &mut GasMeter<T>,
@@ -185,6 +187,8 @@ define_env!(init_env, <E: Ext>,
 	//   where the value to set is placed. If `value_non_null` is set to 0, then this parameter is ignored.
 	// - value_len: the length of the value. If `value_non_null` is set to 0, then this parameter is ignored.
 	ext_set_storage(ctx, key_ptr: u32, value_non_null: u32, value_ptr: u32, value_len: u32) => {
+		charge_gas(ctx.gas_meter, ctx.schedule.host_fn_call_cost)?;
+
 		let key = read_sandbox_memory(ctx, key_ptr, 32)?;
 		let value =
 			if value_non_null != 0 {
@@ -204,6 +208,8 @@ define_env!(init_env, <E: Ext>,
 	// - key_ptr: pointer into the linear memory where the key
 	//   of the requested value is placed.
 	ext_get_storage(ctx, key_ptr: u32) -> u32 => {
+		charge_gas(ctx.gas_meter, ctx.schedule.host_fn_call_cost)?;
+
 		let key = read_sandbox_memory(ctx, key_ptr, 32)?;
 		if let Some(value) = ctx.ext.get_storage(&key) {
 			ctx.scratch_buf = value;
@@ -239,6 +245,8 @@ define_env!(init_env, <E: Ext>,
 		input_data_ptr: u32,
 		input_data_len: u32
 	) -> u32 => {
+		charge_gas(ctx.gas_meter, ctx.schedule.host_fn_call_cost)?;
+
 		let callee = {
 			let callee_buf = read_sandbox_memory(ctx, callee_ptr, callee_len)?;
 			<<E as Ext>::T as system::Trait>::AccountId::decode(&mut &callee_buf[..])
@@ -302,6 +310,8 @@ define_env!(init_env, <E: Ext>,
 		input_data_ptr: u32,
 		input_data_len: u32
 	) -> u32 => {
+		charge_gas(ctx.gas_meter, ctx.schedule.host_fn_call_cost)?;
+
 		let init_code = read_sandbox_memory(ctx, init_code_ptr, init_code_len)?;
 		let value = {
 			let value_buf = read_sandbox_memory(ctx, value_ptr, value_len)?;
@@ -339,6 +349,8 @@ define_env!(init_env, <E: Ext>,
 	// Save a data buffer as a result of the execution, terminate the execution and return a
 	// successful result to the caller.
 	ext_return(ctx, data_ptr: u32, data_len: u32) => {
+		charge_gas(ctx.gas_meter, ctx.schedule.host_fn_call_cost)?;
+
 		let data_len_in_gas = <<E::T as Trait>::Gas as As<u64>>::sa(data_len as u64);
 		let price = (ctx.schedule.return_data_per_byte_cost)
 			.checked_mul(&data_len_in_gas)
@@ -362,12 +374,16 @@ define_env!(init_env, <E: Ext>,
 
 	// Returns the size of the input buffer.
 	ext_input_size(ctx) -> u32 => {
+		charge_gas(ctx.gas_meter, ctx.schedule.host_fn_call_cost)?;
+
 		Ok(ctx.input_data.len() as u32)
 	},
 
 	// Copy data from the input buffer starting from `offset` with length `len` into the contract memory.
 	// The region at which the data should be put is specified by `dest_ptr`.
 	ext_input_copy(ctx, dest_ptr: u32, offset: u32, len: u32) => {
+		charge_gas(ctx.gas_meter, ctx.schedule.host_fn_call_cost)?;
+
 		let offset = offset as usize;
 		if offset > ctx.input_data.len() {
 			// Offset can't be larger than input buffer length.
@@ -394,12 +410,16 @@ define_env!(init_env, <E: Ext>,
 
 	// Returns the size of the scratch buffer.
 	ext_scratch_size(ctx) -> u32 => {
+		charge_gas(ctx.gas_meter, ctx.schedule.host_fn_call_cost)?;
+
 		Ok(ctx.scratch_buf.len() as u32)
 	},
 
 	// Copy data from the scratch buffer starting from `offset` with length `len` into the contract memory.
 	// The region at which the data should be put is specified by `dest_ptr`.
 	ext_scratch_copy(ctx, dest_ptr: u32, offset: u32, len: u32) => {
+		charge_gas(ctx.gas_meter, ctx.schedule.host_fn_call_cost)?;
+
 		let offset = offset as usize;
 		if offset > ctx.scratch_buf.len() {
 			// Offset can't be larger than scratch buffer length.
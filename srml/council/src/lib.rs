@@ -78,6 +78,7 @@ mod tests {
 	#[derive(Clone, Eq, PartialEq, Debug)]
 	pub struct Test;
 	impl system::Trait for Test {
+		const SPEC_VERSION: u32 = 0;
 		type Origin = Origin;
 		type Index = u64;
 		type BlockNumber = u64;
@@ -88,16 +89,22 @@ mod tests {
 		type Header = Header;
 		type Event = Event;
 		type Log = DigestItem;
+		type BaseCallFilter = ();
+		type CallWeigher = ();
 	}
 	impl balances::Trait for Test {
 		type Balance = u64;
 		type AccountIndex = u64;
 		type OnFreeBalanceZero = ();
 		type EnsureAccountLiquid = ();
+		type DustRemoval = ();
+		type OnChargeTransaction = balances::Module<Test>;
 		type Event = Event;
 	}
 	impl democracy::Trait for Test {
 		type Proposal = Call;
+		type FastTrackOrigin = system::EnsureRoot<u64>;
+		type CancellationOrigin = system::EnsureRoot<u64>;
 		type Event = Event;
 	}
 	impl seats::Trait for Test {
@@ -118,6 +125,7 @@ mod tests {
 			balances: vec![(1, 10), (2, 20), (3, 30), (4, 40), (5, 50), (6, 60)],
 			transaction_base_fee: 0,
 			transaction_byte_fee: 0,
+			transaction_weight_fee: 0,
 			existential_deposit: 0,
 			transfer_fee: 0,
 			creation_fee: 0,
@@ -127,7 +135,9 @@ mod tests {
 		t.extend(democracy::GenesisConfig::<Test>{
 			launch_period: 1,
 			voting_period: 3,
+			minimum_voting_period: 1,
 			minimum_deposit: 1,
+			preimage_byte_deposit: 0,
 			_genesis_phantom_data: Default::default(),
 		}.build_storage().unwrap().0);
 		t.extend(seats::GenesisConfig::<Test> {
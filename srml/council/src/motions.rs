@@ -23,6 +23,7 @@ use substrate_primitives::u32_trait::Value as U32;
 use primitives::traits::{Hash, EnsureOrigin};
 use srml_support::dispatch::{Result, Dispatchable, Parameter};
 use srml_support::{StorageValue, StorageMap};
+use srml_support::traits::{Contains, EitherOf};
 use super::{Trait as CouncilTrait, Module as Council};
 use system::{self, ensure_signed};
 
@@ -179,8 +180,7 @@ decl_storage! {
 
 impl<T: Trait> Module<T> {
 	pub fn is_councillor(who: &T::AccountId) -> bool {
-		<Council<T>>::active_council().iter()
-			.any(|&(ref a, _)| a == who)
+		<Council<T>>::contains(who)
 	}
 }
 
@@ -205,6 +205,34 @@ impl<O, N: U32> EnsureOrigin<O> for EnsureMembers<N>
 	}
 }
 
+/// Ensure that the origin `o` represents more than half of the current council. Returns
+/// the number of approving members or an `Err` otherwise.
+pub fn ensure_council_majority<T: Trait, OuterOrigin>(o: OuterOrigin) -> result::Result<u32, &'static str>
+	where OuterOrigin: Into<Option<Origin>>
+{
+	let council_size = <Council<T>>::active_council().len() as u32;
+	match o.into() {
+		Some(Origin::Members(x)) if x * 2 > council_size => Ok(x),
+		_ => Err("bad origin: expected to be a majority of the council"),
+	}
+}
+
+pub struct EnsureMajority<T>(::rstd::marker::PhantomData<T>);
+impl<O, T: Trait> EnsureOrigin<O> for EnsureMajority<T>
+	where O: Into<Option<Origin>>
+{
+	type Success = u32;
+	fn ensure_origin(o: O) -> result::Result<Self::Success, &'static str> {
+		ensure_council_majority::<T, _>(o)
+	}
+}
+
+/// Ensures that the origin is either root or a majority of the current council.
+pub type EnsureRootOrMajority<T> = EitherOf<
+	system::EnsureRoot<<T as system::Trait>::AccountId>,
+	EnsureMajority<T>,
+>;
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -359,4 +387,17 @@ mod tests {
 			]);
 		});
 	}
+
+	#[test]
+	fn root_or_majority_accepts_root_and_more_than_half_of_the_council() {
+		with_externalities(&mut new_test_ext(true), || {
+			type Check = EnsureRootOrMajority<Test>;
+			let members = |n| -> Origin { super::Origin::Members(n).into() };
+
+			assert!(Check::ensure_origin(Origin::ROOT).is_ok());
+			assert!(Check::ensure_origin(members(2)).is_ok());
+			assert!(Check::ensure_origin(members(1)).is_err());
+			assert!(Check::ensure_origin(Origin::signed(1)).is_err());
+		});
+	}
 }
@@ -20,7 +20,7 @@ use rstd::prelude::*;
 use codec::{Compact, HasCompact};
 use primitives::traits::{Zero, One, As};
 use runtime_io::print;
-use srml_support::{StorageValue, StorageMap, dispatch::Result};
+use srml_support::{StorageValue, StorageMap, dispatch::Result, traits::Contains};
 use democracy;
 use balances::{self, address::Address};
 use system::{self, ensure_signed};
@@ -549,6 +549,12 @@ impl<T: Trait> Module<T> {
 	}
 }
 
+impl<T: Trait> Contains<T::AccountId> for Module<T> {
+	fn contains(who: &T::AccountId) -> bool {
+		Self::active_council().iter().any(|&(ref a, _)| a == who)
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
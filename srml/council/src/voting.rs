@@ -19,7 +19,7 @@
 use rstd::prelude::*;
 use rstd::borrow::Borrow;
 use codec::HasCompact;
-use primitives::traits::{Hash, As};
+use primitives::traits::As;
 use runtime_io::print;
 use srml_support::dispatch::Result;
 use srml_support::{StorageValue, StorageMap, IsSubType};
@@ -35,15 +35,16 @@ decl_module! {
 	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
 		fn deposit_event() = default;
 
-		fn propose(origin, proposal: Box<T::Proposal>) -> Result {
+		/// Propose a referendum, given by the hash of its call. The preimage of the call must have
+		/// been noted in the democracy module.
+		fn propose(origin, proposal_hash: T::Hash) -> Result {
 			let who = ensure_signed(origin)?;
 
 			let expiry = <system::Module<T>>::block_number() + Self::voting_period();
 			ensure!(Self::will_still_be_councillor_at(&who, expiry), "proposer would not be on council");
 
-			let proposal_hash = T::Hashing::hash_of(&proposal);
-
-			ensure!(!<ProposalOf<T>>::exists(proposal_hash), "duplicate proposals not allowed");
+			ensure!(<democracy::Module<T>>::preimage_proposal(&proposal_hash).is_some(), "proposal preimage must be noted");
+			ensure!(!<ProposalVoters<T>>::exists(proposal_hash), "duplicate proposals not allowed");
 			ensure!(!Self::is_vetoed(&proposal_hash), "proposal is vetoed");
 
 			let mut proposals = Self::proposals();
@@ -51,7 +52,6 @@ decl_module! {
 			proposals.sort_by_key(|&(expiry, _)| expiry);
 			Self::set_proposals(&proposals);
 
-			<ProposalOf<T>>::insert(proposal_hash, *proposal);
 			<ProposalVoters<T>>::insert(proposal_hash, vec![who.clone()]);
 			<CouncilVoteOf<T>>::insert((proposal_hash, who.clone()), true);
 
@@ -92,7 +92,6 @@ decl_module! {
 				&Self::proposals().into_iter().filter(|&(_, h)| h != proposal_hash
 			).collect::<Vec<_>>());
 			<ProposalVoters<T>>::remove(proposal_hash);
-			<ProposalOf<T>>::remove(proposal_hash);
 			for (c, _) in <Council<T>>::active_council() {
 				<CouncilVoteOf<T>>::remove((proposal_hash, c));
 			}
@@ -123,7 +122,6 @@ decl_storage! {
 		pub CooloffPeriod get(cooloff_period) config(): T::BlockNumber = T::BlockNumber::sa(1000);
 		pub VotingPeriod get(voting_period) config(): T::BlockNumber = T::BlockNumber::sa(3);
 		pub Proposals get(proposals) build(|_| vec![0u8; 0]): Vec<(T::BlockNumber, T::Hash)>; // ordered by expiry.
-		pub ProposalOf get(proposal_of): map T::Hash => Option<T::Proposal>;
 		pub ProposalVoters get(proposal_voters): map T::Hash => Vec<T::AccountId>;
 		pub CouncilVoteOf get(vote_of): map (T::Hash, T::AccountId) => Option<bool>;
 		pub VetoedProposal get(veto_of): map T::Hash => Option<(T::BlockNumber, Vec<T::AccountId>)>;
@@ -191,21 +189,27 @@ impl<T: Trait> Module<T> {
 		<Proposals<T>>::put(p);
 	}
 
-	fn take_proposal_if_expiring_at(n: T::BlockNumber) -> Option<(T::Proposal, T::Hash)> {
+	fn take_proposal_if_expiring_at(n: T::BlockNumber) -> Option<T::Hash> {
 		let proposals = Self::proposals();
 		match proposals.first() {
 			Some(&(expiry, hash)) if expiry == n => {
 				// yes this is horrible, but fixing it will need substantial work in storage.
 				Self::set_proposals(&proposals[1..].to_vec());
-				<ProposalOf<T>>::take(hash).map(|p| (p, hash))	/* defensive only: all queued proposal hashes must have associated proposals*/
+				<ProposalVoters<T>>::remove(hash);
+				Some(hash)
 			}
 			_ => None,
 		}
 	}
 
 	fn end_block(now: T::BlockNumber) -> Result {
-		while let Some((proposal, proposal_hash)) = Self::take_proposal_if_expiring_at(now) {
+		while let Some(proposal_hash) = Self::take_proposal_if_expiring_at(now) {
 			let tally = Self::take_tally(&proposal_hash);
+			// the preimage may have been reaped since the proposal was made, leaving nothing to enact.
+			let proposal = match <democracy::Module<T>>::preimage_proposal(&proposal_hash) {
+				Some(proposal) => proposal,
+				None => continue,
+			};
 			if let Some(&democracy::Call::cancel_referendum(ref_index)) = IsSubType::<democracy::Module<T>>::is_aux_sub_type(&proposal) {
 				Self::deposit_event(RawEvent::TallyCancelation(proposal_hash, tally.0, tally.1, tally.2));
 				if let (_, 0, 0) = tally {
@@ -216,8 +220,8 @@ impl<T: Trait> Module<T> {
 				if tally.0 > tally.1 + tally.2 {
 					Self::kill_veto_of(&proposal_hash);
 					match tally {
-						(_, 0, 0) => <democracy::Module<T>>::internal_start_referendum(proposal, democracy::VoteThreshold::SuperMajorityAgainst).map(|_| ())?,
-						_ => <democracy::Module<T>>::internal_start_referendum(proposal, democracy::VoteThreshold::SimpleMajority).map(|_| ())?,
+						(_, 0, 0) => <democracy::Module<T>>::internal_start_referendum(proposal_hash, democracy::VoteThreshold::SuperMajorityAgainst).map(|_| ())?,
+						_ => <democracy::Module<T>>::internal_start_referendum(proposal_hash, democracy::VoteThreshold::SimpleMajority).map(|_| ())?,
 					};
 				}
 			}
@@ -229,6 +233,7 @@ impl<T: Trait> Module<T> {
 #[cfg(test)]
 mod tests {
 	use super::*;
+	use codec::Encode;
 	use ::tests::*;
 	use ::tests::{Call, Origin};
 	use srml_support::Hashable;
@@ -262,17 +267,26 @@ mod tests {
 		Call::Democracy(democracy::Call::cancel_referendum(id.into()))
 	}
 
+	/// Note the preimage of the proposal if needed and return its hash.
+	fn noted(proposal: &Call) -> H256 {
+		let hash = proposal.blake2_256().into();
+		if Democracy::preimages(hash).is_none() {
+			assert_ok!(Democracy::note_preimage(Origin::signed(1), proposal.encode()));
+		}
+		hash
+	}
+
 	#[test]
 	fn referendum_cancellation_should_work_when_unanimous() {
 		with_externalities(&mut new_test_ext(true), || {
 			System::set_block_number(1);
 			let proposal = set_balance_proposal(42);
-			assert_ok!(Democracy::internal_start_referendum(proposal.clone(), VoteThreshold::SuperMajorityApprove), 0);
-			assert_eq!(Democracy::active_referendums(), vec![(0, 4, proposal, VoteThreshold::SuperMajorityApprove)]);
+			assert_ok!(Democracy::internal_start_referendum(noted(&proposal), VoteThreshold::SuperMajorityApprove), 0);
+			assert_eq!(Democracy::active_referendums(), vec![(0, 4, H256::from(proposal.blake2_256()), VoteThreshold::SuperMajorityApprove)]);
 
 			let cancellation = cancel_referendum_proposal(0);
 			let hash = cancellation.blake2_256().into();
-			assert_ok!(CouncilVoting::propose(Origin::signed(1), Box::new(cancellation)));
+			assert_ok!(CouncilVoting::propose(Origin::signed(1), noted(&cancellation)));
 			assert_ok!(CouncilVoting::vote(Origin::signed(2), hash, true));
 			assert_ok!(CouncilVoting::vote(Origin::signed(3), hash, true));
 			assert_eq!(CouncilVoting::proposals(), vec![(2, hash)]);
@@ -290,18 +304,18 @@ mod tests {
 		with_externalities(&mut new_test_ext(true), || {
 			System::set_block_number(1);
 			let proposal = set_balance_proposal(42);
-			assert_ok!(Democracy::internal_start_referendum(proposal.clone(), VoteThreshold::SuperMajorityApprove), 0);
+			assert_ok!(Democracy::internal_start_referendum(noted(&proposal), VoteThreshold::SuperMajorityApprove), 0);
 
 			let cancellation = cancel_referendum_proposal(0);
 			let hash = cancellation.blake2_256().into();
-			assert_ok!(CouncilVoting::propose(Origin::signed(1), Box::new(cancellation)));
+			assert_ok!(CouncilVoting::propose(Origin::signed(1), noted(&cancellation)));
 			assert_ok!(CouncilVoting::vote(Origin::signed(2), hash, true));
 			assert_ok!(CouncilVoting::vote(Origin::signed(3), hash, false));
 			assert_ok!(CouncilVoting::end_block(System::block_number()));
 
 			System::set_block_number(2);
 			assert_ok!(CouncilVoting::end_block(System::block_number()));
-			assert_eq!(Democracy::active_referendums(), vec![(0, 4, proposal, VoteThreshold::SuperMajorityApprove)]);
+			assert_eq!(Democracy::active_referendums(), vec![(0, 4, H256::from(proposal.blake2_256()), VoteThreshold::SuperMajorityApprove)]);
 		});
 	}
 
@@ -310,17 +324,17 @@ mod tests {
 		with_externalities(&mut new_test_ext(true), || {
 			System::set_block_number(1);
 			let proposal = set_balance_proposal(42);
-			assert_ok!(Democracy::internal_start_referendum(proposal.clone(), VoteThreshold::SuperMajorityApprove), 0);
+			assert_ok!(Democracy::internal_start_referendum(noted(&proposal), VoteThreshold::SuperMajorityApprove), 0);
 
 			let cancellation = cancel_referendum_proposal(0);
 			let hash = cancellation.blake2_256().into();
-			assert_ok!(CouncilVoting::propose(Origin::signed(1), Box::new(cancellation)));
+			assert_ok!(CouncilVoting::propose(Origin::signed(1), noted(&cancellation)));
 			assert_ok!(CouncilVoting::vote(Origin::signed(2), hash, true));
 			assert_ok!(CouncilVoting::end_block(System::block_number()));
 
 			System::set_block_number(2);
 			assert_ok!(CouncilVoting::end_block(System::block_number()));
-			assert_eq!(Democracy::active_referendums(), vec![(0, 4, proposal, VoteThreshold::SuperMajorityApprove)]);
+			assert_eq!(Democracy::active_referendums(), vec![(0, 4, H256::from(proposal.blake2_256()), VoteThreshold::SuperMajorityApprove)]);
 		});
 	}
 
@@ -330,7 +344,7 @@ mod tests {
 			System::set_block_number(1);
 			let proposal = set_balance_proposal(42);
 			let hash = proposal.blake2_256().into();
-			assert_ok!(CouncilVoting::propose(Origin::signed(1), Box::new(proposal.clone())));
+			assert_ok!(CouncilVoting::propose(Origin::signed(1), noted(&proposal)));
 			assert_ok!(CouncilVoting::veto(Origin::signed(2), hash));
 			assert_eq!(CouncilVoting::proposals().len(), 0);
 			assert_eq!(Democracy::active_referendums().len(), 0);
@@ -343,11 +357,11 @@ mod tests {
 			System::set_block_number(1);
 			let proposal = set_balance_proposal(42);
 			let hash = proposal.blake2_256().into();
-			assert_ok!(CouncilVoting::propose(Origin::signed(1), Box::new(proposal.clone())));
+			assert_ok!(CouncilVoting::propose(Origin::signed(1), noted(&proposal)));
 			assert_ok!(CouncilVoting::veto(Origin::signed(2), hash));
 
 			System::set_block_number(3);
-			assert_ok!(CouncilVoting::propose(Origin::signed(1), Box::new(proposal.clone())));
+			assert_ok!(CouncilVoting::propose(Origin::signed(1), noted(&proposal)));
 			assert_noop!(CouncilVoting::veto(Origin::signed(2), hash), "a councillor may not veto a proposal twice");
 		});
 	}
@@ -358,11 +372,11 @@ mod tests {
 			System::set_block_number(1);
 			let proposal = set_balance_proposal(42);
 			let hash = proposal.blake2_256().into();
-			assert_ok!(CouncilVoting::propose(Origin::signed(1), Box::new(proposal.clone())));
+			assert_ok!(CouncilVoting::propose(Origin::signed(1), noted(&proposal)));
 			assert_ok!(CouncilVoting::veto(Origin::signed(2), hash));
 
 			System::set_block_number(2);
-			assert_noop!(CouncilVoting::propose(Origin::signed(1), Box::new(proposal.clone())), "proposal is vetoed");
+			assert_noop!(CouncilVoting::propose(Origin::signed(1), noted(&proposal)), "proposal is vetoed");
 		});
 	}
 
@@ -372,11 +386,11 @@ mod tests {
 			System::set_block_number(1);
 			let proposal = set_balance_proposal(42);
 			let hash = proposal.blake2_256().into();
-			assert_ok!(CouncilVoting::propose(Origin::signed(1), Box::new(proposal.clone())));
+			assert_ok!(CouncilVoting::propose(Origin::signed(1), noted(&proposal)));
 			assert_ok!(CouncilVoting::veto(Origin::signed(2), hash));
 
 			System::set_block_number(3);
-			assert_ok!(CouncilVoting::propose(Origin::signed(1), Box::new(proposal.clone())));
+			assert_ok!(CouncilVoting::propose(Origin::signed(1), noted(&proposal)));
 			assert_ok!(CouncilVoting::vote(Origin::signed(2), hash, false));
 			assert_ok!(CouncilVoting::vote(Origin::signed(3), hash, true));
 			assert_ok!(CouncilVoting::end_block(System::block_number()));
@@ -384,7 +398,7 @@ mod tests {
 			System::set_block_number(4);
 			assert_ok!(CouncilVoting::end_block(System::block_number()));
 			assert_eq!(CouncilVoting::proposals().len(), 0);
-			assert_eq!(Democracy::active_referendums(), vec![(0, 7, set_balance_proposal(42), VoteThreshold::SimpleMajority)]);
+			assert_eq!(Democracy::active_referendums(), vec![(0, 7, H256::from(set_balance_proposal(42).blake2_256()), VoteThreshold::SimpleMajority)]);
 		});
 	}
 
@@ -394,11 +408,11 @@ mod tests {
 			System::set_block_number(1);
 			let proposal = set_balance_proposal(42);
 			let hash = proposal.blake2_256().into();
-			assert_ok!(CouncilVoting::propose(Origin::signed(1), Box::new(proposal.clone())));
+			assert_ok!(CouncilVoting::propose(Origin::signed(1), noted(&proposal)));
 			assert_ok!(CouncilVoting::veto(Origin::signed(2), hash));
 
 			System::set_block_number(3);
-			assert_ok!(CouncilVoting::propose(Origin::signed(1), Box::new(proposal.clone())));
+			assert_ok!(CouncilVoting::propose(Origin::signed(1), noted(&proposal)));
 			assert_ok!(CouncilVoting::veto(Origin::signed(3), hash));
 			assert_eq!(CouncilVoting::proposals().len(), 0);
 			assert_eq!(Democracy::active_referendums().len(), 0);
@@ -411,7 +425,7 @@ mod tests {
 			System::set_block_number(1);
 			let proposal = set_balance_proposal(42);
 			let hash = proposal.blake2_256().into();
-			assert_ok!(CouncilVoting::propose(Origin::signed(1), Box::new(proposal.clone())));
+			assert_ok!(CouncilVoting::propose(Origin::signed(1), noted(&proposal)));
 			assert_eq!(CouncilVoting::proposals().len(), 1);
 			assert_eq!(CouncilVoting::proposal_voters(&hash), vec![1]);
 			assert_eq!(CouncilVoting::vote_of((hash, 1)), Some(true));
@@ -424,7 +438,7 @@ mod tests {
 		with_externalities(&mut new_test_ext(true), || {
 			System::set_block_number(1);
 			let proposal = set_balance_proposal(42);
-			assert_ok!(CouncilVoting::propose(Origin::signed(1), Box::new(proposal.clone())));
+			assert_ok!(CouncilVoting::propose(Origin::signed(1), noted(&proposal)));
 			assert_eq!(CouncilVoting::tally(&proposal.blake2_256().into()), (1, 0, 2));
 			assert_ok!(CouncilVoting::end_block(System::block_number()));
 
@@ -440,7 +454,7 @@ mod tests {
 		with_externalities(&mut new_test_ext(true), || {
 			System::set_block_number(1);
 			let proposal = set_balance_proposal(42);
-			assert_ok!(CouncilVoting::propose(Origin::signed(1), Box::new(proposal.clone())));
+			assert_ok!(CouncilVoting::propose(Origin::signed(1), noted(&proposal)));
 			assert_ok!(CouncilVoting::vote(Origin::signed(2), proposal.blake2_256().into(), true));
 			assert_ok!(CouncilVoting::vote(Origin::signed(3), proposal.blake2_256().into(), true));
 			assert_eq!(CouncilVoting::tally(&proposal.blake2_256().into()), (3, 0, 0));
@@ -449,7 +463,7 @@ mod tests {
 			System::set_block_number(2);
 			assert_ok!(CouncilVoting::end_block(System::block_number()));
 			assert_eq!(CouncilVoting::proposals().len(), 0);
-			assert_eq!(Democracy::active_referendums(), vec![(0, 5, proposal, VoteThreshold::SuperMajorityAgainst)]);
+			assert_eq!(Democracy::active_referendums(), vec![(0, 5, H256::from(proposal.blake2_256()), VoteThreshold::SuperMajorityAgainst)]);
 		});
 	}
 
@@ -458,7 +472,7 @@ mod tests {
 		with_externalities(&mut new_test_ext(true), || {
 			System::set_block_number(1);
 			let proposal = set_balance_proposal(42);
-			assert_ok!(CouncilVoting::propose(Origin::signed(1), Box::new(proposal.clone())));
+			assert_ok!(CouncilVoting::propose(Origin::signed(1), noted(&proposal)));
 			assert_ok!(CouncilVoting::vote(Origin::signed(2), proposal.blake2_256().into(), true));
 			assert_ok!(CouncilVoting::vote(Origin::signed(3), proposal.blake2_256().into(), false));
 			assert_eq!(CouncilVoting::tally(&proposal.blake2_256().into()), (2, 1, 0));
@@ -467,7 +481,7 @@ mod tests {
 			System::set_block_number(2);
 			assert_ok!(CouncilVoting::end_block(System::block_number()));
 			assert_eq!(CouncilVoting::proposals().len(), 0);
-			assert_eq!(Democracy::active_referendums(), vec![(0, 5, proposal, VoteThreshold::SimpleMajority)]);
+			assert_eq!(Democracy::active_referendums(), vec![(0, 5, H256::from(proposal.blake2_256()), VoteThreshold::SimpleMajority)]);
 		});
 	}
 
@@ -475,8 +489,17 @@ mod tests {
 	fn propose_by_public_should_not_work() {
 		with_externalities(&mut new_test_ext(true), || {
 			System::set_block_number(1);
-			let proposal = set_balance_proposal(42);
-			assert_noop!(CouncilVoting::propose(Origin::signed(4), Box::new(proposal)), "proposer would not be on council");
+			let hash = noted(&set_balance_proposal(42));
+			assert_noop!(CouncilVoting::propose(Origin::signed(4), hash), "proposer would not be on council");
+		});
+	}
+
+	#[test]
+	fn propose_without_preimage_should_not_work() {
+		with_externalities(&mut new_test_ext(true), || {
+			System::set_block_number(1);
+			let hash = set_balance_proposal(42).blake2_256().into();
+			assert_noop!(CouncilVoting::propose(Origin::signed(1), hash), "proposal preimage must be noted");
 		});
 	}
 
@@ -485,7 +508,7 @@ mod tests {
 		with_externalities(&mut new_test_ext(true), || {
 			System::set_block_number(1);
 			let proposal = set_balance_proposal(42);
-			assert_ok!(CouncilVoting::propose(Origin::signed(1), Box::new(proposal.clone())));
+			assert_ok!(CouncilVoting::propose(Origin::signed(1), noted(&proposal)));
 			assert_noop!(CouncilVoting::vote(Origin::signed(4), proposal.blake2_256().into(), true), "only councillors may vote on council proposals");
 		});
 	}
@@ -15,6 +15,11 @@
 // along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
 
 //! Democratic system: Handles administration of general stakeholder voting.
+//!
+//! Proposals and referenda only refer to the hash of the call they would dispatch. The call
+//! itself, its preimage, is noted separately with `note_preimage` against a deposit per byte,
+//! so that large calls such as runtime upgrades are only stored once and only needed when the
+//! referendum passes. Preimages that are no longer referenced can be reaped to free the deposit.
 
 #![cfg_attr(not(feature = "std"), no_std)]
 
@@ -36,10 +41,11 @@ extern crate srml_system as system;
 
 use rstd::prelude::*;
 use rstd::result;
-use codec::{HasCompact, Compact};
-use primitives::traits::{Zero, As};
+use codec::{HasCompact, Compact, Decode};
+use primitives::traits::{Zero, As, EnsureOrigin, Hash};
 use srml_support::{StorageValue, StorageMap, Parameter, Dispatchable, IsSubType};
 use srml_support::dispatch::Result;
+use srml_support::deposit::{Footprint, StorageDeposit};
 use system::ensure_signed;
 
 mod vote_threshold;
@@ -53,6 +59,12 @@ pub type ReferendumIndex = u32;
 pub trait Trait: balances::Trait + Sized {
 	type Proposal: Parameter + Dispatchable<Origin=Self::Origin> + IsSubType<Module<Self>>;
 
+	/// Origin from which referenda may be fast-tracked, e.g. for an emergency runtime fix.
+	type FastTrackOrigin: EnsureOrigin<Self::Origin>;
+
+	/// Origin from which any referendum may be cancelled.
+	type CancellationOrigin: EnsureOrigin<Self::Origin>;
+
 	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
 }
 
@@ -60,10 +72,11 @@ decl_module! {
 	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
 		fn deposit_event() = default;
 
-		/// Propose a sensitive action to be taken.
+		/// Propose a sensitive action to be taken, given by the hash of its call. The preimage of the
+		/// call must be noted before the proposal can be enacted.
 		fn propose(
 			origin,
-			proposal: Box<T::Proposal>,
+			proposal_hash: T::Hash,
 			value: <T::Balance as HasCompact>::Type
 		) -> Result {
 			let who = ensure_signed(origin)?;
@@ -78,7 +91,7 @@ decl_module! {
 			<DepositOf<T>>::insert(index, (value, vec![who.clone()]));
 
 			let mut props = Self::public_props();
-			props.push((index, (*proposal).clone(), who));
+			props.push((index, proposal_hash, who));
 			<PublicProps<T>>::put(props);
 			Ok(())
 		}
@@ -89,6 +102,8 @@ decl_module! {
 			let proposal: PropIndex = proposal.into();
 			let mut deposit = Self::deposit_of(proposal)
 				.ok_or("can only second an existing proposal")?;
+			deposit.0.checked_mul(&T::Balance::sa(deposit.1.len() as u64 + 1))
+				.ok_or("locked deposit would overflow")?;
 			<balances::Module<T>>::reserve(&who, deposit.0)
 				.map_err(|_| "seconder's balance too low")?;
 			deposit.1.push(who);
@@ -112,20 +127,91 @@ decl_module! {
 		}
 
 		/// Start a referendum.
-		fn start_referendum(proposal: Box<T::Proposal>, vote_threshold: VoteThreshold) -> Result {
+		fn start_referendum(proposal_hash: T::Hash, vote_threshold: VoteThreshold) -> Result {
 			Self::inject_referendum(
 				<system::Module<T>>::block_number() + Self::voting_period(),
-				*proposal,
+				proposal_hash,
 				vote_threshold
 			).map(|_| ())
 		}
 
+		/// Note the preimage of a proposal, reserving a deposit for each of its bytes.
+		fn note_preimage(origin, encoded_proposal: Vec<u8>) -> Result {
+			let who = ensure_signed(origin)?;
+			let proposal_hash = T::Hashing::hash(&encoded_proposal[..]);
+			ensure!(!<Preimages<T>>::exists(&proposal_hash), "preimage already noted");
+
+			let storage_deposit = StorageDeposit { per_byte: Self::preimage_byte_deposit(), per_item: Zero::zero() };
+			let footprint = Footprint { bytes: encoded_proposal.len() as u32, items: 0 };
+			let deposit = storage_deposit.charge::<balances::Module<T>, _>(&who, footprint)
+				.map_err(|_| "not enough balance to note preimage")?;
+
+			let now = <system::Module<T>>::block_number();
+			<Preimages<T>>::insert(proposal_hash, (encoded_proposal, Some((who.clone(), deposit)), now));
+			Self::deposit_event(RawEvent::PreimageNoted(proposal_hash, who, deposit));
+			Ok(())
+		}
+
+		/// Remove a preimage no proposal or referendum refers to. Its provider gets the deposit
+		/// back; anyone else may only reap it a voting period after it was noted, and collects the
+		/// deposit.
+		fn reap_preimage(origin, proposal_hash: T::Hash) -> Result {
+			let who = ensure_signed(origin)?;
+			let (_, deposit, noted_at) = Self::preimages(&proposal_hash).ok_or("unknown preimage")?;
+			ensure!(!Self::is_referenced(&proposal_hash), "preimage is still referenced");
+
+			if let Some((provider, deposit)) = deposit {
+				if provider == who {
+					srml_support::deposit::refund::<balances::Module<T>, _>(&provider, deposit);
+				} else {
+					let now = <system::Module<T>>::block_number();
+					ensure!(now >= noted_at + Self::voting_period(), "too early to reap preimage");
+					<balances::Module<T>>::repatriate_reserved(&provider, &who, deposit)?;
+				}
+			}
+
+			<Preimages<T>>::remove(&proposal_hash);
+			Self::deposit_event(RawEvent::PreimageReaped(proposal_hash, who));
+			Ok(())
+		}
+
 		/// Remove a referendum.
-		fn cancel_referendum(ref_index: Compact<ReferendumIndex>) -> Result {
+		fn cancel_referendum(origin, ref_index: Compact<ReferendumIndex>) -> Result {
+			T::CancellationOrigin::ensure_origin(origin)?;
 			Self::clear_referendum(ref_index.into());
 			Ok(())
 		}
 
+		/// Shorten the voting period of an active referendum, so that it is tallied `voting_period`
+		/// blocks from now. The period may not be shorter than `MinimumVotingPeriod`.
+		fn fast_track(
+			origin,
+			ref_index: Compact<ReferendumIndex>,
+			voting_period: <T::BlockNumber as HasCompact>::Type
+		) -> Result {
+			T::FastTrackOrigin::ensure_origin(origin)?;
+			let ref_index = ref_index.into();
+			let voting_period = voting_period.into();
+			ensure!(voting_period >= Self::minimum_voting_period(), "voting period too short");
+
+			let (old_end, proposal, vote_threshold) = Self::referendum_info(ref_index)
+				.ok_or("cannot fast-track an invalid referendum")?;
+			let end = <system::Module<T>>::block_number() + voting_period;
+			ensure!(end < old_end, "fast-tracking must shorten the voting period");
+
+			// referenda are tallied in order, so this must not end before any earlier referendum.
+			ensure!(
+				Self::active_referendums().iter()
+					.take_while(|&&(index, _, _, _)| index < ref_index)
+					.all(|&(_, other_end, _, _)| other_end <= end),
+				"cannot fast-track a referendum ahead of earlier referenda"
+			);
+
+			<ReferendumInfoOf<T>>::insert(ref_index, (end, proposal, vote_threshold));
+			Self::deposit_event(RawEvent::FastTracked(ref_index, end));
+			Ok(())
+		}
+
 		fn on_finalise(n: T::BlockNumber) {
 			if let Err(e) = Self::end_block(n) {
 				runtime_io::print(e);
@@ -139,8 +225,8 @@ decl_storage! {
 
 		/// The number of (public) proposals that have been made so far.
 		pub PublicPropCount get(public_prop_count) build(|_| 0 as PropIndex) : PropIndex;
-		/// The public proposals. Unsorted.
-		pub PublicProps get(public_props): Vec<(PropIndex, T::Proposal, T::AccountId)>;
+		/// The public proposals, given by the hash of their call. Unsorted.
+		pub PublicProps get(public_props): Vec<(PropIndex, T::Hash, T::AccountId)>;
 		/// Those who have locked a deposit.
 		pub DepositOf get(deposit_of): map PropIndex => Option<(T::Balance, Vec<T::AccountId>)>;
 		/// How often (in blocks) new public referenda are launched.
@@ -150,13 +236,22 @@ decl_storage! {
 
 		/// How often (in blocks) to check for new votes.
 		pub VotingPeriod get(voting_period) config(): T::BlockNumber = T::BlockNumber::sa(1000);
+		/// The minimum voting period (in blocks) a referendum may be fast-tracked to.
+		pub MinimumVotingPeriod get(minimum_voting_period) config(): T::BlockNumber = T::BlockNumber::sa(1);
 
 		/// The next free referendum index, aka the number of referendums started so far.
 		pub ReferendumCount get(referendum_count) build(|_| 0 as ReferendumIndex): ReferendumIndex;
 		/// The next referendum index that should be tallied.
 		pub NextTally get(next_tally) build(|_| 0 as ReferendumIndex): ReferendumIndex;
-		/// Information concerning any given referendum.
-		pub ReferendumInfoOf get(referendum_info): map ReferendumIndex => Option<(T::BlockNumber, T::Proposal, VoteThreshold)>;
+		/// Information concerning any given referendum: its end, the hash of its proposal and its
+		/// threshold.
+		pub ReferendumInfoOf get(referendum_info): map ReferendumIndex => Option<(T::BlockNumber, T::Hash, VoteThreshold)>;
+
+		/// The encoded proposals noted by their hash, together with the account and deposit of
+		/// whoever noted them, if any, and the block they were noted at.
+		pub Preimages get(preimages): map T::Hash => Option<(Vec<u8>, Option<(T::AccountId, T::Balance)>, T::BlockNumber)>;
+		/// The deposit reserved for each byte of a noted preimage.
+		pub PreimageByteDeposit get(preimage_byte_deposit) config(): T::Balance;
 
 		/// Get the voters for the current proposal.
 		pub VotersFor get(voters_for): map ReferendumIndex => Vec<T::AccountId>;
@@ -168,13 +263,28 @@ decl_storage! {
 
 decl_event!(
 	/// An event in this module.
-	pub enum Event<T> where <T as balances::Trait>::Balance, <T as system::Trait>::AccountId {
+	pub enum Event<T> where
+		<T as balances::Trait>::Balance,
+		<T as system::Trait>::AccountId,
+		<T as system::Trait>::BlockNumber,
+		<T as system::Trait>::Hash
+	{
 		Tabled(PropIndex, Balance, Vec<AccountId>),
 		Started(ReferendumIndex, VoteThreshold),
+		/// A referendum was fast-tracked to end at the given block.
+		FastTracked(ReferendumIndex, BlockNumber),
 		Passed(ReferendumIndex),
 		NotPassed(ReferendumIndex),
 		Cancelled(ReferendumIndex),
 		Executed(ReferendumIndex, bool),
+		/// A preimage was noted by the account, reserving the deposit.
+		PreimageNoted(Hash, AccountId, Balance),
+		/// A preimage was removed by the account.
+		PreimageReaped(Hash, AccountId),
+		/// A referendum passed, but the preimage of its proposal was never noted.
+		PreimageMissing(Hash, ReferendumIndex),
+		/// A referendum passed, but the preimage of its proposal isn't a valid call.
+		PreimageInvalid(Hash, ReferendumIndex),
 	}
 );
 
@@ -184,7 +294,7 @@ impl<T: Trait> Module<T> {
 	/// Get the amount locked in support of `proposal`; `None` if proposal isn't a valid proposal
 	/// index.
 	pub fn locked_for(proposal: PropIndex) -> Option<T::Balance> {
-		Self::deposit_of(proposal).map(|(d, l)| d * T::Balance::sa(l.len() as u64))
+		Self::deposit_of(proposal).and_then(|(d, l)| d.checked_mul(&T::Balance::sa(l.len() as u64)))
 	}
 
 	/// Return true if `ref_index` is an on-going referendum.
@@ -193,7 +303,7 @@ impl<T: Trait> Module<T> {
 	}
 
 	/// Get all referendums currently active.
-	pub fn active_referendums() -> Vec<(ReferendumIndex, T::BlockNumber, T::Proposal, VoteThreshold)> {
+	pub fn active_referendums() -> Vec<(ReferendumIndex, T::BlockNumber, T::Hash, VoteThreshold)> {
 		let next = Self::next_tally();
 		let last = Self::referendum_count();
 		(next..last).into_iter()
@@ -202,7 +312,7 @@ impl<T: Trait> Module<T> {
 	}
 
 	/// Get all referendums ready for tally at block `n`.
-	pub fn maturing_referendums_at(n: T::BlockNumber) -> Vec<(ReferendumIndex, T::BlockNumber, T::Proposal, VoteThreshold)> {
+	pub fn maturing_referendums_at(n: T::BlockNumber) -> Vec<(ReferendumIndex, T::BlockNumber, T::Hash, VoteThreshold)> {
 		let next = Self::next_tally();
 		let last = Self::referendum_count();
 		(next..last).into_iter()
@@ -221,9 +331,26 @@ impl<T: Trait> Module<T> {
 
 	// Exposed mutables.
 
-	/// Start a referendum. Can be called directly by the council.
-	pub fn internal_start_referendum(proposal: T::Proposal, vote_threshold: VoteThreshold) -> result::Result<ReferendumIndex, &'static str> {
-		<Module<T>>::inject_referendum(<system::Module<T>>::block_number() + <Module<T>>::voting_period(), proposal, vote_threshold)
+	/// Whether a public proposal or an active referendum refers to the given proposal hash.
+	pub fn is_referenced(proposal_hash: &T::Hash) -> bool {
+		Self::public_props().iter().any(|&(_, ref hash, _)| hash == proposal_hash)
+			|| Self::active_referendums().iter().any(|&(_, _, ref hash, _)| hash == proposal_hash)
+	}
+
+	/// The proposal noted with the given hash, if its preimage is noted and is a valid call.
+	pub fn preimage_proposal(proposal_hash: &T::Hash) -> Option<T::Proposal> {
+		Self::preimages(proposal_hash)
+			.and_then(|(encoded_proposal, _, _)| T::Proposal::decode(&mut &encoded_proposal[..]))
+	}
+
+	/// Start a referendum on the proposal with the given hash. Can be called directly by the
+	/// council.
+	pub fn internal_start_referendum(proposal_hash: T::Hash, vote_threshold: VoteThreshold) -> result::Result<ReferendumIndex, &'static str> {
+		<Module<T>>::inject_referendum(
+			<system::Module<T>>::block_number() + <Module<T>>::voting_period(),
+			proposal_hash,
+			vote_threshold,
+		)
 	}
 
 	/// Remove a referendum. Can be called directly by the council.
@@ -237,7 +364,7 @@ impl<T: Trait> Module<T> {
 	/// Start a referendum
 	fn inject_referendum(
 		end: T::BlockNumber,
-		proposal: T::Proposal,
+		proposal_hash: T::Hash,
 		vote_threshold: VoteThreshold
 	) -> result::Result<ReferendumIndex, &'static str> {
 		let ref_index = Self::referendum_count();
@@ -246,7 +373,7 @@ impl<T: Trait> Module<T> {
 		}
 
 		<ReferendumCount<T>>::put(ref_index + 1);
-		<ReferendumInfoOf<T>>::insert(ref_index, (end, proposal, vote_threshold));
+		<ReferendumInfoOf<T>>::insert(ref_index, (end, proposal_hash, vote_threshold));
 		Self::deposit_event(RawEvent::Started(ref_index, vote_threshold));
 		Ok(ref_index)
 	}
@@ -260,6 +387,29 @@ impl<T: Trait> Module<T> {
 		}
 	}
 
+	/// Dispatch the proposal of a passed referendum, consuming its preimage and returning the
+	/// deposit of whoever noted it.
+	fn enact_proposal(index: ReferendumIndex, proposal_hash: T::Hash) {
+		let (encoded_proposal, deposit, _) = match <Preimages<T>>::take(&proposal_hash) {
+			Some(preimage) => preimage,
+			None => {
+				Self::deposit_event(RawEvent::PreimageMissing(proposal_hash, index));
+				return;
+			}
+		};
+		if let Some((provider, deposit)) = deposit {
+			srml_support::deposit::refund::<balances::Module<T>, _>(&provider, deposit);
+		}
+
+		match T::Proposal::decode(&mut &encoded_proposal[..]) {
+			Some(proposal) => {
+				let ok = proposal.dispatch(system::RawOrigin::Root.into()).is_ok();
+				Self::deposit_event(RawEvent::Executed(index, ok));
+			}
+			None => Self::deposit_event(RawEvent::PreimageInvalid(proposal_hash, index)),
+		}
+	}
+
 	/// Current era is ending; we should finish up any proposals.
 	fn end_block(now: T::BlockNumber) -> Result {
 		// pick out another public referendum if it's time.
@@ -269,7 +419,7 @@ impl<T: Trait> Module<T> {
 				.enumerate()
 				.max_by_key(|x| Self::locked_for((x.1).0).unwrap_or_else(Zero::zero)/*defensive only: All current public proposals have an amount locked*/)
 			{
-				let (prop_index, proposal, _) = public_props.swap_remove(winner_index);
+				let (prop_index, proposal_hash, _) = public_props.swap_remove(winner_index);
 				<PublicProps<T>>::put(public_props);
 
 				if let Some((deposit, depositors)) = <DepositOf<T>>::take(prop_index) {//: (T::Balance, Vec<T::AccountId>) =
@@ -278,20 +428,19 @@ impl<T: Trait> Module<T> {
 						<balances::Module<T>>::unreserve(d, deposit);
 					}
 					Self::deposit_event(RawEvent::Tabled(prop_index, deposit, depositors));
-					Self::inject_referendum(now + Self::voting_period(), proposal, VoteThreshold::SuperMajorityApprove)?;
+					Self::inject_referendum(now + Self::voting_period(), proposal_hash, VoteThreshold::SuperMajorityApprove)?;
 				}
 			}
 		}
 
 		// tally up votes for any expiring referenda.
-		for (index, _, proposal, vote_threshold) in Self::maturing_referendums_at(now) {
+		for (index, _, proposal_hash, vote_threshold) in Self::maturing_referendums_at(now) {
 			let (approve, against) = Self::tally(index);
 			let total_issuance = <balances::Module<T>>::total_issuance();
 			Self::clear_referendum(index);
 			if vote_threshold.approved(approve, against, total_issuance) {
 				Self::deposit_event(RawEvent::Passed(index));
-				let ok = proposal.dispatch(system::RawOrigin::Root.into()).is_ok();
-				Self::deposit_event(RawEvent::Executed(index, ok));
+				Self::enact_proposal(index, proposal_hash);
 			} else {
 				Self::deposit_event(RawEvent::NotPassed(index));
 			}
@@ -304,6 +453,7 @@ impl<T: Trait> Module<T> {
 #[cfg(test)]
 mod tests {
 	use super::*;
+	use codec::Encode;
 	use runtime_io::with_externalities;
 	use substrate_primitives::{H256, Blake2Hasher};
 	use primitives::BuildStorage;
@@ -325,6 +475,7 @@ mod tests {
 	#[derive(Clone, Eq, PartialEq, Debug)]
 	pub struct Test;
 	impl system::Trait for Test {
+		const SPEC_VERSION: u32 = 0;
 		type Origin = Origin;
 		type Index = u64;
 		type BlockNumber = u64;
@@ -335,16 +486,22 @@ mod tests {
 		type Header = Header;
 		type Event = ();
 		type Log = DigestItem;
+		type BaseCallFilter = ();
+		type CallWeigher = ();
 	}
 	impl balances::Trait for Test {
 		type Balance = u64;
 		type AccountIndex = u64;
 		type OnFreeBalanceZero = ();
 		type EnsureAccountLiquid = ();
+		type DustRemoval = ();
+		type OnChargeTransaction = balances::Module<Test>;
 		type Event = ();
 	}
 	impl Trait for Test {
 		type Proposal = Call;
+		type FastTrackOrigin = system::EnsureRoot<u64>;
+		type CancellationOrigin = system::EnsureRoot<u64>;
 		type Event = ();
 	}
 
@@ -354,6 +511,7 @@ mod tests {
 			balances: vec![(1, 10), (2, 20), (3, 30), (4, 40), (5, 50), (6, 60)],
 			transaction_base_fee: 0,
 			transaction_byte_fee: 0,
+			transaction_weight_fee: 0,
 			existential_deposit: 0,
 			transfer_fee: 0,
 			creation_fee: 0,
@@ -363,7 +521,9 @@ mod tests {
 		t.extend(GenesisConfig::<Test>{
 			launch_period: 1,
 			voting_period: 1,
+			minimum_voting_period: 1,
 			minimum_deposit: 1,
+			preimage_byte_deposit: 0,
 			_genesis_phantom_data: Default::default(),
 		}.build_storage().unwrap().0);
 		runtime_io::TestExternalities::new(t)
@@ -389,8 +549,18 @@ mod tests {
 		Call::Balances(balances::Call::set_balance(balances::address::Address::Id(42), value.into(), 0.into()))
 	}
 
+	/// Note the preimage of the proposal if needed and return its hash.
+	fn set_balance_proposal_hash(value: u64) -> H256 {
+		let encoded = set_balance_proposal(value).encode();
+		let hash = BlakeTwo256::hash(&encoded[..]);
+		if Democracy::preimages(hash).is_none() {
+			assert_ok!(Democracy::note_preimage(Origin::signed(6), encoded));
+		}
+		hash
+	}
+
 	fn propose_set_balance(who: u64, value: u64, locked: u64) -> super::Result {
-		Democracy::propose(Origin::signed(who), Box::new(set_balance_proposal(value)), locked.into())
+		Democracy::propose(Origin::signed(who), set_balance_proposal_hash(value), locked.into())
 	}
 
 	#[test]
@@ -428,6 +598,17 @@ mod tests {
 		});
 	}
 
+	#[test]
+	fn seconding_should_not_overflow_locked_deposit() {
+		with_externalities(&mut new_test_ext(), || {
+			System::set_block_number(1);
+			<DepositOf<Test>>::insert(0, (u64::max_value(), vec![1]));
+			assert_eq!(Democracy::locked_for(0), Some(u64::max_value()));
+			assert_eq!(Democracy::second(Origin::signed(2), 0.into()), Err("locked deposit would overflow"));
+			assert_eq!(Democracy::deposit_of(0), Some((u64::max_value(), vec![1])));
+		});
+	}
+
 	#[test]
 	fn deposit_for_proposals_should_be_taken() {
 		with_externalities(&mut new_test_ext(), || {
@@ -513,7 +694,7 @@ mod tests {
 	fn simple_passing_should_work() {
 		with_externalities(&mut new_test_ext(), || {
 			System::set_block_number(1);
-			let r = Democracy::inject_referendum(1, set_balance_proposal(2), VoteThreshold::SuperMajorityApprove).unwrap();
+			let r = Democracy::inject_referendum(1, set_balance_proposal_hash(2), VoteThreshold::SuperMajorityApprove).unwrap();
 			assert_ok!(Democracy::vote(Origin::signed(1), r.into(), true));
 
 			assert_eq!(Democracy::voters_for(r), vec![1]);
@@ -530,13 +711,108 @@ mod tests {
 	fn cancel_referendum_should_work() {
 		with_externalities(&mut new_test_ext(), || {
 			System::set_block_number(1);
-			let r = Democracy::inject_referendum(1, set_balance_proposal(2), VoteThreshold::SuperMajorityApprove).unwrap();
+			let r = Democracy::inject_referendum(1, set_balance_proposal_hash(2), VoteThreshold::SuperMajorityApprove).unwrap();
+			assert_ok!(Democracy::vote(Origin::signed(1), r.into(), true));
+			assert_ok!(Democracy::cancel_referendum(Origin::ROOT, r.into()));
+
+			assert_eq!(Democracy::end_block(System::block_number()), Ok(()));
+
+			assert_eq!(Balances::free_balance(&42), 0);
+		});
+	}
+
+	#[test]
+	fn fast_track_referendum_should_work() {
+		with_externalities(&mut new_test_ext(), || {
+			System::set_block_number(1);
+			let r = Democracy::inject_referendum(10, set_balance_proposal_hash(2), VoteThreshold::SuperMajorityApprove).unwrap();
+			assert_ok!(Democracy::vote(Origin::signed(1), r.into(), true));
+			assert_ok!(Democracy::fast_track(Origin::ROOT, r.into(), 1.into()));
+			assert_eq!(Democracy::referendum_info(r).map(|i| i.0), Some(2));
+
+			System::set_block_number(2);
+			assert_eq!(Democracy::end_block(System::block_number()), Ok(()));
+
+			assert_eq!(Balances::free_balance(&42), 2);
+		});
+	}
+
+	#[test]
+	fn fast_track_referendum_should_respect_limits() {
+		with_externalities(&mut new_test_ext(), || {
+			System::set_block_number(1);
+			let r0 = Democracy::inject_referendum(5, set_balance_proposal_hash(2), VoteThreshold::SuperMajorityApprove).unwrap();
+			let r1 = Democracy::inject_referendum(10, set_balance_proposal_hash(3), VoteThreshold::SuperMajorityApprove).unwrap();
+
+			assert_noop!(Democracy::fast_track(Origin::signed(1), r1.into(), 4.into()), "bad origin: expected to be a root origin");
+			assert_noop!(Democracy::fast_track(Origin::ROOT, r1.into(), 0.into()), "voting period too short");
+			assert_noop!(Democracy::fast_track(Origin::ROOT, r1.into(), 9.into()), "fast-tracking must shorten the voting period");
+			assert_noop!(Democracy::fast_track(Origin::ROOT, 2.into(), 4.into()), "cannot fast-track an invalid referendum");
+			assert_noop!(
+				Democracy::fast_track(Origin::ROOT, r1.into(), 3.into()),
+				"cannot fast-track a referendum ahead of earlier referenda"
+			);
+
+			assert_ok!(Democracy::fast_track(Origin::ROOT, r1.into(), 4.into()));
+			assert_ok!(Democracy::fast_track(Origin::ROOT, r0.into(), 1.into()));
+		});
+	}
+
+	#[test]
+	fn preimage_deposit_should_be_returned_on_enactment() {
+		with_externalities(&mut new_test_ext(), || {
+			System::set_block_number(1);
+			<PreimageByteDeposit<Test>>::put(1);
+			let encoded = set_balance_proposal(2).encode();
+			let hash = set_balance_proposal_hash(2);
+			assert_eq!(Balances::reserved_balance(&6), encoded.len() as u64);
+			assert_noop!(Democracy::note_preimage(Origin::signed(5), encoded), "preimage already noted");
+
+			let r = Democracy::inject_referendum(1, hash, VoteThreshold::SuperMajorityApprove).unwrap();
 			assert_ok!(Democracy::vote(Origin::signed(1), r.into(), true));
-			assert_ok!(Democracy::cancel_referendum(r.into()));
+			assert_eq!(Democracy::end_block(System::block_number()), Ok(()));
 
+			assert_eq!(Balances::free_balance(&42), 2);
+			assert_eq!(Balances::reserved_balance(&6), 0);
+			assert!(Democracy::preimages(hash).is_none());
+		});
+	}
+
+	#[test]
+	fn passed_referendum_without_preimage_should_not_be_enacted() {
+		with_externalities(&mut new_test_ext(), || {
+			System::set_block_number(1);
+			let r = Democracy::inject_referendum(1, H256::random(), VoteThreshold::SuperMajorityApprove).unwrap();
+			assert_ok!(Democracy::vote(Origin::signed(1), r.into(), true));
 			assert_eq!(Democracy::end_block(System::block_number()), Ok(()));
 
 			assert_eq!(Balances::free_balance(&42), 0);
+			assert_eq!(Democracy::referendum_info(r), None);
+		});
+	}
+
+	#[test]
+	fn reaping_preimage_should_work() {
+		with_externalities(&mut new_test_ext(), || {
+			System::set_block_number(1);
+			<PreimageByteDeposit<Test>>::put(1);
+			let len = set_balance_proposal(2).encode().len() as u64;
+			let hash = set_balance_proposal_hash(2);
+
+			assert_noop!(Democracy::reap_preimage(Origin::signed(5), hash), "too early to reap preimage");
+			assert_ok!(Democracy::reap_preimage(Origin::signed(6), hash));
+			assert_eq!(Balances::free_balance(&6), 60);
+			assert_noop!(Democracy::reap_preimage(Origin::signed(6), hash), "unknown preimage");
+
+			let hash = set_balance_proposal_hash(2);
+			assert_ok!(Democracy::propose(Origin::signed(1), hash, 1.into()));
+			System::set_block_number(2);
+			assert_noop!(Democracy::reap_preimage(Origin::signed(5), hash), "preimage is still referenced");
+
+			<PublicProps<Test>>::kill();
+			assert_ok!(Democracy::reap_preimage(Origin::signed(5), hash));
+			assert_eq!(Balances::free_balance(&5), 50 + len);
+			assert_eq!(Balances::total_balance(&6), 60 - len);
 		});
 	}
 
@@ -544,7 +820,7 @@ mod tests {
 	fn simple_failing_should_work() {
 		with_externalities(&mut new_test_ext(), || {
 			System::set_block_number(1);
-			let r = Democracy::inject_referendum(1, set_balance_proposal(2), VoteThreshold::SuperMajorityApprove).unwrap();
+			let r = Democracy::inject_referendum(1, set_balance_proposal_hash(2), VoteThreshold::SuperMajorityApprove).unwrap();
 			assert_ok!(Democracy::vote(Origin::signed(1), r.into(), false));
 
 			assert_eq!(Democracy::voters_for(r), vec![1]);
@@ -561,7 +837,7 @@ mod tests {
 	fn controversial_voting_should_work() {
 		with_externalities(&mut new_test_ext(), || {
 			System::set_block_number(1);
-			let r = Democracy::inject_referendum(1, set_balance_proposal(2), VoteThreshold::SuperMajorityApprove).unwrap();
+			let r = Democracy::inject_referendum(1, set_balance_proposal_hash(2), VoteThreshold::SuperMajorityApprove).unwrap();
 			assert_ok!(Democracy::vote(Origin::signed(1), r.into(), true));
 			assert_ok!(Democracy::vote(Origin::signed(2), r.into(), false));
 			assert_ok!(Democracy::vote(Origin::signed(3), r.into(), false));
@@ -581,7 +857,7 @@ mod tests {
 	fn controversial_low_turnout_voting_should_work() {
 		with_externalities(&mut new_test_ext(), || {
 			System::set_block_number(1);
-			let r = Democracy::inject_referendum(1, set_balance_proposal(2), VoteThreshold::SuperMajorityApprove).unwrap();
+			let r = Democracy::inject_referendum(1, set_balance_proposal_hash(2), VoteThreshold::SuperMajorityApprove).unwrap();
 			assert_ok!(Democracy::vote(Origin::signed(5), r.into(), false));
 			assert_ok!(Democracy::vote(Origin::signed(6), r.into(), true));
 
@@ -600,7 +876,7 @@ mod tests {
 			assert_eq!(Balances::total_issuance(), 210);
 
 			System::set_block_number(1);
-			let r = Democracy::inject_referendum(1, set_balance_proposal(2), VoteThreshold::SuperMajorityApprove).unwrap();
+			let r = Democracy::inject_referendum(1, set_balance_proposal_hash(2), VoteThreshold::SuperMajorityApprove).unwrap();
 			assert_ok!(Democracy::vote(Origin::signed(4), r.into(), true));
 			assert_ok!(Democracy::vote(Origin::signed(5), r.into(), false));
 			assert_ok!(Democracy::vote(Origin::signed(6), r.into(), true));
@@ -281,6 +281,7 @@ mod tests {
 	#[derive(Clone, Eq, PartialEq)]
 	pub struct Test;
 	impl system::Trait for Test {
+		const SPEC_VERSION: u32 = 0;
 		type Origin = Origin;
 		type Index = u64;
 		type BlockNumber = u64;
@@ -291,12 +292,16 @@ mod tests {
 		type Header = Header;
 		type Event = ();
 		type Log = DigestItem;
+		type BaseCallFilter = ();
+		type CallWeigher = ();
 	}
 	impl balances::Trait for Test {
 		type Balance = u64;
 		type AccountIndex = u64;
 		type OnFreeBalanceZero = ();
 		type EnsureAccountLiquid = ();
+		type DustRemoval = ();
+		type OnChargeTransaction = balances::Module<Test>;
 		type Event = ();
 	}
 	impl Trait for Test {
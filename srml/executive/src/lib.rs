@@ -44,12 +44,13 @@ extern crate srml_balances as balances;
 
 use rstd::prelude::*;
 use rstd::marker::PhantomData;
-use rstd::result;
+use rstd::{cmp, result};
 use primitives::traits::{self, Header, Zero, One, Checkable, Applyable, CheckEqual, OnFinalise,
 	MakePayment, Hash, As, Digest};
 use runtime_support::Dispatchable;
+use runtime_support::traits::Filter;
 use codec::{Codec, Encode};
-use system::extrinsics_root;
+use system::{extrinsics_root, WeighCall};
 use primitives::{ApplyOutcome, ApplyError};
 use primitives::transaction_validity::{TransactionValidity, TransactionPriority, TransactionLongevity};
 
@@ -85,7 +86,9 @@ impl<
 	Block::Extrinsic: Checkable<Context> + Codec,
 	<Block::Extrinsic as Checkable<Context>>::Checked: Applyable<Index=System::Index, AccountId=System::AccountId>,
 	<<Block::Extrinsic as Checkable<Context>>::Checked as Applyable>::Call: Dispatchable,
-	<<<Block::Extrinsic as Checkable<Context>>::Checked as Applyable>::Call as Dispatchable>::Origin: From<Option<System::AccountId>>
+	<<<Block::Extrinsic as Checkable<Context>>::Checked as Applyable>::Call as Dispatchable>::Origin: From<Option<System::AccountId>>,
+	System::BaseCallFilter: Filter<<<Block::Extrinsic as Checkable<Context>>::Checked as Applyable>::Call>,
+	System::CallWeigher: WeighCall<<<Block::Extrinsic as Checkable<Context>>::Checked as Applyable>::Call>,
 {
 	/// Start the execution of a particular block.
 	pub fn initialise_block(header: &System::Header) {
@@ -172,15 +175,27 @@ impl<
 		// Verify the signature is good.
 		let xt = uxt.check(&Default::default()).map_err(internal::ApplyError::BadSignature)?;
 
-		if let (Some(sender), Some(index)) = (xt.sender(), xt.index()) {
+		let index = xt.index().cloned();
+
+		// decode parameters; a call filtered out, e.g. while calls are paused, is not dispatched.
+		let (f, s) = xt.deconstruct();
+		let allowed = <System::BaseCallFilter as Filter<_>>::filter(&f);
+		let weight = <System::CallWeigher as WeighCall<_>>::weigh(&f);
+		let class = <System::CallWeigher as WeighCall<_>>::dispatch_class(&f);
+
+		let mut payer = None;
+		if let (Some(sender), Some(index)) = (s.as_ref(), index) {
 			// check index
 			let expected_index = <system::Module<System>>::account_nonce(sender);
-			if index != &expected_index { return Err(
-				if index < &expected_index { internal::ApplyError::Stale } else { internal::ApplyError::Future }
+			if index != expected_index { return Err(
+				if index < expected_index { internal::ApplyError::Stale } else { internal::ApplyError::Future }
 			) }
 
-			// pay any fees.
-			Payment::make_payment(sender, encoded_len).map_err(|_| internal::ApplyError::CantPay)?;
+			// pay any fees, unless the call is filtered out.
+			if allowed {
+				Payment::make_payment(sender, encoded_len, weight).map_err(|_| internal::ApplyError::CantPay)?;
+				payer = Some(sender.clone());
+			}
 
 			// AUDIT: Under no circumstances may this function panic from here onwards.
 
@@ -188,9 +203,25 @@ impl<
 			<system::Module<System>>::inc_account_nonce(sender);
 		}
 
-		// decode parameters and dispatch
-		let (f, s) = xt.deconstruct();
-		let r = f.dispatch(s.into());
+		// dispatch, unless the call is filtered out
+		let r = if allowed {
+			f.dispatch(s.into())
+		} else {
+			Err("call is disabled")
+		};
+
+		// refund what the call reported it shouldn't have been charged for, and account for the
+		// weight it actually consumed.
+		let post_info = <system::Module<System>>::take_post_dispatch_info();
+		let unused_weight = cmp::min(post_info.unused_weight, weight);
+		if let Some(payer) = payer {
+			let refunded_weight = if post_info.fee_waived { weight } else { unused_weight };
+			if refunded_weight > 0 || post_info.fee_waived {
+				Payment::refund(&payer, encoded_len, refunded_weight, post_info.fee_waived);
+			}
+		}
+		let consumed_weight = if allowed { weight - unused_weight } else { 0 };
+		<system::Module<System>>::note_extrinsic_weight(class, consumed_weight);
 		<system::Module<System>>::note_applied_extrinsic(&r);
 
 		r.map(|_| internal::ApplyOutcome::Success).or_else(|e| Ok(internal::ApplyOutcome::Fail(e)))
@@ -235,36 +266,44 @@ impl<
 			Err(_) => return TransactionValidity::Invalid,
 		};
 
-		if let (Some(sender), Some(index)) = (xt.sender(), xt.index()) {
-			// pay any fees.
-			if Payment::make_payment(sender, encoded_len).is_err() {
-				return TransactionValidity::Invalid
-			}
+		let (sender, index) = match (xt.sender(), xt.index()) {
+			(Some(sender), Some(index)) => (sender.clone(), index.clone()),
+			_ => return TransactionValidity::Invalid,
+		};
 
-			// check index
-			let mut expected_index = <system::Module<System>>::account_nonce(sender);
-			if index < &expected_index {
-				return TransactionValidity::Invalid
-			}
-			if *index > expected_index + As::sa(256) {
-				return TransactionValidity::Unknown
-			}
+		// a call filtered out, e.g. while calls are paused, would not be dispatched.
+		let (f, _) = xt.deconstruct();
+		if !<System::BaseCallFilter as Filter<_>>::filter(&f) {
+			return TransactionValidity::Invalid
+		}
 
-			let mut deps = Vec::new();
-			while expected_index < *index {
-				deps.push((sender, expected_index).encode());
-				expected_index = expected_index + One::one();
-			}
+		// pay any fees.
+		let weight = <System::CallWeigher as WeighCall<_>>::weigh(&f);
+		if Payment::make_payment(&sender, encoded_len, weight).is_err() {
+			return TransactionValidity::Invalid
+		}
 
-			TransactionValidity::Valid {
-				priority: encoded_len as TransactionPriority,
-				requires: deps,
-				provides: vec![(sender, *index).encode()],
-				longevity: TransactionLongevity::max_value(),
-			}
-		} else {
+		// check index
+		let mut expected_index = <system::Module<System>>::account_nonce(&sender);
+		if index < expected_index {
 			return TransactionValidity::Invalid
 		}
+		if index > expected_index + As::sa(256) {
+			return TransactionValidity::Unknown
+		}
+
+		let mut deps = Vec::new();
+		while expected_index < index {
+			deps.push((&sender, expected_index).encode());
+			expected_index = expected_index + One::one();
+		}
+
+		TransactionValidity::Valid {
+			priority: encoded_len as TransactionPriority,
+			requires: deps,
+			provides: vec![(&sender, index).encode()],
+			longevity: TransactionLongevity::max_value(),
+		}
 	}
 }
 
@@ -272,6 +311,7 @@ impl<
 mod tests {
 	use super::*;
 	use balances::Call;
+	use runtime_support::StorageValue;
 	use runtime_io::with_externalities;
 	use substrate_primitives::{H256, Blake2Hasher};
 	use primitives::BuildStorage;
@@ -294,6 +334,7 @@ mod tests {
 	#[derive(Clone, Eq, PartialEq)]
 	pub struct Runtime;
 	impl system::Trait for Runtime {
+		const SPEC_VERSION: u32 = 0;
 		type Origin = Origin;
 		type Index = u64;
 		type BlockNumber = u64;
@@ -304,12 +345,44 @@ mod tests {
 		type Header = Header;
 		type Event = MetaEvent;
 		type Log = DigestItem;
+		type BaseCallFilter = TestCallFilter;
+		type CallWeigher = TestCallWeigher;
+	}
+
+	/// Disables all calls while calls are paused.
+	pub struct TestCallFilter;
+	impl Filter<Call<Runtime>> for TestCallFilter {
+		fn filter(_: &Call<Runtime>) -> bool {
+			!<system::Module<Runtime>>::calls_paused()
+		}
+	}
+
+	/// Weighs transfers like the balances module does, and setting a balance as an operational
+	/// call of weight 1.
+	pub struct TestCallWeigher;
+	impl WeighCall<Call<Runtime>> for TestCallWeigher {
+		fn weigh(call: &Call<Runtime>) -> u32 {
+			match *call {
+				Call::transfer(..) => balances::TRANSFER_WEIGHT,
+				Call::set_balance(..) => 1,
+				_ => 0,
+			}
+		}
+
+		fn dispatch_class(call: &Call<Runtime>) -> system::DispatchClass {
+			match *call {
+				Call::set_balance(..) => system::DispatchClass::Operational,
+				_ => system::DispatchClass::Normal,
+			}
+		}
 	}
 	impl balances::Trait for Runtime {
 		type Balance = u64;
 		type AccountIndex = u64;
 		type OnFreeBalanceZero = ();
 		type EnsureAccountLiquid = ();
+		type DustRemoval = ();
+		type OnChargeTransaction = balances::Module<Runtime>;
 		type Event = MetaEvent;
 	}
 
@@ -323,6 +396,7 @@ mod tests {
 			balances: vec![(1, 111)],
 			transaction_base_fee: 10,
 			transaction_byte_fee: 0,
+			transaction_weight_fee: 0,
 			existential_deposit: 0,
 			transfer_fee: 0,
 			creation_fee: 0,
@@ -340,6 +414,100 @@ mod tests {
 		});
 	}
 
+	#[test]
+	fn unused_weight_is_refunded() {
+		let mut t = system::GenesisConfig::<Runtime>::default().build_storage().unwrap().0;
+		t.extend(balances::GenesisConfig::<Runtime> {
+			balances: vec![(1, 200)],
+			transaction_base_fee: 10,
+			transaction_byte_fee: 0,
+			transaction_weight_fee: 1,
+			existential_deposit: 0,
+			transfer_fee: 0,
+			creation_fee: 0,
+			reclaim_rebate: 0,
+			_genesis_phantom_data: Default::default(),
+		}.build_storage().unwrap().0);
+		let mut t = runtime_io::TestExternalities::<Blake2Hasher>::new(t);
+		with_externalities(&mut t, || {
+			Executive::initialise_block(&Header::new(1, H256::default(), H256::default(),
+				[69u8; 32].into(), Digest::default()));
+			let fee = 10 + balances::TRANSFER_WEIGHT as u64;
+
+			// the transfer creates the account of 2, consuming all of its weight.
+			let xt = primitives::testing::TestXt(Some(1), 0, Call::transfer(2.into(), 69.into()));
+			assert_eq!(Executive::apply_extrinsic(xt), Ok(ApplyOutcome::Success));
+			assert_eq!(<balances::Module<Runtime>>::total_balance(&1), 200 - 69 - fee);
+
+			let xt = primitives::testing::TestXt(Some(1), 1, Call::transfer(2.into(), 5.into()));
+			assert_eq!(Executive::apply_extrinsic(xt), Ok(ApplyOutcome::Success));
+			assert_eq!(
+				<balances::Module<Runtime>>::total_balance(&1),
+				200 - 69 - 5 - 2 * fee + balances::ACCOUNT_CREATION_WEIGHT as u64
+			);
+		});
+	}
+
+	#[test]
+	fn filtered_call_is_not_dispatched() {
+		let mut t = system::GenesisConfig::<Runtime>::default().build_storage().unwrap().0;
+		t.extend(balances::GenesisConfig::<Runtime> {
+			balances: vec![(1, 111)],
+			transaction_base_fee: 10,
+			transaction_byte_fee: 0,
+			transaction_weight_fee: 0,
+			existential_deposit: 0,
+			transfer_fee: 0,
+			creation_fee: 0,
+			reclaim_rebate: 0,
+			_genesis_phantom_data: Default::default(),
+		}.build_storage().unwrap().0);
+		let mut t = runtime_io::TestExternalities::<Blake2Hasher>::new(t);
+		with_externalities(&mut t, || {
+			Executive::initialise_block(&Header::new(1, H256::default(), H256::default(),
+				[69u8; 32].into(), Digest::default()));
+			<system::CallsPaused<Runtime>>::put(true);
+			let xt = primitives::testing::TestXt(Some(1), 0, Call::transfer(2.into(), 69.into()));
+			assert_eq!(Executive::apply_extrinsic(xt), Ok(ApplyOutcome::Fail));
+			// no fee is paid for it
+			assert_eq!(<balances::Module<Runtime>>::total_balance(&1), 111);
+			assert_eq!(<balances::Module<Runtime>>::total_balance(&2), 0);
+
+			<system::CallsPaused<Runtime>>::put(false);
+			let xt = primitives::testing::TestXt(Some(1), 1, Call::transfer(2.into(), 69.into()));
+			assert_eq!(Executive::apply_extrinsic(xt), Ok(ApplyOutcome::Success));
+			assert_eq!(<balances::Module<Runtime>>::total_balance(&2), 69);
+		});
+	}
+
+	#[test]
+	fn filtered_call_is_invalid_in_the_pool() {
+		let mut t = system::GenesisConfig::<Runtime>::default().build_storage().unwrap().0;
+		t.extend(balances::GenesisConfig::<Runtime> {
+			balances: vec![(1, 111)],
+			transaction_base_fee: 10,
+			transaction_byte_fee: 0,
+			transaction_weight_fee: 0,
+			existential_deposit: 0,
+			transfer_fee: 0,
+			creation_fee: 0,
+			reclaim_rebate: 0,
+			_genesis_phantom_data: Default::default(),
+		}.build_storage().unwrap().0);
+		let mut t = runtime_io::TestExternalities::<Blake2Hasher>::new(t);
+		with_externalities(&mut t, || {
+			let xt = || primitives::testing::TestXt(Some(1), 0, Call::transfer(2.into(), 69.into()));
+			<system::CallsPaused<Runtime>>::put(true);
+			assert_eq!(Executive::validate_transaction(xt()), TransactionValidity::Invalid);
+
+			<system::CallsPaused<Runtime>>::put(false);
+			match Executive::validate_transaction(xt()) {
+				TransactionValidity::Valid { .. } => (),
+				validity => panic!("Expected a valid transaction, got {:?}", validity),
+			}
+		});
+	}
+
 	fn new_test_ext() -> runtime_io::TestExternalities<Blake2Hasher> {
 		let mut t = system::GenesisConfig::<Runtime>::default().build_storage().unwrap().0;
 		t.extend(balances::GenesisConfig::<Runtime>::default().build_storage().unwrap().0);
@@ -406,4 +574,35 @@ mod tests {
 			assert_eq!(<system::Module<Runtime>>::extrinsic_index(), Some(0));
 		});
 	}
+
+	#[test]
+	fn block_weight_is_recorded_per_class() {
+		let mut t = system::GenesisConfig::<Runtime>::default().build_storage().unwrap().0;
+		t.extend(balances::GenesisConfig::<Runtime> {
+			balances: vec![(1, 111)],
+			transaction_base_fee: 10,
+			transaction_byte_fee: 0,
+			transaction_weight_fee: 0,
+			existential_deposit: 0,
+			transfer_fee: 0,
+			creation_fee: 0,
+			reclaim_rebate: 0,
+			_genesis_phantom_data: Default::default(),
+		}.build_storage().unwrap().0);
+		let mut t = runtime_io::TestExternalities::<Blake2Hasher>::new(t);
+		let transfer = |index, value: u64| primitives::testing::TestXt(Some(1), index, Call::transfer(2.into(), value.into()));
+		let set_balance = primitives::testing::TestXt(None, 0, Call::set_balance(2.into(), 0.into(), 0.into()));
+		with_externalities(&mut t, || {
+			Executive::initialise_block(&Header::new(1, H256::default(), H256::default(), [69u8; 32].into(), Digest::default()));
+			// the failing dispatch is accounted for as well.
+			Executive::apply_extrinsic(set_balance).unwrap();
+			Executive::apply_extrinsic(transfer(0, 69)).unwrap();
+			// only the weight the second transfer actually consumed is accounted for.
+			Executive::apply_extrinsic(transfer(1, 1)).unwrap();
+			assert_eq!(<system::Module<Runtime>>::block_weight(), system::ClassWeights {
+				normal: 2 * balances::TRANSFER_WEIGHT - balances::ACCOUNT_CREATION_WEIGHT,
+				operational: 1,
+			});
+		});
+	}
 }
@@ -55,11 +55,13 @@ extern crate sr_io as runtime_io;
 pub extern crate substrate_finality_grandpa_primitives as fg_primitives;
 
 use rstd::prelude::*;
-use fg_primitives::ScheduledChange;
+use codec::{Decode, Encode};
+use fg_primitives::{EquivocationProof, ScheduledChange};
 use runtime_support::Parameter;
 use runtime_support::dispatch::Result;
 use runtime_support::storage::StorageValue;
-use runtime_support::storage::unhashed::StorageVec;
+use runtime_support::storage::unhashed::{self, StorageVec};
+use runtime_support::traits::KeyOwnerProofSystem;
 use primitives::traits::{CurrentHeight, Convert};
 use substrate_primitives::AuthorityId;
 use system::ensure_signed;
@@ -89,6 +91,9 @@ pub type Log<T> = RawLog<
 pub trait GrandpaChangeSignal<N> {
 	/// Try to cast the log entry as a contained signal.
 	fn as_signal(&self) -> Option<ScheduledChange<N>>;
+	/// Try to cast the log entry as a contained forced signal, along with the number of the
+	/// block the new set starts voting on top of.
+	fn as_forced_signal(&self) -> Option<(N, ScheduledChange<N>)>;
 }
 
 /// A logs in this module.
@@ -98,6 +103,9 @@ pub enum RawLog<N, SessionKey> {
 	/// Authorities set change has been signalled. Contains the new set of authorities
 	/// and the delay in blocks before applying.
 	AuthoritiesChangeSignal(N, Vec<(SessionKey, u64)>),
+	/// A forced authorities set change. Contains the number of the block the new set starts
+	/// voting on top of, the delay in blocks before applying, and the new set of authorities.
+	ForcedAuthoritiesChangeSignal(N, N, Vec<(SessionKey, u64)>),
 }
 
 impl<N: Clone, SessionKey> RawLog<N, SessionKey> {
@@ -105,6 +113,16 @@ impl<N: Clone, SessionKey> RawLog<N, SessionKey> {
 	pub fn as_signal(&self) -> Option<(N, &[(SessionKey, u64)])> {
 		match *self {
 			RawLog::AuthoritiesChangeSignal(ref n, ref signal) => Some((n.clone(), signal)),
+			RawLog::ForcedAuthoritiesChangeSignal(..) => None,
+		}
+	}
+
+	/// Try to cast the log entry as a contained forced signal.
+	pub fn as_forced_signal(&self) -> Option<(N, N, &[(SessionKey, u64)])> {
+		match *self {
+			RawLog::ForcedAuthoritiesChangeSignal(ref median, ref n, ref signal) =>
+				Some((median.clone(), n.clone(), signal)),
+			RawLog::AuthoritiesChangeSignal(..) => None,
 		}
 	}
 }
@@ -121,6 +139,16 @@ impl<N, SessionKey> GrandpaChangeSignal<N> for RawLog<N, SessionKey>
 				.collect(),
 		})
 	}
+
+	fn as_forced_signal(&self) -> Option<(N, ScheduledChange<N>)> {
+		RawLog::as_forced_signal(self).map(|(median, delay, next_authorities)| (median, ScheduledChange {
+			delay,
+			next_authorities: next_authorities.iter()
+				.cloned()
+				.map(|(k, w)| (k.into(), w))
+				.collect(),
+		}))
+	}
 }
 
 pub trait Trait: system::Trait {
@@ -137,8 +165,17 @@ pub trait Trait: system::Trait {
 
 	/// The event type of this module.
 	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// Proves the ownership of the keys of equivocating authorities, e.g. `session::historical`.
+	type KeyOwnerSystem: KeyOwnerProofSystem<AuthorityId>;
 }
 
+/// The proof of ownership of an authority key.
+pub type KeyOwnerProof<T> = <<T as Trait>::KeyOwnerSystem as KeyOwnerProofSystem<AuthorityId>>::Proof;
+
+/// The owner of an authority key.
+pub type KeyOwner<T> = <<T as Trait>::KeyOwnerSystem as KeyOwnerProofSystem<AuthorityId>>::Owner;
+
 /// A stored pending change.
 #[derive(Encode, Decode)]
 pub struct StoredPendingChange<N, SessionKey> {
@@ -148,6 +185,9 @@ pub struct StoredPendingChange<N, SessionKey> {
 	pub delay: N,
 	/// The next authority set.
 	pub next_authorities: Vec<(SessionKey, u64)>,
+	/// If defined, the change is forced and starts voting on top of the block with the given
+	/// number.
+	pub forced: Option<N>,
 }
 
 /// GRANDPA events.
@@ -180,6 +220,10 @@ decl_storage! {
 				::fg_primitives::well_known_keys::AUTHORITY_COUNT.to_vec(),
 				auth_count.encode(),
 			);
+			storage.insert(
+				::fg_primitives::well_known_keys::SET_ID.to_vec(),
+				0u64.encode(),
+			);
 		});
 	}
 }
@@ -188,20 +232,59 @@ decl_module! {
 	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
 		fn deposit_event() = default;
 
-		/// Report some misbehaviour.
-		fn report_misbehavior(origin, _report: Vec<u8>) -> Result {
+		/// Report some misbehaviour: an encoded equivocation along with the proof of ownership of
+		/// the key of the offender.
+		fn report_misbehavior(origin, report: Vec<u8>) -> Result {
 			ensure_signed(origin)?;
-			// TODO: https://github.com/paritytech/substrate/issues/1112
+			let (equivocation, key_owner_proof) = <(EquivocationProof<T::Hash, T::BlockNumber>, KeyOwnerProof<T>)>::decode(&mut &report[..])
+				.ok_or("invalid misbehaviour report")?;
+			Self::check_equivocation_report(&equivocation, key_owner_proof)
+				.ok_or("equivocation or key ownership not proven")?;
+			// TODO: slash the owner https://github.com/paritytech/substrate/issues/1112
+
+			// proving misbehaviour costs the reporter nothing.
+			<system::Module<T>>::waive_fee();
+			Ok(())
+		}
+
+		/// Force a change of the authority set, which is applied once `in_blocks` blocks are
+		/// imported on top of the current one, without waiting for finality. The new set starts
+		/// voting on top of the block numbered `best_finalized`.
+		///
+		/// Meant to recover from stalled finality, e.g. when too many voters went offline.
+		fn force_change(
+			next_authorities: Vec<(T::SessionKey, u64)>,
+			in_blocks: T::BlockNumber,
+			best_finalized: T::BlockNumber
+		) -> Result {
+			Self::schedule_change(next_authorities, in_blocks, Some(best_finalized))
+		}
+
+		/// Initialise the id of the current authority set with the set id of the finality gadget.
+		///
+		/// Only needed once on chains started before the id was tracked by the runtime, for which
+		/// it is not set at genesis. Fails if the id is initialised already.
+		fn initialise_set_id(set_id: u64) -> Result {
+			ensure!(Self::current_set_id().is_none(), "set id is initialised already");
+			unhashed::put(::fg_primitives::well_known_keys::SET_ID, &set_id);
 			Ok(())
 		}
 
 		fn on_finalise(block_number: T::BlockNumber) {
 			if let Some(pending_change) = <PendingChange<T>>::get() {
 				if block_number == pending_change.scheduled_at {
-					Self::deposit_log(RawLog::AuthoritiesChangeSignal(
-						pending_change.delay,
-						pending_change.next_authorities.clone(),
-					));
+					if let Some(median) = pending_change.forced {
+						Self::deposit_log(RawLog::ForcedAuthoritiesChangeSignal(
+							median,
+							pending_change.delay,
+							pending_change.next_authorities.clone(),
+						));
+					} else {
+						Self::deposit_log(RawLog::AuthoritiesChangeSignal(
+							pending_change.delay,
+							pending_change.next_authorities.clone(),
+						));
+					}
 				}
 
 				if block_number == pending_change.scheduled_at + pending_change.delay {
@@ -209,6 +292,11 @@ decl_module! {
 						RawEvent::NewAuthorities(pending_change.next_authorities.clone())
 					);
 					<AuthorityStorageVec<T::SessionKey>>::set_items(pending_change.next_authorities);
+					// an uninitialised id is left alone: it is initialised with the id of the gadget,
+					// which already accounts for this change.
+					if let Some(set_id) = Self::current_set_id() {
+						unhashed::put(::fg_primitives::well_known_keys::SET_ID, &(set_id + 1));
+					}
 					<PendingChange<T>>::kill();
 				}
 			}
@@ -222,17 +310,61 @@ impl<T: Trait> Module<T> {
 		<AuthorityStorageVec<T::SessionKey>>::items()
 	}
 
+	/// Get the id of the current set of authorities, incremented on every change of the set.
+	///
+	/// Returns `None` until initialised, either at genesis or through `initialise_set_id`.
+	pub fn current_set_id() -> Option<u64> {
+		unhashed::get(::fg_primitives::well_known_keys::SET_ID)
+	}
+
+	/// Prove the ownership of the key of an authority, for reports of its equivocations.
+	pub fn key_ownership_proof(authority: AuthorityId) -> Option<Vec<u8>> {
+		T::KeyOwnerSystem::prove(authority).map(|proof| proof.encode())
+	}
+
+	/// Check that the given equivocation is valid, i.e. that the offender signed two votes of the
+	/// same kind for different blocks, and that the key of the offender is proven to be owned,
+	/// returning the owner.
+	///
+	/// The set id of the equivocation is not checked against the session of the proof: the
+	/// proof only attests that the key was owned while its session is kept.
+	pub fn check_equivocation_report(
+		equivocation: &EquivocationProof<T::Hash, T::BlockNumber>,
+		key_owner_proof: KeyOwnerProof<T>,
+	) -> Option<KeyOwner<T>> {
+		if !equivocation.is_valid() {
+			return None;
+		}
+		T::KeyOwnerSystem::check_proof(equivocation.offender.clone(), key_owner_proof)
+	}
+
+	/// Get the storage keys holding the current set id and authorities, e.g. to prove them.
+	pub fn authority_set_keys() -> Vec<Vec<u8>> {
+		use codec::KeyedVec;
+		use fg_primitives::well_known_keys::{AUTHORITY_COUNT, AUTHORITY_PREFIX, SET_ID};
+
+		let count = <AuthorityStorageVec<T::SessionKey>>::count();
+		vec![SET_ID.to_vec(), AUTHORITY_COUNT.to_vec()].into_iter()
+			.chain((0..count).map(|i| i.to_keyed_vec(AUTHORITY_PREFIX)))
+			.collect()
+	}
+
 	/// Schedule a change in the authorities.
 	///
 	/// The change will be applied at the end of execution of the block
 	/// `in_blocks` after the current block. This value may be 0, in which
 	/// case the change is applied at the end of the current block.
 	///
+	/// If `forced` is given, the change is forced: it is applied once the delay of blocks is
+	/// imported, without waiting for finality, and the new set starts voting on top of the block
+	/// with the given number. This is meant to recover from stalled finality.
+	///
 	/// No change should be signalled while any change is pending. Returns
 	/// an error if a change is already pending.
 	pub fn schedule_change(
 		next_authorities: Vec<(T::SessionKey, u64)>,
 		in_blocks: T::BlockNumber,
+		forced: Option<T::BlockNumber>,
 	) -> Result {
 		if Self::pending_change().is_none() {
 			let scheduled_at = system::ChainContext::<T>::default().current_height();
@@ -240,6 +372,7 @@ impl<T: Trait> Module<T> {
 				delay: in_blocks,
 				scheduled_at,
 				next_authorities,
+				forced,
 			});
 
 			Ok(())
@@ -261,6 +394,13 @@ impl<T: Trait> Module<T> where AuthorityId: core::convert::From<<T as Trait>::Se
 	{
 		<Log<T> as GrandpaChangeSignal<T::BlockNumber>>::as_signal(log)
 	}
+
+	/// See if the digest contains any forced change.
+	pub fn scrape_digest_forced_change(log: &Log<T>)
+		-> Option<(T::BlockNumber, ScheduledChange<T::BlockNumber>)>
+	{
+		<Log<T> as GrandpaChangeSignal<T::BlockNumber>>::as_forced_signal(log)
+	}
 }
 
 /// Helper for authorities being synchronized with the general session authorities.
@@ -297,7 +437,7 @@ impl<X, T> session::OnSessionChange<X> for SyncedAuthorities<T> where
 		// instant changes
 		let last_authorities = <Module<T>>::grandpa_authorities();
 		if next_authorities != last_authorities {
-			let _ = <Module<T>>::schedule_change(next_authorities, Zero::zero());
+			let _ = <Module<T>>::schedule_change(next_authorities, Zero::zero(), None);
 		}
 	}
 }
@@ -42,8 +42,10 @@ impl Trait for Test {
 	type Log = DigestItem;
 	type SessionKey = u64;
 	type Event = TestEvent;
+	type KeyOwnerSystem = ();
 }
 impl system::Trait for Test {
+	const SPEC_VERSION: u32 = 0;
 	type Origin = Origin;
 	type Index = u64;
 	type BlockNumber = u64;
@@ -54,6 +56,8 @@ impl system::Trait for Test {
 	type Header = Header;
 	type Event = TestEvent;
 	type Log = DigestItem;
+	type BaseCallFilter = ();
+	type CallWeigher = ();
 }
 
 mod grandpa {
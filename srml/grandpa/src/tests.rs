@@ -21,15 +21,16 @@
 use primitives::{testing, traits::OnFinalise};
 use primitives::traits::Header;
 use runtime_io::with_externalities;
-use mock::{Grandpa, System, new_test_ext};
+use mock::{Grandpa, Origin, System, new_test_ext};
 use system::{EventRecord, Phase};
+use runtime_support::storage::unhashed;
 use {RawLog, RawEvent};
 
 #[test]
 fn authorities_change_logged() {
 	with_externalities(&mut new_test_ext(vec![(1, 1), (2, 1), (3, 1)]), || {
 		System::initialise(&1, &Default::default(), &Default::default());
-		Grandpa::schedule_change(vec![(4, 1), (5, 1), (6, 1)], 0).unwrap();
+		Grandpa::schedule_change(vec![(4, 1), (5, 1), (6, 1)], 0, None).unwrap();
 
 		System::note_finished_extrinsics();
 		Grandpa::on_finalise(1);
@@ -50,11 +51,48 @@ fn authorities_change_logged() {
 	});
 }
 
+#[test]
+fn set_id_incremented_when_change_enacted() {
+	with_externalities(&mut new_test_ext(vec![(1, 1), (2, 1), (3, 1)]), || {
+		assert_eq!(Grandpa::current_set_id(), Some(0));
+		assert_eq!(Grandpa::authority_set_keys().len(), 5);
+
+		System::initialise(&1, &Default::default(), &Default::default());
+		Grandpa::schedule_change(vec![(4, 1), (5, 1)], 1, None).unwrap();
+		Grandpa::on_finalise(1);
+		assert_eq!(Grandpa::current_set_id(), Some(0));
+
+		System::initialise(&2, &Default::default(), &Default::default());
+		Grandpa::on_finalise(2);
+		assert_eq!(Grandpa::current_set_id(), Some(1));
+		assert_eq!(Grandpa::authority_set_keys().len(), 4);
+	});
+}
+
+#[test]
+fn uninitialised_set_id_is_initialised_once() {
+	with_externalities(&mut new_test_ext(vec![(1, 1), (2, 1), (3, 1)]), || {
+		// a chain started before the set id was tracked.
+		unhashed::take::<u64>(::fg_primitives::well_known_keys::SET_ID);
+		assert_eq!(Grandpa::current_set_id(), None);
+
+		// enacted changes don't touch the uninitialised id.
+		System::initialise(&1, &Default::default(), &Default::default());
+		Grandpa::schedule_change(vec![(4, 1), (5, 1)], 0, None).unwrap();
+		Grandpa::on_finalise(1);
+		assert_eq!(Grandpa::current_set_id(), None);
+
+		assert_ok!(Grandpa::initialise_set_id(7));
+		assert_eq!(Grandpa::current_set_id(), Some(7));
+		assert_noop!(Grandpa::initialise_set_id(8), "set id is initialised already");
+	});
+}
+
 #[test]
 fn authorities_change_logged_after_delay() {
 	with_externalities(&mut new_test_ext(vec![(1, 1), (2, 1), (3, 1)]), || {
 		System::initialise(&1, &Default::default(), &Default::default());
-		Grandpa::schedule_change(vec![(4, 1), (5, 1), (6, 1)], 1).unwrap();
+		Grandpa::schedule_change(vec![(4, 1), (5, 1), (6, 1)], 1, None).unwrap();
 		Grandpa::on_finalise(1);
 		let header = System::finalise();
 		assert_eq!(header.digest, testing::Digest {
@@ -84,25 +122,74 @@ fn authorities_change_logged_after_delay() {
 fn cannot_schedule_change_when_one_pending() {
 	with_externalities(&mut new_test_ext(vec![(1, 1), (2, 1), (3, 1)]), || {
 		System::initialise(&1, &Default::default(), &Default::default());
-		Grandpa::schedule_change(vec![(4, 1), (5, 1), (6, 1)], 1).unwrap();
+		Grandpa::schedule_change(vec![(4, 1), (5, 1), (6, 1)], 1, None).unwrap();
 		assert!(Grandpa::pending_change().is_some());
-		assert!(Grandpa::schedule_change(vec![(5, 1)], 1).is_err());
+		assert!(Grandpa::schedule_change(vec![(5, 1)], 1, None).is_err());
 
 		Grandpa::on_finalise(1);
 		let header = System::finalise();
 
 		System::initialise(&2, &header.hash(), &Default::default());
 		assert!(Grandpa::pending_change().is_some());
-		assert!(Grandpa::schedule_change(vec![(5, 1)], 1).is_err());
+		assert!(Grandpa::schedule_change(vec![(5, 1)], 1, None).is_err());
 
 		Grandpa::on_finalise(2);
 		let header = System::finalise();
 
 		System::initialise(&3, &header.hash(), &Default::default());
 		assert!(Grandpa::pending_change().is_none());
-		assert!(Grandpa::schedule_change(vec![(5, 1)], 1).is_ok());
+		assert!(Grandpa::schedule_change(vec![(5, 1)], 1, None).is_ok());
 
 		Grandpa::on_finalise(3);
 		let _header = System::finalise();
 	});
 }
+
+#[test]
+fn forced_change_logged() {
+	with_externalities(&mut new_test_ext(vec![(1, 1), (2, 1), (3, 1)]), || {
+		System::initialise(&5, &Default::default(), &Default::default());
+		Grandpa::schedule_change(vec![(4, 1), (5, 1), (6, 1)], 2, Some(3)).unwrap();
+		assert!(Grandpa::schedule_change(vec![(5, 1)], 1, None).is_err());
+
+		Grandpa::on_finalise(5);
+		let header = System::finalise();
+		assert_eq!(header.digest, testing::Digest {
+			logs: vec![
+				RawLog::ForcedAuthoritiesChangeSignal(3, 2, vec![(4, 1), (5, 1), (6, 1)]).into(),
+			],
+		});
+
+		let log = RawLog::ForcedAuthoritiesChangeSignal(3, 2, vec![(4, 1)]);
+		assert_eq!(log.as_signal(), None);
+		assert_eq!(log.as_forced_signal(), Some((3, 2, &[(4, 1)][..])));
+	});
+}
+
+#[test]
+fn unproven_misbehavior_reports_are_rejected() {
+	use fg_primitives::{EquivocationProof, SignedVote};
+	use substrate_primitives::H256;
+	use parity_codec::Encode;
+
+	with_externalities(&mut new_test_ext(vec![(1, 1)]), || {
+		assert_eq!(
+			Grandpa::report_misbehavior(Origin::signed(1), vec![1, 2, 3]),
+			Err("invalid misbehaviour report"),
+		);
+
+		let equivocation = EquivocationProof {
+			set_id: 0,
+			round: 1,
+			offender: Default::default(),
+			precommits: false,
+			first: SignedVote { target_hash: H256::from([1; 32]), target_number: 1u64, signature: Default::default() },
+			second: SignedVote { target_hash: H256::from([2; 32]), target_number: 1u64, signature: Default::default() },
+		};
+		assert_eq!(
+			Grandpa::report_misbehavior(Origin::signed(1), (equivocation, ()).encode()),
+			Err("equivocation or key ownership not proven"),
+		);
+		assert!(!System::take_post_dispatch_info().fee_waived);
+	});
+}
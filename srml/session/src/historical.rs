@@ -0,0 +1,307 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Validator sets of past sessions.
+//!
+//! On every session change the merkle root of the new validator set and their session keys is
+//! noted and kept for a bounded number of sessions. Whoever was a validator of one of those
+//! sessions can prove it, and so can the owner of a session key, so that misbehaviour reported
+//! after the validator set has changed can still be attributed to the validator of the session
+//! it happened in.
+
+use rstd::prelude::*;
+use primitives::traits::{As, Hash};
+use runtime_support::{StorageMap, StorageValue};
+use runtime_support::traits::KeyOwnerProofSystem;
+use consensus;
+use system;
+use super::{Module as Session, OnSessionChange, Trait as SessionTrait};
+
+pub trait Trait: SessionTrait {
+	/// Number of sessions whose validator sets are kept, including the current one.
+	const HISTORY_DEPTH: u32;
+}
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: <T as system::Trait>::Origin {
+		fn on_finalise(_n: T::BlockNumber) {
+			if <SessionChanged<T>>::take() {
+				Self::note_current_session();
+			}
+		}
+	}
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as SessionHistorical {
+		/// Merkle roots of the validator sets of the kept sessions, by session index.
+		pub HistoricalRoots get(historical_root): map T::BlockNumber => Option<T::Hash>;
+		/// Whether the session changed in this block. Its validator set is noted at the end of
+		/// the block, once the session keys are updated.
+		SessionChanged: bool;
+	}
+}
+
+/// Proof that an account was a validator of some session.
+#[derive(Encode, Decode, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct MembershipProof<BlockNumber, Hash> {
+	/// Index of the session.
+	pub session: BlockNumber,
+	/// Position of the account in the validator set.
+	pub index: u32,
+	/// Number of validators in the set.
+	pub validator_count: u32,
+	/// Siblings on the path from the account to the root, from the bottom up.
+	pub siblings: Vec<Hash>,
+}
+
+impl<T: Trait> Module<T> {
+	/// Prove that `who` is a validator of the current session, if it is.
+	pub fn prove(who: &T::AccountId) -> Option<MembershipProof<T::BlockNumber, T::Hash>> {
+		let index = <Session<T>>::validators().iter().position(|v| v == who)?;
+		Self::prove_index(index)
+	}
+
+	/// Prove that `key` is the session key of a validator of the current session, returning
+	/// that validator along with the proof.
+	pub fn prove_key_owner(
+		key: &T::SessionKey,
+	) -> Option<(T::AccountId, MembershipProof<T::BlockNumber, T::Hash>)> {
+		let index = <consensus::Module<T>>::authorities().iter().position(|k| k == key)?;
+		let who = <Session<T>>::validators().get(index)?.clone();
+		Self::prove_index(index).map(|proof| (who, proof))
+	}
+
+	/// Check that `who` was a validator of the session of `proof`, with the session key `key`.
+	/// Fails if that session is not kept anymore.
+	pub fn check_proof(
+		who: &T::AccountId,
+		key: &T::SessionKey,
+		proof: &MembershipProof<T::BlockNumber, T::Hash>,
+	) -> bool {
+		let root = match Self::historical_root(&proof.session) {
+			Some(root) => root,
+			None => return false,
+		};
+		let leaf = leaf::<T>(proof.index, who, key);
+		let computed = merkle_root_from_proof::<T::Hashing>(
+			leaf,
+			proof.index as usize,
+			proof.validator_count as usize,
+			&proof.siblings,
+		);
+		computed == Some(root)
+	}
+
+	fn prove_index(index: usize) -> Option<MembershipProof<T::BlockNumber, T::Hash>> {
+		let leaves = leaves::<T>();
+		if index >= leaves.len() {
+			return None;
+		}
+		Some(MembershipProof {
+			session: <Session<T>>::current_index(),
+			index: index as u32,
+			validator_count: leaves.len() as u32,
+			siblings: merkle_proof::<T::Hashing>(leaves, index),
+		})
+	}
+
+	/// Note the validator set of the current session and forget the session falling out of
+	/// the history.
+	fn note_current_session() {
+		let index = <Session<T>>::current_index();
+		let root = merkle_root::<T::Hashing>(leaves::<T>());
+		<HistoricalRoots<T>>::insert(index, root);
+
+		let depth = T::BlockNumber::sa(T::HISTORY_DEPTH as u64);
+		if index >= depth {
+			<HistoricalRoots<T>>::remove(index - depth);
+		}
+	}
+}
+
+/// The set of the new session is noted at the end of the block, so this module must come after
+/// the session module in the runtime.
+impl<T: Trait> OnSessionChange<T::Moment> for Module<T> {
+	fn on_session_change(_elapsed: T::Moment, _should_reward: bool) {
+		<SessionChanged<T>>::put(true);
+	}
+}
+
+/// Session keys are proven to be owned by validators of the sessions kept.
+impl<T: Trait> KeyOwnerProofSystem<T::SessionKey> for Module<T> {
+	type Proof = (T::AccountId, MembershipProof<T::BlockNumber, T::Hash>);
+	type Owner = T::AccountId;
+
+	fn prove(key: T::SessionKey) -> Option<Self::Proof> {
+		Self::prove_key_owner(&key)
+	}
+
+	fn check_proof(key: T::SessionKey, (who, proof): Self::Proof) -> Option<T::AccountId> {
+		if Self::check_proof(&who, &key, &proof) {
+			Some(who)
+		} else {
+			None
+		}
+	}
+}
+
+fn leaf<T: Trait>(index: u32, who: &T::AccountId, key: &T::SessionKey) -> T::Hash {
+	T::Hashing::hash_of(&(index, who.clone(), key.clone()))
+}
+
+/// The leaves of the validators of the current session, each along with its session key.
+fn leaves<T: Trait>() -> Vec<T::Hash> {
+	let keys = <consensus::Module<T>>::authorities();
+	<Session<T>>::validators().iter()
+		.zip(keys.iter())
+		.enumerate()
+		.map(|(i, (v, k))| leaf::<T>(i as u32, v, k))
+		.collect()
+}
+
+fn parent<H: Hash>(left: &H::Output, right: &H::Output) -> H::Output {
+	H::hash(&[left.as_ref(), right.as_ref()].concat())
+}
+
+/// The nodes of the next level of the tree. A node without sibling is moved up unchanged.
+fn parents<H: Hash>(nodes: &[H::Output]) -> Vec<H::Output> {
+	nodes.chunks(2)
+		.map(|pair| if pair.len() == 2 { parent::<H>(&pair[0], &pair[1]) } else { pair[0].clone() })
+		.collect()
+}
+
+fn merkle_root<H: Hash>(mut nodes: Vec<H::Output>) -> H::Output {
+	if nodes.is_empty() {
+		return H::hash(&[]);
+	}
+	while nodes.len() > 1 {
+		nodes = parents::<H>(&nodes);
+	}
+	nodes.pop().expect("the tree has a single node left; qed")
+}
+
+fn merkle_proof<H: Hash>(mut nodes: Vec<H::Output>, mut index: usize) -> Vec<H::Output> {
+	let mut siblings = Vec::new();
+	while nodes.len() > 1 {
+		if let Some(sibling) = nodes.get(index ^ 1) {
+			siblings.push(sibling.clone());
+		}
+		nodes = parents::<H>(&nodes);
+		index /= 2;
+	}
+	siblings
+}
+
+fn merkle_root_from_proof<H: Hash>(
+	leaf: H::Output,
+	mut index: usize,
+	mut count: usize,
+	siblings: &[H::Output],
+) -> Option<H::Output> {
+	if index >= count {
+		return None;
+	}
+
+	let mut node = leaf;
+	let mut siblings = siblings.iter();
+	while count > 1 {
+		if (index ^ 1) < count {
+			let sibling = siblings.next()?;
+			node = if index % 2 == 0 { parent::<H>(&node, sibling) } else { parent::<H>(sibling, &node) };
+		}
+		index /= 2;
+		count = (count + 1) / 2;
+	}
+
+	match siblings.next() {
+		Some(_) => None,
+		None => Some(node),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use runtime_io::with_externalities;
+	use primitives::traits::{BlakeTwo256, OnFinalise};
+	use ::tests::{Test, Origin, System, Session, new_test_ext};
+
+	type Historical = Module<Test>;
+
+	#[test]
+	fn merkle_proofs_check_out_for_every_leaf() {
+		for count in 1..10u64 {
+			let leaves: Vec<_> = (0..count).map(|i| BlakeTwo256::hash_of(&i)).collect();
+			let root = merkle_root::<BlakeTwo256>(leaves.clone());
+			for (index, leaf) in leaves.iter().enumerate() {
+				let proof = merkle_proof::<BlakeTwo256>(leaves.clone(), index);
+				let count = count as usize;
+				assert_eq!(merkle_root_from_proof::<BlakeTwo256>(leaf.clone(), index, count, &proof), Some(root));
+				assert_ne!(merkle_root_from_proof::<BlakeTwo256>(leaf.clone(), index ^ 1, count, &proof), Some(root));
+				assert_eq!(merkle_root_from_proof::<BlakeTwo256>(leaf.clone(), count, count, &proof), None);
+			}
+		}
+	}
+
+	#[test]
+	fn past_validators_can_be_proven_within_history_depth() {
+		with_externalities(&mut new_test_ext(), || {
+			System::set_block_number(2);
+			Session::check_rotate_session(2);
+			Historical::on_finalise(2);
+			let proof = Historical::prove(&2).unwrap();
+			assert_eq!(proof.session, 1);
+			assert_eq!(Historical::prove(&4), None);
+			assert!(Historical::check_proof(&2, &2, &proof));
+			assert!(!Historical::check_proof(&3, &3, &proof));
+			assert!(!Historical::check_proof(&2, &3, &proof));
+
+			Session::set_validators(&[4, 5]);
+			System::set_block_number(4);
+			Session::check_rotate_session(4);
+			Historical::on_finalise(4);
+			assert!(Historical::check_proof(&2, &2, &proof));
+			assert!(Historical::check_proof(&4, &4, &Historical::prove(&4).unwrap()));
+
+			System::set_block_number(6);
+			Session::check_rotate_session(6);
+			Historical::on_finalise(6);
+			assert_eq!(Historical::historical_root(&1), None);
+			assert!(!Historical::check_proof(&2, &2, &proof));
+		});
+	}
+
+	#[test]
+	fn session_keys_are_noted_once_updated() {
+		with_externalities(&mut new_test_ext(), || {
+			System::set_block_number(1);
+			System::inc_providers(&2);
+			assert_ok!(Session::set_key(Origin::signed(2), 7));
+			System::set_block_number(2);
+			Session::check_rotate_session(2);
+			assert_eq!(Historical::historical_root(&1), None);
+			Historical::on_finalise(2);
+
+			assert_eq!(<Historical as KeyOwnerProofSystem<u64>>::prove(2), None);
+			let proof = <Historical as KeyOwnerProofSystem<u64>>::prove(7).unwrap();
+			assert_eq!(proof.0, 2);
+			assert_eq!(<Historical as KeyOwnerProofSystem<u64>>::check_proof(7, proof.clone()), Some(2));
+			assert_eq!(<Historical as KeyOwnerProofSystem<u64>>::check_proof(2, proof), None);
+		});
+	}
+}
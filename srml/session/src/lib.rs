@@ -45,23 +45,14 @@ use runtime_support::dispatch::Result;
 use system::ensure_signed;
 use rstd::ops::Mul;
 
+pub mod historical;
+
 /// A session has changed.
 pub trait OnSessionChange<T> {
 	/// Session has changed.
 	fn on_session_change(time_elapsed: T, should_reward: bool);
 }
 
-macro_rules! for_each_tuple {
-	($m:ident) => {
-		for_each_tuple! { @IMPL $m !! A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, }
-	};
-	(@IMPL $m:ident !!) => { $m! { } };
-	(@IMPL $m:ident !! $h:ident, $($t:ident,)*) => {
-		$m! { $h $($t)* }
-		for_each_tuple! { @IMPL $m !! $($t,)* }
-	}
-}
-
 macro_rules! impl_session_change {
 	() => (
 		impl<T> OnSessionChange<T> for () {
@@ -93,8 +84,13 @@ decl_module! {
 
 		/// Sets the session key of `_validator` to `_key`. This doesn't take effect until the next
 		/// session.
+		///
+		/// The account is kept from being reaped until the key is taken into use.
 		fn set_key(origin, key: T::SessionKey) -> Result {
 			let who = ensure_signed(origin)?;
+			if !<NextKeyFor<T>>::exists(&who) {
+				<system::Module<T>>::inc_consumers(&who)?;
+			}
 			// set new value for next session
 			<NextKeyFor<T>>::insert(who, key);
 			Ok(())
@@ -221,6 +217,7 @@ impl<T: Trait> Module<T> {
 		// Update any changes in session keys.
 		Self::validators().iter().enumerate().for_each(|(i, v)| {
 			if let Some(n) = <NextKeyFor<T>>::take(v) {
+				<system::Module<T>>::dec_consumers(v);
 				<consensus::Module<T>>::set_authority(i as u32, &n);
 			}
 		});
@@ -266,6 +263,7 @@ mod tests {
 		type OnOfflineValidator = ();
 	}
 	impl system::Trait for Test {
+		const SPEC_VERSION: u32 = 0;
 		type Origin = Origin;
 		type Index = u64;
 		type BlockNumber = u64;
@@ -276,6 +274,8 @@ mod tests {
 		type Header = Header;
 		type Event = ();
 		type Log = DigestItem;
+		type BaseCallFilter = ();
+		type CallWeigher = ();
 	}
 	impl timestamp::Trait for Test {
 		const TIMESTAMP_SET_POSITION: u32 = 0;
@@ -283,15 +283,18 @@ mod tests {
 	}
 	impl Trait for Test {
 		type ConvertAccountIdToSessionKey = Identity;
-		type OnSessionChange = ();
+		type OnSessionChange = historical::Module<Test>;
 		type Event = ();
 	}
+	impl historical::Trait for Test {
+		const HISTORY_DEPTH: u32 = 2;
+	}
 
-	type System = system::Module<Test>;
+	pub type System = system::Module<Test>;
 	type Consensus = consensus::Module<Test>;
-	type Session = Module<Test>;
+	pub type Session = Module<Test>;
 
-	fn new_test_ext() -> runtime_io::TestExternalities<Blake2Hasher> {
+	pub fn new_test_ext() -> runtime_io::TestExternalities<Blake2Hasher> {
 		let mut t = system::GenesisConfig::<Test>::default().build_storage().unwrap().0;
 		t.extend(consensus::GenesisConfig::<Test>{
 			code: vec![],
@@ -418,7 +421,9 @@ mod tests {
 
 			// Block 3: Set new key for validator 2; no visible change.
 			System::set_block_number(3);
+			System::inc_providers(&2);
 			assert_ok!(Session::set_key(Origin::signed(2), 5));
+			assert!(System::is_referenced(&2));
 			assert_eq!(Consensus::authorities(), vec![1, 2, 3]);
 
 			Session::check_rotate_session(3);
@@ -428,6 +433,7 @@ mod tests {
 			System::set_block_number(4);
 			Session::check_rotate_session(4);
 			assert_eq!(Consensus::authorities(), vec![1, 5, 3]);
+			assert!(!System::is_referenced(&2));
 		});
 	}
 }
@@ -17,6 +17,18 @@
 // along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
 
 //! Staking manager: Periodically determines the best set of validators.
+//!
+//! Funds are held by a stash account, which `bond`s a controller account to operate it. The
+//! controller signs all operational calls, like `stake` and `nominate`, and is what validators
+//! and nominators are known by, so that the key of the stash can be kept offline.
+//!
+//! At the end of each era, an era payout is minted according to `Trait::EraPayout`. Nothing is
+//! minted per session. The part going to stakers is split between the validators of the era by
+//! the era points they earned, one for each block they authored as found by `Trait::FindAuthor`,
+//! and further between each validator and their nominators: a validator first takes its
+//! commission, the rest is shared in proportion to the stake. Every staker chooses where its
+//! rewards are paid to with `set_payee`. The remainder is handed to `Trait::RewardRemainder`,
+//! e.g. a treasury.
 
 #![cfg_attr(not(feature = "std"), no_std)]
 
@@ -46,13 +58,13 @@ extern crate sr_io as runtime_io;
 extern crate srml_timestamp as timestamp;
 
 use rstd::prelude::*;
-use rstd::cmp;
+use rstd::{cmp, result};
 use codec::{HasCompact, Compact};
 use runtime_support::{Parameter, StorageValue, StorageMap};
 use runtime_support::dispatch::Result;
 use session::OnSessionChange;
 use primitives::{Perbill, traits::{Zero, One, Bounded, As}};
-use balances::{address::Address, OnDilution};
+use balances::{address::Address, OnDilution, OnUnbalanced};
 use system::ensure_signed;
 
 mod mock;
@@ -60,6 +72,11 @@ mod mock;
 mod tests;
 
 const DEFAULT_MINIMUM_VALIDATOR_COUNT: u32 = 4;
+const BILLION: u64 = 1_000_000_000;
+/// The number of seconds in a (Julian) year, which inflation rates refer to.
+const SECONDS_PER_YEAR: u64 = 31_557_600;
+/// The era points a validator earns for authoring a block.
+const BLOCK_AUTHOR_POINTS: u32 = 1;
 
 #[derive(PartialEq, Clone)]
 #[cfg_attr(test, derive(Debug))]
@@ -72,28 +89,144 @@ pub enum LockStatus<BlockNumber: Parameter> {
 /// Preference of what happens on a slash event.
 #[derive(PartialEq, Eq, Clone, Encode, Decode)]
 #[cfg_attr(feature = "std", derive(Debug))]
-pub struct ValidatorPrefs<Balance: HasCompact + Copy> { // TODO: @bkchr shouldn't need this Copy but derive(Encode) breaks otherwise
+pub struct ValidatorPrefs {
 	/// Validator should ensure this many more slashes than is necessary before being unstaked.
 	#[codec(compact)]
 	pub unstake_threshold: u32,
-	// Reward that validator takes up-front; only the rest is split between themselves and nominators.
-	#[codec(encoded_as = "<Balance as HasCompact>::Type")]
-	pub validator_payment: Balance,
+	/// Part of the reward that the validator takes up-front; only the rest is split between
+	/// themselves and nominators.
+	pub commission: Perbill,
 }
 
-impl<B: Default + HasCompact + Copy> Default for ValidatorPrefs<B> {
+/// An election solution queued for the next era.
+#[derive(PartialEq, Eq, Clone, Encode, Decode)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct QueuedSolution<AccountId, Balance> {
+	/// The validators it elects.
+	pub winners: Vec<AccountId>,
+	/// Its score when submitted: the lowest and the total slashable balance of its validators.
+	pub score: PairOf<Balance>,
+	/// The account that submitted it.
+	pub who: AccountId,
+	/// The deposit reserved from the submitter.
+	pub deposit: Balance,
+}
+
+impl Default for ValidatorPrefs {
 	fn default() -> Self {
 		ValidatorPrefs {
 			unstake_threshold: 3,
-			validator_payment: Default::default(),
+			commission: Perbill::zero(),
 		}
 	}
 }
 
+/// The account a staker's rewards are paid into.
+#[derive(PartialEq, Eq, Copy, Clone, Encode, Decode)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum RewardDestination {
+	/// Pay into the stash account, adding to the amount at stake.
+	Staked,
+	/// Pay into the stash account, without adding to the amount at stake.
+	Stash,
+	/// Pay into the controller account.
+	Controller,
+}
+
+impl Default for RewardDestination {
+	fn default() -> Self {
+		RewardDestination::Staked
+	}
+}
+
+/// Determines the payout minted at the end of an era.
+pub trait EraPayout<Balance> {
+	/// Returns the payout to the stakers of the era and the remainder, given the total balance at
+	/// stake, the total issuance and the duration of the era in seconds.
+	fn era_payout(total_staked: Balance, total_issuance: Balance, era_duration: u64) -> (Balance, Balance);
+}
+
+/// Finds the validator that authored the current block.
+pub trait FindAuthor<AccountId> {
+	/// The author of the current block, if known.
+	fn find_author() -> Option<AccountId>;
+}
+
+impl<AccountId> FindAuthor<AccountId> for () {
+	fn find_author() -> Option<AccountId> {
+		None
+	}
+}
+
+impl<Balance: Zero> EraPayout<Balance> for () {
+	fn era_payout(_total_staked: Balance, _total_issuance: Balance, _era_duration: u64) -> (Balance, Balance) {
+		(Zero::zero(), Zero::zero())
+	}
+}
+
+/// An NPoS inflation curve, parameterised by the `IdealStakeRatio`, `MinInflation` and
+/// `MaxInflation` storage items.
+///
+/// The annual inflation paid to stakers rises linearly from the minimum when nothing is staked
+/// to the maximum at the ideal stake ratio, then falls linearly back to the minimum when
+/// everything is staked. The remainder is what is left of the maximum inflation.
+pub struct InflationCurve<T>(::rstd::marker::PhantomData<T>);
+
+impl<T: Trait> EraPayout<T::Balance> for InflationCurve<T> {
+	fn era_payout(total_staked: T::Balance, total_issuance: T::Balance, era_duration: u64) -> (T::Balance, T::Balance) {
+		let staked = billionths_of(total_staked, total_issuance);
+		let ideal = <Module<T>>::ideal_stake_ratio().times(BILLION);
+		let min = <Module<T>>::min_inflation().times(BILLION);
+		let max = <Module<T>>::max_inflation().times(BILLION).max(min);
+
+		let inflation = if staked <= ideal {
+			min + (max - min) * staked / ideal.max(1)
+		} else {
+			min + (max - min) * (BILLION - staked) / (BILLION - ideal).max(1)
+		};
+
+		// the annual rates, applied to the part of the year the era lasted.
+		let for_era = |rate: u64| {
+			let rate = rate as u128 * era_duration as u128 / SECONDS_PER_YEAR as u128;
+			Perbill::from_billionths(rate.min(BILLION as u128) as u32).times(total_issuance)
+		};
+		let to_stakers = for_era(inflation);
+		let max_payout = for_era(max);
+
+		(to_stakers, max_payout.max(to_stakers) - to_stakers)
+	}
+}
+
+/// `part / whole` in billionths, at most one billion.
+fn billionths_of<Balance: As<u64> + Zero + PartialOrd + Copy + ::rstd::ops::Mul<Output=Balance> + ::rstd::ops::Div<Output=Balance>>(
+	part: Balance,
+	whole: Balance,
+) -> u64 {
+	if whole.is_zero() {
+		return 0;
+	}
+	let billion = Balance::sa(BILLION);
+	let ratio = if whole < billion {
+		part * billion / whole
+	} else {
+		part / (whole / billion)
+	};
+	ratio.as_().min(BILLION)
+}
+
 pub trait Trait: balances::Trait + session::Trait {
 	/// Some tokens minted.
 	type OnRewardMinted: OnDilution<<Self as balances::Trait>::Balance>;
 
+	/// The payout minted at the end of each era.
+	type EraPayout: EraPayout<<Self as balances::Trait>::Balance>;
+
+	/// Finds the author of each block, who earns an era point for it.
+	type FindAuthor: FindAuthor<Self::AccountId>;
+
+	/// Handler for the remainder of the era payout that doesn't go to stakers.
+	type RewardRemainder: OnUnbalanced<<Self as balances::Trait>::Balance>;
+
 	/// The overarching event type.
 	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
 }
@@ -102,17 +235,24 @@ decl_module! {
 	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
 		fn deposit_event() = default;
 
+		fn on_finalise() {
+			if let Some(author) = T::FindAuthor::find_author() {
+				Self::add_era_points(&author, BLOCK_AUTHOR_POINTS);
+			}
+		}
+
 		/// Declare the desire to stake for the transactor.
 		///
 		/// Effects will be felt at the beginning of the next era.
 		fn stake(origin) -> Result {
 			let who = ensure_signed(origin)?;
+			let stash = Self::stash_of(&who).ok_or("Account must be a controller")?;
 			ensure!(Self::nominating(&who).is_none(), "Cannot stake if already nominating.");
 			let mut intentions = <Intentions<T>>::get();
 			// can't be in the list twice.
 			ensure!(intentions.iter().find(|&t| t == &who).is_none(), "Cannot stake if already staked.");
 
-			<Bondage<T>>::insert(&who, T::BlockNumber::max_value());
+			<Bondage<T>>::insert(&stash, T::BlockNumber::max_value());
 			intentions.push(who);
 			<Intentions<T>>::put(intentions);
 			Ok(())
@@ -133,6 +273,7 @@ decl_module! {
 
 		fn nominate(origin, target: Address<T::AccountId, T::AccountIndex>) -> Result {
 			let who = ensure_signed(origin)?;
+			let stash = Self::stash_of(&who).ok_or("Account must be a controller")?;
 			let target = <balances::Module<T>>::lookup(target)?;
 
 			ensure!(Self::nominating(&who).is_none(), "Cannot nominate if already nominating.");
@@ -147,7 +288,7 @@ decl_module! {
 			<Nominating<T>>::insert(&who, &target);
 
 			// Update bondage
-			<Bondage<T>>::insert(&who, T::BlockNumber::max_value());
+			<Bondage<T>>::insert(&stash, T::BlockNumber::max_value());
 
 			Ok(())
 		}
@@ -176,10 +317,12 @@ decl_module! {
 			<Nominating<T>>::remove(&source);
 
 			// update bondage
-			<Bondage<T>>::insert(
-				source,
-				<system::Module<T>>::block_number() + Self::bonding_duration()
-			);
+			if let Some(stash) = Self::stash_of(&source) {
+				<Bondage<T>>::insert(
+					stash,
+					<system::Module<T>>::block_number() + Self::bonding_duration()
+				);
+			}
 			Ok(())
 		}
 
@@ -189,7 +332,7 @@ decl_module! {
 		fn register_preferences(
 			origin,
 			intentions_index: Compact<u32>,
-			prefs: ValidatorPrefs<T::Balance>
+			prefs: ValidatorPrefs
 		) -> Result {
 			let who = ensure_signed(origin)?;
 			let intentions_index: u32 = intentions_index.into();
@@ -203,6 +346,75 @@ decl_module! {
 			Ok(())
 		}
 
+		/// Bond the transactor as a stash, to be operated by the given controller account. Rewards
+		/// of the stash are paid as given by `payee`.
+		///
+		/// A stash may be its own controller, though that requires its key to be kept online.
+		fn bond(
+			origin,
+			controller: Address<T::AccountId, T::AccountIndex>,
+			payee: RewardDestination
+		) -> Result {
+			let stash = ensure_signed(origin)?;
+			ensure!(!<Bonded<T>>::exists(&stash), "Stash is already bonded.");
+			let controller = <balances::Module<T>>::lookup(controller)?;
+			ensure!(!<StashOf<T>>::exists(&controller), "Controller is already paired.");
+
+			<Bonded<T>>::insert(&stash, &controller);
+			<StashOf<T>>::insert(&controller, &stash);
+			<Payee<T>>::insert(&stash, payee);
+			Ok(())
+		}
+
+		/// Release the stash operated by the transactor. The funds of the stash stay locked until
+		/// the end of the bonding duration of its last stake or nomination.
+		fn unbond(origin) -> Result {
+			let controller = ensure_signed(origin)?;
+			let stash = Self::stash_of(&controller).ok_or("Account must be a controller")?;
+			ensure!(
+				Self::nominating(&controller).is_none() && !Self::intentions().contains(&controller),
+				"Cannot unbond while staking or nominating."
+			);
+
+			<StashOf<T>>::remove(&controller);
+			<Bonded<T>>::remove(&stash);
+			<Payee<T>>::remove(&stash);
+			Ok(())
+		}
+
+		/// Set where the rewards of the stash operated by the transactor are paid into.
+		fn set_payee(origin, dest: RewardDestination) -> Result {
+			let controller = ensure_signed(origin)?;
+			let stash = Self::stash_of(&controller).ok_or("Account must be a controller")?;
+			<Payee<T>>::insert(stash, dest);
+			Ok(())
+		}
+
+		/// Submit the validators to elect at the next era, as computed off-chain by
+		/// `election_solution`. The solution is only queued if it is feasible and scores better
+		/// than the one already queued, and `solution_deposit` is reserved from the transactor.
+		///
+		/// The deposit is slashed if the solution loses, either to a better submission or to the
+		/// on-chain election at the end of the era, and returned otherwise.
+		fn submit_election_solution(origin, winners: Vec<T::AccountId>) -> Result {
+			let who = ensure_signed(origin)?;
+			let score = Self::check_election_solution(&winners)?;
+			let queued = Self::queued_elected();
+			if let Some(ref queued) = queued {
+				ensure!(score > queued.score, "Solution does not improve on the queued one.");
+			}
+
+			let deposit = Self::solution_deposit();
+			<balances::Module<T>>::reserve(&who, deposit)?;
+			if let Some(queued) = queued {
+				let _ = <balances::Module<T>>::slash_reserved(&queued.who, queued.deposit);
+			}
+
+			<QueuedElected<T>>::put(QueuedSolution { winners, score, who, deposit });
+			Self::deposit_event(RawEvent::SolutionStored(score.0, score.1));
+			Ok(())
+		}
+
 		/// Set the number of sessions in an era.
 		fn set_sessions_per_era(new: <T::BlockNumber as HasCompact>::Type) -> Result {
 			<NextSessionsPerEra<T>>::put(new.into());
@@ -223,7 +435,7 @@ decl_module! {
 		}
 
 		/// Force there to be a new era. This also forces a new session immediately after.
-		/// `apply_rewards` should be true for validators to get the session reward.
+		/// `apply_rewards` is passed on to the session module; staking pays rewards per era only.
 		fn force_new_era(apply_rewards: bool) -> Result {
 			Self::apply_force_new_era(apply_rewards)
 		}
@@ -240,8 +452,11 @@ decl_module! {
 /// An event in this module.
 decl_event!(
 	pub enum Event<T> where <T as balances::Trait>::Balance, <T as system::Trait>::AccountId {
-		/// All validators have been rewarded by the given balance.
-		Reward(Balance),
+		/// An era ended; its stakers were paid the first balance, the second went to the remainder.
+		EraPaid(Balance, Balance),
+		/// An election solution was queued for the next era; its validators are backed by at
+		/// least the first balance and by the second in total.
+		SolutionStored(Balance, Balance),
 		/// One validator (and their nominators) has been given a offline-warning (they're still
 		/// within their grace). The accrued number of slashes is recorded, too.
 		OfflineWarning(AccountId, u32),
@@ -261,8 +476,6 @@ decl_storage! {
 		pub MinimumValidatorCount get(minimum_validator_count) config(): u32 = DEFAULT_MINIMUM_VALIDATOR_COUNT;
 		/// The length of a staking era in sessions.
 		pub SessionsPerEra get(sessions_per_era) config(): T::BlockNumber = T::BlockNumber::sa(1000);
-		/// Maximum reward, per validator, that is provided per acceptable session.
-		pub SessionReward get(session_reward) config(): Perbill = Perbill::from_billionths(60);
 		/// Slash, per validator that is taken for the first time they are found to be offline.
 		pub OfflineSlash get(offline_slash) config(): Perbill = Perbill::from_millionths(1000); // Perbill::from_fraction() is only for std, so use from_millionths().
 		/// Number of instances of offline reports before slashing begins for validators.
@@ -273,7 +486,13 @@ decl_storage! {
 		/// The current era index.
 		pub CurrentEra get(current_era) config(): T::BlockNumber;
 		/// Preferences that a validator has.
-		pub ValidatorPreferences get(validator_preferences): map T::AccountId => ValidatorPrefs<T::Balance>;
+		pub ValidatorPreferences get(validator_preferences): map T::AccountId => ValidatorPrefs;
+		/// The controller operating a bonded stash account.
+		pub Bonded get(bonded): map T::AccountId => Option<T::AccountId>;
+		/// The stash account operated by a controller account.
+		pub StashOf get(stash_of): map T::AccountId => Option<T::AccountId>;
+		/// Where the rewards of a stash are paid into.
+		pub Payee get(payee): map T::AccountId => RewardDestination;
 		/// All the accounts with a desire to stake.
 		pub Intentions get(intentions) config(): Vec<T::AccountId>;
 		/// All nominator -> nominee relationships.
@@ -283,8 +502,6 @@ decl_storage! {
 		/// Nominators for a particular account that is in action right now.
 		pub CurrentNominatorsFor get(current_nominators_for): map T::AccountId => Vec<T::AccountId>;
 
-		/// Maximum reward, per validator, that is provided per acceptable session.
-		pub CurrentSessionReward get(current_session_reward) config(): T::Balance;
 		/// Slash, per validator that is taken for the first time they are found to be offline.
 		pub CurrentOfflineSlash get(current_offline_slash) config(): T::Balance;
 
@@ -296,13 +513,42 @@ decl_storage! {
 		/// The highest and lowest staked validator slashable balances.
 		pub StakeRange get(stake_range): PairOf<T::Balance>;
 
-		/// The block at which the `who`'s funds become entirely liquid.
+		/// The block at which the funds of the stash `who` become entirely liquid.
 		pub Bondage get(bondage): map T::AccountId => T::BlockNumber;
 		/// The number of times a given validator has been reported offline. This gets decremented by one each era that passes.
 		pub SlashCount get(slash_count): map T::AccountId => u32;
 
 		/// We are forcing a new era.
 		pub ForcingNewEra get(forcing_new_era): Option<()>;
+
+		/// The best election solution submitted for the next era.
+		pub QueuedElected get(queued_elected): Option<QueuedSolution<T::AccountId, T::Balance>>;
+		/// The deposit reserved for submitting an election solution.
+		pub SolutionDeposit get(solution_deposit) config(): T::Balance;
+
+		/// The stake ratio at which the `InflationCurve` pays out the most to stakers.
+		pub IdealStakeRatio get(ideal_stake_ratio) config(): Perbill = Perbill::from_percent(50);
+		/// The annual inflation paid to stakers by the `InflationCurve` at the extremes of the curve.
+		pub MinInflation get(min_inflation) config(): Perbill = Perbill::from_percent(2);
+		/// The annual inflation paid to stakers by the `InflationCurve` at the ideal stake ratio.
+		pub MaxInflation get(max_inflation) config(): Perbill = Perbill::from_percent(10);
+
+		/// The points earned by the validators of the current era, which the era payout is split by.
+		pub EraPoints get(era_points): map T::AccountId => u32;
+		/// The time elapsed in the current era, as reported by the session module.
+		pub EraElapsed get(era_elapsed): T::Moment;
+	}
+	add_extra_genesis {
+		config(stakers): Vec<(T::AccountId, T::AccountId)>;
+
+		build(|storage: &mut primitives::StorageMap, _: &mut primitives::ChildrenStorageMap, config: &GenesisConfig<T>| {
+			use codec::Encode;
+
+			for &(ref stash, ref controller) in &config.stakers {
+				storage.insert(<Bonded<T>>::key_for(stash), controller.encode());
+				storage.insert(<StashOf<T>>::key_for(controller), stash.encode());
+			}
+		});
 	}
 }
 
@@ -320,21 +566,27 @@ impl<T: Trait> Module<T> {
 		Self::sessions_per_era() * <session::Module<T>>::length()
 	}
 
+	/// The total balance of the stash operated by a controller; zero if it operates none.
+	pub fn stash_balance(controller: &T::AccountId) -> T::Balance {
+		Self::stash_of(controller)
+			.map_or_else(Zero::zero, |stash| <balances::Module<T>>::total_balance(&stash))
+	}
+
 	/// Balance of a (potential) validator that includes all nominators.
 	pub fn nomination_balance(who: &T::AccountId) -> T::Balance {
 		Self::nominators_for(who).iter()
-			.map(<balances::Module<T>>::total_balance)
+			.map(Self::stash_balance)
 			.fold(Zero::zero(), |acc, x| acc + x)
 	}
 
-	/// The total balance that can be slashed from an account.
+	/// The total balance that can be slashed from the stashes of a controller and its nominators.
 	pub fn slashable_balance(who: &T::AccountId) -> T::Balance {
 		Self::nominators_for(who).iter()
-			.map(<balances::Module<T>>::total_balance)
-			.fold(<balances::Module<T>>::total_balance(who), |acc, x| acc + x)
+			.map(Self::stash_balance)
+			.fold(Self::stash_balance(who), |acc, x| acc + x)
 	}
 
-	/// The block at which the `who`'s funds become entirely liquid.
+	/// The block at which the funds of the stash `who` become entirely liquid.
 	pub fn unlock_block(who: &T::AccountId) -> LockStatus<T::BlockNumber> {
 		match Self::bondage(who) {
 			i if i == T::BlockNumber::max_value() => LockStatus::Bonded,
@@ -354,38 +606,65 @@ impl<T: Trait> Module<T> {
 			return
 		}
 
-		if let Some(rem) = <balances::Module<T>>::slash(v, slash) {
+		let rem = match Self::stash_of(v) {
+			Some(stash) => <balances::Module<T>>::slash(&stash, slash),
+			None => Some(slash),
+		};
+		if let Some(rem) = rem {
 			let noms = Self::current_nominators_for(v);
-			let total = noms.iter().map(<balances::Module<T>>::total_balance).fold(T::Balance::zero(), |acc, x| acc + x);
+			let total = noms.iter().map(Self::stash_balance).fold(T::Balance::zero(), |acc, x| acc + x);
 			if !total.is_zero() {
 				let safe_mul_rational = |b| b * rem / total;// TODO: avoid overflow
 				for n in noms.iter() {
-					let _ = <balances::Module<T>>::slash(n, safe_mul_rational(<balances::Module<T>>::total_balance(n)));	// best effort - not much that can be done on fail.
+					if let Some(stash) = Self::stash_of(n) {
+						let _ = <balances::Module<T>>::slash(&stash, safe_mul_rational(Self::stash_balance(n)));	// best effort - not much that can be done on fail.
+					}
 				}
 			}
 		}
 	}
 
+	/// Award era points to a validator of the current era.
+	pub fn add_era_points(validator: &T::AccountId, points: u32) {
+		<EraPoints<T>>::mutate(validator, |p| *p = p.saturating_add(points));
+	}
+
 	/// Reward a given validator by a specific amount. Add the reward to their, and their nominators'
 	/// balance, pro-rata.
 	fn reward_validator(who: &T::AccountId, reward: T::Balance) {
-		let off_the_table = reward.min(Self::validator_preferences(who).validator_payment);
-		let reward = reward - off_the_table;
+		let commission = Self::validator_preferences(who).commission.times(reward);
+		let reward = reward - commission;
 		let validator_cut = if reward.is_zero() {
 			Zero::zero()
 		} else {
 			let noms = Self::current_nominators_for(who);
 			let total = noms.iter()
-				.map(<balances::Module<T>>::total_balance)
-				.fold(<balances::Module<T>>::total_balance(who), |acc, x| acc + x)
+				.map(Self::stash_balance)
+				.fold(Self::stash_balance(who), |acc, x| acc + x)
 				.max(One::one());
 			let safe_mul_rational = |b| b * reward / total;// TODO: avoid overflow
 			for n in noms.iter() {
-				let _ = <balances::Module<T>>::reward(n, safe_mul_rational(<balances::Module<T>>::total_balance(n)));
+				Self::make_payout(n, safe_mul_rational(Self::stash_balance(n)));
 			}
-			safe_mul_rational(<balances::Module<T>>::total_balance(who))
+			safe_mul_rational(Self::stash_balance(who))
 		};
-		let _ = <balances::Module<T>>::reward(who, validator_cut + off_the_table);
+		Self::make_payout(who, validator_cut + commission);
+	}
+
+	/// Pay a reward to the staker operated by the given controller, into the account chosen by
+	/// the `RewardDestination` of its stash.
+	///
+	/// The whole balance of a stash is at stake, so rewards paid into it are staked either way.
+	fn make_payout(controller: &T::AccountId, amount: T::Balance) {
+		let stash = match Self::stash_of(controller) {
+			Some(stash) => stash,
+			None => return,
+		};
+		let dest = match Self::payee(&stash) {
+			RewardDestination::Staked | RewardDestination::Stash => stash,
+			RewardDestination::Controller => controller.clone(),
+		};
+		let _ = <balances::Module<T>>::reward(&dest, amount);
 	}
 
 	/// Actually carry out the unstake operation.
@@ -399,35 +678,16 @@ impl<T: Trait> Module<T> {
 		<Intentions<T>>::put(intentions);
 		<ValidatorPreferences<T>>::remove(who);
 		<SlashCount<T>>::remove(who);
-		<Bondage<T>>::insert(who, <system::Module<T>>::block_number() + Self::bonding_duration());
-		Ok(())
-	}
-
-	/// Get the reward for the session, assuming it ends with this block.
-	fn this_session_reward(actual_elapsed: T::Moment) -> T::Balance {
-		let ideal_elapsed = <session::Module<T>>::ideal_session_duration();
-		if ideal_elapsed.is_zero() {
-			return Self::current_session_reward();
+		if let Some(stash) = Self::stash_of(who) {
+			<Bondage<T>>::insert(stash, <system::Module<T>>::block_number() + Self::bonding_duration());
 		}
-		let per65536: u64 = (T::Moment::sa(65536u64) * ideal_elapsed.clone() / actual_elapsed.max(ideal_elapsed)).as_();
-		Self::current_session_reward() * T::Balance::sa(per65536) / T::Balance::sa(65536u64)
+		Ok(())
 	}
 
-	/// Session has just changed. We need to determine whether we pay a reward, slash and/or
-	/// move to a new era.
-	fn new_session(actual_elapsed: T::Moment, should_reward: bool) {
-		if should_reward {
-			// apply good session reward
-			let reward = Self::this_session_reward(actual_elapsed);
-			let validators = <session::Module<T>>::validators();
-			for v in validators.iter() {
-				Self::reward_validator(v, reward);
-			}
-			Self::deposit_event(RawEvent::Reward(reward));
-			let total_minted = reward * <T::Balance as As<usize>>::sa(validators.len());
-			let total_rewarded_stake = Self::stake_range().1 * <T::Balance as As<usize>>::sa(validators.len());
-			T::OnRewardMinted::on_dilution(total_minted, total_rewarded_stake);
-		}
+	/// Session has just changed. We need to determine whether we move to a new era, which pays
+	/// out the era that ends.
+	fn new_session(actual_elapsed: T::Moment) {
+		<EraElapsed<T>>::mutate(|e| *e += actual_elapsed.clone());
 
 		let session_index = <session::Module<T>>::current_index();
 		if <ForcingNewEra<T>>::take().is_some()
@@ -437,11 +697,90 @@ impl<T: Trait> Module<T> {
 		}
 	}
 
+	/// Mint the payout of the era that is ending and split it between its validators by their era
+	/// points. If no points were earned, everything goes to the remainder.
+	fn pay_era() {
+		let era_duration = <EraElapsed<T>>::take().as_();
+		let validators = <session::Module<T>>::validators();
+		let points: Vec<u32> = validators.iter().map(|v| <EraPoints<T>>::take(v)).collect();
+		let total_points = points.iter().fold(0u32, |acc, p| acc.saturating_add(*p));
+
+		let total_staked = validators.iter()
+			.map(Self::slashable_balance)
+			.fold(Zero::zero(), |acc: T::Balance, x| acc + x);
+		let total_issuance = <balances::Module<T>>::total_issuance();
+		let (mut to_stakers, mut remainder) = T::EraPayout::era_payout(total_staked, total_issuance, era_duration);
+
+		if total_points == 0 {
+			remainder += to_stakers;
+			to_stakers = Zero::zero();
+		}
+		for (v, p) in validators.iter().zip(points) {
+			if p > 0 {
+				Self::reward_validator(v, to_stakers * T::Balance::sa(p as u64) / T::Balance::sa(total_points as u64));
+			}
+		}
+		if !to_stakers.is_zero() {
+			T::OnRewardMinted::on_dilution(to_stakers, total_staked);
+		}
+		T::RewardRemainder::on_unbalanced(remainder);
+		Self::deposit_event(RawEvent::EraPaid(to_stakers, remainder));
+	}
+
+	/// Compute the validators to elect at the next era: the most backed of the current
+	/// intentions. This is what offchain workers submit through `submit_election_solution`, and
+	/// the on-chain election that a queued solution has to score as well as. `None` if there are
+	/// too few intentions.
+	pub fn election_solution() -> Option<Vec<T::AccountId>> {
+		let mut intentions = Self::intentions()
+			.into_iter()
+			.map(|v| (Self::slashable_balance(&v), v))
+			.collect::<Vec<_>>();
+
+		// Avoid reevaluate validator set if it would leave us with fewer than the minimum
+		// needed validators
+		if intentions.len() < Self::minimum_validator_count() as usize {
+			return None
+		}
+
+		intentions.sort_unstable_by(|&(ref b1, _), &(ref b2, _)| b2.cmp(&b1));
+
+		Some(intentions.into_iter()
+			.map(|(_, v)| v)
+			.take(Self::validator_count() as usize)
+			.collect())
+	}
+
+	/// Check that an election solution is feasible against the current intentions and return its
+	/// score: the lowest and the total slashable balance of the validators it elects.
+	fn check_election_solution(winners: &[T::AccountId]) -> result::Result<PairOf<T::Balance>, &'static str> {
+		let intentions = Self::intentions();
+		ensure!(intentions.len() >= Self::minimum_validator_count() as usize, "Too few intentions to elect.");
+
+		let desired_validator_count = cmp::min(Self::validator_count() as usize, intentions.len());
+		ensure!(winners.len() == desired_validator_count, "Solution elects the wrong number of validators.");
+
+		let mut lowest: Option<T::Balance> = None;
+		let mut total: T::Balance = Zero::zero();
+		for (i, v) in winners.iter().enumerate() {
+			ensure!(intentions.contains(v), "Solution elects an account that does not intend to stake.");
+			ensure!(!winners[..i].contains(v), "Solution elects a validator twice.");
+
+			let balance = Self::slashable_balance(v);
+			lowest = Some(lowest.map_or(balance, |lowest| cmp::min(lowest, balance)));
+			total = total + balance;
+		}
+
+		Ok((lowest.unwrap_or_else(Zero::zero), total))
+	}
+
 	/// The era has changed - enact new staking set.
 	///
 	/// NOTE: This always happens immediately before a session change to ensure that new validators
 	/// get a chance to set their session keys.
 	fn new_era() {
+		Self::pay_era();
+
 		// Increment current era.
 		<CurrentEra<T>>::put(&(<CurrentEra<T>>::get() + One::one()));
 
@@ -453,37 +792,41 @@ impl<T: Trait> Module<T> {
 			}
 		}
 
-		// evaluate desired staking amounts and nominations and optimise to find the best
-		// combination of validators, then use session::internal::set_validators().
-		// for now, this just orders would-be stakers by their balances and chooses the top-most
-		// <ValidatorCount<T>>::get() of them.
-		// TODO: this is not sound. this should be moved to an off-chain solution mechanism.
-		let mut intentions = Self::intentions()
-			.into_iter()
-			.map(|v| (Self::slashable_balance(&v), v))
-			.collect::<Vec<_>>();
-
-		// Avoid reevaluate validator set if it would leave us with fewer than the minimum
-		// needed validators
-		if intentions.len() < Self::minimum_validator_count() as usize {
-			return
-		}
-
-		intentions.sort_unstable_by(|&(ref b1, _), &(ref b2, _)| b2.cmp(&b1));
-
-		let desired_validator_count = <ValidatorCount<T>>::get() as usize;
-		let stake_range = if !intentions.is_empty() {
-			let n = cmp::min(desired_validator_count, intentions.len());
-			(intentions[0].0, intentions[n - 1].0)
-		} else {
-			(Zero::zero(), Zero::zero())
+		// use the solution submitted off-chain if it is still feasible, as intentions may have
+		// changed since, and scores at least as well as the on-chain election. Its deposit is
+		// slashed if the on-chain election beats it. Then use session::internal::set_validators().
+		let onchain = Self::election_solution();
+		let onchain_score = onchain.as_ref().and_then(|winners| Self::check_election_solution(winners).ok());
+		let queued = <QueuedElected<T>>::take().and_then(|queued| {
+			let score = match Self::check_election_solution(&queued.winners) {
+				Ok(score) => score,
+				Err(_) => {
+					let _ = <balances::Module<T>>::unreserve(&queued.who, queued.deposit);
+					return None;
+				}
+			};
+			if onchain_score.map_or(false, |onchain_score| onchain_score > score) {
+				let _ = <balances::Module<T>>::slash_reserved(&queued.who, queued.deposit);
+				None
+			} else {
+				let _ = <balances::Module<T>>::unreserve(&queued.who, queued.deposit);
+				Some(queued.winners)
+			}
+		});
+		let vals = &match queued.or(onchain) {
+			Some(vals) => vals,
+			None => return,
 		};
+
+		let stake_range = vals.iter()
+			.map(Self::slashable_balance)
+			.fold(None, |range: Option<PairOf<T::Balance>>, b| Some(match range {
+				Some((highest, lowest)) => (cmp::max(highest, b), cmp::min(lowest, b)),
+				None => (b, b),
+			}))
+			.unwrap_or_else(|| (Zero::zero(), Zero::zero()));
 		<StakeRange<T>>::put(&stake_range);
 
-		let vals = &intentions.into_iter()
-			.map(|(_, v)| v)
-			.take(desired_validator_count)
-			.collect::<Vec<_>>();
 		for v in <session::Module<T>>::validators().iter() {
 			<CurrentNominatorsFor<T>>::remove(v);
 			let slash_count = <SlashCount<T>>::take(v);
@@ -498,13 +841,12 @@ impl<T: Trait> Module<T> {
 
 		// Update the balances for slashing/rewarding according to the stakes.
 		<CurrentOfflineSlash<T>>::put(Self::offline_slash().times(stake_range.1));
-		<CurrentSessionReward<T>>::put(Self::session_reward().times(stake_range.1));
 	}
 }
 
 impl<T: Trait> OnSessionChange<T::Moment> for Module<T> {
-	fn on_session_change(elapsed: T::Moment, should_reward: bool) {
-		Self::new_session(elapsed, should_reward);
+	fn on_session_change(elapsed: T::Moment, _should_reward: bool) {
+		Self::new_session(elapsed);
 	}
 }
 
@@ -521,6 +863,10 @@ impl<T: Trait> balances::EnsureAccountLiquid<T::AccountId> for Module<T> {
 impl<T: Trait> balances::OnFreeBalanceZero<T::AccountId> for Module<T> {
 	fn on_free_balance_zero(who: &T::AccountId) {
 		<Bondage<T>>::remove(who);
+		<Payee<T>>::remove(who);
+		if let Some(controller) = <Bonded<T>>::take(who) {
+			<StashOf<T>>::remove(controller);
+		}
 	}
 }
 
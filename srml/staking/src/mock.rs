@@ -18,12 +18,29 @@
 
 #![cfg(test)]
 
+use std::cell::RefCell;
 use primitives::BuildStorage;
 use primitives::{Perbill, traits::Identity};
 use primitives::testing::{Digest, DigestItem, Header};
 use substrate_primitives::{H256, Blake2Hasher};
 use runtime_io;
-use {GenesisConfig, Module, Trait, consensus, session, system, timestamp, balances};
+use {GenesisConfig, Module, Trait, InflationCurve, FindAuthor, consensus, session, system, timestamp, balances};
+
+thread_local! {
+	static AUTHOR: RefCell<Option<u64>> = RefCell::new(None);
+}
+
+/// Block author that tests set through `set_author`.
+pub struct Author;
+impl FindAuthor<u64> for Author {
+	fn find_author() -> Option<u64> {
+		AUTHOR.with(|a| *a.borrow())
+	}
+}
+
+pub fn set_author(author: Option<u64>) {
+	AUTHOR.with(|a| *a.borrow_mut() = author);
+}
 
 impl_outer_origin!{
 	pub enum Origin for Test {}
@@ -39,6 +56,7 @@ impl consensus::Trait for Test {
 	type OnOfflineValidator = ();
 }
 impl system::Trait for Test {
+	const SPEC_VERSION: u32 = 0;
 	type Origin = Origin;
 	type Index = u64;
 	type BlockNumber = u64;
@@ -49,12 +67,16 @@ impl system::Trait for Test {
 	type Header = Header;
 	type Event = ();
 	type Log = DigestItem;
+	type BaseCallFilter = ();
+	type CallWeigher = ();
 }
 impl balances::Trait for Test {
 	type Balance = u64;
 	type AccountIndex = u64;
 	type OnFreeBalanceZero = Staking;
 	type EnsureAccountLiquid = Staking;
+	type DustRemoval = ();
+	type OnChargeTransaction = balances::Module<Test>;
 	type Event = ();
 }
 impl session::Trait for Test {
@@ -68,6 +90,9 @@ impl timestamp::Trait for Test {
 }
 impl Trait for Test {
 	type OnRewardMinted = ();
+	type EraPayout = InflationCurve<Test>;
+	type RewardRemainder = ();
+	type FindAuthor = Author;
 	type Event = ();
 }
 
@@ -107,6 +132,7 @@ pub fn new_test_ext(
 		},
 		transaction_base_fee: 0,
 		transaction_byte_fee: 0,
+		transaction_weight_fee: 0,
 		existential_deposit: ext_deposit,
 		transfer_fee: 0,
 		creation_fee: 0,
@@ -117,14 +143,17 @@ pub fn new_test_ext(
 		sessions_per_era,
 		current_era,
 		intentions: vec![10, 20],
+		stakers: vec![(1, 1), (2, 2), (3, 3), (4, 4), (10, 10), (20, 20)],
 		validator_count: 2,
 		minimum_validator_count: 0,
 		bonding_duration: sessions_per_era * session_length * 3,
-		session_reward: Perbill::from_millionths((1000000 * reward / balance_factor) as u32),
 		offline_slash: if monied { Perbill::from_percent(40) } else { Perbill::zero() },
-		current_session_reward: reward,
 		current_offline_slash: 20,
 		offline_slash_grace: 0,
+		solution_deposit: 5,
+		ideal_stake_ratio: Perbill::from_percent(50),
+		min_inflation: Perbill::from_percent(2),
+		max_inflation: Perbill::from_percent(10),
 		_genesis_phantom_data: Default::default(),
 	}.build_storage().unwrap().0);
 	t.extend(timestamp::GenesisConfig::<Test>{
@@ -20,8 +20,9 @@
 
 use super::*;
 use consensus::OnOfflineValidator;
+use primitives::traits::OnFinalise;
 use runtime_io::with_externalities;
-use mock::{Balances, Session, Staking, System, Timestamp, Test, new_test_ext, Origin};
+use mock::{Balances, Session, Staking, System, Timestamp, Test, new_test_ext, set_author, Origin};
 
 #[test]
 fn note_null_offline_should_work() {
@@ -130,7 +131,7 @@ fn note_offline_auto_unstake_session_change_should_work() {
 	with_externalities(&mut new_test_ext(0, 3, 3, 0, true, 10), || {
 		Balances::set_free_balance(&10, 7000);
 		Balances::set_free_balance(&20, 7000);
-		assert_ok!(Staking::register_preferences(Origin::signed(10), 0.into(), ValidatorPrefs { unstake_threshold: 1, validator_payment: 0 }));
+		assert_ok!(Staking::register_preferences(Origin::signed(10), 0.into(), ValidatorPrefs { unstake_threshold: 1, commission: Perbill::zero() }));
 		
 		assert_eq!(Staking::intentions(), vec![10, 20]);
 
@@ -176,23 +177,35 @@ fn rewards_should_work() {
 		assert_eq!(Balances::total_balance(&10), 1);
 
 		System::set_block_number(3);
-		Timestamp::set_timestamp(15);	// on time.
+		Timestamp::set_timestamp(15);
+		set_author(Some(10));
+		<Staking as OnFinalise<u64>>::on_finalise(3);
 		Session::check_rotate_session(System::block_number());
 		assert_eq!(Staking::current_era(), 0);
 		assert_eq!(Session::current_index(), 1);
-		assert_eq!(Balances::total_balance(&10), 11);
+		assert_eq!(Balances::total_balance(&10), 1);	// nothing minted per session
+		assert_eq!(Staking::era_points(&10), 1);
+
 		System::set_block_number(6);
-		Timestamp::set_timestamp(31);	// a little late
+		Timestamp::set_timestamp(31);
+		<Staking as OnFinalise<u64>>::on_finalise(6);
+		set_author(Some(20));
+		<Staking as OnFinalise<u64>>::on_finalise(6);
+		set_author(None);
+		<Staking as OnFinalise<u64>>::on_finalise(6);
 		Session::check_rotate_session(System::block_number());
-		assert_eq!(Staking::current_era(), 0);
 		assert_eq!(Session::current_index(), 2);
-		assert_eq!(Balances::total_balance(&10), 20);	// less reward
+		assert_eq!(Balances::total_balance(&10), 1);
+		assert_eq!(Staking::era_points(&10), 2);
+		assert_eq!(Staking::era_points(&20), 1);
+
 		System::set_block_number(9);
-		Timestamp::set_timestamp(50);	// very late
+		Timestamp::set_timestamp(50);
 		Session::check_rotate_session(System::block_number());
 		assert_eq!(Staking::current_era(), 1);
 		assert_eq!(Session::current_index(), 3);
-		assert_eq!(Balances::total_balance(&10), 27);	// much less reward
+		assert_eq!(Staking::era_points(&10), 0);
+		assert_eq!(Staking::era_points(&20), 0);
 	});
 }
 
@@ -210,13 +223,13 @@ fn slashing_should_work() {
 		Session::check_rotate_session(System::block_number());
 		assert_eq!(Staking::current_era(), 0);
 		assert_eq!(Session::current_index(), 1);
-		assert_eq!(Balances::total_balance(&10), 11);
+		assert_eq!(Balances::total_balance(&10), 1);
 
 		System::set_block_number(6);
 		Session::check_rotate_session(System::block_number());
 		assert_eq!(Staking::current_era(), 0);
 		assert_eq!(Session::current_index(), 2);
-		assert_eq!(Balances::total_balance(&10), 21);
+		Balances::set_free_balance(&10, 21);
 
 		System::set_block_number(7);
 		System::set_extrinsic_index(1);
@@ -316,33 +329,36 @@ fn nominating_and_rewards_should_work() {
 		Session::check_rotate_session(System::block_number());
 		assert_eq!(Staking::current_era(), 2);
 		assert_eq!(Session::validators(), vec![3, 2]);
-		assert_eq!(Balances::total_balance(&1), 16);
+		assert_eq!(Balances::total_balance(&1), 10);
 		assert_eq!(Balances::total_balance(&2), 20);
-		assert_eq!(Balances::total_balance(&3), 60);
-		assert_eq!(Balances::total_balance(&4), 64);
+		assert_eq!(Balances::total_balance(&3), 30);
+		assert_eq!(Balances::total_balance(&4), 40);
 
 		System::set_block_number(3);
 		assert_ok!(Staking::stake(Origin::signed(4)));
 		assert_ok!(Staking::unstake(Origin::signed(3), (Staking::intentions().iter().position(|&x| x == 3).unwrap() as u32).into()));
 		assert_ok!(Staking::nominate(Origin::signed(3), 1.into()));
 		Session::check_rotate_session(System::block_number());
-		assert_eq!(Session::validators(), vec![1, 4]);
-		assert_eq!(Balances::total_balance(&1), 16);
-		assert_eq!(Balances::total_balance(&2), 40);
-		assert_eq!(Balances::total_balance(&3), 80);
-		assert_eq!(Balances::total_balance(&4), 64);
+		let mut validators = Session::validators();
+		validators.sort();
+		assert_eq!(validators, vec![1, 4]);	// 1 + 3, 4
 
 		System::set_block_number(4);
 		Session::check_rotate_session(System::block_number());
-		assert_eq!(Balances::total_balance(&1), 26);
-		assert_eq!(Balances::total_balance(&2), 40);
-		assert_eq!(Balances::total_balance(&3), 133);
-		assert_eq!(Balances::total_balance(&4), 128);
+		assert_eq!(Balances::total_balance(&1), 10);
+		assert_eq!(Balances::total_balance(&3), 30);
+
+		// rewards are split between the validator and its nominators, pro-rata.
+		Staking::reward_validator(&1, 40);
+		assert_eq!(Balances::total_balance(&1), 20);
+		assert_eq!(Balances::total_balance(&2), 20);
+		assert_eq!(Balances::total_balance(&3), 60);
+		assert_eq!(Balances::total_balance(&4), 40);
 	});
 }
 
 #[test]
-fn rewards_with_off_the_table_should_work() {
+fn rewards_with_commission_should_work() {
 	with_externalities(&mut new_test_ext(0, 1, 1, 0, true, 10), || {
 		System::set_block_number(1);
 		assert_ok!(Staking::stake(Origin::signed(1)));
@@ -358,12 +374,26 @@ fn rewards_with_off_the_table_should_work() {
 		assert_ok!(Staking::register_preferences(
 			Origin::signed(1),
 			(Staking::intentions().into_iter().position(|i| i == 1).unwrap() as u32).into(),
-			ValidatorPrefs { unstake_threshold: 3, validator_payment: 4 }
+			ValidatorPrefs { unstake_threshold: 3, commission: Perbill::from_percent(10) }
 		));
 		Session::check_rotate_session(System::block_number());
-		assert_eq!(Balances::total_balance(&1), 22);
-		assert_eq!(Balances::total_balance(&2), 37);
-		assert_eq!(Balances::total_balance(&3), 60);
+		assert_eq!(Balances::total_balance(&1), 10);
+
+		// the commission goes to the validator before the rest is split pro-rata.
+		Staking::reward_validator(&1, 100);
+		assert_eq!(Balances::total_balance(&1), 50);
+		assert_eq!(Balances::total_balance(&2), 80);
+		assert_eq!(Balances::total_balance(&3), 30);
+	});
+}
+
+#[test]
+fn reward_destination_can_be_set() {
+	with_externalities(&mut new_test_ext(0, 1, 1, 0, true, 10), || {
+		assert_eq!(Staking::payee(&1), RewardDestination::Staked);
+		assert_ok!(Staking::set_payee(Origin::signed(1), RewardDestination::Controller));
+		assert_eq!(Staking::payee(&1), RewardDestination::Controller);
+		assert_eq!(Staking::payee(&2), RewardDestination::Staked);
 	});
 }
 
@@ -505,3 +535,112 @@ fn deducting_balance_when_bonded_should_not_work() {
 		assert_noop!(Balances::reserve(&1, 69), "cannot transfer illiquid funds");
 	});
 }
+
+#[test]
+fn inflation_curve_should_peak_at_ideal_stake_ratio() {
+	with_externalities(&mut new_test_ext(0, 1, 1, 0, false, 0), || {
+		let payout = |staked| InflationCurve::<Test>::era_payout(staked, 100, SECONDS_PER_YEAR);
+		assert_eq!(payout(50), (10, 0));
+		assert_eq!(payout(25), (6, 4));
+		assert_eq!(payout(0), (2, 8));
+		assert_eq!(payout(100), (2, 8));
+		assert_eq!(InflationCurve::<Test>::era_payout(50, 100, SECONDS_PER_YEAR / 2), (5, 0));
+	});
+}
+
+#[test]
+fn era_payout_should_be_split_by_era_points() {
+	with_externalities(&mut new_test_ext(0, 1, 1, 0, false, 0), || {
+		Balances::set_free_balance(&10, 50);
+		Balances::set_free_balance(&20, 50);
+		Balances::increase_total_stake_by(198);
+		assert_eq!(Balances::total_issuance(), 200);
+
+		<EraElapsed<Test>>::put(SECONDS_PER_YEAR);
+		Staking::add_era_points(&10, 3);
+		Staking::add_era_points(&20, 1);
+		Staking::pay_era();
+
+		assert_eq!(Balances::total_balance(&10), 65);
+		assert_eq!(Balances::total_balance(&20), 55);
+		assert_eq!(Staking::era_points(&10), 0);
+		assert_eq!(Staking::era_elapsed(), 0);
+	});
+}
+
+#[test]
+fn controller_operates_bonded_stash() {
+	with_externalities(&mut new_test_ext(0, 1, 1, 0, true, 10), || {
+		assert_ok!(Staking::unbond(Origin::signed(1)));
+		assert_ok!(Staking::unbond(Origin::signed(2)));
+		assert_ok!(Staking::bond(Origin::signed(1), 2.into(), RewardDestination::Controller));
+		assert_eq!(Staking::bonded(&1), Some(2));
+		assert_eq!(Staking::stash_of(&2), Some(1));
+
+		assert_noop!(Staking::bond(Origin::signed(1), 3.into(), RewardDestination::Staked), "Stash is already bonded.");
+		assert_noop!(Staking::bond(Origin::signed(3), 2.into(), RewardDestination::Staked), "Controller is already paired.");
+		assert_noop!(Staking::stake(Origin::signed(1)), "Account must be a controller");
+
+		assert_ok!(Staking::stake(Origin::signed(2)));
+		assert_noop!(Staking::unbond(Origin::signed(2)), "Cannot unbond while staking or nominating.");
+		assert_eq!(Staking::slashable_balance(&2), 10);
+		assert_eq!(Staking::unlock_block(&1), LockStatus::Bonded);
+		assert_noop!(Balances::transfer(Origin::signed(1), 3.into(), 5.into()), "cannot transfer illiquid funds");
+
+		// rewards go to the controller, as chosen when bonding.
+		Staking::reward_validator(&2, 10);
+		assert_eq!(Balances::total_balance(&1), 10);
+		assert_eq!(Balances::total_balance(&2), 30);
+	});
+}
+
+#[test]
+fn submitted_election_solution_is_used_at_next_era() {
+	with_externalities(&mut new_test_ext(0, 1, 2, 0, true, 0), || {
+		System::set_block_number(1);
+		assert_ok!(Staking::stake(Origin::signed(1)));
+		assert_ok!(Staking::stake(Origin::signed(2)));
+		assert_ok!(Staking::stake(Origin::signed(4)));
+		assert_eq!(Staking::election_solution(), Some(vec![4, 2]));
+
+		assert_noop!(Staking::submit_election_solution(Origin::signed(1), vec![1]), "Solution elects the wrong number of validators.");
+		assert_noop!(Staking::submit_election_solution(Origin::signed(1), vec![1, 3]), "Solution elects an account that does not intend to stake.");
+		assert_noop!(Staking::submit_election_solution(Origin::signed(1), vec![1, 1]), "Solution elects a validator twice.");
+
+		assert_ok!(Staking::submit_election_solution(Origin::signed(1), vec![1, 2]));
+		assert_eq!(Staking::queued_elected().map(|q| (q.winners, q.score)), Some((vec![1, 2], (10, 30))));
+		assert_eq!(Balances::reserved_balance(&1), 5);
+		assert_noop!(Staking::submit_election_solution(Origin::signed(1), vec![2, 1]), "Solution does not improve on the queued one.");
+
+		// not the best solution, but a better one than queued: the losing deposit is slashed.
+		assert_ok!(Staking::submit_election_solution(Origin::signed(3), vec![4, 1]));
+		assert_eq!(Staking::queued_elected().map(|q| (q.winners, q.who)), Some((vec![4, 1], 3)));
+		assert_eq!(Balances::reserved_balance(&1), 0);
+		assert_eq!(Balances::total_balance(&1), 5);
+		assert_eq!(Balances::reserved_balance(&3), 5);
+
+		// Block 2: the on-chain election beats the queued solution, whose deposit is slashed.
+		System::set_block_number(2);
+		Session::check_rotate_session(System::block_number());
+		assert_eq!(Staking::current_era(), 1);
+		assert_eq!(Session::validators(), vec![4, 2]);
+		assert_eq!(Staking::queued_elected(), None);
+		assert_eq!(Balances::reserved_balance(&3), 0);
+		assert_eq!(Balances::total_balance(&3), 25);
+
+		// a solution as good as the on-chain election is used, and its deposit returned.
+		System::set_block_number(3);
+		Session::check_rotate_session(System::block_number());
+		assert_ok!(Staking::submit_election_solution(Origin::signed(3), vec![2, 4]));
+		assert_eq!(Balances::free_balance(&3), 20);
+
+		// Block 4: the queued solution is elected.
+		System::set_block_number(4);
+		Session::check_rotate_session(System::block_number());
+		assert_eq!(Staking::current_era(), 2);
+		assert_eq!(Session::validators(), vec![2, 4]);
+		assert_eq!(Staking::stake_range(), (40, 20));
+		assert_eq!(Balances::free_balance(&3), 25);
+		assert_eq!(Balances::reserved_balance(&3), 0);
+	});
+}
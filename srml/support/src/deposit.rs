@@ -0,0 +1,181 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+
+//! Deposits for storage, so that the accounts growing the state pay for it.
+//!
+//! A module reserves a `StorageDeposit` from the account whose call adds storage and refunds it
+//! once the storage is removed. The deposit is proportional to both the bytes and the items
+//! stored, as every item weighs on the state beyond its size.
+//!
+//! The deposit per byte and per item may change over time, so modules keep the deposit they
+//! reserved along with the storage and refund or update exactly that.
+
+use codec::Encode;
+use rstd::ops::Add;
+use rstd::result;
+use runtime_primitives::traits::{As, SimpleArithmetic};
+use traits::ReservableCurrency;
+
+/// The storage used by some data: the number of bytes and items it is stored in.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Footprint {
+	/// The number of bytes stored.
+	pub bytes: u32,
+	/// The number of storage items.
+	pub items: u32,
+}
+
+impl Footprint {
+	/// The footprint of a value stored as a single item.
+	pub fn of<V: Encode>(value: &V) -> Self {
+		Footprint {
+			bytes: value.using_encoded(|encoded| encoded.len() as u32),
+			items: 1,
+		}
+	}
+}
+
+impl Add for Footprint {
+	type Output = Footprint;
+
+	fn add(self, other: Footprint) -> Footprint {
+		Footprint {
+			bytes: self.bytes.saturating_add(other.bytes),
+			items: self.items.saturating_add(other.items),
+		}
+	}
+}
+
+/// The deposit for storage, per byte and per item.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct StorageDeposit<Balance> {
+	/// The deposit per byte stored.
+	pub per_byte: Balance,
+	/// The deposit per storage item.
+	pub per_item: Balance,
+}
+
+impl<Balance: SimpleArithmetic + Copy> StorageDeposit<Balance> {
+	/// The deposit for storage with the given footprint, unless it overflows.
+	pub fn deposit(&self, footprint: Footprint) -> Option<Balance> {
+		let bytes = self.per_byte.checked_mul(&Balance::sa(footprint.bytes as u64))?;
+		let items = self.per_item.checked_mul(&Balance::sa(footprint.items as u64))?;
+		bytes.checked_add(&items)
+	}
+
+	/// Reserve the deposit for storage with the given footprint from `who`, returning it.
+	pub fn charge<C, AccountId>(&self, who: &AccountId, footprint: Footprint) -> result::Result<Balance, &'static str>
+		where C: ReservableCurrency<AccountId, Balance=Balance>
+	{
+		let deposit = self.deposit(footprint).ok_or("storage deposit overflow")?;
+		C::reserve(who, deposit)?;
+		Ok(deposit)
+	}
+
+	/// Change the deposit `held` from `who` to the deposit for storage with the given footprint,
+	/// reserving or refunding the difference. Returns the new deposit.
+	pub fn update<C, AccountId>(&self, who: &AccountId, held: Balance, footprint: Footprint) -> result::Result<Balance, &'static str>
+		where C: ReservableCurrency<AccountId, Balance=Balance>
+	{
+		let deposit = self.deposit(footprint).ok_or("storage deposit overflow")?;
+		if deposit > held {
+			C::reserve(who, deposit - held)?;
+		} else {
+			refund::<C, _>(who, held - deposit);
+		}
+		Ok(deposit)
+	}
+}
+
+/// Refund the deposit `held` from `who` for storage that was removed.
+pub fn refund<C: ReservableCurrency<AccountId>, AccountId>(who: &AccountId, held: C::Balance) {
+	// the deposit may have been slashed meanwhile, in which case there is less to refund.
+	let _ = C::unreserve(who, held);
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::cell::Cell;
+
+	thread_local! {
+		static FREE: Cell<u64> = Cell::new(100);
+		static RESERVED: Cell<u64> = Cell::new(0);
+	}
+
+	struct Currency;
+
+	impl ReservableCurrency<u64> for Currency {
+		type Balance = u64;
+
+		fn reserve(_: &u64, value: u64) -> result::Result<(), &'static str> {
+			if FREE.with(|free| free.get()) < value {
+				return Err("not enough free funds");
+			}
+			FREE.with(|free| free.set(free.get() - value));
+			RESERVED.with(|reserved| reserved.set(reserved.get() + value));
+			Ok(())
+		}
+
+		fn unreserve(_: &u64, value: u64) -> Option<u64> {
+			let actual = RESERVED.with(|reserved| ::std::cmp::min(reserved.get(), value));
+			RESERVED.with(|reserved| reserved.set(reserved.get() - actual));
+			FREE.with(|free| free.set(free.get() + actual));
+			if actual == value { None } else { Some(value - actual) }
+		}
+	}
+
+	fn balances() -> (u64, u64) {
+		(FREE.with(|free| free.get()), RESERVED.with(|reserved| reserved.get()))
+	}
+
+	const DEPOSIT: StorageDeposit<u64> = StorageDeposit { per_byte: 2, per_item: 10 };
+
+	#[test]
+	fn deposit_is_proportional_to_bytes_and_items() {
+		assert_eq!(Footprint::of(&vec![1u8, 2, 3]), Footprint { bytes: 4, items: 1 });
+		assert_eq!(DEPOSIT.deposit(Footprint::of(&5u32) + Footprint::of(&5u64)), Some(44));
+		assert_eq!(DEPOSIT.deposit(Footprint::default()), Some(0));
+		assert_eq!(DEPOSIT.deposit(Footprint { bytes: 1, items: 0 }), Some(2));
+
+		let expensive = StorageDeposit { per_byte: u64::max_value(), per_item: 0 };
+		assert_eq!(expensive.deposit(Footprint { bytes: 2, items: 0 }), None);
+	}
+
+	#[test]
+	fn deposits_are_charged_updated_and_refunded() {
+		let held = DEPOSIT.charge::<Currency, _>(&1, Footprint { bytes: 10, items: 2 }).unwrap();
+		assert_eq!(held, 40);
+		assert_eq!(balances(), (60, 40));
+
+		assert_eq!(DEPOSIT.charge::<Currency, _>(&1, Footprint { bytes: 30, items: 1 }), Err("not enough free funds"));
+		assert_eq!(balances(), (60, 40));
+
+		let held = DEPOSIT.update::<Currency, _>(&1, held, Footprint { bytes: 20, items: 2 }).unwrap();
+		assert_eq!(held, 60);
+		assert_eq!(balances(), (40, 60));
+
+		let held = DEPOSIT.update::<Currency, _>(&1, held, Footprint { bytes: 0, items: 1 }).unwrap();
+		assert_eq!(held, 10);
+		assert_eq!(balances(), (90, 10));
+
+		refund::<Currency, _>(&1, held);
+		assert_eq!(balances(), (100, 0));
+	}
+}
@@ -18,7 +18,7 @@
 pub use rstd::{cmp, result::Result, vec::Vec};
 #[doc(hidden)]
 pub use runtime_primitives::{
-	traits::{ProvideInherent, Block as BlockT}, CheckInherentError
+	traits::{ProvideInherent, Block as BlockT}, CheckInherentError, CheckInherentsResult
 };
 
 
@@ -51,9 +51,10 @@ macro_rules! impl_outer_inherent {
 			fn check_inherents(
 				block: $block,
 				data: $inherent
-			) -> $crate::inherent::Result<(), $crate::inherent::CheckInherentError> {
-				use $crate::inherent::CheckInherentError;
+			) -> $crate::inherent::CheckInherentsResult {
+				use $crate::inherent::{CheckInherentError, CheckInherentsResult};
 
+				let mut result = CheckInherentsResult::new();
 				let mut max_valid_after = None;
 				$(
 					let res = <$module_ty as $crate::inherent::ProvideInherent>::check_inherent(
@@ -68,16 +69,21 @@ macro_rules! impl_outer_inherent {
 					match res {
 						Err(CheckInherentError::ValidAtTimestamp(t)) =>
 							max_valid_after = $crate::inherent::cmp::max(max_valid_after, Some(t)),
-						res => res?
+						Err(e) => {
+							result.put_error(e);
+							return result;
+						},
+						Ok(()) => {},
 					}
 				)*
 
 				// once everything else has checked out, take the maximum of
 				// all things which are timestamp-restricted.
-				match max_valid_after {
-					Some(t) => Err(CheckInherentError::ValidAtTimestamp(t)),
-					None => Ok(())
+				if let Some(t) = max_valid_after {
+					result.put_error(CheckInherentError::ValidAtTimestamp(t));
 				}
+
+				result
 			}
 		}
 	};
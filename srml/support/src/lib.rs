@@ -60,6 +60,8 @@ pub mod metadata;
 mod runtime;
 #[macro_use]
 pub mod inherent;
+pub mod traits;
+pub mod deposit;
 
 pub use self::storage::{StorageVec, StorageList, StorageValue, StorageMap};
 pub use self::hashable::Hashable;
@@ -112,6 +114,23 @@ macro_rules! assert_ok {
 	}
 }
 
+/// Call the macro `$m` for the unit type and for tuples of up to 19 elements, with the type
+/// names of the elements.
+///
+/// Meant to implement hook traits for tuples of handlers, so that a runtime can plug several
+/// modules into a single hook point without writing an aggregator by hand.
+#[macro_export]
+macro_rules! for_each_tuple {
+	($m:ident) => {
+		for_each_tuple! { @IMPL $m !! A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, }
+	};
+	(@IMPL $m:ident !!) => { $m! { } };
+	(@IMPL $m:ident !! $h:ident, $($t:ident,)*) => {
+		$m! { $h $($t)* }
+		for_each_tuple! { @IMPL $m !! $($t,)* }
+	}
+}
+
 /// The void type - it cannot exist.
 // Oh rust, you crack me up...
 #[derive(Clone, Eq, PartialEq)]
@@ -124,3 +143,54 @@ pub use mashup::*;
 #[cfg(feature = "std")]
 #[doc(hidden)]
 pub use serde_derive::*;
+
+#[cfg(test)]
+mod tests {
+	use std::cell::RefCell;
+
+	thread_local! {
+		static CALLS: RefCell<Vec<(&'static str, u32)>> = RefCell::new(Vec::new());
+	}
+
+	trait OnEvent {
+		fn on_event(value: u32);
+	}
+
+	macro_rules! impl_on_event {
+		() => (
+			impl OnEvent for () {
+				fn on_event(_: u32) {}
+			}
+		);
+
+		( $($t:ident)* ) => {
+			impl<$($t: OnEvent),*> OnEvent for ($($t,)*) {
+				fn on_event(value: u32) {
+					$($t::on_event(value);)*
+				}
+			}
+		}
+	}
+
+	for_each_tuple!(impl_on_event);
+
+	struct First;
+	impl OnEvent for First {
+		fn on_event(value: u32) {
+			CALLS.with(|calls| calls.borrow_mut().push(("first", value)));
+		}
+	}
+
+	struct Second;
+	impl OnEvent for Second {
+		fn on_event(value: u32) {
+			CALLS.with(|calls| calls.borrow_mut().push(("second", value)));
+		}
+	}
+
+	#[test]
+	fn tuples_call_all_handlers_in_order() {
+		<(First, (), (Second, First))>::on_event(42);
+		CALLS.with(|calls| assert_eq!(*calls.borrow(), vec![("first", 42), ("second", 42), ("first", 42)]));
+	}
+}
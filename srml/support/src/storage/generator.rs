@@ -58,7 +58,31 @@ pub use srml_metadata::{
 	StorageFunctionType, StorageFunctionModifier
 };
 
-/// Abstraction around storage.
+/// The key of a value declared with `decl_storage!`, which is also the prefix of the keys of a
+/// map: `twox128(module) ++ twox128(item)`. The items of a module thus share a prefix, and
+/// can't collide with those of other modules.
+pub fn storage_prefix(module: &[u8], item: &[u8]) -> Vec<u8> {
+	let mut key = ::runtime_io::twox_128(module).to_vec();
+	key.extend_from_slice(&::runtime_io::twox_128(item)[..]);
+	key
+}
+
+/// The key of the value of a map declared with `decl_storage!` under the given map key: the
+/// prefix of the map followed by the `blake2_256` of the encoded map key.
+pub fn storage_map_key<K: codec::Encode>(prefix: &[u8], key: &K) -> Vec<u8> {
+	let mut final_key = prefix.to_vec();
+	final_key.extend_from_slice(&key.using_encoded(::runtime_io::blake2_256)[..]);
+	final_key
+}
+
+/// The key of a raw key declared with `storage_items!`: its `twox128`. This is also how the keys
+/// of `decl_storage!` items were derived before they were namespaced per module, from the
+/// `"Module Item"` string followed by the encoded map key, if any.
+pub fn hashed_key(raw: &[u8]) -> Vec<u8> {
+	::runtime_io::twox_128(raw).to_vec()
+}
+
+/// Abstraction around storage. Keys are final: they are used as they are.
 pub trait Storage {
 	/// true if the key exists in storage.
 	fn exists(&self, key: &[u8]) -> bool;
@@ -100,11 +124,11 @@ pub trait StorageValue<T: codec::Codec> {
 	type Query;
 
 	/// Get the storage key.
-	fn key() -> &'static [u8];
+	fn key() -> Vec<u8>;
 
 	/// true if the value is defined in storage.
 	fn exists<S: Storage>(storage: &S) -> bool {
-		storage.exists(Self::key())
+		storage.exists(&Self::key()[..])
 	}
 
 	/// Load the value from the provided storage instance.
@@ -115,7 +139,7 @@ pub trait StorageValue<T: codec::Codec> {
 
 	/// Store a value under this key into the provided storage instance.
 	fn put<S: Storage>(val: &T, storage: &S) {
-		storage.put(Self::key(), val)
+		storage.put(&Self::key()[..], val)
 	}
 
 	/// Mutate this value
@@ -123,7 +147,7 @@ pub trait StorageValue<T: codec::Codec> {
 
 	/// Clear the storage value.
 	fn kill<S: Storage>(storage: &S) {
-		storage.kill(Self::key())
+		storage.kill(&Self::key()[..])
 	}
 }
 
@@ -163,7 +187,7 @@ pub trait StorageMap<K: codec::Codec, V: codec::Codec> {
 	type Query;
 
 	/// Get the prefix key in storage.
-	fn prefix() -> &'static [u8];
+	fn prefix() -> Vec<u8>;
 
 	/// Get the storage key used to fetch a value corresponding to a specific key.
 	fn key_for(x: &K) -> Vec<u8>;
@@ -327,18 +351,18 @@ macro_rules! __storage_items_internal {
 			type Query = $gettype;
 
 			/// Get the storage key.
-			fn key() -> &'static [u8] {
-				$key
+			fn key() -> $crate::rstd::vec::Vec<u8> {
+				$crate::storage::generator::hashed_key($key)
 			}
 
 			/// Load the value from the provided storage instance.
 			fn get<S: $crate::GenericStorage>(storage: &S) -> Self::Query {
-				storage.$getter($key)
+				storage.$getter(&<$name as $crate::storage::generator::StorageValue<$ty>>::key()[..])
 			}
 
 			/// Take a value from storage, removing it afterwards.
 			fn take<S: $crate::GenericStorage>(storage: &S) -> Self::Query {
-				storage.$taker($key)
+				storage.$taker(&<$name as $crate::storage::generator::StorageValue<$ty>>::key()[..])
 			}
 
 			/// Mutate this value.
@@ -376,15 +400,15 @@ macro_rules! __storage_items_internal {
 			type Query = $gettype;
 
 			/// Get the prefix key in storage.
-			fn prefix() -> &'static [u8] {
-				$prefix
+			fn prefix() -> $crate::rstd::vec::Vec<u8> {
+				$prefix.to_vec()
 			}
 
 			/// Get the storage key used to fetch a value corresponding to a specific key.
 			fn key_for(x: &$kty) -> $crate::rstd::vec::Vec<u8> {
 				let mut key = $prefix.to_vec();
 				$crate::codec::Encode::encode_to(x, &mut key);
-				key
+				$crate::storage::generator::hashed_key(&key)
 			}
 
 			/// Load the value associated with the given key from the map.
@@ -448,14 +472,14 @@ macro_rules! __storage_items_internal {
 			fn len_key() -> $crate::rstd::vec::Vec<u8> {
 				let mut key = $prefix.to_vec();
 				key.extend(b"len");
-				key
+				$crate::storage::generator::hashed_key(&key)
 			}
 
 			/// Get the storage key used to fetch a value at a given index.
 			fn key_for(index: u32) -> $crate::rstd::vec::Vec<u8> {
 				let mut key = $prefix.to_vec();
 				$crate::codec::Encode::encode_to(&index, &mut key);
-				key
+				$crate::storage::generator::hashed_key(&key)
 			}
 
 			/// Read out all the items.
@@ -655,7 +679,7 @@ macro_rules! __generate_genesis_config {
 				$({
 					use $crate::codec::Encode;
 					let v = ($normalbuild)(&self);
-					r.insert(Self::hash(<$normalclassname<$traitinstance>>::key()).to_vec(), v.encode());
+					r.insert(<$normalclassname<$traitinstance>>::key(), v.encode());
 				})*
 
 				// for maps
@@ -663,7 +687,7 @@ macro_rules! __generate_genesis_config {
 					use $crate::codec::Encode;
 					let data = ($mapbuild)(&self);
 					for (k, v) in data.into_iter() {
-						r.insert(Self::hash(&<$mapclassname<$traitinstance>>::key_for(k)).to_vec(), v.encode());
+						r.insert(<$mapclassname<$traitinstance>>::key_for(k), v.encode());
 					}
 				})*
 
@@ -1316,15 +1340,17 @@ macro_rules! __decl_storage_item {
 			type Query = $gettype;
 
 			/// Get the prefix key in storage.
-			fn prefix() -> &'static [u8] {
-				stringify!($cratename $name).as_bytes()
+			fn prefix() -> $crate::rstd::vec::Vec<u8> {
+				$crate::storage::generator::storage_prefix(
+					stringify!($cratename).as_bytes(),
+					stringify!($name).as_bytes(),
+				)
 			}
 
 			/// Get the storage key used to fetch a value corresponding to a specific key.
 			fn key_for(x: &$kty) -> $crate::rstd::vec::Vec<u8> {
-				let mut key = <$name<$traitinstance> as $crate::storage::generator::StorageMap<$kty, $ty>>::prefix().to_vec();
-				$crate::codec::Encode::encode_to(x, &mut key);
-				key
+				let prefix = <$name<$traitinstance> as $crate::storage::generator::StorageMap<$kty, $ty>>::prefix();
+				$crate::storage::generator::storage_map_key(&prefix, x)
 			}
 
 			/// Load the value associated with the given key from the map.
@@ -1382,36 +1408,37 @@ macro_rules! __decl_storage_item {
 			type Query = $gettype;
 
 			/// Get the storage key.
-			fn key() -> &'static [u8] {
-				stringify!($cratename $name).as_bytes()
+			fn key() -> $crate::rstd::vec::Vec<u8> {
+				$crate::storage::generator::storage_prefix(
+					stringify!($cratename).as_bytes(),
+					stringify!($name).as_bytes(),
+				)
 			}
 
 			/// Load the value from the provided storage instance.
 			fn get<S: $crate::GenericStorage>(storage: &S) -> Self::Query {
+				let key = <$name<$traitinstance> as $crate::storage::generator::StorageValue<$ty>>::key();
 
 				__handle_wrap_internal!($wraptype {
 					// raw type case
-					storage.get(<$name<$traitinstance> as $crate::storage::generator::StorageValue<$ty>>::key())
-						.unwrap_or_else(|| $default)
+					storage.get(&key[..]).unwrap_or_else(|| $default)
 				} {
 					// Option<> type case
-					storage.get(<$name<$traitinstance> as $crate::storage::generator::StorageValue<$ty>>::key())
-						.or_else(|| $default)
+					storage.get(&key[..]).or_else(|| $default)
 				})
 			}
 
 			/// Take a value from storage, removing it afterwards.
 			fn take<S: $crate::GenericStorage>(storage: &S) -> Self::Query {
+				let key = <$name<$traitinstance> as $crate::storage::generator::StorageValue<$ty>>::key();
+
 				__handle_wrap_internal!($wraptype {
 					// raw type case
-					storage.take(<$name<$traitinstance> as $crate::storage::generator::StorageValue<$ty>>::key())
-						.unwrap_or_else(|| $default)
+					storage.take(&key[..]).unwrap_or_else(|| $default)
 				} {
 					// Option<> type case
-					storage.take(<$name<$traitinstance> as $crate::storage::generator::StorageValue<$ty>>::key())
-						.or_else(|| $default)
+					storage.take(&key[..]).or_else(|| $default)
 				})
-
 			}
 
 			/// Mutate the value under a key.
@@ -2135,6 +2162,24 @@ mod tests {
 		assert_eq!(EXPECTED_METADATA, metadata);
 	}
 
+	#[test]
+	fn storage_keys_are_prefixed_by_module_and_item() {
+		use codec::Encode;
+		use runtime_io::{twox_128, blake2_256};
+
+		let prefix = [twox_128(b"TestStorage"), twox_128(b"MAPU32")].concat();
+		assert_eq!(<MAPU32<TraitImpl> as StorageMap<u32, String>>::prefix(), prefix);
+		assert_eq!(
+			<MAPU32<TraitImpl> as StorageMap<u32, String>>::key_for(&5),
+			[&prefix[..], &blake2_256(&5u32.encode())[..]].concat(),
+		);
+		assert_eq!(
+			<U32<TraitImpl> as StorageValue<u32>>::key(),
+			[twox_128(b"TestStorage"), twox_128(b"U32")].concat(),
+		);
+		assert_eq!(Value::key(), twox_128(b"a").to_vec());
+	}
+
 	#[test]
 	fn check_genesis_config() {
 		let config = GenesisConfig::<TraitImpl>::default();
@@ -75,6 +75,11 @@ pub fn put<T: Codec>(key: &[u8], value: &T) {
 	value.using_encoded(|slice| runtime_io::set_storage(&twox_128(key)[..], slice));
 }
 
+/// Append `item` to the `Vec<T>` in storage under `key`, without decoding the items already there.
+pub fn append<T: Codec>(key: &[u8], item: &T) {
+	item.using_encoded(|slice| runtime_io::storage_append(&twox_128(key)[..], slice));
+}
+
 /// Remove `key` from storage, returning its value if it had an explicit entry or `None` otherwise.
 pub fn take<T: Codec + Sized>(key: &[u8]) -> Option<T> {
 	let r = get(key);
@@ -122,32 +127,33 @@ pub fn put_raw(key: &[u8], value: &[u8]) {
 	runtime_io::set_storage(&twox_128(key)[..], value)
 }
 
-/// The underlying runtime storage.
+/// The underlying runtime storage. The keys of macro-generated storage items are final, so
+/// they are used as they are.
 pub struct RuntimeStorage;
 
 impl ::GenericStorage for RuntimeStorage {
 	fn exists(&self, key: &[u8]) -> bool {
-		super::storage::exists(key)
+		unhashed::exists(key)
 	}
 
 	/// Load the bytes of a key from storage. Can panic if the type is incorrect.
 	fn get<T: Codec>(&self, key: &[u8]) -> Option<T> {
-		super::storage::get(key)
+		unhashed::get(key)
 	}
 
 	/// Put a value in under a key.
 	fn put<T: Codec>(&self, key: &[u8], val: &T) {
-		super::storage::put(key, val)
+		unhashed::put(key, val)
 	}
 
 	/// Remove the bytes of a key from storage.
 	fn kill(&self, key: &[u8]) {
-		super::storage::kill(key)
+		unhashed::kill(key)
 	}
 
 	/// Take a value from storage, deleting it after reading.
 	fn take<T: Codec>(&self, key: &[u8]) -> Option<T> {
-		super::storage::take(key)
+		unhashed::take(key)
 	}
 }
 
@@ -157,7 +163,7 @@ pub trait StorageValue<T: Codec> {
 	type Query;
 
 	/// Get the storage key.
-	fn key() -> &'static [u8];
+	fn key() -> Vec<u8>;
 
 	/// Does the value (explicitly) exist in storage?
 	fn exists() -> bool;
@@ -181,7 +187,7 @@ pub trait StorageValue<T: Codec> {
 impl<T: Codec, U> StorageValue<T> for U where U: generator::StorageValue<T> {
 	type Query = U::Query;
 
-	fn key() -> &'static [u8] {
+	fn key() -> Vec<u8> {
 		<U as generator::StorageValue<T>>::key()
 	}
 	fn exists() -> bool {
@@ -278,7 +284,7 @@ pub trait StorageMap<K: Codec, V: Codec> {
 	type Query;
 
 	/// Get the prefix key in storage.
-	fn prefix() -> &'static [u8];
+	fn prefix() -> Vec<u8>;
 
 	/// Get the storage key used to fetch a value corresponding to a specific key.
 	fn key_for<KeyArg: Borrow<K>>(key: KeyArg) -> Vec<u8>;
@@ -305,7 +311,7 @@ pub trait StorageMap<K: Codec, V: Codec> {
 impl<K: Codec, V: Codec, U> StorageMap<K, V> for U where U: generator::StorageMap<K, V> {
 	type Query = U::Query;
 
-	fn prefix() -> &'static [u8] {
+	fn prefix() -> Vec<u8> {
 		<U as generator::StorageMap<K, V>>::prefix()
 	}
 
@@ -435,6 +441,11 @@ pub mod unhashed {
 		value.using_encoded(|slice| runtime_io::set_storage(key, slice));
 	}
 
+	/// Append `item` to the `Vec<T>` in storage under `key`, without decoding the items already there.
+	pub fn append<T: Codec>(key: &[u8], item: &T) {
+		item.using_encoded(|slice| runtime_io::storage_append(key, slice));
+	}
+
 	/// Remove `key` from storage, returning its value if it had an explicit entry or `None` otherwise.
 	pub fn take<T: Codec + Sized>(key: &[u8]) -> Option<T> {
 		let r = get(key);
@@ -477,6 +488,15 @@ pub mod unhashed {
 		runtime_io::clear_prefix(prefix);
 	}
 
+	/// Remove at most `limit` entries which keys start with the given `prefix`, starting from the
+	/// `cursor` key if any.
+	///
+	/// Returns the number of removed entries and the cursor to continue from, if any entry with
+	/// the `prefix` is left, so that whole maps can be removed over several blocks.
+	pub fn kill_prefix_limited(prefix: &[u8], limit: u32, cursor: Option<&[u8]>) -> (u32, Option<Vec<u8>>) {
+		runtime_io::clear_prefix_limited(prefix, limit, cursor)
+	}
+
 	/// Get a Vec of bytes from storage.
 	pub fn get_raw(key: &[u8]) -> Option<Vec<u8>> {
 		runtime_io::storage(key)
@@ -540,6 +560,88 @@ pub mod unhashed {
 	}
 }
 
+/// Moving values from the keys `decl_storage!` items had before they were namespaced per module:
+/// the `twox128` of `"Module Item"`, followed by the encoded map key for maps. Runtime upgrades
+/// call these for the items they keep, before the items are accessed again.
+///
+/// The `*_by_name` functions only need the names of the items, so that a runtime can move the
+/// items of all its modules, including private and removed ones, in one place.
+pub mod migration {
+	use codec::Encode;
+	use super::{generator, unhashed, Codec, Vec};
+
+	/// Move the value of a storage value from its old key, given the module and item names.
+	/// Returns whether there was a value to move.
+	pub fn migrate_value<T: Codec, U: generator::StorageValue<T>>(module: &str, item: &str) -> bool {
+		move_raw(&old_key(module, item, &[]), &U::key())
+	}
+
+	/// Move the values of the given keys of a storage map from their old keys, given the module
+	/// and item names. Old map keys are hashed, so the map keys to move can't be enumerated and
+	/// must be known. Returns the number of values moved.
+	pub fn migrate_map<K, V, U, I>(module: &str, item: &str, keys: I) -> u32 where
+		K: Codec,
+		V: Codec,
+		U: generator::StorageMap<K, V>,
+		I: IntoIterator<Item=K>,
+	{
+		let mut moved = 0;
+		for key in keys {
+			let old = key.using_encoded(|encoded| old_key(module, item, encoded));
+			if move_raw(&old, &U::key_for(&key)) {
+				moved += 1;
+			}
+		}
+		moved
+	}
+
+	/// Move the value of a storage value from its old key, given only the module and item names.
+	/// Returns whether there was a value to move.
+	pub fn migrate_value_by_name(module: &str, item: &str) -> bool {
+		move_raw(&old_key(module, item, &[]), &generator::storage_prefix(module.as_bytes(), item.as_bytes()))
+	}
+
+	/// Move the values of the given keys of a storage map from their old keys, given only the
+	/// module and item names. Returns the number of values moved.
+	pub fn migrate_map_by_name<K: Encode, I: IntoIterator<Item=K>>(module: &str, item: &str, keys: I) -> u32 {
+		let prefix = generator::storage_prefix(module.as_bytes(), item.as_bytes());
+		let mut moved = 0;
+		for key in keys {
+			let old = key.using_encoded(|encoded| old_key(module, item, encoded));
+			if move_raw(&old, &generator::storage_map_key(&prefix, &key)) {
+				moved += 1;
+			}
+		}
+		moved
+	}
+
+	/// Remove the value a storage value that no longer exists had under its old key.
+	pub fn kill_value_by_name(module: &str, item: &str) {
+		unhashed::kill(&old_key(module, item, &[]));
+	}
+
+	/// The key a `decl_storage!` item had before storage was namespaced per module.
+	fn old_key(module: &str, item: &str, encoded_map_key: &[u8]) -> Vec<u8> {
+		let mut raw = Vec::with_capacity(module.len() + 1 + item.len() + encoded_map_key.len());
+		raw.extend_from_slice(module.as_bytes());
+		raw.push(b' ');
+		raw.extend_from_slice(item.as_bytes());
+		raw.extend_from_slice(encoded_map_key);
+		generator::hashed_key(&raw)
+	}
+
+	fn move_raw(old_key: &[u8], new_key: &[u8]) -> bool {
+		match unhashed::get_raw(old_key) {
+			Some(value) => {
+				unhashed::kill(old_key);
+				unhashed::put_raw(new_key, &value);
+				true
+			}
+			None => false,
+		}
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -606,4 +708,60 @@ mod tests {
 			assert_eq!(x, y);
 		});
 	}
+
+	#[test]
+	fn values_can_be_migrated_from_unprefixed_keys() {
+		use codec::Encode;
+
+		struct Value;
+		impl generator::StorageValue<u32> for Value {
+			type Query = Option<u32>;
+			fn key() -> Vec<u8> { generator::storage_prefix(b"Module", b"Value") }
+			fn get<S: ::GenericStorage>(storage: &S) -> Option<u32> { storage.get(&Self::key()[..]) }
+			fn take<S: ::GenericStorage>(storage: &S) -> Option<u32> { storage.take(&Self::key()[..]) }
+			fn mutate<R, F: FnOnce(&mut Option<u32>) -> R, S: ::GenericStorage>(f: F, storage: &S) -> R {
+				f(&mut Self::get(storage))
+			}
+		}
+
+		let mut t = TestExternalities::default();
+		with_externalities(&mut t, || {
+			runtime_io::set_storage(&twox_128(b"Module Value"), &69u32.encode());
+			assert!(migration::migrate_value::<u32, Value>("Module", "Value"));
+			assert!(!migration::migrate_value::<u32, Value>("Module", "Value"));
+			assert_eq!(<Value as StorageValue<u32>>::get(), Some(69));
+			assert!(runtime_io::storage(&twox_128(b"Module Value")).is_none());
+		});
+	}
+
+	#[test]
+	fn items_can_be_migrated_by_name() {
+		use codec::Encode;
+
+		let old_map_key = |key: u32| {
+			let mut raw = b"Module Map".to_vec();
+			key.encode_to(&mut raw);
+			twox_128(&raw)
+		};
+		let prefix = generator::storage_prefix(b"Module", b"Map");
+
+		let mut t = TestExternalities::default();
+		with_externalities(&mut t, || {
+			runtime_io::set_storage(&twox_128(b"Module Value"), &69u32.encode());
+			runtime_io::set_storage(&twox_128(b"Module Removed"), &1u32.encode());
+			runtime_io::set_storage(&old_map_key(1), &10u64.encode());
+			runtime_io::set_storage(&old_map_key(2), &20u64.encode());
+
+			assert!(migration::migrate_value_by_name("Module", "Value"));
+			assert_eq!(migration::migrate_map_by_name("Module", "Map", vec![1u32, 2, 3]), 2);
+			migration::kill_value_by_name("Module", "Removed");
+
+			assert_eq!(get::<u32>(&generator::storage_prefix(b"Module", b"Value")), Some(69));
+			assert_eq!(get::<u64>(&generator::storage_map_key(&prefix, &1u32)), Some(10));
+			assert_eq!(get::<u64>(&generator::storage_map_key(&prefix, &2u32)), Some(20));
+			assert!(runtime_io::storage(&twox_128(b"Module Value")).is_none());
+			assert!(runtime_io::storage(&twox_128(b"Module Removed")).is_none());
+			assert!(runtime_io::storage(&old_map_key(1)).is_none());
+		});
+	}
 }
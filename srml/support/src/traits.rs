@@ -0,0 +1,146 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Traits for declaring the privileged origins of a module generically.
+//!
+//! A module names the origins it accepts for a call through an associated type bounded by
+//! `EnsureOrigin`, and the runtime picks a concrete check for it, e.g. `system::EnsureRoot`,
+//! a council threshold or a combination of those through `EitherOf`.
+
+use rstd::marker::PhantomData;
+use rstd::result;
+use codec::Codec;
+use runtime_primitives::traits::SimpleArithmetic;
+
+pub use runtime_primitives::traits::EnsureOrigin;
+
+/// A set of items that can be checked for membership, e.g. the members of a collective.
+pub trait Contains<T> {
+	/// Whether `t` is a member of the set.
+	fn contains(t: &T) -> bool;
+}
+
+/// Decides whether a call may be dispatched, e.g. to disable some calls of a chain.
+pub trait Filter<Call> {
+	/// Whether `call` may be dispatched.
+	fn filter(call: &Call) -> bool;
+}
+
+/// Allows all calls.
+impl<Call> Filter<Call> for () {
+	fn filter(_: &Call) -> bool {
+		true
+	}
+}
+
+/// Proves that a key is owned by some account, e.g. by a validator of a past session, so that
+/// misbehaviour signed with the key can be attributed to its owner.
+pub trait KeyOwnerProofSystem<Key> {
+	/// The proof of ownership of a key.
+	type Proof: Codec;
+	/// The owner of a key.
+	type Owner;
+
+	/// Prove the current ownership of `key`, if it is owned.
+	fn prove(key: Key) -> Option<Self::Proof>;
+
+	/// Check the given proof of ownership of `key`, returning the owner if it holds.
+	fn check_proof(key: Key, proof: Self::Proof) -> Option<Self::Owner>;
+}
+
+/// Proves nothing.
+impl<Key> KeyOwnerProofSystem<Key> for () {
+	type Proof = ();
+	type Owner = ();
+
+	fn prove(_: Key) -> Option<()> {
+		None
+	}
+
+	fn check_proof(_: Key, _: ()) -> Option<()> {
+		None
+	}
+}
+
+/// A currency in which funds of an account can be reserved, e.g. as a deposit.
+pub trait ReservableCurrency<AccountId> {
+	/// The balance of an account.
+	type Balance: SimpleArithmetic + Copy;
+
+	/// Move `value` from the free to the reserved balance of `who`. Fails without moving any
+	/// funds if the free balance is too low.
+	fn reserve(who: &AccountId, value: Self::Balance) -> result::Result<(), &'static str>;
+
+	/// Move up to `value` from the reserved to the free balance of `who`, returning the part of
+	/// `value` that was not reserved, if any.
+	fn unreserve(who: &AccountId, value: Self::Balance) -> Option<Self::Balance>;
+}
+
+/// The outcome of `EitherOf`: which of the two checks passed, together with its result.
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum Either<L, R> {
+	/// The first check passed.
+	Left(L),
+	/// The first check failed and the second one passed.
+	Right(R),
+}
+
+/// Ensures that an origin passes either the check `L` or, failing that, the check `R`.
+pub struct EitherOf<L, R>(PhantomData<(L, R)>);
+
+impl<O, L, R> EnsureOrigin<O> for EitherOf<L, R> where
+	O: Clone,
+	L: EnsureOrigin<O>,
+	R: EnsureOrigin<O>,
+{
+	type Success = Either<L::Success, R::Success>;
+	fn ensure_origin(o: O) -> result::Result<Self::Success, &'static str> {
+		L::ensure_origin(o.clone())
+			.map(Either::Left)
+			.or_else(|_| R::ensure_origin(o).map(Either::Right))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	struct EnsureEven;
+	impl EnsureOrigin<u32> for EnsureEven {
+		type Success = u32;
+		fn ensure_origin(o: u32) -> result::Result<u32, &'static str> {
+			if o % 2 == 0 { Ok(o / 2) } else { Err("odd") }
+		}
+	}
+
+	struct EnsureSmall;
+	impl EnsureOrigin<u32> for EnsureSmall {
+		type Success = ();
+		fn ensure_origin(o: u32) -> result::Result<(), &'static str> {
+			if o < 10 { Ok(()) } else { Err("too large") }
+		}
+	}
+
+	#[test]
+	fn either_of_tries_both_checks_in_order() {
+		type Check = EitherOf<EnsureEven, EnsureSmall>;
+		assert_eq!(Check::ensure_origin(4), Ok(Either::Left(2)));
+		assert_eq!(Check::ensure_origin(12), Ok(Either::Left(6)));
+		assert_eq!(Check::ensure_origin(5), Ok(Either::Right(())));
+		assert_eq!(Check::ensure_origin(11), Err("too large"));
+	}
+}
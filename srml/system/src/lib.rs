@@ -41,13 +41,16 @@ use primitives::traits::{self, CheckEqual, SimpleArithmetic, SimpleBitOps, Zero,
 	MaybeSerializeDebugButNotDeserialize, MaybeSerializeDebug};
 use substrate_primitives::storage::well_known_keys;
 use runtime_support::{storage, StorageValue, StorageMap, Parameter};
+use runtime_support::traits::Contains;
 use safe_mix::TripletMix;
 
+pub use primitives::weights::{ClassWeights, DispatchClass};
+
 #[cfg(any(feature = "std", test))]
 use codec::Encode;
 
 #[cfg(any(feature = "std", test))]
-use runtime_io::{twox_128, TestExternalities, Blake2Hasher};
+use runtime_io::{TestExternalities, Blake2Hasher};
 
 #[cfg(any(feature = "std", test))]
 use substrate_primitives::ChangesTrieConfiguration;
@@ -64,6 +67,9 @@ pub fn extrinsics_data_root<H: Hash>(xts: Vec<Vec<u8>>) -> H::Output {
 }
 
 pub trait Trait: Eq + Clone {
+	/// The `spec_version` of the runtime, which signed extrinsics commit to.
+	const SPEC_VERSION: u32;
+
 	type Origin: Into<Option<RawOrigin<Self::AccountId>>> + From<RawOrigin<Self::AccountId>>;
 	type Index: Parameter + Member + MaybeSerializeDebugButNotDeserialize + Default + MaybeDisplay + SimpleArithmetic + Copy;
 	type BlockNumber: Parameter + Member + MaybeSerializeDebug + MaybeDisplay + SimpleArithmetic + Default + Bounded + Copy + rstd::hash::Hash;
@@ -78,6 +84,12 @@ pub trait Trait: Eq + Clone {
 	>;
 	type Event: Parameter + Member + From<Event>;
 	type Log: From<Log<Self>> + Into<DigestItemOf<Self>>;
+	/// Filter of the calls the executive may dispatch, checked before each extrinsic's call is
+	/// dispatched. Use `()` to allow all calls.
+	type BaseCallFilter;
+	/// Weighs the calls the executive dispatches, see `WeighCall`. Use `()` for calls that weigh
+	/// nothing.
+	type CallWeigher;
 }
 
 pub type DigestItemOf<T> = <<T as Trait>::Digest as traits::Digest>::Item;
@@ -88,9 +100,14 @@ decl_module! {
 		pub fn deposit_event(event: T::Event) {
 			let extrinsic_index = Self::extrinsic_index();
 			let phase = extrinsic_index.map_or(Phase::Finalization, |c| Phase::ApplyExtrinsic(c));
-			let mut events = Self::events();
-			events.push(EventRecord { phase, event });
-			<Events<T>>::put(events);
+			storage::unhashed::append(&<Events<T>>::key(), &EventRecord { phase, event });
+		}
+
+		/// Pause or resume the calls the runtime's `BaseCallFilter` disables while calls are paused.
+		fn set_calls_paused(origin, paused: bool) -> Result<(), &'static str> {
+			ensure_root(origin)?;
+			<CallsPaused<T>>::put(paused);
+			Ok(())
 		}
 	}
 }
@@ -115,6 +132,37 @@ pub struct EventRecord<E: Parameter + Member> {
 	pub event: E,
 }
 
+/// Tells the weight of a call: how much of the capacity of a block dispatching it may consume at
+/// most. The fee of an extrinsic is charged on the weight of its call before it is dispatched.
+pub trait WeighCall<Call> {
+	/// The weight of `call`.
+	fn weigh(call: &Call) -> u32;
+
+	/// The class of `call`, which the weight it consumes is accounted to.
+	fn dispatch_class(call: &Call) -> DispatchClass;
+}
+
+/// Every call weighs nothing and is of the normal class.
+impl<Call> WeighCall<Call> for () {
+	fn weigh(_: &Call) -> u32 {
+		0
+	}
+
+	fn dispatch_class(_: &Call) -> DispatchClass {
+		DispatchClass::Normal
+	}
+}
+
+/// What a call reported about its dispatch, to correct the fee charged for it in advance.
+#[derive(Encode, Decode, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct PostDispatchInfo {
+	/// The part of the weight of the call its dispatch didn't consume.
+	pub unused_weight: u32,
+	/// Whether the fee of the extrinsic is waived.
+	pub fee_waived: bool,
+}
+
 /// Event for the system module.
 decl_event!(
 	pub enum Event {
@@ -186,6 +234,12 @@ decl_storage! {
 	trait Store for Module<T: Trait> as System {
 
 		pub AccountNonce get(account_nonce): map T::AccountId => T::Index;
+		/// The number of modules that keep an account alive, e.g. by holding a balance of it which is
+		/// at least the existential deposit. The account is killed when this drops to zero.
+		pub AccountProviders get(providers): map T::AccountId => u32;
+		/// The number of modules that depend on an account to exist, e.g. by storing a session key
+		/// or a lock of it. An account cannot be killed while this is non-zero.
+		pub AccountConsumers get(consumers): map T::AccountId => u32;
 
 		ExtrinsicCount: Option<u32>;
 		pub BlockHash get(block_hash) build(|_| vec![(T::BlockNumber::zero(), [69u8; 32])]): map T::BlockNumber => T::Hash;
@@ -198,6 +252,19 @@ decl_storage! {
 		Digest get(digest): T::Digest;
 
 		Events get(events): Vec<EventRecord<T::Event>>;
+		/// Whether the storage items have been moved to their keys namespaced per module, see
+		/// `srml_support::storage::migration`.
+		pub StorageKeysMigrated get(storage_keys_migrated) build(|_| true): bool;
+		/// The weight consumed by the calls of the extrinsics of the current block, per dispatch
+		/// class. Like the events, it is kept after the block to be inspected by the client.
+		pub BlockWeight get(block_weight): ClassWeights;
+
+		/// Whether calls are paused. Which calls are disabled while paused is up to the runtime's
+		/// `BaseCallFilter`.
+		pub CallsPaused get(calls_paused): bool;
+		/// What the call being dispatched reported so far, taken by the executive once it is
+		/// dispatched.
+		PostDispatch get(post_dispatch_info): PostDispatchInfo;
 	}
 	add_extra_genesis {
 		config(changes_trie_config): Option<ChangesTrieConfiguration>;
@@ -224,6 +291,32 @@ impl<O: Into<Option<RawOrigin<AccountId>>>, AccountId> EnsureOrigin<O> for Ensur
 	}
 }
 
+/// Ensures that the origin is signed by any account, returning that account.
+pub struct EnsureSigned<AccountId>(::rstd::marker::PhantomData<AccountId>);
+impl<O: Into<Option<RawOrigin<AccountId>>>, AccountId> EnsureOrigin<O> for EnsureSigned<AccountId> {
+	type Success = AccountId;
+	fn ensure_origin(o: O) -> Result<Self::Success, &'static str> {
+		ensure_signed(o)
+	}
+}
+
+/// Ensures that the origin is signed by a member of `Who`, returning that member.
+pub struct EnsureSignedBy<Who, AccountId>(::rstd::marker::PhantomData<(Who, AccountId)>);
+impl<O, Who, AccountId> EnsureOrigin<O> for EnsureSignedBy<Who, AccountId> where
+	O: Into<Option<RawOrigin<AccountId>>>,
+	Who: Contains<AccountId>,
+{
+	type Success = AccountId;
+	fn ensure_origin(o: O) -> Result<Self::Success, &'static str> {
+		let who = ensure_signed(o)?;
+		if Who::contains(&who) {
+			Ok(who)
+		} else {
+			Err("bad origin: expected to be signed by a member")
+		}
+	}
+}
+
 /// Ensure that the origin `o` represents a signed extrinsic (i.e. transaction).
 /// Returns `Ok` with the account that signed the extrinsic or an `Err` otherwise.
 pub fn ensure_signed<OuterOrigin, AccountId>(o: OuterOrigin) -> Result<AccountId, &'static str>
@@ -271,6 +364,7 @@ impl<T: Trait> Module<T> {
 		<ExtrinsicsRoot<T>>::put(txs_root);
 		<RandomSeed<T>>::put(Self::calculate_random());
 		<Events<T>>::kill();
+		<BlockWeight<T>>::kill();
 	}
 
 	/// Remove temporary "environment" entries in storage.
@@ -322,10 +416,10 @@ impl<T: Trait> Module<T> {
 	#[cfg(any(feature = "std", test))]
 	pub fn externalities() -> TestExternalities<Blake2Hasher> {
 		TestExternalities::new(map![
-			twox_128(&<BlockHash<T>>::key_for(T::BlockNumber::zero())).to_vec() => [69u8; 32].encode(),	// TODO: replace with Hash::default().encode
-			twox_128(<Number<T>>::key()).to_vec() => T::BlockNumber::one().encode(),
-			twox_128(<ParentHash<T>>::key()).to_vec() => [69u8; 32].encode(),	// TODO: replace with Hash::default().encode
-			twox_128(<RandomSeed<T>>::key()).to_vec() => T::Hash::default().encode()
+			<BlockHash<T>>::key_for(T::BlockNumber::zero()) => [69u8; 32].encode(),	// TODO: replace with Hash::default().encode
+			<Number<T>>::key() => T::BlockNumber::one().encode(),
+			<ParentHash<T>>::key() => [69u8; 32].encode(),	// TODO: replace with Hash::default().encode
+			<RandomSeed<T>>::key() => T::Hash::default().encode()
 		])
 	}
 
@@ -361,6 +455,57 @@ impl<T: Trait> Module<T> {
 		<AccountNonce<T>>::insert(who, Self::account_nonce(who) + T::Index::one());
 	}
 
+	/// Note that a module keeps the given account alive.
+	pub fn inc_providers(who: &T::AccountId) {
+		<AccountProviders<T>>::insert(who, Self::providers(who).saturating_add(1));
+	}
+
+	/// Note that a module no longer keeps the given account alive. If it was the last one, the
+	/// account is killed and `Ok(true)` returned.
+	///
+	/// Fails if this would kill an account that is still referenced by some consumer.
+	pub fn dec_providers(who: &T::AccountId) -> Result<bool, &'static str> {
+		match Self::providers(who) {
+			0 | 1 => {
+				if Self::is_referenced(who) {
+					return Err("account is still referenced by other modules");
+				}
+				<AccountProviders<T>>::remove(who);
+				<AccountNonce<T>>::remove(who);
+				Ok(true)
+			}
+			providers => {
+				<AccountProviders<T>>::insert(who, providers - 1);
+				Ok(false)
+			}
+		}
+	}
+
+	/// Note that a module depends on the given account to exist. This prevents the account
+	/// from being killed until `dec_consumers` is called.
+	///
+	/// Fails if the account is not kept alive by any module.
+	pub fn inc_consumers(who: &T::AccountId) -> Result<(), &'static str> {
+		if Self::providers(who) == 0 {
+			return Err("account does not exist");
+		}
+		<AccountConsumers<T>>::insert(who, Self::consumers(who).saturating_add(1));
+		Ok(())
+	}
+
+	/// Note that a module no longer depends on the given account to exist.
+	pub fn dec_consumers(who: &T::AccountId) {
+		match Self::consumers(who) {
+			0 | 1 => <AccountConsumers<T>>::remove(who),
+			consumers => <AccountConsumers<T>>::insert(who, consumers - 1),
+		}
+	}
+
+	/// `true` if some module depends on the given account to exist.
+	pub fn is_referenced(who: &T::AccountId) -> bool {
+		Self::consumers(who) > 0
+	}
+
 	/// Note what the extrinsic data of the current extrinsic index is. If this is called, then
 	/// ensure `derive_extrinsics` is also called before block-building is completed.
 	pub fn note_extrinsic(encoded_xt: Vec<u8>) {
@@ -378,6 +523,31 @@ impl<T: Trait> Module<T> {
 		storage::unhashed::put(well_known_keys::EXTRINSIC_INDEX, &next_extrinsic_index);
 	}
 
+	/// Account for the `weight` the call of an applied extrinsic of the given class consumed.
+	pub fn note_extrinsic_weight(class: DispatchClass, weight: u32) {
+		<BlockWeight<T>>::mutate(|weights| match class {
+			DispatchClass::Normal => weights.normal = weights.normal.saturating_add(weight),
+			DispatchClass::Operational => weights.operational = weights.operational.saturating_add(weight),
+		});
+	}
+
+	/// Report that the call being dispatched didn't consume `weight` of its weight, e.g. because
+	/// it took a cheaper path than the one it was weighed for. The fee for that part of the weight
+	/// is refunded to the sender of the extrinsic.
+	pub fn note_unused_weight(weight: u32) {
+		<PostDispatch<T>>::mutate(|info| info.unused_weight = info.unused_weight.saturating_add(weight));
+	}
+
+	/// Waive the fee of the extrinsic whose call is being dispatched: it is refunded to its sender.
+	pub fn waive_fee() {
+		<PostDispatch<T>>::mutate(|info| info.fee_waived = true);
+	}
+
+	/// Take what the call just dispatched reported about its dispatch.
+	pub fn take_post_dispatch_info() -> PostDispatchInfo {
+		<PostDispatch<T>>::take()
+	}
+
 	/// To be called immediately after `note_applied_extrinsic` of the last extrinsic of the block
 	/// has been called.
 	pub fn note_finished_extrinsics() {
@@ -439,6 +609,7 @@ mod tests {
 	#[derive(Clone, Eq, PartialEq)]
 	pub struct Test;
 	impl Trait for Test {
+		const SPEC_VERSION: u32 = 0;
 		type Origin = Origin;
 		type Index = u64;
 		type BlockNumber = u64;
@@ -449,6 +620,8 @@ mod tests {
 		type Header = Header;
 		type Event = u16;
 		type Log = DigestItem;
+		type BaseCallFilter = ();
+		type CallWeigher = ();
 	}
 
 	impl From<Event> for u16 {
@@ -490,4 +663,67 @@ mod tests {
 			]);
 		});
 	}
+
+	#[test]
+	fn block_weight_is_accounted_per_class() {
+		with_externalities(&mut new_test_ext(), || {
+			System::initialise(&1, &[0u8; 32].into(), &[0u8; 32].into());
+			System::note_extrinsic_weight(DispatchClass::Operational, 10);
+			System::note_extrinsic_weight(DispatchClass::Normal, 100);
+			System::note_extrinsic_weight(DispatchClass::Normal, 50);
+			System::finalise();
+			assert_eq!(System::block_weight(), ClassWeights { normal: 150, operational: 10 });
+			assert_eq!(System::block_weight().total(), 160);
+
+			System::initialise(&2, &[0u8; 32].into(), &[0u8; 32].into());
+			assert_eq!(System::block_weight(), ClassWeights::default());
+		});
+	}
+
+	#[test]
+	fn account_reference_counting_should_work() {
+		with_externalities(&mut new_test_ext(), || {
+			// an account that nothing keeps alive cannot be referenced.
+			assert_eq!(System::inc_consumers(&1), Err("account does not exist"));
+
+			System::inc_providers(&1);
+			System::inc_account_nonce(&1);
+			assert_eq!(System::inc_consumers(&1), Ok(()));
+			assert!(System::is_referenced(&1));
+
+			// a referenced account cannot be killed.
+			assert_eq!(System::dec_providers(&1), Err("account is still referenced by other modules"));
+			assert_eq!(System::providers(&1), 1);
+
+			// with more than one provider, the account stays alive.
+			System::inc_providers(&1);
+			assert_eq!(System::dec_providers(&1), Ok(false));
+			assert_eq!(System::account_nonce(&1), 1);
+
+			System::dec_consumers(&1);
+			assert!(!System::is_referenced(&1));
+			assert_eq!(System::dec_providers(&1), Ok(true));
+			assert_eq!(System::providers(&1), 0);
+			assert_eq!(System::account_nonce(&1), 0);
+		});
+	}
+
+	#[test]
+	fn ensure_signed_by_only_accepts_members() {
+		struct Members;
+		impl Contains<u64> for Members {
+			fn contains(who: &u64) -> bool {
+				*who < 3
+			}
+		}
+		type EnsureMember = EnsureSignedBy<Members, u64>;
+
+		assert_eq!(EnsureMember::ensure_origin(Origin::signed(1)), Ok(1));
+		assert_eq!(
+			EnsureMember::ensure_origin(Origin::signed(3)),
+			Err("bad origin: expected to be signed by a member"),
+		);
+		assert!(EnsureMember::ensure_origin(Origin::ROOT).is_err());
+		assert_eq!(EnsureSigned::<u64>::ensure_origin(Origin::signed(3)), Ok(3));
+	}
 }
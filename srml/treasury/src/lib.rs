@@ -43,7 +43,7 @@ use runtime_support::{StorageValue, StorageMap};
 use runtime_support::dispatch::Result;
 use runtime_primitives::{Permill, traits::{Zero, EnsureOrigin}};
 use codec::{HasCompact, Compact};
-use balances::{OnDilution, address::Address};
+use balances::{OnDilution, OnUnbalanced, address::Address};
 use system::ensure_signed;
 
 /// Our module's configuration trait. All our types and consts go in here. If the
@@ -277,6 +277,13 @@ impl<T: Trait> OnDilution<T::Balance> for Module<T> {
 	}
 }
 
+impl<T: Trait> OnUnbalanced<T::Balance> for Module<T> {
+	fn on_unbalanced(amount: T::Balance) {
+		// Funds that were removed from circulation, like dust, go to the treasury.
+		<Pot<T>>::mutate(|x| *x += amount);
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -294,6 +301,7 @@ mod tests {
 	#[derive(Clone, Eq, PartialEq)]
 	pub struct Test;
 	impl system::Trait for Test {
+		const SPEC_VERSION: u32 = 0;
 		type Origin = Origin;
 		type Index = u64;
 		type BlockNumber = u64;
@@ -304,12 +312,16 @@ mod tests {
 		type Header = Header;
 		type Event = ();
 		type Log = DigestItem;
+		type BaseCallFilter = ();
+		type CallWeigher = ();
 	}
 	impl balances::Trait for Test {
 		type Balance = u64;
 		type AccountIndex = u64;
 		type OnFreeBalanceZero = ();
 		type EnsureAccountLiquid = ();
+		type DustRemoval = ();
+		type OnChargeTransaction = balances::Module<Test>;
 		type Event = ();
 	}
 	impl Trait for Test {
@@ -326,6 +338,7 @@ mod tests {
 			balances: vec![(0, 100), (1, 99), (2, 1)],
 			transaction_base_fee: 0,
 			transaction_byte_fee: 0,
+			transaction_weight_fee: 0,
 			transfer_fee: 0,
 			creation_fee: 0,
 			existential_deposit: 0,
@@ -363,6 +376,14 @@ mod tests {
 		});
 	}
 
+	#[test]
+	fn unbalanced_funds_go_to_pot() {
+		with_externalities(&mut new_test_ext(), || {
+			Treasury::on_unbalanced(10);
+			assert_eq!(Treasury::pot(), 10);
+		});
+	}
+
 	#[test]
 	fn spend_proposal_takes_min_deposit() {
 		with_externalities(&mut new_test_ext(), || {
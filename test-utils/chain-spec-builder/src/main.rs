@@ -36,6 +36,7 @@ fn generate_chain_spec() -> String {
 	let chain_spec = chain_spec::ChainSpec::from_genesis(
 		"Custom",
 		"custom",
+		substrate_service::ChainType::Local,
 		genesis_constructor,
 		vec![],
 		None,